@@ -0,0 +1,216 @@
+mod s3;
+
+pub use s3::S3Backend;
+
+use anyhow::Result;
+use ring::digest::{Context, SHA256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+use hayride_host_traits::blob::{errors::ErrorCode, BlobInfo, BlobTrait};
+use hayride_utils::config::BlobConfig;
+
+static SHARED: OnceLock<RwLock<Option<Arc<dyn BlobTrait>>>> = OnceLock::new();
+
+/// Blobs of every engine of the process, files under `~/.hayride/blobs` until `configure`
+/// sets another backend, i.e. a bucket.
+pub fn shared() -> Arc<dyn BlobTrait> {
+    let shared = SHARED.get_or_init(|| RwLock::new(None));
+    if let Some(backend) = shared.read().ok().and_then(|backend| backend.clone()) {
+        return backend;
+    }
+
+    let Ok(mut backend) = shared.write() else {
+        return Arc::new(FileBackend::new(&default_path()));
+    };
+    backend
+        .get_or_insert_with(|| Arc::new(FileBackend::new(&default_path())))
+        .clone()
+}
+
+/// Store the blobs of every engine in the backend, blobs already stored are not moved.
+pub fn configure(backend: Arc<dyn BlobTrait>) {
+    if let Ok(mut shared) = SHARED.get_or_init(|| RwLock::new(None)).write() {
+        *shared = Some(backend);
+    }
+}
+
+/// Open the store selected by the config, a bucket if one is configured.
+pub fn from_config(config: &BlobConfig) -> Result<Arc<dyn BlobTrait>> {
+    match &config.s3 {
+        Some(s3) => Ok(Arc::new(S3Backend::from_config(s3)?)),
+        None => {
+            let root = hayride_utils::paths::hayride::default_hayride_dir()?.join(&config.path);
+            Ok(Arc::new(FileBackend::new(&root)))
+        }
+    }
+}
+
+/// Blobs kept in a directory as files named by their digest, sharded by its first byte.
+pub struct FileBackend {
+    root: PathBuf,
+}
+
+impl FileBackend {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    fn path(&self, digest: &str) -> Result<PathBuf, ErrorCode> {
+        check_digest(digest)?;
+        Ok(self.root.join(&digest[..2]).join(digest))
+    }
+}
+
+impl BlobTrait for FileBackend {
+    fn staging_dir(&self) -> PathBuf {
+        self.root.join("tmp")
+    }
+
+    fn put_file(&self, digest: &str, path: &Path) -> Result<BlobInfo, ErrorCode> {
+        let blob = self.path(digest)?;
+        // The content is already stored
+        if blob.is_file() {
+            let _ = std::fs::remove_file(path);
+            return self.stat(digest);
+        }
+
+        let stored = blob
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::rename(path, &blob));
+        if let Err(e) = stored {
+            log::warn!("failed to store blob {}: {:?}", digest, e);
+            let _ = std::fs::remove_file(path);
+            return Err(ErrorCode::WriteFailed);
+        }
+        self.stat(digest)
+    }
+
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + Send>, ErrorCode> {
+        let file = File::open(self.path(digest)?).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            _ => ErrorCode::ReadFailed,
+        })?;
+        Ok(Box::new(file))
+    }
+
+    fn stat(&self, digest: &str) -> Result<BlobInfo, ErrorCode> {
+        let metadata = std::fs::metadata(self.path(digest)?).map_err(|_| ErrorCode::NotFound)?;
+        Ok(BlobInfo {
+            digest: digest.to_string(),
+            size: metadata.len(),
+            // Blobs are never rewritten, so they were last modified when they were stored
+            created: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        })
+    }
+
+    fn delete(&self, digest: &str) -> Result<bool, ErrorCode> {
+        match std::fs::remove_file(self.path(digest)?) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => {
+                log::warn!("failed to delete blob {}: {:?}", digest, e);
+                Err(ErrorCode::WriteFailed)
+            }
+        }
+    }
+}
+
+/// Writes a blob to a staging file, hashing it as it is written, until it is handed to
+/// the store. A writer dropped before it is finished removes its staging file.
+pub struct BlobWriter {
+    path: PathBuf,
+    file: Option<File>,
+    context: Context,
+}
+
+impl BlobWriter {
+    pub fn create(staging_dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(staging_dir)?;
+        let path = staging_dir.join(uuid::Uuid::new_v4().to_string());
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            file: Some(file),
+            context: Context::new(&SHA256),
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), ErrorCode> {
+        let file = self.file.as_mut().ok_or(ErrorCode::WriteFailed)?;
+        file.write_all(data).map_err(|e| {
+            log::warn!("failed to write blob {}: {:?}", self.path.display(), e);
+            ErrorCode::WriteFailed
+        })?;
+        self.context.update(data);
+        Ok(())
+    }
+
+    /// Move the blob written so far into a new writer, this writer can not be written
+    /// afterwards.
+    pub fn take(&mut self) -> Self {
+        Self {
+            path: self.path.clone(),
+            file: self.file.take(),
+            context: self.context.clone(),
+        }
+    }
+
+    /// Hand the blob to the store, the writer can not be written afterwards.
+    pub fn finish(&mut self, backend: &dyn BlobTrait) -> Result<BlobInfo, ErrorCode> {
+        let file = self.file.take().ok_or(ErrorCode::WriteFailed)?;
+        if let Err(e) = file.sync_all() {
+            log::warn!("failed to write blob {}: {:?}", self.path.display(), e);
+            let _ = std::fs::remove_file(&self.path);
+            return Err(ErrorCode::WriteFailed);
+        }
+        drop(file);
+
+        let digest = hex(self.context.clone().finish().as_ref());
+        backend.put_file(&digest, &self.path)
+    }
+}
+
+impl Drop for BlobWriter {
+    fn drop(&mut self) {
+        if self.file.is_some() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Path of the blob dir shared by every engine, `~/.hayride/blobs`.
+pub fn default_path() -> PathBuf {
+    match hayride_utils::paths::hayride::default_hayride_dir() {
+        Ok(dir) => dir.join("blobs"),
+        Err(e) => {
+            log::warn!(
+                "failed to find the hayride dir, storing blobs in temp: {:?}",
+                e
+            );
+            std::env::temp_dir().join("hayride").join("blobs")
+        }
+    }
+}
+
+/// Digests are the lowercase hex sha256 digest of the content.
+pub fn check_digest(digest: &str) -> Result<(), ErrorCode> {
+    let valid = digest.len() == 64
+        && digest
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    match valid {
+        true => Ok(()),
+        false => Err(ErrorCode::InvalidDigest),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
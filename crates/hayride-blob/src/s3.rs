@@ -0,0 +1,274 @@
+use anyhow::{bail, Result};
+use reqwest::blocking::{Body, Client, Response};
+use reqwest::{Method, StatusCode};
+use ring::digest::SHA256;
+use ring::hmac;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use url::Url;
+
+use hayride_host_traits::blob::{errors::ErrorCode, BlobInfo, BlobTrait};
+use hayride_utils::config::S3Config;
+
+use crate::{check_digest, hex};
+
+// Payload hash of requests without a body
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Blobs kept in an S3 compatible bucket as objects named by their digest.
+///
+/// Requests are signed with AWS signature version 4 and blobs are staged in the temp dir
+/// before they are uploaded.
+pub struct S3Backend {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    prefix: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    // Created on the first request, as blocking clients can not be created on the runtime
+    client: OnceLock<Client>,
+}
+
+impl S3Backend {
+    pub fn from_config(config: &S3Config) -> Result<Self> {
+        if config.bucket.is_empty() {
+            bail!("no bucket configured for the blob store");
+        }
+        let (Some(access_key_id), Some(secret_access_key)) =
+            (&config.access_key_id, &config.secret_access_key)
+        else {
+            bail!("no credentials configured for the blob store bucket");
+        };
+
+        Ok(Self {
+            endpoint: Url::parse(&config.endpoint)?,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            prefix: config.prefix.trim_matches('/').to_string(),
+            access_key_id: access_key_id.clone(),
+            secret_access_key: secret_access_key.clone(),
+            session_token: config.session_token.clone(),
+            client: OnceLock::new(),
+        })
+    }
+
+    fn url(&self, digest: &str) -> Result<Url, ErrorCode> {
+        check_digest(digest)?;
+        let key = match self.prefix.is_empty() {
+            true => digest.to_string(),
+            false => format!("{}/{}", self.prefix, digest),
+        };
+        // Signatures cover the path with every segment encoded, so it is encoded here
+        // rather than left to the url
+        let path = format!(
+            "{}/{}/{}",
+            self.endpoint.path().trim_end_matches('/'),
+            uri_encode(&self.bucket),
+            key.split('/').map(uri_encode).collect::<Vec<_>>().join("/")
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        Ok(url)
+    }
+
+    // Send a signed request for the object of the digest
+    fn send(
+        &self,
+        method: Method,
+        digest: &str,
+        payload_hash: &str,
+        body: Option<Body>,
+    ) -> Result<Response, ErrorCode> {
+        let url = self.url(digest)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        // Headers signed besides the host, sorted by name
+        let mut headers = vec![
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(session_token) = &self.session_token {
+            headers.push(("x-amz-security-token", session_token.clone()));
+        }
+        let signed_headers = std::iter::once("host")
+            .chain(headers.iter().map(|(name, _)| *name))
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\n{}\n{}\n{}",
+            method,
+            url.path(),
+            host,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(ring::digest::digest(&SHA256, canonical_request.as_bytes()).as_ref())
+        );
+
+        let key = [date.as_str(), self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.secret_access_key).into_bytes(),
+                |key, part| sign(&key, part.as_bytes()),
+            );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id,
+            scope,
+            signed_headers,
+            hex(&sign(&key, string_to_sign.as_bytes()))
+        );
+
+        let mut request = self
+            .client
+            .get_or_init(Client::new)
+            .request(method, url)
+            .header("authorization", authorization);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        request.send().map_err(|e| {
+            log::warn!("blob store request failed: {:?}", e);
+            ErrorCode::Unknown
+        })
+    }
+
+    // Upload a staged file, its digest is the hash of the payload
+    fn upload(&self, digest: &str, path: &Path) -> Result<BlobInfo, ErrorCode> {
+        let file = File::open(path).map_err(|_| ErrorCode::WriteFailed)?;
+        let size = file.metadata().map_err(|_| ErrorCode::WriteFailed)?.len();
+
+        let response = self.send(Method::PUT, digest, digest, Some(Body::sized(file, size)))?;
+        if !response.status().is_success() {
+            log::warn!("failed to put blob {}: {}", digest, response.status());
+            return Err(ErrorCode::WriteFailed);
+        }
+        Ok(BlobInfo {
+            digest: digest.to_string(),
+            size,
+            created: SystemTime::now(),
+        })
+    }
+}
+
+impl BlobTrait for S3Backend {
+    fn staging_dir(&self) -> PathBuf {
+        std::env::temp_dir().join("hayride").join("blobs")
+    }
+
+    fn put_file(&self, digest: &str, path: &Path) -> Result<BlobInfo, ErrorCode> {
+        let result = match self.stat(digest) {
+            // The content is already stored
+            Ok(info) => Ok(info),
+            Err(ErrorCode::NotFound) => self.upload(digest, path),
+            Err(code) => Err(code),
+        };
+        let _ = std::fs::remove_file(path);
+        result
+    }
+
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + Send>, ErrorCode> {
+        let response = self.send(Method::GET, digest, EMPTY_SHA256, None)?;
+        match response.status() {
+            status if status.is_success() => Ok(Box::new(response)),
+            StatusCode::NOT_FOUND => Err(ErrorCode::NotFound),
+            status => {
+                log::warn!("failed to get blob {}: {}", digest, status);
+                Err(ErrorCode::ReadFailed)
+            }
+        }
+    }
+
+    fn stat(&self, digest: &str) -> Result<BlobInfo, ErrorCode> {
+        let response = self.send(Method::HEAD, digest, EMPTY_SHA256, None)?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => return Err(ErrorCode::NotFound),
+            status => {
+                log::warn!("failed to stat blob {}: {}", digest, status);
+                return Err(ErrorCode::ReadFailed);
+            }
+        }
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let size = header("content-length")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or_default();
+        let created = header("last-modified")
+            .and_then(|modified| chrono::DateTime::parse_from_rfc2822(&modified).ok())
+            .map(SystemTime::from)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        Ok(BlobInfo {
+            digest: digest.to_string(),
+            size,
+            created,
+        })
+    }
+
+    fn delete(&self, digest: &str) -> Result<bool, ErrorCode> {
+        // Deleting an object succeeds whether or not it exists
+        match self.stat(digest) {
+            Ok(_) => {}
+            Err(ErrorCode::NotFound) => return Ok(false),
+            Err(code) => return Err(code),
+        }
+
+        let response = self.send(Method::DELETE, digest, EMPTY_SHA256, None)?;
+        match response.status().is_success() {
+            true => Ok(true),
+            false => {
+                log::warn!("failed to delete blob {}: {}", digest, response.status());
+                Err(ErrorCode::WriteFailed)
+            }
+        }
+    }
+}
+
+// Percent encode all but the unreserved characters, as signatures expect
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
@@ -1,31 +1,171 @@
+pub mod registry;
+pub mod release;
+pub mod sessions;
+pub mod signature;
+pub mod system;
+pub mod update;
+
 use anyhow::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hayride_host_traits::core::version::{errors::ErrorCode, ReleaseInfo, VersionInner};
+
+// Highlights beyond this are left to the full release notes
+const MAX_HIGHLIGHTS: usize = 10;
+
+#[derive(Clone)]
+pub struct VersionBackend {
+    /// File the latest release is cached in, nothing is cached without one
+    cache_path: Option<PathBuf>,
+    ttl: Duration,
+}
 
-use hayride_host_traits::core::version::{errors::ErrorCode, VersionInner};
+impl Default for VersionBackend {
+    fn default() -> Self {
+        Self {
+            cache_path: hayride_utils::paths::hayride::default_hayride_dir()
+                .ok()
+                .map(|dir| dir.join("cache").join("latest-release.json")),
+            ttl: release::DEFAULT_TTL,
+        }
+    }
+}
 
-#[derive(Clone, Default)]
-pub struct VersionBackend {}
+impl VersionBackend {
+    pub fn with_cache_path(mut self, cache_path: Option<PathBuf>) -> Self {
+        self.cache_path = cache_path;
+        self
+    }
 
+    /// Set how long a checked release is used before checking again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+#[async_trait::async_trait]
 impl VersionInner for VersionBackend {
-    fn latest(&self) -> Result<String, ErrorCode> {
-        // Get the latest version from Hayride releases
-        let client = reqwest::blocking::Client::new();
-        let response = match client
-            .get("https://api.github.com/repos/hayride-dev/releases/releases/latest")
-            .header(reqwest::header::USER_AGENT, "Hayride")
-            .send()
-        {
-            Ok(resp) => resp,
-            Err(_) => {
-                return Err(ErrorCode::GetVersionFailed);
+    async fn release(&self) -> Result<ReleaseInfo, ErrorCode> {
+        // Get the latest release from Hayride releases
+        let json = release::latest(self.cache_path.as_deref(), self.ttl).await?;
+        parse_release(&json)
+    }
+}
+
+// Parse a github release into release info
+fn parse_release(json: &serde_json::Value) -> Result<ReleaseInfo, ErrorCode> {
+    let version = json
+        .get("tag_name")
+        .and_then(|v| v.as_str())
+        .ok_or(ErrorCode::GetVersionFailed)?;
+    let date = json
+        .get("published_at")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let notes = json
+        .get("body")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    let assets: Vec<(&str, &str)> = json
+        .get("assets")
+        .and_then(|v| v.as_array())
+        .map(|assets| {
+            assets
+                .iter()
+                .filter_map(|asset| {
+                    let name = asset.get("name")?.as_str()?;
+                    let url = asset.get("browser_download_url")?.as_str()?;
+                    Some((name, url))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let download_url = platform_asset(&assets);
+    Ok(ReleaseInfo {
+        version: version.into(),
+        date: date.into(),
+        highlights: highlights(notes),
+        download_url: download_url.map(|url| url.to_string()),
+        signature_url: download_url
+            .and_then(|url| signature_asset(&assets, url))
+            .map(|url| url.to_string()),
+        notes: notes.into(),
+    })
+}
+
+// Collect the bullet points of the highlights section, or of the whole notes if there is none
+fn highlights(notes: &str) -> Vec<String> {
+    let has_section = notes.lines().any(is_highlights_heading);
+
+    let mut in_section = !has_section;
+    let mut highlights = vec![];
+    for line in notes.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            if has_section {
+                in_section = is_highlights_heading(line);
             }
-        };
-
-        // Parse the tag
-        let json: serde_json::Value = response.json().map_err(|_| ErrorCode::GetVersionFailed)?;
-        let tag_name = json
-            .get("tag_name")
-            .and_then(|v| v.as_str())
-            .ok_or(ErrorCode::GetVersionFailed)?;
-        Ok(tag_name.into())
+            continue;
+        }
+
+        if !in_section {
+            continue;
+        }
+        let bullet = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix));
+        if let Some(bullet) = bullet {
+            highlights.push(bullet.trim().to_string());
+            if highlights.len() == MAX_HIGHLIGHTS {
+                break;
+            }
+        }
     }
+
+    highlights
+}
+
+fn is_highlights_heading(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('#') && line.to_lowercase().contains("highlight")
+}
+
+// Find the download url of the asset built for the host os and architecture
+fn platform_asset<'a>(assets: &[(&str, &'a str)]) -> Option<&'a str> {
+    let os: &[&str] = match std::env::consts::OS {
+        "linux" => &["linux"],
+        "macos" => &["darwin", "macos", "apple"],
+        "windows" => &["windows"],
+        _ => return None,
+    };
+    let arch: &[&str] = match std::env::consts::ARCH {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        _ => return None,
+    };
+
+    assets.iter().find_map(|(name, url)| {
+        let name = name.to_lowercase();
+        // Skip checksums and signatures published next to the binaries
+        let is_checksum = [".sha256", ".sig", ".asc"]
+            .iter()
+            .any(|ext| name.ends_with(ext));
+        let matches =
+            os.iter().any(|os| name.contains(os)) && arch.iter().any(|arch| name.contains(arch));
+        (matches && !is_checksum).then_some(*url)
+    })
+}
+
+// Find the signature published next to an asset
+fn signature_asset<'a>(assets: &[(&str, &'a str)], asset_url: &str) -> Option<&'a str> {
+    let (asset, _) = assets.iter().find(|(_, url)| *url == asset_url)?;
+    let sidecar = format!("{}.sig", asset).to_lowercase();
+    assets
+        .iter()
+        .find(|(name, _)| name.to_lowercase() == sidecar)
+        .map(|(_, url)| *url)
 }
@@ -0,0 +1,217 @@
+//! Client for remote morph registries served over plain HTTPS.
+//!
+//! A remote registry mirrors the layout of the local registry:
+//!
+//! - `GET {url}/{package}/index.json` lists published versions, i.e. `{"versions": ["0.0.1"]}`
+//! - `GET {url}/{package}/{version}/{name}.wasm` returns the morph
+//! - `GET {url}/{package}/{version}/{name}.wasm.sig` returns the morph's signature, see
+//!   [`crate::signature`]
+//!
+//! Pushing uploads the morph and its signature with `PUT` to the same paths, the registry
+//! is expected to update the package index.
+
+use anyhow::Result;
+use ring::signature::Ed25519KeyPair;
+use std::path::PathBuf;
+
+use crate::signature::{sign, signature_path, MorphVerifier};
+
+use hayride_host_traits::core::registry::{ErrorCode, RegistryInner};
+use hayride_utils::paths::registry::{find_morph_path, parse_identifier};
+
+pub struct RegistryClient {
+    url: String,
+    // Local registry morphs are pulled into and pushed from
+    local_path: PathBuf,
+    token: Option<String>,
+    // Signs pushed morphs, pushing is disabled without one
+    signing_key: Option<Ed25519KeyPair>,
+    // Keys pulled morphs must be signed by, signatures are not checked without any
+    verifier: MorphVerifier,
+}
+
+impl RegistryClient {
+    pub fn new(url: &str, local_path: PathBuf) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            local_path,
+            token: None,
+            signing_key: None,
+            verifier: MorphVerifier::new(),
+        }
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Load the key pushed morphs are signed with from a PKCS#8 file.
+    pub fn with_signing_key(mut self, path: &str) -> Result<Self> {
+        let pkcs8 = std::fs::read(path)?;
+        let key = Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .map_err(|e| anyhow::anyhow!("invalid signing key {}: {}", path, e))?;
+        self.signing_key = Some(key);
+        Ok(self)
+    }
+
+    /// Require pulled morphs to be signed by one of the base64 encoded public keys.
+    pub fn with_trusted_keys(mut self, keys: &[String]) -> Result<Self> {
+        self.verifier = self.verifier.with_trusted_keys(keys)?;
+        Ok(self)
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>, ErrorCode> {
+        let client = reqwest::blocking::Client::new();
+        let mut request = client
+            .get(url)
+            .header(reqwest::header::USER_AGENT, "Hayride");
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().map_err(|e| {
+            log::warn!("failed to fetch {}: {}", url, e);
+            ErrorCode::PullFailed
+        })?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Err(ErrorCode::NotFound),
+            status if !status.is_success() => {
+                log::warn!("failed to fetch {}: {}", url, status);
+                Err(ErrorCode::PullFailed)
+            }
+            _ => Ok(response
+                .bytes()
+                .map_err(|_| ErrorCode::PullFailed)?
+                .to_vec()),
+        }
+    }
+
+    fn put(&self, url: &str, body: Vec<u8>) -> Result<(), ErrorCode> {
+        let client = reqwest::blocking::Client::new();
+        let mut request = client
+            .put(url)
+            .header(reqwest::header::USER_AGENT, "Hayride")
+            .body(body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().map_err(|e| {
+            log::warn!("failed to push {}: {}", url, e);
+            ErrorCode::PushFailed
+        })?;
+        if !response.status().is_success() {
+            log::warn!("failed to push {}: {}", url, response.status());
+            return Err(ErrorCode::PushFailed);
+        }
+        Ok(())
+    }
+
+    // Find the highest published version of a package
+    fn latest_version(&self, package: &str) -> Result<String, ErrorCode> {
+        let index = self.get(&format!("{}/{}/index.json", self.url, package))?;
+        let index: serde_json::Value =
+            serde_json::from_slice(&index).map_err(|_| ErrorCode::PullFailed)?;
+
+        index
+            .get("versions")
+            .and_then(|versions| versions.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|version| version.as_str())
+            .filter_map(|version| semver::Version::parse(version).ok())
+            .max()
+            .map(|version| version.to_string())
+            .ok_or(ErrorCode::NotFound)
+    }
+}
+
+impl RegistryInner for RegistryClient {
+    fn pull(&self, reference: &str) -> Result<String, ErrorCode> {
+        let (package, name, version) = parse_reference(reference)?;
+        let version = match version {
+            Some(version) => version.to_string(),
+            None => self.latest_version(package)?,
+        };
+
+        let url = format!("{}/{}/{}/{}.wasm", self.url, package, version, name);
+        let morph = self.get(&url)?;
+        let signature = match self.get(&format!("{}.sig", url)) {
+            Ok(signature) => Some(signature),
+            Err(ErrorCode::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        if !self.verifier.has_trusted_keys() {
+            log::warn!(
+                "no trusted registry keys, skipping signature check of {}",
+                reference
+            );
+        } else {
+            let signature = signature.as_ref().ok_or(ErrorCode::InvalidSignature)?;
+            self.verifier.verify(&morph, signature).map_err(|e| {
+                log::warn!("failed to verify {}: {}", reference, e);
+                ErrorCode::InvalidSignature
+            })?;
+        }
+
+        let mut path = self.local_path.join(package).join(&version);
+        std::fs::create_dir_all(&path).map_err(|_| ErrorCode::PullFailed)?;
+        path.push(format!("{}.wasm", name));
+
+        // Write to a temporary file first so a failed pull never leaves a partial morph
+        let partial = path.with_extension("wasm.partial");
+        std::fs::write(&partial, &morph).map_err(|_| ErrorCode::PullFailed)?;
+        // Keep the signature so the morph can be verified when it is loaded
+        if let Some(signature) = &signature {
+            std::fs::write(signature_path(&path), signature).map_err(|_| ErrorCode::PullFailed)?;
+        }
+        std::fs::rename(&partial, &path).map_err(|_| ErrorCode::PullFailed)?;
+
+        log::info!("pulled {}:{}@{} from {}", package, name, version, self.url);
+        Ok(format!("{}:{}@{}", package, name, version))
+    }
+
+    fn push(&self, reference: &str) -> Result<(), ErrorCode> {
+        let key = self.signing_key.as_ref().ok_or(ErrorCode::NotConfigured)?;
+        let (package, name, _) = parse_reference(reference)?;
+
+        let local_path = self
+            .local_path
+            .to_str()
+            .ok_or(ErrorCode::Unknown)?
+            .to_string();
+        let path = find_morph_path(local_path, reference).map_err(|_| ErrorCode::NotFound)?;
+        // The version directory the morph was resolved to
+        let version = path
+            .parent()
+            .and_then(|version| version.file_name())
+            .and_then(|version| version.to_str())
+            .ok_or(ErrorCode::Unknown)?;
+
+        let morph = std::fs::read(&path).map_err(|_| ErrorCode::NotFound)?;
+        let signature = sign(key, &morph);
+
+        let url = format!("{}/{}/{}/{}.wasm", self.url, package, version, name);
+        self.put(&url, morph)?;
+        self.put(&format!("{}.sig", url), signature.clone().into_bytes())?;
+        if let Err(e) = std::fs::write(signature_path(&path), signature) {
+            log::warn!("failed to keep signature of {}: {}", reference, e);
+        }
+
+        log::info!("pushed {}:{}@{} to {}", package, name, version, self.url);
+        Ok(())
+    }
+}
+
+// Parse a reference, rejecting parts that would resolve outside the registry
+fn parse_reference(reference: &str) -> Result<(&str, &str, Option<&str>), ErrorCode> {
+    let (package, name, version) =
+        parse_identifier(reference).ok_or(ErrorCode::InvalidReference)?;
+    let valid =
+        |part: &str| !part.is_empty() && part != "." && part != ".." && !part.contains(['/', '\\']);
+    if !valid(package) || !valid(name) || !version.is_none_or(valid) {
+        return Err(ErrorCode::InvalidReference);
+    }
+    Ok((package, name, version))
+}
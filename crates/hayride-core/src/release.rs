@@ -0,0 +1,203 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hayride_host_traits::core::version::errors::ErrorCode;
+use reqwest::{header, StatusCode};
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/hayride-dev/releases/releases/latest";
+
+/// How long a checked release is used before asking github again.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(3600);
+
+// Seconds to wait when github limits the requests without saying until when
+const RATE_LIMIT_BACKOFF: u64 = 600;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Client for release checks, shared by every version backend
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+fn client() -> &'static Client {
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .user_agent("Hayride")
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Return the github json of the latest release, from the cache at `cache_path` while
+/// it is younger than `ttl`. When github can not be reached or limits the requests the
+/// cached release is returned however old it is.
+pub async fn latest(cache_path: Option<&Path>, ttl: Duration) -> Result<Value, ErrorCode> {
+    let now = now();
+    let mut cached = Cached::load(cache_path);
+    if let Some(release) = &cached.release {
+        if now < cached.checked_at.saturating_add(ttl.as_secs()) {
+            return Ok(release.clone());
+        }
+    }
+    if cached
+        .retry_after
+        .is_some_and(|retry_after| now < retry_after)
+    {
+        log::debug!("release checks are rate limited, using the cached release");
+        return cached.release.ok_or(ErrorCode::GetVersionFailed);
+    }
+
+    // Only a cached release can answer a not modified response
+    let etag = cached.release.as_ref().and(cached.etag.clone());
+    let fetched = fetch(etag).await;
+    match fetched {
+        Ok(Fetched::Release(release, etag)) => {
+            cached = Cached {
+                checked_at: now,
+                etag,
+                retry_after: None,
+                release: Some(release),
+            };
+        }
+        Ok(Fetched::NotModified) => {
+            cached.checked_at = now;
+            cached.retry_after = None;
+        }
+        Ok(Fetched::RateLimited(retry_after)) => {
+            log::warn!(
+                "github rate limit reached, checking for releases again in {}s",
+                retry_after.saturating_sub(now)
+            );
+            cached.retry_after = Some(retry_after);
+        }
+        Err(e) => {
+            // Offline, keep the cache as it is so the next call tries again
+            log::warn!("failed to check the latest release: {}", e);
+            return cached.release.ok_or(ErrorCode::GetVersionFailed);
+        }
+    }
+
+    cached.store(cache_path);
+    cached.release.ok_or(ErrorCode::GetVersionFailed)
+}
+
+enum Fetched {
+    Release(Value, Option<String>),
+    NotModified,
+    /// Epoch seconds after which github accepts requests again
+    RateLimited(u64),
+}
+
+async fn fetch(etag: Option<String>) -> reqwest::Result<Fetched> {
+    let mut request = client()
+        .get(LATEST_RELEASE_URL)
+        .header(header::ACCEPT, "application/vnd.github+json");
+    // Conditional requests do not count against the rate limit
+    if let Some(etag) = etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+
+    let response = request.send().await?;
+    match response.status() {
+        StatusCode::NOT_MODIFIED => Ok(Fetched::NotModified),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS if is_rate_limited(&response) => {
+            Ok(Fetched::RateLimited(retry_after(response.headers())))
+        }
+        _ => {
+            let response = response.error_for_status()?;
+            let etag = header_str(response.headers(), header::ETAG.as_str()).map(String::from);
+            Ok(Fetched::Release(response.json().await?, etag))
+        }
+    }
+}
+
+fn is_rate_limited(response: &Response) -> bool {
+    let headers = response.headers();
+    response.status() == StatusCode::TOO_MANY_REQUESTS
+        || header_str(headers, "x-ratelimit-remaining") == Some("0")
+        || headers.contains_key(header::RETRY_AFTER)
+}
+
+// When github accepts requests again, from retry-after seconds or the epoch of the
+// rate limit reset
+fn retry_after(headers: &header::HeaderMap) -> u64 {
+    let now = now();
+    header_str(headers, header::RETRY_AFTER.as_str())
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .map(|secs| now.saturating_add(secs))
+        .or_else(|| {
+            header_str(headers, "x-ratelimit-reset").and_then(|reset| reset.parse::<u64>().ok())
+        })
+        .filter(|retry_after| *retry_after > now)
+        .unwrap_or(now + RATE_LIMIT_BACKOFF)
+}
+
+fn header_str<'a>(headers: &'a header::HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// The last release checked and when, as stored on disk
+#[derive(Default)]
+struct Cached {
+    /// epoch seconds
+    checked_at: u64,
+    etag: Option<String>,
+    /// epoch seconds before which github is not asked again
+    retry_after: Option<u64>,
+    release: Option<Value>,
+}
+
+impl Cached {
+    // A missing or unreadable cache is treated as empty
+    fn load(path: Option<&Path>) -> Self {
+        let Some(json) = path
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+        else {
+            return Self::default();
+        };
+
+        Self {
+            checked_at: json
+                .get("checked_at")
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default(),
+            etag: json.get("etag").and_then(|v| v.as_str()).map(String::from),
+            retry_after: json.get("retry_after").and_then(|v| v.as_u64()),
+            release: json.get("release").filter(|v| !v.is_null()).cloned(),
+        }
+    }
+
+    fn store(&self, path: Option<&Path>) {
+        let Some(path) = path else {
+            return;
+        };
+        let json = json!({
+            "checked_at": self.checked_at,
+            "etag": self.etag,
+            "retry_after": self.retry_after,
+            "release": self.release,
+        });
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, json.to_string()));
+        if let Err(e) = result {
+            log::warn!(
+                "failed to cache the latest release in {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hayride_host_traits::core::sessions::{ErrorCode, Message, Session, SessionsInner};
+
+// Longest title and role accepted, in characters
+const MAX_LABEL_LEN: usize = 255;
+
+// Most sessions or messages returned by a single call
+const MAX_PAGE: u32 = 1000;
+
+const SESSION_COLUMNS: &str = "id, title, created_at, updated_at,
+     (SELECT COUNT(*) FROM messages WHERE session_id = sessions.id)";
+
+static SHARED: OnceLock<Arc<SessionStore>> = OnceLock::new();
+
+/// Chat sessions kept in a sqlite database.
+pub struct SessionStore {
+    conn: Mutex<Option<Connection>>,
+}
+
+impl SessionStore {
+    /// Open the session database at the path, creating the file and its session and
+    /// message tables if they do not exist.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS sessions (
+                 id TEXT PRIMARY KEY,
+                 title TEXT NOT NULL,
+                 created_at INTEGER NOT NULL,
+                 updated_at INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS sessions_updated_at ON sessions (updated_at);
+             CREATE TABLE IF NOT EXISTS messages (
+                 session_id TEXT NOT NULL,
+                 position INTEGER NOT NULL,
+                 role TEXT NOT NULL,
+                 content TEXT NOT NULL,
+                 created_at INTEGER NOT NULL,
+                 PRIMARY KEY (session_id, position)
+             );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(Some(conn)),
+        })
+    }
+
+    /// Chat sessions of every engine of the process, in `~/.hayride/data/sessions.sqlite`.
+    ///
+    /// A database that fails to open is logged once, every call then fails.
+    pub fn shared() -> Arc<SessionStore> {
+        SHARED
+            .get_or_init(|| {
+                let store = default_path().and_then(|path| Self::open(&path));
+                Arc::new(store.unwrap_or_else(|e| {
+                    log::warn!("failed to open session store: {:?}", e);
+                    Self {
+                        conn: Mutex::new(None),
+                    }
+                }))
+            })
+            .clone()
+    }
+
+    fn with_conn<R>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<R>,
+    ) -> Result<R, ErrorCode> {
+        let conn = self.conn.lock().map_err(|_| ErrorCode::StorageFailed)?;
+        let conn = conn.as_ref().ok_or(ErrorCode::StorageFailed)?;
+        f(conn).map_err(|e| {
+            log::warn!("session store failed: {:?}", e);
+            ErrorCode::StorageFailed
+        })
+    }
+}
+
+impl SessionsInner for SessionStore {
+    fn create(&self, title: &str) -> Result<Session, ErrorCode> {
+        check_label(title)?;
+        let now = now();
+        let session = Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            created_at: now,
+            updated_at: now,
+            message_count: 0,
+        };
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO sessions (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+                params![session.id, session.title, session.created_at as i64],
+            )
+        })?;
+        Ok(session)
+    }
+
+    fn get(&self, id: &str) -> Result<Session, ErrorCode> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+                params![id],
+                session,
+            )
+            .optional()
+        })?
+        .ok_or(ErrorCode::NotFound)
+    }
+
+    fn append(&self, id: &str, role: &str, content: &str) -> Result<u64, ErrorCode> {
+        if role.is_empty() {
+            return Err(ErrorCode::InvalidArgument);
+        }
+        check_label(role)?;
+
+        let now = now() as i64;
+        let position = self.with_conn(|conn| {
+            // The position is the number of messages before it, so appends are serialized
+            let tx = conn.unchecked_transaction()?;
+            let updated = tx.execute(
+                "UPDATE sessions SET updated_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            let position: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+                params![id],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                "INSERT INTO messages (session_id, position, role, content, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, position, role, content, now],
+            )?;
+            tx.commit()?;
+            Ok(Some(position as u64))
+        })?;
+        position.ok_or(ErrorCode::NotFound)
+    }
+
+    fn list(&self, offset: u64, limit: u32) -> Result<Vec<Session>, ErrorCode> {
+        check_page(limit)?;
+        self.with_conn(|conn| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM sessions ORDER BY updated_at DESC, id LIMIT ?1 OFFSET ?2",
+                SESSION_COLUMNS
+            ))?;
+            let sessions = statement
+                .query_map(params![limit, offset as i64], session)?
+                .collect();
+            sessions
+        })
+    }
+
+    fn history(&self, id: &str, offset: u64, limit: u32) -> Result<Vec<Message>, ErrorCode> {
+        check_page(limit)?;
+        // An unknown session is an error rather than an empty history
+        self.get(id)?;
+        self.with_conn(|conn| {
+            let mut statement = conn.prepare(
+                "SELECT position, role, content, created_at FROM messages
+                 WHERE session_id = ?1 ORDER BY position LIMIT ?2 OFFSET ?3",
+            )?;
+            let messages = statement
+                .query_map(params![id, limit, offset as i64], |row| {
+                    Ok(Message {
+                        index: row.get::<_, i64>(0)? as u64,
+                        role: row.get(1)?,
+                        content: row.get(2)?,
+                        created_at: row.get::<_, i64>(3)? as u64,
+                    })
+                })?
+                .collect();
+            messages
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, ErrorCode> {
+        self.with_conn(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM messages WHERE session_id = ?1", params![id])?;
+            let deleted = tx.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+            tx.commit()?;
+            Ok(deleted > 0)
+        })
+    }
+}
+
+/// Path of the store shared by every engine, `~/.hayride/data/sessions.sqlite`.
+pub fn default_path() -> Result<PathBuf> {
+    Ok(hayride_utils::paths::hayride::default_hayride_dir()?
+        .join("data")
+        .join("sessions.sqlite"))
+}
+
+fn session(row: &Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        created_at: row.get::<_, i64>(2)? as u64,
+        updated_at: row.get::<_, i64>(3)? as u64,
+        message_count: row.get::<_, i64>(4)? as u64,
+    })
+}
+
+fn check_label(label: &str) -> Result<(), ErrorCode> {
+    match label.chars().count() > MAX_LABEL_LEN {
+        true => Err(ErrorCode::InvalidArgument),
+        false => Ok(()),
+    }
+}
+
+fn check_page(limit: u32) -> Result<(), ErrorCode> {
+    match limit == 0 || limit > MAX_PAGE {
+        true => Err(ErrorCode::InvalidArgument),
+        false => Ok(()),
+    }
+}
+
+// Milliseconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,133 @@
+//! Ed25519 signatures of morphs and release assets.
+//!
+//! A morph is signed over the sha256 digest of its wasm binary, the base64 signature is
+//! kept next to it as `<name>.wasm.sig`, both in remote registries and the local registry.
+//! Release assets are signed the same way, with the signature published as `<asset>.sig`.
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+use std::path::{Path, PathBuf};
+
+// Extension of public key files in a trusted keys dir
+const KEY_EXTENSION: &str = "pub";
+
+/// Returns the path of the signature kept next to a morph.
+pub fn signature_path(morph: &Path) -> PathBuf {
+    let mut path = morph.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Sign a morph, returning the base64 signature.
+pub fn sign(key: &Ed25519KeyPair, morph: &[u8]) -> String {
+    let signature = key.sign(digest(&SHA256, morph).as_ref());
+    base64::engine::general_purpose::STANDARD.encode(signature.as_ref())
+}
+
+/// Checks morphs against a set of trusted public keys.
+#[derive(Clone, Debug, Default)]
+pub struct MorphVerifier {
+    trusted_keys: Vec<Vec<u8>>,
+    // Reject unsigned and invalid morphs instead of warning about them
+    enforce: bool,
+}
+
+impl MorphVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the base64 encoded public keys.
+    pub fn with_trusted_keys(mut self, keys: &[String]) -> Result<Self> {
+        for key in keys {
+            self.trusted_keys.push(decode_key(key)?);
+        }
+        Ok(self)
+    }
+
+    /// Trust the base64 encoded public keys in the `.pub` files of a directory, a missing
+    /// directory trusts no keys.
+    pub fn with_key_dir(mut self, dir: &Path) -> Result<Self> {
+        if !dir.exists() {
+            return Ok(self);
+        }
+
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_EXTENSION) {
+                continue;
+            }
+            let key = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("failed to read key {}: {}", path.display(), e))?;
+            self.trusted_keys
+                .push(decode_key(&key).map_err(|e| anyhow!("{}: {}", path.display(), e))?);
+        }
+        Ok(self)
+    }
+
+    pub fn with_enforce(mut self, enforce: bool) -> Self {
+        self.enforce = enforce;
+        self
+    }
+
+    pub fn has_trusted_keys(&self) -> bool {
+        !self.trusted_keys.is_empty()
+    }
+
+    /// Verify a base64 signature of a morph against the trusted keys.
+    pub fn verify(&self, morph: &[u8], signature: &[u8]) -> Result<()> {
+        self.verify_digest(digest(&SHA256, morph).as_ref(), signature)
+    }
+
+    /// Verify a base64 signature of a sha256 digest against the trusted keys, for files
+    /// hashed while they are written.
+    pub fn verify_digest(&self, digest: &[u8], signature: &[u8]) -> Result<()> {
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(String::from_utf8_lossy(signature).trim())
+            .map_err(|e| anyhow!("malformed signature: {}", e))?;
+
+        let trusted = self.trusted_keys.iter().any(|key| {
+            UnparsedPublicKey::new(&ED25519, key)
+                .verify(digest, &signature)
+                .is_ok()
+        });
+        if !trusted {
+            return Err(anyhow!("not signed by a trusted key"));
+        }
+        Ok(())
+    }
+
+    /// Check a morph loaded from `path` against the signature kept next to it.
+    ///
+    /// Unsigned and invalid morphs are rejected when enforcement is enabled, otherwise
+    /// they are logged and allowed to load.
+    pub fn check(&self, path: &Path, morph: &[u8]) -> Result<()> {
+        if !self.enforce && self.trusted_keys.is_empty() {
+            return Ok(());
+        }
+
+        let result = match std::fs::read(signature_path(path)) {
+            Ok(signature) => self.verify(morph, &signature),
+            Err(_) => Err(anyhow!("no signature")),
+        };
+        match result {
+            Ok(()) => {
+                log::debug!("verified signature of {}", path.display());
+                Ok(())
+            }
+            Err(e) if self.enforce => Err(anyhow!("rejected morph {}: {}", path.display(), e)),
+            Err(e) => {
+                log::warn!("loading morph {}: {}", path.display(), e);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(key.trim())
+        .map_err(|e| anyhow!("invalid trusted key: {}", e))
+}
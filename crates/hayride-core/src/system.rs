@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
+
+use hayride_host_traits::core::system::{Capabilities, Device, MemoryStats, SystemInner};
+
+/// Hardware of the host read with sysinfo, devices are listed by the inference backend.
+pub struct SystemInfo {
+    system: Mutex<System>,
+    capabilities: Capabilities,
+    devices: fn() -> Vec<Device>,
+}
+
+impl SystemInfo {
+    /// Read the hardware of the host, listing no devices.
+    pub fn new() -> Self {
+        let system = System::new_with_specifics(
+            RefreshKind::nothing()
+                .with_cpu(CpuRefreshKind::nothing())
+                .with_memory(MemoryRefreshKind::everything()),
+        );
+        let capabilities = Capabilities {
+            os: System::name().unwrap_or_default(),
+            os_version: System::os_version().unwrap_or_default(),
+            arch: System::cpu_arch(),
+            cpu_brand: system
+                .cpus()
+                .first()
+                .map(|cpu| cpu.brand().trim().to_string())
+                .unwrap_or_default(),
+            physical_cores: System::physical_core_count().unwrap_or_default() as u32,
+            logical_cores: system.cpus().len() as u32,
+            memory_total: system.total_memory(),
+            gpu_backends: vec![],
+        };
+        Self {
+            system: Mutex::new(system),
+            capabilities,
+            devices: Vec::new,
+        }
+    }
+
+    /// GPU backends the runtime was built with.
+    pub fn with_gpu_backends(mut self, gpu_backends: Vec<String>) -> Self {
+        self.capabilities.gpu_backends = gpu_backends;
+        self
+    }
+
+    /// List the devices of the inference backend with the function.
+    pub fn with_devices(mut self, devices: fn() -> Vec<Device>) -> Self {
+        self.devices = devices;
+        self
+    }
+}
+
+impl Default for SystemInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemInner for SystemInfo {
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+
+    fn memory(&self) -> MemoryStats {
+        let mut system = self
+            .system
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        system.refresh_memory();
+        MemoryStats {
+            memory_total: system.total_memory(),
+            memory_available: system.available_memory(),
+            swap_total: system.total_swap(),
+            swap_free: system.free_swap(),
+        }
+    }
+
+    fn devices(&self) -> Vec<Device> {
+        (self.devices)()
+    }
+}
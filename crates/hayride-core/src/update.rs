@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use ring::digest::{Context, SHA256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use hayride_host_traits::core::version::ReleaseInfo;
+
+use crate::signature::MorphVerifier;
+
+// Release assets that have to be unpacked before they can replace the executable
+const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar.gz", ".tgz", ".zip", ".tar.xz"];
+
+/// Whether the latest release tag is a newer version than the running one, tags may
+/// start with a `v`.
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let parse = |version: &str| semver::Version::parse(version.trim().trim_start_matches('v'));
+    match (parse(latest), parse(current)) {
+        (Ok(latest), Ok(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Download the platform asset of a release into `dir/<version>`, removing it again
+/// unless its published signature was made by one of the keys the verifier trusts.
+///
+/// A checksum published with the release would only catch a corrupted download, the
+/// signature also rejects assets of a tampered release.
+pub fn download(release: &ReleaseInfo, dir: &Path, verifier: &MorphVerifier) -> Result<PathBuf> {
+    if !verifier.has_trusted_keys() {
+        return Err(anyhow!("no trusted release keys are configured"));
+    }
+    let url = release
+        .download_url
+        .as_ref()
+        .ok_or_else(|| anyhow!("release {} has no asset for this platform", release.version))?;
+    // Never install an asset that can not be verified
+    let signature_url = release
+        .signature_url
+        .as_ref()
+        .ok_or_else(|| anyhow!("release {} has no published signature", release.version))?;
+    let name = url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("invalid download url {}", url))?;
+
+    let client = reqwest::blocking::Client::new();
+    let signature = client
+        .get(signature_url)
+        .header(reqwest::header::USER_AGENT, "Hayride")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .map_err(|e| anyhow!("failed to download signature {}: {}", signature_url, e))?;
+
+    let dir = dir.join(release.version.trim_start_matches('v'));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    let partial = dir.join(format!("{}.part", name));
+
+    let mut response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, "Hayride")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| anyhow!("failed to download {}: {}", url, e))?;
+    let digest = match write_hashed(&mut response, &partial) {
+        Ok(digest) => digest,
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(anyhow!("failed to download {}: {}", url, e));
+        }
+    };
+    if let Err(e) = verifier.verify_digest(&digest, &signature) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(anyhow!("rejected {}: {}", name, e));
+    }
+
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// Replace the running executable with a downloaded release asset, keeping the previous
+/// executable next to it with an `.old` extension. The new version runs once hayride
+/// restarts.
+pub fn apply(asset: &Path) -> Result<()> {
+    let name = asset
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if ARCHIVE_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) {
+        return Err(anyhow!(
+            "{} is an archive, install it manually",
+            asset.display()
+        ));
+    }
+
+    let exe = std::env::current_exe()?;
+    let staged = exe.with_extension("new");
+    let previous = exe.with_extension("old");
+    let failed = |e: std::io::Error| anyhow!("failed to replace {}: {}", exe.display(), e);
+
+    // Stage the asset next to the executable so the final renames stay on one filesystem
+    std::fs::copy(asset, &staged).map_err(failed)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(failed)?;
+    }
+
+    // A running executable can be renamed but not always overwritten
+    std::fs::rename(&exe, &previous).map_err(failed)?;
+    if let Err(e) = std::fs::rename(&staged, &exe) {
+        let _ = std::fs::rename(&previous, &exe);
+        let _ = std::fs::remove_file(&staged);
+        return Err(failed(e));
+    }
+    Ok(())
+}
+
+// Write the reader to a file, returning the sha256 digest of what was written
+fn write_hashed(reader: &mut impl Read, path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::create(path)?;
+    let mut context = Context::new(&SHA256);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buf[..n]);
+        file.write_all(&buf[..n])?;
+    }
+    file.sync_all()?;
+    Ok(context.finish().as_ref().to_vec())
+}
@@ -60,7 +60,7 @@ impl ConnectionStringParser {
 
         // ---- SQLite ----
         // In-memory forms
-        if lower == "sqlite::memory:" || lower.starts_with("file::memory:") {
+        if lower == ":memory:" || lower == "sqlite::memory:" || lower.starts_with("file::memory:") {
             return DatabaseType::SQLite;
         }
         // Common SQLite file extensions or typical path-like strings.
@@ -1,36 +1,38 @@
 use anyhow::Result;
-use hayride_host_traits::db::{errors::ErrorCode, Connection, DBConnection, DBTrait};
-use std::sync::OnceLock;
-use tokio::runtime::Runtime;
+use hayride_host_traits::db::{errors::ErrorCode, Connection, DBConnectionAsync, DBTraitAsync};
+use std::time::Duration;
 
 pub mod connection_string;
+pub mod migrations;
 #[cfg(feature = "postgres")]
 pub mod postgres;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
+pub mod statement_cache;
 
 use connection_string::{ConnectionStringParser, DatabaseType};
 
-#[derive(Clone)]
-pub struct DBBackend {}
-
-// Global runtime for database operations
-static DB_RUNTIME: OnceLock<Runtime> = OnceLock::new();
-
-fn get_db_runtime() -> &'static Runtime {
-    DB_RUNTIME.get_or_init(|| Runtime::new().expect("Failed to create database runtime"))
+#[derive(Clone, Default)]
+pub struct DBBackend {
+    query_timeout: Option<Duration>,
 }
 
 impl DBBackend {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Set the timeout of queries on connections that do not set their own.
+    pub fn with_query_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.query_timeout = timeout;
+        self
     }
 
     /// Create a database connection based on the connection string
-    fn create_connection(
+    async fn create_connection(
         &self,
         connection_string: &str,
-    ) -> Result<Box<dyn DBConnection>, ErrorCode> {
+    ) -> Result<Box<dyn DBConnectionAsync>, ErrorCode> {
         let parser = ConnectionStringParser::new(connection_string);
         let db_type = parser
             .get_database_type()
@@ -40,15 +42,14 @@ impl DBBackend {
             DatabaseType::PostgreSQL => {
                 #[cfg(feature = "postgres")]
                 {
-                    tokio::task::block_in_place(|| {
-                        let rt = get_db_runtime();
-                        rt.block_on(async {
-                            postgres::PostgresDBConnection::new(connection_string)
-                                .await
-                                .map(|conn| Box::new(conn) as Box<dyn DBConnection>)
-                                .map_err(|_| ErrorCode::OpenFailed)
+                    // The connection is driven by a task on the runtime of the host
+                    postgres::PostgresDBConnection::new(connection_string, self.query_timeout)
+                        .await
+                        .map(|conn| Box::new(conn) as Box<dyn DBConnectionAsync>)
+                        .map_err(|e| {
+                            log::warn!("failed to connect to PostgreSQL: {}", e);
+                            ErrorCode::OpenFailed
                         })
-                    })
                 }
                 #[cfg(not(feature = "postgres"))]
                 {
@@ -61,9 +62,18 @@ impl DBBackend {
             DatabaseType::SQLite => {
                 #[cfg(feature = "sqlite")]
                 {
-                    sqlite::SQLiteDBConnection::new(connection_string)
-                        .map(|conn| Box::new(conn) as Box<dyn DBConnection>)
-                        .map_err(|_| ErrorCode::OpenFailed)
+                    // Opening sets pragmas, which can wait on the lock of the database
+                    let connection_string = connection_string.to_string();
+                    tokio::task::spawn_blocking(move || {
+                        sqlite::SQLiteDBConnection::new(&connection_string)
+                            .map(|conn| Box::new(conn) as Box<dyn DBConnectionAsync>)
+                            .map_err(|e| {
+                                log::warn!("failed to open SQLite database: {}", e);
+                                ErrorCode::OpenFailed
+                            })
+                    })
+                    .await
+                    .map_err(|_| ErrorCode::OpenFailed)?
                 }
                 #[cfg(not(feature = "sqlite"))]
                 {
@@ -87,9 +97,10 @@ impl DBBackend {
     }
 }
 
-impl DBTrait for DBBackend {
-    fn open(&mut self, connection_string: String) -> Result<Connection, ErrorCode> {
-        let connection = self.create_connection(&connection_string)?;
+#[async_trait::async_trait]
+impl DBTraitAsync for DBBackend {
+    async fn open(&mut self, connection_string: String) -> Result<Connection, ErrorCode> {
+        let connection = self.create_connection(&connection_string).await?;
         Ok(connection.into())
     }
 }
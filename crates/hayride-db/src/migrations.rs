@@ -0,0 +1,131 @@
+use anyhow::anyhow;
+use hayride_host_traits::db::db::DBValue;
+use hayride_host_traits::db::{DBConnectionAsync, Error, ErrorCode, Migration};
+use std::collections::HashSet;
+
+/// Table tracking the migrations applied to a database.
+pub const SCHEMA_TABLE: &str = "hayride_migrations";
+
+/// Apply the migrations not yet applied to the database in the order of their version,
+/// returning the versions applied. A dry run returns the versions it would apply
+/// without changing the database.
+///
+/// Each migration runs in its own transaction with the row recording it, so a failed
+/// migration leaves the database at the previous version.
+pub async fn migrate(
+    connection: &dyn DBConnectionAsync,
+    migrations: &[Migration],
+    dry_run: bool,
+) -> Result<Vec<u64>, Error> {
+    let mut migrations: Vec<&Migration> = migrations.iter().collect();
+    migrations.sort_by_key(|migration| migration.version);
+    if let Some(pair) = migrations
+        .windows(2)
+        .find(|pair| pair[0].version == pair[1].version)
+    {
+        return Err(error(
+            ErrorCode::ExecuteFailed,
+            format!("duplicate migration version {}", pair[0].version),
+        ));
+    }
+
+    // A database never migrated has no schema table, which a dry run does not create
+    if !dry_run {
+        connection
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (version BIGINT PRIMARY KEY, name TEXT NOT NULL, applied_at TEXT NOT NULL)",
+                SCHEMA_TABLE
+            ))
+            .await
+            .map_err(|code| error(code, "failed to create the migrations table".to_string()))?;
+    }
+    let applied = match applied(connection).await {
+        Ok(applied) => applied,
+        Err(_) if dry_run => HashSet::new(),
+        Err(e) => return Err(e),
+    };
+
+    // Migrations older than the database would apply on top of a newer schema
+    let latest = applied.iter().max().copied();
+    let pending: Vec<&Migration> = migrations
+        .into_iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect();
+    if let (Some(latest), Some(first)) = (latest, pending.first()) {
+        if first.version < latest {
+            return Err(error(
+                ErrorCode::ExecuteFailed,
+                format!(
+                    "migration {} is older than the applied version {}",
+                    first.version, latest
+                ),
+            ));
+        }
+    }
+
+    let mut versions = vec![];
+    for migration in pending {
+        if !dry_run {
+            apply(connection, migration).await?;
+            log::info!("applied migration {} {}", migration.version, migration.name);
+        }
+        versions.push(migration.version);
+    }
+    Ok(versions)
+}
+
+/// Versions of the migrations applied to the database.
+pub async fn applied(connection: &dyn DBConnectionAsync) -> Result<HashSet<u64>, Error> {
+    let failed = |code| error(code, "failed to read the applied migrations".to_string());
+    let statement = connection
+        .prepare(format!("SELECT version FROM {}", SCHEMA_TABLE))
+        .await
+        .map_err(failed)?;
+    let mut rows = statement.query(vec![]).await.map_err(failed)?;
+
+    let mut versions = HashSet::new();
+    loop {
+        let row = match rows.next().await {
+            Ok(row) => row,
+            Err(ErrorCode::EndOfRows) => break,
+            Err(code) => return Err(failed(code)),
+        };
+        let version = match row.0.first() {
+            Some(DBValue::Int64(version)) => *version as u64,
+            Some(DBValue::Int32(version)) => *version as u64,
+            Some(DBValue::Uint64(version)) => *version,
+            Some(DBValue::Uint32(version)) => *version as u64,
+            _ => return Err(failed(ErrorCode::QueryFailed)),
+        };
+        versions.insert(version);
+    }
+    Ok(versions)
+}
+
+// Run a migration and record it in one transaction
+async fn apply(connection: &dyn DBConnectionAsync, migration: &Migration) -> Result<(), Error> {
+    let batch = format!(
+        "BEGIN;\n{}\n;\nINSERT INTO {} (version, name, applied_at) VALUES ({}, '{}', '{}');\nCOMMIT;",
+        migration.sql.trim().trim_end_matches(';'),
+        SCHEMA_TABLE,
+        migration.version,
+        migration.name.replace('\'', "''"),
+        chrono::Utc::now().to_rfc3339()
+    );
+    if let Err(code) = connection.execute_batch(&batch).await {
+        // A failed statement leaves the transaction open
+        let _ = connection.execute_batch("ROLLBACK").await;
+        return Err(error(
+            code,
+            format!("migration {} {} failed", migration.version, migration.name),
+        ));
+    }
+    Ok(())
+}
+
+fn error(code: ErrorCode, message: String) -> Error {
+    Error {
+        code,
+        data: anyhow!(message),
+    }
+}
@@ -1,20 +1,22 @@
 use hayride_host_traits::db::{
-    errors::ErrorCode, DBConnection, DBRows, DBStatement, IsolationLevel, Rows, Statement,
-    Transaction,
+    errors::ErrorCode, DBConnectionAsync, DBRowsAsync, DBStatementAsync, IsolationLevel, Rows,
+    Statement, Transaction,
 };
 
 use futures::stream::Stream;
 use futures::StreamExt;
 use native_tls::TlsConnector;
 use postgres_native_tls::MakeTlsConnector;
+use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tokio_postgres::Row;
+use tokio_postgres::{CancelToken, Row};
 use tokio_util::sync::CancellationToken;
 
-use crate::get_db_runtime;
+use crate::statement_cache::StatementCache;
 
 // PostgreSQL-specific trait implementations for DBValue
 use hayride_host_traits::db::db::DBValue;
@@ -335,19 +337,119 @@ fn parse_datetime_string(
     Err(format!("Cannot parse datetime from: {}", s).into())
 }
 
+// Connection string parameters read by the host, which are not passed on to the server:
+// the timeout of queries in seconds and the statements kept prepared
+const QUERY_TIMEOUT: &str = "query_timeout";
+const STATEMENT_CACHE: &str = "statement_cache";
+
+/// Deadline of the operations of a connection, a query running past it is cancelled on
+/// the server so it stops holding the connection.
+#[derive(Clone)]
+struct Deadline {
+    timeout: Option<Duration>,
+    cancel_token: CancelToken,
+    tls: MakeTlsConnector,
+}
+
+impl Deadline {
+    async fn run<T>(
+        &self,
+        operation: impl Future<Output = Result<T, ErrorCode>>,
+    ) -> Result<T, ErrorCode> {
+        let Some(timeout) = self.timeout else {
+            return operation.await;
+        };
+        match tokio::time::timeout(timeout, operation).await {
+            Ok(result) => result,
+            Err(_) => {
+                log::warn!("PostgreSQL query timed out after {:?}, cancelling", timeout);
+                let cancel_token = self.cancel_token.clone();
+                let tls = self.tls.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = cancel_token.cancel_query(tls).await {
+                        log::warn!("Error cancelling PostgreSQL query: {}", e);
+                    }
+                });
+                Err(ErrorCode::Timeout)
+            }
+        }
+    }
+}
+
+/// Take the parameters read by the host out of a URL or keyword/value connection string.
+fn split_host_params(conn_str: &str) -> (String, Vec<(String, String)>) {
+    let is_host_param = |key: &str| key == QUERY_TIMEOUT || key == STATEMENT_CACHE;
+
+    if let Ok(mut url) = url::Url::parse(conn_str) {
+        let (host_params, pairs): (Vec<_>, Vec<_>) = url
+            .query_pairs()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .partition(|(key, _)| is_host_param(key));
+        if pairs.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        return (url.to_string(), host_params);
+    }
+
+    let mut host_params = vec![];
+    let mut keywords = vec![];
+    for keyword in conn_str.split_whitespace() {
+        match keyword.split_once('=') {
+            Some((key, value)) if is_host_param(key) => {
+                host_params.push((key.to_string(), value.to_string()))
+            }
+            _ => keywords.push(keyword),
+        }
+    }
+    (keywords.join(" "), host_params)
+}
+
 pub struct PostgresDBConnection {
     client: Arc<Mutex<Option<tokio_postgres::Client>>>,
     cancellation_token: CancellationToken,
+    deadline: Deadline,
+    statements: Statements,
 }
 
 impl PostgresDBConnection {
-    pub async fn new(conn_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Connect to the server, queries time out after the `query_timeout` of the connection
+    /// string or else the default timeout. Up to `statement_cache` statements, or the
+    /// process default, are kept prepared on the server.
+    pub async fn new(
+        conn_str: &str,
+        default_timeout: Option<Duration>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let tls = {
             let builder = TlsConnector::builder();
             MakeTlsConnector::new(builder.build()?)
         };
+        let (conn_str, host_params) = split_host_params(conn_str);
+        let mut timeout = None;
+        let mut capacity = crate::statement_cache::statement_cache().capacity();
+        for (key, value) in host_params {
+            match key.as_str() {
+                QUERY_TIMEOUT => {
+                    let secs: f64 = value
+                        .parse()
+                        .map_err(|_| format!("invalid {}: {}", QUERY_TIMEOUT, value))?;
+                    timeout = (secs > 0.0).then(|| Duration::from_secs_f64(secs));
+                }
+                _ => {
+                    capacity = value
+                        .parse()
+                        .map_err(|_| format!("invalid {}: {}", STATEMENT_CACHE, value))?;
+                }
+            }
+        }
 
-        let (client, connection) = tokio_postgres::connect(&conn_str, tls).await?;
+        let (client, connection) = tokio_postgres::connect(&conn_str, tls.clone()).await?;
+        let deadline = Deadline {
+            timeout: timeout.or(default_timeout),
+            cancel_token: client.cancel_token(),
+            tls,
+        };
         let cancellation_token = CancellationToken::new();
         let cancel_clone = cancellation_token.clone();
 
@@ -368,6 +470,8 @@ impl PostgresDBConnection {
         Ok(PostgresDBConnection {
             client: Arc::new(Mutex::new(Some(client))),
             cancellation_token,
+            deadline,
+            statements: Arc::new(std::sync::Mutex::new(StatementCache::new(capacity))),
         })
     }
 }
@@ -380,32 +484,55 @@ impl Drop for PostgresDBConnection {
     }
 }
 
-impl DBConnection for PostgresDBConnection {
-    fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
-        tokio::task::block_in_place(|| {
-            let rt = get_db_runtime();
-            rt.block_on(async {
+#[async_trait::async_trait]
+impl DBConnectionAsync for PostgresDBConnection {
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
+        self.deadline
+            .run(async {
                 let client_guard = self.client.lock().await;
                 match client_guard.as_ref() {
                     Some(client) => {
-                        let statement = client.prepare(&query).await.map_err(|e| {
-                            log::warn!("PostgresDBConnection prepare failed with error: {}", e);
-                            ErrorCode::PrepareFailed
-                        })?;
-
-                        let postgres_statement =
-                            PostgresStatement::new(self.client.clone(), statement);
-
-                        let boxed_statement: Box<dyn DBStatement> = Box::new(postgres_statement);
+                        let cached = self
+                            .statements
+                            .lock()
+                            .ok()
+                            .and_then(|mut statements| statements.get(&query));
+                        let statement = match cached {
+                            Some(statement) => statement,
+                            None => {
+                                let statement = client.prepare(&query).await.map_err(|e| {
+                                    log::warn!(
+                                        "PostgresDBConnection prepare failed with error: {}",
+                                        e
+                                    );
+                                    ErrorCode::PrepareFailed
+                                })?;
+                                if let Ok(mut statements) = self.statements.lock() {
+                                    statements.insert(query.clone(), statement.clone());
+                                }
+                                statement
+                            }
+                        };
+
+                        let postgres_statement = PostgresStatement {
+                            client: self.client.clone(),
+                            statement,
+                            query,
+                            statements: self.statements.clone(),
+                            deadline: self.deadline.clone(),
+                        };
+
+                        let boxed_statement: Box<dyn DBStatementAsync> =
+                            Box::new(postgres_statement);
                         Ok(boxed_statement.into())
                     }
                     None => Err(ErrorCode::PrepareFailed),
                 }
             })
-        })
+            .await
     }
 
-    fn begin_transaction(
+    async fn begin_transaction(
         &mut self,
         _isolation_level: IsolationLevel,
         _read_only: bool,
@@ -415,49 +542,72 @@ impl DBConnection for PostgresDBConnection {
         Err(ErrorCode::NotEnabled)
     }
 
-    fn close(&mut self) -> std::result::Result<(), ErrorCode> {
-        tokio::task::block_in_place(|| {
-            let rt = get_db_runtime();
-            rt.block_on(async {
-                // Signal the background task to stop
-                self.cancellation_token.cancel();
+    async fn execute_batch(&self, sql: &str) -> Result<(), ErrorCode> {
+        self.deadline
+            .run(async {
+                let client_guard = self.client.lock().await;
+                let client = client_guard.as_ref().ok_or(ErrorCode::ExecuteFailed)?;
+                client.batch_execute(sql).await.map_err(|e| {
+                    log::warn!("PostgresDBConnection batch failed with error: {}", e);
+                    ErrorCode::ExecuteFailed
+                })
+            })
+            .await
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, ErrorCode> {
+        log::warn!("PostgreSQL has no rowid, use a RETURNING clause with execute-returning");
+        Err(ErrorCode::NotEnabled)
+    }
+
+    async fn close(&mut self) -> std::result::Result<(), ErrorCode> {
+        // Signal the background task to stop
+        self.cancellation_token.cancel();
 
-                // Close the client connection
-                let mut client_guard = self.client.lock().await;
-                if let Some(client) = client_guard.take() {
-                    // The client will be dropped here, which closes the connection
-                    drop(client);
-                    log::debug!("PostgresDBConnection closed");
-                }
+        // Close the client connection
+        if let Ok(mut statements) = self.statements.lock() {
+            statements.clear();
+        }
+        let mut client_guard = self.client.lock().await;
+        if let Some(client) = client_guard.take() {
+            // The client will be dropped here, which closes the connection
+            drop(client);
+            log::debug!("PostgresDBConnection closed");
+        }
 
-                Ok(())
-            })
-        })
+        Ok(())
     }
 }
 
+// Statements kept prepared on a connection, shared with the statements prepared on it
+type Statements = Arc<std::sync::Mutex<StatementCache<tokio_postgres::Statement>>>;
+
 struct PostgresStatement {
     client: Arc<Mutex<Option<tokio_postgres::Client>>>,
     statement: tokio_postgres::Statement,
+    query: String,
+    statements: Statements,
+    deadline: Deadline,
 }
 
 impl PostgresStatement {
-    fn new(
-        client: Arc<Mutex<Option<tokio_postgres::Client>>>,
-        statement: tokio_postgres::Statement,
-    ) -> Self {
-        Self { client, statement }
+    // Drop the statement from the cache once it failed, as it may be stale after the
+    // schema changed, so the query is prepared again
+    fn evict(&self) {
+        if let Ok(mut statements) = self.statements.lock() {
+            statements.remove(&self.query);
+        }
     }
 }
 
-impl DBStatement for PostgresStatement {
-    fn query(
+#[async_trait::async_trait]
+impl DBStatementAsync for PostgresStatement {
+    async fn query(
         &self,
         params: Vec<hayride_host_traits::db::db::DBValue>,
     ) -> std::result::Result<Rows, ErrorCode> {
-        tokio::task::block_in_place(|| {
-            let rt = get_db_runtime();
-            rt.block_on(async {
+        self.deadline
+            .run(async {
                 let client_guard = self.client.lock().await;
                 match client_guard.as_ref() {
                     Some(client) => {
@@ -475,6 +625,7 @@ impl DBStatement for PostgresStatement {
                             .await
                             .map_err(|e| {
                                 log::warn!("PostgresStatement Query failed with error: {}", e);
+                                self.evict();
                                 ErrorCode::QueryFailed
                             })?;
 
@@ -498,23 +649,23 @@ impl DBStatement for PostgresStatement {
                                     + Sync,
                             >,
                         > = Box::pin(stream);
-                        let postgres_rows = PostgresRows::new(boxed_stream, columns);
-                        let boxed_rows: Box<dyn DBRows> = Box::new(postgres_rows);
+                        let postgres_rows =
+                            PostgresRows::new(boxed_stream, columns, self.deadline.clone());
+                        let boxed_rows: Box<dyn DBRowsAsync> = Box::new(postgres_rows);
                         Ok(boxed_rows.into())
                     }
                     None => Err(ErrorCode::QueryFailed),
                 }
             })
-        })
+            .await
     }
 
-    fn execute(
+    async fn execute(
         &self,
         params: Vec<hayride_host_traits::db::db::DBValue>,
     ) -> std::result::Result<u64, ErrorCode> {
-        tokio::task::block_in_place(|| {
-            let rt = get_db_runtime();
-            rt.block_on(async {
+        self.deadline
+            .run(async {
                 let client_guard = self.client.lock().await;
                 match client_guard.as_ref() {
                     Some(client) => {
@@ -527,23 +678,39 @@ impl DBStatement for PostgresStatement {
                                 .map(|p| p as &(dyn tokio_postgres::types::ToSql + Sync))
                                 .collect();
 
-                        let result = client
-                            .execute(&self.statement, &param_refs)
-                            .await
-                            .map_err(|_| ErrorCode::ExecuteFailed)?;
+                        let result =
+                            client
+                                .execute(&self.statement, &param_refs)
+                                .await
+                                .map_err(|e| {
+                                    log::warn!(
+                                        "PostgresStatement Execute failed with error: {}",
+                                        e
+                                    );
+                                    self.evict();
+                                    ErrorCode::ExecuteFailed
+                                })?;
                         Ok(result)
                     }
                     None => Err(ErrorCode::ExecuteFailed),
                 }
             })
-        })
+            .await
+    }
+
+    async fn execute_returning(
+        &self,
+        params: Vec<hayride_host_traits::db::db::DBValue>,
+    ) -> std::result::Result<Rows, ErrorCode> {
+        // The server runs the statement whether or not its rows are read
+        self.query(params).await
     }
 
     fn number_parameters(&self) -> Result<u32, ErrorCode> {
         Ok(self.statement.params().len() as u32)
     }
 
-    fn close(&mut self) -> std::result::Result<(), ErrorCode> {
+    async fn close(&mut self) -> std::result::Result<(), ErrorCode> {
         log::debug!("PostgresStatement closed (no-op)");
         Ok(())
     }
@@ -555,6 +722,7 @@ struct PostgresRows {
     >,
     columns: Vec<String>,
     finished: bool,
+    deadline: Deadline,
 }
 
 impl PostgresRows {
@@ -565,48 +733,53 @@ impl PostgresRows {
             >,
         >,
         columns: Vec<String>,
+        deadline: Deadline,
     ) -> Self {
         Self {
             stream,
             columns,
             finished: false,
+            deadline,
         }
     }
 }
 
-impl DBRows for PostgresRows {
+#[async_trait::async_trait]
+impl DBRowsAsync for PostgresRows {
     fn columns(&self) -> Vec<String> {
         self.columns.clone()
     }
 
-    fn next(&mut self) -> Result<hayride_host_traits::db::db::Row, ErrorCode> {
+    async fn next(&mut self) -> Result<hayride_host_traits::db::db::Row, ErrorCode> {
         if self.finished {
             return Err(ErrorCode::EndOfRows);
         }
 
-        tokio::task::block_in_place(|| {
-            let rt = get_db_runtime();
-            rt.block_on(async {
-                match self.stream.next().await {
+        let stream = &mut self.stream;
+        let result = self
+            .deadline
+            .run(async {
+                match stream.next().await {
                     Some(Ok(row)) => {
                         let db_row = row_to_dbvalue_row(&row);
                         Ok(db_row)
                     }
                     Some(Err(e)) => {
                         log::warn!("Error reading row from stream: {}", e);
-                        self.finished = true;
                         Err(ErrorCode::QueryFailed)
                     }
-                    None => {
-                        self.finished = true;
-                        Err(ErrorCode::EndOfRows)
-                    }
+                    None => Err(ErrorCode::EndOfRows),
                 }
             })
-        })
+            .await;
+        // The stream ends on its last row, an error or a cancelled query
+        if result.is_err() {
+            self.finished = true;
+        }
+        result
     }
 
-    fn close(&mut self) -> Result<(), ErrorCode> {
+    async fn close(&mut self) -> Result<(), ErrorCode> {
         self.finished = true;
         log::debug!("PostgresRows closed");
         Ok(())
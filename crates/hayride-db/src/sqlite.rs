@@ -1,176 +1,630 @@
 use hayride_host_traits::db::{
-    errors::ErrorCode, DBConnection, DBRows, DBStatement, IsolationLevel, Rows, Statement,
-    Transaction,
+    db::DBValue, errors::ErrorCode, DBConnectionAsync, DBRowsAsync, DBStatementAsync,
+    DBTransactionAsync, IsolationLevel, Rows, Statement, Transaction,
 };
 
-use rusqlite::{params_from_iter, Connection as SqliteConnection};
+use rusqlite::{params_from_iter, CachedStatement, Connection as SqliteConnection, OpenFlags};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::statement_cache::StatementCache;
+
+// Pragmas settable through the query of the connection string,
+// e.g. `sqlite://app.db?synchronous=normal&foreign_keys=on`
+const PRAGMAS: &[&str] = &[
+    "journal_mode",
+    "synchronous",
+    "foreign_keys",
+    "busy_timeout",
+    "cache_size",
+    "temp_store",
+    "mmap_size",
+    "wal_autocheckpoint",
+];
+
+// How long a write waits for another connection to release the database
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Rows a cursor reads ahead of the component
+const CURSOR_BUFFER: usize = 64;
+
+// Idle read connections kept per connection for the cursors of its queries
+const MAX_IDLE_READERS: usize = 4;
+
+type SharedConnection = Arc<Mutex<Option<SqliteHandle>>>;
+
+/// A connection and the queries sqlite keeps prepared for it, mirroring the statement
+/// cache of the connection to count its hits.
+struct SqliteHandle {
+    connection: SqliteConnection,
+    prepared: Mutex<StatementCache<()>>,
+}
 
-pub struct SQLiteDBConnection {
-    connection: Arc<Mutex<Option<SqliteConnection>>>,
+impl SqliteHandle {
+    fn new(connection: SqliteConnection, capacity: usize) -> Self {
+        Self {
+            connection,
+            prepared: Mutex::new(StatementCache::new(capacity)),
+        }
+    }
+
+    fn prepare_cached(&self, query: &str) -> rusqlite::Result<CachedStatement<'_>> {
+        let statement = self.connection.prepare_cached(query);
+        if let Ok(mut prepared) = self.prepared.lock() {
+            match statement {
+                // Statements failing to prepare are not kept by sqlite
+                Err(_) => prepared.remove(query),
+                Ok(_) if prepared.get(query).is_none() => prepared.insert(query.to_string(), ()),
+                Ok(_) => {}
+            }
+        }
+        statement
+    }
 }
 
-impl SQLiteDBConnection {
-    pub fn new(conn_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        // Remove sqlite:// prefix if present
-        let path = if conn_str.starts_with("sqlite://") {
-            &conn_str[9..]
-        } else if conn_str.starts_with("file:") {
-            &conn_str[5..]
+impl std::ops::Deref for SqliteHandle {
+    type Target = SqliteConnection;
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
+
+/// Where a connection string opens its database.
+#[derive(Debug, PartialEq)]
+enum Target {
+    /// A private in-memory database, shared by the statements and transactions of
+    /// the connection
+    Memory,
+    Path(String),
+    /// A `file:` uri, e.g. `file:cache?mode=memory&cache=shared` for an in-memory
+    /// database shared by the connections of the process
+    Uri(String),
+}
+
+/// A parsed sqlite connection string.
+#[derive(Debug, PartialEq)]
+struct SqliteOptions {
+    target: Target,
+    pragmas: Vec<(String, String)>,
+    statement_cache: usize,
+}
+
+impl SqliteOptions {
+    /// Parse `sqlite://<path>`, `<path>`, `:memory:`, `sqlite::memory:` or `file:<path>`
+    /// connection strings, taking pragmas from the query.
+    ///
+    /// The other parameters of `file:` uris are left for sqlite to interpret.
+    fn parse(conn_str: &str) -> Result<Self, ErrorCode> {
+        let conn_str = conn_str.trim();
+        let conn_str = conn_str
+            .strip_prefix("sqlite://")
+            .or_else(|| conn_str.strip_prefix("sqlite:"))
+            .unwrap_or(conn_str);
+        let (path, query) = conn_str.split_once('?').unwrap_or((conn_str, ""));
+
+        let mut pragmas = vec![];
+        let mut statement_cache = crate::statement_cache::statement_cache().capacity();
+        let mut uri_params = vec![];
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let key = key.to_ascii_lowercase();
+            if key == "statement_cache" {
+                statement_cache = value.parse().map_err(|_| {
+                    log::warn!("invalid sqlite statement_cache: {}", value);
+                    ErrorCode::OpenFailed
+                })?;
+            } else if PRAGMAS.contains(&key.as_str()) {
+                if !value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    log::warn!("invalid value of sqlite pragma {}: {}", key, value);
+                    return Err(ErrorCode::OpenFailed);
+                }
+                pragmas.push((key, value.to_string()));
+            } else if path.starts_with("file:") {
+                uri_params.push((key, value.to_string()));
+            } else {
+                log::warn!("unknown sqlite connection parameter: {}", key);
+                return Err(ErrorCode::OpenFailed);
+            }
+        }
+
+        let target = if path.starts_with("file:") {
+            let mut uri = path.to_string();
+            if !uri_params.is_empty() {
+                let query: String = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(uri_params)
+                    .finish();
+                uri = format!("{}?{}", uri, query);
+            }
+            Target::Uri(uri)
+        } else if path.is_empty() || path == ":memory:" {
+            Target::Memory
         } else {
-            conn_str
+            Target::Path(path.to_string())
         };
 
-        let connection = SqliteConnection::open(path)?;
+        Ok(Self {
+            target,
+            pragmas,
+            statement_cache,
+        })
+    }
+
+    /// Whether other connections can open the database, a private in-memory database
+    /// is only seen by its connection.
+    fn shareable(&self) -> bool {
+        match &self.target {
+            Target::Memory => false,
+            Target::Path(_) => true,
+            // Shared in-memory databases lock tables instead of waiting on readers
+            Target::Uri(uri) => !uri.contains("mode=memory") && !uri.contains(":memory:"),
+        }
+    }
+
+    /// Open a read only connection to the database, for the cursors of queries.
+    fn open_reader(&self) -> rusqlite::Result<SqliteConnection> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            | OpenFlags::SQLITE_OPEN_URI;
+        let connection = match &self.target {
+            Target::Path(path) => SqliteConnection::open_with_flags(path, flags)?,
+            Target::Uri(uri) => SqliteConnection::open_with_flags(uri, flags)?,
+            Target::Memory => return Err(rusqlite::Error::InvalidPath(":memory:".into())),
+        };
+        connection.busy_timeout(DEFAULT_BUSY_TIMEOUT)?;
+        connection.set_prepared_statement_cache_capacity(self.statement_cache);
+
+        // The journal mode is kept in the database file by its writer
+        for (key, value) in self.pragmas.iter().filter(|(key, _)| key != "journal_mode") {
+            set_pragma(&connection, key, value)?;
+        }
+        Ok(connection)
+    }
+
+    fn open(&self) -> rusqlite::Result<SqliteConnection> {
+        let connection = match &self.target {
+            Target::Memory => SqliteConnection::open_in_memory()?,
+            Target::Path(path) => SqliteConnection::open(path)?,
+            Target::Uri(uri) => SqliteConnection::open_with_flags(
+                uri,
+                OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI,
+            )?,
+        };
+        connection.busy_timeout(DEFAULT_BUSY_TIMEOUT)?;
+        connection.set_prepared_statement_cache_capacity(self.statement_cache);
+
+        // Readers do not block the writer of a database file, unless configured otherwise
+        if matches!(self.target, Target::Path(_))
+            && !self.pragmas.iter().any(|(key, _)| key == "journal_mode")
+        {
+            set_pragma(&connection, "journal_mode", "wal")?;
+        }
+        for (key, value) in &self.pragmas {
+            set_pragma(&connection, key, value)?;
+        }
+        Ok(connection)
+    }
+}
+
+// Set a pragma, reading the row some pragmas return with their new value
+fn set_pragma(connection: &SqliteConnection, key: &str, value: &str) -> rusqlite::Result<()> {
+    let value = match value.parse::<i64>() {
+        Ok(number) => number.to_string(),
+        Err(_) => format!("'{}'", value),
+    };
+    let mut statement = connection.prepare(&format!("PRAGMA {} = {}", key, value))?;
+    let mut rows = statement.query([])?;
+    while rows.next()?.is_some() {}
+    Ok(())
+}
+
+// Run sqlite calls on a blocking thread, so they do not hold up the workers of the runtime
+async fn blocking<T: Send + 'static>(
+    code: ErrorCode,
+    f: impl FnOnce() -> Result<T, ErrorCode> + Send + 'static,
+) -> Result<T, ErrorCode> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        log::warn!("SQLite call failed: {}", e);
+        code
+    })?
+}
+
+pub struct SQLiteDBConnection {
+    connection: SharedConnection,
+    readers: Option<Arc<Readers>>,
+}
+
+impl SQLiteDBConnection {
+    pub fn new(conn_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let options =
+            SqliteOptions::parse(conn_str).map_err(|_| "invalid sqlite connection string")?;
+        let connection = SqliteHandle::new(options.open()?, options.statement_cache);
+        let readers = options.shareable().then(|| {
+            Arc::new(Readers {
+                options,
+                idle: Mutex::new(vec![]),
+            })
+        });
 
         Ok(SQLiteDBConnection {
             connection: Arc::new(Mutex::new(Some(connection))),
+            readers,
         })
     }
 }
 
-impl DBConnection for SQLiteDBConnection {
-    fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
-        let connection_guard = self
-            .connection
-            .lock()
-            .map_err(|_| ErrorCode::PrepareFailed)?;
-        match connection_guard.as_ref() {
-            Some(_conn) => {
-                // For SQLite, we'll store the query and prepare it on execution
-                let sqlite_statement = SQLiteStatement::new(self.connection.clone(), query);
-
-                let boxed_statement: Box<dyn DBStatement> = Box::new(sqlite_statement);
-                Ok(boxed_statement.into())
-            }
-            None => Err(ErrorCode::PrepareFailed),
-        }
+#[async_trait::async_trait]
+impl DBConnectionAsync for SQLiteDBConnection {
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
+        let connection = self.connection.clone();
+        let readers = self.readers.clone();
+        blocking(ErrorCode::PrepareFailed, move || {
+            prepare(&connection, readers, query)
+        })
+        .await
     }
 
-    fn begin_transaction(
+    async fn begin_transaction(
         &mut self,
-        _isolation_level: IsolationLevel,
-        _read_only: bool,
+        isolation_level: IsolationLevel,
+        read_only: bool,
     ) -> std::result::Result<Transaction, ErrorCode> {
-        // TODO: Implement transaction support for SQLite
-        log::warn!("SQLiteDBConnection begin_transaction not yet implemented");
-        Err(ErrorCode::NotEnabled)
+        let connection = self.connection.clone();
+        let transaction = blocking(ErrorCode::BeginTransactionFailed, move || {
+            SQLiteTransaction::begin(connection, isolation_level, read_only)
+        })
+        .await?;
+        let boxed_transaction: Box<dyn DBTransactionAsync> = Box::new(transaction);
+        Ok(boxed_transaction.into())
+    }
+
+    async fn execute_batch(&self, sql: &str) -> Result<(), ErrorCode> {
+        let connection = self.connection.clone();
+        let sql = sql.to_string();
+        blocking(ErrorCode::ExecuteFailed, move || {
+            let connection_guard = connection.lock().map_err(|_| ErrorCode::ExecuteFailed)?;
+            let conn = connection_guard.as_ref().ok_or(ErrorCode::ExecuteFailed)?;
+            conn.execute_batch(&sql).map_err(|e| {
+                log::warn!("Error executing SQLite batch: {}", e);
+                ErrorCode::ExecuteFailed
+            })
+        })
+        .await
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, ErrorCode> {
+        let connection_guard = self.connection.lock().map_err(|_| ErrorCode::Unknown)?;
+        let conn = connection_guard.as_ref().ok_or(ErrorCode::Unknown)?;
+        Ok(conn.last_insert_rowid())
     }
 
-    fn close(&mut self) -> std::result::Result<(), ErrorCode> {
+    async fn close(&mut self) -> std::result::Result<(), ErrorCode> {
         let mut connection_guard = self.connection.lock().map_err(|_| ErrorCode::CloseFailed)?;
         if let Some(conn) = connection_guard.take() {
             drop(conn);
             log::debug!("SQLiteDBConnection closed");
         }
+        if let Some(readers) = &self.readers {
+            if let Ok(mut idle) = readers.idle.lock() {
+                idle.clear();
+            }
+        }
         Ok(())
     }
 }
 
-struct SQLiteStatement {
-    connection: Arc<Mutex<Option<SqliteConnection>>>,
+// Prepare a statement on the connection, checking the query and keeping it in the cache
+// of the connection for its executions
+fn prepare(
+    connection: &SharedConnection,
+    readers: Option<Arc<Readers>>,
     query: String,
+) -> Result<Statement, ErrorCode> {
+    let connection_guard = connection.lock().map_err(|_| ErrorCode::PrepareFailed)?;
+    let conn = connection_guard.as_ref().ok_or(ErrorCode::PrepareFailed)?;
+    let parameters = conn
+        .prepare_cached(&query)
+        .map_err(|e| {
+            log::warn!("Error preparing SQLite statement: {}", e);
+            ErrorCode::PrepareFailed
+        })?
+        .parameter_count();
+
+    let sqlite_statement = SQLiteStatement {
+        connection: connection.clone(),
+        readers,
+        query,
+        parameters: parameters as u32,
+    };
+    let boxed_statement: Box<dyn DBStatementAsync> = Box::new(sqlite_statement);
+    Ok(boxed_statement.into())
 }
 
-impl SQLiteStatement {
-    fn new(connection: Arc<Mutex<Option<SqliteConnection>>>, query: String) -> Self {
-        Self { connection, query }
+// Run a query, streaming its rows from a cursor on a read connection when it reads the
+// committed database, otherwise collecting them while the connection is locked
+fn query(
+    connection: &SharedConnection,
+    readers: Option<&Arc<Readers>>,
+    query: &str,
+    params: Vec<DBValue>,
+) -> Result<Rows, ErrorCode> {
+    let connection_guard = connection.lock().map_err(|_| ErrorCode::QueryFailed)?;
+    let conn = connection_guard.as_ref().ok_or(ErrorCode::QueryFailed)?;
+    let mut stmt = conn.prepare_cached(query).map_err(|e| {
+        log::warn!("Error preparing SQLite query: {}", e);
+        ErrorCode::QueryFailed
+    })?;
+
+    // Convert DBValues to rusqlite parameters
+    let sqlite_params: Vec<rusqlite::types::Value> =
+        params.iter().map(dbvalue_to_sqlite_value).collect();
+
+    // Writes and queries in a transaction see changes only this connection sees
+    if let Some(readers) = readers {
+        if stmt.readonly() && conn.is_autocommit() {
+            drop(stmt);
+            drop(connection_guard);
+            let cursor = SQLiteCursor::open(readers.clone(), query.to_string(), sqlite_params)?;
+            let boxed_rows: Box<dyn DBRowsAsync> = Box::new(cursor);
+            return Ok(boxed_rows.into());
+        }
     }
+
+    // Collect all rows, the statement borrows the locked connection
+    let collected_rows = stmt
+        .query_map(params_from_iter(sqlite_params.iter()), |row| {
+            sqlite_row_to_dbvalue_row(row)
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            log::warn!("Error reading SQLite row: {}", e);
+            ErrorCode::QueryFailed
+        })?;
+
+    // Get column names
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+
+    let sqlite_rows = SQLiteRows::new(collected_rows, column_names);
+    let boxed_rows: Box<dyn DBRowsAsync> = Box::new(sqlite_rows);
+    Ok(boxed_rows.into())
+}
+
+fn execute(
+    connection: &SharedConnection,
+    query: &str,
+    params: Vec<DBValue>,
+) -> Result<u64, ErrorCode> {
+    let connection_guard = connection.lock().map_err(|_| ErrorCode::ExecuteFailed)?;
+    let conn = connection_guard.as_ref().ok_or(ErrorCode::ExecuteFailed)?;
+    let mut stmt = conn.prepare_cached(query).map_err(|e| {
+        log::warn!("Error preparing SQLite statement: {}", e);
+        ErrorCode::ExecuteFailed
+    })?;
+
+    // Convert DBValues to rusqlite parameters
+    let sqlite_params: Vec<rusqlite::types::Value> =
+        params.iter().map(dbvalue_to_sqlite_value).collect();
+
+    let result = stmt
+        .execute(params_from_iter(sqlite_params.iter()))
+        .map_err(|e| {
+            log::warn!("Error executing SQLite statement: {}", e);
+            ErrorCode::ExecuteFailed
+        })?;
+    Ok(result as u64)
 }
 
-impl DBStatement for SQLiteStatement {
-    fn query(
+/// A statement prepared on a connection, kept in the statement cache of the connection
+/// between executions.
+struct SQLiteStatement {
+    connection: SharedConnection,
+    readers: Option<Arc<Readers>>,
+    query: String,
+    parameters: u32,
+}
+
+#[async_trait::async_trait]
+impl DBStatementAsync for SQLiteStatement {
+    async fn query(&self, params: Vec<DBValue>) -> std::result::Result<Rows, ErrorCode> {
+        let connection = self.connection.clone();
+        let readers = self.readers.clone();
+        let statement = self.query.clone();
+        blocking(ErrorCode::QueryFailed, move || {
+            query(&connection, readers.as_ref(), &statement, params)
+        })
+        .await
+    }
+
+    async fn execute(&self, params: Vec<DBValue>) -> std::result::Result<u64, ErrorCode> {
+        let connection = self.connection.clone();
+        let statement = self.query.clone();
+        blocking(ErrorCode::ExecuteFailed, move || {
+            execute(&connection, &statement, params)
+        })
+        .await
+    }
+
+    async fn execute_returning(
         &self,
-        params: Vec<hayride_host_traits::db::db::DBValue>,
+        params: Vec<DBValue>,
     ) -> std::result::Result<Rows, ErrorCode> {
-        let connection_guard = self.connection.lock().map_err(|_| ErrorCode::QueryFailed)?;
-        match connection_guard.as_ref() {
-            Some(conn) => {
-                let mut stmt = conn
-                    .prepare(&self.query)
-                    .map_err(|_| ErrorCode::QueryFailed)?;
-
-                // Convert DBValues to rusqlite parameters
-                let sqlite_params: Vec<rusqlite::types::Value> =
-                    params.iter().map(dbvalue_to_sqlite_value).collect();
-
-                let rows = stmt
-                    .query_map(params_from_iter(sqlite_params.iter()), |row| {
-                        // Convert SQLite row to DBValue row
-                        sqlite_row_to_dbvalue_row(row)
-                    })
-                    .map_err(|_| ErrorCode::QueryFailed)?;
-
-                // Collect all rows (SQLite doesn't support streaming)
-                let mut collected_rows = Vec::new();
-                for row_result in rows {
-                    match row_result {
-                        Ok(row) => collected_rows.push(row),
-                        Err(e) => {
-                            log::warn!("Error reading SQLite row: {}", e);
-                            return Err(ErrorCode::QueryFailed);
-                        }
-                    }
-                }
+        // Writes run on the connection, so its rows are collected instead of streamed
+        let connection = self.connection.clone();
+        let statement = self.query.clone();
+        blocking(ErrorCode::QueryFailed, move || {
+            query(&connection, None, &statement, params)
+        })
+        .await
+    }
 
-                // Get column names
-                let column_names: Vec<String> =
-                    stmt.column_names().iter().map(|s| s.to_string()).collect();
+    fn number_parameters(&self) -> Result<u32, ErrorCode> {
+        Ok(self.parameters)
+    }
 
-                let sqlite_rows = SQLiteRows::new(collected_rows, column_names);
-                let boxed_rows: Box<dyn DBRows> = Box::new(sqlite_rows);
-                Ok(boxed_rows.into())
+    async fn close(&mut self) -> std::result::Result<(), ErrorCode> {
+        log::debug!("SQLiteStatement closed (no-op)");
+        Ok(())
+    }
+}
+
+/// A transaction on a connection, statements of the connection run in it until it ends.
+///
+/// SQLite transactions are serializable, stricter isolation levels take the write lock
+/// when the transaction begins instead of on its first write. A transaction dropped
+/// before it ends is rolled back.
+struct SQLiteTransaction {
+    connection: SharedConnection,
+    read_only: bool,
+    done: Arc<AtomicBool>,
+}
+
+impl SQLiteTransaction {
+    fn begin(
+        connection: SharedConnection,
+        isolation_level: IsolationLevel,
+        read_only: bool,
+    ) -> Result<Self, ErrorCode> {
+        let begin = match (&isolation_level, read_only) {
+            (_, true)
+            | (IsolationLevel::ReadUncommitted, _)
+            | (IsolationLevel::ReadCommitted, _)
+            | (IsolationLevel::WriteCommitted, _) => "BEGIN DEFERRED",
+            _ => "BEGIN IMMEDIATE",
+        };
+
+        {
+            let connection_guard = connection
+                .lock()
+                .map_err(|_| ErrorCode::BeginTransactionFailed)?;
+            let conn = connection_guard
+                .as_ref()
+                .ok_or(ErrorCode::BeginTransactionFailed)?;
+            if !conn.is_autocommit() {
+                log::warn!("SQLite transactions can not be nested");
+                return Err(ErrorCode::BeginTransactionFailed);
+            }
+
+            let begun = conn.execute_batch(begin).and_then(|_| match read_only {
+                true => conn.execute_batch("PRAGMA query_only = ON"),
+                false => Ok(()),
+            });
+            if let Err(e) = begun {
+                log::warn!("Error beginning SQLite transaction: {}", e);
+                let _ = conn.execute_batch("ROLLBACK");
+                return Err(ErrorCode::BeginTransactionFailed);
             }
-            None => Err(ErrorCode::QueryFailed),
         }
+
+        Ok(Self {
+            connection,
+            read_only,
+            done: Arc::new(AtomicBool::new(false)),
+        })
     }
 
-    fn execute(
+    // End the transaction with COMMIT or ROLLBACK on a blocking thread
+    async fn end_blocking(
         &self,
-        params: Vec<hayride_host_traits::db::db::DBValue>,
-    ) -> std::result::Result<u64, ErrorCode> {
-        let connection_guard = self
-            .connection
-            .lock()
-            .map_err(|_| ErrorCode::ExecuteFailed)?;
-        match connection_guard.as_ref() {
-            Some(conn) => {
-                let mut stmt = conn
-                    .prepare(&self.query)
-                    .map_err(|_| ErrorCode::ExecuteFailed)?;
-
-                // Convert DBValues to rusqlite parameters
-                let sqlite_params: Vec<rusqlite::types::Value> =
-                    params.iter().map(dbvalue_to_sqlite_value).collect();
-
-                let result = stmt
-                    .execute(params_from_iter(sqlite_params.iter()))
-                    .map_err(|_| ErrorCode::ExecuteFailed)?;
-                Ok(result as u64)
-            }
-            None => Err(ErrorCode::ExecuteFailed),
-        }
+        statement: &'static str,
+        code: ErrorCode,
+    ) -> Result<(), ErrorCode> {
+        let connection = self.connection.clone();
+        let done = self.done.clone();
+        let read_only = self.read_only;
+        blocking(code.clone(), move || {
+            end(&connection, read_only, &done, statement, code)
+        })
+        .await
     }
 
-    fn number_parameters(&self) -> Result<u32, ErrorCode> {
-        let connection_guard = self
-            .connection
-            .lock()
-            .map_err(|_| ErrorCode::PrepareFailed)?;
-        match connection_guard.as_ref() {
-            Some(conn) => {
-                let stmt = conn
-                    .prepare(&self.query)
-                    .map_err(|_| ErrorCode::PrepareFailed)?;
-                Ok(stmt.parameter_count() as u32)
-            }
-            None => Err(ErrorCode::PrepareFailed),
+    fn check_open(&self, code: ErrorCode) -> Result<(), ErrorCode> {
+        match self.done.load(Ordering::SeqCst) {
+            true => Err(code),
+            false => Ok(()),
         }
     }
+}
 
-    fn close(&mut self) -> std::result::Result<(), ErrorCode> {
-        log::debug!("SQLiteStatement closed (no-op)");
-        Ok(())
+// End a transaction with COMMIT or ROLLBACK
+fn end(
+    connection: &SharedConnection,
+    read_only: bool,
+    done: &AtomicBool,
+    statement: &str,
+    code: ErrorCode,
+) -> Result<(), ErrorCode> {
+    if done.load(Ordering::SeqCst) {
+        return Err(code);
+    }
+    let connection_guard = connection.lock().map_err(|_| ErrorCode::Unknown)?;
+    let Some(conn) = connection_guard.as_ref() else {
+        return Err(code);
+    };
+
+    if read_only {
+        let _ = conn.execute_batch("PRAGMA query_only = OFF");
+    }
+    conn.execute_batch(statement).map_err(|e| {
+        log::warn!("Error ending SQLite transaction with {}: {}", statement, e);
+        code
+    })?;
+    done.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl DBTransactionAsync for SQLiteTransaction {
+    async fn commit(&mut self) -> Result<(), ErrorCode> {
+        self.end_blocking("COMMIT", ErrorCode::CommitFailed).await
+    }
+
+    async fn rollback(&mut self) -> Result<(), ErrorCode> {
+        self.end_blocking("ROLLBACK", ErrorCode::RollbackFailed)
+            .await
+    }
+
+    async fn query(&self, query_str: String, params: Vec<DBValue>) -> Result<Rows, ErrorCode> {
+        self.check_open(ErrorCode::QueryFailed)?;
+        let connection = self.connection.clone();
+        blocking(ErrorCode::QueryFailed, move || {
+            query(&connection, None, &query_str, params)
+        })
+        .await
+    }
+
+    async fn execute(&self, query: String, params: Vec<DBValue>) -> Result<u64, ErrorCode> {
+        self.check_open(ErrorCode::ExecuteFailed)?;
+        let connection = self.connection.clone();
+        blocking(ErrorCode::ExecuteFailed, move || {
+            execute(&connection, &query, params)
+        })
+        .await
+    }
+
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
+        self.check_open(ErrorCode::PrepareFailed)?;
+        let connection = self.connection.clone();
+        blocking(ErrorCode::PrepareFailed, move || {
+            prepare(&connection, None, query)
+        })
+        .await
+    }
+}
+
+impl Drop for SQLiteTransaction {
+    fn drop(&mut self) {
+        if !self.done.load(Ordering::SeqCst) {
+            let _ = end(
+                &self.connection,
+                self.read_only,
+                &self.done,
+                "ROLLBACK",
+                ErrorCode::RollbackFailed,
+            );
+        }
     }
 }
 
@@ -190,12 +644,13 @@ impl SQLiteRows {
     }
 }
 
-impl DBRows for SQLiteRows {
+#[async_trait::async_trait]
+impl DBRowsAsync for SQLiteRows {
     fn columns(&self) -> Vec<String> {
         self.columns.clone()
     }
 
-    fn next(&mut self) -> Result<hayride_host_traits::db::db::Row, ErrorCode> {
+    async fn next(&mut self) -> Result<hayride_host_traits::db::db::Row, ErrorCode> {
         if self.current_index >= self.rows.len() {
             return Err(ErrorCode::EndOfRows);
         }
@@ -205,16 +660,148 @@ impl DBRows for SQLiteRows {
         Ok(row)
     }
 
-    fn close(&mut self) -> Result<(), ErrorCode> {
+    async fn close(&mut self) -> Result<(), ErrorCode> {
         log::debug!("SQLiteRows closed");
         Ok(())
     }
 }
 
-fn dbvalue_to_sqlite_value(
-    dbvalue: &hayride_host_traits::db::db::DBValue,
-) -> rusqlite::types::Value {
-    use hayride_host_traits::db::db::DBValue;
+/// Read only connections to the database of a connection, each cursor takes one for as
+/// long as it reads its rows.
+struct Readers {
+    options: SqliteOptions,
+    idle: Mutex<Vec<SqliteConnection>>,
+}
+
+impl Readers {
+    fn take(&self) -> rusqlite::Result<SqliteConnection> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        match idle {
+            Some(connection) => Ok(connection),
+            None => self.options.open_reader(),
+        }
+    }
+
+    fn put(&self, connection: SqliteConnection) {
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < MAX_IDLE_READERS {
+                idle.push(connection);
+            }
+        }
+    }
+}
+
+enum CursorMessage {
+    Columns(Vec<String>),
+    Row(hayride_host_traits::db::db::Row),
+    Failed,
+}
+
+/// Rows of a query stepped on a thread holding a read connection, a few rows ahead of
+/// the component. Closing the rows stops the thread and hands its connection back.
+struct SQLiteCursor {
+    columns: Vec<String>,
+    receiver: Option<Receiver<CursorMessage>>,
+}
+
+impl SQLiteCursor {
+    fn open(
+        readers: Arc<Readers>,
+        query: String,
+        params: Vec<rusqlite::types::Value>,
+    ) -> Result<Self, ErrorCode> {
+        let (sender, mut receiver) = mpsc::channel(CURSOR_BUFFER);
+        std::thread::Builder::new()
+            .name("sqlite-cursor".to_string())
+            .spawn(move || {
+                let connection = match readers.take() {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        log::warn!("Error opening SQLite read connection: {}", e);
+                        let _ = sender.blocking_send(CursorMessage::Failed);
+                        return;
+                    }
+                };
+                if let Err(e) = step_cursor(&connection, &query, &params, &sender) {
+                    log::warn!("Error reading SQLite row: {}", e);
+                    let _ = sender.blocking_send(CursorMessage::Failed);
+                }
+                readers.put(connection);
+            })
+            .map_err(|e| {
+                log::warn!("Error starting SQLite cursor: {}", e);
+                ErrorCode::QueryFailed
+            })?;
+
+        // Cursors are opened on blocking threads
+        match receiver.blocking_recv() {
+            Some(CursorMessage::Columns(columns)) => Ok(Self {
+                columns,
+                receiver: Some(receiver),
+            }),
+            _ => Err(ErrorCode::QueryFailed),
+        }
+    }
+}
+
+// Step the rows of the query until they end or the cursor is closed
+fn step_cursor(
+    connection: &SqliteConnection,
+    query: &str,
+    params: &[rusqlite::types::Value],
+    sender: &Sender<CursorMessage>,
+) -> rusqlite::Result<()> {
+    let mut stmt = connection.prepare_cached(query)?;
+    let columns = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    if sender
+        .blocking_send(CursorMessage::Columns(columns))
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    let mut rows = stmt.query(params_from_iter(params.iter()))?;
+    while let Some(row) = rows.next()? {
+        let row = sqlite_row_to_dbvalue_row(row)?;
+        if sender.blocking_send(CursorMessage::Row(row)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl DBRowsAsync for SQLiteCursor {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    async fn next(&mut self) -> Result<hayride_host_traits::db::db::Row, ErrorCode> {
+        let Some(rx) = self.receiver.as_mut() else {
+            return Err(ErrorCode::EndOfRows);
+        };
+        match rx.recv().await {
+            Some(CursorMessage::Row(row)) => Ok(row),
+            Some(CursorMessage::Failed) => {
+                self.receiver = None;
+                Err(ErrorCode::NextFailed)
+            }
+            Some(CursorMessage::Columns(_)) | None => {
+                self.receiver = None;
+                Err(ErrorCode::EndOfRows)
+            }
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        // Dropping the receiver stops the thread at its next row
+        self.receiver = None;
+        log::debug!("SQLiteCursor closed");
+        Ok(())
+    }
+}
+
+fn dbvalue_to_sqlite_value(dbvalue: &DBValue) -> rusqlite::types::Value {
     use rusqlite::types::Value;
 
     match dbvalue {
@@ -237,8 +824,6 @@ fn dbvalue_to_sqlite_value(
 fn sqlite_row_to_dbvalue_row(
     row: &rusqlite::Row,
 ) -> Result<hayride_host_traits::db::db::Row, rusqlite::Error> {
-    use hayride_host_traits::db::db::DBValue;
-
     let mut values = Vec::new();
 
     for i in 0..row.as_ref().column_count() {
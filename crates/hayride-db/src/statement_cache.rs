@@ -0,0 +1,95 @@
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Statements kept prepared per connection unless configured otherwise.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+static STATS: OnceLock<StatementCacheStats> = OnceLock::new();
+
+/// Returns the capacity and hit counts shared by the statement caches of the process.
+pub fn statement_cache() -> &'static StatementCacheStats {
+    STATS.get_or_init(|| StatementCacheStats {
+        capacity: AtomicUsize::new(DEFAULT_CAPACITY),
+        hits: AtomicU64::new(0),
+        misses: AtomicU64::new(0),
+    })
+}
+
+/// Default capacity of the statement caches of new connections, and how often their
+/// queries were found prepared.
+pub struct StatementCacheStats {
+    capacity: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StatementCacheStats {
+    /// Set the capacity of connections opened afterwards, 0 disables caching.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Statements prepared on a connection keyed by their query, the least recently used
+/// is dropped once the cache is full.
+#[cfg_attr(not(any(feature = "sqlite", feature = "postgres")), allow(dead_code))]
+pub(crate) struct StatementCache<S> {
+    // Nothing is cached with a capacity of 0
+    entries: Option<LruCache<String, S>>,
+}
+
+#[cfg_attr(not(any(feature = "sqlite", feature = "postgres")), allow(dead_code))]
+impl<S: Clone> StatementCache<S> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+        }
+    }
+
+    /// Returns the statement of the query if it is cached, counting the hit or miss.
+    pub(crate) fn get(&mut self, query: &str) -> Option<S> {
+        let statement = self
+            .entries
+            .as_mut()
+            .and_then(|entries| entries.get(query).cloned());
+        let stats = statement_cache();
+        match statement {
+            Some(_) => stats.hits.fetch_add(1, Ordering::Relaxed),
+            None => stats.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        statement
+    }
+
+    pub(crate) fn insert(&mut self, query: String, statement: S) {
+        if let Some(entries) = self.entries.as_mut() {
+            entries.put(query, statement);
+        }
+    }
+
+    /// Drop the statement of the query, e.g. once it failed and may be stale.
+    pub(crate) fn remove(&mut self, query: &str) {
+        if let Some(entries) = self.entries.as_mut() {
+            entries.pop(query);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        if let Some(entries) = self.entries.as_mut() {
+            entries.clear();
+        }
+    }
+}
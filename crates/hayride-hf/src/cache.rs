@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Partial downloads younger than this may still be in progress and are kept
+const STALE_PARTIAL: Duration = Duration::from_secs(10 * 60);
+
+// Prefix hf-hub gives the directory of a model repo, `models--<owner>--<name>`
+const MODEL_PREFIX: &str = "models--";
+// Revision hf-hub resolves cached files from
+const DEFAULT_REF: &str = "main";
+
+// Extensions hf-hub uses next to blobs for download locks and partial downloads
+const LOCK_EXTENSION: &str = "lock";
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Remove a model file from a repo in the cache and clean up what it leaves behind.
+///
+/// Returns the number of bytes freed.
+pub fn remove_model(repo_dir: &Path, pointer: &Path) -> std::io::Result<u64> {
+    // Pointers are symlinks into the blobs dir, except on platforms where
+    // hf-hub falls back to renaming the blob into the snapshot
+    let is_link = std::fs::symlink_metadata(pointer)?.file_type().is_symlink();
+    let mut freed = 0;
+    if !is_link {
+        freed += file_size(pointer);
+    }
+    std::fs::remove_file(pointer)?;
+
+    Ok(freed + clean_repo(repo_dir, false)?)
+}
+
+/// Remove unreferenced blobs, stale lock files and empty directories from a repo in the cache.
+///
+/// Partial downloads are only removed when `partials` is set and they have not been
+/// written to recently. Returns the number of bytes freed.
+pub fn clean_repo(repo_dir: &Path, partials: bool) -> std::io::Result<u64> {
+    let snapshots = repo_dir.join("snapshots");
+    let blobs = repo_dir.join("blobs");
+    let referenced = referenced_blobs(&snapshots);
+    let mut freed = 0;
+
+    if let Ok(entries) = std::fs::read_dir(&blobs) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            let remove = match path.extension().and_then(|e| e.to_str()) {
+                // Locks are left behind by hf-hub once the download completes
+                Some(LOCK_EXTENSION) => !path.with_extension(PARTIAL_EXTENSION).exists(),
+                Some(PARTIAL_EXTENSION) => partials && is_stale(&path),
+                _ => !referenced.contains(&canonical(&path)),
+            };
+
+            if remove {
+                let size = file_size(&path);
+                match std::fs::remove_file(&path) {
+                    Ok(()) => {
+                        log::debug!("removed cached file: {}", path.display());
+                        freed += size;
+                    }
+                    Err(e) => log::warn!("failed to remove {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+
+    remove_empty_dirs(&snapshots);
+
+    // Without snapshots the refs point to nothing, drop the repo once it is empty
+    if !snapshots.exists() {
+        let _ = std::fs::remove_dir_all(repo_dir.join("refs"));
+        let _ = std::fs::remove_dir(&blobs);
+        let _ = std::fs::remove_dir(repo_dir);
+    }
+
+    Ok(freed)
+}
+
+/// Clean every repo in the cache, returning the number of bytes freed.
+pub fn purge(cache: &Path) -> std::io::Result<u64> {
+    let mut freed = 0;
+    for entry in std::fs::read_dir(cache)?.flatten() {
+        let path = entry.path();
+        let is_repo = path.is_dir()
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("models--"))
+                .unwrap_or(false);
+        if is_repo {
+            freed += clean_repo(&path, true)?;
+        }
+    }
+
+    Ok(freed)
+}
+
+/// List the files cached for every repo as `(repo id, file)`, sorted by name.
+///
+/// Only the snapshot of the main ref is listed, it is the one files are looked up in.
+/// Files in subdirectories of the snapshot keep their relative path, e.g. `sub/model.gguf`.
+pub fn list(cache: &Path) -> std::io::Result<Vec<(String, String)>> {
+    let mut models = Vec::new();
+    for entry in std::fs::read_dir(cache)?.flatten() {
+        let path = entry.path();
+        let Some(repo) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(MODEL_PREFIX))
+            .map(|n| n.replace("--", "/"))
+        else {
+            continue;
+        };
+        let Ok(commit) = std::fs::read_to_string(path.join("refs").join(DEFAULT_REF)) else {
+            continue;
+        };
+
+        let snapshot = path.join("snapshots").join(commit.trim());
+        let mut stack = vec![snapshot.clone()];
+        while let Some(dir) = stack.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if is_dir {
+                    stack.push(path);
+                // Follows the pointer, skipping those whose blob was removed
+                } else if path.is_file() {
+                    let Some(file) = path.strip_prefix(&snapshot).ok().and_then(|f| f.to_str())
+                    else {
+                        continue;
+                    };
+                    models.push((repo.clone(), file.replace('\\', "/")));
+                }
+            }
+        }
+    }
+
+    models.sort();
+    Ok(models)
+}
+
+// Collect the blobs pointed to by any snapshot of the repo
+fn referenced_blobs(snapshots: &Path) -> HashSet<PathBuf> {
+    let mut referenced = HashSet::new();
+    let mut stack = vec![snapshots.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if is_dir {
+                    stack.push(path);
+                } else {
+                    referenced.insert(canonical(&path));
+                }
+            }
+        }
+    }
+    referenced
+}
+
+// Remove empty directories bottom up, including the root itself
+fn remove_empty_dirs(dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+
+    let mut empty = true;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if !is_dir || !remove_empty_dirs(&path) {
+            empty = false;
+        }
+    }
+
+    empty && std::fs::remove_dir(dir).is_ok()
+}
+
+fn is_stale(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map(|age| age > STALE_PARTIAL)
+        .unwrap_or(false)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use hayride_host_traits::ai::model::ModelInfo;
+
+// File in the cache dir the index is kept in
+const INDEX_FILE: &str = "index.json";
+
+// Serializes the read-modify-write of the index between repositories of the process
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Metadata of the models downloaded into a cache, keyed by model name.
+pub struct ModelIndex {
+    path: PathBuf,
+}
+
+impl ModelIndex {
+    pub fn new(cache: &Path) -> Self {
+        Self {
+            path: cache.join(INDEX_FILE),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<ModelInfo> {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        self.load().remove(name)
+    }
+
+    pub fn insert(&self, info: ModelInfo) {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut models = self.load();
+        models.insert(info.name.clone(), info);
+        self.store(&models);
+    }
+
+    pub fn remove(&self, name: &str) {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut models = self.load();
+        if models.remove(name).is_some() {
+            self.store(&models);
+        }
+    }
+
+    // A missing or unreadable index is treated as empty, it is rebuilt from the files
+    fn load(&self) -> BTreeMap<String, ModelInfo> {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn store(&self, models: &BTreeMap<String, ModelInfo>) {
+        let result = serde_json::to_vec_pretty(models)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&self.path, json));
+        if let Err(e) = result {
+            log::warn!(
+                "failed to write the model index {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
@@ -1,18 +1,135 @@
-use std::path::PathBuf;
+mod cache;
+mod index;
+mod progress;
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 
 use hf_hub::api::sync::ApiBuilder;
 
-use hayride_host_traits::ai::model::{ErrorCode, ModelRepositoryInner};
+use hayride_host_traits::ai::model::{
+    DownloadEvent, DownloadObserver, ErrorCode, ModelFilter, ModelInfo, ModelRepositoryInner,
+    RemoteFile, RemoteRepository,
+};
+use ring::digest::{Context, SHA256};
+
+// Environment variables used to configure the repository
+const HF_ENDPOINT: &str = "HF_ENDPOINT";
+const HF_HUB_OFFLINE: &str = "HF_HUB_OFFLINE";
+const HF_TOKEN: &str = "HF_TOKEN";
+const HAYRIDE_HF_OFFLINE: &str = "HAYRIDE_HF_OFFLINE";
+const HAYRIDE_HF_RETRIES: &str = "HAYRIDE_HF_RETRIES";
+const HAYRIDE_HF_BACKOFF_MS: &str = "HAYRIDE_HF_BACKOFF_MS";
+
+// Upper bound for the delay between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+// Repositories returned by a search
+const SEARCH_LIMIT: usize = 50;
+
+// Token from the runtime configuration, used over `HF_TOKEN`
+static TOKEN: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the hub token of repositories created afterwards, e.g. from the runtime configuration.
+pub fn set_token(token: Option<String>) {
+    if let Ok(mut configured) = TOKEN.write() {
+        *configured = token.filter(|token| !token.trim().is_empty());
+    }
+}
+
+/// Options for the Hugging Face model repository.
+#[derive(Clone, Debug)]
+pub struct HuggingFaceOptions {
+    /// Mirror or endpoint to download models from, defaults to https://huggingface.co
+    pub endpoint: Option<String>,
+    /// Only serve files that are already in the cache
+    pub offline: bool,
+    /// Number of times a failed download is retried
+    pub retries: usize,
+    /// Delay before the first retry, doubled on every following attempt
+    pub backoff: Duration,
+    /// Token for gated and private repositories, the token saved by `huggingface-cli login`
+    /// is used without one
+    pub token: Option<String>,
+}
+
+impl Default for HuggingFaceOptions {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            offline: false,
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            token: None,
+        }
+    }
+}
+
+impl HuggingFaceOptions {
+    /// Read the options from the environment.
+    ///
+    /// `HF_ENDPOINT` overrides the endpoint, `HF_HUB_OFFLINE` or `HAYRIDE_HF_OFFLINE`
+    /// enable offline mode, `HAYRIDE_HF_RETRIES` and `HAYRIDE_HF_BACKOFF_MS` configure
+    /// the retry behavior. The token set with [`set_token`] is used over `HF_TOKEN`.
+    pub fn from_env() -> Self {
+        let mut options = Self::default();
+
+        if let Ok(endpoint) = std::env::var(HF_ENDPOINT) {
+            let endpoint = endpoint.trim().trim_end_matches('/');
+            if !endpoint.is_empty() {
+                options.endpoint = Some(endpoint.to_string());
+            }
+        }
+
+        options.offline = env_flag(HF_HUB_OFFLINE) || env_flag(HAYRIDE_HF_OFFLINE);
+
+        if let Some(retries) = env_parse::<usize>(HAYRIDE_HF_RETRIES) {
+            options.retries = retries;
+        }
+        if let Some(backoff) = env_parse::<u64>(HAYRIDE_HF_BACKOFF_MS) {
+            options.backoff = Duration::from_millis(backoff);
+        }
+
+        options.token = TOKEN
+            .read()
+            .ok()
+            .and_then(|token| token.clone())
+            .or_else(|| std::env::var(HF_TOKEN).ok())
+            .filter(|token| !token.trim().is_empty());
+
+        options
+    }
+
+    // Delay before the given retry attempt, starting at 0
+    fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1u32 << attempt.min(16);
+        self.backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
 
 pub struct HuggingFaceModelRepository {
     api: hf_hub::api::sync::Api,
+    // Client for the hub api calls hf_hub has no support for
+    hub: ureq::Agent,
+    token: Option<String>,
     cache: PathBuf,
+    // Metadata of the models downloaded into the cache
+    index: index::ModelIndex,
+    options: HuggingFaceOptions,
+    observer: Option<Arc<dyn DownloadObserver>>,
 }
 
 impl HuggingFaceModelRepository {
     pub fn new() -> Result<Self> {
+        Self::with_options(HuggingFaceOptions::from_env())
+    }
+
+    pub fn with_options(options: HuggingFaceOptions) -> Result<Self> {
         let hayride_dir = hayride_utils::paths::hayride::default_hayride_dir()?;
         let custom_cache = hayride_dir.join("ai/hf_hub");
 
@@ -20,16 +137,134 @@ impl HuggingFaceModelRepository {
         std::fs::create_dir_all(&custom_cache)?;
 
         // Build the API with the custom cache directory
-        let api = ApiBuilder::new()
+        let mut builder = ApiBuilder::new()
             .with_cache_dir(custom_cache.clone())
             .with_progress(false) // Disable progress bar
-            .build()?;
+            .with_retries(options.retries);
+        if let Some(endpoint) = &options.endpoint {
+            log::debug!("using hugging face endpoint: {}", endpoint);
+            builder = builder.with_endpoint(endpoint.clone());
+        }
+        if options.token.is_some() {
+            builder = builder.with_token(options.token.clone());
+        }
+        let api = builder.build()?;
+
+        // Fall back to the token saved by `huggingface-cli login`, as hf_hub does
+        let token = options
+            .token
+            .clone()
+            .or_else(|| hf_hub::Cache::default().token());
 
         Ok(HuggingFaceModelRepository {
             api: api,
+            hub: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            token,
+            index: index::ModelIndex::new(&custom_cache),
             cache: custom_cache,
+            options,
+            observer: None,
         })
     }
+
+    /// Report the lifecycle of model downloads to the observer.
+    pub fn with_observer(mut self, observer: Arc<dyn DownloadObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    fn emit(&self, event: DownloadEvent) {
+        if let Some(observer) = &self.observer {
+            observer.event(event);
+        }
+    }
+
+    // Get a json document from the hub api, e.g. `models?search=llama`
+    fn hub_get(&self, path: &str, query: &[(&str, &str)]) -> Result<serde_json::Value, ErrorCode> {
+        if self.options.offline {
+            log::debug!("offline mode, not calling the hub: {}", path);
+            return Err(ErrorCode::NotEnabled);
+        }
+
+        let endpoint = self.options.endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT);
+        let mut request = self.hub.get(&format!("{}/api/{}", endpoint, path));
+        for (param, value) in query {
+            request = request.query(param, value);
+        }
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+
+        match request.call() {
+            Ok(response) => response.into_json().map_err(|e| {
+                log::error!("Failed to read hub response for '{}': {}", path, e);
+                ErrorCode::RuntimeError
+            }),
+            Err(ureq::Error::Status(404, _)) => Err(ErrorCode::ModelNotFound),
+            // Gated and private repositories answer without a valid token as if missing
+            Err(ureq::Error::Status(status @ (401 | 403), _)) => {
+                log::warn!("hub refused '{}' with {}, check the token", path, status);
+                Err(ErrorCode::ModelNotFound)
+            }
+            Err(e) => {
+                log::error!("Failed to call hub for '{}': {}", path, e);
+                Err(ErrorCode::RuntimeError)
+            }
+        }
+    }
+
+    // Compare a downloaded file with the sha256 the hub publishes for files stored with
+    // git lfs, returning the verified digest. Other files have no digest to check.
+    fn verify(&self, repo: &str, file: &str, path: &Path) -> Result<Option<String>, ErrorCode> {
+        let json = match self.hub_get(&format!("models/{}", repo), &[("blobs", "true")]) {
+            Ok(json) => json,
+            Err(code) => {
+                log::warn!(
+                    "no digest for '{}/{}' from the hub, the download is not verified: {}",
+                    repo,
+                    file,
+                    code
+                );
+                return Ok(None);
+            }
+        };
+        let expected = json
+            .get("siblings")
+            .and_then(|siblings| siblings.as_array())
+            .and_then(|siblings| {
+                siblings
+                    .iter()
+                    .find(|sibling| sibling.get("rfilename").and_then(|n| n.as_str()) == Some(file))
+            })
+            .and_then(|sibling| sibling.get("lfs")?.get("sha256")?.as_str())
+            .map(|sha256| sha256.to_lowercase());
+        let Some(expected) = expected else {
+            log::debug!(
+                "'{}/{}' is not stored with lfs, nothing to verify",
+                repo,
+                file
+            );
+            return Ok(None);
+        };
+
+        let actual = sha256_file(path).map_err(|e| {
+            log::error!("Failed to hash model file {:?}: {}", path, e);
+            ErrorCode::RuntimeError
+        })?;
+        if actual != expected {
+            log::error!(
+                "sha256 of '{}/{}' is {}, the hub published {}",
+                repo,
+                file,
+                actual,
+                expected
+            );
+            return Err(ErrorCode::ChecksumMismatch);
+        }
+        Ok(Some(actual))
+    }
 }
 
 impl ModelRepositoryInner for HuggingFaceModelRepository {
@@ -39,13 +274,99 @@ impl ModelRepositoryInner for HuggingFaceModelRepository {
         // Parse the model file from the repo id
         let (model_id, model_file) = parse_model_name(&name)?;
 
-        let model = self.api.model(model_id);
-        let path = model.get(model_file).map_err(|err| {
-            log::error!("Failed to get model file '{}': {}", model_file, err);
-            ErrorCode::RuntimeError
-        })?;
+        // Offline mode only serves files that are already cached
+        if self.options.offline {
+            log::debug!("offline mode, looking up cached model: {}", name);
+            return self.get(name);
+        }
 
-        Ok(path.to_string_lossy().to_string())
+        // Nothing to download when the file is already cached
+        if let Ok(path) = self.get(name.clone()) {
+            return Ok(path);
+        }
+
+        self.emit(DownloadEvent::Started { name: name.clone() });
+
+        let model = self.api.model(model_id.clone());
+        let mut attempt = 0;
+        let path = loop {
+            let result = match &self.observer {
+                Some(observer) => model.download_with_progress(
+                    model_file,
+                    progress::ObserverProgress::new(name.clone(), observer.clone()),
+                ),
+                None => model.download(model_file),
+            };
+            match result {
+                Ok(path) => break path,
+                Err(err) if attempt < self.options.retries => {
+                    let delay = self.options.delay(attempt);
+                    log::warn!(
+                        "Failed to get model file '{}', retrying in {:?}: {}",
+                        model_file,
+                        delay,
+                        err
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(err) => {
+                    log::error!("Failed to get model file '{}': {}", model_file, err);
+                    self.emit(DownloadEvent::Failed {
+                        name,
+                        error: err.to_string(),
+                    });
+                    return Err(ErrorCode::RuntimeError);
+                }
+            }
+        };
+
+        // Make sure the downloaded file landed in the cache
+        let size = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > 0 => metadata.len(),
+            _ => {
+                log::error!("Downloaded model file is missing or empty: {:?}", path);
+                self.emit(DownloadEvent::Failed {
+                    name,
+                    error: "downloaded file is missing or empty".to_string(),
+                });
+                return Err(ErrorCode::RuntimeError);
+            }
+        };
+
+        let sha256 = match self.verify(&model_id, model_file, &path) {
+            Ok(sha256) => sha256,
+            Err(code) => {
+                // Never leave a file that failed verification in the cache
+                let repo = hf_hub::Repo::new(model_id, hf_hub::RepoType::Model);
+                if let Err(e) = cache::remove_model(&self.cache.join(repo.folder_name()), &path) {
+                    log::error!("Failed to remove unverified model file {:?}: {}", path, e);
+                }
+                self.emit(DownloadEvent::Failed {
+                    name,
+                    error: code.to_string(),
+                });
+                return Err(code);
+            }
+        };
+
+        match ModelInfo::from_file(name.clone(), &path) {
+            Ok(info) => self.index.insert(ModelInfo {
+                sha256,
+                downloaded_at: now(),
+                ..info
+            }),
+            Err(e) => log::warn!("Failed to read the metadata of {:?}: {}", path, e),
+        }
+
+        let path = path.to_string_lossy().to_string();
+        self.emit(DownloadEvent::Verified {
+            name,
+            path: path.clone(),
+            size,
+        });
+
+        Ok(path)
     }
 
     fn get(&self, name: String) -> Result<String, ErrorCode> {
@@ -67,40 +388,138 @@ impl ModelRepositoryInner for HuggingFaceModelRepository {
         let (model_id, model_file) = parse_model_name(&name)?;
 
         let repo = hf_hub::Repo::new(model_id, hf_hub::RepoType::Model);
+        let repo_dir = self.cache.join(repo.folder_name());
         let cache = hf_hub::Cache::new(self.cache.clone());
 
-        if let Some(path) = cache.repo(repo).get(model_file) {
-            // Remove the file from the cache
-            std::fs::remove_file(path).map_err(|_| ErrorCode::RuntimeError)?;
-            return Ok(());
-        }
+        let Some(path) = cache.repo(repo).get(model_file) else {
+            return Err(ErrorCode::ModelNotFound);
+        };
 
-        return Err(ErrorCode::ModelNotFound);
+        // Remove the file and any blobs, locks and directories only it used
+        let freed = cache::remove_model(&repo_dir, &path).map_err(|e| {
+            log::error!("Failed to delete model '{}': {}", name, e);
+            ErrorCode::RuntimeError
+        })?;
+        log::debug!("deleted model {}, freed {} bytes", name, freed);
+        self.index.remove(&name);
+
+        Ok(())
     }
 
-    fn list(&self) -> std::result::Result<Vec<String>, ErrorCode> {
-        // List all models in the cache directory
-        let mut models = Vec::new();
-
-        // Recursively find all model files in the cache directory and its subdirectories
-        let mut stack = vec![self.cache.clone()];
-        while let Some(dir) = stack.pop() {
-            if let Ok(entries) = std::fs::read_dir(&dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        stack.push(path);
-                    } else if let Some(name) = path.to_str() {
-                        if name.ends_with(".gguf") {
-                            models.push(name.to_string());
-                        }
-                    }
-                }
-            }
+    fn info(&self, name: String) -> std::result::Result<ModelInfo, ErrorCode> {
+        let path = self.get(name.clone())?;
+        // Models cached before the index was kept are read from their file
+        if let Some(info) = self.index.get(&name).filter(|info| info.path == path) {
+            return Ok(info);
         }
+        ModelInfo::from_file(name, Path::new(&path)).map_err(|e| {
+            log::error!("Failed to read the metadata of {}: {}", path, e);
+            ErrorCode::RuntimeError
+        })
+    }
+
+    fn purge(&mut self) -> std::result::Result<u64, ErrorCode> {
+        let freed = cache::purge(&self.cache).map_err(|e| {
+            log::error!("Failed to purge model cache: {}", e);
+            ErrorCode::RuntimeError
+        })?;
+        log::debug!("purged model cache, freed {} bytes", freed);
+
+        Ok(freed)
+    }
+
+    fn list(&self, filter: &ModelFilter) -> std::result::Result<Vec<String>, ErrorCode> {
+        let cached = cache::list(&self.cache).map_err(|e| {
+            log::error!("Failed to list model cache: {}", e);
+            ErrorCode::RuntimeError
+        })?;
+
+        let extension = filter
+            .extension
+            .as_deref()
+            .map(|extension| extension.trim_start_matches('.'));
+        let models = cached
+            .into_iter()
+            .filter(|(repo, _)| filter.repo.as_ref().is_none_or(|r| r == repo))
+            .filter(|(_, file)| {
+                extension.is_none_or(|extension| {
+                    std::path::Path::new(file)
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+                })
+            })
+            .map(|(repo, file)| format!("{}/{}", repo, file))
+            .collect();
 
         Ok(models)
     }
+
+    fn search(&self, query: String) -> std::result::Result<Vec<RemoteRepository>, ErrorCode> {
+        let limit = SEARCH_LIMIT.to_string();
+        let json = self.hub_get(
+            "models",
+            &[
+                ("search", query.trim()),
+                ("filter", "gguf"),
+                ("sort", "downloads"),
+                ("direction", "-1"),
+                ("limit", &limit),
+            ],
+        )?;
+
+        let repositories = json
+            .as_array()
+            .ok_or(ErrorCode::RuntimeError)?
+            .iter()
+            .filter_map(|repo| {
+                Some(RemoteRepository {
+                    id: repo.get("id")?.as_str()?.to_string(),
+                    downloads: repo.get("downloads").and_then(|v| v.as_u64()).unwrap_or(0),
+                    likes: repo.get("likes").and_then(|v| v.as_u64()).unwrap_or(0),
+                })
+            })
+            .collect();
+        Ok(repositories)
+    }
+
+    fn list_remote(&self, repo: String) -> std::result::Result<Vec<RemoteFile>, ErrorCode> {
+        let repo = repo.trim().trim_matches('/');
+        if !is_repo_id(repo) {
+            return Err(ErrorCode::InvalidModelName);
+        }
+        // Blobs add the size of every file
+        let json = self.hub_get(&format!("models/{}", repo), &[("blobs", "true")])?;
+
+        let files = json
+            .get("siblings")
+            .and_then(|siblings| siblings.as_array())
+            .ok_or(ErrorCode::RuntimeError)?
+            .iter()
+            .filter_map(|file| {
+                let name = file.get("rfilename")?.as_str()?;
+                Some(RemoteFile {
+                    name: format!("{}/{}", repo, name),
+                    size: file.get("size").and_then(|v| v.as_u64()),
+                })
+            })
+            .collect();
+        Ok(files)
+    }
+}
+
+// Repository ids are `<owner>/<name>` or a bare name for canonical models
+fn is_repo_id(repo: &str) -> bool {
+    let parts: Vec<&str> = repo.split('/').collect();
+    parts.len() <= 2
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && *part != "."
+                && *part != ".."
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
 }
 
 fn parse_model_name(name: &str) -> Result<(String, &str), ErrorCode> {
@@ -121,3 +540,47 @@ fn parse_model_name(name: &str) -> Result<(String, &str), ErrorCode> {
 
     Ok((model_id, model_file))
 }
+
+// Hex sha256 digest of a file, read in chunks so large models are not held in memory
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut context = Context::new(&SHA256);
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buf[..n]);
+    }
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn env_flag(key: &str) -> bool {
+    std::env::var(key)
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    let value = std::env::var(key).ok()?;
+    match value.trim().parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            log::warn!("ignoring invalid value for {}: {}", key, value);
+            None
+        }
+    }
+}
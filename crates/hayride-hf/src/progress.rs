@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use hayride_host_traits::ai::model::{DownloadEvent, DownloadObserver};
+
+// Report progress at most once per this many percent of the file
+const PROGRESS_STEP: u64 = 5;
+
+/// Forwards hf-hub download progress to a download observer.
+#[derive(Clone)]
+pub struct ObserverProgress {
+    name: String,
+    observer: Arc<dyn DownloadObserver>,
+    downloaded: u64,
+    total: u64,
+    reported: u64,
+}
+
+impl ObserverProgress {
+    pub fn new(name: String, observer: Arc<dyn DownloadObserver>) -> Self {
+        Self {
+            name,
+            observer,
+            downloaded: 0,
+            total: 0,
+            reported: 0,
+        }
+    }
+
+    fn report(&mut self) {
+        self.reported = self.downloaded;
+        self.observer.event(DownloadEvent::Progress {
+            name: self.name.clone(),
+            downloaded: self.downloaded,
+            total: self.total,
+        });
+    }
+}
+
+impl hf_hub::api::Progress for ObserverProgress {
+    fn init(&mut self, size: usize, _filename: &str) {
+        // Called again when a failed download resumes
+        self.total = size as u64;
+        self.downloaded = 0;
+        self.report();
+    }
+
+    fn update(&mut self, size: usize) {
+        self.downloaded += size as u64;
+        let step = (self.total * PROGRESS_STEP / 100).max(1);
+        if self.downloaded.saturating_sub(self.reported) >= step {
+            self.report();
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.reported != self.downloaded {
+            self.report();
+        }
+    }
+}
@@ -1,9 +1,12 @@
 pub mod context;
+pub mod eval;
+pub mod memory;
 pub mod model;
 pub mod nn;
 pub mod rag;
 
 pub use nn::{
-    BackendError, BackendExecutionContext, BackendGraph, BackendInner, Error, ErrorCode,
-    ExecutionContext, FutureResult, Graph, Tensor, TensorStream, TensorType,
+    AbortHandle, BackendError, BackendExecutionContext, BackendExecutionContextAsync, BackendGraph,
+    BackendInner, Error, ErrorCode, ExecutionContext, FutureResult, Graph, RepetitionDetector,
+    Tensor, TensorStream, TensorType,
 };
@@ -1,5 +1,3 @@
-pub mod context;
 pub mod errors;
 
-pub use context::Context;
 pub use errors::{Error, ErrorCode};
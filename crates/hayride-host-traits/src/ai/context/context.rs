@@ -1,3 +0,0 @@
-// backend defined Context to represent the resource, but not currently used
-// This is a fallback for when a component does not implement the Context import.
-pub struct Context {}
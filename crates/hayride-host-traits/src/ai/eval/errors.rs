@@ -0,0 +1,29 @@
+use std::fmt;
+
+/// Host side evaluation error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidDataset,
+    LoadFailed,
+    NotEnabled,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::InvalidDataset => "InvalidDataset",
+            ErrorCode::LoadFailed => "LoadFailed",
+            ErrorCode::NotEnabled => "NotEnabled",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
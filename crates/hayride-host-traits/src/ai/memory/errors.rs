@@ -0,0 +1,31 @@
+use std::fmt;
+
+/// Host side memory error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    ConnectionFailed,
+    EmbedFailed,
+    QueryFailed,
+    NotEnabled,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::ConnectionFailed => "ConnectionFailed",
+            ErrorCode::EmbedFailed => "EmbedFailed",
+            ErrorCode::QueryFailed => "QueryFailed",
+            ErrorCode::NotEnabled => "NotEnabled",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
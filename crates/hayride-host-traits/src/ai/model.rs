@@ -1,6 +1,9 @@
 pub mod errors;
+pub mod events;
+pub mod gguf;
 pub mod mock;
 pub mod model;
 
 pub use errors::{Error, ErrorCode};
-pub use model::ModelRepositoryInner;
+pub use events::{DownloadEvent, DownloadObserver};
+pub use model::{ModelFilter, ModelInfo, ModelRepositoryInner, RemoteFile, RemoteRepository};
@@ -13,6 +13,8 @@ pub enum ErrorCode {
     InvalidModelName,
     RuntimeError,
     NotEnabled,
+    /// The downloaded file does not match the digest the hub published
+    ChecksumMismatch,
     Unknown,
 }
 
@@ -24,6 +26,7 @@ impl fmt::Display for ErrorCode {
             ErrorCode::InvalidModelName => "InvalidModelName",
             ErrorCode::RuntimeError => "RuntimeError",
             ErrorCode::NotEnabled => "NotEnabled",
+            ErrorCode::ChecksumMismatch => "ChecksumMismatch",
             ErrorCode::Unknown => "Unknown",
         };
         write!(f, "{}", description)
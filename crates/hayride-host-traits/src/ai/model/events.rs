@@ -0,0 +1,29 @@
+use serde::Serialize;
+
+/// Lifecycle events of a model download.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum DownloadEvent {
+    Started {
+        name: String,
+    },
+    Progress {
+        name: String,
+        downloaded: u64,
+        total: u64,
+    },
+    Verified {
+        name: String,
+        path: String,
+        size: u64,
+    },
+    Failed {
+        name: String,
+        error: String,
+    },
+}
+
+/// Observes the downloads of a model repository.
+pub trait DownloadObserver: Send + Sync {
+    fn event(&self, event: DownloadEvent);
+}
@@ -0,0 +1,198 @@
+//! Metadata of gguf model files, read from the key-value section of their header.
+//!
+//! Only the header is read, tensors are never loaded. Arrays such as the vocab of the
+//! tokenizer are skipped without being kept in memory.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+// Strings longer than this are treated as a corrupt header
+const MAX_STRING: u64 = 1 << 24;
+
+// Types of the values in the key-value section
+const TYPE_UINT8: u32 = 0;
+const TYPE_INT8: u32 = 1;
+const TYPE_UINT16: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_UINT32: u32 = 4;
+const TYPE_INT32: u32 = 5;
+const TYPE_FLOAT32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_UINT64: u32 = 10;
+const TYPE_INT64: u32 = 11;
+const TYPE_FLOAT64: u32 = 12;
+
+/// Metadata from the header of a gguf file, unset fields are missing from the header.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GgufMetadata {
+    /// `general.architecture`, e.g. `qwen3`
+    pub architecture: Option<String>,
+    /// `general.name`
+    pub name: Option<String>,
+    /// `<architecture>.context_length`, the context the model was trained with
+    pub context_length: Option<u64>,
+    /// The quantization of `general.file_type`, e.g. `Q4_K_M`
+    pub quantization: Option<String>,
+}
+
+enum Value {
+    Integer(u64),
+    Text(String),
+    Other,
+}
+
+/// Read the metadata of the gguf file at the path.
+pub fn read(path: &Path) -> io::Result<GgufMetadata> {
+    read_from(&mut BufReader::new(File::open(path)?))
+}
+
+/// Read gguf metadata from the start of a gguf file.
+pub fn read_from(reader: &mut impl Read) -> io::Result<GgufMetadata> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a gguf file"));
+    }
+    // Version 1 counted with 32 bit integers and is no longer written
+    let version = read_u32(reader)?;
+    if version < 2 {
+        return Err(invalid("unsupported gguf version"));
+    }
+    let _tensors = read_u64(reader)?;
+    let entries = read_u64(reader)?;
+
+    let mut metadata = GgufMetadata::default();
+    let mut context_lengths: Vec<(String, u64)> = vec![];
+    for _ in 0..entries {
+        let key = read_string(reader)?;
+        let ty = read_u32(reader)?;
+        let value = read_value(reader, ty)?;
+        match (key.as_str(), value) {
+            ("general.architecture", Value::Text(architecture)) => {
+                metadata.architecture = Some(architecture)
+            }
+            ("general.name", Value::Text(name)) => metadata.name = Some(name),
+            ("general.file_type", Value::Integer(file_type)) => {
+                metadata.quantization = file_type_name(file_type).map(str::to_string)
+            }
+            (key, Value::Integer(length)) if key.ends_with(".context_length") => {
+                context_lengths.push((key.to_string(), length))
+            }
+            _ => {}
+        }
+    }
+
+    // The architecture may come after its keys
+    metadata.context_length = context_lengths
+        .iter()
+        .find(|(key, _)| {
+            metadata
+                .architecture
+                .as_ref()
+                .is_some_and(|architecture| *key == format!("{}.context_length", architecture))
+        })
+        .or(context_lengths.first())
+        .map(|(_, length)| *length);
+    Ok(metadata)
+}
+
+fn read_value(reader: &mut impl Read, ty: u32) -> io::Result<Value> {
+    let value = match ty {
+        TYPE_UINT8 | TYPE_INT8 | TYPE_BOOL => Value::Integer(read_bytes::<1>(reader)?[0] as u64),
+        TYPE_UINT16 | TYPE_INT16 => Value::Integer(u16::from_le_bytes(read_bytes(reader)?) as u64),
+        TYPE_UINT32 | TYPE_INT32 => Value::Integer(read_u32(reader)? as u64),
+        TYPE_UINT64 | TYPE_INT64 => Value::Integer(read_u64(reader)?),
+        TYPE_FLOAT32 => {
+            read_bytes::<4>(reader)?;
+            Value::Other
+        }
+        TYPE_FLOAT64 => {
+            read_bytes::<8>(reader)?;
+            Value::Other
+        }
+        TYPE_STRING => Value::Text(read_string(reader)?),
+        TYPE_ARRAY => {
+            let item_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            for _ in 0..len {
+                read_value(reader, item_type)?;
+            }
+            Value::Other
+        }
+        _ => return Err(invalid("unknown gguf value type")),
+    };
+    Ok(value)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_STRING {
+        return Err(invalid("gguf string too long"));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Names of the `llama_ftype` values llama.cpp writes to `general.file_type`
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        38 => "MXFP4_MOE",
+        _ => return None,
+    };
+    Some(name)
+}
@@ -1,5 +1,5 @@
 use super::errors::ErrorCode;
-use super::model::ModelRepositoryInner;
+use super::model::{ModelFilter, ModelRepositoryInner, RemoteFile, RemoteRepository};
 
 #[derive(Default)]
 pub struct MockModelRepositoryInner {}
@@ -17,7 +17,19 @@ impl ModelRepositoryInner for MockModelRepositoryInner {
         return Err(ErrorCode::NotEnabled);
     }
 
-    fn list(&self) -> Result<Vec<String>, ErrorCode> {
+    fn list(&self, _filter: &ModelFilter) -> Result<Vec<String>, ErrorCode> {
         return Err(ErrorCode::NotEnabled);
     }
+
+    fn purge(&mut self) -> Result<u64, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+
+    fn search(&self, _query: String) -> Result<Vec<RemoteRepository>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+
+    fn list_remote(&self, _repo: String) -> Result<Vec<RemoteFile>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
 }
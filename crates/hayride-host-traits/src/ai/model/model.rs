@@ -1,8 +1,116 @@
 use super::errors::ErrorCode;
+use super::gguf;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A model repository on the hub.
+#[derive(Clone, Debug)]
+pub struct RemoteRepository {
+    pub id: String,
+    pub downloads: u64,
+    pub likes: u64,
+}
+
+/// A file of a model repository on the hub.
+#[derive(Clone, Debug)]
+pub struct RemoteFile {
+    /// Name the file is downloaded by, `<repo>/<file>`
+    pub name: String,
+    pub size: Option<u64>,
+}
+
+/// Narrows down the cached models that are listed, unset fields match any model.
+#[derive(Clone, Debug, Default)]
+pub struct ModelFilter {
+    /// File extension without the leading dot, e.g. `gguf`
+    pub extension: Option<String>,
+    /// Repository id, e.g. `unsloth/Qwen3-0.6B-GGUF`
+    pub repo: Option<String>,
+}
+
+/// Metadata of a downloaded model.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub name: String,
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex sha256 digest the download was verified against, if the hub published one
+    pub sha256: Option<String>,
+    /// Architecture from the gguf header, e.g. `qwen3`
+    pub architecture: Option<String>,
+    /// Quantization from the gguf header, e.g. `Q4_K_M`
+    pub quantization: Option<String>,
+    /// Context length the model was trained with, from the gguf header
+    pub context_length: Option<u64>,
+    /// Epoch seconds the model was downloaded, the modification time of the file if the
+    /// repository did not record it
+    pub downloaded_at: u64,
+}
+
+impl ModelInfo {
+    /// Read the metadata of a model file, from its gguf header if it has one.
+    pub fn from_file(name: String, path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let is_gguf = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gguf"));
+        // Ollama keeps gguf files as blobs without an extension
+        let header = match gguf::read(path) {
+            Ok(header) => header,
+            Err(e) => {
+                if is_gguf {
+                    return Err(e);
+                }
+                gguf::GgufMetadata::default()
+            }
+        };
+
+        Ok(Self {
+            name,
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            sha256: None,
+            architecture: header.architecture,
+            quantization: header.quantization,
+            context_length: header.context_length,
+            downloaded_at: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs())
+                .unwrap_or_default(),
+        })
+    }
+}
 
 pub trait ModelRepositoryInner: Send + Sync {
     fn download(&mut self, name: String) -> Result<String, ErrorCode>;
     fn get(&self, name: String) -> Result<String, ErrorCode>;
     fn delete(&mut self, name: String) -> Result<(), ErrorCode>;
-    fn list(&self) -> Result<Vec<String>, ErrorCode>;
+    /// List the cached models matching the filter as `<owner>/<repo>/<file>`.
+    fn list(&self, filter: &ModelFilter) -> Result<Vec<String>, ErrorCode>;
+    /// Remove unreferenced and partial files from the cache, returning the bytes freed.
+    fn purge(&mut self) -> Result<u64, ErrorCode>;
+    /// Search the hub for repositories matching the query.
+    fn search(&self, query: String) -> Result<Vec<RemoteRepository>, ErrorCode>;
+    /// List the files of a repository on the hub.
+    fn list_remote(&self, repo: String) -> Result<Vec<RemoteFile>, ErrorCode>;
+
+    /// Metadata of a downloaded model, read from the model file unless the repository
+    /// keeps an index of its downloads.
+    fn info(&self, name: String) -> Result<ModelInfo, ErrorCode> {
+        let path = self.get(name.clone())?;
+        ModelInfo::from_file(name, Path::new(&path)).map_err(|_| ErrorCode::RuntimeError)
+    }
+
+    /// List the cached models matching the filter with their metadata.
+    fn list_info(&self, filter: &ModelFilter) -> Result<Vec<ModelInfo>, ErrorCode> {
+        self.list(filter)?
+            .into_iter()
+            .map(|name| self.info(name))
+            .collect()
+    }
 }
@@ -1,10 +1,16 @@
 pub mod errors;
+pub mod filter;
 pub mod mock;
 pub mod nn;
+pub mod postprocess;
+pub mod repetition;
 
 pub use nn::{
-    BackendExecutionContext, BackendGraph, BackendInner, ExecutionContext, FutureResult, Graph,
-    Tensor, TensorStream, TensorType,
+    AbortHandle, BackendExecutionContext, BackendExecutionContextAsync, BackendGraph, BackendInner,
+    ExecutionContext, FutureResult, Graph, Tensor, TensorStream, TensorType,
 };
 
 pub use errors::{BackendError, Error, ErrorCode};
+pub use filter::{ChunkFilter, FilterPattern, OutputFilter, StreamFilter};
+pub use postprocess::{PostProcessor, PostStep, StreamPostProcessor};
+pub use repetition::RepetitionDetector;
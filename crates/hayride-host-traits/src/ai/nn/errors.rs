@@ -37,6 +37,15 @@ pub enum BackendError {
     FailedContextTooLarge,
     FailedResultNotSet,
     FailedToWriteOutput,
+    FailedInvalidJson,
+    /// An option of the computation is invalid, e.g. a logit bias of a token the model
+    /// does not have
+    InvalidArgument,
+    Aborted,
+    Repetition,
+    Unsupported,
+    /// No backend loads models of the encoding
+    UnsupportedEncoding,
     Unknown,
 }
 
@@ -52,6 +61,12 @@ impl fmt::Display for BackendError {
             BackendError::FailedContextTooLarge => "FailedContextTooLarge",
             BackendError::FailedResultNotSet => "FailedResultNotSet",
             BackendError::FailedToWriteOutput => "FailedToWriteOutput",
+            BackendError::FailedInvalidJson => "FailedInvalidJson",
+            BackendError::InvalidArgument => "InvalidArgument",
+            BackendError::Aborted => "Aborted",
+            BackendError::Repetition => "Repetition",
+            BackendError::Unsupported => "Unsupported",
+            BackendError::UnsupportedEncoding => "UnsupportedEncoding",
             BackendError::Unknown => "Unknown",
         };
         write!(f, "{}", description)
@@ -60,3 +75,22 @@ impl fmt::Display for BackendError {
 
 // Implement std::error::Error for BackendError
 impl std::error::Error for BackendError {}
+
+/// The `wasi-nn` error code closest to a backend error, so components written against
+/// `wasi-nn` can tell bad input apart from failures of the backend.
+impl From<&BackendError> for ErrorCode {
+    fn from(error: &BackendError) -> Self {
+        match error {
+            BackendError::FailedTokenization
+            | BackendError::FailedDecoding
+            | BackendError::FailedTensorNotSet
+            | BackendError::InvalidArgument => ErrorCode::InvalidArgument,
+            BackendError::FailedInvalidJson | BackendError::UnsupportedEncoding => {
+                ErrorCode::InvalidEncoding
+            }
+            BackendError::FailedContextTooLarge => ErrorCode::TooLarge,
+            BackendError::Unsupported => ErrorCode::UnsupportedOperation,
+            _ => ErrorCode::RuntimeError,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use bytes::Bytes;
+use regex::bytes::Regex;
+use std::sync::Arc;
+
+/// Replacement written in place of masked output.
+pub const MASK: &str = "[REDACTED]";
+
+// Bytes held back from a stream so patterns split across chunks are still masked
+const DEFAULT_WINDOW: usize = 64;
+
+/// A pattern masked in inference output.
+#[derive(Clone, Debug)]
+pub enum FilterPattern {
+    /// A case insensitive literal, i.e. a word from a profanity list
+    Literal(String),
+    /// A regular expression, i.e. the format of an api key
+    Regex(String),
+}
+
+/// Masks configured patterns in inference output.
+#[derive(Debug)]
+pub struct OutputFilter {
+    pattern: Regex,
+    window: usize,
+}
+
+impl OutputFilter {
+    pub fn new(patterns: Vec<FilterPattern>) -> Result<Self, regex::Error> {
+        let mut window = DEFAULT_WINDOW;
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(|pattern| match pattern {
+                FilterPattern::Literal(literal) => {
+                    window = window.max(literal.len());
+                    format!("(?i:{})", regex::escape(&literal))
+                }
+                FilterPattern::Regex(regex) => format!("(?:{})", regex),
+            })
+            .collect();
+
+        Ok(Self {
+            pattern: Regex::new(&patterns.join("|"))?,
+            window,
+        })
+    }
+
+    /// Load patterns from a file with one pattern per line, lines starting with
+    /// `re:` are regular expressions and `#` starts a comment.
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let patterns = std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_prefix("re:") {
+                Some(regex) => FilterPattern::Regex(regex.to_string()),
+                None => FilterPattern::Literal(line.to_string()),
+            })
+            .collect();
+
+        Ok(Self::new(patterns)?)
+    }
+
+    /// Set the number of bytes held back from streams, regex matches longer than
+    /// this may be split across chunks and only partially masked.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Mask all patterns in complete output.
+    pub fn mask(&self, output: &[u8]) -> Vec<u8> {
+        self.pattern
+            .replace_all(output, MASK.as_bytes())
+            .into_owned()
+    }
+
+    /// Start filtering a stream of output.
+    pub fn stream(self: &Arc<Self>) -> StreamFilter {
+        StreamFilter {
+            filter: self.clone(),
+            pending: vec![],
+        }
+    }
+}
+
+/// Transforms output arriving in chunks, holding back what later chunks may change.
+pub trait ChunkFilter: Send + 'static {
+    /// Add a chunk of output, returning the output that is safe to release.
+    fn push(&mut self, chunk: &[u8]) -> Bytes;
+
+    /// Release the output held back once the stream ends.
+    fn finish(&mut self) -> Bytes;
+}
+
+/// Masks patterns in output arriving in chunks.
+pub struct StreamFilter {
+    filter: Arc<OutputFilter>,
+    pending: Vec<u8>,
+}
+
+impl StreamFilter {
+    /// Add a chunk of output, returning the output that is safe to release.
+    pub fn push(&mut self, chunk: &[u8]) -> Bytes {
+        self.pending.extend_from_slice(chunk);
+
+        // Matches reaching into the window may continue in the next chunk
+        let mut release = self.pending.len().saturating_sub(self.filter.window);
+        for found in self.filter.pattern.find_iter(&self.pending) {
+            if found.end() > release {
+                release = release.min(found.start());
+                break;
+            }
+        }
+
+        let rest = self.pending.split_off(release);
+        let released = std::mem::replace(&mut self.pending, rest);
+        self.filter.mask(&released).into()
+    }
+
+    /// Release the output held back once the stream ends.
+    pub fn finish(&mut self) -> Bytes {
+        let pending = std::mem::take(&mut self.pending);
+        self.filter.mask(&pending).into()
+    }
+}
+
+impl ChunkFilter for StreamFilter {
+    fn push(&mut self, chunk: &[u8]) -> Bytes {
+        StreamFilter::push(self, chunk)
+    }
+
+    fn finish(&mut self) -> Bytes {
+        StreamFilter::finish(self)
+    }
+}
@@ -20,6 +20,20 @@ impl BackendGraph for MockGraph {
         let context: Box<dyn BackendExecutionContext> = Box::new(MockExecutionContext {});
         return Ok(context.into());
     }
+
+    // Every byte is a token. `tokenize_count` counts these tokens too, so counts are in
+    // bytes, where they were in words before the mock could tokenize
+    fn tokenize(&self, text: &str) -> Result<Vec<u32>, BackendError> {
+        Ok(text.bytes().map(u32::from).collect())
+    }
+
+    fn detokenize(&self, tokens: &[u32]) -> Result<String, BackendError> {
+        let bytes = tokens
+            .iter()
+            .map(|token| u8::try_from(*token).map_err(|_| BackendError::FailedDecoding))
+            .collect::<Result<Vec<u8>, BackendError>>()?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
 }
 
 struct MockExecutionContext {}
@@ -43,4 +57,8 @@ impl BackendExecutionContext for MockExecutionContext {
 
         Ok(tensor)
     }
+
+    fn embed(&mut self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, BackendError> {
+        Ok(inputs.iter().map(|_| vec![0.0, 0.5, 1.0]).collect())
+    }
 }
@@ -1,8 +1,10 @@
 use super::errors::BackendError;
+use super::filter::ChunkFilter;
 use anyhow::anyhow;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use wasmtime_wasi::p2::StreamError;
 
@@ -12,6 +14,24 @@ pub trait BackendInner: Send + Sync {
 
 pub trait BackendGraph: Send + Sync {
     fn init_execution_context(&self) -> Result<ExecutionContext, BackendError>;
+    /// Split text into the token ids of the vocab of the model, without the begin of
+    /// text token.
+    fn tokenize(&self, _text: &str) -> Result<Vec<u32>, BackendError> {
+        Err(BackendError::Unsupported)
+    }
+    /// The text of token ids, the inverse of `tokenize`.
+    fn detokenize(&self, _tokens: &[u32]) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported)
+    }
+    /// Count the tokens the vocab of the model splits text into, without the begin of
+    /// text token.
+    fn tokenize_count(&self, text: &str) -> Result<usize, BackendError> {
+        self.tokenize(text).map(|tokens| tokens.len())
+    }
+    /// Tokens in the context window the model was trained with, if the backend knows.
+    fn context_size(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub trait BackendExecutionContext: Send {
@@ -22,17 +42,101 @@ pub trait BackendExecutionContext: Send {
         &mut self,
         tensors: Vec<(String, Tensor)>,
     ) -> Result<TensorStream, BackendError>;
+    /// Embed each input into a vector, for backends of embedding models.
+    fn embed(&mut self, _inputs: Vec<String>) -> Result<Vec<Vec<f32>>, BackendError> {
+        Err(BackendError::Unsupported)
+    }
+}
+
+impl<T: BackendExecutionContext + ?Sized> BackendExecutionContext for Box<T> {
+    fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError> {
+        <T as BackendExecutionContext>::compute(&mut **self, tensors)
+    }
+
+    fn compute_stream(
+        &mut self,
+        tensors: Vec<(String, Tensor)>,
+    ) -> Result<TensorStream, BackendError> {
+        <T as BackendExecutionContext>::compute_stream(&mut **self, tensors)
+    }
+
+    fn embed(&mut self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, BackendError> {
+        <T as BackendExecutionContext>::embed(&mut **self, inputs)
+    }
+}
+
+/// An async execution context used by async hosts.
+///
+/// Sync backends are run on blocking threads once converted into an
+/// [`ExecutionContext`]. Backends that are async internally should implement
+/// this directly instead of blocking on their own runtime.
+#[async_trait::async_trait]
+pub trait BackendExecutionContextAsync: Send {
+    async fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError>;
+    async fn compute_stream(
+        &mut self,
+        tensors: Vec<(String, Tensor)>,
+    ) -> Result<TensorStream, BackendError>;
+    async fn embed(&mut self, _inputs: Vec<String>) -> Result<Vec<Vec<f32>>, BackendError> {
+        Err(BackendError::Unsupported)
+    }
+}
+
+/// Runs a sync execution context on blocking threads of the runtime.
+///
+/// A call whose future is dropped keeps the context locked until the backend returns.
+struct BlockingExecutionContext(Arc<Mutex<Box<dyn BackendExecutionContext>>>);
+
+impl BlockingExecutionContext {
+    async fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut dyn BackendExecutionContext) -> Result<R, BackendError> + Send + 'static,
+    ) -> Result<R, BackendError> {
+        let context = self.0.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut context = context.lock().unwrap_or_else(|e| e.into_inner());
+            f(context.as_mut())
+        })
+        .await
+        .map_err(|_| BackendError::Unknown)?
+    }
+}
+
+#[async_trait::async_trait]
+impl BackendExecutionContextAsync for BlockingExecutionContext {
+    async fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError> {
+        self.run(move |context| context.compute(tensors)).await
+    }
+
+    async fn compute_stream(
+        &mut self,
+        tensors: Vec<(String, Tensor)>,
+    ) -> Result<TensorStream, BackendError> {
+        self.run(move |context| context.compute_stream(tensors))
+            .await
+    }
+
+    async fn embed(&mut self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, BackendError> {
+        self.run(move |context| context.embed(inputs)).await
+    }
 }
 
 /// A backend-defined execution context.
-pub struct ExecutionContext(Box<dyn BackendExecutionContext>);
+pub struct ExecutionContext(Box<dyn BackendExecutionContextAsync>);
 impl From<Box<dyn BackendExecutionContext>> for ExecutionContext {
     fn from(value: Box<dyn BackendExecutionContext>) -> Self {
+        Self(Box::new(BlockingExecutionContext(Arc::new(Mutex::new(
+            value,
+        )))))
+    }
+}
+impl From<Box<dyn BackendExecutionContextAsync>> for ExecutionContext {
+    fn from(value: Box<dyn BackendExecutionContextAsync>) -> Self {
         Self(value)
     }
 }
 impl std::ops::Deref for ExecutionContext {
-    type Target = dyn BackendExecutionContext;
+    type Target = dyn BackendExecutionContextAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -77,16 +181,56 @@ pub struct PromptOptions {
     seed: u32,
 }
 
+/// A handle to a background compute task.
+///
+/// Backends check [`AbortHandle::is_aborted`] while generating and stop early once
+/// the host no longer wants the output, marking the handle finished when they return.
+#[derive(Clone, Default)]
+pub struct AbortHandle(Arc<AbortState>);
+
+#[derive(Default)]
+struct AbortState {
+    aborted: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl AbortHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn abort(&self) {
+        self.0.aborted.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.0.aborted.load(Ordering::Relaxed)
+    }
+
+    pub fn finish(&self) {
+        self.0.finished.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.finished.load(Ordering::Relaxed)
+    }
+}
+
 /// A host-side tensor-stream.
 pub struct TensorStream {
     pub dimensions: Vec<u32>,
     pub ty: TensorType,
 
+    // Task producing the stream, aborted when the stream is dropped
+    abort: Option<AbortHandle>,
+
     // Based on wasmtime AsyncReadStream
     closed: bool,
     buffer: Option<Result<Bytes, StreamError>>,
     receiver: mpsc::Receiver<Result<Bytes, StreamError>>,
     _join_handle: Option<wasmtime_wasi::runtime::AbortOnDropJoinHandle<()>>,
+    // Tasks passing the stream through filters, in the order they were added
+    _filter_handles: Vec<wasmtime_wasi::runtime::AbortOnDropJoinHandle<()>>,
 }
 
 impl TensorStream {
@@ -118,11 +262,64 @@ impl TensorStream {
         Self {
             dimensions,
             ty,
+            abort: None,
 
             closed: false,
             buffer: None,
             receiver,
             _join_handle: Some(join_handle),
+            _filter_handles: vec![],
+        }
+    }
+}
+
+impl TensorStream {
+    /// Tie the task producing the stream to the lifetime of the stream.
+    pub fn with_abort(mut self, abort: AbortHandle) -> Self {
+        self.abort = Some(abort);
+        self
+    }
+
+    pub fn abort_handle(&self) -> Option<AbortHandle> {
+        self.abort.clone()
+    }
+
+    /// Pass the stream through a filter before it is read, after the filters added before.
+    pub fn with_filter(mut self, mut filter: impl ChunkFilter) -> Self {
+        let (sender, receiver) = mpsc::channel(1);
+        let mut upstream = std::mem::replace(&mut self.receiver, receiver);
+        let join_handle = wasmtime_wasi::runtime::spawn(async move {
+            while let Some(result) = upstream.recv().await {
+                let result = match result {
+                    Ok(bytes) => Ok(filter.push(&bytes)),
+                    Err(StreamError::Closed) => {
+                        // Release what the filter held back before closing
+                        let rest = filter.finish();
+                        if !rest.is_empty() && sender.send(Ok(rest)).await.is_err() {
+                            break;
+                        }
+                        Err(StreamError::Closed)
+                    }
+                    Err(e) => Err(e),
+                };
+                if matches!(result, Ok(ref bytes) if bytes.is_empty()) {
+                    continue;
+                }
+                if sender.send(result).await.is_err() {
+                    // no more receiver - stop filtering
+                    break;
+                }
+            }
+        });
+        self._filter_handles.push(join_handle);
+        self
+    }
+}
+
+impl Drop for TensorStream {
+    fn drop(&mut self) {
+        if let Some(abort) = &self.abort {
+            abort.abort();
         }
     }
 }
@@ -0,0 +1,335 @@
+use super::filter::ChunkFilter;
+use bytes::Bytes;
+use regex::bytes::Regex;
+use serde::Deserialize;
+use std::sync::Arc;
+
+// Bytes held back from a stream so replacements split across chunks still match
+const DEFAULT_WINDOW: usize = 64;
+
+/// A step of post-processing applied to inference output.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PostStep {
+    /// Trim whitespace off the start and end of the output
+    Trim,
+    /// Cut the output at the first stop sequence, dropping it and anything after it
+    StripStop { sequences: Vec<String> },
+    /// Drop reasoning blocks, i.e. `<think>...</think>`, a block left open drops the rest
+    /// of the output
+    StripThink {
+        #[serde(default = "default_think_open")]
+        open: String,
+        #[serde(default = "default_think_close")]
+        close: String,
+    },
+    /// Replace matches of a regular expression, `$1` in the replacement expands to groups
+    Replace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+}
+
+fn default_think_open() -> String {
+    "<think>".to_string()
+}
+
+fn default_think_close() -> String {
+    "</think>".to_string()
+}
+
+/// A chain of post-processing steps applied in order to inference output.
+///
+/// Complete output is processed as a stream of a single chunk, so streamed and complete
+/// output end up the same, except for replacements of matches longer than the window.
+#[derive(Debug)]
+pub struct PostProcessor {
+    steps: Vec<Compiled>,
+}
+
+#[derive(Debug)]
+enum Compiled {
+    Trim,
+    StripStop(Vec<Vec<u8>>),
+    StripThink(Vec<u8>, Vec<u8>),
+    Replace(Arc<Regex>, Vec<u8>),
+}
+
+impl PostProcessor {
+    pub fn new(steps: Vec<PostStep>) -> Result<Self, regex::Error> {
+        let steps = steps
+            .into_iter()
+            .map(|step| {
+                Ok(match step {
+                    PostStep::Trim => Compiled::Trim,
+                    PostStep::StripStop { sequences } => Compiled::StripStop(
+                        sequences
+                            .into_iter()
+                            .filter(|sequence| !sequence.is_empty())
+                            .map(String::into_bytes)
+                            .collect(),
+                    ),
+                    PostStep::StripThink { open, close } => {
+                        Compiled::StripThink(open.into_bytes(), close.into_bytes())
+                    }
+                    PostStep::Replace {
+                        pattern,
+                        replacement,
+                    } => {
+                        Compiled::Replace(Arc::new(Regex::new(&pattern)?), replacement.into_bytes())
+                    }
+                })
+            })
+            .collect::<Result<_, regex::Error>>()?;
+        Ok(Self { steps })
+    }
+
+    /// Returns true if the output is left as it is.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Process complete output.
+    pub fn apply(self: &Arc<Self>, output: &[u8]) -> Vec<u8> {
+        let mut stream = self.stream();
+        let mut processed = stream.push(output).to_vec();
+        processed.extend_from_slice(&stream.finish());
+        processed
+    }
+
+    /// Start processing a stream of output.
+    pub fn stream(self: &Arc<Self>) -> StreamPostProcessor {
+        StreamPostProcessor {
+            steps: self.steps.iter().map(Compiled::start).collect(),
+        }
+    }
+}
+
+impl Compiled {
+    fn start(&self) -> Box<dyn Step> {
+        match self {
+            Compiled::Trim => Box::new(Trim::default()),
+            Compiled::StripStop(sequences) => Box::new(StripStop {
+                sequences: sequences.clone(),
+                pending: vec![],
+                stopped: false,
+            }),
+            Compiled::StripThink(open, close) => Box::new(StripThink {
+                open: open.clone(),
+                close: close.clone(),
+                pending: vec![],
+                inside: false,
+            }),
+            Compiled::Replace(pattern, replacement) => Box::new(Replace {
+                pattern: pattern.clone(),
+                replacement: replacement.clone(),
+                pending: vec![],
+            }),
+        }
+    }
+}
+
+/// Post-processes output arriving in chunks.
+pub struct StreamPostProcessor {
+    steps: Vec<Box<dyn Step>>,
+}
+
+impl ChunkFilter for StreamPostProcessor {
+    fn push(&mut self, chunk: &[u8]) -> Bytes {
+        let mut data = chunk.to_vec();
+        for step in self.steps.iter_mut() {
+            data = step.push(&data);
+        }
+        data.into()
+    }
+
+    fn finish(&mut self) -> Bytes {
+        // What a step held back still goes through the steps after it
+        let mut output = vec![];
+        for i in 0..self.steps.len() {
+            let mut data = self.steps[i].finish();
+            for step in self.steps[i + 1..].iter_mut() {
+                data = step.push(&data);
+            }
+            output.extend(data);
+        }
+        output.into()
+    }
+}
+
+trait Step: Send {
+    fn push(&mut self, chunk: &[u8]) -> Vec<u8>;
+    fn finish(&mut self) -> Vec<u8>;
+}
+
+#[derive(Default)]
+struct Trim {
+    started: bool,
+    // Whitespace held back until more output follows it
+    pending: Vec<u8>,
+}
+
+impl Step for Trim {
+    fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut chunk = chunk;
+        if !self.started {
+            match chunk.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(start) => {
+                    self.started = true;
+                    chunk = &chunk[start..];
+                }
+                None => return vec![],
+            }
+        }
+
+        match chunk.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(end) => {
+                let mut output = std::mem::take(&mut self.pending);
+                output.extend_from_slice(&chunk[..=end]);
+                self.pending.extend_from_slice(&chunk[end + 1..]);
+                output
+            }
+            None => {
+                self.pending.extend_from_slice(chunk);
+                vec![]
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        self.pending.clear();
+        vec![]
+    }
+}
+
+struct StripStop {
+    sequences: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+    stopped: bool,
+}
+
+impl Step for StripStop {
+    fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        if self.stopped {
+            return vec![];
+        }
+        self.pending.extend_from_slice(chunk);
+
+        let found = self
+            .sequences
+            .iter()
+            .filter_map(|sequence| find(&self.pending, sequence))
+            .min();
+        if let Some(at) = found {
+            self.stopped = true;
+            self.pending.truncate(at);
+            return std::mem::take(&mut self.pending);
+        }
+
+        // A stop sequence may start in the bytes held back
+        let longest = self.sequences.iter().map(Vec::len).max().unwrap_or(1);
+        let release = self.pending.len().saturating_sub(longest - 1);
+        let rest = self.pending.split_off(release);
+        std::mem::replace(&mut self.pending, rest)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+struct StripThink {
+    open: Vec<u8>,
+    close: Vec<u8>,
+    pending: Vec<u8>,
+    inside: bool,
+}
+
+impl Step for StripThink {
+    fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut output = vec![];
+        loop {
+            let tag = match self.inside {
+                true => &self.close,
+                false => &self.open,
+            };
+            match find(&self.pending, tag) {
+                Some(at) => {
+                    if !self.inside {
+                        output.extend_from_slice(&self.pending[..at]);
+                    }
+                    self.pending.drain(..at + tag.len());
+                    self.inside = !self.inside;
+                }
+                None => {
+                    // A tag may start in the bytes held back
+                    let release = self.pending.len().saturating_sub(tag.len().max(1) - 1);
+                    let rest = self.pending.split_off(release);
+                    let released = std::mem::replace(&mut self.pending, rest);
+                    if !self.inside {
+                        output.extend(released);
+                    }
+                    return output;
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let pending = std::mem::take(&mut self.pending);
+        match self.inside {
+            true => vec![],
+            false => pending,
+        }
+    }
+}
+
+struct Replace {
+    pattern: Arc<Regex>,
+    replacement: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl Replace {
+    fn replace(&self, output: &[u8]) -> Vec<u8> {
+        self.pattern
+            .replace_all(output, self.replacement.as_slice())
+            .into_owned()
+    }
+}
+
+impl Step for Replace {
+    fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+
+        // Matches reaching into the window may continue in the next chunk
+        let mut release = self.pending.len().saturating_sub(DEFAULT_WINDOW);
+        for found in self.pattern.find_iter(&self.pending) {
+            if found.end() > release {
+                release = release.min(found.start());
+                break;
+            }
+        }
+
+        let rest = self.pending.split_off(release);
+        let released = std::mem::replace(&mut self.pending, rest);
+        self.replace(&released)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let pending = std::mem::take(&mut self.pending);
+        self.replace(&pending)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
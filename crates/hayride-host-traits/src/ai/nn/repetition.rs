@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Number of tokens in the n-grams checked for repetition.
+pub const DEFAULT_NGRAM: usize = 12;
+/// Number of times an n-gram may be generated within the window before generation stops.
+pub const DEFAULT_MAX_REPEATS: usize = 8;
+/// Number of recent tokens repetitions are counted over.
+pub const DEFAULT_WINDOW: usize = 2048;
+
+/// Watches generated tokens for degenerate repetition.
+///
+/// A looping generation repeats every n-gram of the loop, so generation is treated as
+/// runaway once the same n-gram has been generated `max_repeats` times within the window,
+/// regardless of the length of the loop.
+pub struct RepetitionDetector<T> {
+    ngram: usize,
+    max_repeats: usize,
+    window: usize,
+
+    position: usize,
+    recent: VecDeque<T>,
+    // Positions each n-gram in the window was generated at
+    seen: HashMap<Vec<T>, VecDeque<usize>>,
+}
+
+impl<T: Clone + Eq + Hash> RepetitionDetector<T> {
+    /// Create a detector, an `ngram` or `max_repeats` of zero disables detection.
+    pub fn new(ngram: usize, max_repeats: usize) -> Self {
+        Self {
+            ngram,
+            max_repeats,
+            window: DEFAULT_WINDOW,
+            position: 0,
+            recent: VecDeque::with_capacity(ngram),
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ngram > 0 && self.max_repeats > 0
+    }
+
+    /// Add a generated token, returning true once the latest n-gram has repeated too often.
+    pub fn push(&mut self, token: T) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        self.position += 1;
+        self.recent.push_back(token);
+        if self.recent.len() > self.ngram {
+            self.recent.pop_front();
+        }
+        if self.recent.len() < self.ngram {
+            return false;
+        }
+
+        let key: Vec<T> = self.recent.iter().cloned().collect();
+        let oldest = self.position.saturating_sub(self.window);
+        let positions = self.seen.entry(key).or_default();
+        positions.push_back(self.position);
+        while positions.front().is_some_and(|p| *p <= oldest) {
+            positions.pop_front();
+        }
+        let repeated = positions.len() >= self.max_repeats;
+
+        // Forget n-grams that have left the window
+        if self.seen.len() > self.window {
+            self.seen
+                .retain(|_, positions| positions.back().is_some_and(|p| *p > oldest));
+        }
+
+        repeated
+    }
+}
+
+impl<T: Clone + Eq + Hash> Default for RepetitionDetector<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_NGRAM, DEFAULT_MAX_REPEATS)
+    }
+}
@@ -3,4 +3,7 @@ pub mod mock;
 pub mod rag;
 
 pub use errors::{Error, ErrorCode};
-pub use rag::{Connection, Embedding, RagConnection, RagInner, RagOption, Transformer};
+pub use rag::{
+    Connection, Embedding, QueryResult, RagConnection, RagConnectionAsync, RagInnerAsync,
+    RagOption, RagRow, Transformer,
+};
@@ -16,6 +16,8 @@ pub enum ErrorCode {
     RegisterFailed,
     MissingTable,
     InvalidOption,
+    DeleteFailed,
     NotEnabled,
+    Timeout,
     Unknown,
 }
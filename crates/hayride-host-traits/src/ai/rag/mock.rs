@@ -1,11 +1,12 @@
 use super::errors::ErrorCode;
-use super::rag::{Connection, RagInner};
+use super::rag::{Connection, RagInnerAsync};
 
 #[derive(Default)]
 pub struct MockRagInner {}
 
-impl RagInner for MockRagInner {
-    fn connect(&mut self, _dsn: String) -> Result<Connection, ErrorCode> {
+#[async_trait::async_trait]
+impl RagInnerAsync for MockRagInner {
+    async fn connect(&mut self, _dsn: String) -> Result<Connection, ErrorCode> {
         return Err(ErrorCode::NotEnabled);
     }
 }
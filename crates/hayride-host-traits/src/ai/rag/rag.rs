@@ -1,9 +1,6 @@
 use super::errors::ErrorCode;
 use std::fmt;
-
-pub trait RagInner: Send + Sync {
-    fn connect(&mut self, dsn: String) -> Result<Connection, ErrorCode>;
-}
+use std::sync::{Arc, RwLock};
 
 pub trait RagConnection: Send + Sync {
     fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode>;
@@ -13,18 +10,177 @@ pub trait RagConnection: Send + Sync {
         table: String,
         data: String,
         options: Vec<RagOption>,
-    ) -> Result<Vec<String>, ErrorCode>;
+    ) -> Result<Vec<QueryResult>, ErrorCode>;
+    fn delete(&self, table: String, filter: String) -> Result<(), ErrorCode>;
+    fn upsert(&self, table: String, id: String, data: String) -> Result<(), ErrorCode>;
+
+    /// Embed all data into the table, backends should override this to write once.
+    fn embed_batch(&self, table: String, data: Vec<String>) -> Result<(), ErrorCode> {
+        data.into_iter()
+            .try_for_each(|data| self.embed(table.clone(), data))
+    }
+
+    /// Upsert all rows into the table, backends should override this to write once.
+    fn upsert_batch(&self, table: String, rows: Vec<RagRow>) -> Result<(), ErrorCode> {
+        rows.into_iter()
+            .try_for_each(|row| self.upsert(table.clone(), row.id, row.data))
+    }
+}
+
+impl<T: RagConnection + ?Sized> RagConnection for Box<T> {
+    fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode> {
+        <T as RagConnection>::register(&mut **self, transformer)
+    }
+
+    fn embed(&self, table: String, data: String) -> Result<(), ErrorCode> {
+        <T as RagConnection>::embed(&**self, table, data)
+    }
+
+    fn query(
+        &self,
+        table: String,
+        data: String,
+        options: Vec<RagOption>,
+    ) -> Result<Vec<QueryResult>, ErrorCode> {
+        <T as RagConnection>::query(&**self, table, data, options)
+    }
+
+    fn delete(&self, table: String, filter: String) -> Result<(), ErrorCode> {
+        <T as RagConnection>::delete(&**self, table, filter)
+    }
+
+    fn upsert(&self, table: String, id: String, data: String) -> Result<(), ErrorCode> {
+        <T as RagConnection>::upsert(&**self, table, id, data)
+    }
+
+    fn embed_batch(&self, table: String, data: Vec<String>) -> Result<(), ErrorCode> {
+        <T as RagConnection>::embed_batch(&**self, table, data)
+    }
+
+    fn upsert_batch(&self, table: String, rows: Vec<RagRow>) -> Result<(), ErrorCode> {
+        <T as RagConnection>::upsert_batch(&**self, table, rows)
+    }
+}
+
+/// A rag backend used by async hosts.
+#[async_trait::async_trait]
+pub trait RagInnerAsync: Send + Sync {
+    async fn connect(&mut self, dsn: String) -> Result<Connection, ErrorCode>;
+}
+
+/// An async rag connection used by async hosts.
+///
+/// Sync connections are run on blocking threads once converted into a [`Connection`].
+#[async_trait::async_trait]
+pub trait RagConnectionAsync: Send + Sync {
+    async fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode>;
+    async fn embed(&self, table: String, data: String) -> Result<(), ErrorCode>;
+    async fn query(
+        &self,
+        table: String,
+        data: String,
+        options: Vec<RagOption>,
+    ) -> Result<Vec<QueryResult>, ErrorCode>;
+    async fn delete(&self, table: String, filter: String) -> Result<(), ErrorCode>;
+    async fn upsert(&self, table: String, id: String, data: String) -> Result<(), ErrorCode>;
+    async fn embed_batch(&self, table: String, data: Vec<String>) -> Result<(), ErrorCode>;
+    async fn upsert_batch(&self, table: String, rows: Vec<RagRow>) -> Result<(), ErrorCode>;
+}
+
+/// Runs a sync rag connection on blocking threads of the runtime, registering
+/// transformers waits for the calls in flight.
+struct BlockingConnection(Arc<RwLock<Box<dyn RagConnection>>>);
+
+impl BlockingConnection {
+    async fn run<R: Send + 'static>(
+        &self,
+        code: ErrorCode,
+        f: impl FnOnce(&dyn RagConnection) -> Result<R, ErrorCode> + Send + 'static,
+    ) -> Result<R, ErrorCode> {
+        let connection = self.0.clone();
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.read().unwrap_or_else(|e| e.into_inner());
+            f(connection.as_ref())
+        })
+        .await
+        .map_err(|_| code)?
+    }
+}
+
+#[async_trait::async_trait]
+impl RagConnectionAsync for BlockingConnection {
+    async fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode> {
+        let connection = self.0.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.write().unwrap_or_else(|e| e.into_inner());
+            connection.register(transformer)
+        })
+        .await
+        .map_err(|_| ErrorCode::RegisterFailed)?
+    }
+
+    async fn embed(&self, table: String, data: String) -> Result<(), ErrorCode> {
+        self.run(ErrorCode::EmbedFailed, move |connection| {
+            connection.embed(table, data)
+        })
+        .await
+    }
+
+    async fn query(
+        &self,
+        table: String,
+        data: String,
+        options: Vec<RagOption>,
+    ) -> Result<Vec<QueryResult>, ErrorCode> {
+        self.run(ErrorCode::QueryFailed, move |connection| {
+            connection.query(table, data, options)
+        })
+        .await
+    }
+
+    async fn delete(&self, table: String, filter: String) -> Result<(), ErrorCode> {
+        self.run(ErrorCode::DeleteFailed, move |connection| {
+            connection.delete(table, filter)
+        })
+        .await
+    }
+
+    async fn upsert(&self, table: String, id: String, data: String) -> Result<(), ErrorCode> {
+        self.run(ErrorCode::EmbedFailed, move |connection| {
+            connection.upsert(table, id, data)
+        })
+        .await
+    }
+
+    async fn embed_batch(&self, table: String, data: Vec<String>) -> Result<(), ErrorCode> {
+        self.run(ErrorCode::EmbedFailed, move |connection| {
+            connection.embed_batch(table, data)
+        })
+        .await
+    }
+
+    async fn upsert_batch(&self, table: String, rows: Vec<RagRow>) -> Result<(), ErrorCode> {
+        self.run(ErrorCode::EmbedFailed, move |connection| {
+            connection.upsert_batch(table, rows)
+        })
+        .await
+    }
 }
 
 /// A backend-defined Rag Connection
-pub struct Connection(Box<dyn RagConnection>);
+pub struct Connection(Box<dyn RagConnectionAsync>);
 impl From<Box<dyn RagConnection>> for Connection {
     fn from(value: Box<dyn RagConnection>) -> Self {
+        Self(Box::new(BlockingConnection(Arc::new(RwLock::new(value)))))
+    }
+}
+impl From<Box<dyn RagConnectionAsync>> for Connection {
+    fn from(value: Box<dyn RagConnectionAsync>) -> Self {
         Self(value)
     }
 }
 impl std::ops::Deref for Connection {
-    type Target = dyn RagConnection;
+    type Target = dyn RagConnectionAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -63,3 +219,18 @@ pub struct RagOption {
     pub name: String,
     pub value: String,
 }
+
+/// A row matched by a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub text: String,
+    pub distance: Option<f32>,
+    pub columns: Vec<(String, String)>,
+}
+
+/// A row to upsert into a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RagRow {
+    pub id: String,
+    pub data: String,
+}
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+pub mod blob;
+pub mod errors;
+
+pub use blob::{BlobInfo, BlobTrait};
+pub use errors::{Error, ErrorCode};
@@ -0,0 +1,29 @@
+use super::errors::ErrorCode;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A blob in the store.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobInfo {
+    /// Lowercase hex sha256 digest of the content
+    pub digest: String,
+    pub size: u64,
+    pub created: SystemTime,
+}
+
+/// Content-addressed store of blobs named by the sha256 digest of their content.
+///
+/// Blobs are written to a staging file while they are hashed, then handed to the store.
+pub trait BlobTrait: Send + Sync {
+    /// Dir blobs are staged in, on the same filesystem as the store if it is local.
+    fn staging_dir(&self) -> PathBuf;
+    /// Store a staged file as the blob of the digest, taking ownership of the file.
+    fn put_file(&self, digest: &str, path: &Path) -> Result<BlobInfo, ErrorCode>;
+    /// Open the content of a blob for reading.
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + Send>, ErrorCode>;
+    /// Get the size and creation time of a blob.
+    fn stat(&self, digest: &str) -> Result<BlobInfo, ErrorCode>;
+    /// Delete a blob, returning whether it was stored.
+    fn delete(&self, digest: &str) -> Result<bool, ErrorCode>;
+}
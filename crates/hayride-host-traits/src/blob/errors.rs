@@ -0,0 +1,16 @@
+/// Host side error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub enum ErrorCode {
+    NotFound,
+    InvalidDigest,
+    WriteFailed,
+    ReadFailed,
+    /// Unsupported operation.
+    Unknown,
+}
@@ -1 +1,8 @@
+pub mod config;
+pub mod pubsub;
+pub mod ratelimit;
+pub mod registry;
+pub mod sessions;
+pub mod system;
+pub mod timers;
 pub mod version;
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+pub mod config;
+pub mod errors;
+
+pub use config::{ConfigInner, ConfigValue};
+pub use errors::{Error, ErrorCode};
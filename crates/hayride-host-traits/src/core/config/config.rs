@@ -0,0 +1,23 @@
+use super::errors::ErrorCode;
+
+/// Host settings readable by morphs.
+///
+/// Settings are addressed by dotted keys, i.e. `ai.default-model`. A morph may have
+/// its own overrides, which take precedence over the shared settings.
+pub trait ConfigInner: Send + Sync {
+    /// Get a setting as seen by the given morph.
+    fn get(&self, morph: &str, key: &str) -> Result<ConfigValue, ErrorCode>;
+
+    /// List the sorted keys visible to the given morph that start with the prefix.
+    fn keys(&self, morph: &str, prefix: &str) -> Result<Vec<String>, ErrorCode>;
+}
+
+/// A typed setting value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigValue {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    TextList(Vec<String>),
+}
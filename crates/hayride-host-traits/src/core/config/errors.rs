@@ -0,0 +1,25 @@
+use std::fmt;
+
+/// Host side config error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    NotFound,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::NotFound => "NotFound",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
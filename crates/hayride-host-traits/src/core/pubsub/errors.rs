@@ -0,0 +1,31 @@
+use std::fmt;
+
+/// Host side pubsub error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidTopic,
+    Full,
+    Lagged,
+    Closed,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::InvalidTopic => "InvalidTopic",
+            ErrorCode::Full => "Full",
+            ErrorCode::Lagged => "Lagged",
+            ErrorCode::Closed => "Closed",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
@@ -0,0 +1,6 @@
+pub mod errors;
+#[allow(clippy::module_inception)]
+pub mod registry;
+
+pub use errors::{Error, ErrorCode};
+pub use registry::RegistryInner;
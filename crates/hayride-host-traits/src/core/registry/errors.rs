@@ -0,0 +1,35 @@
+use std::fmt;
+
+/// Host side registry error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    NotConfigured,
+    InvalidReference,
+    NotFound,
+    InvalidSignature,
+    PullFailed,
+    PushFailed,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::NotConfigured => "NotConfigured",
+            ErrorCode::InvalidReference => "InvalidReference",
+            ErrorCode::NotFound => "NotFound",
+            ErrorCode::InvalidSignature => "InvalidSignature",
+            ErrorCode::PullFailed => "PullFailed",
+            ErrorCode::PushFailed => "PushFailed",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
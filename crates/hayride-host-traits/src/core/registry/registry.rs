@@ -0,0 +1,13 @@
+use super::errors::ErrorCode;
+
+/// A remote registry morphs are distributed through.
+///
+/// Morphs are referenced as `package:name@version`, the latest published version is
+/// used when the version is left out.
+pub trait RegistryInner: Send + Sync {
+    /// Pull a morph into the local registry, returning its reference with the version resolved.
+    fn pull(&self, reference: &str) -> Result<String, ErrorCode>;
+
+    /// Sign and push a morph from the local registry.
+    fn push(&self, reference: &str) -> Result<(), ErrorCode>;
+}
@@ -0,0 +1,6 @@
+pub mod errors;
+#[allow(clippy::module_inception)]
+pub mod sessions;
+
+pub use errors::{Error, ErrorCode};
+pub use sessions::{Message, Session, SessionsInner};
@@ -0,0 +1,43 @@
+use super::errors::ErrorCode;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub id: String,
+    pub title: String,
+    /// Milliseconds since the unix epoch
+    pub created_at: u64,
+    /// Milliseconds since the unix epoch of the last message appended
+    pub updated_at: u64,
+    pub message_count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    /// Position of the message in its session, starting at 0
+    pub index: u64,
+    pub role: String,
+    pub content: String,
+    /// Milliseconds since the unix epoch
+    pub created_at: u64,
+}
+
+/// Durable chat sessions shared by every morph.
+pub trait SessionsInner: Send + Sync {
+    /// Create an empty session.
+    fn create(&self, title: &str) -> Result<Session, ErrorCode>;
+
+    /// Get a session by id.
+    fn get(&self, id: &str) -> Result<Session, ErrorCode>;
+
+    /// Append a message to a session, returning its index.
+    fn append(&self, id: &str, role: &str, content: &str) -> Result<u64, ErrorCode>;
+
+    /// List sessions, the most recently updated first.
+    fn list(&self, offset: u64, limit: u32) -> Result<Vec<Session>, ErrorCode>;
+
+    /// Fetch messages of a session in the order they were appended.
+    fn history(&self, id: &str, offset: u64, limit: u32) -> Result<Vec<Message>, ErrorCode>;
+
+    /// Delete a session and its messages, returning whether it existed.
+    fn delete(&self, id: &str) -> Result<bool, ErrorCode>;
+}
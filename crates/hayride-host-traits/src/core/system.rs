@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod system;
+
+pub use system::{Capabilities, Device, DeviceType, MemoryStats, SystemInner};
@@ -0,0 +1,50 @@
+/// Hardware of the host running the components.
+pub trait SystemInner: Send + Sync {
+    fn capabilities(&self) -> Capabilities;
+
+    /// Memory of the host, read on every call.
+    fn memory(&self) -> MemoryStats;
+
+    /// Devices of the inference backend, with their free memory read on every call.
+    fn devices(&self) -> Vec<Device>;
+}
+
+/// Hardware that does not change while the runtime runs, memory in bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    pub os: String,
+    pub os_version: String,
+    pub arch: String,
+    pub cpu_brand: String,
+    pub physical_cores: u32,
+    pub logical_cores: u32,
+    pub memory_total: u64,
+    /// GPU backends the runtime was built with, `cuda` or `metal`
+    pub gpu_backends: Vec<String>,
+}
+
+/// Memory of the host in bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub memory_total: u64,
+    pub memory_available: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceType {
+    Cpu,
+    Gpu,
+    Accelerator,
+}
+
+/// A compute device of the inference backend, memory in bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub description: String,
+    pub device_type: DeviceType,
+    pub memory_total: u64,
+    pub memory_free: u64,
+}
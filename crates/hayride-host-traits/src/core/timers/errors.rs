@@ -0,0 +1,29 @@
+use std::fmt;
+
+/// Host side timers error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidTopic,
+    InvalidInterval,
+    TooManyTimers,
+    Unknown,
+}
+
+// Implement Display for ErrorCode
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            ErrorCode::InvalidTopic => "InvalidTopic",
+            ErrorCode::InvalidInterval => "InvalidInterval",
+            ErrorCode::TooManyTimers => "TooManyTimers",
+            ErrorCode::Unknown => "Unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
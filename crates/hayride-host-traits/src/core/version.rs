@@ -3,4 +3,4 @@ pub mod mock;
 pub mod version;
 
 pub use errors::{Error, ErrorCode};
-pub use version::VersionInner;
+pub use version::{ReleaseInfo, VersionInner};
@@ -1,11 +1,15 @@
 use super::errors::ErrorCode;
-use super::version::VersionInner;
+use super::version::{ReleaseInfo, VersionInner};
 
 #[derive(Default)]
 pub struct MockVersionInner {}
 
+#[async_trait::async_trait]
 impl VersionInner for MockVersionInner {
-    fn latest(&self) -> Result<String, ErrorCode> {
-        Ok("mock-version".into())
+    async fn release(&self) -> Result<ReleaseInfo, ErrorCode> {
+        Ok(ReleaseInfo {
+            version: "mock-version".into(),
+            ..Default::default()
+        })
     }
 }
@@ -1,4 +1,24 @@
 use super::errors::ErrorCode;
+
+#[async_trait::async_trait]
 pub trait VersionInner: Send + Sync {
-    fn latest(&self) -> Result<String, ErrorCode>;
+    async fn release(&self) -> Result<ReleaseInfo, ErrorCode>;
+
+    async fn latest(&self) -> Result<String, ErrorCode> {
+        self.release().await.map(|release| release.version)
+    }
+}
+
+/// Structured info of a hayride release.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReleaseInfo {
+    pub version: String,
+    /// RFC 3339 publish date
+    pub date: String,
+    pub highlights: Vec<String>,
+    /// Download url of the asset matching the host platform
+    pub download_url: Option<String>,
+    /// Download url of the ed25519 signature of the asset
+    pub signature_url: Option<String>,
+    pub notes: String,
 }
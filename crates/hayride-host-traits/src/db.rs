@@ -2,7 +2,8 @@ pub mod db;
 pub mod errors;
 
 pub use db::{
-    Connection, DBConnection, DBRows, DBStatement, DBTrait, DBTransaction, IsolationLevel, Rows,
-    Statement, Transaction,
+    BlockingDB, Connection, DBConnection, DBConnectionAsync, DBRows, DBRowsAsync, DBStatement,
+    DBStatementAsync, DBTrait, DBTraitAsync, DBTransaction, DBTransactionAsync, IsolationLevel,
+    Migration, Rows, Statement, Transaction,
 };
 pub use errors::{Error, ErrorCode};
@@ -1,5 +1,7 @@
 use super::errors::ErrorCode;
+use std::sync::{Arc, Mutex};
 
+/// A sync DB backend, opened on blocking threads once wrapped in a [`BlockingDB`].
 pub trait DBTrait: Send + Sync {
     fn open(&mut self, name: String) -> Result<Connection, ErrorCode>;
 }
@@ -11,12 +13,18 @@ pub trait DBConnection: Send + Sync {
         isolation_level: IsolationLevel,
         read_only: bool,
     ) -> Result<Transaction, ErrorCode>;
+    /// Execute statements separated by semicolons, without parameters.
+    fn execute_batch(&self, sql: &str) -> Result<(), ErrorCode>;
+    /// Rowid of the last row inserted on the connection, for backends that have one.
+    fn last_insert_rowid(&self) -> Result<i64, ErrorCode>;
     fn close(&mut self) -> Result<(), ErrorCode>;
 }
 
 pub trait DBStatement: Send + Sync {
     fn query(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode>;
     fn execute(&self, params: Vec<DBValue>) -> Result<u64, ErrorCode>;
+    /// Execute a write, returning the rows of its `RETURNING` clause.
+    fn execute_returning(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode>;
     fn number_parameters(&self) -> Result<u32, ErrorCode>;
     fn close(&mut self) -> Result<(), ErrorCode>;
 }
@@ -35,15 +43,252 @@ pub trait DBTransaction: Send + Sync {
     fn prepare(&self, query: String) -> Result<Statement, ErrorCode>;
 }
 
+/// A DB backend, opening connections on the runtime of the host.
+///
+/// Backends run their queries as futures of the host runtime. Backends that block,
+/// such as embedded databases, move their work to blocking threads themselves or
+/// implement the sync traits and are wrapped in a [`BlockingDB`].
+#[async_trait::async_trait]
+pub trait DBTraitAsync: Send + Sync {
+    async fn open(&mut self, name: String) -> Result<Connection, ErrorCode>;
+}
+
+#[async_trait::async_trait]
+pub trait DBConnectionAsync: Send + Sync {
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode>;
+    async fn begin_transaction(
+        &mut self,
+        isolation_level: IsolationLevel,
+        read_only: bool,
+    ) -> Result<Transaction, ErrorCode>;
+    /// Execute statements separated by semicolons, without parameters.
+    async fn execute_batch(&self, sql: &str) -> Result<(), ErrorCode>;
+    /// Rowid of the last row inserted on the connection, for backends that have one.
+    async fn last_insert_rowid(&self) -> Result<i64, ErrorCode>;
+    async fn close(&mut self) -> Result<(), ErrorCode>;
+}
+
+#[async_trait::async_trait]
+pub trait DBStatementAsync: Send + Sync {
+    async fn query(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode>;
+    async fn execute(&self, params: Vec<DBValue>) -> Result<u64, ErrorCode>;
+    /// Execute a write, returning the rows of its `RETURNING` clause.
+    async fn execute_returning(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode>;
+    fn number_parameters(&self) -> Result<u32, ErrorCode>;
+    async fn close(&mut self) -> Result<(), ErrorCode>;
+}
+
+#[async_trait::async_trait]
+pub trait DBRowsAsync: Send + Sync {
+    fn columns(&self) -> Vec<String>;
+    async fn next(&mut self) -> Result<Row, ErrorCode>;
+    async fn close(&mut self) -> Result<(), ErrorCode>;
+}
+
+#[async_trait::async_trait]
+pub trait DBTransactionAsync: Send + Sync {
+    async fn commit(&mut self) -> Result<(), ErrorCode>;
+    async fn rollback(&mut self) -> Result<(), ErrorCode>;
+    async fn query(&self, query: String, params: Vec<DBValue>) -> Result<Rows, ErrorCode>;
+    async fn execute(&self, query: String, params: Vec<DBValue>) -> Result<u64, ErrorCode>;
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode>;
+}
+
+/// Runs a sync DB backend on blocking threads of the runtime.
+pub struct BlockingDB<T>(Arc<Mutex<T>>);
+
+impl<T: DBTrait> BlockingDB<T> {
+    pub fn new(backend: T) -> Self {
+        Self(Arc::new(Mutex::new(backend)))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: DBTrait + 'static> DBTraitAsync for BlockingDB<T> {
+    async fn open(&mut self, name: String) -> Result<Connection, ErrorCode> {
+        let backend = self.0.clone();
+        tokio::task::spawn_blocking(move || {
+            backend.lock().unwrap_or_else(|e| e.into_inner()).open(name)
+        })
+        .await
+        .map_err(|_| ErrorCode::OpenFailed)?
+    }
+}
+
+// Runs a call of a sync connection, statement, rows or transaction on a blocking thread,
+// calls wait for the ones in flight
+async fn run_blocking<T: ?Sized + Send + 'static, R: Send + 'static>(
+    inner: &Arc<Mutex<Box<T>>>,
+    code: ErrorCode,
+    f: impl FnOnce(&mut T) -> Result<R, ErrorCode> + Send + 'static,
+) -> Result<R, ErrorCode> {
+    let inner = inner.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut inner = inner.lock().unwrap_or_else(|e| e.into_inner());
+        f(inner.as_mut())
+    })
+    .await
+    .map_err(|_| code)?
+}
+
+struct BlockingConnection(Arc<Mutex<Box<dyn DBConnection>>>);
+
+#[async_trait::async_trait]
+impl DBConnectionAsync for BlockingConnection {
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::PrepareFailed, move |connection| {
+            connection.prepare(query)
+        })
+        .await
+    }
+
+    async fn begin_transaction(
+        &mut self,
+        isolation_level: IsolationLevel,
+        read_only: bool,
+    ) -> Result<Transaction, ErrorCode> {
+        run_blocking(
+            &self.0,
+            ErrorCode::BeginTransactionFailed,
+            move |connection| connection.begin_transaction(isolation_level, read_only),
+        )
+        .await
+    }
+
+    async fn execute_batch(&self, sql: &str) -> Result<(), ErrorCode> {
+        let sql = sql.to_string();
+        run_blocking(&self.0, ErrorCode::ExecuteFailed, move |connection| {
+            connection.execute_batch(&sql)
+        })
+        .await
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::Unknown, |connection| {
+            connection.last_insert_rowid()
+        })
+        .await
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        run_blocking(&self.0, ErrorCode::CloseFailed, |connection| {
+            connection.close()
+        })
+        .await
+    }
+}
+
+struct BlockingStatement(Arc<Mutex<Box<dyn DBStatement>>>);
+
+#[async_trait::async_trait]
+impl DBStatementAsync for BlockingStatement {
+    async fn query(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::QueryFailed, move |statement| {
+            statement.query(params)
+        })
+        .await
+    }
+
+    async fn execute(&self, params: Vec<DBValue>) -> Result<u64, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::ExecuteFailed, move |statement| {
+            statement.execute(params)
+        })
+        .await
+    }
+
+    async fn execute_returning(&self, params: Vec<DBValue>) -> Result<Rows, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::ExecuteFailed, move |statement| {
+            statement.execute_returning(params)
+        })
+        .await
+    }
+
+    fn number_parameters(&self) -> Result<u32, ErrorCode> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .number_parameters()
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        run_blocking(&self.0, ErrorCode::CloseFailed, |statement| {
+            statement.close()
+        })
+        .await
+    }
+}
+
+struct BlockingRows(Arc<Mutex<Box<dyn DBRows>>>);
+
+#[async_trait::async_trait]
+impl DBRowsAsync for BlockingRows {
+    fn columns(&self) -> Vec<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).columns()
+    }
+
+    async fn next(&mut self) -> Result<Row, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::NextFailed, |rows| rows.next()).await
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        run_blocking(&self.0, ErrorCode::CloseFailed, |rows| rows.close()).await
+    }
+}
+
+struct BlockingTransaction(Arc<Mutex<Box<dyn DBTransaction>>>);
+
+#[async_trait::async_trait]
+impl DBTransactionAsync for BlockingTransaction {
+    async fn commit(&mut self) -> Result<(), ErrorCode> {
+        run_blocking(&self.0, ErrorCode::CommitFailed, |transaction| {
+            transaction.commit()
+        })
+        .await
+    }
+
+    async fn rollback(&mut self) -> Result<(), ErrorCode> {
+        run_blocking(&self.0, ErrorCode::RollbackFailed, |transaction| {
+            transaction.rollback()
+        })
+        .await
+    }
+
+    async fn query(&self, query: String, params: Vec<DBValue>) -> Result<Rows, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::QueryFailed, move |transaction| {
+            transaction.query(query, params)
+        })
+        .await
+    }
+
+    async fn execute(&self, query: String, params: Vec<DBValue>) -> Result<u64, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::ExecuteFailed, move |transaction| {
+            transaction.execute(query, params)
+        })
+        .await
+    }
+
+    async fn prepare(&self, query: String) -> Result<Statement, ErrorCode> {
+        run_blocking(&self.0, ErrorCode::PrepareFailed, move |transaction| {
+            transaction.prepare(query)
+        })
+        .await
+    }
+}
+
 /// A backend-defined DB Connection
-pub struct Connection(Box<dyn DBConnection>);
+pub struct Connection(Box<dyn DBConnectionAsync>);
+impl From<Box<dyn DBConnectionAsync>> for Connection {
+    fn from(value: Box<dyn DBConnectionAsync>) -> Self {
+        Self(value)
+    }
+}
 impl From<Box<dyn DBConnection>> for Connection {
     fn from(value: Box<dyn DBConnection>) -> Self {
-        Self(value)
+        Self(Box::new(BlockingConnection(Arc::new(Mutex::new(value)))))
     }
 }
 impl std::ops::Deref for Connection {
-    type Target = dyn DBConnection;
+    type Target = dyn DBConnectionAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -55,14 +300,19 @@ impl std::ops::DerefMut for Connection {
 }
 
 /// A backend-defined prepared statement
-pub struct Statement(Box<dyn DBStatement>);
+pub struct Statement(Box<dyn DBStatementAsync>);
+impl From<Box<dyn DBStatementAsync>> for Statement {
+    fn from(value: Box<dyn DBStatementAsync>) -> Self {
+        Self(value)
+    }
+}
 impl From<Box<dyn DBStatement>> for Statement {
     fn from(value: Box<dyn DBStatement>) -> Self {
-        Self(value)
+        Self(Box::new(BlockingStatement(Arc::new(Mutex::new(value)))))
     }
 }
 impl std::ops::Deref for Statement {
-    type Target = dyn DBStatement;
+    type Target = dyn DBStatementAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -73,14 +323,19 @@ impl std::ops::DerefMut for Statement {
     }
 }
 
-pub struct Rows(Box<dyn DBRows>);
+pub struct Rows(Box<dyn DBRowsAsync>);
+impl From<Box<dyn DBRowsAsync>> for Rows {
+    fn from(value: Box<dyn DBRowsAsync>) -> Self {
+        Self(value)
+    }
+}
 impl From<Box<dyn DBRows>> for Rows {
     fn from(value: Box<dyn DBRows>) -> Self {
-        Self(value)
+        Self(Box::new(BlockingRows(Arc::new(Mutex::new(value)))))
     }
 }
 impl std::ops::Deref for Rows {
-    type Target = dyn DBRows;
+    type Target = dyn DBRowsAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -91,14 +346,19 @@ impl std::ops::DerefMut for Rows {
     }
 }
 
-pub struct Transaction(Box<dyn DBTransaction>);
+pub struct Transaction(Box<dyn DBTransactionAsync>);
+impl From<Box<dyn DBTransactionAsync>> for Transaction {
+    fn from(value: Box<dyn DBTransactionAsync>) -> Self {
+        Self(value)
+    }
+}
 impl From<Box<dyn DBTransaction>> for Transaction {
     fn from(value: Box<dyn DBTransaction>) -> Self {
-        Self(value)
+        Self(Box::new(BlockingTransaction(Arc::new(Mutex::new(value)))))
     }
 }
 impl std::ops::Deref for Transaction {
-    type Target = dyn DBTransaction;
+    type Target = dyn DBTransactionAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -120,6 +380,14 @@ pub enum IsolationLevel {
     Linearizable,
 }
 
+/// A schema change, applied once per database in the order of its version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub version: u64,
+    pub name: String,
+    pub sql: String,
+}
+
 /// A single row of DB values.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Row(pub Vec<DBValue>);
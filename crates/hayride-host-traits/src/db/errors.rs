@@ -5,7 +5,7 @@ pub struct Error {
     pub data: anyhow::Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ErrorCode {
     OpenFailed,
     QueryFailed,
@@ -19,6 +19,7 @@ pub enum ErrorCode {
     NextFailed,
     EndOfRows,
     NotEnabled,
+    Timeout,
     /// Unsupported operation.
     Unknown,
 }
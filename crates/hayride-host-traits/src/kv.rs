@@ -0,0 +1,6 @@
+pub mod errors;
+#[allow(clippy::module_inception)]
+pub mod kv;
+
+pub use errors::{Error, ErrorCode};
+pub use kv::KvTrait;
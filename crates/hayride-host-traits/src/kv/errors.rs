@@ -0,0 +1,15 @@
+/// Host side error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub enum ErrorCode {
+    InvalidKey,
+    ValueTooLarge,
+    StorageFailed,
+    /// Unsupported operation.
+    Unknown,
+}
@@ -0,0 +1,20 @@
+use super::errors::ErrorCode;
+use std::time::Duration;
+
+/// Durable key-value state of morphs, keys of each namespace are kept apart.
+pub trait KvTrait: Send + Sync {
+    /// Get the value of a key, none if it is not set or expired.
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, ErrorCode>;
+    /// Set the value of a key, expiring it after the ttl if set.
+    fn set(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<(), ErrorCode>;
+    /// Delete a key, returning whether it was set.
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, ErrorCode>;
+    /// List the sorted keys that start with the prefix.
+    fn list(&self, namespace: &str, prefix: &str) -> Result<Vec<String>, ErrorCode>;
+}
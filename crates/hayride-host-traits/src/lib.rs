@@ -1,6 +1,10 @@
 pub mod ai;
+pub mod blob;
 pub mod core;
 pub mod db;
+pub mod kv;
 pub mod mcp;
 pub mod silo;
+pub mod template;
+pub mod validate;
 pub mod wac;
@@ -1,8 +1,12 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum ThreadStatus {
     Unknown,
+    /// Waiting for other threads to finish before it starts
+    Queued,
     Processing,
     Exited,
+    /// Exited after the function failed
+    Failed,
     Killed,
 }
 
@@ -15,4 +19,10 @@ pub struct Thread {
     pub args: Vec<String>,
     pub status: ThreadStatus,
     pub output: Vec<u8>,
+    /// Id of the thread this one runs again
+    pub respawned_from: Option<String>,
+    /// Runs of the thread so far, counting restarts after it failed
+    pub attempt: u32,
+    /// Id of the thread this one was restarted as after failing
+    pub restarted_as: Option<String>,
 }
@@ -0,0 +1,6 @@
+pub mod errors;
+#[allow(clippy::module_inception)]
+pub mod template;
+
+pub use errors::{Error, ErrorCode};
+pub use template::TemplateTrait;
@@ -0,0 +1,5 @@
+use super::errors::Error;
+pub trait TemplateTrait: Send + Sync {
+    fn render(&mut self, source: String, data: String) -> Result<String, Error>;
+    fn render_named(&mut self, name: String, data: String) -> Result<String, Error>;
+}
@@ -0,0 +1,6 @@
+pub mod errors;
+#[allow(clippy::module_inception)]
+pub mod validate;
+
+pub use errors::{Error, ErrorCode};
+pub use validate::{Schema, ValidateSchema, ValidateTrait, ValidationError};
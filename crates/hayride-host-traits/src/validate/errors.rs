@@ -0,0 +1,14 @@
+/// Host side error.
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub data: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub enum ErrorCode {
+    InvalidSchema,
+    InvalidDocument,
+    /// Unsupported operation.
+    Unknown,
+}
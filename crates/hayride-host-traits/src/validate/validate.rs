@@ -0,0 +1,33 @@
+use super::errors::ErrorCode;
+
+pub trait ValidateTrait: Send + Sync {
+    fn compile(&mut self, schema: String) -> Result<Schema, ErrorCode>;
+}
+
+pub trait ValidateSchema: Send + Sync {
+    fn validate(&self, document: String) -> Result<Vec<ValidationError>, ErrorCode>;
+}
+
+/// A place where a document does not conform to its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    /// JSON pointer to the failing value in the document
+    pub instance_location: String,
+    /// JSON pointer to the failing keyword in the schema
+    pub keyword_location: String,
+    pub message: String,
+}
+
+/// A backend-defined compiled schema
+pub struct Schema(Box<dyn ValidateSchema>);
+impl From<Box<dyn ValidateSchema>> for Schema {
+    fn from(value: Box<dyn ValidateSchema>) -> Self {
+        Self(value)
+    }
+}
+impl std::ops::Deref for Schema {
+    type Target = dyn ValidateSchema;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
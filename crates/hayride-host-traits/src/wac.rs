@@ -1,5 +1,5 @@
 pub mod errors;
 pub mod wac;
 
-pub use errors::{Error, ErrorCode};
-pub use wac::WacTrait;
+pub use errors::{Diagnostic, Error, ErrorCode, SourceSpan};
+pub use wac::{ComponentDescription, ComponentSource, ResolveOptions, WacTrait, WorldItem};
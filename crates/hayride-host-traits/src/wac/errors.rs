@@ -3,6 +3,18 @@
 pub struct Error {
     pub code: ErrorCode,
     pub data: anyhow::Error,
+    /// Problems found in the composition, empty when the error is not about its contents.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl From<ErrorCode> for Error {
+    fn from(code: ErrorCode) -> Self {
+        Self {
+            data: anyhow::anyhow!("{:?}", code),
+            code,
+            diagnostics: vec![],
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -11,6 +23,28 @@ pub enum ErrorCode {
     ResolveFailed,
     ComposeFailed,
     EncodeFailed,
+    Timeout,
+    InvalidPath,
+    WriteFailed,
+    InvalidComponent,
     /// Unsupported operation.
     Unknown,
 }
+
+/// A problem found in a composition.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Where in the composition source the problem is.
+    pub span: Option<SourceSpan>,
+    /// Package that could not be found or loaded.
+    pub package: Option<String>,
+}
+
+/// Location in a composition source, lines and columns count from 1.
+#[derive(Clone, Copy, Debug)]
+pub struct SourceSpan {
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+}
@@ -1,5 +1,48 @@
-use super::errors::ErrorCode;
+use super::errors::Error;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Where packages are looked up in addition to the registry of the backend.
+#[derive(Clone, Debug, Default)]
+pub struct ResolveOptions {
+    /// Package names resolved from a local file instead of a registry.
+    pub overrides: HashMap<String, PathBuf>,
+    /// Registry directories searched in order before the registry of the backend.
+    pub registries: Vec<PathBuf>,
+}
+
+/// A component given by a morph identifier or file path, or by its bytes.
+#[derive(Clone, Debug)]
+pub enum ComponentSource {
+    Morph(String),
+    Bytes(Vec<u8>),
+}
+
+/// What a component imports and exports.
+#[derive(Clone, Debug, Default)]
+pub struct ComponentDescription {
+    /// Registry package of a morph, e.g. `hayride:app@0.0.1`.
+    pub package: Option<String>,
+    pub imports: Vec<WorldItem>,
+    pub exports: Vec<WorldItem>,
+}
+
+/// An interface, function or type of the world of a component.
+#[derive(Clone, Debug)]
+pub struct WorldItem {
+    pub name: String,
+    /// Functions of the item as written in WIT.
+    pub functions: Vec<String>,
+}
+
 pub trait WacTrait: Send + Sync {
-    fn compose(&mut self, path: String) -> Result<Vec<u8>, ErrorCode>;
-    fn plug(&mut self, socket_path: String, plug_paths: Vec<String>) -> Result<Vec<u8>, ErrorCode>;
+    fn compose(&mut self, path: String, options: &ResolveOptions) -> Result<Vec<u8>, Error>;
+    fn plug(
+        &mut self,
+        socket_path: String,
+        plug_paths: Vec<String>,
+        options: &ResolveOptions,
+    ) -> Result<Vec<u8>, Error>;
+    fn validate(&mut self, component: ComponentSource) -> Result<(), Error>;
+    fn describe(&mut self, component: ComponentSource) -> Result<ComponentDescription, Error>;
 }
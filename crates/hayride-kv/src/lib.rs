@@ -0,0 +1,169 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hayride_host_traits::kv::{errors::ErrorCode, KvTrait};
+
+// Longest key accepted, in bytes
+const MAX_KEY_LEN: usize = 1024;
+
+// Largest value accepted, larger state belongs in a database or a file
+const MAX_VALUE_LEN: usize = 16 * 1024 * 1024;
+
+static SHARED: OnceLock<Arc<KvBackend>> = OnceLock::new();
+
+/// Key-value store kept in a sqlite database.
+///
+/// Expired keys are hidden from reads and removed when their namespace is next written.
+pub struct KvBackend {
+    conn: Mutex<Option<Connection>>,
+}
+
+impl KvBackend {
+    /// Open the key-value database at the path, creating the file and its table of
+    /// namespaced keys if they do not exist.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS kv (
+                 namespace TEXT NOT NULL,
+                 key TEXT NOT NULL,
+                 value BLOB NOT NULL,
+                 expires_at INTEGER,
+                 PRIMARY KEY (namespace, key)
+             );",
+        )?;
+        Ok(Self {
+            conn: Mutex::new(Some(conn)),
+        })
+    }
+
+    /// Keys of every engine of the process, in `~/.hayride/data/kv.sqlite`.
+    ///
+    /// A database that fails to open is logged once, every operation then fails with
+    /// `storage-failed`.
+    pub fn shared() -> Arc<KvBackend> {
+        SHARED
+            .get_or_init(|| {
+                let backend = default_path().and_then(|path| Self::open(&path));
+                Arc::new(backend.unwrap_or_else(|e| {
+                    log::warn!("failed to open key-value store: {:?}", e);
+                    Self {
+                        conn: Mutex::new(None),
+                    }
+                }))
+            })
+            .clone()
+    }
+
+    fn with_conn<R>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<R>,
+    ) -> Result<R, ErrorCode> {
+        let conn = self.conn.lock().map_err(|_| ErrorCode::StorageFailed)?;
+        let conn = conn.as_ref().ok_or(ErrorCode::StorageFailed)?;
+        f(conn).map_err(|e| {
+            log::warn!("key-value store failed: {:?}", e);
+            ErrorCode::StorageFailed
+        })
+    }
+}
+
+impl KvTrait for KvBackend {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, ErrorCode> {
+        check_key(key)?;
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT value FROM kv
+                 WHERE namespace = ?1 AND key = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+                params![namespace, key, now()],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+    }
+
+    fn set(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<(), ErrorCode> {
+        check_key(key)?;
+        if value.len() > MAX_VALUE_LEN {
+            return Err(ErrorCode::ValueTooLarge);
+        }
+
+        let now = now();
+        let expires_at = ttl.map(|ttl| now.saturating_add(ttl.as_millis() as i64));
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM kv WHERE namespace = ?1 AND expires_at <= ?2",
+                params![namespace, now],
+            )?;
+            conn.execute(
+                "INSERT INTO kv (namespace, key, value, expires_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (namespace, key)
+                 DO UPDATE SET value = excluded.value, expires_at = excluded.expires_at",
+                params![namespace, key, value, expires_at],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, ErrorCode> {
+        check_key(key)?;
+        let live = self.get(namespace, key)?.is_some();
+        self.with_conn(|conn| {
+            conn.execute(
+                "DELETE FROM kv WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+            )
+        })?;
+        Ok(live)
+    }
+
+    fn list(&self, namespace: &str, prefix: &str) -> Result<Vec<String>, ErrorCode> {
+        self.with_conn(|conn| {
+            let mut statement = conn.prepare(
+                "SELECT key FROM kv
+                 WHERE namespace = ?1 AND substr(key, 1, length(?2)) = ?2
+                 AND (expires_at IS NULL OR expires_at > ?3)
+                 ORDER BY key",
+            )?;
+            let keys = statement
+                .query_map(params![namespace, prefix, now()], |row| row.get(0))?
+                .collect();
+            keys
+        })
+    }
+}
+
+/// Path of the store shared by every engine, `~/.hayride/data/kv.sqlite`.
+pub fn default_path() -> Result<PathBuf> {
+    Ok(hayride_utils::paths::hayride::default_hayride_dir()?
+        .join("data")
+        .join("kv.sqlite"))
+}
+
+fn check_key(key: &str) -> Result<(), ErrorCode> {
+    match key.is_empty() || key.len() > MAX_KEY_LEN {
+        true => Err(ErrorCode::InvalidKey),
+        false => Ok(()),
+    }
+}
+
+// Milliseconds since the unix epoch
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
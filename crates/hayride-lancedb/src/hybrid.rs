@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::downcast_array, Array, Float32Array, RecordBatch, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use arrow_select::take::take_record_batch;
+use lance::dataset::ROW_ID;
+use lancedb::rerankers::Reranker;
+
+// Columns lancedb adds to vector, full text and reranked results
+const DISTANCE_COLUMN: &str = "_distance";
+const SCORE_COLUMN: &str = "_score";
+const RELEVANCE_COLUMN: &str = "_relevance_score";
+
+/// Fuses vector and full text search results with a weighted sum of their scores.
+///
+/// Both scores are normalized to `[0, 1]` by lancedb before reranking, the vector
+/// score is the inverted distance so higher is more relevant for both. A weight of
+/// `1.0` only considers the vector score, `0.0` only the full text score.
+#[derive(Debug)]
+pub struct WeightedReranker {
+    weight: f32,
+}
+
+impl WeightedReranker {
+    pub fn new(weight: f32) -> Self {
+        Self {
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Reranker for WeightedReranker {
+    async fn rerank_hybrid(
+        &self,
+        _query: &str,
+        vector_results: RecordBatch,
+        fts_results: RecordBatch,
+    ) -> lancedb::Result<RecordBatch> {
+        let vector_scores = scores(&vector_results, DISTANCE_COLUMN, true)?;
+        let fts_scores = scores(&fts_results, SCORE_COLUMN, false)?;
+
+        let combined = self.merge_results(vector_results, fts_results)?;
+        let row_ids: UInt64Array = downcast_array(row_id_column(&combined)?);
+        let relevance: Vec<f32> = row_ids
+            .values()
+            .iter()
+            .map(|id| {
+                let vector = vector_scores.get(id).copied().unwrap_or(0.0);
+                let fts = fts_scores.get(id).copied().unwrap_or(0.0);
+                self.weight * vector + (1.0 - self.weight) * fts
+            })
+            .collect();
+
+        // Order rows by descending relevance
+        let mut indices: Vec<u32> = (0..relevance.len() as u32).collect();
+        indices.sort_by(|a, b| relevance[*b as usize].total_cmp(&relevance[*a as usize]));
+        let sorted = take_record_batch(&combined, &UInt32Array::from(indices.clone()))?;
+        let relevance =
+            Float32Array::from_iter_values(indices.iter().map(|i| relevance[*i as usize]));
+
+        let mut fields = sorted.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            RELEVANCE_COLUMN,
+            DataType::Float32,
+            false,
+        )));
+        let mut columns = sorted.columns().to_vec();
+        columns.push(Arc::new(relevance));
+
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+}
+
+// Map the row ids of the results to their score, inverting distances
+fn scores(results: &RecordBatch, column: &str, invert: bool) -> lancedb::Result<HashMap<u64, f32>> {
+    let mut scores = HashMap::new();
+    if results.num_rows() == 0 {
+        return Ok(scores);
+    }
+
+    let row_ids: UInt64Array = downcast_array(row_id_column(results)?);
+    let values: Float32Array = downcast_array(results.column_by_name(column).ok_or_else(|| {
+        lancedb::Error::InvalidInput {
+            message: format!("expected column {} not found in results", column),
+        }
+    })?);
+
+    for (id, value) in row_ids.values().iter().zip(values.iter()) {
+        let value = value.unwrap_or(if invert { 1.0 } else { 0.0 });
+        scores.insert(*id, if invert { 1.0 - value } else { value });
+    }
+
+    Ok(scores)
+}
+
+fn row_id_column(results: &RecordBatch) -> lancedb::Result<&Arc<dyn Array>> {
+    results
+        .column_by_name(ROW_ID)
+        .ok_or_else(|| lancedb::Error::InvalidInput {
+            message: format!("expected column {} not found in results", ROW_ID),
+        })
+}
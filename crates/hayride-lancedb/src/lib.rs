@@ -1,15 +1,21 @@
+mod hybrid;
+
 use hayride_host_traits::ai::rag::{
-    Connection, Embedding, ErrorCode, RagConnection, RagInner, RagOption, Transformer,
+    Connection, Embedding, ErrorCode, QueryResult, RagConnectionAsync, RagInnerAsync, RagOption,
+    RagRow, Transformer,
 };
 
 use std::{iter::once, sync::Arc};
 
-use arrow_array::{RecordBatch, RecordBatchIterator, StringArray};
+use arrow_array::{Array, ArrayRef, Float32Array, RecordBatch, RecordBatchIterator, StringArray};
+use arrow_cast::display::array_value_to_string;
 use arrow_schema::{ArrowError, DataType, Field, Schema};
 use tokio::task;
 
 use futures::StreamExt;
 use lancedb::embeddings::{EmbeddingDefinition, EmbeddingFunction};
+use lancedb::index::scalar::{FtsIndexBuilder, FullTextSearchQuery};
+use lancedb::index::{Index, IndexType};
 use lancedb::{
     arrow::IntoArrow,
     connect,
@@ -18,22 +24,33 @@ use lancedb::{
     query::{ExecutableQuery, QueryBase},
 };
 
+// Column used to identify rows that can be replaced by an upsert
+const ID_COLUMN: &str = "id";
+
+// Default share of the vector score in hybrid search, the rest is the full text score
+const DEFAULT_HYBRID_WEIGHT: f32 = 0.7;
+
+// How rows are matched against the query
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SearchMode {
+    Vector,
+    Hybrid,
+}
+
 #[derive(Default)]
 pub struct LanceDBRag {}
 
-impl RagInner for LanceDBRag {
-    fn connect(&mut self, dsn: String) -> Result<Connection, ErrorCode> {
+#[async_trait::async_trait]
+impl RagInnerAsync for LanceDBRag {
+    async fn connect(&mut self, dsn: String) -> Result<Connection, ErrorCode> {
         let builder: ConnectBuilder = connect(&dsn);
 
-        tokio::task::block_in_place(|| {
-            let db = tokio::runtime::Runtime::new()
-                .map_err(|_| ErrorCode::ConnectionFailed)?
-                .block_on(LanceDBConnection::new(builder))
-                .map_err(|_| ErrorCode::ConnectionFailed)?;
+        let db = LanceDBConnection::new(builder)
+            .await
+            .map_err(|_| ErrorCode::ConnectionFailed)?;
 
-            let connection: Box<dyn RagConnection> = Box::new(db);
-            return Ok(connection.into());
-        })
+        let connection: Box<dyn RagConnectionAsync> = Box::new(db);
+        return Ok(connection.into());
     }
 }
 
@@ -44,7 +61,9 @@ struct LanceDBConnection {
 }
 
 impl LanceDBConnection {
-    async fn new(builder: ConnectBuilder) -> Result<Self, Box<dyn std::error::Error>> {
+    async fn new(
+        builder: ConnectBuilder,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let conn: lancedb::Connection =
             task::spawn(async move { builder.execute().await }).await??;
 
@@ -54,10 +73,87 @@ impl LanceDBConnection {
             transformer: None,
         })
     }
+
+    // Embed rows into the table with a single write, creating the table if it does not exist
+    async fn add_rows(
+        &self,
+        table: String,
+        ids: Option<Vec<String>>,
+        data: Vec<String>,
+    ) -> Result<(), ErrorCode> {
+        log::debug!("embedding {} rows into table: {}", data.len(), table);
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let transformer = self.transformer.as_ref().ok_or(ErrorCode::RegisterFailed)?;
+
+        match &self.conn {
+            Some(conn) => match conn.open_table(table.clone()).execute().await {
+                Ok(table) => {
+                    log::debug!("table exists, embedding data: {}", table);
+
+                    // Tables created before ids were supported only have the data column
+                    let with_id = table
+                        .schema()
+                        .await
+                        .map(|schema| schema.field_with_name(ID_COLUMN).is_ok())
+                        .unwrap_or(false);
+                    if ids.is_some() && !with_id {
+                        log::warn!("table {} has no {} column", table, ID_COLUMN);
+                        return Err(ErrorCode::EmbedFailed);
+                    }
+
+                    match table
+                        .add(
+                            make_data(&transformer.data_column, ids, with_id, data)
+                                .map_err(|_| ErrorCode::EmbedFailed)?,
+                        )
+                        .execute()
+                        .await
+                    {
+                        Ok(_) => {}
+                        Err(e) => {
+                            log::warn!("failed to embed data into table: {}", e);
+                            return Err(ErrorCode::EmbedFailed);
+                        }
+                    }
+                }
+                Err(_) => {
+                    log::debug!("table does not exist, creating table: {}", table);
+
+                    // Try to create the table and store the data
+                    conn.create_table(
+                        table.clone(),
+                        make_data(&transformer.data_column, ids, true, data)
+                            .map_err(|_| ErrorCode::EmbedFailed)?,
+                    )
+                    .add_embedding(EmbeddingDefinition::new(
+                        transformer.data_column.clone(),
+                        transformer.embedding.to_string(),
+                        Some(transformer.vector_column.clone()),
+                    ))
+                    .map_err(|_| ErrorCode::CreateTableFailed)?
+                    .execute()
+                    .await
+                    .map_err(|_| ErrorCode::CreateTableFailed)?;
+
+                    log::debug!("table created: {}", table);
+                }
+            },
+            None => {
+                return Err(ErrorCode::ConnectionFailed);
+            }
+        }
+
+        return Ok(());
+    }
 }
 
-impl RagConnection for LanceDBConnection {
-    fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode> {
+#[async_trait::async_trait]
+impl RagConnectionAsync for LanceDBConnection {
+    async fn register(&mut self, transformer: Transformer) -> Result<(), ErrorCode> {
         log::debug!("registering transformer: {:?}", transformer);
         match &self.conn {
             Some(conn) => match transformer.embedding {
@@ -82,83 +178,23 @@ impl RagConnection for LanceDBConnection {
         return Ok(());
     }
 
-    fn embed(&self, table: String, data: String) -> Result<(), ErrorCode> {
-        log::debug!("embedding data into table: {}, data: {}", table, data);
-
-        let transformer = self.transformer.as_ref().ok_or(ErrorCode::RegisterFailed)?;
-
-        match &self.conn {
-            Some(conn) => {
-                tokio::task::block_in_place(|| {
-                    tokio::runtime::Runtime::new()
-                        .map_err(|_| ErrorCode::EmbedFailed)?
-                        .block_on(async {
-                            match conn.open_table(table.clone()).execute().await {
-                                Ok(table) => {
-                                    log::debug!("table exists, embedding data: {}", table);
-
-                                    match table
-                                        .add(
-                                            make_data(&transformer.data_column, data)
-                                                .map_err(|_| ErrorCode::EmbedFailed)?,
-                                        )
-                                        .execute()
-                                        .await
-                                    {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            log::warn!("failed to embed data into table: {}", e);
-                                            return Err(ErrorCode::EmbedFailed);
-                                        }
-                                    }
-
-                                    Ok(())
-                                }
-                                Err(_) => {
-                                    log::debug!("table does not exist, creating table: {}", table);
-
-                                    // Try to create the table and store the data
-                                    conn.create_table(
-                                        table.clone(),
-                                        make_data(&transformer.data_column, data)
-                                            .map_err(|_| ErrorCode::EmbedFailed)?,
-                                    )
-                                    .add_embedding(EmbeddingDefinition::new(
-                                        transformer.data_column.clone(),
-                                        transformer.embedding.to_string(),
-                                        Some(transformer.vector_column.clone()),
-                                    ))
-                                    .map_err(|_| ErrorCode::CreateTableFailed)?
-                                    .execute()
-                                    .await
-                                    .map_err(|_| ErrorCode::CreateTableFailed)?;
-
-                                    log::debug!("table created: {}", table);
-
-                                    Ok(())
-                                }
-                            }
-                        })
-                })?
-            }
-            None => {
-                return Err(ErrorCode::ConnectionFailed);
-            }
-        }
-
-        return Ok(());
+    async fn embed(&self, table: String, data: String) -> Result<(), ErrorCode> {
+        self.add_rows(table, None, vec![data]).await
     }
 
-    fn query(
+    async fn query(
         &self,
         table: String,
         data: String,
         options: Vec<RagOption>,
-    ) -> Result<Vec<String>, ErrorCode> {
+    ) -> Result<Vec<QueryResult>, ErrorCode> {
         log::debug!("querying table: {}, data: {}", table, data);
 
         // Set default options and parse rag options for overrides
         let mut limit = 1;
+        let mut columns: Vec<String> = vec![];
+        let mut mode = SearchMode::Vector;
+        let mut weight = DEFAULT_HYBRID_WEIGHT;
 
         options.iter().for_each(|option| {
             // Match on lowercase option name
@@ -176,6 +212,33 @@ impl RagConnection for LanceDBConnection {
                         }
                     }
                 }
+                "columns" => {
+                    // Comma separated list of extra columns to return
+                    columns = option
+                        .value
+                        .split(',')
+                        .map(|c| c.trim().to_string())
+                        .filter(|c| !c.is_empty())
+                        .collect();
+                }
+                "mode" => match option.value.to_lowercase().as_str() {
+                    "vector" => mode = SearchMode::Vector,
+                    "hybrid" => mode = SearchMode::Hybrid,
+                    _ => {
+                        log::warn!("invalid mode value: {}", option.value);
+                    }
+                },
+                "weight" => {
+                    // Share of the vector score when fusing hybrid results
+                    match option.value.parse::<f32>() {
+                        Ok(value) if (0.0..=1.0).contains(&value) => {
+                            weight = value;
+                        }
+                        _ => {
+                            log::warn!("invalid weight value: {}", option.value);
+                        }
+                    }
+                }
                 _ => {
                     // Invalid option
                     log::warn!("unexpected option: {}", option.name);
@@ -185,54 +248,53 @@ impl RagConnection for LanceDBConnection {
 
         match &self.conn {
             Some(conn) => {
-                let result = tokio::task::block_in_place(|| {
-                    tokio::runtime::Runtime::new()
-                        .map_err(|_| ErrorCode::QueryFailed)?
-                        .block_on(async {
-                            let table = conn
-                                .open_table(table.clone())
-                                .execute()
-                                .await
-                                .map_err(|_| ErrorCode::MissingTable)?;
-
-                            // Compute the query vector
-                            let query = Arc::new(StringArray::from_iter_values(once(data)));
-
-                            let embedding =
-                                self.embedding.as_ref().ok_or(ErrorCode::MissingTable)?;
-                            let query_vector = embedding
-                                .compute_query_embeddings(query)
-                                .map_err(|_| ErrorCode::EmbedFailed)?;
-                            let mut results = table
-                                .vector_search(query_vector)
-                                .map_err(|_| ErrorCode::QueryFailed)?
-                                .limit(limit)
-                                .execute()
-                                .await
-                                .map_err(|_| ErrorCode::QueryFailed)?;
-
-                            let rb = results
-                                .next()
-                                .await
-                                .ok_or(ErrorCode::QueryFailed)?
-                                .map_err(|_| ErrorCode::QueryFailed)?;
-                            let out = rb
-                                .column_by_name("text")
-                                .ok_or(ErrorCode::QueryFailed)?
-                                .as_any()
-                                .downcast_ref::<StringArray>()
-                                .ok_or(ErrorCode::QueryFailed)?;
-
-                            // Return results filtering out nulls
-                            let results: Vec<String> = out
-                                .iter()
-                                .filter_map(|x| x.map(|s| s.to_string()))
-                                .collect();
-                            Ok(results)
-                        })
+                let table = conn
+                    .open_table(table.clone())
+                    .execute()
+                    .await
+                    .map_err(|_| ErrorCode::MissingTable)?;
+
+                let transformer = self.transformer.as_ref().ok_or(ErrorCode::RegisterFailed)?;
+
+                // Compute the query vector
+                let query = Arc::new(StringArray::from_iter_values(once(data.clone())));
+
+                let embedding = self.embedding.as_ref().ok_or(ErrorCode::MissingTable)?;
+                let query_vector = embedding
+                    .compute_query_embeddings(query)
+                    .map_err(|_| ErrorCode::EmbedFailed)?;
+                let search = table
+                    .vector_search(query_vector)
+                    .map_err(|_| ErrorCode::QueryFailed)?
+                    .limit(limit);
+
+                let search = match mode {
+                    SearchMode::Vector => search,
+                    SearchMode::Hybrid => {
+                        // Combine the vector search with a keyword search of the data column
+                        ensure_fts_index(&table, &transformer.data_column).await?;
+                        search
+                            .full_text_search(
+                                FullTextSearchQuery::new(data)
+                                    .columns(Some(vec![transformer.data_column.clone()])),
+                            )
+                            .rerank(Arc::new(hybrid::WeightedReranker::new(weight)))
+                    }
+                };
+
+                let mut results = search.execute().await.map_err(|e| {
+                    log::warn!("failed to query table: {}", e);
+                    ErrorCode::QueryFailed
                 })?;
 
-                return Ok(result);
+                // Collect the rows of every batch, filtering out nulls
+                let mut rows = vec![];
+                while let Some(rb) = results.next().await {
+                    let rb = rb.map_err(|_| ErrorCode::QueryFailed)?;
+                    rows.extend(query_results(&rb, &transformer.data_column, &columns)?);
+                }
+
+                return Ok(rows);
             }
             None => {
                 log::warn!("failed to connect to LanceDB");
@@ -241,13 +303,185 @@ impl RagConnection for LanceDBConnection {
             }
         }
     }
+
+    async fn delete(&self, table: String, filter: String) -> Result<(), ErrorCode> {
+        log::debug!("deleting from table: {}, filter: {}", table, filter);
+
+        match &self.conn {
+            Some(conn) => {
+                let table = conn
+                    .open_table(table.clone())
+                    .execute()
+                    .await
+                    .map_err(|_| ErrorCode::MissingTable)?;
+
+                table.delete(&filter).await.map_err(|e| {
+                    log::warn!("failed to delete from table: {}", e);
+                    ErrorCode::DeleteFailed
+                })
+            }
+            None => {
+                return Err(ErrorCode::ConnectionFailed);
+            }
+        }
+    }
+
+    async fn upsert(&self, table: String, id: String, data: String) -> Result<(), ErrorCode> {
+        log::debug!("upserting into table: {}, id: {}", table, id);
+
+        // Merge inserts do not apply the embedding function, so replace the
+        // row by deleting it before embedding the new data
+        let filter = format!("{} = '{}'", ID_COLUMN, id.replace('\'', "''"));
+        match self.delete(table.clone(), filter).await {
+            Ok(()) | Err(ErrorCode::MissingTable) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.add_rows(table, Some(vec![id]), vec![data]).await
+    }
+
+    async fn embed_batch(&self, table: String, data: Vec<String>) -> Result<(), ErrorCode> {
+        self.add_rows(table, None, data).await
+    }
+
+    async fn upsert_batch(&self, table: String, rows: Vec<RagRow>) -> Result<(), ErrorCode> {
+        log::debug!("upserting {} rows into table: {}", rows.len(), table);
+
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let (ids, data): (Vec<String>, Vec<String>) =
+            rows.into_iter().map(|row| (row.id, row.data)).unzip();
+
+        // Replace existing rows by deleting them before embedding the new data
+        let filter = format!(
+            "{} IN ({})",
+            ID_COLUMN,
+            ids.iter()
+                .map(|id| format!("'{}'", id.replace('\'', "''")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        match self.delete(table.clone(), filter).await {
+            Ok(()) | Err(ErrorCode::MissingTable) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.add_rows(table, Some(ids), data).await
+    }
+}
+
+// Create a full text search index on the column if the table does not have one
+async fn ensure_fts_index(table: &lancedb::Table, column: &str) -> Result<(), ErrorCode> {
+    let indices = table.list_indices().await.map_err(|e| {
+        log::warn!("failed to list indices: {}", e);
+        ErrorCode::QueryFailed
+    })?;
+    let indexed = indices.iter().any(|index| {
+        index.index_type == IndexType::FTS && index.columns.iter().any(|c| c == column)
+    });
+    if indexed {
+        return Ok(());
+    }
+
+    log::debug!("creating full text search index on column: {}", column);
+    table
+        .create_index(&[column], Index::FTS(FtsIndexBuilder::default()))
+        .execute()
+        .await
+        .map_err(|e| {
+            log::warn!("failed to create full text search index: {}", e);
+            ErrorCode::QueryFailed
+        })
+}
+
+// Column lancedb adds to vector search results with the distance to the query
+const DISTANCE_COLUMN: &str = "_distance";
+// Column lancedb adds to hybrid search results with the fused relevance
+const RELEVANCE_COLUMN: &str = "_relevance_score";
+
+// Convert a batch of query results, skipping rows without data
+fn query_results(
+    rb: &RecordBatch,
+    data_column: &str,
+    columns: &[String],
+) -> Result<Vec<QueryResult>, ErrorCode> {
+    let text = rb
+        .column_by_name(data_column)
+        .ok_or(ErrorCode::QueryFailed)?
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or(ErrorCode::QueryFailed)?;
+    let distance = rb
+        .column_by_name(DISTANCE_COLUMN)
+        .and_then(|c| c.as_any().downcast_ref::<Float32Array>());
+    let relevance = rb
+        .column_by_name(RELEVANCE_COLUMN)
+        .and_then(|c| c.as_any().downcast_ref::<Float32Array>());
+
+    let selected = columns
+        .iter()
+        .map(|name| match rb.column_by_name(name) {
+            Some(column) => Ok((name.clone(), column)),
+            None => {
+                log::warn!("unknown column: {}", name);
+                Err(ErrorCode::InvalidOption)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut results = vec![];
+    for row in 0..rb.num_rows() {
+        if text.is_null(row) {
+            continue;
+        }
+
+        let mut columns = vec![];
+        for (name, column) in &selected {
+            let value = if column.is_null(row) {
+                String::new()
+            } else {
+                array_value_to_string(column, row).map_err(|_| ErrorCode::QueryFailed)?
+            };
+            columns.push((name.clone(), value));
+        }
+
+        results.push(QueryResult {
+            text: text.value(row).to_string(),
+            distance: match relevance {
+                // Hybrid results report the inverted relevance, so lower is still more similar
+                Some(relevance) => Some(1.0 - relevance.value(row)),
+                None => distance.filter(|d| !d.is_null(row)).map(|d| d.value(row)),
+            },
+            columns,
+        });
+    }
+
+    Ok(results)
 }
 
-fn make_data(data_column: &str, data: String) -> Result<impl IntoArrow, ArrowError> {
-    let schema = Schema::new(vec![Field::new(data_column, DataType::Utf8, false)]);
-    let schema = Arc::new(schema);
-    let source = StringArray::from_iter_values(vec![data]);
+fn make_data(
+    data_column: &str,
+    ids: Option<Vec<String>>,
+    with_id: bool,
+    data: Vec<String>,
+) -> Result<impl IntoArrow, ArrowError> {
+    let mut fields = vec![];
+    let mut columns: Vec<ArrayRef> = vec![];
+    if with_id {
+        // Rows embedded without an id have a null id
+        let ids: Vec<Option<String>> = match ids {
+            Some(ids) => ids.into_iter().map(Some).collect(),
+            None => vec![None; data.len()],
+        };
+        fields.push(Field::new(ID_COLUMN, DataType::Utf8, true));
+        columns.push(Arc::new(StringArray::from(ids)));
+    }
+    fields.push(Field::new(data_column, DataType::Utf8, false));
+    columns.push(Arc::new(StringArray::from_iter_values(data)));
 
-    let rb = RecordBatch::try_new(schema.clone(), vec![Arc::new(source)])?;
+    let schema = Arc::new(Schema::new(fields));
+    let rb = RecordBatch::try_new(schema.clone(), columns)?;
     Ok(Box::new(RecordBatchIterator::new(vec![Ok(rb)], schema)))
 }
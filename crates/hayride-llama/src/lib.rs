@@ -1,19 +1,23 @@
 use std::collections::HashMap;
 use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::num::NonZeroUsize;
 use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
 
+use lru::LruCache;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tokio::io::{self, AsyncWriteExt, DuplexStream};
-use tokio::runtime::Runtime;
-use tokio::task::block_in_place;
+use tokio::runtime::Handle;
 
+use hayride_host_traits::ai::nn::repetition::{DEFAULT_MAX_REPEATS, DEFAULT_NGRAM};
 use hayride_host_traits::ai::{
-    BackendError, BackendExecutionContext, BackendGraph, BackendInner, ExecutionContext, Graph,
-    Tensor, TensorStream, TensorType,
+    AbortHandle, BackendError, BackendExecutionContextAsync, BackendGraph, BackendInner,
+    ExecutionContext, Graph, RepetitionDetector, Tensor, TensorStream, TensorType,
 };
+use hayride_host_traits::core::system::{Device, DeviceType};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PromptOptions {
     temperature: f32,
     num_context: i32,
@@ -22,8 +26,144 @@ pub struct PromptOptions {
     top_k: i32,
     top_p: f32,
     seed: u32,
+    // Constrain sampling to JSON and validate the output on the host
+    #[serde(default)]
+    json_mode: bool,
+    // Number of times to re-ask the model when the output is not valid JSON, 0 disables
+    // re-asking
+    #[serde(default)]
+    json_max_retries: Option<u32>,
+    // Keep the llama context alive between compute calls with the same session id
+    #[serde(default)]
+    session_id: String,
+    // Drop any cached state for the session before computing
+    #[serde(default)]
+    reset_session: bool,
+    // How generated bytes that are not valid UTF-8 are handled
+    #[serde(default)]
+    utf8_policy: Utf8Policy,
+    // Tokens in the n-grams checked for runaway repetition, 0 disables the check
+    #[serde(default)]
+    repetition_ngram: Option<usize>,
+    // Times an n-gram may repeat before generation is stopped, 0 disables the check
+    #[serde(default)]
+    repetition_max_repeats: Option<usize>,
+    // Bias added to the logits of tokens before sampling, a large negative bias bans a token
+    #[serde(default)]
+    logit_bias: Vec<(u32, f32)>,
 }
 
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Utf8Policy {
+    // Replace invalid bytes with U+FFFD
+    #[default]
+    Replace,
+    // Drop invalid bytes from the output
+    Skip,
+}
+
+// Buffers token pieces until they form complete UTF-8 sequences, as a single
+// character can be split across several tokens
+struct Utf8Buffer {
+    bytes: Vec<u8>,
+    policy: Utf8Policy,
+}
+
+impl Utf8Buffer {
+    fn new(policy: Utf8Policy) -> Self {
+        Self {
+            bytes: Vec::new(),
+            policy,
+        }
+    }
+
+    // Push the bytes of a token piece, returning the text that is complete
+    fn push(&mut self, piece: &[u8]) -> String {
+        self.bytes.extend_from_slice(piece);
+
+        let mut output = String::new();
+        loop {
+            match std::str::from_utf8(&self.bytes) {
+                Ok(text) => {
+                    output.push_str(text);
+                    self.bytes.clear();
+                    break;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    output.push_str(
+                        std::str::from_utf8(&self.bytes[..valid]).expect("bytes are valid utf8"),
+                    );
+                    match e.error_len() {
+                        // Incomplete sequence at the end, wait for the next piece
+                        None => {
+                            self.bytes.drain(..valid);
+                            break;
+                        }
+                        Some(len) => {
+                            log::debug!("invalid utf8 in generated output");
+                            self.invalid(&mut output);
+                            self.bytes.drain(..valid + len);
+                        }
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    // Flush an incomplete sequence left at the end of generation
+    fn finish(&mut self) -> String {
+        let mut output = String::new();
+        if !self.bytes.is_empty() {
+            log::debug!("incomplete utf8 sequence at end of generated output");
+            self.invalid(&mut output);
+            self.bytes.clear();
+        }
+        output
+    }
+
+    fn invalid(&self, output: &mut String) {
+        match self.policy {
+            Utf8Policy::Replace => output.push(char::REPLACEMENT_CHARACTER),
+            Utf8Policy::Skip => {}
+        }
+    }
+}
+
+// Default number of re-asks when json_mode is set and json_max_retries is not
+const DEFAULT_JSON_MAX_RETRIES: u32 = 2;
+
+// GBNF grammar for JSON, based on llama.cpp grammars/json.gbnf
+const JSON_GRAMMAR: &str = r#"root   ::= object
+value  ::= object | array | string | number | ("true" | "false" | "null") ws
+
+object ::=
+  "{" ws (
+            string ":" ws value
+    ("," ws string ":" ws value)*
+  )? "}" ws
+
+array  ::=
+  "[" ws (
+            value
+    ("," ws value)*
+  )? "]" ws
+
+string ::=
+  "\"" (
+    [^"\\\x7F\x00-\x1F] |
+    "\\" (["\\bfnrt] | "u" [0-9a-fA-F]{4}) # escapes
+  )* "\"" ws
+
+number ::= ("-"? ([0-9] | [1-9] [0-9]{0,15})) ("." [0-9]+)? ([eE] [-+]? [0-9] [1-9]{0,15})? ws
+
+# Optional space: by convention, applied in this grammar after literal chars when allowed
+ws ::= | " " | "\n" [ \t]{0,20}
+"#;
+
 // RAII wrapper for llama context to ensure proper cleanup
 struct LlamaContextGuard {
     context: *mut hayride_llama_rs_sys::llama_context,
@@ -53,6 +193,9 @@ impl LlamaContextGuard {
     }
 }
 
+// Needed because the context pointer is kept by sessions across compute calls
+unsafe impl Send for LlamaContextGuard {}
+
 impl Drop for LlamaContextGuard {
     fn drop(&mut self) {
         if !self.context.is_null() {
@@ -94,6 +237,56 @@ impl Drop for LlamaSamplerGuard {
     }
 }
 
+// A llama context kept alive between compute calls so the KV cache can be reused
+struct LlamaSession {
+    context: LlamaContextGuard,
+    // Tokens that have been decoded into the KV cache, in position order
+    tokens: Vec<i32>,
+    num_context: i32,
+    batch_size: i32,
+}
+
+// Sessions kept by an execution context, each holds a context with its KV cache
+const MAX_SESSIONS: NonZeroUsize = NonZeroUsize::new(8).unwrap();
+
+// Sessions owned by an execution context, keyed by the session id set in options.
+// The least recently used session is freed once MAX_SESSIONS are kept.
+#[derive(Clone)]
+struct LlamaSessions {
+    sessions: Arc<Mutex<LruCache<String, LlamaSession>>>,
+}
+
+impl Default for LlamaSessions {
+    fn default() -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(LruCache::new(MAX_SESSIONS))),
+        }
+    }
+}
+
+impl LlamaSessions {
+    // Take the session out of the cache while it is in use, it is returned with `put`
+    fn take(&self, session_id: &str) -> Option<LlamaSession> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.pop(session_id)
+    }
+
+    fn put(&self, session_id: String, session: LlamaSession) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((evicted, _)) = sessions.push(session_id.clone(), session) {
+            if evicted != session_id {
+                log::debug!("evicted least recently used llama session: {}", evicted);
+            }
+        }
+    }
+
+    fn reset(&self, session_id: &str) {
+        if self.take(session_id).is_some() {
+            log::debug!("reset llama session: {}", session_id);
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct LlamaCppBackend {
     models: HashMap<String, NonNull<hayride_llama_rs_sys::llama_model>>,
@@ -165,6 +358,45 @@ impl Drop for LlamaCppBackend {
     }
 }
 
+/// Devices llama.cpp can run models on, with their free memory right now.
+pub fn devices() -> Vec<Device> {
+    let count = unsafe { hayride_llama_rs_sys::ggml_backend_dev_count() };
+    (0..count)
+        .filter_map(|index| {
+            // SAFETY: The index is below the device count, devices are never freed.
+            let device = unsafe { hayride_llama_rs_sys::ggml_backend_dev_get(index) };
+            if device.is_null() {
+                return None;
+            }
+            let mut props: hayride_llama_rs_sys::ggml_backend_dev_props =
+                unsafe { std::mem::zeroed() };
+            unsafe { hayride_llama_rs_sys::ggml_backend_dev_get_props(device, &mut props) };
+
+            let device_type = match props.type_ {
+                hayride_llama_rs_sys::GGML_BACKEND_DEVICE_TYPE_CPU => DeviceType::Cpu,
+                hayride_llama_rs_sys::GGML_BACKEND_DEVICE_TYPE_GPU => DeviceType::Gpu,
+                _ => DeviceType::Accelerator,
+            };
+            Some(Device {
+                name: device_string(props.name),
+                description: device_string(props.description),
+                device_type,
+                memory_total: props.memory_total as u64,
+                memory_free: props.memory_free as u64,
+            })
+        })
+        .collect()
+}
+
+fn device_string(text: *const c_char) -> String {
+    if text.is_null() {
+        return String::new();
+    }
+    // SAFETY: Device properties are NUL-terminated C Strings.
+    let text = unsafe { CStr::from_ptr(text) };
+    String::from_utf8_lossy(text.to_bytes()).to_string()
+}
+
 impl BackendInner for LlamaCppBackend {
     fn load(&mut self, name: String) -> Result<Graph, BackendError> {
         log::debug!("loading LlamaCpp model: {}", name);
@@ -224,14 +456,108 @@ impl Drop for LlamaCppGraph {
 
 impl BackendGraph for LlamaCppGraph {
     fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
-        let context: Box<dyn BackendExecutionContext> =
-            Box::new(LlamaCppExecutionContext { model: self.model });
+        let context: Box<dyn BackendExecutionContextAsync> = Box::new(LlamaCppExecutionContext {
+            model: self.model,
+            sessions: LlamaSessions::default(),
+        });
         return Ok(context.into());
     }
+
+    fn tokenize(&self, text: &str) -> Result<Vec<u32>, BackendError> {
+        let c_string = CString::new(text).map_err(|_| BackendError::FailedTokenization)?;
+        let text_len = c_int::try_from(c_string.as_bytes().len())
+            .map_err(|_| BackendError::FailedTokenization)?;
+        let mut tokens: Vec<hayride_llama_rs_sys::llama_token> =
+            vec![0; self.tokenize_count(text)?];
+        let size = unsafe {
+            let llama_vocab = hayride_llama_rs_sys::llama_model_get_vocab(self.model.as_ptr());
+            hayride_llama_rs_sys::llama_tokenize(
+                llama_vocab,
+                c_string.as_ptr(),
+                text_len,
+                tokens.as_mut_ptr(),
+                c_int::try_from(tokens.len()).map_err(|_| BackendError::FailedTokenization)?,
+                false, // Tokens of the text alone, prompts add the BOT token once
+                true,  // Tokenize control tokens
+            )
+        };
+        let size = usize::try_from(size).map_err(|_| BackendError::FailedTokenization)?;
+        tokens.truncate(size);
+        Ok(tokens.into_iter().map(|token| token as u32).collect())
+    }
+
+    fn detokenize(&self, tokens: &[u32]) -> Result<String, BackendError> {
+        let llama_vocab =
+            unsafe { hayride_llama_rs_sys::llama_model_get_vocab(self.model.as_ptr()) };
+        // Ids outside the vocab would read past its tables
+        let n_vocab = unsafe { hayride_llama_rs_sys::llama_vocab_n_tokens(llama_vocab) };
+        let tokens = tokens
+            .iter()
+            .map(|token| i32::try_from(*token).ok().filter(|token| *token < n_vocab))
+            .collect::<Option<Vec<hayride_llama_rs_sys::llama_token>>>()
+            .ok_or(BackendError::FailedDecoding)?;
+        let n_tokens = c_int::try_from(tokens.len()).map_err(|_| BackendError::FailedDecoding)?;
+
+        let detokenize = |text: &mut Vec<u8>| -> Result<i32, BackendError> {
+            let text_len = c_int::try_from(text.len()).map_err(|_| BackendError::FailedDecoding)?;
+            Ok(unsafe {
+                hayride_llama_rs_sys::llama_detokenize(
+                    llama_vocab,
+                    tokens.as_ptr(),
+                    n_tokens,
+                    text.as_mut_ptr() as *mut c_char,
+                    text_len,
+                    false, // Keep the BOS and EOS tokens that were passed
+                    true,  // Render special tokens
+                )
+            })
+        };
+        let mut text = vec![0u8; tokens.len() * 8];
+        let mut n = detokenize(&mut text)?;
+        if n < 0 {
+            // Too small a buffer, llama returns the negated length of the text
+            text.resize(n.unsigned_abs() as usize, 0);
+            n = detokenize(&mut text)?;
+        }
+        let n = usize::try_from(n).map_err(|_| BackendError::FailedDecoding)?;
+        text.truncate(n);
+        // Tokens can end in the middle of a character
+        Ok(String::from_utf8_lossy(&text).to_string())
+    }
+
+    fn tokenize_count(&self, text: &str) -> Result<usize, BackendError> {
+        let c_string = CString::new(text).map_err(|_| BackendError::FailedTokenization)?;
+        let n_tokens = unsafe {
+            let llama_vocab = hayride_llama_rs_sys::llama_model_get_vocab(self.model.as_ptr());
+            hayride_llama_rs_sys::llama_tokenize(
+                llama_vocab,
+                c_string.as_ptr(),
+                c_int::try_from(c_string.as_bytes().len())
+                    .map_err(|_| BackendError::FailedTokenization)?,
+                std::ptr::null_mut(),
+                0,
+                false, // Count the text alone, prompts add the BOT token once
+                true,  // Tokenize control tokens
+            )
+        };
+        // Without a buffer llama returns the negated number of tokens, or the minimum of
+        // the int when the count overflows it
+        n_tokens
+            .checked_neg()
+            .and_then(|n_tokens| usize::try_from(n_tokens).ok())
+            .ok_or(BackendError::FailedTokenization)
+    }
+
+    fn context_size(&self) -> Option<usize> {
+        let n_ctx_train =
+            unsafe { hayride_llama_rs_sys::llama_model_n_ctx_train(self.model.as_ptr()) };
+        usize::try_from(n_ctx_train).ok().filter(|n| *n > 0)
+    }
 }
 
 struct LlamaCppExecutionContext {
     model: NonNull<hayride_llama_rs_sys::llama_model>,
+    sessions: LlamaSessions,
 }
 
 // Needed because NonNull pointer is not Send/Sync
@@ -246,8 +572,9 @@ impl Drop for LlamaCppExecutionContext {
     }
 }
 
-impl BackendExecutionContext for LlamaCppExecutionContext {
-    fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError> {
+#[async_trait::async_trait]
+impl BackendExecutionContextAsync for LlamaCppExecutionContext {
+    async fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError> {
         let graph = LlamaCppGraph { model: self.model };
         let mut options_tensor = None;
         let mut input_tensor = None;
@@ -272,10 +599,20 @@ impl BackendExecutionContext for LlamaCppExecutionContext {
             );
         }
 
-        let mut result = process_compute(graph, input_tensor, options_tensor, None)?;
+        let options = parse_options(options_tensor)?;
+        let sessions = self.sessions.clone();
 
-        // Trim whitespace off of result
-        result = result.trim().to_string();
+        // Inference is CPU bound, run it on the blocking pool to keep the runtime free
+        let result = tokio::task::spawn_blocking(move || match options {
+            Some(ref o) if o.json_mode => {
+                process_compute_json(graph, &sessions, input_tensor, o.clone(), None, None)
+            }
+            _ => process_compute(graph, &sessions, input_tensor, options, None, None),
+        })
+        .await
+        .map_err(|_| BackendError::Unknown)??;
+
+        // Output is trimmed and otherwise post-processed by the host
         log::debug!("setting result tensor with data: [{}]", result);
 
         // Build result tensor manually
@@ -288,7 +625,7 @@ impl BackendExecutionContext for LlamaCppExecutionContext {
         Ok(result_tensor)
     }
 
-    fn compute_stream(
+    async fn compute_stream(
         &mut self,
         tensors: Vec<(String, Tensor)>,
     ) -> Result<TensorStream, BackendError> {
@@ -309,26 +646,135 @@ impl BackendExecutionContext for LlamaCppExecutionContext {
         let input_tensor: Tensor = input_tensor
             .clone()
             .ok_or(BackendError::FailedTensorNotSet)?;
-
-        tokio::task::spawn(async move {
+        let options = parse_options(options_tensor)?;
+        let sessions = self.sessions.clone();
+
+        // Generation runs detached from the stream, the host aborts it once the stream
+        // or its execution context is dropped
+        let abort = AbortHandle::new();
+        let task_abort = abort.clone();
+        tokio::task::spawn_blocking(move || {
             // Provide writer for async compute
-            let result = process_compute(graph, input_tensor, options_tensor, Some(writer));
-            if let Err(e) = result {
-                log::warn!("error in compute_stream: {:?}", e);
+            let result = match options {
+                // JSON output is validated before it is written, so it cannot be streamed by token
+                Some(ref o) if o.json_mode => process_compute_json(
+                    graph,
+                    &sessions,
+                    input_tensor,
+                    o.clone(),
+                    Some(writer),
+                    Some(&task_abort),
+                ),
+                _ => process_compute(
+                    graph,
+                    &sessions,
+                    input_tensor,
+                    options,
+                    Some(writer),
+                    Some(&task_abort),
+                ),
+            };
+            task_abort.finish();
+            match result {
+                Err(BackendError::Aborted) => log::debug!("compute_stream aborted"),
+                Err(e) => log::warn!("error in compute_stream: {:?}", e),
+                Ok(_) => {}
             }
         });
 
-        let tensor = TensorStream::new(vec![1], TensorType::U8, reader);
+        let tensor = TensorStream::new(vec![1], TensorType::U8, reader).with_abort(abort);
 
         Ok(tensor)
     }
 }
 
+// Parse the prompt options from the options tensor if set
+fn parse_options(options: Option<Tensor>) -> Result<Option<PromptOptions>, BackendError> {
+    match options {
+        Some(tensor) => {
+            let options_str =
+                String::from_utf8(tensor.data.clone()).map_err(|_| BackendError::FailedDecoding)?;
+            let options: PromptOptions =
+                serde_json::from_str(&options_str).map_err(|_| BackendError::FailedDecoding)?;
+            Ok(Some(options))
+        }
+        None => Ok(None),
+    }
+}
+
+// Run a grammar constrained compute and validate the result is JSON, re-asking the model
+// up to json_max_retries times. The normalized JSON is written to the writer once valid.
+fn process_compute_json(
+    graph: LlamaCppGraph,
+    sessions: &LlamaSessions,
+    input: Tensor,
+    mut options: PromptOptions,
+    writer: Option<DuplexStream>,
+    abort: Option<&AbortHandle>,
+) -> Result<String, BackendError> {
+    let max_retries = options.json_max_retries.unwrap_or(DEFAULT_JSON_MAX_RETRIES);
+
+    for attempt in 0..=max_retries {
+        let attempt_graph = LlamaCppGraph { model: graph.model };
+        let output = match process_compute(
+            attempt_graph,
+            sessions,
+            input.clone(),
+            Some(options.clone()),
+            None,
+            abort,
+        ) {
+            Ok(output) => output,
+            // Nobody is reading the output anymore
+            Err(BackendError::Aborted) => return Err(BackendError::Aborted),
+            Err(e) => {
+                if let Some(writer) = writer {
+                    write_output(writer, &e.to_string())?;
+                }
+                return Err(e);
+            }
+        };
+
+        match serde_json::from_str::<serde_json::Value>(output.trim()) {
+            Ok(value) => {
+                let json = value.to_string();
+                if let Some(writer) = writer {
+                    write_output(writer, &json)?;
+                }
+                return Ok(json);
+            }
+            Err(e) => {
+                log::warn!(
+                    "json mode attempt {} of {} produced invalid json: {}",
+                    attempt + 1,
+                    max_retries + 1,
+                    e
+                );
+
+                // Greedy sampling would produce the same output again, so
+                // re-ask with a new seed and a small temperature
+                options.seed = options.seed.wrapping_add(1).max(1);
+                if options.temperature <= 0.0 {
+                    options.temperature = 0.2;
+                }
+            }
+        }
+    }
+
+    // If Writer set, write error to the buffer, blocking while we write to the stream
+    if let Some(writer) = writer {
+        write_output(writer, &BackendError::FailedInvalidJson.to_string())?;
+    }
+    Err(BackendError::FailedInvalidJson)
+}
+
 fn process_compute(
     graph: LlamaCppGraph,
+    sessions: &LlamaSessions,
     input: Tensor,
-    options: Option<Tensor>,
+    options: Option<PromptOptions>,
     mut writer: Option<DuplexStream>,
+    abort: Option<&AbortHandle>,
 ) -> Result<String, BackendError> {
     let start = std::time::Instant::now();
     let llama_model = graph.get_model();
@@ -348,12 +794,14 @@ fn process_compute(
     let penalty_presence = 0.5;
     let mut rng = rand::rng(); // Default random seed
     let mut seed: u32 = rng.random();
+    let mut json_mode = false;
+    let mut session_id = None;
+    let mut utf8_policy = Utf8Policy::default();
+    let mut repetition_ngram = DEFAULT_NGRAM;
+    let mut repetition_max_repeats = DEFAULT_MAX_REPEATS;
+    let mut logit_bias = Vec::new();
     match options {
-        Some(tensor) => {
-            let options_str =
-                String::from_utf8(tensor.data.clone()).map_err(|_| BackendError::FailedDecoding)?;
-            let options: PromptOptions =
-                serde_json::from_str(&options_str).map_err(|_| BackendError::FailedDecoding)?;
+        Some(options) => {
             if options.num_context != 0 {
                 num_context = options.num_context;
 
@@ -380,10 +828,31 @@ fn process_compute(
 
             temperature = options.temperature;
             top_p = options.top_p;
+            json_mode = options.json_mode;
+            utf8_policy = options.utf8_policy;
+            if let Some(ngram) = options.repetition_ngram {
+                repetition_ngram = ngram;
+            }
+            if let Some(max_repeats) = options.repetition_max_repeats {
+                repetition_max_repeats = max_repeats;
+            }
+            logit_bias = options.logit_bias;
+
+            if !options.session_id.is_empty() {
+                if options.reset_session {
+                    sessions.reset(&options.session_id);
+                }
+                session_id = Some(options.session_id);
+            }
         }
         None => {}
     }
 
+    // Reuse the context of an existing session, keeping the decoded tokens in its KV cache
+    let session = session_id.as_deref().and_then(|id| sessions.take(id));
+    let mut cached_tokens: Vec<i32> = Vec::new();
+    let mut cache_valid = true;
+
     let mut context_params: hayride_llama_rs_sys::llama_context_params =
         unsafe { hayride_llama_rs_sys::llama_context_default_params() };
     context_params.n_batch = batch_size as u32; // size of the logits and embeddings buffer, which limits the maximum batch size passed to llama_decode
@@ -392,17 +861,37 @@ fn process_compute(
                                    // context_params.n_threads = 8; // number of threads to use for computation
     log::debug!("context params: {:?}", context_params);
 
-    // Create context
-    let llama_context_ptr: *mut hayride_llama_rs_sys::llama_context = unsafe {
-        hayride_llama_rs_sys::llama_new_context_with_model(llama_model.as_ptr(), context_params)
-    };
+    let mut llama_context = match session {
+        Some(session) => {
+            log::debug!(
+                "reusing llama session with {} cached tokens",
+                session.tokens.len()
+            );
+            // The context was created with the session's sizes
+            num_context = session.num_context;
+            batch_size = session.batch_size;
+            context_params.n_ctx = num_context as u32;
+            context_params.n_batch = batch_size as u32;
+            cached_tokens = session.tokens;
+            session.context
+        }
+        None => {
+            // Create context
+            let llama_context_ptr: *mut hayride_llama_rs_sys::llama_context = unsafe {
+                hayride_llama_rs_sys::llama_new_context_with_model(
+                    llama_model.as_ptr(),
+                    context_params,
+                )
+            };
 
-    // Use RAII wrapper to ensure cleanup
-    let mut llama_context = LlamaContextGuard::new(llama_context_ptr).ok_or_else(|| {
-        let error_msg = "Failed to create llama context - possibly out of memory";
-        log::error!("{}", error_msg);
-        BackendError::FailedToLoadModel
-    })?;
+            // Use RAII wrapper to ensure cleanup
+            LlamaContextGuard::new(llama_context_ptr).ok_or_else(|| {
+                let error_msg = "Failed to create llama context - possibly out of memory";
+                log::error!("{}", error_msg);
+                BackendError::FailedToLoadModel
+            })?
+        }
+    };
 
     // Tokenize the prompt
     let prompt: Vec<u8> = input.data.clone();
@@ -465,7 +954,40 @@ fn process_compute(
         return Err(BackendError::FailedTokenization);
     }
 
+    let size = usize::try_from(prompt_size).expect("size is positive and usize ");
+    // Safety: `size` < `capacity` and llama-cpp has initialized elements up to `size`
+    unsafe { prompt_tokens.set_len(size) }
+
+    // Find the prefix of the prompt that is already in the KV cache, always
+    // decoding at least the last prompt token so there are logits to sample from
+    let mut n_cached = cached_tokens
+        .iter()
+        .zip(prompt_tokens.iter())
+        .take_while(|(cached, token)| cached == token)
+        .count()
+        .min(prompt_tokens.len().saturating_sub(1));
+    if n_cached < cached_tokens.len() {
+        let removed = unsafe {
+            hayride_llama_rs_sys::llama_kv_self_seq_rm(
+                llama_context.as_ptr(),
+                0,
+                n_cached as i32,
+                -1,
+            )
+        };
+        if !removed {
+            // Partial removal is not supported by every model, start over
+            llama_context.clear_kv_cache();
+            n_cached = 0;
+        }
+    }
+    cached_tokens.truncate(n_cached);
+    if n_cached > 0 {
+        log::debug!("reusing {} cached prompt tokens", n_cached);
+    }
+
     // Handle context too large by dynamically adjusting batch size or truncating prompt
+    let prompt_size = prompt_size - n_cached as i32;
     if prompt_size >= batch_size {
         log::warn!(
             "Prompt size ({}) exceeds batch size ({}), attempting to handle...",
@@ -473,8 +995,9 @@ fn process_compute(
             batch_size
         );
 
-        // Strategy 1: Try to increase batch size if within context limits
-        let new_batch_size = std::cmp::min(prompt_size + 512, num_context);
+        // Strategy 1: Try to increase batch size if within context limits,
+        // the new context has to fit the whole prompt as nothing is cached
+        let new_batch_size = std::cmp::min(prompt_tokens.len() as i32 + 512, num_context);
         if new_batch_size <= num_context && new_batch_size > batch_size {
             log::info!(
                 "Increasing batch size from {} to {} to accommodate prompt",
@@ -486,8 +1009,10 @@ fn process_compute(
             // Recreate context with new batch size
             context_params.n_batch = batch_size as u32;
 
-            // Drop the old context and create a new one
+            // Drop the old context and create a new one, the cached prefix is lost with it
             drop(llama_context);
+            cached_tokens.clear();
+            n_cached = 0;
             let new_llama_context_ptr: *mut hayride_llama_rs_sys::llama_context = unsafe {
                 hayride_llama_rs_sys::llama_new_context_with_model(
                     llama_model.as_ptr(),
@@ -511,16 +1036,19 @@ fn process_compute(
 
             // Truncate from the beginning, keeping the end of the prompt
             let truncate_amount = prompt_size - max_prompt_tokens;
-            prompt_tokens.drain(0..truncate_amount as usize);
+            prompt_tokens.drain(0..truncate_amount as usize + n_cached);
+
+            // The truncated prompt no longer matches the cached prefix
+            if n_cached > 0 {
+                llama_context.clear_kv_cache();
+                cached_tokens.clear();
+                n_cached = 0;
+            }
 
             log::info!("Prompt truncated, new size: {} tokens", prompt_tokens.len());
         }
     }
 
-    let size = usize::try_from(prompt_size).expect("size is positive and usize ");
-    // Safety: `size` < `capacity` and llama-cpp has initialized elements up to `size`
-    unsafe { prompt_tokens.set_len(size) }
-
     // initialize the sampler
     // https://github.com/ggerganov/llama.cpp/blob/master/examples/simple/simple.cpp#L118
 
@@ -534,6 +1062,59 @@ fn process_compute(
         log::error!("Failed to create llama sampler");
         BackendError::FailedToLoadModel
     })?;
+
+    // Constrain the output to JSON, grammar is applied before any other samplers
+    if json_mode {
+        let grammar = CString::new(JSON_GRAMMAR).expect("grammar has no null bytes");
+        let root = CString::new("root").expect("root has no null bytes");
+        let grammar_sampler = unsafe {
+            hayride_llama_rs_sys::llama_sampler_init_grammar(
+                llama_vocab,
+                grammar.as_ptr(),
+                root.as_ptr(),
+            )
+        };
+        if grammar_sampler.is_null() {
+            log::error!("Failed to create json grammar sampler");
+            return Err(BackendError::FailedToLoadModel);
+        }
+        unsafe {
+            hayride_llama_rs_sys::llama_sampler_chain_add(llama_sampler.as_ptr(), grammar_sampler);
+        }
+    }
+
+    // Bias tokens before they are picked, so a banned token is never sampled
+    if !logit_bias.is_empty() {
+        let n_vocab = unsafe { hayride_llama_rs_sys::llama_vocab_n_tokens(llama_vocab) };
+        let biases = logit_bias
+            .iter()
+            .map(|(token, bias)| {
+                i32::try_from(*token)
+                    .ok()
+                    .filter(|token| *token < n_vocab)
+                    .map(|token| hayride_llama_rs_sys::llama_logit_bias { token, bias: *bias })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                log::error!("logit bias for a token that is not in the vocab");
+                BackendError::InvalidArgument
+            })?;
+        let bias_sampler = unsafe {
+            hayride_llama_rs_sys::llama_sampler_init_logit_bias(
+                n_vocab,
+                c_int::try_from(biases.len()).map_err(|_| BackendError::InvalidArgument)?,
+                biases.as_ptr(),
+            )
+        };
+        if bias_sampler.is_null() {
+            log::error!("Failed to create logit bias sampler");
+            return Err(BackendError::InvalidArgument);
+        }
+        unsafe {
+            hayride_llama_rs_sys::llama_sampler_chain_add(llama_sampler.as_ptr(), bias_sampler);
+        }
+    }
+
     unsafe {
         // Add sampler params for temp
         if temperature > 0.0 {
@@ -571,11 +1152,14 @@ fn process_compute(
     log::debug!("final prompt context size: {}", prompt_tokens.len());
 
     // prepare a batch for the prompt (use actual length after potential truncation)
-    let mut batch = LlamaBatch::new(prompt_tokens.len());
+    let mut batch = LlamaBatch::new(prompt_tokens.len() - n_cached);
+
+    // Tokens in the current batch, added to the cached tokens once decoded
+    let mut pending_tokens: Vec<i32> = prompt_tokens[n_cached..].to_vec();
 
-    // Add tokens to batch
+    // Add tokens to batch, skipping the prefix already in the KV cache
     let last_index: i32 = (prompt_tokens.len() - 1) as i32;
-    for (i, token) in (0_i32..).zip(prompt_tokens.iter()) {
+    for (i, token) in (n_cached as i32..).zip(pending_tokens.iter()) {
         let is_last = i == last_index;
         match batch.add(*token, i, &[0], is_last) {
             Ok(_) => {}
@@ -594,11 +1178,19 @@ fn process_compute(
     let start_time = unsafe { hayride_llama_rs_sys::ggml_time_us() };
     let mut n_decoded = 0;
 
-    let mut position = 0;
+    let mut position = n_cached as i32;
     let mut result: String = "".to_owned();
+    let mut utf8_buffer = Utf8Buffer::new(utf8_policy);
+    let mut repetition = RepetitionDetector::new(repetition_ngram, repetition_max_repeats);
     let actual_prompt_size = prompt_tokens.len() as i32;
 
     while position + batch.n_tokens() < actual_prompt_size + max_predict {
+        // Stop generating once the output is no longer wanted
+        if abort.is_some_and(|abort| abort.is_aborted()) {
+            log::debug!("generation aborted after {} tokens", n_decoded);
+            return Err(BackendError::Aborted);
+        }
+
         // Check if we're approaching context limits and need to manage memory
         if position > num_context - 1000 {
             // Leave 1000 tokens buffer
@@ -619,6 +1211,7 @@ fn process_compute(
                     log::warn!("llama_decode failed with error -3 (likely memory/context issue), attempting recovery");
                     // Try clearing KV cache and retrying once
                     llama_context.clear_kv_cache();
+                    cache_valid = false;
                     let retry_res = unsafe {
                         hayride_llama_rs_sys::llama_decode(llama_context.as_ptr(), batch.batch())
                     };
@@ -648,6 +1241,7 @@ fn process_compute(
         }
 
         position += batch.n_tokens();
+        cached_tokens.append(&mut pending_tokens);
 
         // sample the next token
         {
@@ -664,6 +1258,19 @@ fn process_compute(
                 break;
             }
 
+            // Stop runaway generations stuck repeating themselves
+            if repetition.push(new_token_id) {
+                log::warn!(
+                    "generation stopped on repetition after {} tokens",
+                    n_decoded
+                );
+                // If Writer set, write error to the buffer, blocking while we write to the stream
+                if let Some(writer) = writer {
+                    write_output(writer, &BackendError::Repetition.to_string())?;
+                }
+                return Err(BackendError::Repetition);
+            }
+
             let string = CString::new(vec![b'*'; 32]).expect("no null");
             let len = string.as_bytes().len();
             let len = c_int::try_from(len).expect("length fits into c_int");
@@ -690,22 +1297,14 @@ fn process_compute(
             let mut bytes = string.into_bytes();
             let len = usize::try_from(n).expect("size is positive and fits into usize");
             bytes.truncate(len);
-            // convert bytes to string
-            let output = String::from_utf8(bytes).map_err(|_| BackendError::FailedTokenization);
-            let output = match output {
-                Ok(s) => s,
-                Err(e) => {
-                    // If Writer set, write error to the buffer, blocking while we write to the stream
-                    if let Some(writer) = writer {
-                        write_output(writer, &e.to_string())?;
-                    }
-                    return Err(e);
-                }
-            };
+            // convert bytes to string, holding back incomplete sequences
+            let output = utf8_buffer.push(&bytes);
 
             // If Writer set, Write to the buffer, blocking while we write to the stream
             if let Some(ref mut writer) = writer {
-                write_output(writer, &output)?;
+                if !output.is_empty() {
+                    write_output(writer, &output)?;
+                }
             }
 
             // Push output for result
@@ -713,6 +1312,7 @@ fn process_compute(
 
             // prepare the next batch with the sampled token
             batch.clear();
+            pending_tokens.push(new_token_id);
             match batch.add(new_token_id, position, &[0], true) {
                 Ok(_) => {}
                 Err(e) => {
@@ -731,6 +1331,7 @@ fn process_compute(
                     position
                 );
                 llama_context.clear_kv_cache();
+                cache_valid = false;
 
                 // Reset position to prevent overflow
                 position = actual_prompt_size;
@@ -741,6 +1342,15 @@ fn process_compute(
         }
     }
 
+    // Flush any incomplete sequence left in the buffer
+    let output = utf8_buffer.finish();
+    if !output.is_empty() {
+        if let Some(ref mut writer) = writer {
+            write_output(writer, &output)?;
+        }
+        result.push_str(&output);
+    }
+
     let end_time = unsafe { hayride_llama_rs_sys::ggml_time_us() };
 
     let duration = start.elapsed();
@@ -752,6 +1362,24 @@ fn process_compute(
         duration.as_millis()
     );
 
+    // Keep the context alive for the next compute call in the session
+    if let Some(session_id) = session_id {
+        if !cache_valid {
+            // The KV cache no longer lines up with the decoded tokens
+            llama_context.clear_kv_cache();
+            cached_tokens.clear();
+        }
+        sessions.put(
+            session_id,
+            LlamaSession {
+                context: llama_context,
+                tokens: cached_tokens,
+                num_context,
+                batch_size,
+            },
+        );
+    }
+
     // RAII wrappers will automatically free the sampler and context when they go out of scope
 
     return Ok(result);
@@ -851,19 +1479,17 @@ impl Drop for LlamaBatch {
 }
 
 // write the output string to the writer blocking the thread
+// Must be called from the blocking pool, where the runtime handle is available
 // Can be used to write output or errors to the stream
 // Returns BackendError::FailedToWriteOutput on failure
 fn write_output<W: tokio::io::AsyncWrite + Unpin>(
     mut writer: W,
     output: &str,
 ) -> Result<(), BackendError> {
-    block_in_place(|| {
-        let rt = Runtime::new().map_err(|_| BackendError::FailedToWriteOutput)?;
-        rt.block_on(async {
-            writer
-                .write_all(output.as_bytes())
-                .await
-                .map_err(|_| BackendError::FailedToWriteOutput)
-        })
+    Handle::current().block_on(async {
+        writer
+            .write_all(output.as_bytes())
+            .await
+            .map_err(|_| BackendError::FailedToWriteOutput)
     })
 }
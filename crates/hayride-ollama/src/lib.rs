@@ -0,0 +1,271 @@
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use hayride_host_traits::ai::model::{
+    DownloadEvent, DownloadObserver, ErrorCode, ModelFilter, ModelRepositoryInner, RemoteFile,
+    RemoteRepository,
+};
+
+/// Endpoint of a local Ollama instance.
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:11434";
+
+// Ollama models are gguf files in its blob store
+const MODEL_EXTENSION: &str = "gguf";
+// Report pull progress at most once per this many percent of a layer
+const PROGRESS_STEP: u64 = 5;
+
+// Endpoint from the runtime configuration, the repository is only used when set
+static ENDPOINT: RwLock<Option<String>> = RwLock::new(None);
+
+/// Serve models from the Ollama instance at the endpoint in repositories created
+/// afterwards, e.g. from the runtime configuration.
+pub fn configure(endpoint: Option<String>) {
+    if let Ok(mut configured) = ENDPOINT.write() {
+        *configured = endpoint
+            .map(|endpoint| endpoint.trim().trim_end_matches('/').to_string())
+            .filter(|endpoint| !endpoint.is_empty());
+    }
+}
+
+/// The endpoint of the Ollama instance models are served from, if one was configured.
+pub fn configured() -> Option<String> {
+    ENDPOINT.read().ok().and_then(|endpoint| endpoint.clone())
+}
+
+/// Serves the models of an Ollama instance.
+///
+/// Models are named like in Ollama, e.g. `llama3.2` or `llama3.2:1b`, and resolve to the
+/// gguf blob Ollama stores them in, so they are loaded without being downloaded again.
+/// The blob store has to be readable by hayride, i.e. Ollama runs on the same machine.
+pub struct OllamaModelRepository {
+    endpoint: String,
+    agent: ureq::Agent,
+    observer: Option<Arc<dyn DownloadObserver>>,
+}
+
+impl OllamaModelRepository {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            // Pulls take as long as they take, only stalls time out
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(5))
+                .timeout_read(Duration::from_secs(60))
+                .build(),
+            observer: None,
+        }
+    }
+
+    /// Report the lifecycle of model pulls to the observer.
+    pub fn with_observer(mut self, observer: Arc<dyn DownloadObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    fn emit(&self, event: DownloadEvent) {
+        if let Some(observer) = &self.observer {
+            observer.event(event);
+        }
+    }
+
+    // Call the api, e.g. `tags`, with a json body unless it is a get
+    fn call(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<ureq::Response, ErrorCode> {
+        let request = self
+            .agent
+            .request(method, &format!("{}/api/{}", self.endpoint, path));
+        let result = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(404, _)) => Err(ErrorCode::ModelNotFound),
+            Err(ureq::Error::Status(400, response)) => {
+                log::warn!(
+                    "ollama rejected '{}': {}",
+                    path,
+                    response.into_string().unwrap_or_default()
+                );
+                Err(ErrorCode::InvalidModelName)
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to call ollama at {} for '{}': {}",
+                    self.endpoint,
+                    path,
+                    e
+                );
+                Err(ErrorCode::RuntimeError)
+            }
+        }
+    }
+
+    fn call_json(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value, ErrorCode> {
+        self.call(method, path, body)?.into_json().map_err(|e| {
+            log::error!("Failed to read ollama response for '{}': {}", path, e);
+            ErrorCode::RuntimeError
+        })
+    }
+
+    // Pull the model, forwarding the progress Ollama streams as json lines
+    fn pull(&self, name: &str) -> Result<(), ErrorCode> {
+        let response = self.call(
+            "POST",
+            "pull",
+            Some(json!({ "model": name, "stream": true })),
+        )?;
+
+        let mut reported = 0;
+        for line in std::io::BufReader::new(response.into_reader()).lines() {
+            let line = line.map_err(|e| {
+                log::error!("Failed to read ollama pull of '{}': {}", name, e);
+                ErrorCode::RuntimeError
+            })?;
+            let Ok(status) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if let Some(error) = status.get("error").and_then(|e| e.as_str()) {
+                log::error!("Failed to pull '{}' with ollama: {}", name, error);
+                return Err(ErrorCode::RuntimeError);
+            }
+
+            let total = status.get("total").and_then(|v| v.as_u64());
+            let completed = status.get("completed").and_then(|v| v.as_u64());
+            if let (Some(total), Some(completed)) = (total, completed) {
+                // Every layer starts over at 0
+                let step = (total * PROGRESS_STEP / 100).max(1);
+                if completed < reported || completed - reported >= step || completed == total {
+                    reported = completed;
+                    self.emit(DownloadEvent::Progress {
+                        name: name.to_string(),
+                        downloaded: completed,
+                        total,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ModelRepositoryInner for OllamaModelRepository {
+    // Pull a model by tag, models that are already pulled are not pulled again
+    fn download(&mut self, name: String) -> Result<String, ErrorCode> {
+        if let Ok(path) = self.get(name.clone()) {
+            return Ok(path);
+        }
+
+        self.emit(DownloadEvent::Started { name: name.clone() });
+        let path = self.pull(&name).and_then(|_| self.get(name.clone()));
+        match &path {
+            Ok(path) => self.emit(DownloadEvent::Verified {
+                name,
+                path: path.clone(),
+                size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            }),
+            Err(error) => self.emit(DownloadEvent::Failed {
+                name,
+                error: error.to_string(),
+            }),
+        }
+        path
+    }
+
+    fn get(&self, name: String) -> Result<String, ErrorCode> {
+        if name.trim().is_empty() {
+            return Err(ErrorCode::InvalidModelName);
+        }
+
+        let show = self.call_json("POST", "show", Some(json!({ "model": name })))?;
+        let modelfile = show
+            .get("modelfile")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default();
+        let Some(path) = blob_path(modelfile) else {
+            log::warn!("ollama model '{}' has no model file", name);
+            return Err(ErrorCode::ModelNotFound);
+        };
+
+        if !Path::new(path).is_file() {
+            log::warn!(
+                "blob of ollama model '{}' is not readable at {}, is ollama running on another machine?",
+                name,
+                path
+            );
+            return Err(ErrorCode::ModelNotFound);
+        }
+
+        Ok(path.to_string())
+    }
+
+    fn delete(&mut self, name: String) -> Result<(), ErrorCode> {
+        self.call("DELETE", "delete", Some(json!({ "model": name })))?;
+        Ok(())
+    }
+
+    fn list(&self, filter: &ModelFilter) -> Result<Vec<String>, ErrorCode> {
+        let is_gguf = filter.extension.as_deref().is_none_or(|extension| {
+            extension
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(MODEL_EXTENSION)
+        });
+        if !is_gguf {
+            return Ok(vec![]);
+        }
+
+        let tags = self.call_json("GET", "tags", None)?;
+        let mut models: Vec<String> = tags
+            .get("models")
+            .and_then(|models| models.as_array())
+            .ok_or(ErrorCode::RuntimeError)?
+            .iter()
+            .filter_map(|model| model.get("name")?.as_str())
+            // The repo of a model is its name without the tag
+            .filter(|name| {
+                let repo = name.split_once(':').map_or(*name, |(repo, _)| repo);
+                filter.repo.as_deref().is_none_or(|r| r == repo)
+            })
+            .map(String::from)
+            .collect();
+
+        models.sort();
+        Ok(models)
+    }
+
+    fn purge(&mut self) -> Result<u64, ErrorCode> {
+        // Ollama removes unused blobs itself
+        Ok(0)
+    }
+
+    fn search(&self, _query: String) -> Result<Vec<RemoteRepository>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+
+    fn list_remote(&self, _repo: String) -> Result<Vec<RemoteFile>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+}
+
+// Path of the model blob in a modelfile, the first `FROM` pointing at a file
+fn blob_path(modelfile: &str) -> Option<&str> {
+    modelfile
+        .lines()
+        .filter_map(|line| {
+            let (instruction, argument) = line.trim().split_once(char::is_whitespace)?;
+            instruction
+                .eq_ignore_ascii_case("FROM")
+                .then(|| argument.trim())
+        })
+        .find(|argument| Path::new(argument).is_absolute())
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Embed build details reported by hayride:core/version build-info
+fn main() {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+
+    // Builds outside a git checkout, i.e. docker, can set the commit explicitly
+    println!("cargo:rerun-if-env-changed=HAYRIDE_GIT_COMMIT");
+    println!(
+        "cargo:rerun-if-changed={}",
+        workspace.join(".git/HEAD").display()
+    );
+    let commit = std::env::var("HAYRIDE_GIT_COMMIT")
+        .ok()
+        .or_else(|| git_commit(&workspace))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=HAYRIDE_GIT_COMMIT={}", commit);
+
+    // Respect reproducible build timestamps when set
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    println!(
+        "cargo:rustc-env=HAYRIDE_BUILD_DATE={}",
+        format_rfc3339(timestamp)
+    );
+
+    let lockfile = workspace.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+    let wasmtime = locked_version(&lockfile, "wasmtime").unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=HAYRIDE_WASMTIME_VERSION={}", wasmtime);
+}
+
+fn git_commit(workspace: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(workspace)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_string())
+}
+
+// Find the resolved version of a package in the lockfile
+fn locked_version(lockfile: &Path, package: &str) -> Option<String> {
+    let lock = std::fs::read_to_string(lockfile).ok()?;
+    let name = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == name {
+            let version = lines.next()?.trim().strip_prefix("version = ")?;
+            return Some(version.trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+// Format epoch seconds as an RFC 3339 UTC timestamp
+fn format_rfc3339(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
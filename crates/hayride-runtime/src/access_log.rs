@@ -0,0 +1,197 @@
+use crate::middleware::{ClientAddr, HostResponse};
+use crate::session_output::{OutputOptions, SessionOutput};
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::body::{Body, Frame, SizeHint};
+use serde::Serialize;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use uuid::Uuid;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+static ACCESS_LOG: OnceLock<AccessLog> = OnceLock::new();
+
+/// Returns the process wide access log of host servers.
+pub fn access_log() -> &'static AccessLog {
+    ACCESS_LOG.get_or_init(AccessLog::default)
+}
+
+/// Uri of a request before a router rewrote it, set as a request extension.
+#[derive(Clone, Debug)]
+pub struct OriginalUri(pub hyper::Uri);
+
+/// Requests served by the http and websocket servers, written as json lines.
+///
+/// Nothing is written until a file is configured.
+#[derive(Default)]
+pub struct AccessLog {
+    out: Mutex<Option<SessionOutput>>,
+}
+
+impl AccessLog {
+    /// Write entries to a file, rotated like session files.
+    pub fn configure(&self, path: &Path, options: OutputOptions) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let out = SessionOutput::open_with(path, options)?;
+        if let Ok(mut current) = self.out.lock() {
+            *current = Some(out);
+        }
+        Ok(())
+    }
+
+    /// Record a request with the status and bytes of its response, timing it until now.
+    pub fn record(&self, entry: &AccessEntry, status: u16, bytes: u64) {
+        let Ok(mut out) = self.out.lock() else {
+            return;
+        };
+        let Some(out) = out.as_mut() else {
+            return;
+        };
+
+        let result = serde_json::to_vec(&entry.line(status, bytes))
+            .map_err(std::io::Error::from)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                out.write_all(&line)?;
+                out.flush()
+            });
+        if let Err(e) = result {
+            log::warn!("failed to write access log: {:?}", e);
+        }
+    }
+}
+
+/// A request being served, recorded in the access log once its response was sent.
+#[derive(Clone, Debug)]
+pub struct AccessEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// `http` or `websocket`
+    pub server: &'static str,
+    pub method: String,
+    pub path: String,
+    pub remote_addr: Option<SocketAddr>,
+    pub session_id: Uuid,
+    started: Instant,
+}
+
+impl AccessEntry {
+    pub fn new<B>(server: &'static str, req: &hyper::Request<B>, session_id: Uuid) -> Self {
+        // Log the path the client requested, not the one a route rewrote it to
+        let path = match req.extensions().get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path().to_string(),
+            None => req.uri().path().to_string(),
+        };
+        Self {
+            timestamp: chrono::Utc::now(),
+            server,
+            method: req.method().to_string(),
+            path,
+            remote_addr: req
+                .extensions()
+                .get::<ClientAddr>()
+                .map(|ClientAddr(addr)| *addr),
+            session_id,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record the entry with the status and bytes sent, timing it until now.
+    pub fn finish(self, status: u16, bytes: u64) {
+        access_log().record(&self, status, bytes);
+    }
+
+    /// Record the entry once the body of the response was sent, or dropped.
+    pub fn finish_with(self, resp: HostResponse) -> HostResponse {
+        let status = resp.status().as_u16();
+        resp.map(|body| {
+            LoggedBody {
+                inner: body,
+                entry: Some(self),
+                status,
+                bytes: 0,
+            }
+            .boxed()
+        })
+    }
+
+    fn line(&self, status: u16, bytes: u64) -> AccessLine<'_> {
+        AccessLine {
+            timestamp: self
+                .timestamp
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            server: self.server,
+            method: &self.method,
+            path: &self.path,
+            status,
+            latency_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            bytes,
+            remote_addr: self.remote_addr,
+            session_id: self.session_id,
+        }
+    }
+}
+
+// A line of the access log
+#[derive(Serialize)]
+struct AccessLine<'a> {
+    timestamp: String,
+    server: &'a str,
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    latency_ms: f64,
+    bytes: u64,
+    remote_addr: Option<SocketAddr>,
+    session_id: Uuid,
+}
+
+// Counts the bytes of a response body, recording its entry when the body is dropped
+struct LoggedBody {
+    inner: HyperOutgoingBody,
+    entry: Option<AccessEntry>,
+    status: u16,
+    bytes: u64,
+}
+
+impl Body for LoggedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            entry.finish(self.status, self.bytes);
+        }
+    }
+}
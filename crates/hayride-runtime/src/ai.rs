@@ -1,18 +1,26 @@
 mod ai_impl;
 
 pub mod ai;
+pub mod backends;
 pub mod bindings;
+pub mod eval;
+pub mod local;
+pub mod memory;
+pub mod postprocess;
+pub mod tokens;
+pub mod trace;
 
+pub use ai::model_repository;
 pub use ai::AiCtx;
 pub use ai::{AiImpl, AiView};
 
-use hayride_host_traits::ai::model::ModelRepositoryInner;
-use hayride_host_traits::ai::rag::RagInner;
+use hayride_host_traits::ai::model::{DownloadEvent, DownloadObserver, ModelRepositoryInner};
+use hayride_host_traits::ai::rag::RagInnerAsync;
 use hayride_host_traits::ai::BackendInner;
 
 use wasmtime::component::HasData;
 
-pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
 where
     T: AiView,
 {
@@ -26,6 +34,10 @@ where
     bindings::ai::rag::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
     bindings::ai::transformer::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
     bindings::ai::model_repository::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
+    bindings::ai::memory::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
+    bindings::ai::trace::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
+    bindings::ai::eval::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
+    bindings::ai::tokens::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
 
     // Context added as a fallback to satisfy the imports if needed.
     bindings::ai::context::add_to_linker::<T, HasAi<T>>(l, |x| AiImpl(x))?;
@@ -59,9 +71,9 @@ impl<T: BackendInner + 'static> From<T> for Backend {
 }
 
 /// A rag backend
-pub struct Rag(Box<dyn RagInner>);
+pub struct Rag(Box<dyn RagInnerAsync>);
 impl std::ops::Deref for Rag {
-    type Target = dyn RagInner;
+    type Target = dyn RagInnerAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -71,7 +83,7 @@ impl std::ops::DerefMut for Rag {
         self.0.as_mut()
     }
 }
-impl<T: RagInner + 'static> From<T> for Rag {
+impl<T: RagInnerAsync + 'static> From<T> for Rag {
     fn from(value: T) -> Self {
         Self(Box::new(value))
     }
@@ -95,3 +107,35 @@ impl<T: ModelRepositoryInner + 'static> From<T> for ModelRepository {
         Self(Box::new(value))
     }
 }
+
+/// Topic model download events are published on as json.
+pub const MODEL_EVENTS_TOPIC: &str = "hayride/models";
+
+/// Logs model download events and publishes them on the event bus.
+pub struct ModelEvents;
+
+impl DownloadObserver for ModelEvents {
+    fn event(&self, event: DownloadEvent) {
+        match &event {
+            DownloadEvent::Started { name } => log::info!("downloading model: {}", name),
+            DownloadEvent::Progress {
+                name,
+                downloaded,
+                total,
+            } => log::debug!("downloading model {}: {}/{} bytes", name, downloaded, total),
+            DownloadEvent::Verified { name, path, size } => {
+                log::info!("downloaded model {} to {} ({} bytes)", name, path, size)
+            }
+            DownloadEvent::Failed { name, error } => {
+                log::warn!("failed to download model {}: {}", name, error)
+            }
+        }
+
+        match serde_json::to_string(&event) {
+            Ok(data) => {
+                crate::events::bus().publish(MODEL_EVENTS_TOPIC, data.into_bytes());
+            }
+            Err(e) => log::warn!("failed to serialize model event: {}", e),
+        }
+    }
+}
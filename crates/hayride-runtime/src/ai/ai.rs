@@ -1,51 +1,233 @@
+use super::backends::BackendRegistry;
+use super::bindings::graph::ExecutionTarget;
+#[cfg(any(feature = "llamacpp", feature = "whisper"))]
+use super::bindings::graph::GraphEncoding;
+use super::memory::MemoryStore;
+use super::trace::TraceRecorder;
 use super::{Backend, ModelRepository, Rag};
+use crate::capabilities::CapabilityPolicy;
+use crate::manifest::ManifestRecorder;
+use crate::timeouts::HostTimeouts;
 use anyhow::Result;
+use hayride_host_traits::ai::nn::OutputFilter;
+use hayride_host_traits::ai::{AbortHandle, BackendError, Graph};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use wasmtime::component::ResourceTable;
 pub struct AiCtx {
     // The output directory for the runtime.
     pub out_dir: Option<String>,
 
-    pub backend: Backend,
+    // Backends that models are routed to by name, shared with the blocking threads
+    // loading models
+    pub backends: Arc<Mutex<BackendRegistry>>,
     pub rag: Rag,
 
     pub model_repository: ModelRepository,
 
+    // Long-term memory for the session, backed by the rag backend.
+    pub memory: MemoryStore,
+
     // An optional model path to load models from
     pub model_path: Option<String>,
     thread_id: Arc<AtomicI32>,
+
+    // Deadlines for compute and rag calls
+    pub timeouts: HostTimeouts,
+
+    // Background compute tasks by the execution context that spawned them
+    tasks: HashMap<u32, Vec<AbortHandle>>,
+
+    // Names of the models of graphs and execution contexts, picking their profile
+    models: HashMap<u32, String>,
+
+    // Masks sensitive patterns in inference output
+    pub output_filter: Option<Arc<OutputFilter>>,
+
+    // Capabilities enabled when the store was created
+    pub capabilities: CapabilityPolicy,
+
+    // Manifest of the session, pinning the seed of compute calls
+    pub manifest: Option<ManifestRecorder>,
+
+    // Records the compute calls of the session when tracing is enabled
+    pub trace: Option<TraceRecorder>,
 }
 
 impl AiCtx {
-    pub fn new(out_dir: Option<String>, model_path: Option<String>) -> Result<Self> {
+    pub fn new(
+        session_id: String,
+        out_dir: Option<String>,
+        model_path: Option<String>,
+    ) -> Result<Self> {
+        let mut backends = BackendRegistry::new();
+
+        // The mock backend takes any model on any device
         #[cfg(not(feature = "llamacpp"))]
-        let backend = Box::new(hayride_host_traits::ai::nn::mock::MockBackend::default());
+        backends.register_for(
+            "mock",
+            Backend(Box::new(
+                hayride_host_traits::ai::nn::mock::MockBackend::default(),
+            )),
+            &[],
+            &[
+                ExecutionTarget::Cpu,
+                ExecutionTarget::Gpu,
+                ExecutionTarget::Tpu,
+            ],
+        );
         #[cfg(feature = "llamacpp")]
-        let backend = Box::new(hayride_llama::LlamaCppBackend::new());
+        backends.register_for(
+            "llama",
+            Backend(Box::new(hayride_llama::LlamaCppBackend::new())),
+            &[GraphEncoding::Ggml],
+            &[
+                ExecutionTarget::Cpu,
+                #[cfg(any(feature = "cuda", feature = "metal"))]
+                ExecutionTarget::Gpu,
+            ],
+        );
+
+        // whisper.cpp models are distributed as `ggml-<size>.bin` files
+        #[cfg(feature = "whisper")]
+        {
+            backends.register_for(
+                "whisper",
+                Backend(Box::new(hayride_whisper::WhisperBackend::new())),
+                &[GraphEncoding::Ggml],
+                &[ExecutionTarget::Cpu],
+            );
+            backends.route("*whisper*", "whisper");
+            backends.route("ggml-*.bin", "whisper");
+        }
 
         #[cfg(not(feature = "lancedb"))]
         let rag = Box::new(hayride_host_traits::ai::rag::mock::MockRagInner::default());
         #[cfg(feature = "lancedb")]
         let rag = Box::new(hayride_lancedb::LanceDBRag::default());
 
-        #[cfg(not(feature = "hf"))]
-        let model_repository =
-            Box::new(hayride_host_traits::ai::model::mock::MockModelLoaderInner::default());
-        #[cfg(feature = "hf")]
-        let model_repository = Box::new(hayride_hf::HuggingFaceModelRepository::new()?);
+        let model_repository = model_repository()?;
+
+        let trace = out_dir
+            .as_deref()
+            .filter(|_| super::trace::enabled())
+            .map(|out_dir| TraceRecorder::new(out_dir, session_id.clone()));
+
+        let memory_dir = hayride_utils::paths::hayride::default_hayride_dir()?.join("ai/memory");
+        let memory = MemoryStore::new(session_id, memory_dir.to_str().map(|dir| dir.to_string()));
 
         let thread_id = Arc::new(AtomicI32::new(0));
         Ok(Self {
             out_dir,
-            backend: Backend(backend),
+            backends: Arc::new(Mutex::new(backends)),
             rag: Rag(rag),
-            model_repository: ModelRepository(model_repository),
+            model_repository,
+            memory,
             model_path: model_path,
             thread_id,
+            timeouts: HostTimeouts::default(),
+            tasks: HashMap::new(),
+            models: HashMap::new(),
+            output_filter: None,
+            capabilities: CapabilityPolicy::default(),
+            manifest: None,
+            trace,
         })
     }
 
+    pub fn with_timeouts(mut self, timeouts: HostTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn with_output_filter(mut self, output_filter: Option<Arc<OutputFilter>>) -> Self {
+        self.output_filter = output_filter;
+        self
+    }
+
+    pub fn with_manifest(mut self, manifest: Option<ManifestRecorder>) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: CapabilityPolicy) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Register a background compute task spawned by an execution context.
+    pub fn register_task(&mut self, context: u32, abort: AbortHandle) {
+        let tasks = self.tasks.entry(context).or_default();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(abort);
+    }
+
+    /// Abort the background compute tasks of a dropped execution context.
+    pub fn abort_tasks(&mut self, context: u32) {
+        for task in self.tasks.remove(&context).unwrap_or_default() {
+            task.abort();
+        }
+    }
+
+    /// Load a model by name, names the model repository serves are loaded from its path
+    /// and anything else is passed to the backends as is.
+    pub fn load_model(&mut self, name: &str) -> std::result::Result<Graph, BackendError> {
+        let path = self.model_file(name);
+        self.backends
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .load(path)
+    }
+
+    /// Load a model by name like `load_model` on a blocking thread, so loading it does not
+    /// hold up the runtime.
+    pub async fn load_model_blocking(
+        &mut self,
+        name: &str,
+    ) -> std::result::Result<Graph, BackendError> {
+        let path = self.model_file(name);
+        let backends = self.backends.clone();
+        tokio::task::spawn_blocking(move || {
+            backends
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .load(path)
+        })
+        .await
+        .map_err(|_| BackendError::Unknown)?
+    }
+
+    /// Returns the file a model name refers to, either the name itself or the path the
+    /// model repository serves it at, or `None` if neither has the model.
+    pub fn find_model(&self, name: &str) -> Option<String> {
+        if std::path::Path::new(name).exists() {
+            Some(name.to_string())
+        } else {
+            self.model_repository.get(name.to_string()).ok()
+        }
+    }
+
+    // Names nothing serves are passed to the backends as is
+    fn model_file(&self, name: &str) -> String {
+        self.find_model(name).unwrap_or_else(|| name.to_string())
+    }
+
+    /// Record the name of the model a graph or execution context was loaded from.
+    pub fn set_model(&mut self, rep: u32, name: String) {
+        self.models.insert(rep, name);
+    }
+
+    /// Returns the name of the model of a graph or execution context.
+    pub fn model(&self, rep: u32) -> Option<&str> {
+        self.models.get(&rep).map(String::as_str)
+    }
+
+    /// Forget the model of a dropped graph or execution context.
+    pub fn forget_model(&mut self, rep: u32) {
+        self.models.remove(&rep);
+    }
+
     pub fn next_thread_id(&self) -> Option<i32> {
         match self
             .thread_id
@@ -59,6 +241,15 @@ impl AiCtx {
     }
 }
 
+impl Drop for AiCtx {
+    fn drop(&mut self) {
+        // Nothing can read the output of tasks outliving the store
+        for task in self.tasks.drain().flat_map(|(_, tasks)| tasks) {
+            task.abort();
+        }
+    }
+}
+
 pub trait AiView: Send {
     /// Returns a mutable reference to the ml context.
     fn ctx(&mut self) -> &mut AiCtx;
@@ -87,6 +278,28 @@ impl<T: ?Sized + AiView> AiView for Box<T> {
     }
 }
 
+/// The configured model repository, local directories and Ollama take the place of the hub.
+pub fn model_repository() -> Result<ModelRepository> {
+    if let Some(options) = super::local::configured() {
+        return Ok(super::local::LocalModelRepository::new(options).into());
+    }
+    #[cfg(feature = "ollama")]
+    if let Some(endpoint) = hayride_ollama::configured() {
+        return Ok(hayride_ollama::OllamaModelRepository::new(endpoint)
+            .with_observer(Arc::new(super::ModelEvents))
+            .into());
+    }
+
+    #[cfg(not(feature = "hf"))]
+    let model_repository =
+        hayride_host_traits::ai::model::mock::MockModelRepositoryInner::default().into();
+    #[cfg(feature = "hf")]
+    let model_repository = hayride_hf::HuggingFaceModelRepository::new()?
+        .with_observer(Arc::new(super::ModelEvents))
+        .into();
+    Ok(model_repository)
+}
+
 /// A concrete structure that all generated `Host` traits are implemented for.
 ///
 /// This type serves as a small newtype wrapper to implement all of the `Host`
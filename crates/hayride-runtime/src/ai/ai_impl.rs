@@ -2,18 +2,30 @@ use super::ai::{AiImpl, AiView};
 use super::bindings::ai::graph_stream::GraphStream;
 use super::bindings::ai::inference_stream::TensorStream;
 use super::bindings::ai::{
-    context, graph_stream, inference_stream, model_repository, rag, tensor_stream, transformer,
+    context, eval, graph_stream, inference_stream, memory, model_repository, rag, tensor_stream,
+    tokens, trace, transformer,
 };
 use super::bindings::graph::{ExecutionTarget, GraphBuilder, GraphEncoding};
 use super::bindings::{errors, graph, inference, tensor};
-use hayride_host_traits::ai::context::{Context, ErrorCode as ContextErrorCode};
-use hayride_host_traits::ai::model::ErrorCode as ModelErrorCode;
+use super::eval::{evaluate, EvalOptions, Report};
+use super::memory::SessionContext;
+use super::postprocess::model_profiles;
+use super::tokens::fit_messages;
+use super::trace::{CallKind, TraceEntry, TraceFilter};
+use hayride_host_traits::ai::context::ErrorCode as ContextErrorCode;
+use hayride_host_traits::ai::eval::ErrorCode as EvalErrorCode;
+use hayride_host_traits::ai::memory::ErrorCode as MemoryErrorCode;
+use hayride_host_traits::ai::model::{ErrorCode as ModelErrorCode, ModelFilter, ModelInfo};
 use hayride_host_traits::ai::rag::{
-    Connection, Error as RagError, ErrorCode as RagErrorCode, RagOption, Transformer,
+    Connection, Error as RagError, ErrorCode as RagErrorCode, RagOption, RagRow, Transformer,
+};
+use hayride_host_traits::ai::{
+    BackendError, Error, ErrorCode, ExecutionContext, Graph, Tensor, TensorType,
 };
-use hayride_host_traits::ai::{Error, ErrorCode, ExecutionContext, Graph, Tensor};
 
+use crate::timeouts::deadline;
 use anyhow::anyhow;
+use tracing::Instrument;
 use wasmtime::component::Resource;
 use wasmtime::Result;
 use wasmtime_wasi::p2::InputStream;
@@ -111,28 +123,48 @@ where
         &mut self,
         path: String,
     ) -> Result<Result<Resource<Graph>, Resource<errors::Error>>> {
-        match self.ctx().backend.load(path) {
+        match self.ctx().load_model(&path) {
             Ok(graph) => {
                 let id = self.table().push(graph)?;
+                self.ctx().set_model(id.rep(), path);
                 return Ok(Ok(id));
             }
+            // Names of models that do not exist are reported as unknown, as wasi-nn
+            // specifies, models that exist but fail to load keep the error of the backend
+            Err(BackendError::FailedToLoadModel) if self.ctx().find_model(&path).is_none() => {
+                bail!(
+                    self,
+                    ErrorCode::NotFound,
+                    anyhow!("model not found: {}", path)
+                );
+            }
             Err(error) => {
-                bail!(self, ErrorCode::RuntimeError, error);
+                bail!(self, ErrorCode::from(&error), error);
             }
         }
     }
 
     fn load(
         &mut self,
-        _builder: Vec<GraphBuilder>,
-        _encoding: GraphEncoding,
-        _target: ExecutionTarget,
+        builder: Vec<GraphBuilder>,
+        encoding: GraphEncoding,
+        target: ExecutionTarget,
     ) -> Result<Result<Resource<Graph>, Resource<errors::Error>>> {
-        bail!(
-            self,
-            ErrorCode::UnsupportedOperation,
-            anyhow!("Load not implemented, use load_by_name")
-        );
+        let result = self
+            .ctx()
+            .backends
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .load_graph(builder, encoding, target);
+        match result {
+            Ok(graph) => {
+                let id = self.table().push(graph)?;
+                return Ok(Ok(id));
+            }
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
     }
 }
 
@@ -144,19 +176,24 @@ where
         &mut self,
         graph: Resource<Graph>,
     ) -> Result<Result<Resource<ExecutionContext>, Resource<graph::Error>>> {
+        let model = self.ctx().model(graph.rep()).map(str::to_string);
         let graph = self.table().get(&graph)?;
         match graph.init_execution_context() {
             Ok(exec_context) => {
                 let id = self.table().push(exec_context)?;
+                if let Some(model) = model {
+                    self.ctx().set_model(id.rep(), model);
+                }
                 return Ok(Ok(id));
             }
             Err(error) => {
-                bail!(self, ErrorCode::RuntimeError, error);
+                bail!(self, ErrorCode::from(&error), error);
             }
         }
     }
 
     fn drop(&mut self, id: Resource<Graph>) -> Result<(), wasmtime::Error> {
+        self.ctx().forget_model(id.rep());
         self.table().delete(id)?;
         Ok(())
     }
@@ -168,25 +205,80 @@ impl<T> inference::HostGraphExecutionContext for AiImpl<T>
 where
     T: AiView,
 {
-    fn compute(
+    async fn compute(
         &mut self,
         exec_context: Resource<inference::GraphExecutionContext>,
         inputs: Vec<(String, Resource<Tensor>)>,
     ) -> Result<Result<Vec<(String, Resource<Tensor>)>, Resource<errors::Error>>, wasmtime::Error>
     {
+        if !self.ctx().capabilities.ai_compute {
+            bail!(
+                self,
+                ErrorCode::UnsupportedOperation,
+                anyhow!("ai compute is disabled")
+            );
+        }
+
         // Convert tensor resources to tensors
-        let converted_inputs: Vec<(String, Tensor)> = inputs
+        let mut converted_inputs: Vec<(String, Tensor)> = inputs
             .into_iter()
             .map(|(name, tensor)| {
                 let tensor = self.table().get(&tensor)?;
                 Ok((name, tensor.clone()))
             })
             .collect::<Result<Vec<(String, Tensor)>>>()?;
+        if let Some(manifest) = self.ctx().manifest.clone() {
+            manifest.record_compute(self.ctx().model(exec_context.rep()), &mut converted_inputs);
+        }
+        let post_processor = match model_profiles()
+            .post_processor(self.ctx().model(exec_context.rep()), &converted_inputs)
+        {
+            Ok(post_processor) => post_processor,
+            Err(error) => {
+                bail!(self, ErrorCode::InvalidArgument, error);
+            }
+        };
+
+        let ctx = self.ctx();
+        let call = ctx.trace.as_ref().map(|trace| {
+            trace.start(
+                CallKind::Compute,
+                ctx.model(exec_context.rep()),
+                &converted_inputs,
+            )
+        });
 
         // Compute
+        let timeout = self.ctx().timeouts.ai;
         let context = self.table().get_mut(&exec_context)?;
-        match context.compute(converted_inputs) {
-            Ok(tensor) => {
+        let result = match deadline(
+            timeout,
+            context
+                .compute(converted_inputs)
+                .instrument(tracing::info_span!("ai.compute")),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(elapsed) => {
+                if let Some(call) = call {
+                    call.finish(Err(elapsed.to_string()));
+                }
+                bail!(self, ErrorCode::Timeout, elapsed);
+            }
+        };
+        match result {
+            Ok(mut tensor) => {
+                // Steps work on text, other tensor types are binary
+                if !post_processor.is_empty() && tensor.ty == TensorType::U8 {
+                    tensor.data = post_processor.apply(&tensor.data);
+                }
+                if let Some(filter) = &self.ctx().output_filter {
+                    tensor.data = filter.mask(&tensor.data);
+                }
+                if let Some(call) = call {
+                    call.finish(Ok(&tensor.data));
+                }
                 let mut results: Vec<(String, Resource<Tensor>)> = Vec::new();
                 let id = self.table().push(tensor)?;
                 results.push(("Output".to_string(), id));
@@ -194,12 +286,18 @@ where
                 return Ok(Ok(results));
             }
             Err(error) => {
-                bail!(self, ErrorCode::RuntimeError, error);
+                if let Some(call) = call {
+                    call.finish(Err(error.to_string()));
+                }
+                // JSON mode exhausting its re-asks is reported as an invalid encoding
+                bail!(self, ErrorCode::from(&error), error);
             }
         }
     }
 
     fn drop(&mut self, id: Resource<inference::GraphExecutionContext>) -> Result<()> {
+        self.ctx().abort_tasks(id.rep());
+        self.ctx().forget_model(id.rep());
         self.table().delete(id)?;
         Ok(())
     }
@@ -312,13 +410,23 @@ where
         &mut self,
         path: String,
     ) -> Result<Result<Resource<GraphStream>, Resource<errors::Error>>> {
-        match self.ctx().backend.load(path) {
+        match self.ctx().load_model(&path) {
             Ok(graph) => {
                 let id = self.table().push(graph)?;
+                self.ctx().set_model(id.rep(), path);
                 return Ok(Ok(id));
             }
+            // Names of models that do not exist are reported as unknown, as wasi-nn
+            // specifies, models that exist but fail to load keep the error of the backend
+            Err(BackendError::FailedToLoadModel) if self.ctx().find_model(&path).is_none() => {
+                bail!(
+                    self,
+                    ErrorCode::NotFound,
+                    anyhow!("model not found: {}", path)
+                );
+            }
             Err(error) => {
-                bail!(self, ErrorCode::RuntimeError, error);
+                bail!(self, ErrorCode::from(&error), error);
             }
         }
     }
@@ -332,10 +440,14 @@ where
         &mut self,
         graph: Resource<GraphStream>,
     ) -> Result<Result<Resource<ExecutionContext>, Resource<graph::Error>>> {
+        let model = self.ctx().model(graph.rep()).map(str::to_string);
         let graph = self.table().get(&graph)?;
         match graph.init_execution_context() {
             Ok(exec_context) => {
                 let id = self.table().push(exec_context)?;
+                if let Some(model) = model {
+                    self.ctx().set_model(id.rep(), model);
+                }
                 return Ok(Ok(id));
             }
             Err(error) => {
@@ -345,6 +457,7 @@ where
     }
 
     fn drop(&mut self, id: Resource<Graph>) -> Result<(), wasmtime::Error> {
+        self.ctx().forget_model(id.rep());
         self.table().delete(id)?;
         Ok(())
     }
@@ -356,25 +469,83 @@ impl<T> inference_stream::HostGraphExecutionContextStream for AiImpl<T>
 where
     T: AiView,
 {
-    fn compute(
+    async fn compute(
         &mut self,
         exec_context: Resource<ExecutionContext>,
         inputs: Vec<inference_stream::NamedTensor>,
     ) -> Result<Result<inference_stream::NamedTensorStream, Resource<inference_stream::Error>>>
     {
+        if !self.ctx().capabilities.ai_compute {
+            bail!(
+                self,
+                ErrorCode::UnsupportedOperation,
+                anyhow!("ai compute is disabled")
+            );
+        }
+
         // Convert tensor resources to tensors
-        let inputs: Vec<(String, Tensor)> = inputs
+        let mut inputs: Vec<(String, Tensor)> = inputs
             .into_iter()
             .map(|(name, tensor)| {
                 let tensor = self.table().get(&tensor)?;
                 Ok((name, tensor.clone()))
             })
             .collect::<Result<Vec<(String, Tensor)>>>()?;
+        if let Some(manifest) = self.ctx().manifest.clone() {
+            manifest.record_compute(self.ctx().model(exec_context.rep()), &mut inputs);
+        }
+        let post_processor =
+            match model_profiles().post_processor(self.ctx().model(exec_context.rep()), &inputs) {
+                Ok(post_processor) => post_processor,
+                Err(error) => {
+                    bail!(self, ErrorCode::InvalidArgument, error);
+                }
+            };
+
+        let ctx = self.ctx();
+        let call = ctx.trace.as_ref().map(|trace| {
+            trace.start(
+                CallKind::ComputeStream,
+                ctx.model(exec_context.rep()),
+                &inputs,
+            )
+        });
 
         // Get the compute stream from the execution context
+        let timeout = self.ctx().timeouts.ai;
         let context = self.table().get_mut(&exec_context)?;
-        match context.compute_stream(inputs) {
-            Ok(tensor_stream) => {
+        let result = match deadline(
+            timeout,
+            context
+                .compute_stream(inputs)
+                .instrument(tracing::info_span!("ai.compute_stream")),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(elapsed) => {
+                if let Some(call) = call {
+                    call.finish(Err(elapsed.to_string()));
+                }
+                bail!(self, ErrorCode::Timeout, elapsed);
+            }
+        };
+        match result {
+            Ok(mut tensor_stream) => {
+                // Steps work on text, other tensor types are binary
+                if !post_processor.is_empty() && tensor_stream.ty == TensorType::U8 {
+                    tensor_stream = tensor_stream.with_filter(post_processor.stream());
+                }
+                if let Some(filter) = &self.ctx().output_filter {
+                    tensor_stream = tensor_stream.with_filter(filter.stream());
+                }
+                // Traced last, recording the output as the morph reads it
+                if let Some(call) = call {
+                    tensor_stream = tensor_stream.with_filter(call.stream());
+                }
+                if let Some(abort) = tensor_stream.abort_handle() {
+                    self.ctx().register_task(exec_context.rep(), abort);
+                }
                 let id = self.table().push(tensor_stream)?;
 
                 // TODO: How to get a valid output name?
@@ -383,12 +554,17 @@ where
                 return Ok(Ok(named_tensor_stream));
             }
             Err(error) => {
+                if let Some(call) = call {
+                    call.finish(Err(error.to_string()));
+                }
                 bail!(self, ErrorCode::RuntimeError, error);
             }
         }
     }
 
     fn drop(&mut self, id: Resource<inference::GraphExecutionContext>) -> Result<()> {
+        self.ctx().abort_tasks(id.rep());
+        self.ctx().forget_model(id.rep());
         self.table().delete(id)?;
         Ok(())
     }
@@ -398,11 +574,16 @@ impl<T> rag::Host for AiImpl<T>
 where
     T: AiView,
 {
-    fn connect(
+    async fn connect(
         &mut self,
         dsn: String,
     ) -> Result<Result<Resource<Connection>, Resource<rag::Error>>> {
-        match self.ctx().rag.connect(dsn.clone()) {
+        let timeout = self.ctx().timeouts.rag;
+        let connect = self.ctx().rag.connect(dsn.clone());
+        match deadline(timeout, connect)
+            .await
+            .unwrap_or(Err(RagErrorCode::Timeout))
+        {
             Ok(conn) => {
                 let id = self.table().push(conn)?;
                 return Ok(Ok(id));
@@ -418,7 +599,7 @@ impl<T> rag::HostConnection for AiImpl<T>
 where
     T: AiView,
 {
-    fn register(
+    async fn register(
         &mut self,
         conn: Resource<rag::Connection>,
         transformer: Resource<Transformer>,
@@ -428,9 +609,13 @@ where
             let transformer_ref = table.get(&transformer)?;
             transformer_ref.clone()
         };
-        let conn = table.get_mut(&conn)?;
+        let timeout = self.ctx().timeouts.rag;
+        let conn = self.table().get_mut(&conn)?;
 
-        match conn.register(transformer.clone()) {
+        match deadline(timeout, conn.register(transformer.clone()))
+            .await
+            .unwrap_or(Err(RagErrorCode::Timeout))
+        {
             Ok(()) => {
                 return Ok(Ok(()));
             }
@@ -444,14 +629,22 @@ where
         }
     }
 
-    fn embed(
+    async fn embed(
         &mut self,
         conn: Resource<rag::Connection>,
         table: String,
         data: String,
     ) -> Result<Result<(), Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
         let conn = self.table().get(&conn)?;
-        match conn.embed(table.clone(), data.clone()) {
+        match deadline(
+            timeout,
+            conn.embed(table.clone(), data.clone())
+                .instrument(tracing::info_span!("ai.rag.embed", rag.table = %table)),
+        )
+        .await
+        .unwrap_or(Err(RagErrorCode::Timeout))
+        {
             Ok(()) => {
                 return Ok(Ok(()));
             }
@@ -465,13 +658,14 @@ where
         }
     }
 
-    fn query(
+    async fn query(
         &mut self,
         conn: Resource<rag::Connection>,
         table: String,
         data: String,
         options: Vec<rag::RagOption>,
-    ) -> Result<Result<Vec<String>, Resource<RagError>>> {
+    ) -> Result<Result<Vec<rag::QueryResult>, Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
         let conn = self.table().get(&conn)?;
 
         // Convert RagOption to hayride_rag::RagOption
@@ -483,8 +677,23 @@ where
             })
             .collect();
 
-        match conn.query(table.clone(), data.clone(), options) {
+        match deadline(
+            timeout,
+            conn.query(table.clone(), data.clone(), options)
+                .instrument(tracing::info_span!("ai.rag.query", rag.table = %table)),
+        )
+        .await
+        .unwrap_or(Err(RagErrorCode::Timeout))
+        {
             Ok(results) => {
+                let results = results
+                    .into_iter()
+                    .map(|result| rag::QueryResult {
+                        text: result.text,
+                        distance: result.distance,
+                        columns: result.columns,
+                    })
+                    .collect();
                 return Ok(Ok(results));
             }
             Err(error) => {
@@ -497,6 +706,122 @@ where
         }
     }
 
+    async fn delete(
+        &mut self,
+        conn: Resource<rag::Connection>,
+        table: String,
+        filter: String,
+    ) -> Result<Result<(), Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
+        let conn = self.table().get(&conn)?;
+        match deadline(timeout, conn.delete(table.clone(), filter.clone()))
+            .await
+            .unwrap_or(Err(RagErrorCode::Timeout))
+        {
+            Ok(()) => {
+                return Ok(Ok(()));
+            }
+            Err(error) => {
+                rag_bail!(
+                    self,
+                    error,
+                    anyhow!("Delete failed for table: {}, filter: {}", table, filter)
+                );
+            }
+        }
+    }
+
+    async fn upsert(
+        &mut self,
+        conn: Resource<rag::Connection>,
+        table: String,
+        id: String,
+        data: String,
+    ) -> Result<Result<(), Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
+        let conn = self.table().get(&conn)?;
+        match deadline(
+            timeout,
+            conn.upsert(table.clone(), id.clone(), data.clone()),
+        )
+        .await
+        .unwrap_or(Err(RagErrorCode::Timeout))
+        {
+            Ok(()) => {
+                return Ok(Ok(()));
+            }
+            Err(error) => {
+                rag_bail!(
+                    self,
+                    error,
+                    anyhow!("Upsert failed for table: {}, id: {}", table, id)
+                );
+            }
+        }
+    }
+
+    async fn embed_batch(
+        &mut self,
+        conn: Resource<rag::Connection>,
+        table: String,
+        data: Vec<String>,
+    ) -> Result<Result<(), Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
+        let conn = self.table().get(&conn)?;
+        let count = data.len();
+        match deadline(timeout, conn.embed_batch(table.clone(), data))
+            .await
+            .unwrap_or(Err(RagErrorCode::Timeout))
+        {
+            Ok(()) => {
+                return Ok(Ok(()));
+            }
+            Err(error) => {
+                rag_bail!(
+                    self,
+                    error,
+                    anyhow!("Embed batch failed for table: {}, rows: {}", table, count)
+                );
+            }
+        }
+    }
+
+    async fn upsert_batch(
+        &mut self,
+        conn: Resource<rag::Connection>,
+        table: String,
+        rows: Vec<rag::RagRow>,
+    ) -> Result<Result<(), Resource<RagError>>> {
+        let timeout = self.ctx().timeouts.rag;
+        let conn = self.table().get(&conn)?;
+
+        // Convert RagRow to hayride_rag::RagRow
+        let rows: Vec<RagRow> = rows
+            .into_iter()
+            .map(|row| RagRow {
+                id: row.0,
+                data: row.1,
+            })
+            .collect();
+        let count = rows.len();
+
+        match deadline(timeout, conn.upsert_batch(table.clone(), rows))
+            .await
+            .unwrap_or(Err(RagErrorCode::Timeout))
+        {
+            Ok(()) => {
+                return Ok(Ok(()));
+            }
+            Err(error) => {
+                rag_bail!(
+                    self,
+                    error,
+                    anyhow!("Upsert batch failed for table: {}, rows: {}", table, count)
+                );
+            }
+        }
+    }
+
     fn drop(&mut self, id: Resource<rag::Connection>) -> Result<()> {
         self.table().delete(id)?;
         return Ok(());
@@ -517,7 +842,9 @@ where
             RagErrorCode::RegisterFailed => Ok(rag::ErrorCode::RegisterFailed),
             RagErrorCode::MissingTable => Ok(rag::ErrorCode::MissingTable),
             RagErrorCode::InvalidOption => Ok(rag::ErrorCode::InvalidOption),
+            RagErrorCode::DeleteFailed => Ok(rag::ErrorCode::DeleteFailed),
             RagErrorCode::NotEnabled => Ok(rag::ErrorCode::NotEnabled),
+            RagErrorCode::Timeout => Ok(rag::ErrorCode::Timeout),
             RagErrorCode::Unknown => Ok(rag::ErrorCode::Unknown),
         }
     }
@@ -673,7 +1000,12 @@ where
             wasmtime::component::Resource<hayride_host_traits::ai::model::Error>,
         >,
     > {
-        match self.ctx().model_repository.list() {
+        // Only the models the runtime can load
+        let filter = ModelFilter {
+            extension: Some("gguf".to_string()),
+            repo: None,
+        };
+        match self.ctx().model_repository.list(&filter) {
             Ok(models) => Ok(Ok(models)),
             Err(error) => {
                 model_bail!(
@@ -684,6 +1016,121 @@ where
             }
         }
     }
+
+    fn list_models_with(
+        &mut self,
+        filter: model_repository::ModelFilter,
+    ) -> Result<Result<Vec<String>, Resource<model_repository::Error>>> {
+        let filter = ModelFilter {
+            extension: filter.extension,
+            repo: filter.repo,
+        };
+        match self.ctx().model_repository.list(&filter) {
+            Ok(models) => Ok(Ok(models)),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("list models failed with '{}'", error)
+                );
+            }
+        }
+    }
+
+    fn info(
+        &mut self,
+        name: String,
+    ) -> Result<Result<model_repository::ModelInfo, Resource<model_repository::Error>>> {
+        match self.ctx().model_repository.info(name.clone()) {
+            Ok(info) => Ok(Ok(model_info(info))),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("info of '{}' failed with '{}'", name, error)
+                );
+            }
+        }
+    }
+
+    fn list_models_info(
+        &mut self,
+        filter: model_repository::ModelFilter,
+    ) -> Result<Result<Vec<model_repository::ModelInfo>, Resource<model_repository::Error>>> {
+        let filter = ModelFilter {
+            extension: filter.extension,
+            repo: filter.repo,
+        };
+        match self.ctx().model_repository.list_info(&filter) {
+            Ok(models) => Ok(Ok(models.into_iter().map(model_info).collect())),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("list models failed with '{}'", error)
+                );
+            }
+        }
+    }
+
+    fn purge_cache(&mut self) -> Result<Result<u64, Resource<model_repository::Error>>> {
+        match self.ctx().model_repository.purge() {
+            Ok(freed) => Ok(Ok(freed)),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("purge cache failed with '{}'", error)
+                );
+            }
+        }
+    }
+
+    fn search(
+        &mut self,
+        query: String,
+    ) -> Result<Result<Vec<model_repository::RemoteRepository>, Resource<model_repository::Error>>>
+    {
+        match self.ctx().model_repository.search(query.clone()) {
+            Ok(repositories) => Ok(Ok(repositories
+                .into_iter()
+                .map(|repo| model_repository::RemoteRepository {
+                    id: repo.id,
+                    downloads: repo.downloads,
+                    likes: repo.likes,
+                })
+                .collect())),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("search for '{}' failed with '{}'", query, error)
+                );
+            }
+        }
+    }
+
+    fn list_remote(
+        &mut self,
+        repo: String,
+    ) -> Result<Result<Vec<model_repository::RemoteFile>, Resource<model_repository::Error>>> {
+        match self.ctx().model_repository.list_remote(repo.clone()) {
+            Ok(files) => Ok(Ok(files
+                .into_iter()
+                .map(|file| model_repository::RemoteFile {
+                    name: file.name,
+                    size: file.size,
+                })
+                .collect())),
+            Err(error) => {
+                model_bail!(
+                    self,
+                    error.clone(),
+                    anyhow!("list remote '{}' failed with '{}'", repo, error)
+                );
+            }
+        }
+    }
 }
 
 impl<T> model_repository::HostError for AiImpl<T>
@@ -700,6 +1147,7 @@ where
             ModelErrorCode::InvalidModelName => Ok(model_repository::ErrorCode::InvalidModelName),
             ModelErrorCode::RuntimeError => Ok(model_repository::ErrorCode::RuntimeError),
             ModelErrorCode::NotEnabled => Ok(model_repository::ErrorCode::NotEnabled),
+            ModelErrorCode::ChecksumMismatch => Ok(model_repository::ErrorCode::ChecksumMismatch),
             ModelErrorCode::Unknown => Ok(model_repository::ErrorCode::Unknown),
         }
     }
@@ -722,37 +1170,36 @@ where
     T: AiView,
 {
     fn new(&mut self) -> Result<Resource<context::Context>> {
-        let ctx = Context {};
+        let ctx = SessionContext::default();
         let id: Resource<context::Context> = self.table().push(ctx)?;
         Ok(id)
     }
 
-    fn push(
+    async fn push(
         &mut self,
-        _self: Resource<context::Context>,
-        _msg: context::Message,
+        self_: Resource<context::Context>,
+        msg: context::Message,
     ) -> Result<std::result::Result<(), Resource<context::Error>>> {
-        let e = context::Error {
-            code: ContextErrorCode::Unknown,
-            data: anyhow::anyhow!("Context not implemented").into(),
-        };
-        let r = self.table().push(e)?;
-        return Ok(Err(r));
+        // Remember finalized turns, memory is best effort and should not fail the push
+        let ctx = self.ctx();
+        if let Err(e) = ctx.memory.remember(&mut ctx.rag, &msg).await {
+            log::debug!("message not remembered: {}", e);
+        }
+
+        let context = self.table().get_mut(&self_)?;
+        context.messages.push(msg);
+        Ok(Ok(()))
     }
 
     fn messages(
         &mut self,
-        _self: Resource<context::Context>,
+        self_: Resource<context::Context>,
     ) -> Result<std::result::Result<Vec<context::Message>, Resource<context::Error>>> {
-        let e = context::Error {
-            code: ContextErrorCode::Unknown,
-            data: anyhow::anyhow!("Context not implemented").into(),
-        };
-        let r = self.table().push(e)?;
-        return Ok(Err(r));
+        let context = self.table().get(&self_)?;
+        Ok(Ok(context.messages.clone()))
     }
 
-    fn drop(&mut self, rep: wasmtime::component::Resource<Context>) -> wasmtime::Result<()> {
+    fn drop(&mut self, rep: Resource<context::Context>) -> wasmtime::Result<()> {
         self.table().delete(rep)?;
         return Ok(());
     }
@@ -784,3 +1231,312 @@ where
         return Ok(());
     }
 }
+
+impl<T> memory::Host for AiImpl<T>
+where
+    T: AiView,
+{
+    async fn retrieve_memories(
+        &mut self,
+        query: String,
+        limit: u32,
+    ) -> Result<Result<Vec<String>, Resource<memory::Error>>> {
+        let ctx = self.ctx();
+        match ctx.memory.retrieve(&mut ctx.rag, query, limit).await {
+            Ok(memories) => Ok(Ok(memories)),
+            Err(error) => {
+                let e = memory::Error {
+                    code: error.clone(),
+                    data: anyhow!("retrieve memories failed with '{}'", error),
+                };
+                let r = self.table().push(e)?;
+                return Ok(Err(r));
+            }
+        }
+    }
+}
+
+impl<T> memory::HostError for AiImpl<T>
+where
+    T: AiView,
+{
+    fn code(&mut self, error: Resource<memory::Error>) -> Result<memory::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            MemoryErrorCode::ConnectionFailed => Ok(memory::ErrorCode::ConnectionFailed),
+            MemoryErrorCode::EmbedFailed => Ok(memory::ErrorCode::EmbedFailed),
+            MemoryErrorCode::QueryFailed => Ok(memory::ErrorCode::QueryFailed),
+            MemoryErrorCode::NotEnabled => Ok(memory::ErrorCode::NotEnabled),
+            MemoryErrorCode::Unknown => Ok(memory::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<memory::Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        return Ok(error.data.to_string());
+    }
+
+    fn drop(&mut self, error: Resource<memory::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        return Ok(());
+    }
+}
+
+impl<T> trace::Host for AiImpl<T>
+where
+    T: AiView,
+{
+    fn query(
+        &mut self,
+        filter: trace::TraceFilter,
+    ) -> Result<Result<Vec<trace::TraceEntry>, trace::ErrorCode>> {
+        let Some(recorder) = &self.ctx().trace else {
+            return Ok(Err(trace::ErrorCode::NotEnabled));
+        };
+        let query = TraceFilter {
+            model: filter.model,
+            since: filter.since,
+            limit: filter.limit as usize,
+        };
+        match recorder.query(filter.session.as_deref(), &query) {
+            Ok(entries) => Ok(Ok(entries.into_iter().map(trace_entry).collect())),
+            Err(error) => {
+                log::warn!("failed to query trace: {:?}", error);
+                Ok(Err(trace::ErrorCode::ReadFailed))
+            }
+        }
+    }
+}
+
+fn model_info(info: ModelInfo) -> model_repository::ModelInfo {
+    model_repository::ModelInfo {
+        name: info.name,
+        path: info.path,
+        size: info.size,
+        sha256: info.sha256,
+        architecture: info.architecture,
+        quantization: info.quantization,
+        context_length: info.context_length,
+        downloaded_at: info.downloaded_at,
+    }
+}
+
+fn trace_entry(entry: TraceEntry) -> trace::TraceEntry {
+    trace::TraceEntry {
+        session: entry.session,
+        model: entry.model,
+        kind: match entry.kind {
+            CallKind::Compute => trace::CallKind::Compute,
+            CallKind::ComputeStream => trace::CallKind::ComputeStream,
+        },
+        started: entry.started,
+        finished: entry.finished,
+        inputs: entry.inputs,
+        output: entry.output,
+        error: entry.error,
+    }
+}
+
+impl<T> eval::Host for AiImpl<T>
+where
+    T: AiView,
+{
+    async fn run(
+        &mut self,
+        dataset: String,
+        options: eval::EvalOptions,
+    ) -> Result<Result<eval::Report, Resource<eval::Error>>> {
+        if !self.ctx().capabilities.ai_compute {
+            let e = eval::Error {
+                code: EvalErrorCode::NotEnabled,
+                data: anyhow!("ai compute is disabled"),
+            };
+            let r = self.table().push(e)?;
+            return Ok(Err(r));
+        }
+
+        let options = EvalOptions {
+            model: options.model,
+            concurrency: options.concurrency as usize,
+        };
+        match evaluate(self.ctx(), &dataset, &options)
+            .instrument(tracing::info_span!("ai.eval"))
+            .await
+        {
+            Ok(report) => Ok(Ok(eval_report(report))),
+            Err(e) => {
+                let r = self.table().push(e)?;
+                Ok(Err(r))
+            }
+        }
+    }
+}
+
+impl<T> eval::HostError for AiImpl<T>
+where
+    T: AiView,
+{
+    fn code(&mut self, error: Resource<eval::Error>) -> Result<eval::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            EvalErrorCode::InvalidDataset => Ok(eval::ErrorCode::InvalidDataset),
+            EvalErrorCode::LoadFailed => Ok(eval::ErrorCode::LoadFailed),
+            EvalErrorCode::NotEnabled => Ok(eval::ErrorCode::NotEnabled),
+            EvalErrorCode::Unknown => Ok(eval::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<eval::Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<eval::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+fn eval_report(report: Report) -> eval::Report {
+    eval::Report {
+        model: report.model,
+        cases: report
+            .cases
+            .into_iter()
+            .map(|case| eval::CaseResult {
+                id: case.id,
+                output: case.output,
+                error: case.error,
+                passed: case.passed,
+                score: case.score,
+                checks: case
+                    .checks
+                    .into_iter()
+                    .map(|check| eval::CheckResult {
+                        kind: check.kind,
+                        passed: check.passed,
+                        score: check.score,
+                        detail: check.detail,
+                    })
+                    .collect(),
+                duration_ms: case.duration_ms,
+            })
+            .collect(),
+        passed: report.passed as u32,
+        failed: report.failed as u32,
+        score: report.score,
+        duration_ms: report.duration_ms,
+    }
+}
+
+impl<T> tokens::Host for AiImpl<T>
+where
+    T: AiView,
+{
+    fn tokenize(
+        &mut self,
+        model: String,
+        text: String,
+    ) -> Result<Result<Vec<u32>, Resource<errors::Error>>> {
+        let graph = match self.ctx().load_model(&model) {
+            Ok(graph) => graph,
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        };
+        match graph.tokenize(&text) {
+            Ok(tokens) => Ok(Ok(tokens)),
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
+    }
+
+    fn detokenize(
+        &mut self,
+        model: String,
+        tokens: Vec<u32>,
+    ) -> Result<Result<String, Resource<errors::Error>>> {
+        let graph = match self.ctx().load_model(&model) {
+            Ok(graph) => graph,
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        };
+        match graph.detokenize(&tokens) {
+            Ok(text) => Ok(Ok(text)),
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
+    }
+
+    fn tokenize_count(
+        &mut self,
+        model: String,
+        text: String,
+    ) -> Result<Result<u32, Resource<errors::Error>>> {
+        let graph = match self.ctx().load_model(&model) {
+            Ok(graph) => graph,
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        };
+        match graph.tokenize_count(&text) {
+            Ok(count) => Ok(Ok(count as u32)),
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
+    }
+
+    fn context_size(&mut self, model: String) -> Result<Result<u32, Resource<errors::Error>>> {
+        match self.ctx().load_model(&model) {
+            Ok(graph) => Ok(Ok(graph.context_size().unwrap_or_default() as u32)),
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
+    }
+
+    fn fit_messages(
+        &mut self,
+        model: String,
+        messages: Vec<tokens::Message>,
+        budget: u32,
+    ) -> Result<Result<Vec<tokens::Message>, Resource<errors::Error>>> {
+        let graph = match self.ctx().load_model(&model) {
+            Ok(graph) => graph,
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        };
+        let budget = match (budget, graph.context_size()) {
+            (0, Some(context_size)) => context_size,
+            (0, None) => {
+                bail!(
+                    self,
+                    ErrorCode::InvalidArgument,
+                    anyhow!("the context size of {} is unknown, set a budget", model)
+                );
+            }
+            (budget, _) => budget as usize,
+        };
+        match fit_messages(messages, budget, |text| graph.tokenize_count(text)) {
+            Ok(messages) => Ok(Ok(messages)),
+            Err(BackendError::FailedContextTooLarge) => {
+                bail!(
+                    self,
+                    ErrorCode::TooLarge,
+                    anyhow!(
+                        "the system messages and the last message do not fit in {} tokens",
+                        budget
+                    )
+                );
+            }
+            Err(error) => {
+                bail!(self, ErrorCode::from(&error), error);
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use super::bindings::graph::{ExecutionTarget, GraphEncoding};
+use super::Backend;
+use hayride_host_traits::ai::{BackendError, Graph};
+use ring::digest::{digest, SHA256};
+use std::path::PathBuf;
+
+// Magic number starting gguf files
+const GGUF_MAGIC: &[u8] = b"GGUF";
+
+// Suffix of model names selecting the device they are executed on, e.g. `?target=gpu`
+const TARGET_SUFFIX: &str = "?target=";
+
+struct Registered {
+    name: String,
+    backend: Backend,
+    // Encodings of the models the backend loads, empty if it takes any model
+    encodings: Vec<GraphEncoding>,
+    targets: Vec<ExecutionTarget>,
+}
+
+/// Routes models to the machine learning backend that serves them.
+///
+/// A model name can select a backend explicitly with a `<backend>:` or `<backend>://`
+/// prefix, e.g. `llama:Llama-3.2-1B.gguf` or `onnx://model.onnx`, the prefix is stripped
+/// before the model is loaded. Other names are matched against the configured routes in
+/// order, then go to the first backend loading the encoding of their file extension on
+/// the requested device, falling back to the default backend for names without a known
+/// extension. The device defaults to the cpu and is requested with a `?target=gpu` suffix.
+pub struct BackendRegistry {
+    // Backends in the order they were registered
+    backends: Vec<Registered>,
+    // Model name patterns and the backend they are routed to
+    routes: Vec<(String, String)>,
+    default: Option<String>,
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        Self {
+            backends: vec![],
+            routes: vec![],
+            default: None,
+        }
+    }
+
+    /// Register a backend by name that takes any model on the cpu, the first registered
+    /// backend is the default.
+    pub fn register(&mut self, name: impl Into<String>, backend: Backend) {
+        self.register_for(name, backend, &[], &[ExecutionTarget::Cpu]);
+    }
+
+    /// Register a backend by name that loads models of the encodings, executing them on
+    /// the targets.
+    pub fn register_for(
+        &mut self,
+        name: impl Into<String>,
+        backend: Backend,
+        encodings: &[GraphEncoding],
+        targets: &[ExecutionTarget],
+    ) {
+        let name = name.into();
+        if self.default.is_none() {
+            self.default = Some(name.clone());
+        }
+        self.backends.retain(|registered| registered.name != name);
+        self.backends.push(Registered {
+            name,
+            backend,
+            encodings: encodings.to_vec(),
+            targets: targets.to_vec(),
+        });
+    }
+
+    /// Set the backend used when no prefix, route or encoding matches the model name.
+    pub fn set_default(&mut self, name: impl Into<String>) {
+        self.default = Some(name.into());
+    }
+
+    /// Route model names matching the pattern to the named backend.
+    ///
+    /// Patterns are matched against the lowercase file name of the model and may
+    /// contain `*` wildcards, e.g. `ggml-*.bin` or `*.onnx`.
+    pub fn route(&mut self, pattern: impl Into<String>, backend: impl Into<String>) {
+        self.routes
+            .push((pattern.into().to_lowercase(), backend.into()));
+    }
+
+    /// Load the named model with the backend it is routed to.
+    pub fn load(&mut self, name: String) -> Result<Graph, BackendError> {
+        let Some((backend_name, model)) = self.resolve(&name) else {
+            crate::status::status().model_error();
+            return Err(BackendError::FailedToLoadModel);
+        };
+        self.load_with(&backend_name, model)
+    }
+
+    /// Load a model from its bytes with a backend loading the encoding on the target.
+    ///
+    /// Backends load models from files, so the model is written to a file named by its
+    /// digest in the temp dir first. Models split over several builders are not supported.
+    pub fn load_graph(
+        &mut self,
+        builders: Vec<Vec<u8>>,
+        encoding: GraphEncoding,
+        target: ExecutionTarget,
+    ) -> Result<Graph, BackendError> {
+        let [bytes] = builders.as_slice() else {
+            log::warn!(
+                "models of {} graph builders are not supported",
+                builders.len()
+            );
+            return Err(BackendError::Unsupported);
+        };
+        let encoding = match encoding {
+            GraphEncoding::Autodetect => detect_encoding(bytes).ok_or_else(|| {
+                log::warn!("failed to detect the encoding of model");
+                BackendError::UnsupportedEncoding
+            })?,
+            encoding => encoding,
+        };
+        let Some(backend_name) = self.select(encoding, target) else {
+            return Err(BackendError::UnsupportedEncoding);
+        };
+
+        let path = write_model(bytes, encoding).map_err(|e| {
+            log::warn!("failed to write model: {:?}", e);
+            BackendError::FailedToLoadModel
+        })?;
+        let model = path
+            .to_str()
+            .ok_or(BackendError::FailedToLoadModel)?
+            .to_string();
+        self.load_with(&backend_name, model)
+    }
+
+    fn load_with(&mut self, backend_name: &str, model: String) -> Result<Graph, BackendError> {
+        log::debug!("routing model {} to backend: {}", model, backend_name);
+
+        let result = match self.get_mut(backend_name) {
+            Some(registered) => registered.backend.load(model.clone()),
+            None => {
+                log::warn!("no backend registered for: {}", backend_name);
+                Err(BackendError::FailedToLoadModel)
+            }
+        };
+
+        match result {
+            Ok(_) => crate::status::status().model_loaded(&model, backend_name),
+            Err(_) => crate::status::status().model_error(),
+        }
+        result
+    }
+
+    // Resolve the backend name and model name for the requested model
+    fn resolve(&self, name: &str) -> Option<(String, String)> {
+        let (name, target) = match name.rsplit_once(TARGET_SUFFIX) {
+            Some((name, target)) => (name, parse_target(target)?),
+            None => (name, ExecutionTarget::Cpu),
+        };
+        if let Some((prefix, model)) = name.split_once(':') {
+            if self.get(prefix).is_some() {
+                let model = model.strip_prefix("//").unwrap_or(model);
+                return Some((prefix.to_string(), model.to_string()));
+            }
+        }
+
+        let file_name = std::path::Path::new(name)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(name)
+            .to_lowercase();
+        for (pattern, backend) in &self.routes {
+            if self.get(backend).is_some() && matches(pattern, &file_name) {
+                return Some((backend.clone(), name.to_string()));
+            }
+        }
+
+        if let Some(encoding) = encoding_of(&file_name) {
+            return Some((self.select(encoding, target)?, name.to_string()));
+        }
+
+        Some((self.default.clone().unwrap_or_default(), name.to_string()))
+    }
+
+    // Select the first backend loading the encoding on the target, or on another device
+    // if none executes models on the target
+    fn select(&self, encoding: GraphEncoding, target: ExecutionTarget) -> Option<String> {
+        let loads = |registered: &&Registered| {
+            registered.encodings.is_empty() || registered.encodings.contains(&encoding)
+        };
+        if let Some(registered) = self
+            .backends
+            .iter()
+            .filter(loads)
+            .find(|registered| registered.targets.contains(&target))
+        {
+            return Some(registered.name.clone());
+        }
+
+        match self.backends.iter().find(loads) {
+            Some(registered) => {
+                log::warn!(
+                    "no backend executes {:?} models on {:?}, using {}",
+                    encoding,
+                    target,
+                    registered.name
+                );
+                Some(registered.name.clone())
+            }
+            None => {
+                log::warn!("no backend loads {:?} models", encoding);
+                None
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Registered> {
+        self.backends
+            .iter()
+            .find(|registered| registered.name == name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Registered> {
+        self.backends
+            .iter_mut()
+            .find(|registered| registered.name == name)
+    }
+}
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Encoding of a model by the extension of its file name
+fn encoding_of(file_name: &str) -> Option<GraphEncoding> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    match extension {
+        "onnx" => Some(GraphEncoding::Onnx),
+        "gguf" | "ggml" => Some(GraphEncoding::Ggml),
+        "pt" | "pth" => Some(GraphEncoding::Pytorch),
+        "tflite" => Some(GraphEncoding::Tensorflowlite),
+        "pb" => Some(GraphEncoding::Tensorflow),
+        "xml" => Some(GraphEncoding::Openvino),
+        _ => None,
+    }
+}
+
+// Extension models of an encoding are written with
+fn extension_of(encoding: GraphEncoding) -> &'static str {
+    match encoding {
+        GraphEncoding::Onnx => "onnx",
+        GraphEncoding::Ggml => "gguf",
+        GraphEncoding::Pytorch => "pt",
+        GraphEncoding::Tensorflowlite => "tflite",
+        GraphEncoding::Tensorflow => "pb",
+        GraphEncoding::Openvino => "xml",
+        GraphEncoding::Autodetect => "bin",
+    }
+}
+
+fn detect_encoding(bytes: &[u8]) -> Option<GraphEncoding> {
+    bytes.starts_with(GGUF_MAGIC).then_some(GraphEncoding::Ggml)
+}
+
+fn parse_target(target: &str) -> Option<ExecutionTarget> {
+    match target.to_lowercase().as_str() {
+        "cpu" => Some(ExecutionTarget::Cpu),
+        "gpu" => Some(ExecutionTarget::Gpu),
+        "tpu" => Some(ExecutionTarget::Tpu),
+        _ => {
+            log::warn!("unknown execution target: {}", target);
+            None
+        }
+    }
+}
+
+// Write a model to the temp dir by its digest, reusing the file if it was written before
+fn write_model(bytes: &[u8], encoding: GraphEncoding) -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("hayride").join("models");
+    std::fs::create_dir_all(&dir)?;
+
+    let name: String = digest(&SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let path = dir.join(format!("{}.{}", name, extension_of(encoding)));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    // Write to a temporary file first so concurrent loads never see a partial model
+    let partial = dir.join(format!("{}.{}", name, uuid::Uuid::new_v4()));
+    std::fs::write(&partial, bytes)?;
+    if let Err(e) = std::fs::rename(&partial, &path) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+// Match a value against a pattern where `*` matches any sequence of characters
+pub(crate) fn matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard, the value must match exactly
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
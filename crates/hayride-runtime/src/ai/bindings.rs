@@ -9,8 +9,22 @@ mod generated {
         // async bindings as is the case with WASI in-tree.
         require_store_data_send: true,
 
-        // Wrap functions returns with a result with error
+        // Wrap functions returns with a result with error, functions that wait on
+        // a backend are async so they do not block the runtime
         imports: {
+            "wasi:nn/inference/[method]graph-execution-context.compute": async | trappable,
+            "hayride:ai/inference-stream/[method]graph-execution-context-stream.compute": async | trappable,
+            "hayride:ai/rag/connect": async | trappable,
+            "hayride:ai/rag/[method]connection.register": async | trappable,
+            "hayride:ai/rag/[method]connection.embed": async | trappable,
+            "hayride:ai/rag/[method]connection.query": async | trappable,
+            "hayride:ai/rag/[method]connection.delete": async | trappable,
+            "hayride:ai/rag/[method]connection.upsert": async | trappable,
+            "hayride:ai/rag/[method]connection.embed-batch": async | trappable,
+            "hayride:ai/rag/[method]connection.upsert-batch": async | trappable,
+            "hayride:ai/context/[method]context.push": async | trappable,
+            "hayride:ai/memory/retrieve-memories": async | trappable,
+            "hayride:ai/eval/run": async | trappable,
             default: trappable,
         },
         with: {
@@ -28,7 +42,9 @@ mod generated {
             "hayride:ai/transformer/transformer": hayride_host_traits::ai::rag::Transformer,
             "hayride:ai/rag/error": hayride_host_traits::ai::rag::Error,
             "hayride:ai/model-repository/error": hayride_host_traits::ai::model::Error,
-            "hayride:ai/context/context": hayride_host_traits::ai::context::Context,
+            "hayride:ai/memory/error": hayride_host_traits::ai::memory::Error,
+            "hayride:ai/eval/error": hayride_host_traits::ai::eval::Error,
+            "hayride:ai/context/context": crate::ai::memory::SessionContext,
             "hayride:ai/context/error": hayride_host_traits::ai::context::Error,
         },
     });
@@ -0,0 +1,537 @@
+use super::postprocess::model_profiles;
+use super::AiCtx;
+use crate::gateway::{prompt_options, ChatFormat};
+use crate::timeouts::{deadline, HostTimeouts};
+use hayride_host_traits::ai::eval::{Error, ErrorCode};
+use hayride_host_traits::ai::nn::OutputFilter;
+use hayride_host_traits::ai::{BackendError, ExecutionContext, Graph, Tensor, TensorType};
+use hayride_host_traits::validate::{Schema, ValidateTrait};
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Cases computed at once unless the options set it.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+// Most cases computed at once
+const MAX_CONCURRENCY: usize = 64;
+
+// Similarity an output needs to pass a check that sets no threshold
+const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// Prompts with the properties their output is expected to have.
+///
+/// Read from json like:
+///
+/// ```json
+/// {
+///   "model": "unsloth/Qwen3-0.6B-GGUF/Qwen3-0.6B-Q4_K_M.gguf",
+///   "embedding_model": "nomic-ai/nomic-embed-text-v1.5-GGUF/nomic-embed-text-v1.5.Q4_K_M.gguf",
+///   "options": { "temperature": 0, "max_tokens": 256, "seed": 1 },
+///   "cases": [
+///     {
+///       "id": "capital",
+///       "messages": [{ "role": "user", "content": "What is the capital of France?" }],
+///       "expect": [
+///         { "regex": "(?i)paris" },
+///         { "similar": "The capital of France is Paris.", "threshold": 0.7 }
+///       ]
+///     },
+///     {
+///       "prompt": "Reply with a json object with a name field.",
+///       "expect": [{ "json_schema": { "type": "object", "required": ["name"] } }]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// Cases take a raw `prompt`, or `messages` laid out in the chat format of the model.
+/// Options are sampling parameters named as in the OpenAI api, applied to every case.
+pub struct Dataset {
+    pub model: Option<String>,
+    /// Model `similar` checks embed outputs with, the evaluated model if unset
+    pub embedding_model: Option<String>,
+    pub options: Value,
+    pub cases: Vec<EvalCase>,
+}
+
+pub struct EvalCase {
+    pub id: String,
+    pub input: CaseInput,
+    pub expect: Vec<Check>,
+}
+
+pub enum CaseInput {
+    Prompt(String),
+    /// `(role, content)` rendered in the chat format of the model
+    Messages(Vec<(String, String)>),
+}
+
+/// A property the output of a case is expected to have.
+pub enum Check {
+    /// The output matches the pattern
+    Regex(Regex),
+    /// The output is a json document valid against the schema
+    JsonSchema(Schema),
+    /// The embedding of the output is at least as similar as the threshold to the
+    /// embedding of the text, by cosine similarity
+    Similar { text: String, threshold: f64 },
+}
+
+impl Check {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Regex(_) => "regex",
+            Self::JsonSchema(_) => "json-schema",
+            Self::Similar { .. } => "similar",
+        }
+    }
+}
+
+impl Dataset {
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let json: DatasetJson =
+            serde_json::from_str(json).map_err(|e| anyhow!("invalid dataset: {}", e))?;
+        let cases = json
+            .cases
+            .into_iter()
+            .enumerate()
+            .map(|(index, case)| EvalCase::from_json(index, case))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            model: json.model,
+            embedding_model: json.embedding_model,
+            options: json.options,
+            cases,
+        })
+    }
+
+    fn needs_embeddings(&self) -> bool {
+        self.cases.iter().any(|case| {
+            case.expect
+                .iter()
+                .any(|check| matches!(check, Check::Similar { .. }))
+        })
+    }
+}
+
+impl EvalCase {
+    fn from_json(index: usize, json: CaseJson) -> anyhow::Result<Self> {
+        let id = json.id.unwrap_or_else(|| index.to_string());
+
+        let input = match (json.prompt, json.messages) {
+            (Some(prompt), None) => CaseInput::Prompt(prompt),
+            (None, Some(messages)) => CaseInput::Messages(
+                messages
+                    .into_iter()
+                    .map(|message| (message.role, message.content))
+                    .collect(),
+            ),
+            _ => return Err(anyhow!("case {}: set either prompt or messages", id)),
+        };
+
+        let expect = json
+            .expect
+            .into_iter()
+            .map(|check| Check::from_json(check).map_err(|e| anyhow!("case {}: {}", id, e)))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { id, input, expect })
+    }
+
+    fn prompt(&self, model: &str) -> String {
+        match &self.input {
+            CaseInput::Prompt(prompt) => prompt.clone(),
+            CaseInput::Messages(messages) => ChatFormat::detect(model).prompt(messages),
+        }
+    }
+}
+
+impl Check {
+    fn from_json(json: CheckJson) -> anyhow::Result<Self> {
+        match json {
+            CheckJson::Regex { regex } => Ok(Self::Regex(Regex::new(&regex)?)),
+            CheckJson::JsonSchema { json_schema } => {
+                let schema = hayride_validate::ValidateBackend::new()
+                    .compile(json_schema.to_string())
+                    .map_err(|e| anyhow!("json schema does not compile: {:?}", e))?;
+                Ok(Self::JsonSchema(schema))
+            }
+            CheckJson::Similar { similar, threshold } => Ok(Self::Similar {
+                text: similar,
+                threshold: threshold.unwrap_or(DEFAULT_THRESHOLD),
+            }),
+        }
+    }
+}
+
+// A dataset as written, cases are checked once parsed
+#[derive(Deserialize)]
+struct DatasetJson {
+    model: Option<String>,
+    embedding_model: Option<String>,
+    #[serde(default = "empty_options")]
+    options: Value,
+    cases: Vec<CaseJson>,
+}
+
+#[derive(Deserialize)]
+struct CaseJson {
+    id: Option<String>,
+    prompt: Option<String>,
+    messages: Option<Vec<MessageJson>>,
+    #[serde(default)]
+    expect: Vec<CheckJson>,
+}
+
+#[derive(Deserialize)]
+struct MessageJson {
+    role: String,
+    content: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a regex, json_schema or similar check")]
+enum CheckJson {
+    Regex {
+        regex: String,
+    },
+    JsonSchema {
+        json_schema: Value,
+    },
+    Similar {
+        similar: String,
+        threshold: Option<f64>,
+    },
+}
+
+fn empty_options() -> Value {
+    json!({})
+}
+
+/// How a dataset is run.
+#[derive(Clone, Debug, Default)]
+pub struct EvalOptions {
+    /// Overrides the model of the dataset
+    pub model: Option<String>,
+    /// Cases computed at once, the default if 0
+    pub concurrency: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub kind: String,
+    pub passed: bool,
+    pub score: f64,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CaseResult {
+    pub id: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub passed: bool,
+    pub score: f64,
+    pub checks: Vec<CheckResult>,
+    pub duration_ms: u64,
+}
+
+/// Scores of the cases of a dataset, in the order of the dataset.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub model: String,
+    pub cases: Vec<CaseResult>,
+    pub passed: usize,
+    pub failed: usize,
+    pub score: f64,
+    pub duration_ms: u64,
+}
+
+/// Run the dataset against its model with the backends of the context.
+///
+/// The context is only borrowed to load the models, cases are computed on execution
+/// contexts of their own so they run in parallel.
+pub async fn evaluate(
+    ai: &mut AiCtx,
+    dataset: &str,
+    options: &EvalOptions,
+) -> Result<Report, Error> {
+    let dataset = Dataset::parse(dataset).map_err(|e| Error {
+        code: ErrorCode::InvalidDataset,
+        data: e,
+    })?;
+    let model = options
+        .model
+        .clone()
+        .or_else(|| dataset.model.clone())
+        .ok_or_else(|| Error {
+            code: ErrorCode::InvalidDataset,
+            data: anyhow!("no model to evaluate, set one in the dataset or the options"),
+        })?;
+
+    let load_failed = |model: &str, e: BackendError| Error {
+        code: ErrorCode::LoadFailed,
+        data: anyhow!("failed to load model {}: {}", model, e),
+    };
+    let graph = ai
+        .load_model_blocking(&model)
+        .await
+        .map_err(|e| load_failed(&model, e))?;
+    let embedder = match &dataset.embedding_model {
+        Some(embedding_model) if dataset.needs_embeddings() => Some(
+            ai.load_model_blocking(embedding_model)
+                .await
+                .map_err(|e| load_failed(embedding_model, e))?,
+        ),
+        _ => None,
+    };
+
+    let concurrency = match options.concurrency {
+        0 => DEFAULT_CONCURRENCY,
+        concurrency => concurrency.min(MAX_CONCURRENCY),
+    };
+    let runner = EvalRunner {
+        graph,
+        embedder,
+        model,
+        timeouts: ai.timeouts,
+        output_filter: ai.output_filter.clone(),
+    };
+    runner.run(dataset, concurrency).await
+}
+
+// Computes the cases of a dataset on workers of their own
+struct EvalRunner {
+    graph: Graph,
+    // Embeds for similarity checks, the evaluated model if unset
+    embedder: Option<Graph>,
+    model: String,
+    timeouts: HostTimeouts,
+    output_filter: Option<Arc<OutputFilter>>,
+}
+
+// Execution contexts of a worker
+struct Worker {
+    context: ExecutionContext,
+    embedder: Option<ExecutionContext>,
+}
+
+impl EvalRunner {
+    async fn run(self, dataset: Dataset, concurrency: usize) -> Result<Report, Error> {
+        let started = Instant::now();
+        let workers = concurrency.min(dataset.cases.len()).max(1);
+        let load_failed = |e| Error {
+            code: ErrorCode::LoadFailed,
+            data: anyhow!("failed to init an execution context: {}", e),
+        };
+
+        let runner = Arc::new(self);
+        let dataset = Arc::new(dataset);
+        let next = Arc::new(AtomicUsize::new(0));
+        let results = Arc::new(Mutex::new(Vec::with_capacity(dataset.cases.len())));
+
+        let mut tasks = Vec::with_capacity(workers);
+        for _ in 0..workers {
+            let mut worker = Worker {
+                context: runner.graph.init_execution_context().map_err(load_failed)?,
+                embedder: match &runner.embedder {
+                    Some(embedder) => Some(embedder.init_execution_context().map_err(load_failed)?),
+                    None => None,
+                },
+            };
+            let (runner, dataset, next, results) = (
+                runner.clone(),
+                dataset.clone(),
+                next.clone(),
+                results.clone(),
+            );
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(case) = dataset.cases.get(index) else {
+                        break;
+                    };
+                    let result = runner.run_case(&mut worker, &dataset.options, case).await;
+                    results
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push((index, result));
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.map_err(|e| Error {
+                code: ErrorCode::Unknown,
+                data: anyhow!("evaluation worker failed: {}", e),
+            })?;
+        }
+
+        let mut results = std::mem::take(&mut *results.lock().unwrap_or_else(|e| e.into_inner()));
+        results.sort_by_key(|(index, _)| *index);
+        let cases: Vec<CaseResult> = results.into_iter().map(|(_, result)| result).collect();
+        let passed = cases.iter().filter(|case| case.passed).count();
+        let score = match cases.len() {
+            0 => 0.0,
+            len => cases.iter().map(|case| case.score).sum::<f64>() / len as f64,
+        };
+
+        Ok(Report {
+            model: runner.model.clone(),
+            failed: cases.len() - passed,
+            passed,
+            score,
+            duration_ms: started.elapsed().as_millis() as u64,
+            cases,
+        })
+    }
+
+    async fn run_case(&self, worker: &mut Worker, options: &Value, case: &EvalCase) -> CaseResult {
+        let started = Instant::now();
+        let mut result = CaseResult {
+            id: case.id.clone(),
+            output: None,
+            error: None,
+            passed: false,
+            score: 0.0,
+            checks: vec![],
+            duration_ms: 0,
+        };
+
+        match self.compute(worker, options, case).await {
+            Ok(output) => {
+                for check in &case.expect {
+                    let check = self.check(worker, check, &output).await;
+                    result.checks.push(check);
+                }
+                result.passed = result.checks.iter().all(|check| check.passed);
+                result.score = match result.checks.len() {
+                    0 => 1.0,
+                    len => result.checks.iter().map(|check| check.score).sum::<f64>() / len as f64,
+                };
+                result.output = Some(output);
+            }
+            Err(error) => result.error = Some(error),
+        }
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        result
+    }
+
+    // Compute the output of a case like the gateway completes a chat
+    async fn compute(
+        &self,
+        worker: &mut Worker,
+        options: &Value,
+        case: &EvalCase,
+    ) -> Result<String, String> {
+        let inputs = vec![
+            ("prompt".to_string(), text_tensor(case.prompt(&self.model))),
+            (
+                "options".to_string(),
+                text_tensor(prompt_options(options)?.to_string()),
+            ),
+        ];
+        let post_processor = model_profiles()
+            .post_processor(Some(&self.model), &inputs)
+            .map_err(|e| e.to_string())?;
+
+        let tensor = deadline(self.timeouts.ai, worker.context.compute(inputs))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let mut output = tensor.data;
+        if !post_processor.is_empty() {
+            output = post_processor.apply(&output);
+        }
+        if let Some(filter) = &self.output_filter {
+            output = filter.mask(&output);
+        }
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    async fn check(&self, worker: &mut Worker, check: &Check, output: &str) -> CheckResult {
+        let (passed, score, detail) = match check {
+            Check::Regex(regex) => match regex.is_match(output) {
+                true => (true, 1.0, String::new()),
+                false => (false, 0.0, format!("output does not match {}", regex)),
+            },
+            Check::JsonSchema(schema) => match schema.validate(output.to_string()) {
+                Ok(errors) if errors.is_empty() => (true, 1.0, String::new()),
+                Ok(errors) => {
+                    let messages: Vec<String> = errors
+                        .iter()
+                        .map(|e| format!("{}: {}", e.instance_location, e.message))
+                        .collect();
+                    (false, 0.0, messages.join("; "))
+                }
+                Err(e) => (
+                    false,
+                    0.0,
+                    format!("output is not a json document: {:?}", e),
+                ),
+            },
+            Check::Similar { text, threshold } => {
+                let context = match &mut worker.embedder {
+                    Some(embedder) => embedder,
+                    None => &mut worker.context,
+                };
+                let inputs = vec![output.to_string(), text.clone()];
+                match deadline(self.timeouts.ai, context.embed(inputs)).await {
+                    Ok(Ok(embeddings)) if embeddings.len() == 2 => {
+                        let similarity = cosine_similarity(&embeddings[0], &embeddings[1]);
+                        (
+                            similarity >= *threshold,
+                            similarity,
+                            format!("similarity {:.3}, threshold {:.3}", similarity, threshold),
+                        )
+                    }
+                    Ok(Ok(embeddings)) => (
+                        false,
+                        0.0,
+                        format!("expected 2 embeddings, got {}", embeddings.len()),
+                    ),
+                    Ok(Err(e)) => (false, 0.0, format!("failed to embed: {}", e)),
+                    Err(e) => (false, 0.0, e.to_string()),
+                }
+            }
+        };
+        CheckResult {
+            kind: check.kind().to_string(),
+            passed,
+            score,
+            detail,
+        }
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+fn text_tensor(data: String) -> Tensor {
+    Tensor {
+        dimensions: vec![1],
+        ty: TensorType::U8,
+        data: data.into_bytes(),
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use hayride_host_traits::ai::model::{
+    ErrorCode, ModelFilter, ModelRepositoryInner, RemoteFile, RemoteRepository,
+};
+use hayride_utils::config::LocalModelsConfig;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+static LOCAL_MODELS: OnceLock<RwLock<Option<LocalModelOptions>>> = OnceLock::new();
+
+/// Serve models from local directories in repositories created afterwards, instead of
+/// the Hugging Face hub.
+pub fn configure(options: Option<LocalModelOptions>) {
+    if let Ok(mut current) = LOCAL_MODELS.get_or_init(|| RwLock::new(None)).write() {
+        *current = options;
+    }
+}
+
+/// The local directories models are served from, if any were configured.
+pub fn configured() -> Option<LocalModelOptions> {
+    LOCAL_MODELS
+        .get_or_init(|| RwLock::new(None))
+        .read()
+        .ok()
+        .and_then(|options| options.clone())
+}
+
+/// Options for the local directory model repository.
+#[derive(Clone, Debug, Default)]
+pub struct LocalModelOptions {
+    /// Directories scanned for models, the first one containing a model serves it
+    pub roots: Vec<PathBuf>,
+    /// Serve files and directories that are symlinks
+    pub follow_symlinks: bool,
+}
+
+impl LocalModelOptions {
+    pub fn from_config(config: &LocalModelsConfig) -> Result<Self> {
+        let hayride_dir = hayride_utils::paths::hayride::default_hayride_dir()?;
+        Ok(Self {
+            roots: config
+                .roots
+                .iter()
+                .map(|root| hayride_dir.join(root))
+                .collect(),
+            follow_symlinks: config.follow_symlinks,
+        })
+    }
+}
+
+/// Serves the model files found in local directories.
+///
+/// Models are named by their path relative to the root they are in, e.g.
+/// `llama/Llama-3.2-1B.gguf`, with the directory as the repo. Models are managed outside
+/// of hayride, so they can not be downloaded or deleted.
+pub struct LocalModelRepository {
+    options: LocalModelOptions,
+}
+
+impl LocalModelRepository {
+    pub fn new(options: LocalModelOptions) -> Self {
+        for root in &options.roots {
+            if !root.is_dir() {
+                log::warn!("model directory does not exist: {}", root.display());
+            }
+        }
+        Self { options }
+    }
+
+    // Whether the path is served, symlinks only when they are followed
+    fn is_served(&self, path: &Path) -> bool {
+        self.options.follow_symlinks
+            || std::fs::symlink_metadata(path)
+                .map(|m| !m.file_type().is_symlink())
+                .unwrap_or(false)
+    }
+
+    // Model files under the root as their relative name, skipping directories already
+    // visited through a symlink
+    fn scan(&self, root: &Path) -> Vec<String> {
+        let mut models = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            let canonical = std::fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone());
+            if !visited.insert(canonical) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !self.is_served(&path) {
+                    continue;
+                }
+                if path.is_dir() {
+                    stack.push(path);
+                } else if path.is_file() {
+                    if let Some(name) = relative_name(root, &path) {
+                        models.push(name);
+                    }
+                }
+            }
+        }
+        models
+    }
+}
+
+impl ModelRepositoryInner for LocalModelRepository {
+    fn download(&mut self, name: String) -> Result<String, ErrorCode> {
+        // Nothing to download, the model is either there or not
+        self.get(name)
+    }
+
+    fn get(&self, name: String) -> Result<String, ErrorCode> {
+        let relative = Path::new(&name);
+        let is_relative = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if name.is_empty() || !is_relative {
+            return Err(ErrorCode::InvalidModelName);
+        }
+
+        for root in &self.options.roots {
+            let path = root.join(relative);
+            // Every component under the root is checked when symlinks are not followed
+            let served = self.options.follow_symlinks
+                || relative
+                    .ancestors()
+                    .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                    .all(|ancestor| self.is_served(&root.join(ancestor)));
+            if served && path.is_file() {
+                return Ok(path.to_string_lossy().to_string());
+            }
+        }
+
+        Err(ErrorCode::ModelNotFound)
+    }
+
+    fn delete(&mut self, _name: String) -> Result<(), ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+
+    fn list(&self, filter: &ModelFilter) -> Result<Vec<String>, ErrorCode> {
+        let extension = filter
+            .extension
+            .as_deref()
+            .map(|extension| extension.trim_start_matches('.'));
+
+        // Models in earlier roots hide those of the same name in later ones
+        let mut seen = HashSet::new();
+        let mut models: Vec<String> = self
+            .options
+            .roots
+            .iter()
+            .flat_map(|root| self.scan(root))
+            .filter(|name| seen.insert(name.clone()))
+            .filter(|name| {
+                let repo = name.rsplit_once('/').map(|(repo, _)| repo).unwrap_or("");
+                filter.repo.as_ref().is_none_or(|r| r == repo)
+            })
+            .filter(|name| {
+                extension.is_none_or(|extension| {
+                    Path::new(name)
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+                })
+            })
+            .collect();
+
+        models.sort();
+        Ok(models)
+    }
+
+    fn purge(&mut self) -> Result<u64, ErrorCode> {
+        // There is no cache to clean up
+        Ok(0)
+    }
+
+    fn search(&self, _query: String) -> Result<Vec<RemoteRepository>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+
+    fn list_remote(&self, _repo: String) -> Result<Vec<RemoteFile>, ErrorCode> {
+        Err(ErrorCode::NotEnabled)
+    }
+}
+
+// Name of a model file relative to its root, separated by `/` on every platform
+fn relative_name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("/"))
+}
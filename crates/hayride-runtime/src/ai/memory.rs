@@ -0,0 +1,161 @@
+use super::bindings::ai::types::{Message, MessageContent, Role};
+use super::Rag;
+use crate::middleware::KEY_NAME_HEADER;
+use hayride_host_traits::ai::memory::ErrorCode;
+use hayride_host_traits::ai::rag::{
+    Connection, Embedding, ErrorCode as RagErrorCode, RagOption, Transformer,
+};
+use ring::digest::{digest, SHA256};
+
+// Column names expected by the rag backend when querying a table.
+const DATA_COLUMN: &str = "text";
+const VECTOR_COLUMN: &str = "vector";
+const EMBEDDING_MODEL: &str = "sentence-transformers/all-MiniLM-L6-v2";
+
+/// Header clients keep their memory across requests and connections with. The session
+/// is a secret of the client, anyone sending it reads the memories of the session.
+pub const SESSION_HEADER: &str = "x-hayride-session";
+
+/// Memory session of a request to a server morph, from the session header scoped to the
+/// api key the request was authorized with. Requests without the header get a session
+/// of their own, so clients never share memories by default.
+pub fn session_id(headers: &hyper::HeaderMap) -> String {
+    let Some(session) = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|session| !session.is_empty())
+    else {
+        return uuid::Uuid::new_v4().to_string();
+    };
+    let key_name = headers
+        .get(KEY_NAME_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    // Sessions are hashed, so any header value makes a valid table name
+    let scoped = format!("{}\0{}", key_name, session);
+    digest(&SHA256, scoped.as_bytes()).as_ref()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Host-side conversation context used when a component does not provide
+/// its own implementation of the `hayride:ai/context` import.
+#[derive(Default)]
+pub struct SessionContext {
+    pub messages: Vec<Message>,
+}
+
+/// A per-session vector memory built on top of the rag backend.
+///
+/// Finalized user and assistant turns are embedded into a table owned by the
+/// session, which can later be searched with `retrieve`.
+pub struct MemoryStore {
+    session_id: String,
+    dsn: Option<String>,
+    connection: Option<Connection>,
+}
+
+impl MemoryStore {
+    pub fn new(session_id: String, dsn: Option<String>) -> Self {
+        Self {
+            session_id,
+            dsn,
+            connection: None,
+        }
+    }
+
+    /// Move the store to another session, keeping its connection.
+    pub fn set_session(&mut self, session_id: String) {
+        self.session_id = session_id;
+    }
+
+    /// The table name used to store memories for this session.
+    pub fn table(&self) -> String {
+        format!("memory_{}", self.session_id.replace('-', "_"))
+    }
+
+    /// Embed the text of a message if it is a finalized user or assistant turn.
+    pub async fn remember(&mut self, rag: &mut Rag, message: &Message) -> Result<(), ErrorCode> {
+        if !message.final_ {
+            return Ok(());
+        }
+
+        match message.role {
+            Role::User | Role::Assistant => {}
+            _ => return Ok(()),
+        }
+
+        let text = message
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                MessageContent::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        let table = self.table();
+        let connection = self.connection(rag).await?;
+        connection.embed(table, text).await.map_err(|e| {
+            log::warn!("failed to embed memory: {:?}", e);
+            ErrorCode::EmbedFailed
+        })
+    }
+
+    /// Retrieve up to `limit` memories that are the most similar to the query.
+    pub async fn retrieve(
+        &mut self,
+        rag: &mut Rag,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<String>, ErrorCode> {
+        let table = self.table();
+        let connection = self.connection(rag).await?;
+
+        let options = vec![RagOption {
+            name: "limit".to_string(),
+            value: limit.max(1).to_string(),
+        }];
+
+        match connection.query(table, query, options).await {
+            Ok(memories) => Ok(memories.into_iter().map(|m| m.text).collect()),
+            // Nothing has been remembered for this session yet
+            Err(RagErrorCode::MissingTable) => Ok(vec![]),
+            Err(e) => {
+                log::warn!("failed to query memories: {:?}", e);
+                Err(ErrorCode::QueryFailed)
+            }
+        }
+    }
+
+    // Lazily connect to the rag backend, as most sessions never use memory.
+    async fn connection(&mut self, rag: &mut Rag) -> Result<&mut Connection, ErrorCode> {
+        if self.connection.is_none() {
+            let dsn = self.dsn.clone().ok_or(ErrorCode::NotEnabled)?;
+            let mut connection = rag.connect(dsn).await.map_err(|e| match e {
+                RagErrorCode::NotEnabled => ErrorCode::NotEnabled,
+                _ => ErrorCode::ConnectionFailed,
+            })?;
+
+            connection
+                .register(Transformer {
+                    embedding: Embedding::Sentence,
+                    model: EMBEDDING_MODEL.to_string(),
+                    data_column: DATA_COLUMN.to_string(),
+                    vector_column: VECTOR_COLUMN.to_string(),
+                })
+                .await
+                .map_err(|_| ErrorCode::ConnectionFailed)?;
+
+            self.connection = Some(connection);
+        }
+
+        self.connection.as_mut().ok_or(ErrorCode::ConnectionFailed)
+    }
+}
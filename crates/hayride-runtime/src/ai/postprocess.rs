@@ -0,0 +1,114 @@
+use super::backends::matches;
+use anyhow::Result;
+use hayride_host_traits::ai::nn::{PostProcessor, PostStep};
+use hayride_host_traits::ai::Tensor;
+use hayride_utils::config;
+use std::sync::{Arc, OnceLock, RwLock};
+
+static MODEL_PROFILES: OnceLock<ModelProfiles> = OnceLock::new();
+
+/// Returns the process wide profiles of models.
+pub fn model_profiles() -> &'static ModelProfiles {
+    MODEL_PROFILES.get_or_init(ModelProfiles::default)
+}
+
+/// Post-processing of the output of models with a name matching a pattern.
+pub struct ModelProfile {
+    pattern: String,
+    post_processor: Arc<PostProcessor>,
+}
+
+impl ModelProfile {
+    /// Patterns are matched against the lowercase file name of models and may contain `*`
+    /// wildcards.
+    pub fn new(pattern: impl Into<String>, steps: Vec<PostStep>) -> Result<Self> {
+        Ok(Self {
+            pattern: pattern.into().to_lowercase(),
+            post_processor: Arc::new(PostProcessor::new(steps)?),
+        })
+    }
+
+    pub fn from_config(config: &config::ModelProfile) -> Result<Self> {
+        let steps = config
+            .post_process
+            .iter()
+            .map(|step| match step.clone() {
+                config::PostStep::Trim => PostStep::Trim,
+                config::PostStep::StripStop { sequences } => PostStep::StripStop { sequences },
+                config::PostStep::StripThink { open, close } => PostStep::StripThink {
+                    open: open.unwrap_or_else(|| "<think>".to_string()),
+                    close: close.unwrap_or_else(|| "</think>".to_string()),
+                },
+                config::PostStep::Replace {
+                    pattern,
+                    replacement,
+                } => PostStep::Replace {
+                    pattern,
+                    replacement,
+                },
+            })
+            .collect();
+        Self::new(&config.model, steps)
+            .map_err(|e| anyhow::anyhow!("invalid profile for model {}: {}", config.model, e))
+    }
+}
+
+/// Profiles of models, the first profile matching a model applies.
+#[derive(Default)]
+pub struct ModelProfiles {
+    profiles: RwLock<Vec<ModelProfile>>,
+}
+
+impl ModelProfiles {
+    pub fn configure(&self, profiles: Vec<ModelProfile>) {
+        if let Ok(mut current) = self.profiles.write() {
+            *current = profiles;
+        }
+    }
+
+    /// Returns the post-processing of compute output of a model.
+    ///
+    /// A `post_process` list of steps in the `options` input of the request overrides the
+    /// profile of the model, output is left as the backend returned it if neither sets any.
+    pub fn post_processor(
+        &self,
+        model: Option<&str>,
+        inputs: &[(String, Tensor)],
+    ) -> Result<Arc<PostProcessor>> {
+        if let Some(steps) = request_steps(inputs) {
+            return Ok(Arc::new(PostProcessor::new(steps)?));
+        }
+
+        if let Some(model) = model {
+            let file_name = std::path::Path::new(model)
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or(model)
+                .to_lowercase();
+            if let Ok(profiles) = self.profiles.read() {
+                if let Some(profile) = profiles
+                    .iter()
+                    .find(|profile| matches(&profile.pattern, &file_name))
+                {
+                    return Ok(profile.post_processor.clone());
+                }
+            }
+        }
+
+        Ok(Arc::new(PostProcessor::new(vec![])?))
+    }
+}
+
+// Steps set in the options of a request, options are otherwise read by the backend
+fn request_steps(inputs: &[(String, Tensor)]) -> Option<Vec<PostStep>> {
+    let (_, options) = inputs.iter().find(|(name, _)| name == "options")?;
+    let mut options: serde_json::Value = serde_json::from_slice(&options.data).ok()?;
+    let steps = options.get_mut("post_process")?.take();
+    match serde_json::from_value(steps) {
+        Ok(steps) => Some(steps),
+        Err(e) => {
+            log::warn!("ignoring invalid post_process options: {}", e);
+            None
+        }
+    }
+}
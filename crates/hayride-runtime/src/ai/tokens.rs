@@ -0,0 +1,86 @@
+use super::bindings::ai::types::{Message, MessageContent, Role};
+use super::bindings::mcp::types::Content;
+use hayride_host_traits::ai::BackendError;
+
+/// Tokens counted for each message on top of its content, for the role markers and
+/// separators chat formats lay messages out with.
+pub const MESSAGE_OVERHEAD: usize = 4;
+
+/// Drop the oldest messages until the rest fit in the budget, counting text with `count`.
+///
+/// Leading system messages are always kept, messages after them are kept newest first
+/// for as long as they fit, so the conversation keeps its recent turns. Fails with
+/// [`BackendError::FailedContextTooLarge`] if the system messages and the last message
+/// alone do not fit.
+pub fn fit_messages(
+    messages: Vec<Message>,
+    budget: usize,
+    mut count: impl FnMut(&str) -> Result<usize, BackendError>,
+) -> Result<Vec<Message>, BackendError> {
+    let costs = messages
+        .iter()
+        .map(|message| Ok(count(&message_text(message))? + MESSAGE_OVERHEAD))
+        .collect::<Result<Vec<usize>, BackendError>>()?;
+
+    let pinned = messages
+        .iter()
+        .take_while(|message| message.role == Role::System)
+        .count();
+    let mut used: usize = costs[..pinned].iter().sum();
+
+    // Walk back from the newest message, stopping at the first that does not fit
+    let mut first_kept = messages.len();
+    for (index, cost) in costs.iter().enumerate().skip(pinned).rev() {
+        if used + cost > budget {
+            break;
+        }
+        used += cost;
+        first_kept = index;
+    }
+    if used > budget || (first_kept == messages.len() && messages.len() > pinned) {
+        return Err(BackendError::FailedContextTooLarge);
+    }
+
+    Ok(messages
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| *index < pinned || *index >= first_kept)
+        .map(|(_, message)| message)
+        .collect())
+}
+
+// Text of a message as a model reads it, tools by their name and description
+fn message_text(message: &Message) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for content in &message.content {
+        match content {
+            MessageContent::Text(text) => parts.push(text),
+            MessageContent::Tools(tools) => {
+                for tool in tools {
+                    parts.push(&tool.name);
+                    parts.push(&tool.description);
+                }
+            }
+            MessageContent::ToolInput(params) => {
+                parts.push(&params.name);
+                for (key, value) in &params.arguments {
+                    parts.push(key);
+                    parts.push(value);
+                }
+            }
+            MessageContent::ToolOutput(result) => {
+                for content in &result.content {
+                    if let Content::Text(text) = content {
+                        parts.push(&text.text);
+                    }
+                }
+                for (key, value) in &result.structured_content {
+                    parts.push(key);
+                    parts.push(value);
+                }
+            }
+            MessageContent::None | MessageContent::Blob(_) => {}
+        }
+    }
+    parts.join("\n")
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use bytes::Bytes;
+use hayride_host_traits::ai::nn::ChunkFilter;
+use hayride_host_traits::ai::Tensor;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File the trace of a session is written to, in the directory of the session.
+pub const TRACE_FILE: &str = "trace.jsonl";
+
+// Longest input or output kept in a trace entry, longer ones are cut
+const MAX_FIELD_BYTES: usize = 1 << 20;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Trace the compute calls of sessions started from now on.
+pub fn configure(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CallKind {
+    Compute,
+    ComputeStream,
+}
+
+/// A compute call of a session, with its inputs and output as text.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub session: String,
+    pub model: Option<String>,
+    pub kind: CallKind,
+    /// Milliseconds since the unix epoch
+    pub started: u64,
+    pub finished: u64,
+    pub inputs: Vec<(String, String)>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Narrows down the calls a trace query returns, unset fields match any call.
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    pub model: Option<String>,
+    pub since: Option<u64>,
+    /// Most recent calls returned, 0 for all
+    pub limit: usize,
+}
+
+/// Appends the compute calls of a session to its trace file.
+#[derive(Clone)]
+pub struct TraceRecorder {
+    out_dir: String,
+    session: String,
+    path: PathBuf,
+}
+
+impl TraceRecorder {
+    pub fn new(out_dir: &str, session: String) -> Self {
+        let path = Path::new(out_dir).join(&session).join(TRACE_FILE);
+        Self {
+            out_dir: out_dir.to_string(),
+            session,
+            path,
+        }
+    }
+
+    /// Read the calls of a session, this one unless set.
+    pub fn query(&self, session: Option<&str>, filter: &TraceFilter) -> Result<Vec<TraceEntry>> {
+        let session = match session {
+            // Session ids are uuids, anything else could reach outside the sessions dir
+            Some(session) => uuid::Uuid::parse_str(session)?.to_string(),
+            None => self.session.clone(),
+        };
+        query(&self.out_dir, &session, filter)
+    }
+
+    /// Start tracing a call with the inputs, recorded once it finishes.
+    pub fn start(
+        &self,
+        kind: CallKind,
+        model: Option<&str>,
+        inputs: &[(String, Tensor)],
+    ) -> TraceCall {
+        TraceCall {
+            recorder: self.clone(),
+            entry: Some(TraceEntry {
+                session: self.session.clone(),
+                model: model.map(str::to_string),
+                kind,
+                started: now(),
+                finished: 0,
+                inputs: inputs
+                    .iter()
+                    .map(|(name, tensor)| (name.clone(), text(&tensor.data)))
+                    .collect(),
+                output: None,
+                error: None,
+            }),
+        }
+    }
+
+    fn write(&self, entry: &TraceEntry) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                // One write per line, so calls finishing together do not interleave
+                let mut line = serde_json::to_string(entry)?;
+                line.push('\n');
+                file.write_all(line.as_bytes())
+            });
+        if let Err(e) = result {
+            log::warn!("failed to write trace {}: {:?}", self.path.display(), e);
+        }
+    }
+}
+
+/// A traced call that has not finished, recorded as failed if dropped unfinished.
+pub struct TraceCall {
+    recorder: TraceRecorder,
+    entry: Option<TraceEntry>,
+}
+
+impl TraceCall {
+    pub fn finish(mut self, output: Result<&[u8], String>) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.finished = now();
+            match output {
+                Ok(output) => entry.output = Some(text(output)),
+                Err(error) => entry.error = Some(error),
+            }
+            self.recorder.write(&entry);
+        }
+    }
+
+    /// Trace the output of a stream as it passes through, the call finishes when the
+    /// stream closes.
+    pub fn stream(self) -> TraceStream {
+        TraceStream {
+            call: Some(self),
+            output: vec![],
+        }
+    }
+}
+
+impl Drop for TraceCall {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.finished = now();
+            entry.error = Some("call ended before it finished".to_string());
+            self.recorder.write(&entry);
+        }
+    }
+}
+
+/// Collects the output of a compute stream for its trace.
+pub struct TraceStream {
+    call: Option<TraceCall>,
+    output: Vec<u8>,
+}
+
+impl ChunkFilter for TraceStream {
+    fn push(&mut self, chunk: &[u8]) -> Bytes {
+        let room = MAX_FIELD_BYTES.saturating_sub(self.output.len());
+        self.output
+            .extend_from_slice(&chunk[..chunk.len().min(room)]);
+        Bytes::copy_from_slice(chunk)
+    }
+
+    fn finish(&mut self) -> Bytes {
+        if let Some(call) = self.call.take() {
+            call.finish(Ok(&self.output));
+        }
+        Bytes::new()
+    }
+}
+
+impl Drop for TraceStream {
+    fn drop(&mut self) {
+        // Keep what was read of a stream dropped before it closed
+        if let Some(mut call) = self.call.take() {
+            if let Some(entry) = call.entry.as_mut() {
+                entry.output = Some(text(&self.output));
+            }
+        }
+    }
+}
+
+/// Read the calls of a session from its trace file, oldest first.
+pub fn query(out_dir: &str, session: &str, filter: &TraceFilter) -> Result<Vec<TraceEntry>> {
+    let path = Path::new(out_dir).join(session).join(TRACE_FILE);
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    let mut entries: Vec<TraceEntry> = BufReader::new(std::fs::File::open(&path)?)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str::<TraceEntry>(&line).ok())
+        .filter(|entry| filter.model.is_none() || entry.model == filter.model)
+        .filter(|entry| filter.since.is_none_or(|since| entry.started >= since))
+        .collect();
+    if filter.limit > 0 && entries.len() > filter.limit {
+        entries.drain(..entries.len() - filter.limit);
+    }
+    Ok(entries)
+}
+
+// Tensor data as text, cut to the longest field kept
+fn text(data: &[u8]) -> String {
+    String::from_utf8_lossy(&data[..data.len().min(MAX_FIELD_BYTES)]).to_string()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,42 @@
+pub mod bindings;
+#[allow(clippy::module_inception)]
+pub mod blob;
+mod blob_impl;
+
+pub use blob::BlobCtx;
+pub use blob::{BlobImpl, BlobView};
+
+use hayride_host_traits::blob::BlobTrait;
+use std::sync::Arc;
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: BlobView,
+{
+    crate::blob::bindings::blob::add_to_linker::<T, HasBlob<T>>(l, |x| BlobImpl(x))?;
+
+    Ok(())
+}
+
+struct HasBlob<T>(T);
+
+impl<T: 'static> HasData for HasBlob<T> {
+    type Data<'a> = BlobImpl<&'a mut T>;
+}
+
+/// The blob store shared by every store, so the backend is reference counted.
+#[derive(Clone)]
+pub struct BlobBackend(Arc<dyn BlobTrait>);
+impl std::ops::Deref for BlobBackend {
+    type Target = dyn BlobTrait;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl From<Arc<dyn BlobTrait>> for BlobBackend {
+    fn from(value: Arc<dyn BlobTrait>) -> Self {
+        Self(value)
+    }
+}
@@ -0,0 +1,26 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-blob",
+        // Calls on the store run on blocking threads, so they do not block the runtime
+        imports: {
+            "hayride:blob/blob/put": async | trappable,
+            "hayride:blob/blob/create": async | trappable,
+            "hayride:blob/blob/get": async | trappable,
+            "hayride:blob/blob/read": async | trappable,
+            "hayride:blob/blob/stat": async | trappable,
+            "hayride:blob/blob/delete": async | trappable,
+            "hayride:blob/blob/[method]writer.finish": async | trappable,
+            default: trappable,
+        },
+        // Streams of wasi:io are linked for stores sent between threads
+        require_store_data_send: true,
+        with: {
+            "hayride:blob/blob/error": hayride_host_traits::blob::Error,
+            "hayride:blob/blob/writer": hayride_blob::BlobWriter,
+            "wasi:io": wasmtime_wasi::p2::bindings::io,
+        },
+    });
+}
+
+pub use self::generated::hayride::blob::*;
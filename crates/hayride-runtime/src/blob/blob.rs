@@ -0,0 +1,72 @@
+use wasmtime::component::ResourceTable;
+
+use super::BlobBackend;
+
+pub struct BlobCtx {
+    pub blob_backend: BlobBackend,
+}
+
+impl BlobCtx {
+    pub fn new() -> Self {
+        Self {
+            blob_backend: hayride_blob::shared().into(),
+        }
+    }
+}
+
+impl Default for BlobCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait BlobView: Send {
+    /// Returns a mutable reference to the blob context.
+    fn ctx(&mut self) -> &mut BlobCtx;
+
+    /// Returns a mutable reference to the blob resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + BlobView> BlobView for &mut T {
+    fn ctx(&mut self) -> &mut BlobCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + BlobView> BlobView for Box<T> {
+    fn ctx(&mut self) -> &mut BlobCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:blob`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_sync`](crate::blob::add_to_linker_sync)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct BlobImpl<T>(pub T);
+
+impl<T: BlobView> BlobView for BlobImpl<T> {
+    fn ctx(&mut self) -> &mut BlobCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::blob::bindings::{blob, types::BlobInfo, types::ErrorCode};
+use crate::blob::{BlobBackend, BlobImpl, BlobView};
+use hayride_blob::BlobWriter;
+use hayride_host_traits::blob::Error;
+
+use wasmtime::component::Resource;
+use wasmtime::Result;
+use wasmtime_wasi::p2::DynInputStream;
+
+use anyhow::anyhow;
+use std::io::Read;
+use std::time::UNIX_EPOCH;
+
+impl<T> BlobImpl<T>
+where
+    T: BlobView,
+{
+    // Run an operation on the store on a blocking thread, moving the error into the table
+    async fn run<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&BlobBackend) -> Result<R, hayride_host_traits::blob::ErrorCode> + Send + 'static,
+        context: impl FnOnce() -> String,
+    ) -> Result<Result<R, Resource<blob::Error>>> {
+        let backend = self.ctx().blob_backend.clone();
+        let result = tokio::task::spawn_blocking(move || f(&backend)).await?;
+        self.error(result, context)
+    }
+
+    // Move the error of an operation into the table
+    fn error<R>(
+        &mut self,
+        result: Result<R, hayride_host_traits::blob::ErrorCode>,
+        context: impl FnOnce() -> String,
+    ) -> Result<Result<R, Resource<blob::Error>>> {
+        match result {
+            Ok(value) => Ok(Ok(value)),
+            Err(code) => {
+                let error = Error {
+                    code,
+                    data: anyhow!(context()),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> blob::Host for BlobImpl<T>
+where
+    T: BlobView,
+{
+    async fn put(&mut self, data: Vec<u8>) -> Result<Result<BlobInfo, Resource<blob::Error>>> {
+        self.run(
+            move |backend| {
+                let mut writer = BlobWriter::create(&backend.staging_dir())
+                    .map_err(|_| hayride_host_traits::blob::ErrorCode::WriteFailed)?;
+                writer.write(&data)?;
+                writer.finish(&**backend).map(blob_info)
+            },
+            || "Error putting blob".to_string(),
+        )
+        .await
+    }
+
+    async fn create(&mut self) -> Result<Result<Resource<BlobWriter>, Resource<blob::Error>>> {
+        let writer = self
+            .run(
+                |backend| {
+                    BlobWriter::create(&backend.staging_dir())
+                        .map_err(|_| hayride_host_traits::blob::ErrorCode::WriteFailed)
+                },
+                || "Error creating blob writer".to_string(),
+            )
+            .await?;
+        match writer {
+            Ok(writer) => Ok(Ok(self.table().push(writer)?)),
+            Err(error) => Ok(Err(error)),
+        }
+    }
+
+    async fn get(&mut self, digest: String) -> Result<Result<Vec<u8>, Resource<blob::Error>>> {
+        let context = format!("Error getting blob: {}", digest);
+        self.run(
+            move |backend| {
+                let mut data = vec![];
+                backend
+                    .open(&digest)?
+                    .read_to_end(&mut data)
+                    .map_err(|_| hayride_host_traits::blob::ErrorCode::ReadFailed)?;
+                Ok(data)
+            },
+            || context,
+        )
+        .await
+    }
+
+    async fn read(
+        &mut self,
+        digest: String,
+    ) -> Result<Result<Resource<DynInputStream>, Resource<blob::Error>>> {
+        let context = format!("Error reading blob: {}", digest);
+        let opened = digest.clone();
+        let reader = self
+            .run(move |backend| backend.open(&opened), || context)
+            .await?;
+        match reader {
+            Ok(reader) => {
+                let stream = crate::blocking_input_stream(reader, format!("blob {}", digest));
+                Ok(Ok(self.table().push(stream)?))
+            }
+            Err(error) => Ok(Err(error)),
+        }
+    }
+
+    async fn stat(&mut self, digest: String) -> Result<Result<BlobInfo, Resource<blob::Error>>> {
+        let context = format!("Error getting blob: {}", digest);
+        self.run(
+            move |backend| backend.stat(&digest).map(blob_info),
+            || context,
+        )
+        .await
+    }
+
+    async fn delete(&mut self, digest: String) -> Result<Result<bool, Resource<blob::Error>>> {
+        let context = format!("Error deleting blob: {}", digest);
+        self.run(move |backend| backend.delete(&digest), || context)
+            .await
+    }
+}
+
+impl<T> blob::HostWriter for BlobImpl<T>
+where
+    T: BlobView,
+{
+    fn write(
+        &mut self,
+        writer: Resource<BlobWriter>,
+        data: Vec<u8>,
+    ) -> Result<Result<(), Resource<blob::Error>>> {
+        let result = self.table().get_mut(&writer)?.write(&data);
+        self.error(result, || "Error writing blob".to_string())
+    }
+
+    async fn finish(
+        &mut self,
+        writer: Resource<BlobWriter>,
+    ) -> Result<Result<BlobInfo, Resource<blob::Error>>> {
+        let mut writer = self.table().get_mut(&writer)?.take();
+        self.run(
+            move |backend| writer.finish(&**backend).map(blob_info),
+            || "Error finishing blob".to_string(),
+        )
+        .await
+    }
+
+    fn drop(&mut self, writer: Resource<BlobWriter>) -> Result<()> {
+        self.table().delete(writer)?;
+        Ok(())
+    }
+}
+
+impl<T> blob::HostError for BlobImpl<T>
+where
+    T: BlobView,
+{
+    fn code(&mut self, error: Resource<Error>) -> Result<ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            hayride_host_traits::blob::ErrorCode::NotFound => Ok(ErrorCode::NotFound),
+            hayride_host_traits::blob::ErrorCode::InvalidDigest => Ok(ErrorCode::InvalidDigest),
+            hayride_host_traits::blob::ErrorCode::WriteFailed => Ok(ErrorCode::WriteFailed),
+            hayride_host_traits::blob::ErrorCode::ReadFailed => Ok(ErrorCode::ReadFailed),
+            hayride_host_traits::blob::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+fn blob_info(info: hayride_host_traits::blob::BlobInfo) -> BlobInfo {
+    BlobInfo {
+        digest: info.digest,
+        size: info.size,
+        created: info
+            .created
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+    }
+}
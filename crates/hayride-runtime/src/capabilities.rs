@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+
+// Path the capability admin api is served on by host servers
+pub const ADMIN_PATH: &str = "/_hayride/admin/capabilities";
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Returns the process wide capability policy.
+pub fn capabilities() -> &'static Capabilities {
+    CAPABILITIES.get_or_init(Capabilities::default)
+}
+
+/// Host operations that can be switched off while the runtime is running.
+///
+/// Stores take a snapshot of the policy when they are created, so changes apply to new
+/// stores while existing ones finish with the policy they started with. Capabilities that
+/// were not linked when the engine was built cannot be enabled here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CapabilityPolicy {
+    /// Running inference with `wasi:nn` and `hayride:ai/inference-stream`
+    pub ai_compute: bool,
+    /// Spawning threads and processes with `hayride:silo`
+    pub silo_spawn: bool,
+    /// Executing statements with `hayride:db`
+    pub db_write: bool,
+    /// Outgoing requests with `wasi:http`
+    pub http_egress: bool,
+}
+
+impl Default for CapabilityPolicy {
+    fn default() -> Self {
+        Self {
+            ai_compute: true,
+            silo_spawn: true,
+            db_write: true,
+            http_egress: true,
+        }
+    }
+}
+
+impl CapabilityPolicy {
+    /// Apply the flags set in a json object, leaving the others unchanged.
+    pub fn merge_json(&mut self, json: &serde_json::Value) -> anyhow::Result<()> {
+        // Validate every flag before changing any
+        let update = PolicyUpdate::deserialize(json)
+            .map_err(|e| anyhow::anyhow!("invalid capability flags: {}", e))?;
+        let flags = [
+            (&mut self.ai_compute, update.ai_compute),
+            (&mut self.silo_spawn, update.silo_spawn),
+            (&mut self.db_write, update.db_write),
+            (&mut self.http_egress, update.http_egress),
+        ];
+        for (flag, enabled) in flags {
+            if let Some(enabled) = enabled {
+                *flag = enabled;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Flags of a policy update, unset ones are left unchanged
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PolicyUpdate {
+    ai_compute: Option<bool>,
+    silo_spawn: Option<bool>,
+    db_write: Option<bool>,
+    http_egress: Option<bool>,
+}
+
+/// The capability policy shared by every engine in the process.
+#[derive(Default)]
+pub struct Capabilities {
+    policy: RwLock<CapabilityPolicy>,
+}
+
+impl Capabilities {
+    /// Returns a snapshot of the current policy.
+    pub fn policy(&self) -> CapabilityPolicy {
+        self.policy.read().map(|policy| *policy).unwrap_or_default()
+    }
+
+    pub fn set_policy(&self, policy: CapabilityPolicy) {
+        if let Ok(mut current) = self.policy.write() {
+            log::info!("capability policy updated: {:?}", policy);
+            *current = policy;
+        }
+    }
+
+    /// Update the policy from a json object of flags, returning the new policy.
+    pub fn update(&self, json: &serde_json::Value) -> anyhow::Result<CapabilityPolicy> {
+        let mut policy = self
+            .policy
+            .write()
+            .map_err(|_| anyhow::anyhow!("capability policy lock poisoned"))?;
+        policy.merge_json(json)?;
+        log::info!("capability policy updated: {:?}", *policy);
+        Ok(*policy)
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use ring::digest::{digest, SHA256};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wasmtime::component::Component;
+
+// Extension of precompiled components in the cache dir
+const CWASM_EXTENSION: &str = "cwasm";
+
+// Most components kept compiled in memory for an engine
+const MAX_COMPILED: usize = 64;
+
+/// A wasmtime engine with the components it compiled, shared by the engines of spawned
+/// threads so morphs spawned again skip compiling and loading their component.
+///
+/// Components are keyed by the sha256 of their bytes, so a morph that changed on disk is
+/// compiled again.
+#[derive(Clone)]
+pub struct CompiledComponents {
+    engine: wasmtime::Engine,
+    components: Arc<dashmap::DashMap<String, Component>>,
+}
+
+impl CompiledComponents {
+    pub fn new(engine: wasmtime::Engine) -> Self {
+        Self {
+            engine,
+            components: Arc::new(dashmap::DashMap::new()),
+        }
+    }
+
+    pub fn engine(&self) -> &wasmtime::Engine {
+        &self.engine
+    }
+
+    /// The component compiled before from the bytes, or the component loaded with
+    /// [`load_component`] the first time.
+    pub fn load(&self, bytes: &[u8], cache_dir: Option<&Path>) -> Result<Component> {
+        let key = content_hash(bytes);
+        if let Some(component) = self.components.get(&key) {
+            log::debug!("reusing compiled component {}", key);
+            return Ok(component.clone());
+        }
+
+        let component = load_component(&self.engine, bytes, cache_dir)?;
+        if self.components.len() >= MAX_COMPILED {
+            // Make room, the evicted component loads from the cache dir next time
+            let evicted = self.components.iter().next().map(|e| e.key().clone());
+            if let Some(evicted) = evicted {
+                self.components.remove(&evicted);
+            }
+        }
+        self.components.insert(key, component.clone());
+        Ok(component)
+    }
+}
+
+/// Returns the default component cache dir, `~/.hayride/cache/components`.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    hayride_utils::paths::hayride::default_hayride_dir()
+        .ok()
+        .map(|dir| dir.join("cache").join("components"))
+}
+
+/// Compile a component, reusing a precompiled artifact from the cache dir if there is one.
+///
+/// Artifacts are keyed by the sha256 of the component and the engine's compatibility
+/// hash, which covers the wasmtime version and compilation settings, so they are never
+/// loaded into an engine they were not compiled for. Failing to read or write the cache
+/// falls back to compiling the component.
+pub fn load_component(
+    engine: &wasmtime::Engine,
+    bytes: &[u8],
+    cache_dir: Option<&Path>,
+) -> Result<Component> {
+    let Some(cache_dir) = cache_dir else {
+        return Component::from_binary(engine, bytes);
+    };
+    let path = cache_dir.join(cache_key(engine, bytes));
+
+    if path.exists() {
+        // Safety: artifacts are only written by `Component::serialize` below, into the
+        // hayride dir, for an engine with the same compatibility hash
+        match unsafe { Component::deserialize_file(engine, &path) } {
+            Ok(component) => {
+                log::debug!("loaded precompiled component {}", path.display());
+                return Ok(component);
+            }
+            Err(e) => log::warn!("ignoring precompiled component {}: {:?}", path.display(), e),
+        }
+    }
+
+    let component = Component::from_binary(engine, bytes)?;
+    if let Err(e) = store(&component, cache_dir, &path) {
+        log::warn!("failed to cache component {}: {:?}", path.display(), e);
+    }
+    Ok(component)
+}
+
+fn cache_key(engine: &wasmtime::Engine, bytes: &[u8]) -> String {
+    let content = content_hash(bytes);
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+
+    format!("{}-{:016x}.{}", content, hasher.finish(), CWASM_EXTENSION)
+}
+
+/// Sha256 of the bytes of a component, as hex.
+pub fn content_hash(bytes: &[u8]) -> String {
+    digest(&SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn store(component: &Component, cache_dir: &Path, path: &Path) -> Result<()> {
+    std::fs::create_dir_all(cache_dir)?;
+    let serialized = component.serialize()?;
+
+    // Write to a temporary file first so concurrent loads never see a partial artifact
+    let partial = path.with_extension(format!("{}.{}", CWASM_EXTENSION, uuid::Uuid::new_v4()));
+    std::fs::write(&partial, serialized)?;
+    if let Err(e) = std::fs::rename(&partial, path) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e.into());
+    }
+    Ok(())
+}
@@ -1,19 +1,36 @@
 pub mod bindings;
+pub mod build;
 pub mod core;
 mod core_impl;
+pub mod logging;
+pub mod registry;
+pub mod settings;
 
 pub use core::CoreCtx;
 pub use core::{CoreImpl, CoreView};
 
+use hayride_host_traits::core::config::ConfigInner;
+use hayride_host_traits::core::registry::RegistryInner;
+use hayride_host_traits::core::sessions::SessionsInner;
+use hayride_host_traits::core::system::{Device, SystemInner};
 use hayride_host_traits::core::version::VersionInner;
+use std::sync::{Arc, OnceLock};
 
 use wasmtime::component::HasData;
 
-pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
 where
     T: CoreView,
 {
     crate::core::bindings::version::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::config::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::registry::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::pubsub::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::timers::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::sessions::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::logging::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::ratelimit::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
+    crate::core::bindings::system::add_to_linker::<T, HasCore<T>>(l, |x| CoreImpl(x))?;
 
     Ok(())
 }
@@ -24,20 +41,108 @@ impl<T: 'static> HasData for HasCore<T> {
     type Data<'a> = CoreImpl<&'a mut T>;
 }
 
-pub struct VersionBackend(Box<dyn VersionInner>);
+#[derive(Clone)]
+pub struct VersionBackend(Arc<dyn VersionInner>);
 impl std::ops::Deref for VersionBackend {
     type Target = dyn VersionInner;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
 }
-impl std::ops::DerefMut for VersionBackend {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.as_mut()
+impl<T: VersionInner + 'static> From<T> for VersionBackend {
+    fn from(value: T) -> Self {
+        Self(Arc::new(value))
     }
 }
-impl<T: VersionInner + 'static> From<T> for VersionBackend {
+
+/// Settings shared by every store, so the backend is reference counted.
+#[derive(Clone)]
+pub struct ConfigBackend(Arc<dyn ConfigInner>);
+impl std::ops::Deref for ConfigBackend {
+    type Target = dyn ConfigInner;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl<T: ConfigInner + 'static> From<T> for ConfigBackend {
     fn from(value: T) -> Self {
-        Self(Box::new(value))
+        Self(Arc::new(value))
     }
 }
+
+/// The remote registry shared by every store.
+#[derive(Clone)]
+pub struct RegistryBackend(Arc<dyn RegistryInner>);
+impl std::ops::Deref for RegistryBackend {
+    type Target = dyn RegistryInner;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl<T: RegistryInner + 'static> From<T> for RegistryBackend {
+    fn from(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+/// Chat sessions shared by every store.
+#[derive(Clone)]
+pub struct SessionsBackend(Arc<dyn SessionsInner>);
+impl std::ops::Deref for SessionsBackend {
+    type Target = dyn SessionsInner;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl<T: SessionsInner + 'static> From<Arc<T>> for SessionsBackend {
+    fn from(value: Arc<T>) -> Self {
+        Self(value)
+    }
+}
+
+/// Hardware of the host shared by every store.
+#[derive(Clone)]
+pub struct SystemBackend(Arc<dyn SystemInner>);
+impl SystemBackend {
+    /// The hardware of the host, with the devices of llama.cpp when the runtime is built with it.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<SystemBackend> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let gpu_backends = [
+                    ("cuda", cfg!(feature = "cuda")),
+                    ("metal", cfg!(feature = "metal")),
+                ]
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(backend, _)| backend.to_string())
+                .collect();
+                hayride_core::system::SystemInfo::new()
+                    .with_gpu_backends(gpu_backends)
+                    .with_devices(devices)
+                    .into()
+            })
+            .clone()
+    }
+}
+impl std::ops::Deref for SystemBackend {
+    type Target = dyn SystemInner;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl<T: SystemInner + 'static> From<T> for SystemBackend {
+    fn from(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+#[cfg(feature = "llamacpp")]
+fn devices() -> Vec<Device> {
+    hayride_llama::devices()
+}
+
+#[cfg(not(feature = "llamacpp"))]
+fn devices() -> Vec<Device> {
+    Vec::new()
+}
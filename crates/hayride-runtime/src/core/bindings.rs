@@ -3,10 +3,29 @@ pub mod generated {
         path: "../../wit",
         world: "hayride-core",
         imports: {
+            // Waiting for a message is async so it does not block the runtime
+            "hayride:core/pubsub/[method]subscription.next": async | trappable,
+            // Waiting for a token is async so it does not block the runtime
+            "hayride:core/ratelimit/acquire": async | trappable,
+            // Checking releases and pulling or pushing morphs is async so it does not block
+            // the runtime
+            "hayride:core/version/latest": async | trappable,
+            "hayride:core/version/latest-release": async | trappable,
+            "hayride:core/version/check-update": async | trappable,
+            "hayride:core/registry/pull": async | trappable,
+            "hayride:core/registry/push": async | trappable,
             default: trappable,
         },
         with: {
             "hayride:core/version/error": hayride_host_traits::core::version::Error,
+            "hayride:core/config/error": hayride_host_traits::core::config::Error,
+            "hayride:core/registry/error": hayride_host_traits::core::registry::Error,
+            "hayride:core/pubsub/error": hayride_host_traits::core::pubsub::Error,
+            "hayride:core/pubsub/subscription": crate::events::Subscription,
+            "hayride:core/timers/error": hayride_host_traits::core::timers::Error,
+            "hayride:core/sessions/error": hayride_host_traits::core::sessions::Error,
+            "hayride:core/ratelimit/error": hayride_host_traits::core::ratelimit::Error,
+            "wasi:io": wasmtime_wasi::p2::bindings::io,
         },
     });
 }
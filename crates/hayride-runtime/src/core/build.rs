@@ -0,0 +1,28 @@
+/// Crate version of the runtime.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short git commit the runtime was built from.
+pub const COMMIT: &str = env!("HAYRIDE_GIT_COMMIT");
+/// RFC 3339 build date.
+pub const DATE: &str = env!("HAYRIDE_BUILD_DATE");
+/// Version of the embedded wasmtime.
+pub const WASMTIME_VERSION: &str = env!("HAYRIDE_WASMTIME_VERSION");
+
+/// Returns the cargo features the runtime was built with.
+pub fn features() -> Vec<&'static str> {
+    let features = [
+        ("lancedb", cfg!(feature = "lancedb")),
+        ("llamacpp", cfg!(feature = "llamacpp")),
+        ("hf", cfg!(feature = "hf")),
+        ("whisper", cfg!(feature = "whisper")),
+        ("postgres", cfg!(feature = "postgres")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("cuda", cfg!(feature = "cuda")),
+        ("metal", cfg!(feature = "metal")),
+    ];
+
+    features
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name)
+        .collect()
+}
@@ -1,51 +1,79 @@
 use wasmtime::component::ResourceTable;
 
-use super::VersionBackend;
-#[derive(Clone, Debug, Default)]
-pub struct VersionCache {
-    /// epoch seconds
-    pub last_check: Option<u64>,
-    /// Last version string returned
-    pub last_version: Option<String>,
-}
-use std::sync::{Arc, Mutex};
+use super::settings::Settings;
+use super::{ConfigBackend, RegistryBackend, SessionsBackend, SystemBackend, VersionBackend};
+use log::LevelFilter;
 
 pub struct CoreCtx {
     pub version_backend: VersionBackend,
-    /// Cache for version info
-    pub version_cache: Arc<Mutex<VersionCache>>,
+    /// Host settings readable through hayride:core/config
+    pub config_backend: ConfigBackend,
+    /// The `package:name` of the morph, used for its setting overrides
+    pub morph: String,
+    /// Remote registry morphs are pulled from and pushed to, if configured
+    pub registry_backend: Option<RegistryBackend>,
+    /// Chat sessions shared between the UI and agents
+    pub sessions_backend: SessionsBackend,
+    /// Level of the records the morph logs through hayride:core/logging
+    pub log_level: LevelFilter,
+    /// Hardware of the host
+    pub system_backend: SystemBackend,
 }
 
 impl CoreCtx {
     pub fn new() -> Self {
-        let version_backend: Box<hayride_core::VersionBackend> =
-            Box::new(hayride_core::VersionBackend::default());
         Self {
-            version_backend: VersionBackend(version_backend),
-            version_cache: Arc::new(Mutex::new(VersionCache::default())),
+            version_backend: hayride_core::VersionBackend::default().into(),
+            config_backend: Settings::default().into(),
+            morph: String::new(),
+            registry_backend: None,
+            sessions_backend: hayride_core::sessions::SessionStore::shared().into(),
+            log_level: LevelFilter::Info,
+            system_backend: SystemBackend::shared(),
         }
     }
 
-    /// Get a clone of the version cache struct
-    pub fn get_version_cache(&self) -> VersionCache {
-        self.version_cache.lock().unwrap().clone()
+    pub fn with_config(mut self, config_backend: ConfigBackend) -> Self {
+        self.config_backend = config_backend;
+        self
+    }
+
+    pub fn with_morph(mut self, morph: String) -> Self {
+        self.morph = morph;
+        self
+    }
+
+    pub fn with_registry(mut self, registry_backend: Option<RegistryBackend>) -> Self {
+        self.registry_backend = registry_backend;
+        self
+    }
+
+    pub fn with_sessions(mut self, sessions_backend: SessionsBackend) -> Self {
+        self.sessions_backend = sessions_backend;
+        self
+    }
+
+    pub fn with_log_level(mut self, log_level: LevelFilter) -> Self {
+        self.log_level = log_level;
+        self
     }
 
-    /// Set the version cache values
-    pub fn set_version_cache(&self, last_check: Option<u64>, last_version: Option<String>) {
-        let mut cache = self.version_cache.lock().unwrap();
-        cache.last_check = last_check;
-        cache.last_version = last_version;
+    pub fn with_system(mut self, system_backend: SystemBackend) -> Self {
+        self.system_backend = system_backend;
+        self
     }
 }
 
 impl Clone for CoreCtx {
     fn clone(&self) -> Self {
-        let version_backend: Box<hayride_core::VersionBackend> =
-            Box::new(hayride_core::VersionBackend::default());
         Self {
-            version_backend: VersionBackend(version_backend),
-            version_cache: Arc::clone(&self.version_cache),
+            version_backend: hayride_core::VersionBackend::default().into(),
+            config_backend: self.config_backend.clone(),
+            morph: self.morph.clone(),
+            registry_backend: self.registry_backend.clone(),
+            sessions_backend: self.sessions_backend.clone(),
+            log_level: self.log_level,
+            system_backend: self.system_backend.clone(),
         }
     }
 }
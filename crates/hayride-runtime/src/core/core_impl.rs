@@ -1,45 +1,38 @@
-use crate::core::bindings::{version, version::ErrorCode};
+use crate::core::bindings::{
+    config, logging, pubsub, ratelimit, registry, sessions, system, timers, version,
+    version::ErrorCode,
+};
+use crate::core::build;
+use crate::core::logging::{Fields, LOG_TARGET};
 use crate::core::{CoreImpl, CoreView};
-use hayride_host_traits::core::version::Error;
+use crate::events::{self, Subscription};
+use crate::ratelimit::RateLimitError;
+use crate::timers::TimerError;
+use hayride_host_traits::core::config::ConfigValue;
+use hayride_host_traits::core::pubsub::ErrorCode as PubsubErrorCode;
+use hayride_host_traits::core::ratelimit::ErrorCode as RateLimitErrorCode;
+use hayride_host_traits::core::registry::ErrorCode as RegistryErrorCode;
+use hayride_host_traits::core::sessions::ErrorCode as SessionsErrorCode;
+use hayride_host_traits::core::timers::ErrorCode as TimersErrorCode;
+use hayride_host_traits::core::version::{Error, ReleaseInfo};
 
 use wasmtime::component::Resource;
 use wasmtime::Result;
+use wasmtime_wasi::p2::DynPollable;
 
 use anyhow::anyhow;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 
-impl<T> version::Host for CoreImpl<T>
+impl<T> CoreImpl<T>
 where
     T: CoreView,
 {
-    fn latest(&mut self) -> Result<Result<String, Resource<version::Error>>> {
-        let ctx = self.ctx();
-        let cache = ctx.get_version_cache();
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_err(|e| anyhow!("Error getting current time: {}", e))?
-            .as_secs();
-
-        // Only check the version if it's been more than an hour since the last check
-        let should_check = match cache.last_check {
-            Some(last) => now > last + 3600,
-            None => true,
-        };
-
-        if !should_check {
-            // If we have a cached version and it's still valid, return it
-            if let Some(version) = cache.last_version {
-                return Ok(Ok(version));
-            }
-        }
-
-        let result = ctx.version_backend.latest();
-        match result {
-            Ok(version) => {
-                // Store the new version in the cache
-                ctx.set_version_cache(Some(now), Some(version.clone()));
-                Ok(Ok(version))
-            }
+    // Return the latest release, the backend caches it between checks
+    async fn release(&mut self) -> Result<Result<ReleaseInfo, Resource<version::Error>>> {
+        let backend = self.ctx().version_backend.clone();
+        match backend.release().await {
+            Ok(release) => Ok(Ok(release)),
             Err(e) => {
                 let error = Error {
                     code: e,
@@ -52,6 +45,51 @@ where
     }
 }
 
+impl<T> version::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    async fn latest(&mut self) -> Result<Result<String, Resource<version::Error>>> {
+        Ok(self.release().await?.map(|release| release.version))
+    }
+
+    async fn latest_release(
+        &mut self,
+    ) -> Result<Result<version::ReleaseInfo, Resource<version::Error>>> {
+        Ok(self.release().await?.map(|release| version::ReleaseInfo {
+            version: release.version,
+            date: release.date,
+            highlights: release.highlights,
+            download_url: release.download_url,
+            notes: release.notes,
+        }))
+    }
+
+    fn build_info(&mut self) -> Result<version::Build> {
+        Ok(version::Build {
+            version: build::VERSION.to_string(),
+            commit: build::COMMIT.to_string(),
+            date: build::DATE.to_string(),
+            features: build::features()
+                .into_iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            wasmtime_version: build::WASMTIME_VERSION.to_string(),
+        })
+    }
+
+    async fn check_update(
+        &mut self,
+    ) -> Result<Result<version::UpdateCheck, Resource<version::Error>>> {
+        Ok(self.release().await?.map(|release| version::UpdateCheck {
+            current: build::VERSION.to_string(),
+            update_available: hayride_core::update::is_newer(&release.version, build::VERSION),
+            latest: release.version,
+            download_url: release.download_url,
+        }))
+    }
+}
+
 impl<T> version::HostError for CoreImpl<T>
 where
     T: CoreView,
@@ -76,3 +114,747 @@ where
         return Ok(());
     }
 }
+
+impl<T> config::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn get(&mut self, key: String) -> Result<Result<config::Value, Resource<config::Error>>> {
+        let ctx = self.ctx();
+        match ctx.config_backend.get(&ctx.morph, &key) {
+            Ok(value) => Ok(Ok(match value {
+                ConfigValue::Boolean(b) => config::Value::Boolean(b),
+                ConfigValue::Integer(i) => config::Value::Integer(i),
+                ConfigValue::Float(f) => config::Value::Float(f),
+                ConfigValue::Text(s) => config::Value::Text(s),
+                ConfigValue::TextList(list) => config::Value::TextList(list),
+            })),
+            Err(code) => {
+                let error = hayride_host_traits::core::config::Error {
+                    code,
+                    data: anyhow!("Setting not found: {}", key),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    fn keys(&mut self, prefix: String) -> Result<Result<Vec<String>, Resource<config::Error>>> {
+        let ctx = self.ctx();
+        match ctx.config_backend.keys(&ctx.morph, &prefix) {
+            Ok(keys) => Ok(Ok(keys)),
+            Err(code) => {
+                let error = hayride_host_traits::core::config::Error {
+                    code,
+                    data: anyhow!("Error listing settings"),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> config::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::config::Error>,
+    ) -> Result<config::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            hayride_host_traits::core::config::ErrorCode::NotFound => {
+                Ok(config::ErrorCode::NotFound)
+            }
+            hayride_host_traits::core::config::ErrorCode::Unknown => Ok(config::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::config::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        return Ok(error.data.to_string());
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::config::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        return Ok(());
+    }
+}
+
+impl<T> registry::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    async fn pull(
+        &mut self,
+        reference: String,
+    ) -> Result<Result<String, Resource<registry::Error>>> {
+        let result = match self.ctx().registry_backend.clone() {
+            Some(registry) => {
+                let reference = reference.clone();
+                tokio::task::spawn_blocking(move || registry.pull(&reference)).await?
+            }
+            None => Err(RegistryErrorCode::NotConfigured),
+        };
+        match result {
+            Ok(reference) => Ok(Ok(reference)),
+            Err(code) => {
+                let error = hayride_host_traits::core::registry::Error {
+                    code,
+                    data: anyhow!("Error pulling {}", reference),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    async fn push(&mut self, reference: String) -> Result<Result<(), Resource<registry::Error>>> {
+        let result = match self.ctx().registry_backend.clone() {
+            Some(registry) => {
+                let reference = reference.clone();
+                tokio::task::spawn_blocking(move || registry.push(&reference)).await?
+            }
+            None => Err(RegistryErrorCode::NotConfigured),
+        };
+        match result {
+            Ok(()) => Ok(Ok(())),
+            Err(code) => {
+                let error = hayride_host_traits::core::registry::Error {
+                    code,
+                    data: anyhow!("Error pushing {}", reference),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> registry::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::registry::Error>,
+    ) -> Result<registry::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            RegistryErrorCode::NotConfigured => Ok(registry::ErrorCode::NotConfigured),
+            RegistryErrorCode::InvalidReference => Ok(registry::ErrorCode::InvalidReference),
+            RegistryErrorCode::NotFound => Ok(registry::ErrorCode::NotFound),
+            RegistryErrorCode::InvalidSignature => Ok(registry::ErrorCode::InvalidSignature),
+            RegistryErrorCode::PullFailed => Ok(registry::ErrorCode::PullFailed),
+            RegistryErrorCode::PushFailed => Ok(registry::ErrorCode::PushFailed),
+            RegistryErrorCode::Unknown => Ok(registry::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::registry::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        return Ok(error.data.to_string());
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::registry::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        return Ok(());
+    }
+}
+
+impl<T> CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn pubsub_error(
+        &mut self,
+        code: PubsubErrorCode,
+        data: anyhow::Error,
+    ) -> Result<Resource<pubsub::Error>> {
+        let error = hayride_host_traits::core::pubsub::Error { code, data };
+        Ok(self.table().push(error)?)
+    }
+
+    // Map a failed read of a subscription to its error
+    fn recv_error(&mut self, topic: &str, error: RecvError) -> Result<Resource<pubsub::Error>> {
+        match error {
+            RecvError::Lagged(missed) => self.pubsub_error(
+                PubsubErrorCode::Lagged,
+                anyhow!("Missed {} messages on {}", missed, topic),
+            ),
+            RecvError::Closed => {
+                self.pubsub_error(PubsubErrorCode::Closed, anyhow!("{} was closed", topic))
+            }
+        }
+    }
+}
+
+impl<T> pubsub::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn publish(
+        &mut self,
+        topic: String,
+        data: Vec<u8>,
+    ) -> Result<Result<u32, Resource<pubsub::Error>>> {
+        if !events::publishable_topic(&topic) {
+            let error = self.pubsub_error(
+                PubsubErrorCode::InvalidTopic,
+                anyhow!("Can not publish on topic: {}", topic),
+            )?;
+            return Ok(Err(error));
+        }
+
+        match events::bus().try_publish(&topic, data) {
+            Ok(sent) => Ok(Ok(sent as u32)),
+            Err(_) => {
+                let error = self.pubsub_error(
+                    PubsubErrorCode::Full,
+                    anyhow!("A subscriber of {} is too far behind", topic),
+                )?;
+                Ok(Err(error))
+            }
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        topic: String,
+    ) -> Result<Result<Resource<Subscription>, Resource<pubsub::Error>>> {
+        if !events::valid_topic(&topic) {
+            let error = self.pubsub_error(
+                PubsubErrorCode::InvalidTopic,
+                anyhow!("Invalid topic: {}", topic),
+            )?;
+            return Ok(Err(error));
+        }
+
+        let id = self.table().push(Subscription::new(&topic))?;
+        Ok(Ok(id))
+    }
+}
+
+impl<T> pubsub::HostSubscription for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn topic(&mut self, subscription: Resource<Subscription>) -> Result<String> {
+        let subscription = self.table().get(&subscription)?;
+        Ok(subscription.topic().to_string())
+    }
+
+    async fn next(
+        &mut self,
+        subscription: Resource<Subscription>,
+    ) -> Result<Result<Vec<u8>, Resource<pubsub::Error>>> {
+        let subscription = self.table().get_mut(&subscription)?;
+        let topic = subscription.topic().to_string();
+        match subscription.recv().await {
+            Ok(event) => Ok(Ok(event.data)),
+            Err(e) => Ok(Err(self.recv_error(&topic, e)?)),
+        }
+    }
+
+    fn try_next(
+        &mut self,
+        subscription: Resource<Subscription>,
+    ) -> Result<Result<Option<Vec<u8>>, Resource<pubsub::Error>>> {
+        let subscription = self.table().get_mut(&subscription)?;
+        let topic = subscription.topic().to_string();
+        match subscription.try_recv() {
+            Ok(event) => Ok(Ok(event.map(|event| event.data))),
+            Err(e) => Ok(Err(self.recv_error(&topic, e)?)),
+        }
+    }
+
+    fn subscribe(&mut self, subscription: Resource<Subscription>) -> Result<Resource<DynPollable>> {
+        wasmtime_wasi::p2::subscribe(self.table(), subscription)
+    }
+
+    fn drop(&mut self, subscription: Resource<Subscription>) -> Result<()> {
+        self.table().delete(subscription)?;
+        Ok(())
+    }
+}
+
+impl<T> pubsub::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::pubsub::Error>,
+    ) -> Result<pubsub::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            PubsubErrorCode::InvalidTopic => Ok(pubsub::ErrorCode::InvalidTopic),
+            PubsubErrorCode::Full => Ok(pubsub::ErrorCode::Full),
+            PubsubErrorCode::Lagged => Ok(pubsub::ErrorCode::Lagged),
+            PubsubErrorCode::Closed => Ok(pubsub::ErrorCode::Closed),
+            PubsubErrorCode::Unknown => Ok(pubsub::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::pubsub::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::pubsub::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+impl<T> CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn timers_error(
+        &mut self,
+        code: TimersErrorCode,
+        data: anyhow::Error,
+    ) -> Result<Resource<timers::Error>> {
+        let error = hayride_host_traits::core::timers::Error { code, data };
+        Ok(self.table().push(error)?)
+    }
+
+    // Schedule a timer of the morph, publishing on the topic when it fires
+    fn schedule(
+        &mut self,
+        delay: Duration,
+        interval: Option<Duration>,
+        topic: String,
+        data: Vec<u8>,
+    ) -> Result<Result<u64, Resource<timers::Error>>> {
+        if !events::publishable_topic(&topic) {
+            let error = self.timers_error(
+                TimersErrorCode::InvalidTopic,
+                anyhow!("Can not publish on topic: {}", topic),
+            )?;
+            return Ok(Err(error));
+        }
+
+        let morph = self.ctx().morph.clone();
+        let error = match crate::timers::timers().schedule(&morph, delay, interval, topic, data) {
+            Ok(id) => return Ok(Ok(id)),
+            Err(TimerError::InvalidInterval) => self.timers_error(
+                TimersErrorCode::InvalidInterval,
+                anyhow!(
+                    "Interval is shorter than {}ms",
+                    crate::timers::MIN_INTERVAL.as_millis()
+                ),
+            )?,
+            Err(TimerError::TooManyTimers) => self.timers_error(
+                TimersErrorCode::TooManyTimers,
+                anyhow!("Too many timers scheduled by {}", morph),
+            )?,
+        };
+        Ok(Err(error))
+    }
+}
+
+impl<T> timers::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn schedule_once(
+        &mut self,
+        delay: u64,
+        topic: String,
+        data: Vec<u8>,
+    ) -> Result<Result<u64, Resource<timers::Error>>> {
+        self.schedule(Duration::from_millis(delay), None, topic, data)
+    }
+
+    fn schedule_repeating(
+        &mut self,
+        interval: u64,
+        topic: String,
+        data: Vec<u8>,
+    ) -> Result<Result<u64, Resource<timers::Error>>> {
+        let interval = Duration::from_millis(interval);
+        self.schedule(interval, Some(interval), topic, data)
+    }
+
+    fn cancel(&mut self, id: u64) -> Result<bool> {
+        let morph = &self.ctx().morph;
+        Ok(crate::timers::timers().cancel(morph, id))
+    }
+}
+
+impl<T> timers::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::timers::Error>,
+    ) -> Result<timers::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            TimersErrorCode::InvalidTopic => Ok(timers::ErrorCode::InvalidTopic),
+            TimersErrorCode::InvalidInterval => Ok(timers::ErrorCode::InvalidInterval),
+            TimersErrorCode::TooManyTimers => Ok(timers::ErrorCode::TooManyTimers),
+            TimersErrorCode::Unknown => Ok(timers::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::timers::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::timers::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+impl<T> CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn sessions_error(
+        &mut self,
+        code: SessionsErrorCode,
+        data: anyhow::Error,
+    ) -> Result<Resource<sessions::Error>> {
+        let error = hayride_host_traits::core::sessions::Error { code, data };
+        Ok(self.table().push(error)?)
+    }
+
+    // Push the error of a failed call on the session to the table
+    fn sessions_result<R>(
+        &mut self,
+        result: std::result::Result<R, SessionsErrorCode>,
+        context: impl FnOnce() -> String,
+    ) -> Result<Result<R, Resource<sessions::Error>>> {
+        match result {
+            Ok(value) => Ok(Ok(value)),
+            Err(code) => {
+                let error = self.sessions_error(code, anyhow!(context()))?;
+                Ok(Err(error))
+            }
+        }
+    }
+}
+
+impl From<hayride_host_traits::core::sessions::Session> for sessions::Session {
+    fn from(session: hayride_host_traits::core::sessions::Session) -> Self {
+        Self {
+            id: session.id,
+            title: session.title,
+            created_at: session.created_at,
+            updated_at: session.updated_at,
+            message_count: session.message_count,
+        }
+    }
+}
+
+impl<T> sessions::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn create(
+        &mut self,
+        title: String,
+    ) -> Result<Result<sessions::Session, Resource<sessions::Error>>> {
+        let result = self.ctx().sessions_backend.create(&title).map(Into::into);
+        self.sessions_result(result, || format!("Error creating session {}", title))
+    }
+
+    fn get(&mut self, id: String) -> Result<Result<sessions::Session, Resource<sessions::Error>>> {
+        let result = self.ctx().sessions_backend.get(&id).map(Into::into);
+        self.sessions_result(result, || format!("Error getting session {}", id))
+    }
+
+    fn append(
+        &mut self,
+        id: String,
+        role: String,
+        content: String,
+    ) -> Result<Result<u64, Resource<sessions::Error>>> {
+        let result = self.ctx().sessions_backend.append(&id, &role, &content);
+        self.sessions_result(result, || format!("Error appending to session {}", id))
+    }
+
+    fn list(
+        &mut self,
+        offset: u64,
+        limit: u32,
+    ) -> Result<Result<Vec<sessions::Session>, Resource<sessions::Error>>> {
+        let result = self
+            .ctx()
+            .sessions_backend
+            .list(offset, limit)
+            .map(|sessions| sessions.into_iter().map(Into::into).collect());
+        self.sessions_result(result, || format!("Error listing {} sessions", limit))
+    }
+
+    fn history(
+        &mut self,
+        id: String,
+        offset: u64,
+        limit: u32,
+    ) -> Result<Result<Vec<sessions::Message>, Resource<sessions::Error>>> {
+        let result = self
+            .ctx()
+            .sessions_backend
+            .history(&id, offset, limit)
+            .map(|messages| {
+                messages
+                    .into_iter()
+                    .map(|message| sessions::Message {
+                        index: message.index,
+                        role: message.role,
+                        content: message.content,
+                        created_at: message.created_at,
+                    })
+                    .collect()
+            });
+        self.sessions_result(result, || {
+            format!("Error fetching history of session {}", id)
+        })
+    }
+
+    fn delete(&mut self, id: String) -> Result<Result<bool, Resource<sessions::Error>>> {
+        let result = self.ctx().sessions_backend.delete(&id);
+        self.sessions_result(result, || format!("Error deleting session {}", id))
+    }
+}
+
+impl<T> sessions::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::sessions::Error>,
+    ) -> Result<sessions::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            SessionsErrorCode::NotFound => Ok(sessions::ErrorCode::NotFound),
+            SessionsErrorCode::InvalidArgument => Ok(sessions::ErrorCode::InvalidArgument),
+            SessionsErrorCode::StorageFailed => Ok(sessions::ErrorCode::StorageFailed),
+            SessionsErrorCode::Unknown => Ok(sessions::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::sessions::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::sessions::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+impl From<logging::Level> for log::Level {
+    fn from(level: logging::Level) -> Self {
+        match level {
+            logging::Level::Trace => log::Level::Trace,
+            logging::Level::Debug => log::Level::Debug,
+            logging::Level::Info => log::Level::Info,
+            logging::Level::Warn => log::Level::Warn,
+            logging::Level::Error => log::Level::Error,
+        }
+    }
+}
+
+impl<T> logging::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn log(
+        &mut self,
+        level: logging::Level,
+        message: String,
+        fields: Vec<logging::Field>,
+    ) -> Result<()> {
+        let level = log::Level::from(level);
+        if level > self.ctx().log_level {
+            return Ok(());
+        }
+        let fields: Vec<(String, String)> = fields
+            .into_iter()
+            .map(|field| (field.key, field.value))
+            .collect();
+        log::log!(
+            target: LOG_TARGET,
+            level,
+            "{}: {}{}",
+            self.ctx().morph,
+            message,
+            Fields(&fields)
+        );
+        Ok(())
+    }
+
+    fn enabled(&mut self, level: logging::Level) -> Result<bool> {
+        let level = log::Level::from(level);
+        Ok(level <= self.ctx().log_level && log::log_enabled!(target: LOG_TARGET, level))
+    }
+}
+
+impl<T> CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn ratelimit_error(
+        &mut self,
+        name: &str,
+        error: RateLimitError,
+    ) -> Result<Resource<ratelimit::Error>> {
+        let (code, data) = match error {
+            RateLimitError::NotFound => (
+                RateLimitErrorCode::NotFound,
+                anyhow!("No bucket named {}", name),
+            ),
+            RateLimitError::InvalidArgument => (
+                RateLimitErrorCode::InvalidArgument,
+                anyhow!("Invalid limits for bucket {}", name),
+            ),
+            RateLimitError::TooManyBuckets => (
+                RateLimitErrorCode::TooManyBuckets,
+                anyhow!("Too many buckets configured"),
+            ),
+        };
+        let error = hayride_host_traits::core::ratelimit::Error { code, data };
+        Ok(self.table().push(error)?)
+    }
+}
+
+impl<T> ratelimit::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn configure(
+        &mut self,
+        name: String,
+        rate: f64,
+        burst: u32,
+    ) -> Result<Result<(), Resource<ratelimit::Error>>> {
+        match crate::ratelimit::rate_limits().configure(&name, rate, burst) {
+            Ok(()) => Ok(Ok(())),
+            Err(e) => Ok(Err(self.ratelimit_error(&name, e)?)),
+        }
+    }
+
+    async fn acquire(&mut self, name: String) -> Result<Result<(), Resource<ratelimit::Error>>> {
+        match crate::ratelimit::rate_limits().acquire(&name).await {
+            Ok(()) => Ok(Ok(())),
+            Err(e) => Ok(Err(self.ratelimit_error(&name, e)?)),
+        }
+    }
+
+    fn try_acquire(&mut self, name: String) -> Result<Result<bool, Resource<ratelimit::Error>>> {
+        match crate::ratelimit::rate_limits().try_acquire(&name) {
+            Ok(acquired) => Ok(Ok(acquired)),
+            Err(e) => Ok(Err(self.ratelimit_error(&name, e)?)),
+        }
+    }
+}
+
+impl<T> ratelimit::HostError for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn code(
+        &mut self,
+        error: Resource<hayride_host_traits::core::ratelimit::Error>,
+    ) -> Result<ratelimit::ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            RateLimitErrorCode::NotFound => Ok(ratelimit::ErrorCode::NotFound),
+            RateLimitErrorCode::InvalidArgument => Ok(ratelimit::ErrorCode::InvalidArgument),
+            RateLimitErrorCode::TooManyBuckets => Ok(ratelimit::ErrorCode::TooManyBuckets),
+            RateLimitErrorCode::Unknown => Ok(ratelimit::ErrorCode::Unknown),
+        }
+    }
+
+    fn data(
+        &mut self,
+        error: Resource<hayride_host_traits::core::ratelimit::Error>,
+    ) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<hayride_host_traits::core::ratelimit::Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+impl From<hayride_host_traits::core::system::Device> for system::Device {
+    fn from(device: hayride_host_traits::core::system::Device) -> Self {
+        use hayride_host_traits::core::system::DeviceType;
+        Self {
+            name: device.name,
+            description: device.description,
+            device_type: match device.device_type {
+                DeviceType::Cpu => system::DeviceType::Cpu,
+                DeviceType::Gpu => system::DeviceType::Gpu,
+                DeviceType::Accelerator => system::DeviceType::Accelerator,
+            },
+            memory_total: device.memory_total,
+            memory_free: device.memory_free,
+        }
+    }
+}
+
+impl<T> system::Host for CoreImpl<T>
+where
+    T: CoreView,
+{
+    fn capabilities(&mut self) -> Result<system::HostCapabilities> {
+        let capabilities = self.ctx().system_backend.capabilities();
+        Ok(system::HostCapabilities {
+            os: capabilities.os,
+            os_version: capabilities.os_version,
+            arch: capabilities.arch,
+            cpu_brand: capabilities.cpu_brand,
+            physical_cores: capabilities.physical_cores,
+            logical_cores: capabilities.logical_cores,
+            memory_total: capabilities.memory_total,
+            gpu_backends: capabilities.gpu_backends,
+        })
+    }
+
+    fn memory(&mut self) -> Result<system::MemoryStats> {
+        let memory = self.ctx().system_backend.memory();
+        Ok(system::MemoryStats {
+            memory_total: memory.memory_total,
+            memory_available: memory.memory_available,
+            swap_total: memory.swap_total,
+            swap_free: memory.swap_free,
+        })
+    }
+
+    fn devices(&mut self) -> Result<Vec<system::Device>> {
+        let devices = self.ctx().system_backend.devices();
+        Ok(devices.into_iter().map(system::Device::from).collect())
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use hayride_utils::config::LogConfig;
+use log::LevelFilter;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Target of the records morphs log, so they can be filtered apart from host logs.
+pub const LOG_TARGET: &str = "hayride_runtime::logging";
+
+/// Levels of the records morphs log through `hayride:core/logging`, from the `[log.morphs]`
+/// table of the runtime config.
+#[derive(Clone, Debug, Default)]
+pub struct MorphLogLevels {
+    morphs: BTreeMap<String, LevelFilter>,
+}
+
+impl MorphLogLevels {
+    pub fn from_config(config: &LogConfig) -> Result<Self> {
+        let morphs = config
+            .morphs
+            .iter()
+            .map(|(morph, level)| {
+                let level = level
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid log level {} for morph {}", level, morph))?;
+                Ok((morph.clone(), level))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { morphs })
+    }
+
+    /// The level set for the morph, if any.
+    pub fn get(&self, morph: &str) -> Option<LevelFilter> {
+        self.morphs.get(morph).copied()
+    }
+
+    /// The most verbose level set for a morph, the host logger has to log the target at it.
+    pub fn max(&self) -> Option<LevelFilter> {
+        self.morphs.values().copied().max()
+    }
+}
+
+/// Fields of a record, formatted as ` key=value` pairs with values quoted when needed.
+pub struct Fields<'a>(pub &'a [(String, String)]);
+
+impl fmt::Display for Fields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in self.0 {
+            let quote = value.is_empty()
+                || value
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '=' || c == '"');
+            match quote {
+                true => write!(f, " {}={:?}", key, value)?,
+                false => write!(f, " {}={}", key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use super::RegistryBackend;
+use std::path::PathBuf;
+
+/// Find a morph in the local registry, pulling it from the remote registry when it is
+/// missing locally.
+pub async fn find_or_pull(
+    registry: Option<&RegistryBackend>,
+    registry_path: String,
+    input: &str,
+) -> anyhow::Result<PathBuf> {
+    let local = hayride_utils::paths::registry::find_morph_path(registry_path.clone(), input);
+    let (Err(e), Some(registry)) = (&local, registry) else {
+        return local;
+    };
+
+    log::info!("{} not found locally ({}), pulling from registry", input, e);
+    // Pulls download over blocking clients, so they run on a blocking thread
+    let (registry, reference) = (registry.clone(), input.to_string());
+    let reference = tokio::task::spawn_blocking(move || registry.pull(&reference))
+        .await?
+        .map_err(|code| anyhow::anyhow!("failed to pull {}: {}", input, code))?;
+    hayride_utils::paths::registry::find_morph_path(registry_path, &reference)
+}
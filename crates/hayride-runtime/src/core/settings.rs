@@ -0,0 +1,107 @@
+use hayride_host_traits::core::config::{ConfigInner, ConfigValue, ErrorCode};
+use hayride_utils::config::Config;
+use std::collections::{BTreeMap, HashMap};
+
+/// Settings from the `settings` and `morph_settings` tables of the runtime config.
+///
+/// Nested tables are flattened into dotted keys, so `[settings.ai] default-model = "x"`
+/// is read as `ai.default-model`.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    shared: BTreeMap<String, ConfigValue>,
+    morphs: HashMap<String, BTreeMap<String, ConfigValue>>,
+}
+
+impl Settings {
+    pub fn from_config(config: &Config) -> Self {
+        let mut shared = BTreeMap::new();
+        flatten("", &config.settings, &mut shared);
+
+        let morphs = config
+            .morph_settings
+            .iter()
+            .map(|(morph, table)| {
+                let mut settings = BTreeMap::new();
+                flatten("", table, &mut settings);
+                (morph.clone(), settings)
+            })
+            .collect();
+
+        Self { shared, morphs }
+    }
+}
+
+impl ConfigInner for Settings {
+    fn get(&self, morph: &str, key: &str) -> Result<ConfigValue, ErrorCode> {
+        self.morphs
+            .get(morph)
+            .and_then(|settings| settings.get(key))
+            .or_else(|| self.shared.get(key))
+            .cloned()
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn keys(&self, morph: &str, prefix: &str) -> Result<Vec<String>, ErrorCode> {
+        let mut keys: Vec<String> = self
+            .shared
+            .keys()
+            .chain(self.morphs.get(morph).into_iter().flat_map(|s| s.keys()))
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+}
+
+// Flatten nested tables into dotted keys
+fn flatten(prefix: &str, table: &toml::Table, settings: &mut BTreeMap<String, ConfigValue>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        let value = match value {
+            toml::Value::Table(table) => {
+                flatten(&key, table, settings);
+                continue;
+            }
+            toml::Value::Boolean(b) => ConfigValue::Boolean(*b),
+            toml::Value::Integer(i) => ConfigValue::Integer(*i),
+            toml::Value::Float(f) => ConfigValue::Float(*f),
+            toml::Value::String(s) => ConfigValue::Text(s.clone()),
+            toml::Value::Datetime(d) => ConfigValue::Text(d.to_string()),
+            toml::Value::Array(values) => ConfigValue::TextList(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        toml::Value::String(s) => s.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect(),
+            ),
+        };
+        settings.insert(key, value);
+    }
+}
+
+/// Returns the `package:name` of a morph from its path in the registry,
+/// i.e. `<registry>/<package>/<version>/<name>.wasm`.
+pub fn morph_name(path: &std::path::Path) -> String {
+    let name = path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let package = path
+        .parent()
+        .and_then(|version| version.parent())
+        .and_then(|package| package.file_name())
+        .and_then(|package| package.to_str());
+    match package {
+        Some(package) => format!("{}:{}", package, name),
+        None => name.to_string(),
+    }
+}
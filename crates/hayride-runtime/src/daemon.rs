@@ -0,0 +1,362 @@
+use crate::engine::WasmtimeEngine;
+use crate::middleware::is_bearer_token;
+use crate::server::json_response;
+use crate::silo::silo::{thread_json, RestartPolicy, ThreadParams};
+use crate::silo::SiloCtx;
+use hayride_host_traits::silo::ThreadStatus;
+use hayride_utils::config::Config;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Limited};
+use hyper::header::{CONTENT_TYPE, ORIGIN};
+use hyper::server::conn::http1;
+use hyper::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Instant, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+use wasmtime::Result;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::io::TokioIo;
+
+// Largest request body the admin api reads
+const MAX_BODY_SIZE: usize = 1 << 20;
+// File in the hayride dir the token of a daemon without a configured one is written to
+const TOKEN_FILE: &str = "daemon.token";
+
+/// Builds the engine threads of the daemon are spawned with from a config.
+pub type EngineFactory = Box<dyn Fn(&Config) -> Result<WasmtimeEngine> + Send + Sync>;
+
+/// A long running runtime managed through a local http admin api.
+///
+/// The api lists, spawns and kills threads, reloads the config, lists the models loaded
+/// by threads and answers health checks. Every request needs a bearer token, the admin
+/// token of the config when one is set. Without one the daemon only listens on loopback
+/// addresses and generates a token, readable by the user in `~/.hayride/daemon.token`.
+/// Requests browsers send with an `Origin` header are rejected, so web pages can not
+/// drive the api.
+pub struct Daemon {
+    factory: EngineFactory,
+    state: RwLock<DaemonState>,
+    started: Instant,
+    // Token used while the config sets none
+    generated_token: String,
+    // Address the api listens on, set once the listener is bound
+    local: OnceLock<SocketAddr>,
+}
+
+// Settings swapped when the config is reloaded, threads are kept across reloads
+struct DaemonState {
+    silo_ctx: SiloCtx,
+    entrypoint: String,
+    admin_token: String,
+    // Whether the token is the one of the config
+    configured: bool,
+}
+
+impl Daemon {
+    pub fn new(config: &Config, factory: EngineFactory) -> Result<Self> {
+        let generated_token = Uuid::new_v4().simple().to_string();
+        let state = DaemonState::new(config, &factory, None, &generated_token)?;
+        Ok(Self {
+            factory,
+            state: RwLock::new(state),
+            started: Instant::now(),
+            generated_token,
+            local: OnceLock::new(),
+        })
+    }
+
+    /// Serve the admin api on the address until the listener fails.
+    pub async fn serve(self: Arc<Self>, address: &str) -> Result<()> {
+        let listener = TcpListener::bind(address).await?;
+        let local = listener.local_addr()?;
+        check_exposure(&local, &self.read())?;
+        let _ = self.local.set(local);
+        if !self.read().configured {
+            let path = write_token(&self.generated_token)?;
+            log::info!("daemon token written to {}", path.display());
+        }
+        log::info!("daemon listening on {}", local);
+
+        loop {
+            let (client, addr) = listener.accept().await?;
+            log::debug!("accepted admin client from: {}", addr);
+
+            let daemon = self.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(
+                        TokioIo::new(client),
+                        hyper::service::service_fn(move |req| {
+                            let daemon = daemon.clone();
+                            async move { daemon.handle_request(req).await }
+                        }),
+                    )
+                    .await
+                {
+                    log::error!("daemon error: {}", e);
+                }
+            });
+        }
+    }
+
+    pub async fn handle_request(
+        &self,
+        req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        // Browsers send an origin with cross-site and rebound requests, tools do not
+        if req.headers().contains_key(ORIGIN) {
+            log::warn!("rejected daemon request from a browser origin");
+            return error(
+                StatusCode::FORBIDDEN,
+                "requests from browsers are not allowed",
+            );
+        }
+        if !self.authorized(req.headers()) {
+            log::warn!("unauthorized daemon request");
+            return json_response(StatusCode::UNAUTHORIZED, json!({ "error": "unauthorized" }));
+        }
+
+        let method = req.method().clone();
+        match (method, req.uri().path()) {
+            (Method::GET, "/health") => self.health(),
+            (Method::GET, "/threads") => {
+                json_response(StatusCode::OK, self.read().silo_ctx.threads_json())
+            }
+            (Method::POST, "/threads") => {
+                if !is_json(req.headers()) {
+                    return error(
+                        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                        "expected an application/json body",
+                    );
+                }
+                let body = match read_json(req).await {
+                    Ok(body) => body,
+                    Err(message) => return error(StatusCode::BAD_REQUEST, &message),
+                };
+                self.spawn(body).await
+            }
+            (Method::DELETE, "/threads") => self.kill(req.uri().query()),
+            (Method::POST, "/config/reload") => self.reload(),
+            (Method::GET, "/models") => json_response(StatusCode::OK, models_json()),
+            (_, "/health" | "/threads" | "/config/reload" | "/models") => {
+                error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+            }
+            _ => error(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    fn health(&self) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let threads = self.read().silo_ctx.threads();
+        let running = threads
+            .iter()
+            .filter(|thread| thread.status == ThreadStatus::Processing)
+            .count();
+        json_response(
+            StatusCode::OK,
+            json!({
+                "status": "ok",
+                "uptime_secs": self.started.elapsed().as_secs(),
+                "threads": threads.len(),
+                "running": running,
+            }),
+        )
+    }
+
+    // Spawn a thread from `{"morph", "function", "args", "envs"}`
+    async fn spawn(&self, body: Value) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let Some(morph) = body["morph"].as_str() else {
+            return error(StatusCode::BAD_REQUEST, "missing morph");
+        };
+        // The state is not held while the morph is found, which may pull it
+        let (silo_ctx, params) = {
+            let state = self.read();
+            let params = ThreadParams {
+                morph: morph.to_string(),
+                function: body["function"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| state.entrypoint.clone()),
+                args: strings(&body["args"]),
+                envs: body["envs"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                    .collect(),
+                mounts: state.silo_ctx.mounts.clone(),
+                restart: RestartPolicy::Never,
+            };
+            (state.silo_ctx.clone(), params)
+        };
+        match silo_ctx.start_thread(params, None, 1).await {
+            Ok(thread) => json_response(StatusCode::CREATED, thread_json(&thread)),
+            Err(e) => error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                &format!("failed to spawn {}: error {}", morph, u32::from(e)),
+            ),
+        }
+    }
+
+    // Kill the thread with the `id` query parameter
+    fn kill(&self, query: Option<&str>) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let id = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+            .find(|(key, _)| key == "id")
+            .and_then(|(_, id)| Uuid::parse_str(&id).ok());
+        let Some(id) = id else {
+            return error(StatusCode::BAD_REQUEST, "missing or invalid thread id");
+        };
+        match self.read().silo_ctx.kill_thread(id) {
+            Ok(()) => json_response(StatusCode::OK, json!({ "killed": id.to_string() })),
+            Err(_) => error(StatusCode::NOT_FOUND, "no running thread with the id"),
+        }
+    }
+
+    // Load the config again, threads spawned after it run under the new settings. A config
+    // that could not be served on the bound address is rejected.
+    fn reload(&self) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let reloaded = Config::load().and_then(|config| {
+            let threads = self.read().silo_ctx.threads.clone();
+            let state =
+                DaemonState::new(&config, &self.factory, Some(threads), &self.generated_token)?;
+            if let Some(local) = self.local.get() {
+                check_exposure(local, &state)?;
+            }
+            if !state.configured && self.read().configured {
+                write_token(&self.generated_token)?;
+            }
+            Ok(state)
+        });
+        match reloaded {
+            Ok(state) => {
+                *self.state.write().unwrap_or_else(|e| e.into_inner()) = state;
+                log::info!("daemon reloaded its config");
+                json_response(StatusCode::OK, json!({ "reloaded": true }))
+            }
+            Err(e) => {
+                log::error!("failed to reload the daemon config: {:?}", e);
+                error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("failed to reload the config: {}", e),
+                )
+            }
+        }
+    }
+
+    fn authorized(&self, headers: &hyper::HeaderMap) -> bool {
+        is_bearer_token(headers, &self.read().admin_token)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, DaemonState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl DaemonState {
+    fn new(
+        config: &Config,
+        factory: &EngineFactory,
+        threads: Option<Arc<dashmap::DashMap<Uuid, crate::silo::silo::ThreadData>>>,
+        generated_token: &str,
+    ) -> Result<Self> {
+        let engine = factory(config)?;
+        let mut silo_ctx = engine.silo_ctx();
+        if let Some(threads) = threads {
+            silo_ctx.threads = threads;
+        }
+        Ok(Self {
+            silo_ctx,
+            entrypoint: config.entrypoint.clone(),
+            admin_token: config
+                .admin
+                .token
+                .clone()
+                .unwrap_or_else(|| generated_token.to_string()),
+            configured: config.admin.token.is_some(),
+        })
+    }
+}
+
+// Only a token of the config may guard an api reachable from other hosts
+fn check_exposure(local: &SocketAddr, state: &DaemonState) -> Result<()> {
+    if !local.ip().is_loopback() && !state.configured {
+        return Err(anyhow::anyhow!(
+            "the daemon needs an admin token to listen on {}",
+            local
+        ));
+    }
+    Ok(())
+}
+
+// Write the generated token where only the user can read it
+fn write_token(token: &str) -> Result<PathBuf> {
+    let path = hayride_utils::paths::hayride::default_hayride_dir()?.join(TOKEN_FILE);
+    // A file left by an earlier run may have looser permissions
+    let _ = std::fs::remove_file(&path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(&path)?, token.as_bytes())?;
+    Ok(path)
+}
+
+fn is_json(headers: &hyper::HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+// The models loaded by threads of the process
+fn models_json() -> Value {
+    let models: Vec<Value> = crate::status::status()
+        .models()
+        .iter()
+        .map(|model| {
+            json!({
+                "name": model.name,
+                "backend": model.backend,
+                "loads": model.loads,
+                "last_loaded": model
+                    .last_loaded
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    Value::Array(models)
+}
+
+async fn read_json(
+    req: hyper::Request<hyper::body::Incoming>,
+) -> std::result::Result<Value, String> {
+    let body: Bytes = Limited::new(req.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|e| format!("failed to read the body: {}", e))?
+        .to_bytes();
+    serde_json::from_slice(&body).map_err(|e| format!("invalid json: {}", e))
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect()
+}
+
+fn error(status: StatusCode, message: &str) -> Result<hyper::Response<HyperOutgoingBody>> {
+    json_response(status, json!({ "error": message }))
+}
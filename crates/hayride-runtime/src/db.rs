@@ -1,15 +1,16 @@
 pub mod bindings;
+pub mod cache;
 pub mod db;
 mod db_impl;
 
 pub use db::DBCtx;
 pub use db::{DBImpl, DBView};
 
-use hayride_host_traits::db::DBTrait;
+use hayride_host_traits::db::DBTraitAsync;
 
 use wasmtime::component::HasData;
 
-pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
 where
     T: DBView,
 {
@@ -24,9 +25,9 @@ impl<T: 'static> HasData for HasDB<T> {
     type Data<'a> = DBImpl<&'a mut T>;
 }
 
-pub struct DBBackend(Box<dyn DBTrait>);
+pub struct DBBackend(Box<dyn DBTraitAsync>);
 impl std::ops::Deref for DBBackend {
-    type Target = dyn DBTrait;
+    type Target = dyn DBTraitAsync;
     fn deref(&self) -> &Self::Target {
         self.0.as_ref()
     }
@@ -36,7 +37,7 @@ impl std::ops::DerefMut for DBBackend {
         self.0.as_mut()
     }
 }
-impl<T: DBTrait + 'static> From<T> for DBBackend {
+impl<T: DBTraitAsync + 'static> From<T> for DBBackend {
     fn from(value: T) -> Self {
         Self(Box::new(value))
     }
@@ -2,7 +2,25 @@ pub mod generated {
     wasmtime::component::bindgen!({
         path: "../../wit",
         world: "hayride-db",
+        // Functions that wait on the database are async so they do not block the runtime
         imports: {
+            "hayride:db/db/open": async | trappable,
+            "hayride:db/db/[method]connection.prepare": async | trappable,
+            "hayride:db/db/[method]connection.begin-transaction": async | trappable,
+            "hayride:db/db/[method]connection.migrate": async | trappable,
+            "hayride:db/db/[method]connection.last-insert-rowid": async | trappable,
+            "hayride:db/db/[method]connection.close": async | trappable,
+            "hayride:db/db/[method]statement.query": async | trappable,
+            "hayride:db/db/[method]statement.execute": async | trappable,
+            "hayride:db/db/[method]statement.execute-returning": async | trappable,
+            "hayride:db/db/[method]statement.close": async | trappable,
+            "hayride:db/db/[method]transaction.commit": async | trappable,
+            "hayride:db/db/[method]transaction.rollback": async | trappable,
+            "hayride:db/db/[method]transaction.query": async | trappable,
+            "hayride:db/db/[method]transaction.execute": async | trappable,
+            "hayride:db/db/[method]transaction.prepare": async | trappable,
+            "hayride:db/db/[method]rows.next": async | trappable,
+            "hayride:db/db/[method]rows.close": async | trappable,
             default: trappable,
         },
         with: {
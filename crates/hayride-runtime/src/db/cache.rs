@@ -0,0 +1,526 @@
+use hayride_host_traits::db::db::{DBValue, Row};
+use hayride_host_traits::db::{DBRowsAsync, ErrorCode, Rows};
+use hayride_utils::config::DbConfig;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Environment variable with the cache ttl in seconds
+const CACHE_TTL: &str = "HAYRIDE_DB_CACHE_TTL";
+
+// Comment hints recognized in statements, i.e. `/* hayride:tables=users,orders */`
+const NO_CACHE_HINT: &str = "hayride:no-cache";
+const TABLES_HINT: &str = "hayride:tables=";
+
+// Keywords that make a statement a write even when it starts as a read
+const WRITE_KEYWORDS: [&str; 11] = [
+    "insert", "update", "delete", "merge", "replace", "create", "drop", "alter", "truncate",
+    "into", "lock",
+];
+
+static QUERY_CACHE: OnceLock<QueryCache> = OnceLock::new();
+
+/// Returns the process wide query result cache.
+pub fn query_cache() -> &'static QueryCache {
+    QUERY_CACHE.get_or_init(QueryCache::default)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueryCacheConfig {
+    /// How long results are served from the cache, caching is disabled without one
+    pub ttl: Option<Duration>,
+    pub max_entries: usize,
+    /// Results with more rows are not cached
+    pub max_rows: usize,
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            max_entries: 1024,
+            max_rows: 1000,
+        }
+    }
+}
+
+impl QueryCacheConfig {
+    pub fn from_config(config: &DbConfig) -> Self {
+        Self {
+            ttl: config
+                .cache_ttl
+                .filter(|s| *s > 0.0)
+                .map(Duration::from_secs_f64),
+            max_entries: config.cache_max_entries,
+            max_rows: config.cache_max_rows,
+        }
+    }
+
+    /// Override the ttl in seconds if `HAYRIDE_DB_CACHE_TTL` is set.
+    pub fn with_env(self) -> Self {
+        match std::env::var(CACHE_TTL).map(|s| s.parse::<f64>()) {
+            Ok(Ok(secs)) => Self {
+                ttl: (secs > 0.0).then(|| Duration::from_secs_f64(secs)),
+                ..self
+            },
+            Ok(Err(_)) => {
+                log::warn!("ignoring invalid {}", CACHE_TTL);
+                self
+            }
+            Err(_) => self,
+        }
+    }
+}
+
+/// What a statement reads or writes, as far as the cache is concerned.
+#[derive(Clone, Debug, Default)]
+pub struct StatementInfo {
+    /// Statement with comments removed and whitespace collapsed
+    pub normalized: String,
+    pub read: bool,
+    /// Lowercase names of the tables the statement references, without schema
+    pub tables: HashSet<String>,
+    /// Set by a `hayride:no-cache` hint
+    pub no_cache: bool,
+}
+
+impl StatementInfo {
+    pub fn parse(sql: &str) -> Self {
+        let mut info = Self::default();
+        let tokens = tokenize(sql, &mut info);
+        info.normalized = tokens.join(" ");
+
+        let keywords: Vec<String> = tokens.iter().map(|t| t.to_lowercase()).collect();
+        info.read = matches!(
+            keywords.first().map(String::as_str),
+            Some("select") | Some("with")
+        ) && !keywords
+            .iter()
+            .any(|k| WRITE_KEYWORDS.contains(&k.as_str()));
+
+        let mut i = 0;
+        while i < keywords.len() {
+            if matches!(
+                keywords[i].as_str(),
+                "from" | "join" | "into" | "update" | "table" | "truncate"
+            ) {
+                i = table_list(&tokens, &keywords, i + 1, &mut info.tables);
+            } else {
+                i += 1;
+            }
+        }
+        info
+    }
+
+    /// Whether results of the statement may be served from and stored in the cache.
+    pub fn cacheable(&self) -> bool {
+        self.read && !self.no_cache
+    }
+
+    fn key(&self, params: &[DBValue]) -> String {
+        format!("{}\0{:?}", self.normalized, params)
+    }
+}
+
+// Split a statement into tokens, dropping comments and collecting the hints in them
+fn tokenize(sql: &str, info: &mut StatementInfo) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '-' if chars.peek() == Some(&'-') => {
+                let comment: String = chars.by_ref().take_while(|c| *c != '\n').collect();
+                hints(&comment, info);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut comment = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        break;
+                    }
+                    comment.push(c);
+                }
+                hints(&comment, info);
+            }
+            '\'' | '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let mut token = c.to_string();
+                while let Some(c) = chars.next() {
+                    token.push(c);
+                    if c == close {
+                        // A doubled quote escapes itself
+                        if chars.peek() == Some(&close) && close != ']' {
+                            token.extend(chars.next());
+                            continue;
+                        }
+                        break;
+                    }
+                }
+                tokens.push(token);
+            }
+            c if is_word(c) => {
+                let mut token = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_word(*c)) {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+            c => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn hints(comment: &str, info: &mut StatementInfo) {
+    for hint in comment.split_whitespace() {
+        if hint == NO_CACHE_HINT {
+            info.no_cache = true;
+        } else if let Some(tables) = hint.strip_prefix(TABLES_HINT) {
+            info.tables.extend(
+                tables
+                    .split(',')
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.to_lowercase()),
+            );
+        }
+    }
+}
+
+// Collect the comma separated tables starting at `i`, returning the index after them
+fn table_list(
+    tokens: &[String],
+    keywords: &[String],
+    mut i: usize,
+    tables: &mut HashSet<String>,
+) -> usize {
+    loop {
+        while keywords
+            .get(i)
+            .is_some_and(|k| matches!(k.as_str(), "if" | "not" | "exists" | "only" | "table"))
+        {
+            i += 1;
+        }
+
+        // A possibly quoted and schema qualified name
+        let mut name = None;
+        while let Some(token) = tokens.get(i) {
+            if token == "(" || token == "," || token == ";" || token == ")" {
+                break;
+            }
+            name = Some(unquote(token));
+            if tokens.get(i + 1).is_some_and(|t| t == ".") {
+                i += 2;
+            } else {
+                i += 1;
+                break;
+            }
+        }
+        match name {
+            Some(name) => {
+                tables.insert(name);
+            }
+            None => return i,
+        }
+
+        // Skip an alias
+        if keywords.get(i).is_some_and(|k| k == "as") {
+            i += 1;
+        }
+        if tokens.get(i).is_some_and(|t| t.chars().all(is_word))
+            && !keywords.get(i).is_some_and(|k| is_clause(k))
+        {
+            i += 1;
+        }
+
+        if tokens.get(i).is_some_and(|t| t == ",") {
+            i += 1;
+        } else {
+            return i;
+        }
+    }
+}
+
+// Keywords that can follow a table name and must not be taken for an alias
+fn is_clause(keyword: &str) -> bool {
+    matches!(
+        keyword,
+        "where"
+            | "join"
+            | "inner"
+            | "left"
+            | "right"
+            | "full"
+            | "cross"
+            | "natural"
+            | "on"
+            | "using"
+            | "group"
+            | "order"
+            | "having"
+            | "limit"
+            | "offset"
+            | "union"
+            | "intersect"
+            | "except"
+            | "set"
+            | "values"
+            | "select"
+            | "default"
+            | "returning"
+            | "window"
+            | "for"
+    )
+}
+
+fn unquote(token: &str) -> String {
+    token
+        .trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+        .to_lowercase()
+}
+
+/// A statement prepared by a store, tracked by resource.
+#[derive(Clone)]
+pub(crate) struct TrackedStatement {
+    pub db: String,
+    pub info: StatementInfo,
+    // Transaction the statement was prepared in
+    pub transaction: Option<u32>,
+}
+
+/// A transaction opened by a store, tracked by resource.
+pub(crate) struct TrackedTransaction {
+    pub db: String,
+    // Tables written to, invalidated again on commit
+    pub writes: Vec<HashSet<String>>,
+}
+
+struct Entry {
+    columns: Vec<String>,
+    rows: Arc<Vec<Row>>,
+    tables: HashSet<String>,
+    expires: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    config: QueryCacheConfig,
+    // Entries per database
+    entries: HashMap<String, HashMap<String, Entry>>,
+    // Bumped on every invalidation, results read before one are not stored
+    generations: HashMap<String, u64>,
+}
+
+/// Results of read statements shared by every store in the process.
+///
+/// Results are keyed by database, normalized statement and parameters, and dropped once
+/// their ttl passes or a statement run through the runtime writes to a table they read.
+/// Writes made outside the runtime are only picked up when the ttl passes, as are writes
+/// to tables hidden behind views, triggers or functions unless they are named with a
+/// `hayride:tables=` hint.
+#[derive(Default)]
+pub struct QueryCache {
+    state: Mutex<CacheState>,
+}
+
+impl QueryCache {
+    pub fn configure(&self, config: QueryCacheConfig) {
+        if let Ok(mut state) = self.state.lock() {
+            state.config = config;
+            state.entries.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.config.ttl.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Returns the cached result of a statement, if it has not expired.
+    pub fn get(&self, db: &str, info: &StatementInfo, params: &[DBValue]) -> Option<Rows> {
+        let state = self.state.lock().ok()?;
+        let entry = state.entries.get(db)?.get(&info.key(params))?;
+        if entry.expires <= Instant::now() {
+            return None;
+        }
+
+        let rows: Box<dyn DBRowsAsync> = Box::new(CachedRows {
+            columns: entry.columns.clone(),
+            rows: entry.rows.clone(),
+            position: 0,
+        });
+        Some(rows.into())
+    }
+
+    /// Returns the generation to pass to [`QueryCache::insert`] for a result read now.
+    pub fn generation(&self, db: &str) -> u64 {
+        self.state
+            .lock()
+            .map(|state| state.generations.get(db).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Read a result and cache it, returning rows replaying it.
+    ///
+    /// The result is not cached if it has more rows than the limit, or if the database
+    /// was written to since `generation`.
+    pub async fn insert(
+        &self,
+        db: &str,
+        info: &StatementInfo,
+        params: &[DBValue],
+        generation: u64,
+        mut rows: Rows,
+    ) -> Rows {
+        let Some((ttl, max_entries, max_rows)) = self.state.lock().ok().and_then(|state| {
+            Some((
+                state.config.ttl?,
+                state.config.max_entries,
+                state.config.max_rows,
+            ))
+        }) else {
+            return rows;
+        };
+
+        let columns = rows.columns();
+        let mut read = vec![];
+        let mut pending = None;
+        while read.len() <= max_rows {
+            match rows.next().await {
+                Ok(row) => read.push(row),
+                Err(ErrorCode::EndOfRows) => {
+                    let _ = rows.close().await;
+                    break;
+                }
+                Err(code) => {
+                    pending = Some(code);
+                    break;
+                }
+            }
+        }
+
+        if pending.is_some() || read.len() > max_rows {
+            let partial: Box<dyn DBRowsAsync> = Box::new(PartialRows {
+                columns,
+                read: read.into(),
+                pending,
+                rest: rows,
+            });
+            return partial.into();
+        }
+
+        let read = Arc::new(read);
+        if let Ok(mut state) = self.state.lock() {
+            if state.generations.get(db).copied().unwrap_or_default() == generation {
+                let now = Instant::now();
+                let entries = state.entries.entry(db.to_string()).or_default();
+                if entries.len() >= max_entries {
+                    entries.retain(|_, entry| entry.expires > now);
+                }
+                if entries.len() >= max_entries {
+                    // Evict the entry closest to expiring
+                    let oldest = entries
+                        .iter()
+                        .min_by_key(|(_, entry)| entry.expires)
+                        .map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        entries.remove(&oldest);
+                    }
+                }
+                entries.insert(
+                    info.key(params),
+                    Entry {
+                        columns: columns.clone(),
+                        rows: read.clone(),
+                        tables: info.tables.clone(),
+                        expires: now + ttl,
+                    },
+                );
+            }
+        }
+
+        let rows: Box<dyn DBRowsAsync> = Box::new(CachedRows {
+            columns,
+            rows: read,
+            position: 0,
+        });
+        rows.into()
+    }
+
+    /// Drop the cached results of a database that read any of the tables, a write that
+    /// names no tables drops every result of the database.
+    pub fn invalidate(&self, db: &str, tables: &HashSet<String>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        *state.generations.entry(db.to_string()).or_default() += 1;
+
+        if tables.is_empty() {
+            state.entries.remove(db);
+        } else if let Some(entries) = state.entries.get_mut(db) {
+            entries.retain(|_, entry| entry.tables.is_disjoint(tables));
+        }
+    }
+}
+
+// Replays a cached result
+struct CachedRows {
+    columns: Vec<String>,
+    rows: Arc<Vec<Row>>,
+    position: usize,
+}
+
+#[async_trait::async_trait]
+impl DBRowsAsync for CachedRows {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    async fn next(&mut self) -> Result<Row, ErrorCode> {
+        let row = self.rows.get(self.position).ok_or(ErrorCode::EndOfRows)?;
+        self.position += 1;
+        Ok(row.clone())
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+}
+
+// A result that could not be cached, replays the rows already read before the rest
+struct PartialRows {
+    columns: Vec<String>,
+    read: VecDeque<Row>,
+    pending: Option<ErrorCode>,
+    rest: Rows,
+}
+
+#[async_trait::async_trait]
+impl DBRowsAsync for PartialRows {
+    fn columns(&self) -> Vec<String> {
+        self.columns.clone()
+    }
+
+    async fn next(&mut self) -> Result<Row, ErrorCode> {
+        if let Some(row) = self.read.pop_front() {
+            return Ok(row);
+        }
+        if let Some(code) = self.pending.take() {
+            return Err(code);
+        }
+        self.rest.next().await
+    }
+
+    async fn close(&mut self) -> Result<(), ErrorCode> {
+        self.rest.close().await
+    }
+}
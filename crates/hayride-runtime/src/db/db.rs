@@ -1,9 +1,25 @@
+use std::collections::HashMap;
 use wasmtime::component::ResourceTable;
 
+use super::cache::{TrackedStatement, TrackedTransaction};
 use super::DBBackend;
+use crate::capabilities::CapabilityPolicy;
+use crate::timeouts::HostTimeouts;
 
 pub struct DBCtx {
     pub db_backend: DBBackend,
+
+    // Deadline for opening connections, and the default timeout of their queries
+    pub timeouts: HostTimeouts,
+
+    // Capabilities enabled when the store was created
+    pub capabilities: CapabilityPolicy,
+
+    // Database of each open connection, statement and transaction, keyed by resource,
+    // used to look up and invalidate cached query results
+    pub(crate) connections: HashMap<u32, String>,
+    pub(crate) statements: HashMap<u32, TrackedStatement>,
+    pub(crate) transactions: HashMap<u32, TrackedTransaction>,
 }
 
 impl DBCtx {
@@ -11,8 +27,25 @@ impl DBCtx {
         let db_backend: Box<hayride_db::DBBackend> = Box::new(hayride_db::DBBackend::new());
         Self {
             db_backend: DBBackend(db_backend),
+            timeouts: HostTimeouts::default(),
+            capabilities: CapabilityPolicy::default(),
+            connections: HashMap::new(),
+            statements: HashMap::new(),
+            transactions: HashMap::new(),
         }
     }
+
+    pub fn with_timeouts(mut self, timeouts: HostTimeouts) -> Self {
+        let db_backend = hayride_db::DBBackend::new().with_query_timeout(timeouts.db);
+        self.db_backend = DBBackend(Box::new(db_backend));
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: CapabilityPolicy) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
 }
 
 pub trait DBView: Send {
@@ -1,13 +1,17 @@
 use crate::db::bindings::db::Statement;
 use crate::db::bindings::{db, db::ErrorCode};
-use crate::db::{DBImpl, DBView};
+use crate::db::cache::{query_cache, StatementInfo, TrackedStatement, TrackedTransaction};
+use crate::db::{DBCtx, DBImpl, DBView};
 use hayride_host_traits::db::db::{DBValue as HostDBValue, Statement as HostStatement};
-use hayride_host_traits::db::{Connection, Error, IsolationLevel, Rows};
+use hayride_host_traits::db::{Connection, Error, IsolationLevel, Migration, Rows};
 
 use wasmtime::component::Resource;
 use wasmtime::Result;
 
+use crate::timeouts::deadline;
 use anyhow::anyhow;
+use std::collections::HashSet;
+use tracing::Instrument;
 
 // Conversion functions between WIT types and host trait types
 fn convert_db_value_to_host(value: db::DbValue) -> HostDBValue {
@@ -46,15 +50,44 @@ fn convert_host_db_value_to_wit(value: HostDBValue) -> db::DbValue {
     }
 }
 
+// Returns the error for executing a statement while db writes are disabled
+fn writes_disabled(ctx: &DBCtx) -> Option<Error> {
+    if ctx.capabilities.db_write {
+        return None;
+    }
+    Some(Error {
+        code: hayride_host_traits::db::ErrorCode::NotEnabled,
+        data: anyhow!("DB writes are disabled"),
+    })
+}
+
+// Drop cached results made stale by a write, a write in a transaction drops them again
+// once the transaction commits
+fn invalidate(ctx: &mut DBCtx, db: &str, info: &StatementInfo, transaction: Option<u32>) {
+    query_cache().invalidate(db, &info.tables);
+    if let Some(transaction) = transaction.and_then(|rep| ctx.transactions.get_mut(&rep)) {
+        transaction.writes.push(info.tables.clone());
+    }
+}
+
 impl<T> db::Host for DBImpl<T>
 where
     T: DBView,
 {
-    fn open(&mut self, name: String) -> Result<Result<Resource<Connection>, Resource<Error>>> {
+    async fn open(
+        &mut self,
+        name: String,
+    ) -> Result<Result<Resource<Connection>, Resource<Error>>> {
         let ctx = self.ctx();
-        match ctx.db_backend.open(name.into()) {
+        let timeout = ctx.timeouts.db;
+        let span = tracing::info_span!("db.open", db.name = %name);
+        match deadline(timeout, ctx.db_backend.open(name.clone()).instrument(span))
+            .await
+            .unwrap_or(Err(hayride_host_traits::db::ErrorCode::Timeout))
+        {
             Ok(conn) => {
                 let resource = self.table().push(conn)?;
+                self.ctx().connections.insert(resource.rep(), name);
                 Ok(Ok(resource))
             }
             Err(code) => {
@@ -92,6 +125,7 @@ where
             hayride_host_traits::db::ErrorCode::NextFailed => Ok(ErrorCode::NextFailed),
             hayride_host_traits::db::ErrorCode::EndOfRows => Ok(ErrorCode::EndOfRows),
             hayride_host_traits::db::ErrorCode::NotEnabled => Ok(ErrorCode::NotEnabled),
+            hayride_host_traits::db::ErrorCode::Timeout => Ok(ErrorCode::Timeout),
             hayride_host_traits::db::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
         }
     }
@@ -111,15 +145,25 @@ impl<T> db::HostConnection for DBImpl<T>
 where
     T: DBView,
 {
-    fn prepare(
+    async fn prepare(
         &mut self,
         self_: Resource<Connection>,
         query: String,
     ) -> wasmtime::Result<Result<Resource<Statement>, Resource<Error>>> {
+        let db = self.ctx().connections.get(&self_.rep()).cloned();
+        let info = StatementInfo::parse(&query);
         let connection: &Connection = self.table().get(&self_)?;
-        match connection.prepare(query) {
+        match connection.prepare(query).await {
             Ok(statement) => {
                 let resource = self.table().push(statement)?;
+                if let Some(db) = db {
+                    let tracked = TrackedStatement {
+                        db,
+                        info,
+                        transaction: None,
+                    };
+                    self.ctx().statements.insert(resource.rep(), tracked);
+                }
                 Ok(Ok(resource))
             }
             Err(code) => {
@@ -133,7 +177,7 @@ where
         }
     }
 
-    fn begin_transaction(
+    async fn begin_transaction(
         &mut self,
         self_: wasmtime::component::Resource<Connection>,
         isolation_level: db::IsolationLevel,
@@ -156,9 +200,16 @@ where
             db::IsolationLevel::Linearizable => IsolationLevel::Linearizable,
         };
 
-        match connection.begin_transaction(isolation_level, read_only) {
+        match connection
+            .begin_transaction(isolation_level, read_only)
+            .await
+        {
             Ok(transaction) => {
                 let resource = self.table().push(transaction)?;
+                if let Some(db) = self.ctx().connections.get(&self_.rep()).cloned() {
+                    let tracked = TrackedTransaction { db, writes: vec![] };
+                    self.ctx().transactions.insert(resource.rep(), tracked);
+                }
                 Ok(Ok(resource))
             }
             Err(code) => {
@@ -172,12 +223,72 @@ where
         }
     }
 
-    fn close(
+    async fn migrate(
+        &mut self,
+        self_: Resource<Connection>,
+        migrations: Vec<db::Migration>,
+        dry_run: bool,
+    ) -> wasmtime::Result<Result<Vec<u64>, Resource<Error>>> {
+        let span = tracing::info_span!("db.migrate", db.dry_run = dry_run);
+        if !dry_run {
+            if let Some(error) = writes_disabled(self.ctx()) {
+                let resource = self.table().push(error)?;
+                return Ok(Err(resource));
+            }
+        }
+
+        let migrations: Vec<Migration> = migrations
+            .into_iter()
+            .map(|migration| Migration {
+                version: migration.version,
+                name: migration.name,
+                sql: migration.sql,
+            })
+            .collect();
+        let db = self.ctx().connections.get(&self_.rep()).cloned();
+        let connection: &Connection = self.table().get(&self_)?;
+        let result = hayride_db::migrations::migrate(&**connection, &migrations, dry_run)
+            .instrument(span)
+            .await;
+        match result {
+            Ok(versions) => {
+                // Schema changes can touch any table
+                if let Some(db) = db.filter(|_| !dry_run && !versions.is_empty()) {
+                    query_cache().invalidate(&db, &HashSet::new());
+                }
+                Ok(Ok(versions))
+            }
+            Err(error) => {
+                let resource = self.table().push(error)?;
+                Ok(Err(resource))
+            }
+        }
+    }
+
+    async fn last_insert_rowid(
+        &mut self,
+        self_: Resource<Connection>,
+    ) -> wasmtime::Result<Result<i64, Resource<Error>>> {
+        let connection: &Connection = self.table().get(&self_)?;
+        match connection.last_insert_rowid().await {
+            Ok(rowid) => Ok(Ok(rowid)),
+            Err(code) => {
+                let error = Error {
+                    code,
+                    data: anyhow!("DB last insert rowid error"),
+                };
+                let resource = self.table().push(error)?;
+                Ok(Err(resource))
+            }
+        }
+    }
+
+    async fn close(
         &mut self,
         self_: Resource<Connection>,
     ) -> wasmtime::Result<Result<(), Resource<Error>>> {
         let connection: &mut Connection = self.table().get_mut(&self_)?;
-        match connection.close() {
+        match connection.close().await {
             Ok(()) => Ok(Ok(())),
             Err(code) => {
                 let error = Error {
@@ -191,6 +302,7 @@ where
     }
 
     fn drop(&mut self, connection: Resource<Connection>) -> Result<()> {
+        self.ctx().connections.remove(&connection.rep());
         self.table().delete(connection)?;
         Ok(())
     }
@@ -200,7 +312,7 @@ impl<T> db::HostStatement for DBImpl<T>
 where
     T: DBView,
 {
-    fn query(
+    async fn query(
         &mut self,
         statement: wasmtime::component::Resource<HostStatement>,
         args: wasmtime::component::__internal::Vec<db::DbValue>,
@@ -210,14 +322,39 @@ where
             wasmtime::component::Resource<Error>,
         >,
     > {
-        let statement: &HostStatement = self.table().get(&statement)?;
+        let span = tracing::info_span!("db.query", db.cached = tracing::field::Empty);
 
         // Convert WIT params to host trait params
         let host_params: Vec<HostDBValue> =
             args.into_iter().map(convert_db_value_to_host).collect();
 
-        match statement.query(host_params) {
-            Ok(result) => {
+        // Reads outside of transactions are served from the cache when it is enabled
+        let tracked = self.ctx().statements.get(&statement.rep()).cloned();
+        let cache = query_cache();
+        let cached = tracked
+            .as_ref()
+            .filter(|t| t.transaction.is_none() && t.info.cacheable() && cache.is_enabled());
+        if let Some(tracked) = cached {
+            if let Some(rows) = cache.get(&tracked.db, &tracked.info, &host_params) {
+                span.record("db.cached", true);
+                let resource = self.table().push(rows)?;
+                return Ok(Ok(resource));
+            }
+        }
+        let generation = cached.map(|tracked| (cache.generation(&tracked.db), host_params.clone()));
+
+        let statement: &HostStatement = self.table().get(&statement)?;
+        match statement.query(host_params).instrument(span.clone()).await {
+            Ok(mut result) => {
+                if let (Some(tracked), Some((generation, params))) = (cached, generation) {
+                    result = cache
+                        .insert(&tracked.db, &tracked.info, &params, generation, result)
+                        .instrument(span)
+                        .await;
+                }
+                if let Some(tracked) = tracked.as_ref().filter(|t| !t.info.read) {
+                    invalidate(self.ctx(), &tracked.db, &tracked.info, tracked.transaction);
+                }
                 let resource = self.table().push(result)?;
                 Ok(Ok(resource))
             }
@@ -246,19 +383,31 @@ where
         }
     }
 
-    fn execute(
+    async fn execute(
         &mut self,
         statement: Resource<Statement>,
         params: Vec<db::DbValue>,
     ) -> Result<Result<u64, Resource<Error>>> {
+        let span = tracing::info_span!("db.execute");
+        if let Some(error) = writes_disabled(self.ctx()) {
+            let resource = self.table().push(error)?;
+            return Ok(Err(resource));
+        }
+
+        let tracked = self.ctx().statements.get(&statement.rep()).cloned();
         let statement: &HostStatement = self.table().get(&statement)?;
 
         // Convert WIT params to host trait params
         let host_params: Vec<HostDBValue> =
             params.into_iter().map(convert_db_value_to_host).collect();
 
-        match statement.execute(host_params) {
-            Ok(affected_rows) => Ok(Ok(affected_rows)),
+        match statement.execute(host_params).instrument(span).await {
+            Ok(affected_rows) => {
+                if let Some(tracked) = tracked {
+                    invalidate(self.ctx(), &tracked.db, &tracked.info, tracked.transaction);
+                }
+                Ok(Ok(affected_rows))
+            }
             Err(code) => {
                 let error = Error {
                     code,
@@ -270,12 +419,53 @@ where
         }
     }
 
-    fn close(
+    async fn execute_returning(
+        &mut self,
+        statement: Resource<Statement>,
+        params: Vec<db::DbValue>,
+    ) -> Result<Result<Resource<Rows>, Resource<Error>>> {
+        let span = tracing::info_span!("db.execute", db.returning = true);
+        if let Some(error) = writes_disabled(self.ctx()) {
+            let resource = self.table().push(error)?;
+            return Ok(Err(resource));
+        }
+
+        let tracked = self.ctx().statements.get(&statement.rep()).cloned();
+        let statement: &HostStatement = self.table().get(&statement)?;
+
+        // Convert WIT params to host trait params
+        let host_params: Vec<HostDBValue> =
+            params.into_iter().map(convert_db_value_to_host).collect();
+
+        match statement
+            .execute_returning(host_params)
+            .instrument(span)
+            .await
+        {
+            Ok(rows) => {
+                if let Some(tracked) = tracked {
+                    invalidate(self.ctx(), &tracked.db, &tracked.info, tracked.transaction);
+                }
+                let resource = self.table().push(rows)?;
+                Ok(Ok(resource))
+            }
+            Err(code) => {
+                let error = Error {
+                    code,
+                    data: anyhow!("DB execute returning error"),
+                };
+                let resource = self.table().push(error)?;
+                Ok(Err(resource))
+            }
+        }
+    }
+
+    async fn close(
         &mut self,
         statement: Resource<Statement>,
     ) -> wasmtime::Result<Result<(), Resource<Error>>> {
         let statement: &mut HostStatement = self.table().get_mut(&statement)?;
-        match statement.close() {
+        match statement.close().await {
             Ok(()) => Ok(Ok(())),
             Err(code) => {
                 let error = Error {
@@ -289,6 +479,7 @@ where
     }
 
     fn drop(&mut self, statement: Resource<Statement>) -> Result<()> {
+        self.ctx().statements.remove(&statement.rep());
         self.table().delete(statement)?;
         Ok(())
     }
@@ -298,14 +489,22 @@ impl<T> db::HostTransaction for DBImpl<T>
 where
     T: DBView,
 {
-    fn commit(
+    async fn commit(
         &mut self,
         self_: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
     ) -> wasmtime::Result<std::result::Result<(), wasmtime::component::Resource<Error>>> {
         let transaction: &mut hayride_host_traits::db::Transaction =
             self.table().get_mut(&self_)?;
-        match transaction.commit() {
-            Ok(()) => Ok(Ok(())),
+        match transaction.commit().await {
+            Ok(()) => {
+                if let Some(tracked) = self.ctx().transactions.get_mut(&self_.rep()) {
+                    let writes = std::mem::take(&mut tracked.writes);
+                    for tables in writes {
+                        query_cache().invalidate(&tracked.db, &tables);
+                    }
+                }
+                Ok(Ok(()))
+            }
             Err(code) => {
                 let error = Error {
                     code,
@@ -317,14 +516,19 @@ where
         }
     }
 
-    fn rollback(
+    async fn rollback(
         &mut self,
         self_: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
     ) -> wasmtime::Result<std::result::Result<(), wasmtime::component::Resource<Error>>> {
         let transaction: &mut hayride_host_traits::db::Transaction =
             self.table().get_mut(&self_)?;
-        match transaction.rollback() {
-            Ok(()) => Ok(Ok(())),
+        match transaction.rollback().await {
+            Ok(()) => {
+                if let Some(tracked) = self.ctx().transactions.get_mut(&self_.rep()) {
+                    tracked.writes.clear();
+                }
+                Ok(Ok(()))
+            }
             Err(code) => {
                 let error = Error {
                     code,
@@ -336,20 +540,41 @@ where
         }
     }
 
-    fn execute(
+    async fn execute(
         &mut self,
         self_: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
         query: wasmtime::component::__internal::String,
         args: wasmtime::component::__internal::Vec<db::DbValue>,
     ) -> wasmtime::Result<std::result::Result<u64, wasmtime::component::Resource<Error>>> {
+        let span = tracing::info_span!("db.execute", db.transaction = true);
+        if let Some(error) = writes_disabled(self.ctx()) {
+            let resource = self.table().push(error)?;
+            return Ok(Err(resource));
+        }
+
+        let db = self
+            .ctx()
+            .transactions
+            .get(&self_.rep())
+            .map(|t| t.db.clone());
+        let info = StatementInfo::parse(&query);
         let transaction: &hayride_host_traits::db::Transaction = self.table().get(&self_)?;
 
         // Convert WIT params to host trait params
         let host_params: Vec<HostDBValue> =
             args.into_iter().map(convert_db_value_to_host).collect();
 
-        match transaction.execute(query, host_params) {
-            Ok(affected_rows) => Ok(Ok(affected_rows)),
+        match transaction
+            .execute(query, host_params)
+            .instrument(span)
+            .await
+        {
+            Ok(affected_rows) => {
+                if let Some(db) = db {
+                    invalidate(self.ctx(), &db, &info, Some(self_.rep()));
+                }
+                Ok(Ok(affected_rows))
+            }
             Err(code) => {
                 let error = Error {
                     code,
@@ -361,7 +586,7 @@ where
         }
     }
 
-    fn query(
+    async fn query(
         &mut self,
         self_: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
         query: wasmtime::component::__internal::String,
@@ -372,13 +597,23 @@ where
             wasmtime::component::Resource<Error>,
         >,
     > {
+        let span = tracing::info_span!("db.query", db.transaction = true);
+        let db = self
+            .ctx()
+            .transactions
+            .get(&self_.rep())
+            .map(|t| t.db.clone());
+        let info = StatementInfo::parse(&query);
         let transaction: &hayride_host_traits::db::Transaction = self.table().get(&self_)?;
         // Convert WIT params to host trait params
         let host_params: Vec<HostDBValue> =
             args.into_iter().map(convert_db_value_to_host).collect();
 
-        match transaction.query(query, host_params) {
+        match transaction.query(query, host_params).instrument(span).await {
             Ok(rows) => {
+                if let Some(db) = db.filter(|_| !info.read) {
+                    invalidate(self.ctx(), &db, &info, Some(self_.rep()));
+                }
                 let resource = self.table().push(rows)?;
                 Ok(Ok(resource))
             }
@@ -393,7 +628,7 @@ where
         }
     }
 
-    fn prepare(
+    async fn prepare(
         &mut self,
         self_: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
         query: wasmtime::component::__internal::String,
@@ -403,10 +638,24 @@ where
             wasmtime::component::Resource<Error>,
         >,
     > {
+        let db = self
+            .ctx()
+            .transactions
+            .get(&self_.rep())
+            .map(|t| t.db.clone());
+        let info = StatementInfo::parse(&query);
         let transaction: &hayride_host_traits::db::Transaction = self.table().get(&self_)?;
-        match transaction.prepare(query) {
+        match transaction.prepare(query).await {
             Ok(statement) => {
                 let resource = self.table().push(statement)?;
+                if let Some(db) = db {
+                    let tracked = TrackedStatement {
+                        db,
+                        info,
+                        transaction: Some(self_.rep()),
+                    };
+                    self.ctx().statements.insert(resource.rep(), tracked);
+                }
                 Ok(Ok(resource))
             }
             Err(code) => {
@@ -424,6 +673,7 @@ where
         &mut self,
         rep: wasmtime::component::Resource<hayride_host_traits::db::Transaction>,
     ) -> wasmtime::Result<()> {
+        self.ctx().transactions.remove(&rep.rep());
         self.table().delete(rep)?;
         Ok(())
     }
@@ -444,12 +694,12 @@ where
         Ok(columns)
     }
 
-    fn next(
+    async fn next(
         &mut self,
         self_: wasmtime::component::Resource<Rows>,
     ) -> wasmtime::Result<std::result::Result<db::Row, wasmtime::component::Resource<Error>>> {
         let rows: &mut Rows = self.table().get_mut(&self_)?;
-        match rows.next() {
+        match rows.next().await {
             Ok(row) => {
                 let wit_row: db::Row = row
                     .0
@@ -469,12 +719,12 @@ where
         }
     }
 
-    fn close(
+    async fn close(
         &mut self,
         self_: wasmtime::component::Resource<Rows>,
     ) -> wasmtime::Result<std::result::Result<(), wasmtime::component::Resource<Error>>> {
         let rows = self.table().get_mut(&self_)?;
-        match rows.close() {
+        match rows.close().await {
             Ok(()) => Ok(Ok(())),
             Err(code) => {
                 let error = Error {
@@ -0,0 +1,155 @@
+use hayride_utils::config::EgressConfig;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A host, optionally restricted to a port, that outbound requests are matched against.
+///
+/// Hosts starting with `*.` match any subdomain, a host of `*` matches every host. Hosts
+/// are compared in the form of [`normalize_host`], so `Example.com.` matches `example.com`
+/// and `[::ffff:127.0.0.1]` matches `127.0.0.1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EgressRule {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl EgressRule {
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+
+        let host = normalize_host(host);
+        match self.host.strip_prefix("*.") {
+            Some(domain) => host.ends_with(&format!(".{}", domain)),
+            None => self.host == "*" || self.host == host,
+        }
+    }
+}
+
+impl FromStr for EgressRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // Ipv6 hosts are bracketed when a port is set, `[::1]:443`
+        let (host, port) = match s.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid egress port: {}", s))?;
+                (host, Some(port))
+            }
+            _ => (s, None),
+        };
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("invalid egress host: {}", s));
+        }
+
+        let host = match host.strip_prefix("*.") {
+            Some(domain) => format!("*.{}", normalize_host(domain)),
+            None => normalize_host(host),
+        };
+        Ok(Self { host, port })
+    }
+}
+
+/// Host in the form rules are matched in: lowercase, without a trailing dot, and ip
+/// literals in their canonical form, so ipv4 mapped ipv6 addresses and ipv4 addresses
+/// written like `0x7f.1` compare as the ipv4 address they stand for.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return ip.to_canonical().to_string();
+    }
+    match url::Host::parse(host) {
+        Ok(url::Host::Ipv4(ip)) => ip.to_string(),
+        Ok(url::Host::Domain(domain)) => domain,
+        _ => host.to_lowercase(),
+    }
+}
+
+/// Outbound http policy for components.
+///
+/// Denied rules take precedence over allowed rules, requests matching neither
+/// are allowed unless the policy denies by default.
+///
+/// Deny lists are not a security boundary on their own: a host can be reached by names
+/// and addresses no rule lists, like another name resolving to the same address. Set
+/// `HAYRIDE_EGRESS_DEFAULT=deny` and allow the hosts components need to restrict egress.
+#[derive(Clone, Debug, Default)]
+pub struct EgressPolicy {
+    allow: Vec<EgressRule>,
+    deny: Vec<EgressRule>,
+    deny_by_default: bool,
+}
+
+impl EgressPolicy {
+    /// Create a policy from `HAYRIDE_EGRESS_ALLOW` and `HAYRIDE_EGRESS_DENY`, comma
+    /// separated `host[:port]` lists, and `HAYRIDE_EGRESS_DEFAULT` set to `deny`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::default().with_env()
+    }
+
+    pub fn from_config(config: &EgressConfig) -> anyhow::Result<Self> {
+        let parse = |rules: &[String]| -> anyhow::Result<Vec<EgressRule>> {
+            rules.iter().map(|rule| rule.parse()).collect()
+        };
+        Ok(Self {
+            allow: parse(&config.allow)?,
+            deny: parse(&config.deny)?,
+            deny_by_default: config.deny_by_default,
+        })
+    }
+
+    /// Override the parts of the policy that are set in the environment.
+    pub fn with_env(mut self) -> anyhow::Result<Self> {
+        if let Ok(allow) = std::env::var("HAYRIDE_EGRESS_ALLOW") {
+            self.allow = parse_rules(&allow)?;
+        }
+        if let Ok(deny) = std::env::var("HAYRIDE_EGRESS_DENY") {
+            self.deny = parse_rules(&deny)?;
+        }
+        if let Ok(default) = std::env::var("HAYRIDE_EGRESS_DEFAULT") {
+            self.deny_by_default = default.eq_ignore_ascii_case("deny");
+        }
+        Ok(self)
+    }
+
+    pub fn allow(mut self, rule: EgressRule) -> Self {
+        self.allow.push(rule);
+        self
+    }
+
+    pub fn deny(mut self, rule: EgressRule) -> Self {
+        self.deny.push(rule);
+        self
+    }
+
+    pub fn deny_by_default(mut self, deny_by_default: bool) -> Self {
+        self.deny_by_default = deny_by_default;
+        self
+    }
+
+    pub fn is_allowed(&self, host: &str, port: u16) -> bool {
+        if self.deny.iter().any(|rule| rule.matches(host, port)) {
+            return false;
+        }
+        if self.allow.iter().any(|rule| rule.matches(host, port)) {
+            return true;
+        }
+        !self.deny_by_default
+    }
+}
+
+fn parse_rules(rules: &str) -> anyhow::Result<Vec<EgressRule>> {
+    rules
+        .split(',')
+        .filter(|rule| !rule.trim().is_empty())
+        .map(EgressRule::from_str)
+        .collect()
+}
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+// Content types negotiated between clients and host servers
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+pub const CBOR_CONTENT_TYPE: &str = "application/cbor";
+
+/// Returns true if the content type header is set to cbor.
+pub fn is_cbor(headers: &HeaderMap) -> bool {
+    has_media_type(headers.get(CONTENT_TYPE), CBOR_CONTENT_TYPE)
+}
+
+/// Returns true if the accept header includes cbor.
+pub fn accepts_cbor(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .any(|value| has_media_type(Some(value), CBOR_CONTENT_TYPE))
+}
+
+/// Decode a cbor encoded request body into json, components only handle json.
+///
+/// Requests with any other content type are passed through unchanged.
+pub async fn decode_request<B>(
+    req: hyper::Request<B>,
+) -> Result<hyper::Request<BoxBody<Bytes, hyper::Error>>>
+where
+    B: hyper::body::Body<Data = Bytes, Error = hyper::Error> + Send + Sync + 'static,
+{
+    if !is_cbor(req.headers()) {
+        return Ok(req.map(|body| body.boxed()));
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let cbor = body.collect().await?.to_bytes();
+    let json = cbor_to_json(&cbor)?;
+
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(JSON_CONTENT_TYPE));
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(json.len()));
+
+    let body = Full::new(Bytes::from(json))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(hyper::Request::from_parts(parts, body))
+}
+
+/// Encode a json response body as cbor.
+///
+/// Responses with any other content type, such as streamed responses, are
+/// passed through unchanged.
+pub async fn encode_response(
+    resp: hyper::Response<HyperOutgoingBody>,
+) -> Result<hyper::Response<HyperOutgoingBody>> {
+    if !has_media_type(resp.headers().get(CONTENT_TYPE), JSON_CONTENT_TYPE) {
+        return Ok(resp);
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    let json = body
+        .collect()
+        .await
+        .map_err(|e| anyhow!("failed to read response body: {:?}", e))?
+        .to_bytes();
+    let cbor = json_to_cbor(&json)?;
+
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(CBOR_CONTENT_TYPE));
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(cbor.len()));
+
+    let body = Full::new(Bytes::from(cbor))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(hyper::Response::from_parts(parts, body))
+}
+
+fn cbor_to_json(cbor: &[u8]) -> Result<Vec<u8>> {
+    let value: serde_json::Value =
+        ciborium::from_reader(cbor).map_err(|e| anyhow!("failed to decode cbor: {}", e))?;
+    Ok(serde_json::to_vec(&value)?)
+}
+
+fn json_to_cbor(json: &[u8]) -> Result<Vec<u8>> {
+    let value: serde_json::Value = serde_json::from_slice(json)?;
+    let mut cbor = Vec::new();
+    ciborium::into_writer(&value, &mut cbor)
+        .map_err(|e| anyhow!("failed to encode cbor: {}", e))?;
+    Ok(cbor)
+}
+
+// Compare the media type of a header value, ignoring parameters such as charset
+fn has_media_type(value: Option<&HeaderValue>, media_type: &str) -> bool {
+    value
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',').any(|part| {
+                part.split(';')
+                    .next()
+                    .is_some_and(|t| t.trim().eq_ignore_ascii_case(media_type))
+            })
+        })
+        .unwrap_or(false)
+}
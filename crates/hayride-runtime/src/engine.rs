@@ -1,29 +1,66 @@
 use super::create_wasi_ctx;
+use crate::access_log::access_log;
+use crate::ai::local::LocalModelOptions;
+use crate::ai::postprocess::{model_profiles, ModelProfile};
 use crate::ai::AiCtx;
 use crate::bindings::hayride_cli::HayrideCliPre;
 use crate::bindings::hayride_server::HayrideServerPre;
 use crate::bindings::hayride_ws::HayrideWsPre;
-use crate::core::CoreCtx;
+use crate::blob::BlobCtx;
+use crate::component_cache::{content_hash, default_cache_dir, CompiledComponents};
+use crate::core::logging::MorphLogLevels;
+use crate::core::settings::{morph_name, Settings};
+use crate::core::{ConfigBackend, CoreCtx, RegistryBackend};
+use crate::db::cache::{query_cache, QueryCacheConfig};
 use crate::db::DBCtx;
+use crate::egress::EgressPolicy;
+use crate::gateway::{Gateway, GatewayOptions};
+use crate::http_client::HttpClient;
+use crate::json_lines::{JsonLinesWriter, CHUNK_SIZE};
+use crate::kv::KvCtx;
+use crate::manifest::{ManifestRecorder, RunManifest, MANIFEST_FILE};
 use crate::mcp::McpCtx;
-use crate::server::Server;
+use crate::middleware::{ApiKeys, ClientAddr, Pipeline};
+use crate::mounts::{default_mounts, Mount, MountPerms};
+use crate::pool::PoolOptions;
+use crate::retention::RetentionPolicy;
+use crate::server::{ConnectionOptions, RouteRule, Router, Server};
+use crate::session_input::StdinMode;
+use crate::session_output::{session_outputs, OutputOptions};
+use crate::silo::limits::ThreadLimits;
 use crate::silo::SiloCtx;
+use crate::socket::SocketCtx;
+use crate::sse::SseCtx;
+use crate::status::SessionState;
+use crate::template::TemplateCtx;
+use crate::timeouts::HostTimeouts;
+use crate::validate::ValidateCtx;
 use crate::wac::WacCtx;
+use crate::watch::{MorphWatcher, WATCH_INTERVAL};
 use crate::websocket::WebsocketServer;
 use crate::Host;
+use hayride_core::registry::RegistryClient;
+use hayride_core::signature::MorphVerifier;
+use hayride_host_traits::ai::nn::OutputFilter;
+use hayride_host_traits::blob::BlobTrait;
 
+use hayride_utils::config::{Config, MiddlewareConfig, TracingConfig};
 use hayride_utils::wit::parser::WitParser;
+use tracing::Instrument;
 
 use wasmtime::component::types::ComponentItem;
 use wasmtime::{
-    component::{Component, ComponentExportIndex, Linker, ResourceTable},
+    component::{Component, ComponentExportIndex, Linker, Resource, ResourceAny, ResourceTable},
     Result,
 };
+use wasmtime_wasi::p2::{DynInputStream, StreamError};
 use wasmtime_wasi_http::io::TokioIo;
 use wasmtime_wasi_http::WasiHttpCtx;
 
 use hyper::server::conn::http1;
+use log::LevelFilter;
 use std::fs::{self, File};
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
 use std::{path::PathBuf, vec};
@@ -31,46 +68,375 @@ use tokio::net::TcpListener;
 use url::Url;
 use uuid::Uuid;
 
+/// WIT types of the params reactor functions can be passed as string args.
+pub const REACTOR_ARG_TYPES: [&str; 6] = ["string", "s32", "s64", "u32", "u64", "bool"];
+
+/// What running a morph produced.
+#[derive(Debug)]
+pub enum RunOutcome {
+    /// A cli morph ran to completion
+    Cli { exit_code: i32 },
+    /// A reactor function returned, with its result if it has one
+    Reactor(Option<ReactorValue>),
+    /// A server morph is listening on `address`, serving in the background until the
+    /// listener fails or the handle is aborted
+    Server {
+        address: SocketAddr,
+        handle: tokio::task::JoinHandle<Result<()>>,
+    },
+}
+
+impl RunOutcome {
+    /// Wait for a server to stop serving, returning the result of a reactor as bytes.
+    pub async fn wait(self) -> Result<Vec<u8>> {
+        match self {
+            RunOutcome::Cli { .. } => Ok(vec![]),
+            RunOutcome::Reactor(value) => Ok(value.map(|v| v.to_bytes()).unwrap_or_default()),
+            RunOutcome::Server { handle, .. } => {
+                handle.await??;
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+/// Result of a reactor function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReactorValue {
+    String(String),
+    S32(i32),
+    S64(i64),
+    U32(u32),
+    U64(u64),
+    Bool(bool),
+    /// Contents of a returned `input-stream`, read to its end
+    Stream(Vec<u8>),
+}
+
+impl ReactorValue {
+    /// The value as text, the contents of a stream are returned as they are.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            ReactorValue::String(s) => s.clone().into_bytes(),
+            ReactorValue::S32(v) => v.to_string().into_bytes(),
+            ReactorValue::S64(v) => v.to_string().into_bytes(),
+            ReactorValue::U32(v) => v.to_string().into_bytes(),
+            ReactorValue::U64(v) => v.to_string().into_bytes(),
+            ReactorValue::Bool(v) => v.to_string().into_bytes(),
+            ReactorValue::Stream(bytes) => bytes.clone(),
+        }
+    }
+}
+
 pub struct EngineBuilder {
-    engine: wasmtime::Engine,
+    // The wasmtime engine with the components it compiled
+    compiled: CompiledComponents,
     // If out_dir is not set, will inherit stdio for wasmtime execution
     out_dir: Option<String>,
     registry_path: String,
     model_path: Option<String>,
     log_level: String,
+    // Levels of the records morphs log, overriding the log level
+    morph_log_levels: MorphLogLevels,
     inherit_stdio: bool,
+    // Log stdout and stderr of components instead of only writing them out
+    log_stdio: bool,
     envs: Vec<(String, String)>,
+    // Additional directories preopened for components
+    mounts: Vec<Mount>,
+    // Permissions on the default preopens, none to disable them
+    default_mounts: Option<MountPerms>,
+    // Deadlines for host calls per capability
+    timeouts: HostTimeouts,
+    // Outbound http policy for components
+    egress: EgressPolicy,
+    http_client: Arc<HttpClient>,
+    // Masks sensitive patterns in inference output
+    output_filter: Option<Arc<OutputFilter>>,
 
     ai_enabled: bool,
     mcp_enabled: bool,
     silo_enabled: bool,
     wac_enabled: bool,
+    template_enabled: bool,
+    validate_enabled: bool,
     wasi_enabled: bool,
     core_enabled: bool,
     db_enabled: bool,
+    kv_enabled: bool,
+    blob_enabled: bool,
+
+    // Serve the status dashboard from host servers
+    status_enabled: bool,
+
+    // Overrides the address server morphs configure
+    server_address: Option<String>,
+    websocket_address: String,
+
+    // Bearer token for the capability admin api of host servers
+    admin_token: Option<String>,
+    // OpenAI compatible api of host servers
+    gateway: Option<GatewayOptions>,
+
+    // Host settings morphs read through hayride:core/config
+    settings: ConfigBackend,
+
+    // Remote registry morphs are pulled from and pushed to
+    registry: Option<RegistryBackend>,
+
+    // Applied to the process wide query cache on build, left unchanged if not set
+    db_cache: Option<QueryCacheConfig>,
+    statement_cache: Option<usize>,
+    #[cfg_attr(not(feature = "hf"), allow(dead_code))]
+    hf_token: Option<String>,
+    local_models: Option<LocalModelOptions>,
+    #[cfg_attr(not(feature = "ollama"), allow(dead_code))]
+    ollama_endpoint: Option<String>,
+    blob_store: Option<Arc<dyn BlobTrait>>,
+    session_output: Option<OutputOptions>,
+    access_log: Option<(PathBuf, OutputOptions)>,
+    retention: Option<RetentionPolicy>,
+    model_profiles: Option<Vec<ModelProfile>>,
+    ai_trace: Option<bool>,
+    tracing: Option<TracingConfig>,
+
+    // Checks morph signatures before they are instantiated
+    verifier: Arc<MorphVerifier>,
+
+    // Also write reactor results as json lines while they are read
+    json_lines: bool,
+    // Stdin waits for input written to the session in file until it is closed
+    interactive_stdin: bool,
+
+    // Directory precompiled components are cached in, none to always compile
+    component_cache: Option<PathBuf>,
+
+    // Reload server morphs when their wasm file changes
+    watch: bool,
+    // Serve http2 next to http1 to clients of server morphs
+    http2: bool,
+    // Server morphs served behind the listener of the main morph
+    routes: Vec<(String, RouteRule)>,
+    // Host side checks and headers around requests to server morphs
+    middleware: Pipeline,
+    // Api keys with the scopes they allow on http servers
+    api_keys: Option<Arc<ApiKeys>>,
+    // Instances of server morphs instantiated ahead of requests
+    pool: Option<PoolOptions>,
+    // Limits on the silo threads running at once
+    thread_limits: Arc<ThreadLimits>,
 }
 
 impl EngineBuilder {
     pub fn new(engine: wasmtime::Engine, registry_path: String) -> Self {
+        Self::shared(CompiledComponents::new(engine), registry_path)
+    }
+
+    /// A builder of an engine sharing the wasmtime engine and compiled components of
+    /// another one.
+    pub fn shared(compiled: CompiledComponents, registry_path: String) -> Self {
         Self {
-            engine,
+            compiled,
             out_dir: None,
             registry_path,
             model_path: None,
             log_level: "info".to_string(),
+            morph_log_levels: MorphLogLevels::default(),
             inherit_stdio: false,
+            log_stdio: false,
             envs: vec![],
+            mounts: vec![],
+            default_mounts: Some(MountPerms::ReadWrite),
+            timeouts: HostTimeouts::default(),
+            egress: EgressPolicy::default(),
+            http_client: Arc::new(HttpClient::default()),
+            output_filter: None,
 
             ai_enabled: false,
             mcp_enabled: false,
             silo_enabled: false,
             wac_enabled: false,
+            template_enabled: false,
+            validate_enabled: false,
             wasi_enabled: true,
             core_enabled: true,
             db_enabled: true,
+            kv_enabled: true,
+            blob_enabled: true,
+
+            status_enabled: false,
+
+            server_address: None,
+            websocket_address: "127.0.0.1:8082".to_string(),
+
+            admin_token: None,
+            gateway: None,
+
+            settings: Settings::default().into(),
+
+            registry: None,
+
+            db_cache: None,
+            statement_cache: None,
+            hf_token: None,
+            local_models: None,
+            ollama_endpoint: None,
+            blob_store: None,
+            session_output: None,
+            access_log: None,
+            retention: None,
+            model_profiles: None,
+            ai_trace: None,
+            tracing: None,
+
+            verifier: Arc::new(MorphVerifier::new()),
+
+            json_lines: false,
+            interactive_stdin: false,
+
+            component_cache: default_cache_dir(),
+
+            watch: false,
+            http2: false,
+            routes: vec![],
+            middleware: Pipeline::from_config(&MiddlewareConfig::default()),
+            api_keys: None,
+            pool: None,
+            thread_limits: Arc::new(ThreadLimits::default()),
         }
     }
 
+    /// Apply the runtime configuration.
+    pub fn config(self, config: &Config) -> Result<Self> {
+        let output_filter = match &config.ai.output_filter {
+            Some(path) => Some(Arc::new(OutputFilter::from_file(path)?)),
+            None => None,
+        };
+
+        let registry = match &config.registry.url {
+            Some(url) => {
+                let local_path = hayride_utils::paths::hayride::default_hayride_dir()?
+                    .join(&config.registry_path);
+                let mut client = RegistryClient::new(url, local_path)
+                    .with_token(config.registry.token.clone())
+                    .with_trusted_keys(&config.registry.trusted_keys)?;
+                if let Some(signing_key) = &config.registry.signing_key {
+                    client = client.with_signing_key(signing_key)?;
+                }
+                Some(client.into())
+            }
+            None => None,
+        };
+
+        // Access logs sit next to the runtime log, rotated by size
+        let access_log = match config.log.access_file.as_str() {
+            "" => None,
+            file => Some((
+                hayride_utils::paths::hayride::default_hayride_dir()?
+                    .join("logs")
+                    .join(file),
+                OutputOptions {
+                    compress: false,
+                    rotate_size: config.log.access_rotate_size,
+                    rotate_keep: config.log.access_rotate_keep,
+                },
+            )),
+        };
+
+        let key_dir =
+            hayride_utils::paths::hayride::default_hayride_dir()?.join(&config.verify.key_dir);
+        let verifier = MorphVerifier::new()
+            .with_trusted_keys(&config.verify.trusted_keys)?
+            .with_trusted_keys(&config.registry.trusted_keys)?
+            .with_key_dir(&key_dir)?
+            .with_enforce(config.verify.enforce);
+
+        Ok(self
+            .registry_path(config.registry_path.clone())
+            .model_path(Some(config.model_path.clone()))
+            .log_level(config.log.level.clone())
+            .morph_log_levels(MorphLogLevels::from_config(&config.log)?)
+            .ai_enabled(config.subsystems.ai)
+            .mcp_enabled(config.subsystems.mcp)
+            .silo_enabled(config.subsystems.silo)
+            .wac_enabled(config.subsystems.wac)
+            .template_enabled(config.subsystems.template)
+            .validate_enabled(config.subsystems.validate)
+            .wasi_enabled(config.subsystems.wasi)
+            .core_enabled(config.subsystems.core)
+            .db_enabled(config.subsystems.db)
+            .kv_enabled(config.subsystems.kv)
+            .blob_enabled(config.subsystems.blob)
+            .status_enabled(config.subsystems.status)
+            .server_address(config.server.address.clone())
+            .websocket_address(config.server.websocket_address.clone())
+            .watch(config.server.watch)
+            .http2(config.server.http2)
+            .routes(
+                config
+                    .server
+                    .routes
+                    .iter()
+                    .map(|route| Ok((route.morph.clone(), RouteRule::from_config(route)?)))
+                    .collect::<Result<_>>()?,
+            )
+            .middleware(Pipeline::from_config(&config.server.middleware))
+            .api_keys(ApiKeys::from_config(&config.server.middleware).map(Arc::new))
+            .pool(PoolOptions::from_config(&config.server.pool))
+            .thread_limits(Arc::new(ThreadLimits::from_config(&config.threads)?))
+            .admin_token(config.admin.token.clone())
+            .gateway(GatewayOptions::from_config(&config.server.gateway)?)
+            .settings(Settings::from_config(config).into())
+            .registry(registry)
+            .db_cache(Some(QueryCacheConfig::from_config(&config.db).with_env()))
+            .statement_cache(Some(config.db.statement_cache))
+            .hf_token(config.ai.hf_token.clone())
+            .local_models(
+                config
+                    .ai
+                    .local_models
+                    .as_ref()
+                    .map(LocalModelOptions::from_config)
+                    .transpose()?,
+            )
+            .ollama_endpoint(
+                config
+                    .ai
+                    .ollama
+                    .as_ref()
+                    .map(|ollama| ollama.endpoint.clone()),
+            )
+            .blob_store(Some(hayride_blob::from_config(&config.blob)?))
+            .verifier(Arc::new(verifier))
+            .json_lines(config.output.json_lines)
+            .log_stdio(config.output.log_stdio)
+            .session_output(Some(OutputOptions::from_config(&config.output)))
+            .access_log(access_log)
+            .retention(Some(RetentionPolicy::from_config(&config.output)))
+            .model_profiles(Some(
+                config
+                    .ai
+                    .profiles
+                    .iter()
+                    .map(ModelProfile::from_config)
+                    .collect::<Result<_>>()?,
+            ))
+            .ai_trace(Some(config.ai.trace))
+            .tracing(Some(config.tracing.clone()))
+            .component_cache(
+                config
+                    .cache
+                    .components
+                    .then(hayride_utils::paths::hayride::default_hayride_dir)
+                    .transpose()?
+                    .map(|dir| dir.join(&config.cache.dir)),
+            )
+            .timeouts(HostTimeouts::from_config(&config.timeouts).with_env())
+            .egress(EgressPolicy::from_config(&config.egress)?.with_env()?)
+            .http_client(Arc::new(HttpClient::from_config(&config.http)?.with_env()?))
+            .output_filter(output_filter))
+    }
+
     pub fn out_dir(mut self, out_dir: Option<String>) -> Self {
         self.out_dir = out_dir;
         self
@@ -91,16 +457,64 @@ impl EngineBuilder {
         self
     }
 
+    /// Levels of the records morphs log through hayride:core/logging, morphs without one
+    /// log at the log level.
+    pub fn morph_log_levels(mut self, morph_log_levels: MorphLogLevels) -> Self {
+        self.morph_log_levels = morph_log_levels;
+        self
+    }
+
     pub fn inherit_stdio(mut self, inherit_stdio: bool) -> Self {
         self.inherit_stdio = inherit_stdio;
         self
     }
 
+    /// Log stdout and stderr of components through the host logger, a line at a time and
+    /// tagged with the session and morph. Output is still written to session files, but
+    /// no longer inherited.
+    pub fn log_stdio(mut self, log_stdio: bool) -> Self {
+        self.log_stdio = log_stdio;
+        self
+    }
+
     pub fn envs(mut self, envs: Vec<(String, String)>) -> Self {
         self.envs = envs;
         self
     }
 
+    /// Grant components access to host directories, as (host path, guest path, permissions).
+    pub fn mounts(mut self, mounts: Vec<(String, String, MountPerms)>) -> Self {
+        self.mounts = mounts.into_iter().map(Mount::from).collect();
+        self
+    }
+
+    /// Set the permissions on the working directory and hayride dir preopened for
+    /// components, or `None` to only preopen the configured mounts.
+    pub fn default_mounts(mut self, perms: Option<MountPerms>) -> Self {
+        self.default_mounts = perms;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: HostTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn egress(mut self, egress: EgressPolicy) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    pub fn http_client(mut self, http_client: Arc<HttpClient>) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn output_filter(mut self, output_filter: Option<Arc<OutputFilter>>) -> Self {
+        self.output_filter = output_filter;
+        self
+    }
+
     pub fn ai_enabled(mut self, ai_enabled: bool) -> Self {
         self.ai_enabled = ai_enabled;
         self
@@ -121,6 +535,16 @@ impl EngineBuilder {
         self
     }
 
+    pub fn template_enabled(mut self, template_enabled: bool) -> Self {
+        self.template_enabled = template_enabled;
+        self
+    }
+
+    pub fn validate_enabled(mut self, validate_enabled: bool) -> Self {
+        self.validate_enabled = validate_enabled;
+        self
+    }
+
     pub fn wasi_enabled(mut self, wasi_enabled: bool) -> Self {
         self.wasi_enabled = wasi_enabled;
         self
@@ -136,6 +560,186 @@ impl EngineBuilder {
         self
     }
 
+    pub fn kv_enabled(mut self, kv_enabled: bool) -> Self {
+        self.kv_enabled = kv_enabled;
+        self
+    }
+
+    pub fn blob_enabled(mut self, blob_enabled: bool) -> Self {
+        self.blob_enabled = blob_enabled;
+        self
+    }
+
+    pub fn server_address(mut self, server_address: Option<String>) -> Self {
+        self.server_address = server_address;
+        self
+    }
+
+    pub fn websocket_address(mut self, websocket_address: String) -> Self {
+        self.websocket_address = websocket_address;
+        self
+    }
+
+    pub fn admin_token(mut self, admin_token: Option<String>) -> Self {
+        // An empty token would authorize any request
+        self.admin_token = admin_token.filter(|token| !token.is_empty());
+        self
+    }
+
+    /// Serve the OpenAI compatible api from host servers.
+    pub fn gateway(mut self, gateway: Option<GatewayOptions>) -> Self {
+        self.gateway = gateway;
+        self
+    }
+
+    pub fn settings(mut self, settings: ConfigBackend) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn registry(mut self, registry: Option<RegistryBackend>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn verifier(mut self, verifier: Arc<MorphVerifier>) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    pub fn watch(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+
+    pub fn http2(mut self, http2: bool) -> Self {
+        self.http2 = http2;
+        self
+    }
+
+    /// Serve other server morphs, by name, behind the listener of the main morph.
+    pub fn routes(mut self, routes: Vec<(String, RouteRule)>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Middleware requests to server morphs pass through before reaching them.
+    pub fn middleware(mut self, middleware: Pipeline) -> Self {
+        self.middleware = middleware;
+        self
+    }
+
+    /// Require api keys on http servers, checked before the status page, the gateway
+    /// and server morphs are served.
+    pub fn api_keys(mut self, api_keys: Option<Arc<ApiKeys>>) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    /// Keep instances of server morphs ready for requests.
+    pub fn pool(mut self, pool: Option<PoolOptions>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Limit the silo threads running at once, threads spawned past a limit wait for
+    /// others to finish.
+    pub fn thread_limits(mut self, thread_limits: Arc<ThreadLimits>) -> Self {
+        self.thread_limits = thread_limits;
+        self
+    }
+
+    pub fn component_cache(mut self, component_cache: Option<PathBuf>) -> Self {
+        self.component_cache = component_cache;
+        self
+    }
+
+    pub fn json_lines(mut self, json_lines: bool) -> Self {
+        self.json_lines = json_lines;
+        self
+    }
+
+    pub fn interactive_stdin(mut self, interactive_stdin: bool) -> Self {
+        self.interactive_stdin = interactive_stdin;
+        self
+    }
+
+    pub fn db_cache(mut self, db_cache: Option<QueryCacheConfig>) -> Self {
+        self.db_cache = db_cache;
+        self
+    }
+
+    /// Statements kept prepared per db connection, applied process wide.
+    pub fn statement_cache(mut self, statement_cache: Option<usize>) -> Self {
+        self.statement_cache = statement_cache;
+        self
+    }
+
+    /// Token for the Hugging Face hub, applied process wide.
+    pub fn hf_token(mut self, hf_token: Option<String>) -> Self {
+        self.hf_token = hf_token;
+        self
+    }
+
+    /// Local directories models are served from instead of the hub, applied process wide.
+    pub fn local_models(mut self, local_models: Option<LocalModelOptions>) -> Self {
+        self.local_models = local_models;
+        self
+    }
+
+    /// Ollama instance models are served from instead of the hub, applied process wide.
+    pub fn ollama_endpoint(mut self, ollama_endpoint: Option<String>) -> Self {
+        self.ollama_endpoint = ollama_endpoint;
+        self
+    }
+
+    /// Store of `hayride:blob` blobs, applied process wide.
+    pub fn blob_store(mut self, blob_store: Option<Arc<dyn BlobTrait>>) -> Self {
+        self.blob_store = blob_store;
+        self
+    }
+
+    /// Compression and rotation of session out and err files, applied process wide.
+    pub fn session_output(mut self, session_output: Option<OutputOptions>) -> Self {
+        self.session_output = session_output;
+        self
+    }
+
+    /// File requests to host servers are logged to, with its rotation, applied process wide.
+    pub fn access_log(mut self, access_log: Option<(PathBuf, OutputOptions)>) -> Self {
+        self.access_log = access_log;
+        self
+    }
+
+    /// Post-processing of compute output by model, applied process wide.
+    pub fn model_profiles(mut self, model_profiles: Option<Vec<ModelProfile>>) -> Self {
+        self.model_profiles = model_profiles;
+        self
+    }
+
+    /// Trace compute calls to the sessions of the output directory, applied process wide.
+    pub fn ai_trace(mut self, ai_trace: Option<bool>) -> Self {
+        self.ai_trace = ai_trace;
+        self
+    }
+
+    /// How long sessions are kept in the output directory, cleaned up process wide.
+    pub fn retention(mut self, retention: Option<RetentionPolicy>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Collector spans are exported to, installed process wide.
+    pub fn tracing(mut self, tracing: Option<TracingConfig>) -> Self {
+        self.tracing = tracing;
+        self
+    }
+
+    pub fn status_enabled(mut self, status_enabled: bool) -> Self {
+        self.status_enabled = status_enabled;
+        self
+    }
+
     pub fn build(self) -> Result<WasmtimeEngine> {
         let id = Uuid::new_v4();
 
@@ -157,22 +761,99 @@ impl EngineBuilder {
             }
         }
 
+        if let Some(db_cache) = self.db_cache {
+            query_cache().configure(db_cache);
+        }
+        if let Some(capacity) = self.statement_cache {
+            hayride_db::statement_cache::statement_cache().set_capacity(capacity);
+        }
+        #[cfg(feature = "hf")]
+        if let Some(token) = self.hf_token {
+            hayride_hf::set_token(Some(token));
+        }
+        if let Some(local_models) = self.local_models {
+            crate::ai::local::configure(Some(local_models));
+        }
+        #[cfg(feature = "ollama")]
+        if let Some(endpoint) = self.ollama_endpoint {
+            hayride_ollama::configure(Some(endpoint));
+        }
+        if let Some(blob_store) = self.blob_store {
+            hayride_blob::configure(blob_store);
+        }
+        if let Some(session_output) = self.session_output {
+            session_outputs().configure(session_output);
+        }
+        if let Some((path, options)) = &self.access_log {
+            access_log().configure(path, *options)?;
+        }
+        if let Some(profiles) = self.model_profiles {
+            model_profiles().configure(profiles);
+        }
+        if let Some(ai_trace) = self.ai_trace {
+            crate::ai::trace::configure(ai_trace);
+        }
+        if let (Some(retention), Some(out_dir)) = (self.retention, &self.out_dir) {
+            crate::retention::start(out_dir, retention);
+        }
+        if let Some(tracing) = &self.tracing {
+            crate::telemetry::init(tracing)?;
+        }
+
+        let mut mounts = match self.default_mounts {
+            Some(perms) => default_mounts(perms)?,
+            None => vec![],
+        };
+        mounts.extend(self.mounts);
+
         Ok(WasmtimeEngine {
             id: id,
-            engine: self.engine,
+            engine: self.compiled.engine().clone(),
+            compiled: self.compiled,
             out_dir: self.out_dir,
             registry_path: self.registry_path,
             model_path: self.model_path,
             log_level: self.log_level,
+            morph_log_levels: self.morph_log_levels,
             inherit_stdio: self.inherit_stdio,
+            log_stdio: self.log_stdio,
             envs: self.envs,
+            mounts,
+            timeouts: self.timeouts,
+            egress: Arc::new(self.egress),
+            http_client: self.http_client,
+            output_filter: self.output_filter,
             ai_enabled: self.ai_enabled,
             mcp_enabled: self.mcp_enabled,
             silo_enabled: self.silo_enabled,
             wac_enabled: self.wac_enabled,
+            template_enabled: self.template_enabled,
+            validate_enabled: self.validate_enabled,
             wasi_enabled: self.wasi_enabled,
             core_enabled: self.core_enabled,
             db_enabled: self.db_enabled,
+            kv_enabled: self.kv_enabled,
+            blob_enabled: self.blob_enabled,
+            status_enabled: self.status_enabled,
+            server_address: self.server_address,
+            websocket_address: self.websocket_address,
+            admin_token: self.admin_token,
+            gateway: self.gateway,
+            settings: self.settings,
+            registry: self.registry,
+            verifier: self.verifier,
+            json_lines: self.json_lines,
+            interactive_stdin: self.interactive_stdin,
+            component_cache: self.component_cache,
+            watch: self.watch,
+            http2: self.http2,
+            routes: self.routes,
+            middleware: self.middleware,
+            api_keys: self.api_keys,
+            pool: self.pool,
+            thread_limits: self.thread_limits,
+            seed: None,
+            manifest: None,
         })
     }
 }
@@ -185,17 +866,55 @@ pub struct WasmtimeEngine {
     registry_path: String,
     model_path: Option<String>,
     log_level: String,
+    morph_log_levels: MorphLogLevels,
 
     inherit_stdio: bool,
+    log_stdio: bool,
     envs: Vec<(String, String)>,
+    // Additional directories preopened for components
+    mounts: Vec<Mount>,
+    timeouts: HostTimeouts,
+    egress: Arc<EgressPolicy>,
+    http_client: Arc<HttpClient>,
+    output_filter: Option<Arc<OutputFilter>>,
 
     ai_enabled: bool,
     mcp_enabled: bool,
     silo_enabled: bool,
     wac_enabled: bool,
+    template_enabled: bool,
+    validate_enabled: bool,
     wasi_enabled: bool,
     core_enabled: bool,
     db_enabled: bool,
+    kv_enabled: bool,
+    blob_enabled: bool,
+
+    // Serve the status dashboard from host servers
+    status_enabled: bool,
+
+    server_address: Option<String>,
+    websocket_address: String,
+    admin_token: Option<String>,
+    gateway: Option<GatewayOptions>,
+    settings: ConfigBackend,
+    registry: Option<RegistryBackend>,
+    verifier: Arc<MorphVerifier>,
+    json_lines: bool,
+    interactive_stdin: bool,
+    component_cache: Option<PathBuf>,
+    compiled: CompiledComponents,
+    watch: bool,
+    http2: bool,
+    routes: Vec<(String, RouteRule)>,
+    middleware: Pipeline,
+    api_keys: Option<Arc<ApiKeys>>,
+    pool: Option<PoolOptions>,
+    thread_limits: Arc<ThreadLimits>,
+    // Sampler seed of a replayed session, a new one is drawn otherwise
+    seed: Option<u32>,
+    // Manifest of the running session
+    manifest: Option<ManifestRecorder>,
 }
 
 #[derive(Debug)]
@@ -207,32 +926,116 @@ enum ComponentType {
 }
 
 impl WasmtimeEngine {
+    /// The remote registry missing morphs are pulled from, if configured.
+    pub fn registry(&self) -> Option<&RegistryBackend> {
+        self.registry.as_ref()
+    }
+
+    /// The silo context of the session, threads spawned with it run under the settings
+    /// of the engine.
+    pub fn silo_ctx(&self) -> SiloCtx {
+        SiloCtx::new(
+            self.out_dir.clone(),
+            self.registry_path.clone(),
+            self.model_path.clone(),
+            self.mounts.clone(),
+            self.egress.clone(),
+            self.http_client.clone(),
+            self.output_filter.clone(),
+            self.settings.clone(),
+            self.registry.clone(),
+            self.verifier.clone(),
+            self.json_lines,
+            self.log_stdio,
+            self.component_cache.clone(),
+        )
+        .with_session_id(self.id)
+        .with_log_levels(self.log_level.clone(), self.morph_log_levels.clone())
+        .with_thread_limits(self.thread_limits.clone())
+        .with_compiled(self.compiled.clone())
+    }
+
+    /// An ai context under the settings of the engine, for host code computing without
+    /// a morph.
+    pub fn ai_ctx(&self) -> Result<AiCtx> {
+        Ok(AiCtx::new(
+            self.id.to_string(),
+            self.out_dir.clone(),
+            self.model_path.clone(),
+        )?
+        .with_timeouts(self.timeouts)
+        .with_output_filter(self.output_filter.clone())
+        .with_capabilities(crate::capabilities::capabilities().policy()))
+    }
+
+    // Level of the records the morph logs, the log level unless one is set for it
+    fn morph_log_level(&self, morph: &str) -> LevelFilter {
+        self.morph_log_levels
+            .get(morph)
+            .unwrap_or_else(|| self.log_level.parse().unwrap_or(LevelFilter::Info))
+    }
+
     fn create_store(
         &self,
         args: &[impl AsRef<str> + std::marker::Sync],
         silo_ctx: SiloCtx,
         core_ctx: CoreCtx,
-        mut stdin: bool,
+        stdin: bool,
     ) -> wasmtime::Result<wasmtime::Store<Host>> {
         let mut outdir = self.out_dir.clone();
+        let mut stdin = match (stdin, self.interactive_stdin) {
+            (false, _) => StdinMode::Inherit,
+            (true, false) => StdinMode::File,
+            (true, true) => StdinMode::Interactive,
+        };
         if self.inherit_stdio {
             // If inheriting stdio, don't create out dir or stdin files
-            stdin = false;
+            stdin = StdinMode::Inherit;
             outdir = None;
         }
 
-        let wasi_ctx = create_wasi_ctx(args, outdir, self.id, stdin, &self.envs)?;
+        let log_stdio = self.log_stdio.then_some(core_ctx.morph.as_str());
+        let wasi_ctx = create_wasi_ctx(
+            args,
+            outdir,
+            self.id,
+            stdin,
+            &self.envs,
+            &self.mounts,
+            log_stdio,
+        )?;
+        // New stores pick up the current capability policy
+        let capabilities = crate::capabilities::capabilities().policy();
         let store = wasmtime::Store::new(
             &self.engine,
             Host {
                 ctx: wasi_ctx,
                 http_ctx: WasiHttpCtx::new(),
+                egress: self.egress.clone(),
+                http_client: self.http_client.clone(),
+                capabilities,
                 core_ctx: core_ctx.clone(),
-                ai_ctx: AiCtx::new(self.out_dir.clone(), self.model_path.clone())?,
+                ai_ctx: AiCtx::new(
+                    self.id.to_string(),
+                    self.out_dir.clone(),
+                    self.model_path.clone(),
+                )?
+                .with_timeouts(self.timeouts)
+                .with_output_filter(self.output_filter.clone())
+                .with_manifest(self.manifest.clone())
+                .with_capabilities(capabilities),
                 mcp_ctx: McpCtx::new(),
-                silo_ctx: silo_ctx.clone(),
-                wac_ctx: WacCtx::new(self.registry_path.clone()),
-                db_ctx: DBCtx::new(),
+                silo_ctx: silo_ctx.clone().with_capabilities(capabilities),
+                wac_ctx: WacCtx::new(self.registry_path.clone()).with_timeouts(self.timeouts),
+                template_ctx: TemplateCtx::new(self.registry_path.clone()),
+                validate_ctx: ValidateCtx::new(),
+                db_ctx: DBCtx::new()
+                    .with_timeouts(self.timeouts)
+                    .with_capabilities(capabilities),
+                kv_ctx: KvCtx::new(&core_ctx.morph),
+                blob_ctx: BlobCtx::new(),
+                socket_ctx: SocketCtx::new(),
+                sse_ctx: SseCtx::new(),
                 table: ResourceTable::default(),
             },
         );
@@ -251,8 +1054,14 @@ impl WasmtimeEngine {
         let mut mcp: bool = false;
         let mut silo: bool = false;
         let mut wac: bool = false;
+        let mut template: bool = false;
+        let mut validate: bool = false;
         let mut core: bool = false;
         let mut db: bool = false;
+        let mut kv: bool = false;
+        let mut blob: bool = false;
+        let mut socket: bool = false;
+        let mut http: bool = false;
         wit.imports().iter().for_each(|i| {
             match i.name.namespace.as_str() {
                 "hayride" => match i.name.name.as_str() {
@@ -260,8 +1069,14 @@ impl WasmtimeEngine {
                     "ai" => ai = true,
                     "mcp" => mcp = true,
                     "wac" => wac = true,
+                    "template" => template = true,
+                    "validate" => validate = true,
                     "core" => core = true,
                     "db" => db = true,
+                    "kv" => kv = true,
+                    "blob" => blob = true,
+                    "socket" => socket = true,
+                    "http" => http = true,
                     _ => {
                         log::debug!("unknown import Found: {}", i.name.name);
                     }
@@ -284,6 +1099,8 @@ impl WasmtimeEngine {
         log::debug!("ai import enabled: {:?}", ai);
         log::debug!("silo import enabled: {:?}", silo);
         log::debug!("wac import enabled: {:?}", wac);
+        log::debug!("template import enabled: {:?}", template);
+        log::debug!("validate import enabled: {:?}", validate);
         log::debug!("core import enabled: {:?}", core);
 
         if wasi {
@@ -301,7 +1118,7 @@ impl WasmtimeEngine {
                 return Err(anyhow::anyhow!("AI is not enabled").into());
             }
 
-            crate::ai::add_to_linker_sync(&mut linker)?;
+            crate::ai::add_to_linker_async(&mut linker)?;
         }
 
         if mcp {
@@ -313,11 +1130,14 @@ impl WasmtimeEngine {
         }
 
         if silo {
-            if !self.silo_enabled {
-                return Err(anyhow::anyhow!("Silo is not enabled").into());
+            if self.silo_enabled {
+                crate::silo::add_to_linker_async(&mut linker)?;
+            } else {
+                // Spawned threads may still publish artifacts to their parent, imports
+                // of the other silo interfaces fail to link
+                log::debug!("silo is not enabled, only linking artifacts");
+                crate::silo::add_artifacts_to_linker(&mut linker)?;
             }
-
-            crate::silo::add_to_linker_sync(&mut linker)?;
         }
 
         if wac {
@@ -325,7 +1145,23 @@ impl WasmtimeEngine {
                 return Err(anyhow::anyhow!("WAC is not enabled").into());
             }
 
-            crate::wac::add_to_linker_sync(&mut linker)?;
+            crate::wac::add_to_linker_async(&mut linker)?;
+        }
+
+        if template {
+            if !self.template_enabled {
+                return Err(anyhow::anyhow!("Template is not enabled").into());
+            }
+
+            crate::template::add_to_linker_sync(&mut linker)?;
+        }
+
+        if validate {
+            if !self.validate_enabled {
+                return Err(anyhow::anyhow!("Validate is not enabled").into());
+            }
+
+            crate::validate::add_to_linker_sync(&mut linker)?;
         }
 
         if core {
@@ -333,7 +1169,7 @@ impl WasmtimeEngine {
                 return Err(anyhow::anyhow!("Core is not enabled").into());
             }
 
-            crate::core::add_to_linker_sync(&mut linker)?;
+            crate::core::add_to_linker_async(&mut linker)?;
         }
 
         if db {
@@ -341,23 +1177,175 @@ impl WasmtimeEngine {
                 return Err(anyhow::anyhow!("DB is not enabled").into());
             }
 
-            crate::db::add_to_linker_sync(&mut linker)?;
+            crate::db::add_to_linker_async(&mut linker)?;
+        }
+
+        if kv {
+            if !self.kv_enabled {
+                return Err(anyhow::anyhow!("KV is not enabled").into());
+            }
+
+            crate::kv::add_to_linker_sync(&mut linker)?;
+        }
+
+        if blob {
+            if !self.blob_enabled {
+                return Err(anyhow::anyhow!("Blob is not enabled").into());
+            }
+
+            crate::blob::add_to_linker_sync(&mut linker)?;
+        }
+
+        // Requests of multiplexed websocket connections, other stores have none
+        if socket {
+            crate::socket::add_to_linker_sync(&mut linker)?;
+        }
+
+        // Event stream responses, only stores handling http requests can open one
+        if http {
+            crate::sse::add_to_linker_async(&mut linker)?;
         }
 
         return Ok(linker);
     }
 
+    // Find a morph in the registry, pulling it from the remote registry if missing
+    async fn find_morph(&self, morph: &str) -> Result<PathBuf> {
+        let registry_path =
+            hayride_utils::paths::hayride::default_hayride_dir()?.join(&self.registry_path);
+        crate::core::registry::find_or_pull(
+            self.registry.as_ref(),
+            registry_path.to_string_lossy().to_string(),
+            morph,
+        )
+        .await
+    }
+
+    // Compile and link a server morph, for routes and swapping into a running server
+    fn load_server(&self, wasm_file: &Path) -> Result<HayrideServerPre<Host>> {
+        let bytes: Vec<u8> = std::fs::read(wasm_file)?;
+        self.verifier.check(wasm_file, &bytes)?;
+        let component = self
+            .compiled
+            .load(&bytes, self.component_cache.as_deref())?;
+
+        // The morph may import capabilities it did not before
+        let linker = self.link_imports(WitParser::new(bytes)?)?;
+        HayrideServerPre::new(linker.instantiate_pre(&component)?)
+    }
+
     pub async fn run(
         self,
         wasm_file: PathBuf,
         function: String,
         args: &[impl AsRef<str> + std::marker::Sync],
-    ) -> Result<Vec<u8>> {
-        // Set initial logger based on builder
-        hayride_utils::log::init_logger(self.log_level.clone())?;
+    ) -> Result<RunOutcome> {
+        let id = self.id.to_string();
+        let morph = wasm_file
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        crate::status::status().session_started(id.clone(), morph.clone());
+
+        let span = tracing::info_span!(
+            "engine.run",
+            session.id = %id,
+            morph = %morph,
+            function = %function,
+        );
+        let result = self
+            .run_component(wasm_file, function, args)
+            .instrument(span)
+            .await;
+        let state = match result {
+            // Servers finish their session once they stop serving
+            Ok(RunOutcome::Server { .. }) => return result,
+            Ok(_) => SessionState::Exited,
+            Err(_) => SessionState::Failed,
+        };
+        crate::status::status().session_finished(&id, state);
+
+        result
+    }
 
-        let bytes: Vec<u8> = std::fs::read(wasm_file)?;
-        let component: Component = Component::from_binary(&self.engine, &bytes)?;
+    /// Run a session again with the parameters in its manifest, failing if the
+    /// component of the morph changed since.
+    pub async fn replay(mut self, session_id: &str) -> Result<RunOutcome> {
+        let out_dir = self
+            .out_dir
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("replaying a session needs the sessions dir"))?;
+        let session_id = Uuid::parse_str(session_id)?.to_string();
+        let manifest = RunManifest::read(&Path::new(out_dir).join(&session_id))?;
+
+        let bytes = std::fs::read(&manifest.morph)?;
+        if content_hash(&bytes) != manifest.component_hash {
+            return Err(anyhow::anyhow!(
+                "{} changed since session {} ran",
+                manifest.morph.display(),
+                session_id
+            ));
+        }
+        log::info!("replaying session {} as {}", session_id, self.id);
+
+        // Values of envs are not recorded, the replay runs with the ones configured now
+        for name in &manifest.envs {
+            if !self.envs.iter().any(|(key, _)| key == name) {
+                log::warn!(
+                    "env {} of session {} is not set for the replay",
+                    name,
+                    session_id
+                );
+            }
+        }
+        self.seed = Some(manifest.seed);
+        self.run(manifest.morph, manifest.function, &manifest.args)
+            .await
+    }
+
+    async fn run_component(
+        mut self,
+        wasm_file: PathBuf,
+        function: String,
+        args: &[impl AsRef<str> + std::marker::Sync],
+    ) -> Result<RunOutcome> {
+        // Set initial logger based on builder
+        // Records of morphs are logged at the most verbose level set for one
+        let targets: Vec<(&str, LevelFilter)> = self
+            .morph_log_levels
+            .max()
+            .map(|level| (crate::core::logging::LOG_TARGET, level))
+            .into_iter()
+            .collect();
+        hayride_utils::log::init_logger_with_targets(self.log_level.clone(), &targets)?;
+
+        let morph = morph_name(&wasm_file);
+        let bytes: Vec<u8> = std::fs::read(&wasm_file)?;
+        self.verifier.check(&wasm_file, &bytes)?;
+        let component: Component = self
+            .compiled
+            .load(&bytes, self.component_cache.as_deref())?;
+
+        // Write the manifest of the session, so it can be replayed
+        let manifest = RunManifest {
+            id: self.id.to_string(),
+            morph: wasm_file.clone(),
+            component_hash: content_hash(&bytes),
+            function: function.clone(),
+            args: args.iter().map(|arg| arg.as_ref().to_string()).collect(),
+            envs: self.envs.iter().map(|(key, _)| key.clone()).collect(),
+            // A seed of 0 leaves sampling random
+            seed: self
+                .seed
+                .unwrap_or_else(|| (Uuid::new_v4().as_u128() as u32).max(1)),
+            ..Default::default()
+        };
+        let path = self.out_dir.as_ref().map(|out_dir| {
+            Path::new(out_dir)
+                .join(self.id.to_string())
+                .join(MANIFEST_FILE)
+        });
+        self.manifest = Some(ManifestRecorder::new(manifest, path));
 
         // Use wit_component to decode into a wit definition
         let wit_parsed = WitParser::new(bytes)?;
@@ -382,13 +1370,13 @@ impl WasmtimeEngine {
             }
         });
 
-        let silo_ctx = SiloCtx::new(
-            self.out_dir.clone(),
-            self.registry_path.clone(),
-            self.model_path.clone(),
-        );
+        let silo_ctx = self.silo_ctx();
 
-        let core_ctx = CoreCtx::new();
+        let core_ctx = CoreCtx::new()
+            .with_config(self.settings.clone())
+            .with_log_level(self.morph_log_level(&morph))
+            .with_morph(morph)
+            .with_registry(self.registry.clone());
 
         // Handle component based on its type
         match component_type {
@@ -400,11 +1388,26 @@ impl WasmtimeEngine {
                     HayrideCliPre::new(linker.instantiate_pre(&component)?)?;
                 let instance = pre.instantiate_async(&mut store).await?;
 
-                // Execute the cli run function
-                let result = instance.wasi_cli_run().call_run(&mut store).await?;
-                log::info!("runtime executed: {result:?}");
+                // Execute the cli run function, `exit` with a code traps with it
+                let exit_code = match instance.wasi_cli_run().call_run(&mut store).await {
+                    Ok(result) => {
+                        log::info!("runtime executed: {result:?}");
+                        if result.is_ok() {
+                            0
+                        } else {
+                            1
+                        }
+                    }
+                    Err(e) => match e.downcast_ref::<wasmtime_wasi::I32Exit>() {
+                        Some(exit) => {
+                            log::info!("runtime exited with code {}", exit.0);
+                            exit.0
+                        }
+                        None => return Err(e),
+                    },
+                };
 
-                return Ok(vec![]);
+                return Ok(RunOutcome::Cli { exit_code });
             }
             ComponentType::Reactor => {
                 let mut store = self.create_store(args, silo_ctx.clone(), core_ctx, true)?;
@@ -497,6 +1500,10 @@ impl WasmtimeEngine {
                                 wasmtime::component::Type::Bool => {
                                     results.push(wasmtime::component::Val::Bool(false));
                                 }
+                                // Placeholder for a returned stream, overwritten by the call
+                                wasmtime::component::Type::Own(_) => {
+                                    results.push(wasmtime::component::Val::Bool(false));
+                                }
                                 _ => {
                                     return Err(anyhow::Error::msg("Unknown Result Type"));
                                 }
@@ -511,39 +1518,62 @@ impl WasmtimeEngine {
                             results
                         );
 
-                        // Return the results as Vec<u8>
-                        for f in results {
-                            match f {
-                                wasmtime::component::Val::String(s) => {
-                                    return Ok(s.into_bytes());
-                                }
-                                wasmtime::component::Val::S32(result) => {
-                                    return Ok(result.to_string().into_bytes());
-                                }
-                                wasmtime::component::Val::S64(result) => {
-                                    return Ok(result.to_string().into_bytes());
-                                }
-                                wasmtime::component::Val::U32(result) => {
-                                    return Ok(result.to_string().into_bytes());
-                                }
-                                wasmtime::component::Val::U64(result) => {
-                                    return Ok(result.to_string().into_bytes());
-                                }
-                                wasmtime::component::Val::Bool(result) => {
-                                    return Ok(result.to_string().into_bytes());
-                                }
-                                _ => {
-                                    return Err(anyhow::Error::msg("Unknown Result Type"));
-                                }
+                        // Results are also written as json lines when enabled
+                        let writer = match self.json_lines {
+                            true if !results.is_empty() => {
+                                let out_dir = match self.inherit_stdio {
+                                    true => None,
+                                    false => self.out_dir.as_deref(),
+                                };
+                                Some(JsonLinesWriter::new(out_dir, &self.id)?)
+                            }
+                            _ => None,
+                        };
+
+                        // Return the first result
+                        let value = match results.into_iter().next() {
+                            None => None,
+                            Some(wasmtime::component::Val::String(s)) => {
+                                Some(ReactorValue::String(s))
+                            }
+                            Some(wasmtime::component::Val::S32(result)) => {
+                                Some(ReactorValue::S32(result))
+                            }
+                            Some(wasmtime::component::Val::S64(result)) => {
+                                Some(ReactorValue::S64(result))
+                            }
+                            Some(wasmtime::component::Val::U32(result)) => {
+                                Some(ReactorValue::U32(result))
                             }
+                            Some(wasmtime::component::Val::U64(result)) => {
+                                Some(ReactorValue::U64(result))
+                            }
+                            Some(wasmtime::component::Val::Bool(result)) => {
+                                Some(ReactorValue::Bool(result))
+                            }
+                            Some(wasmtime::component::Val::Resource(stream)) => {
+                                let result = read_result_stream(&mut store, stream, writer).await?;
+                                return Ok(RunOutcome::Reactor(Some(ReactorValue::Stream(result))));
+                            }
+                            Some(_) => {
+                                return Err(anyhow::Error::msg("Unknown Result Type"));
+                            }
+                        };
+
+                        if let (Some(value), Some(mut writer)) = (&value, writer) {
+                            for chunk in value.to_bytes().chunks(CHUNK_SIZE) {
+                                writer.chunk(chunk)?;
+                            }
+                            writer.done()?;
                         }
+                        return Ok(RunOutcome::Reactor(value));
                     }
                     None => {
                         log::warn!("no function found for export index {:?}", func_index);
                     }
                 }
 
-                return Ok(vec![]);
+                return Ok(RunOutcome::Reactor(None));
             }
             ComponentType::Server => {
                 // For server, instantiate as server and start listening using component to handle requests
@@ -565,11 +1595,14 @@ impl WasmtimeEngine {
                     }
                 };
 
+                // A configured address takes precedence over the morph's own
+                let address = self.server_address.as_ref().unwrap_or(&config.address);
+
                 // Ensure the input has a scheme
-                let address_with_scheme = if config.address.contains("://") {
-                    config.address.clone()
+                let address_with_scheme = if address.contains("://") {
+                    address.clone()
                 } else {
-                    format!("http://{}", config.address)
+                    format!("http://{}", address)
                 };
 
                 let url = Url::parse(&address_with_scheme)
@@ -582,101 +1615,258 @@ impl WasmtimeEngine {
 
                 log::debug!("starting server with address: {}", address);
 
+                // One gateway serves every route, sharing the loaded models
+                let gateway = match &self.gateway {
+                    Some(options) => Some(Arc::new(Gateway::new(
+                        self.id,
+                        self.out_dir.clone(),
+                        self.model_path.clone(),
+                        options.clone(),
+                        self.timeouts,
+                        self.output_filter.clone(),
+                    )?)),
+                    None => None,
+                };
+
                 // Prepare our server state and start listening for connections.
-                let server = Arc::new(Server::new(
-                    self.id,
-                    self.out_dir.clone(),
-                    pre,
-                    silo_ctx,
-                    core_ctx,
-                    self.registry_path.clone(),
-                    self.model_path.clone(),
-                    args.iter().map(|s| s.as_ref().to_string()).collect(),
-                    self.envs.clone(),
-                ));
+                let new_server = |pre: HayrideServerPre<Host>, core_ctx: CoreCtx| {
+                    let server = Arc::new(
+                        Server::new(
+                            self.id,
+                            self.out_dir.clone(),
+                            pre,
+                            silo_ctx.clone(),
+                            core_ctx,
+                            self.registry_path.clone(),
+                            self.model_path.clone(),
+                            args.iter().map(|s| s.as_ref().to_string()).collect(),
+                            self.envs.clone(),
+                            self.mounts.clone(),
+                            self.timeouts,
+                            self.egress.clone(),
+                            self.http_client.clone(),
+                            self.output_filter.clone(),
+                            self.status_enabled,
+                            self.admin_token.clone(),
+                        )
+                        .with_middleware(self.middleware.clone())
+                        .with_gateway(gateway.clone())
+                        .with_api_keys(self.api_keys.clone())
+                        .with_pool(self.pool)
+                        .with_log_stdio(self.log_stdio),
+                    );
+                    server.fill_pool();
+                    server
+                };
+                let server = new_server(pre, core_ctx);
+
+                let mut watchers = vec![(MorphWatcher::new(wasm_file.clone()), server.clone())];
+                let mut routes = vec![];
+                for (morph, rule) in &self.routes {
+                    let route_file = self.find_morph(morph).await?;
+                    let pre = self.load_server(&route_file)?;
+                    let route_morph = morph_name(&route_file);
+                    let core_ctx = CoreCtx::new()
+                        .with_config(self.settings.clone())
+                        .with_log_level(self.morph_log_level(&route_morph))
+                        .with_morph(route_morph)
+                        .with_registry(self.registry.clone());
+                    let route_server = new_server(pre, core_ctx);
+
+                    log::info!("routing {} to {}", rule, morph);
+                    watchers.push((MorphWatcher::new(route_file), route_server.clone()));
+                    routes.push((rule.clone(), route_server));
+                }
+                let router = Arc::new(Router::new(server, routes));
                 let listener = TcpListener::bind(address).await?;
 
-                // Start long running process
-                loop {
-                    let (client, addr) = listener.accept().await?;
-                    log::debug!("accepted client from: {}", addr);
-
-                    let server = server.clone();
-
-                    // TODO: Set configured read/write timeouts and header limit
-
-                    tokio::task::spawn(async move {
-                        if let Err(e) = http1::Builder::new()
-                            .keep_alive(true)
-                            .serve_connection(
-                                TokioIo::new(client),
-                                hyper::service::service_fn(move |req| {
-                                    let server = server.clone();
-                                    async move { server.handle_request(req).await }
-                                }),
-                            )
-                            .with_upgrades()
-                            .await
-                        {
-                            log::error!("server error: {}", e);
-                        }
-                    });
-                }
+                let address = listener.local_addr()?;
+                let connections = ConnectionOptions::from_config(&config, self.http2);
+                let handle = spawn_server(
+                    self.id,
+                    self.serve_http(listener, router, watchers, connections),
+                );
+                return Ok(RunOutcome::Server { address, handle });
             }
             ComponentType::WebsocketServer => {
                 let ws_pre: HayrideWsPre<Host> =
                     HayrideWsPre::new(linker.instantiate_pre(&component)?)?;
 
                 // TODO: Add instance export for ws config
-                let address = "127.0.0.1:8082".to_string(); // Default address
+                let address = self.websocket_address.clone();
 
                 log::debug!("starting websocket server with address: {}", address);
 
                 // Prepare our server state and start listening for connections.
-                let server = Arc::new(WebsocketServer::new(
-                    self.id,
-                    self.out_dir.clone(),
-                    ws_pre,
-                    silo_ctx,
-                    core_ctx,
-                    self.registry_path.clone(),
-                    self.model_path.clone(),
-                    args.iter().map(|s| s.as_ref().to_string()).collect(),
-                    self.envs.clone(),
-                ));
+                let server = Arc::new(
+                    WebsocketServer::new(
+                        self.id,
+                        self.out_dir.clone(),
+                        ws_pre,
+                        silo_ctx,
+                        core_ctx,
+                        self.registry_path.clone(),
+                        self.model_path.clone(),
+                        args.iter().map(|s| s.as_ref().to_string()).collect(),
+                        self.envs.clone(),
+                        self.mounts.clone(),
+                        self.timeouts,
+                        self.egress.clone(),
+                        self.http_client.clone(),
+                        self.output_filter.clone(),
+                    )
+                    .with_log_stdio(self.log_stdio),
+                );
                 let listener = TcpListener::bind(address).await?;
 
-                // Start long running process
-                loop {
-                    let (client, addr) = listener.accept().await?;
-                    log::debug!("accepted client from: {}", addr);
-
-                    let server = server.clone();
-                    tokio::task::spawn(async move {
-                        if let Err(e) = http1::Builder::new()
-                            .keep_alive(true)
-                            .serve_connection(
-                                TokioIo::new(client),
-                                hyper::service::service_fn(move |req| {
-                                    let server = server.clone();
-                                    async move { server.handle_request(req).await }
-                                }),
-                            )
-                            .with_upgrades()
-                            .await
-                        {
-                            eprintln!("server error: {}", e);
+                let address = listener.local_addr()?;
+                let handle = spawn_server(self.id, serve_websocket(listener, server));
+                return Ok(RunOutcome::Server { address, handle });
+            }
+        }
+    }
+
+    // Accept connections until the listener fails, reloading watched morphs in between
+    async fn serve_http(
+        self,
+        listener: TcpListener,
+        router: Arc<Router>,
+        mut watchers: Vec<(MorphWatcher, Arc<Server>)>,
+        connections: ConnectionOptions,
+    ) -> Result<()> {
+        let mut reload = tokio::time::interval(WATCH_INTERVAL);
+        reload.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        if self.watch {
+            for (watcher, _) in &watchers {
+                log::info!("watching {} for changes", watcher.path().display());
+            }
+        }
+
+        // Start long running process
+        loop {
+            let (client, addr) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = reload.tick(), if self.watch => {
+                    for (watcher, server) in watchers.iter_mut() {
+                        if !watcher.poll() {
+                            continue;
                         }
-                    });
+                        // Keep serving the previous component if the new one fails
+                        match self.load_server(watcher.path()) {
+                            Ok(pre) => {
+                                server.swap_pre(pre);
+                                log::info!("reloaded {}", watcher.path().display());
+                            }
+                            Err(e) => {
+                                log::error!("failed to reload {}: {:?}", watcher.path().display(), e)
+                            }
+                        }
+                    }
+                    continue;
                 }
-            }
+            };
+            log::debug!("accepted client from: {}", addr);
+
+            let router = router.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = connections
+                    .serve(
+                        client,
+                        hyper::service::service_fn(move |mut req| {
+                            req.extensions_mut().insert(ClientAddr(addr));
+                            let router = router.clone();
+                            async move { router.handle_request(req).await }
+                        }),
+                    )
+                    .await
+                {
+                    log::error!("server error: {}", e);
+                }
+            });
         }
     }
 }
 
+// Accept websocket connections until the listener fails
+async fn serve_websocket(listener: TcpListener, server: Arc<WebsocketServer>) -> Result<()> {
+    // Start long running process
+    loop {
+        let (client, addr) = listener.accept().await?;
+        log::debug!("accepted client from: {}", addr);
+
+        let server = server.clone();
+        tokio::task::spawn(async move {
+            if let Err(e) = http1::Builder::new()
+                .keep_alive(true)
+                .serve_connection(
+                    TokioIo::new(client),
+                    hyper::service::service_fn(move |mut req| {
+                        req.extensions_mut().insert(ClientAddr(addr));
+                        let server = server.clone();
+                        async move { server.handle_request(req).await }
+                    }),
+                )
+                .with_upgrades()
+                .await
+            {
+                eprintln!("server error: {}", e);
+            }
+        });
+    }
+}
+
+// Serve in the background, the session fails once the server stops serving
+fn spawn_server(
+    id: Uuid,
+    serve: impl std::future::Future<Output = Result<()>> + Send + 'static,
+) -> tokio::task::JoinHandle<Result<()>> {
+    tokio::task::spawn(async move {
+        let result = serve.await;
+        crate::status::status().session_finished(&id.to_string(), SessionState::Failed);
+        result
+    })
+}
+
 // Lookup the exported function from the component
 // assumes that there will only be one exported function
 // TODO: Handle multiple functions AND nested instances
+// Read a stream returned by a reactor function to its end, writing chunks as they arrive
+async fn read_result_stream(
+    store: &mut wasmtime::Store<Host>,
+    stream: ResourceAny,
+    mut writer: Option<JsonLinesWriter>,
+) -> Result<Vec<u8>> {
+    let stream: Resource<DynInputStream> = stream
+        .try_into_resource(&mut *store)
+        .map_err(|_| anyhow::Error::msg("Unknown Result Type"))?;
+    let mut stream = store.data_mut().table.delete(stream)?;
+
+    let mut result = vec![];
+    loop {
+        match stream.blocking_read(CHUNK_SIZE).await {
+            Ok(bytes) => {
+                if let Some(writer) = writer.as_mut() {
+                    writer.chunk(&bytes)?;
+                }
+                result.extend_from_slice(&bytes);
+            }
+            Err(StreamError::Closed) => break,
+            Err(e) => {
+                let e = anyhow::Error::new(e);
+                if let Some(writer) = writer {
+                    writer.error(&e)?;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.done()?;
+    }
+    Ok(result)
+}
+
 fn get_func_export(
     engine: &wasmtime::Engine,
     component: &Component,
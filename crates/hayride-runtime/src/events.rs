@@ -0,0 +1,152 @@
+use dashmap::DashMap;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use wasmtime_wasi::p2::Pollable;
+
+// Events buffered per topic before slow subscribers start missing them
+const TOPIC_CAPACITY: usize = 256;
+
+// Longest topic name accepted from components
+const MAX_TOPIC_LEN: usize = 255;
+
+// Prefix of the topics the host publishes events on
+const HOST_TOPIC_PREFIX: &str = "hayride/";
+
+static BUS: OnceLock<EventBus> = OnceLock::new();
+
+/// Returns the process wide event bus.
+pub fn bus() -> &'static EventBus {
+    BUS.get_or_init(EventBus::new)
+}
+
+/// An event published on a topic of the bus.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub topic: String,
+    pub data: Vec<u8>,
+}
+
+/// Publishing failed because the slowest subscriber of the topic has a full buffer.
+#[derive(Debug, PartialEq)]
+pub struct Full;
+
+/// In-process publish/subscribe bus with named topics.
+///
+/// Events are only delivered to subscribers of a topic at the time of publishing,
+/// a subscriber that falls more than the topic capacity behind misses the oldest events.
+pub struct EventBus {
+    topics: DashMap<String, broadcast::Sender<Event>>,
+}
+
+impl EventBus {
+    fn new() -> Self {
+        Self {
+            topics: DashMap::new(),
+        }
+    }
+
+    /// Publish data on a topic, returning the number of subscribers it was sent to.
+    pub fn publish(&self, topic: &str, data: Vec<u8>) -> usize {
+        let Some(sender) = self.topics.get(topic) else {
+            return 0;
+        };
+
+        sender
+            .send(Event {
+                topic: topic.to_string(),
+                data,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Publish data on a topic unless a subscriber would miss events because of it,
+    /// returning the number of subscribers it was sent to.
+    pub fn try_publish(&self, topic: &str, data: Vec<u8>) -> Result<usize, Full> {
+        let Some(sender) = self.topics.get(topic) else {
+            return Ok(0);
+        };
+
+        // Events stay queued until every subscriber has seen them
+        if sender.len() >= TOPIC_CAPACITY {
+            return Err(Full);
+        }
+        Ok(sender
+            .send(Event {
+                topic: topic.to_string(),
+                data,
+            })
+            .unwrap_or(0))
+    }
+
+    /// Subscribe to all events published on a topic from now on.
+    pub fn subscribe(&self, topic: &str) -> broadcast::Receiver<Event> {
+        self.topics
+            .entry(topic.to_string())
+            .or_insert_with(|| broadcast::channel(TOPIC_CAPACITY).0)
+            .subscribe()
+    }
+}
+
+/// Returns true if components may subscribe to the topic.
+pub fn valid_topic(topic: &str) -> bool {
+    !topic.is_empty() && topic.len() <= MAX_TOPIC_LEN
+}
+
+/// Returns true if components may publish on the topic, topics of the host are read only.
+pub fn publishable_topic(topic: &str) -> bool {
+    valid_topic(topic) && !topic.starts_with(HOST_TOPIC_PREFIX)
+}
+
+/// A subscription of a component to a topic of the bus.
+pub struct Subscription {
+    topic: String,
+    receiver: broadcast::Receiver<Event>,
+    // Received while polling for the subscription to be ready, returned by the next read
+    pending: Option<Result<Event, RecvError>>,
+}
+
+impl Subscription {
+    /// Subscribe to all events published on a topic of the process wide bus from now on.
+    pub fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            receiver: bus().subscribe(topic),
+            pending: None,
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Wait for the next event.
+    pub async fn recv(&mut self) -> Result<Event, RecvError> {
+        match self.pending.take() {
+            Some(pending) => pending,
+            None => self.receiver.recv().await,
+        }
+    }
+
+    /// Return the next event if one was published, without waiting.
+    pub fn try_recv(&mut self) -> Result<Option<Event>, RecvError> {
+        if let Some(pending) = self.pending.take() {
+            return pending.map(Some);
+        }
+        match self.receiver.try_recv() {
+            Ok(event) => Ok(Some(event)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Lagged(missed)) => Err(RecvError::Lagged(missed)),
+            Err(TryRecvError::Closed) => Err(RecvError::Closed),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for Subscription {
+    async fn ready(&mut self) {
+        if self.pending.is_none() {
+            self.pending = Some(self.receiver.recv().await);
+        }
+    }
+}
@@ -0,0 +1,596 @@
+use crate::ai::postprocess::model_profiles;
+use crate::ai::AiCtx;
+use crate::middleware::{is_bearer_token, HostRequest, MaxBodySize};
+use crate::timeouts::{deadline, HostTimeouts};
+use hayride_host_traits::ai::model::ModelFilter;
+use hayride_host_traits::ai::nn::OutputFilter;
+use hayride_host_traits::ai::{BackendError, Tensor, TensorType};
+use hayride_utils::config::GatewayConfig;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::Instrument;
+use uuid::Uuid;
+use wasmtime::Result;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+/// Path prefix of the api, requests under it are handled by the host.
+pub const GATEWAY_PREFIX: &str = "/v1/";
+
+// Largest request body read when the server has no body limit configured
+const MAX_BODY_SIZE: usize = 16 << 20;
+
+/// How chat messages are laid out in the prompt of a model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatFormat {
+    ChatMl,
+    Llama3,
+    Gemma,
+    Mistral,
+}
+
+impl ChatFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "chatml" => Some(Self::ChatMl),
+            "llama3" => Some(Self::Llama3),
+            "gemma" => Some(Self::Gemma),
+            "mistral" => Some(Self::Mistral),
+            _ => None,
+        }
+    }
+
+    /// Pick the format from the file name of the model, ChatML unless it names a model
+    /// family with a format of its own.
+    pub fn detect(model: &str) -> Self {
+        let name = std::path::Path::new(model)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(model)
+            .to_lowercase();
+        if name.contains("llama-3") || name.contains("llama3") {
+            Self::Llama3
+        } else if name.contains("gemma") {
+            Self::Gemma
+        } else if name.contains("mistral") || name.contains("mixtral") {
+            Self::Mistral
+        } else {
+            Self::ChatMl
+        }
+    }
+
+    /// Render the messages as `(role, content)` into a prompt the assistant continues.
+    ///
+    /// The backend adds the begin of text token itself.
+    pub fn prompt(&self, messages: &[(String, String)]) -> String {
+        let mut prompt = String::new();
+        match self {
+            Self::ChatMl => {
+                for (role, content) in messages {
+                    prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, content));
+                }
+                prompt.push_str("<|im_start|>assistant\n");
+            }
+            Self::Llama3 => {
+                for (role, content) in messages {
+                    prompt.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role, content
+                    ));
+                }
+                prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+            }
+            // Gemma has no system turn, the system prompt leads the next user turn
+            Self::Gemma => {
+                let mut system = String::new();
+                for (role, content) in messages {
+                    match role.as_str() {
+                        "system" | "developer" => system.push_str(&format!("{}\n\n", content)),
+                        "assistant" => prompt
+                            .push_str(&format!("<start_of_turn>model\n{}<end_of_turn>\n", content)),
+                        _ => prompt.push_str(&format!(
+                            "<start_of_turn>user\n{}{}<end_of_turn>\n",
+                            std::mem::take(&mut system),
+                            content
+                        )),
+                    }
+                }
+                prompt.push_str("<start_of_turn>model\n");
+            }
+            Self::Mistral => {
+                let mut system = String::new();
+                for (role, content) in messages {
+                    match role.as_str() {
+                        "system" | "developer" => system.push_str(&format!("{}\n\n", content)),
+                        "assistant" => prompt.push_str(&format!("{}</s>", content)),
+                        _ => prompt.push_str(&format!(
+                            "[INST] {}{} [/INST]",
+                            std::mem::take(&mut system),
+                            content
+                        )),
+                    }
+                }
+            }
+        }
+        prompt
+    }
+}
+
+/// Options of the gateway, from the server config.
+#[derive(Clone, Debug, Default)]
+pub struct GatewayOptions {
+    pub auth_token: Option<String>,
+    /// Format of every model, detected from the model name without one
+    pub chat_format: Option<ChatFormat>,
+}
+
+impl GatewayOptions {
+    /// The options of an enabled gateway, none when it is disabled.
+    pub fn from_config(config: &GatewayConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let chat_format = match &config.chat_format {
+            Some(name) => Some(
+                ChatFormat::parse(name).ok_or_else(|| anyhow!("unknown chat format: {}", name))?,
+            ),
+            None => None,
+        };
+        Ok(Some(Self {
+            auth_token: config.auth_token.clone(),
+            chat_format,
+        }))
+    }
+}
+
+/// OpenAI compatible api to the ai backends, so clients of the OpenAI SDKs can use the
+/// models of a node without a morph.
+///
+/// Serves `/v1/chat/completions`, `/v1/embeddings` and `/v1/models`. Models are named
+/// like in `load-by-name`, e.g. `owner/repo/model.gguf`. Completions are computed in one
+/// go, streaming clients receive the whole completion as a single chunk.
+pub struct Gateway {
+    // Backends keep loaded models, so one context serves every request
+    ai: Arc<Mutex<AiCtx>>,
+    options: GatewayOptions,
+    timeouts: HostTimeouts,
+    output_filter: Option<Arc<OutputFilter>>,
+}
+
+impl Gateway {
+    pub fn new(
+        id: Uuid,
+        out_dir: Option<String>,
+        model_path: Option<String>,
+        options: GatewayOptions,
+        timeouts: HostTimeouts,
+        output_filter: Option<Arc<OutputFilter>>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            ai: Arc::new(Mutex::new(AiCtx::new(id.to_string(), out_dir, model_path)?)),
+            options,
+            timeouts,
+            output_filter,
+        })
+    }
+
+    /// Answer a request that passed the middleware of the server.
+    pub async fn handle_request(
+        &self,
+        req: HostRequest,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        if let Some(token) = &self.options.auth_token {
+            if !is_bearer_token(req.headers(), token) {
+                log::warn!("unauthorized gateway request");
+                return error_response(
+                    hyper::StatusCode::UNAUTHORIZED,
+                    "invalid_api_key",
+                    "missing or invalid bearer token",
+                );
+            }
+        }
+        if !crate::capabilities::capabilities().policy().ai_compute {
+            return error_response(
+                hyper::StatusCode::FORBIDDEN,
+                "permission_denied",
+                "ai compute is disabled",
+            );
+        }
+
+        let path = req.uri().path().trim_end_matches('/').to_string();
+        let method = req.method().clone();
+        let result = match (method, path.as_str()) {
+            (hyper::Method::GET, "/v1/models") => self.models().await,
+            (hyper::Method::POST, "/v1/chat/completions") => {
+                self.chat_completions(req)
+                    .instrument(tracing::info_span!("gateway.chat_completions"))
+                    .await
+            }
+            (hyper::Method::POST, "/v1/embeddings") => {
+                self.embeddings(req)
+                    .instrument(tracing::info_span!("gateway.embeddings"))
+                    .await
+            }
+            _ => Err(GatewayError::new(
+                hyper::StatusCode::NOT_FOUND,
+                "not_found",
+                format!("no such endpoint: {}", path),
+            )),
+        };
+
+        match result {
+            Ok(resp) => Ok(resp),
+            Err(e) => error_response(e.status, e.code, &e.message),
+        }
+    }
+
+    async fn models(&self) -> GatewayResult {
+        let filter = ModelFilter {
+            extension: Some("gguf".to_string()),
+            repo: None,
+        };
+        let models = self
+            .ai
+            .lock()
+            .await
+            .model_repository
+            .list(&filter)
+            .map_err(|e| GatewayError::server(format!("failed to list models: {}", e)))?;
+
+        let data: Vec<Value> = models
+            .into_iter()
+            .map(|model| json!({ "id": model, "object": "model", "created": 0, "owned_by": "hayride" }))
+            .collect();
+        Ok(json_ok(json!({ "object": "list", "data": data }))?)
+    }
+
+    async fn chat_completions(&self, req: HostRequest) -> GatewayResult {
+        let request = parse_body(&read_body(req).await?)?;
+        let model = model_name(&request)?;
+        let messages = chat_messages(&request)?;
+        let stream = request
+            .get("stream")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false);
+
+        let format = self
+            .options
+            .chat_format
+            .unwrap_or_else(|| ChatFormat::detect(&model));
+        let prompt = format.prompt(&messages);
+        let inputs = vec![
+            ("prompt".to_string(), text_tensor(prompt.into_bytes())),
+            (
+                "options".to_string(),
+                text_tensor(
+                    prompt_options(&request)
+                        .map_err(GatewayError::invalid)?
+                        .to_string()
+                        .into_bytes(),
+                ),
+            ),
+        ];
+        let post_processor = model_profiles()
+            .post_processor(Some(&model), &inputs)
+            .map_err(|e| GatewayError::invalid(e.to_string()))?;
+
+        let mut context = self.execution_context(&model).await?;
+        let tensor = deadline(self.timeouts.ai, context.compute(inputs))
+            .await
+            .map_err(|e| GatewayError::timeout(e.to_string()))?
+            .map_err(|e| GatewayError::backend(&model, e))?;
+
+        let mut output = tensor.data;
+        if !post_processor.is_empty() {
+            output = post_processor.apply(&output);
+        }
+        if let Some(filter) = &self.output_filter {
+            output = filter.mask(&output);
+        }
+        let content = String::from_utf8_lossy(&output).to_string();
+
+        let id = format!("chatcmpl-{}", Uuid::new_v4().simple());
+        let created = now();
+        if stream {
+            return Ok(sse_response(&[
+                json!({
+                    "id": id, "object": "chat.completion.chunk", "created": created, "model": model,
+                    "choices": [{ "index": 0, "delta": { "role": "assistant", "content": content }, "finish_reason": null }],
+                }),
+                json!({
+                    "id": id, "object": "chat.completion.chunk", "created": created, "model": model,
+                    "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }],
+                }),
+            ])?);
+        }
+        Ok(json_ok(json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": "stop",
+            }],
+        }))?)
+    }
+
+    async fn embeddings(&self, req: HostRequest) -> GatewayResult {
+        let request = parse_body(&read_body(req).await?)?;
+        let model = model_name(&request)?;
+        let inputs: Vec<String> = match request.get("input") {
+            Some(Value::String(input)) => vec![input.clone()],
+            Some(Value::Array(inputs)) => inputs
+                .iter()
+                .map(|input| input.as_str().map(String::from))
+                .collect::<Option<_>>()
+                .ok_or_else(|| GatewayError::invalid("input must be a string or strings"))?,
+            _ => return Err(GatewayError::invalid("input must be a string or strings")),
+        };
+
+        let mut context = self.execution_context(&model).await?;
+        let embeddings = deadline(self.timeouts.ai, context.embed(inputs))
+            .await
+            .map_err(|e| GatewayError::timeout(e.to_string()))?
+            .map_err(|e| GatewayError::backend(&model, e))?;
+
+        let data: Vec<Value> = embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| {
+                json!({ "object": "embedding", "index": index, "embedding": embedding })
+            })
+            .collect();
+        Ok(json_ok(
+            json!({ "object": "list", "data": data, "model": model }),
+        )?)
+    }
+
+    // Load the model, only holding the context while the backend loads it
+    async fn execution_context(
+        &self,
+        model: &str,
+    ) -> std::result::Result<hayride_host_traits::ai::ExecutionContext, GatewayError> {
+        // Loading blocks, the context stays locked by the blocking thread even if the
+        // request is dropped while it loads
+        let (ai, name) = (self.ai.clone(), model.to_string());
+        let graph = tokio::task::spawn_blocking(move || ai.blocking_lock().load_model(&name))
+            .await
+            .map_err(|_| GatewayError::backend(model, BackendError::Unknown))?
+            .map_err(|e| GatewayError::backend(model, e))?;
+        graph
+            .init_execution_context()
+            .map_err(|e| GatewayError::backend(model, e))
+    }
+}
+
+type GatewayResult = std::result::Result<hyper::Response<HyperOutgoingBody>, GatewayError>;
+
+// An error answered in the format of the OpenAI api
+struct GatewayError {
+    status: hyper::StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl GatewayError {
+    fn new(status: hyper::StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        Self::new(
+            hyper::StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            message,
+        )
+    }
+
+    fn server(message: impl Into<String>) -> Self {
+        Self::new(
+            hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            message,
+        )
+    }
+
+    fn timeout(message: impl Into<String>) -> Self {
+        Self::new(hyper::StatusCode::GATEWAY_TIMEOUT, "timeout", message)
+    }
+
+    fn backend(model: &str, error: BackendError) -> Self {
+        match error {
+            BackendError::FailedToLoadModel => Self::new(
+                hyper::StatusCode::NOT_FOUND,
+                "model_not_found",
+                format!("failed to load model: {}", model),
+            ),
+            BackendError::Unsupported => Self::new(
+                hyper::StatusCode::NOT_IMPLEMENTED,
+                "unsupported",
+                format!("model does not support the operation: {}", model),
+            ),
+            BackendError::FailedContextTooLarge => {
+                Self::invalid(format!("prompt is too large for model: {}", model))
+            }
+            BackendError::InvalidArgument => {
+                Self::invalid(format!("invalid options for model: {}", model))
+            }
+            error => Self::server(format!("model {} failed with {}", model, error)),
+        }
+    }
+}
+
+impl From<anyhow::Error> for GatewayError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::server(error.to_string())
+    }
+}
+
+fn parse_body(body: &[u8]) -> std::result::Result<Value, GatewayError> {
+    serde_json::from_slice::<Value>(body)
+        .ok()
+        .filter(Value::is_object)
+        .ok_or_else(|| GatewayError::invalid("body must be a json object"))
+}
+
+fn model_name(request: &Value) -> std::result::Result<String, GatewayError> {
+    request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .filter(|m| !m.trim().is_empty())
+        .map(String::from)
+        .ok_or_else(|| GatewayError::invalid("model is required"))
+}
+
+// Role and text of the messages, content given as parts is joined
+fn chat_messages(request: &Value) -> std::result::Result<Vec<(String, String)>, GatewayError> {
+    let messages = request
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .filter(|m| !m.is_empty())
+        .ok_or_else(|| GatewayError::invalid("messages are required"))?;
+
+    messages
+        .iter()
+        .map(|message| {
+            let role = message
+                .get("role")
+                .and_then(|r| r.as_str())
+                .ok_or_else(|| GatewayError::invalid("message role is required"))?;
+            let content = match message.get("content") {
+                Some(Value::String(content)) => content.clone(),
+                Some(Value::Array(parts)) => parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Some(Value::Null) | None => String::new(),
+                Some(_) => return Err(GatewayError::invalid("message content must be text")),
+            };
+            Ok((role.to_string(), content))
+        })
+        .collect()
+}
+
+// Options of the backend from the sampling parameters of the request, 0 keeps the
+// default of the backend
+pub(crate) fn prompt_options(request: &Value) -> std::result::Result<Value, String> {
+    let int = |key: &str| request.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+    let max_tokens = request
+        .get("max_completion_tokens")
+        .or_else(|| request.get("max_tokens"))
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0);
+    let json_mode = request
+        .get("response_format")
+        .and_then(|f| f.get("type"))
+        .and_then(|t| t.as_str())
+        .is_some_and(|t| t == "json_object" || t == "json_schema");
+    // Token ids are the keys of the object
+    let logit_bias = request
+        .get("logit_bias")
+        .and_then(|bias| bias.as_object())
+        .into_iter()
+        .flatten()
+        .map(
+            |(token, bias)| match (token.parse::<u32>(), bias.as_f64()) {
+                (Ok(token), Some(bias)) => Ok((token, bias)),
+                _ => Err(format!("invalid logit_bias of token {:?}", token)),
+            },
+        )
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(json!({
+        "temperature": request.get("temperature").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        "num_context": 0,
+        "num_batch": 0,
+        "max_predict": max_tokens,
+        "top_k": int("top_k"),
+        "top_p": request.get("top_p").and_then(|v| v.as_f64()).unwrap_or(0.9),
+        "seed": int("seed"),
+        "json_mode": json_mode,
+        "logit_bias": logit_bias,
+    }))
+}
+
+fn text_tensor(data: Vec<u8>) -> Tensor {
+    Tensor {
+        dimensions: vec![1],
+        ty: TensorType::U8,
+        data,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn json_ok(json: Value) -> Result<hyper::Response<HyperOutgoingBody>> {
+    crate::server::json_response(hyper::StatusCode::OK, json)
+}
+
+// Read the body of a request, up to the limit the body limit middleware set
+async fn read_body(req: HostRequest) -> std::result::Result<Bytes, GatewayError> {
+    let max = req
+        .extensions()
+        .get::<MaxBodySize>()
+        .map_or(MAX_BODY_SIZE, |MaxBodySize(max)| {
+            usize::try_from(*max).unwrap_or(usize::MAX)
+        });
+    match Limited::new(req.into_body(), max).collect().await {
+        Ok(body) => Ok(body.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => Err(GatewayError::new(
+            hyper::StatusCode::PAYLOAD_TOO_LARGE,
+            "invalid_request_error",
+            "request body too large",
+        )),
+        Err(e) => Err(GatewayError::invalid(format!(
+            "failed to read the body: {}",
+            e
+        ))),
+    }
+}
+
+fn error_response(
+    status: hyper::StatusCode,
+    code: &str,
+    message: &str,
+) -> Result<hyper::Response<HyperOutgoingBody>> {
+    crate::server::json_response(
+        status,
+        json!({ "error": { "message": message, "type": code, "code": code } }),
+    )
+}
+
+// Server sent events of the chunks, ended like the OpenAI api ends them
+fn sse_response(chunks: &[Value]) -> Result<hyper::Response<HyperOutgoingBody>> {
+    let mut events = String::new();
+    for chunk in chunks {
+        events.push_str(&format!("data: {}\n\n", chunk));
+    }
+    events.push_str("data: [DONE]\n\n");
+
+    let body = Full::new(Bytes::from(events))
+        .map_err(|never| match never {})
+        .boxed();
+    let resp = hyper::Response::builder()
+        .status(hyper::StatusCode::OK)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-store")
+        .body(body)?;
+    Ok(resp)
+}
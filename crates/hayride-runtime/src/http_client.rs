@@ -0,0 +1,394 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use hayride_utils::config::HttpConfig;
+use hyper::client::conn::http1::SendRequest;
+use hyper::header::{HeaderValue, PROXY_AUTHORIZATION, USER_AGENT};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use wasmtime_wasi::runtime::AbortOnDropJoinHandle;
+use wasmtime_wasi_http::bindings::http::types::{DnsErrorPayload, ErrorCode};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::hyper_request_error;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::types::{
+    HostFutureIncomingResponse, IncomingResponse, OutgoingRequestConfig,
+};
+
+// Largest response to a CONNECT request read from a proxy
+const MAX_TUNNEL_RESPONSE: usize = 8192;
+
+/// An http proxy outgoing requests are sent through.
+#[derive(Clone, Debug)]
+struct Proxy {
+    // `host:port` the proxy listens on
+    authority: String,
+    // Basic credentials from the userinfo of the proxy url
+    authorization: Option<HeaderValue>,
+}
+
+impl Proxy {
+    fn parse(proxy: &str) -> Result<Self> {
+        let url = url::Url::parse(proxy).map_err(|e| anyhow!("invalid proxy {}: {}", proxy, e))?;
+        if url.scheme() != "http" {
+            return Err(anyhow!(
+                "unsupported proxy {}: only http proxies are supported",
+                proxy
+            ));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("invalid proxy {}: missing host", proxy))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        let authorization = match url.username() {
+            "" => None,
+            username => {
+                let credentials = format!("{}:{}", username, url.password().unwrap_or_default());
+                let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+                Some(HeaderValue::from_str(&format!("Basic {}", encoded))?)
+            }
+        };
+
+        Ok(Self {
+            authority: format!("{}:{}", host, port),
+            authorization,
+        })
+    }
+}
+
+/// Sends the outgoing http requests of components.
+///
+/// Requests are sent through the configured proxies, https requests are tunneled with
+/// CONNECT, and servers are trusted if their certificate chains to the bundled roots or
+/// a configured CA bundle. Requests without a User-Agent get the configured one.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    http_proxy: Option<Proxy>,
+    https_proxy: Option<Proxy>,
+    // Lowercase hosts and domains reached without a proxy, `*` bypasses every proxy
+    no_proxy: Vec<String>,
+    // Trusted roots, kept to add CA bundles to
+    roots: rustls::RootCertStore,
+    tls: Arc<rustls::ClientConfig>,
+    user_agent: Option<HeaderValue>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: vec![],
+            roots: bundled_roots(),
+            tls: Arc::new(tls_config(bundled_roots())),
+            user_agent: None,
+        }
+    }
+}
+
+impl HttpClient {
+    pub fn from_config(config: &HttpConfig) -> Result<Self> {
+        let mut roots = bundled_roots();
+        if let Some(ca_bundle) = &config.ca_bundle {
+            add_ca_bundle(&mut roots, Path::new(ca_bundle))?;
+        }
+
+        Ok(Self {
+            http_proxy: config.proxy.as_deref().map(Proxy::parse).transpose()?,
+            https_proxy: config
+                .https_proxy
+                .as_deref()
+                .map(Proxy::parse)
+                .transpose()?,
+            no_proxy: parse_no_proxy(config.no_proxy.iter().map(String::as_str)),
+            tls: Arc::new(tls_config(roots.clone())),
+            roots,
+            user_agent: match config.user_agent.as_str() {
+                "" => None,
+                user_agent => Some(HeaderValue::from_str(user_agent)?),
+            },
+        })
+    }
+
+    /// Override the proxies with `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, in upper or
+    /// lower case, and trust the CA certificates in `HAYRIDE_CA_BUNDLE`.
+    pub fn with_env(mut self) -> Result<Self> {
+        if let Some(proxy) = proxy_env("HTTP_PROXY") {
+            self.http_proxy = Some(Proxy::parse(&proxy)?);
+        }
+        if let Some(proxy) = proxy_env("HTTPS_PROXY") {
+            self.https_proxy = Some(Proxy::parse(&proxy)?);
+        }
+        if let Some(no_proxy) = proxy_env("NO_PROXY") {
+            self.no_proxy = parse_no_proxy(no_proxy.split(','));
+        }
+        if let Ok(ca_bundle) = std::env::var("HAYRIDE_CA_BUNDLE") {
+            add_ca_bundle(&mut self.roots, Path::new(&ca_bundle))?;
+            self.tls = Arc::new(tls_config(self.roots.clone()));
+        }
+        Ok(self)
+    }
+
+    /// Send a request in the background, like `default_send_request` of wasi-http.
+    pub fn send_request(
+        self: &Arc<Self>,
+        request: hyper::Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HostFutureIncomingResponse {
+        let client = self.clone();
+        let handle =
+            wasmtime_wasi::runtime::spawn(async move { Ok(client.send(request, config).await) });
+        HostFutureIncomingResponse::pending(handle)
+    }
+
+    fn proxy_for(&self, host: &str, use_tls: bool) -> Option<&Proxy> {
+        let host = host.to_lowercase();
+        let bypass = self.no_proxy.iter().any(|entry| {
+            entry == "*"
+                || host == *entry
+                || host
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        });
+        if bypass {
+            return None;
+        }
+
+        match use_tls {
+            true => self.https_proxy.as_ref(),
+            false => self.http_proxy.as_ref(),
+        }
+    }
+
+    pub(crate) async fn send(
+        &self,
+        mut request: hyper::Request<HyperOutgoingBody>,
+        OutgoingRequestConfig {
+            use_tls,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        }: OutgoingRequestConfig,
+    ) -> Result<IncomingResponse, ErrorCode> {
+        let host = request
+            .uri()
+            .host()
+            .ok_or(ErrorCode::HttpRequestUriInvalid)?
+            .to_string();
+        let port = request
+            .uri()
+            .port_u16()
+            .unwrap_or(if use_tls { 443 } else { 80 });
+        let authority = format!("{}:{}", host, port);
+
+        if let Some(user_agent) = &self.user_agent {
+            if !request.headers().contains_key(USER_AGENT) {
+                request.headers_mut().insert(USER_AGENT, user_agent.clone());
+            }
+        }
+
+        let proxy = self.proxy_for(&host, use_tls);
+        let address = proxy.map_or(authority.as_str(), |proxy| proxy.authority.as_str());
+        let mut tcp_stream = timeout(connect_timeout, TcpStream::connect(address))
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)?
+            .map_err(connect_error)?;
+
+        let (mut sender, worker) = if use_tls {
+            if let Some(proxy) = proxy {
+                timeout(connect_timeout, tunnel(&mut tcp_stream, &authority, proxy))
+                    .await
+                    .map_err(|_| ErrorCode::ConnectionTimeout)??;
+            }
+
+            // Hosts of ipv6 addresses are bracketed in uris
+            let domain = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']'))
+                .map_err(|e| {
+                    log::warn!("invalid server name {}: {:?}", host, e);
+                    dns_error("invalid dns name")
+                })?
+                .to_owned();
+            let stream = tokio_rustls::TlsConnector::from(self.tls.clone())
+                .connect(domain, tcp_stream)
+                .await
+                .map_err(|e| {
+                    log::warn!("tls error connecting to {}: {:?}", authority, e);
+                    ErrorCode::TlsProtocolError
+                })?;
+            handshake(TokioIo::new(stream), connect_timeout).await?
+        } else {
+            handshake(TokioIo::new(tcp_stream), connect_timeout).await?
+        };
+
+        match proxy {
+            // Plain http requests to a proxy keep their absolute uri
+            Some(proxy) if !use_tls => {
+                if let Some(authorization) = &proxy.authorization {
+                    request
+                        .headers_mut()
+                        .insert(PROXY_AUTHORIZATION, authorization.clone());
+                }
+            }
+            _ => {
+                *request.uri_mut() = hyper::Uri::builder()
+                    .path_and_query(
+                        request
+                            .uri()
+                            .path_and_query()
+                            .map(|p| p.as_str())
+                            .unwrap_or("/"),
+                    )
+                    .build()
+                    .map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+            }
+        }
+
+        let resp = timeout(first_byte_timeout, sender.send_request(request))
+            .await
+            .map_err(|_| ErrorCode::ConnectionReadTimeout)?
+            .map_err(hyper_request_error)?
+            .map(|body| {
+                http_body_util::BodyExt::boxed(http_body_util::BodyExt::map_err(
+                    body,
+                    hyper_request_error,
+                ))
+            });
+
+        Ok(IncomingResponse {
+            resp,
+            worker: Some(worker),
+            between_bytes_timeout,
+        })
+    }
+}
+
+// Open a tunnel to `authority` through a proxy, the stream then carries the tunneled bytes
+async fn tunnel(stream: &mut TcpStream, authority: &str, proxy: &Proxy) -> Result<(), ErrorCode> {
+    let mut connect = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(authorization) = proxy.authorization.as_ref().and_then(|a| a.to_str().ok()) {
+        connect.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    connect.push_str("\r\n");
+    stream
+        .write_all(connect.as_bytes())
+        .await
+        .map_err(|_| ErrorCode::ConnectionTerminated)?;
+
+    // Read byte by byte so nothing past the proxy response is consumed
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_TUNNEL_RESPONSE {
+            return Err(ErrorCode::HttpProtocolError);
+        }
+        match stream.read(&mut byte).await {
+            Ok(0) | Err(_) => return Err(ErrorCode::ConnectionTerminated),
+            Ok(_) => response.push(byte[0]),
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        log::warn!(
+            "proxy {} refused tunnel to {}: {}",
+            proxy.authority,
+            authority,
+            status
+        );
+        return Err(ErrorCode::ConnectionRefused);
+    }
+    Ok(())
+}
+
+async fn handshake<S>(
+    stream: S,
+    connect_timeout: std::time::Duration,
+) -> Result<(SendRequest<HyperOutgoingBody>, AbortOnDropJoinHandle<()>), ErrorCode>
+where
+    S: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let (sender, conn) = timeout(
+        connect_timeout,
+        hyper::client::conn::http1::handshake(stream),
+    )
+    .await
+    .map_err(|_| ErrorCode::ConnectionTimeout)?
+    .map_err(hyper_request_error)?;
+
+    let worker = wasmtime_wasi::runtime::spawn(async move {
+        if let Err(e) = conn.await {
+            log::warn!("outgoing http connection error: {}", e);
+        }
+    });
+    Ok((sender, worker))
+}
+
+fn connect_error(e: std::io::Error) -> ErrorCode {
+    if e.kind() == std::io::ErrorKind::AddrNotAvailable
+        || e.to_string()
+            .starts_with("failed to lookup address information")
+    {
+        return dns_error("address not available");
+    }
+    ErrorCode::ConnectionRefused
+}
+
+fn dns_error(rcode: &str) -> ErrorCode {
+    ErrorCode::DnsError(DnsErrorPayload {
+        rcode: Some(rcode.to_string()),
+        info_code: Some(0),
+    })
+}
+
+fn bundled_roots() -> rustls::RootCertStore {
+    rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+    }
+}
+
+fn add_ca_bundle(roots: &mut rustls::RootCertStore, path: &Path) -> Result<()> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| anyhow!("failed to read CA bundle {}: {}", path.display(), e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("invalid CA bundle {}: {}", path.display(), e))?;
+    let (added, ignored) = roots.add_parsable_certificates(certs);
+    if added == 0 {
+        return Err(anyhow!("no CA certificates in {}", path.display()));
+    }
+    if ignored > 0 {
+        log::warn!(
+            "ignored {} invalid certificates in CA bundle {}",
+            ignored,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn tls_config(roots: rustls::RootCertStore) -> rustls::ClientConfig {
+    rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+fn parse_no_proxy<'a>(entries: impl Iterator<Item = &'a str>) -> Vec<String> {
+    entries
+        .map(|entry| entry.trim().trim_start_matches('.').to_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+fn proxy_env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_lowercase()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
@@ -0,0 +1,86 @@
+use crate::session_output::session_outputs;
+use anyhow::Result;
+use std::io::Write;
+use uuid::Uuid;
+
+// Bytes of a string result written per chunk
+pub const CHUNK_SIZE: usize = 4096;
+
+/// Writes a reactor result as JSON lines so progress can be followed while it is produced.
+///
+/// Each chunk is written as `{"chunk":"..."}`, followed by `{"done":true}` once the result
+/// is complete or `{"error":"..."}` if reading it failed.
+pub struct JsonLinesWriter {
+    out: Box<dyn Write + Send>,
+    // Trailing bytes of an incomplete utf-8 sequence, completed by the next chunk
+    pending: Vec<u8>,
+}
+
+impl JsonLinesWriter {
+    /// Write to the out file of the engine in `out_dir`, or to stdout without one.
+    pub fn new(out_dir: Option<&str>, id: &Uuid) -> Result<Self> {
+        let out: Box<dyn Write + Send> = match out_dir {
+            Some(out_dir) => {
+                let path = std::path::Path::new(out_dir)
+                    .join(id.to_string())
+                    .join("out");
+                // Results are written once the function returned, after anything it printed
+                Box::new(session_outputs().open(&path)?)
+            }
+            None => Box::new(std::io::stdout()),
+        };
+        Ok(Self {
+            out,
+            pending: vec![],
+        })
+    }
+
+    pub fn chunk(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        let text = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.to_string(),
+            // Hold back a sequence that may be completed by the next chunk
+            Err(e) if e.error_len().is_none() => {
+                let valid = e.valid_up_to();
+                let text = String::from_utf8_lossy(&self.pending[..valid]).to_string();
+                self.pending.drain(..valid);
+                return self.write_chunk(text);
+            }
+            Err(_) => String::from_utf8_lossy(&self.pending).to_string(),
+        };
+        self.pending.clear();
+        self.write_chunk(text)
+    }
+
+    pub fn done(mut self) -> Result<()> {
+        self.flush_pending()?;
+        self.line(serde_json::json!({ "done": true }))
+    }
+
+    pub fn error(mut self, error: &anyhow::Error) -> Result<()> {
+        self.flush_pending()?;
+        self.line(serde_json::json!({ "error": error.to_string() }))
+    }
+
+    fn flush_pending(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending.clear();
+        self.write_chunk(text)
+    }
+
+    fn write_chunk(&mut self, text: String) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.line(serde_json::json!({ "chunk": text }))
+    }
+
+    fn line(&mut self, value: serde_json::Value) -> Result<()> {
+        writeln!(self.out, "{}", value)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
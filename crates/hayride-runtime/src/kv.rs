@@ -0,0 +1,42 @@
+pub mod bindings;
+#[allow(clippy::module_inception)]
+pub mod kv;
+mod kv_impl;
+
+pub use kv::KvCtx;
+pub use kv::{KvImpl, KvView};
+
+use hayride_host_traits::kv::KvTrait;
+use std::sync::Arc;
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: KvView,
+{
+    crate::kv::bindings::kv::add_to_linker::<T, HasKv<T>>(l, |x| KvImpl(x))?;
+
+    Ok(())
+}
+
+struct HasKv<T>(T);
+
+impl<T: 'static> HasData for HasKv<T> {
+    type Data<'a> = KvImpl<&'a mut T>;
+}
+
+/// The key-value store shared by every store, so the backend is reference counted.
+#[derive(Clone)]
+pub struct KvBackend(Arc<dyn KvTrait>);
+impl std::ops::Deref for KvBackend {
+    type Target = dyn KvTrait;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl<T: KvTrait + 'static> From<Arc<T>> for KvBackend {
+    fn from(value: Arc<T>) -> Self {
+        Self(value)
+    }
+}
@@ -0,0 +1,19 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-kv",
+        // Calls on the store run on blocking threads, so they do not block the runtime
+        imports: {
+            "hayride:kv/kv/get": async | trappable,
+            "hayride:kv/kv/set": async | trappable,
+            "hayride:kv/kv/delete": async | trappable,
+            "hayride:kv/kv/list": async | trappable,
+            default: trappable,
+        },
+        with: {
+            "hayride:kv/kv/error": hayride_host_traits::kv::Error,
+        },
+    });
+}
+
+pub use self::generated::hayride::kv::*;
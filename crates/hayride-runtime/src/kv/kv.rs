@@ -0,0 +1,74 @@
+use wasmtime::component::ResourceTable;
+
+use super::KvBackend;
+
+pub struct KvCtx {
+    pub kv_backend: KvBackend,
+    /// Keys are namespaced by the package of the morph
+    pub namespace: String,
+}
+
+impl KvCtx {
+    /// Context of the morph, named `package:name` or by its name without a package.
+    pub fn new(morph: &str) -> Self {
+        let namespace = match morph.split_once(':') {
+            Some((package, _)) => package,
+            None => morph,
+        };
+        Self {
+            kv_backend: hayride_kv::KvBackend::shared().into(),
+            namespace: namespace.to_string(),
+        }
+    }
+}
+
+pub trait KvView: Send {
+    /// Returns a mutable reference to the kv context.
+    fn ctx(&mut self) -> &mut KvCtx;
+
+    /// Returns a mutable reference to the kv resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + KvView> KvView for &mut T {
+    fn ctx(&mut self) -> &mut KvCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + KvView> KvView for Box<T> {
+    fn ctx(&mut self) -> &mut KvCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:kv`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_sync`](crate::kv::add_to_linker_sync)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct KvImpl<T>(pub T);
+
+impl<T: KvView> KvView for KvImpl<T> {
+    fn ctx(&mut self) -> &mut KvCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
@@ -0,0 +1,110 @@
+use crate::kv::bindings::{kv, types::ErrorCode};
+use crate::kv::{KvBackend, KvImpl, KvView};
+use hayride_host_traits::kv::Error;
+
+use wasmtime::component::Resource;
+use wasmtime::Result;
+
+use anyhow::anyhow;
+use std::time::Duration;
+
+impl<T> KvImpl<T>
+where
+    T: KvView,
+{
+    // Run an operation on the store in the namespace of the morph on a blocking thread,
+    // moving the error into the table
+    async fn run<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&KvBackend, &str) -> Result<R, hayride_host_traits::kv::ErrorCode>
+            + Send
+            + 'static,
+        context: impl FnOnce() -> String,
+    ) -> Result<Result<R, Resource<kv::Error>>> {
+        let backend = self.ctx().kv_backend.clone();
+        let namespace = self.ctx().namespace.clone();
+        match tokio::task::spawn_blocking(move || f(&backend, &namespace)).await? {
+            Ok(value) => Ok(Ok(value)),
+            Err(code) => {
+                let error = Error {
+                    code,
+                    data: anyhow!(context()),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> kv::Host for KvImpl<T>
+where
+    T: KvView,
+{
+    async fn get(&mut self, key: String) -> Result<Result<Option<Vec<u8>>, Resource<kv::Error>>> {
+        let context = format!("Error getting key: {}", key);
+        self.run(
+            move |backend, namespace| backend.get(namespace, &key),
+            || context,
+        )
+        .await
+    }
+
+    async fn set(
+        &mut self,
+        key: String,
+        value: Vec<u8>,
+        ttl: Option<u64>,
+    ) -> Result<Result<(), Resource<kv::Error>>> {
+        let ttl = ttl.map(Duration::from_secs);
+        let context = format!("Error setting key: {}", key);
+        self.run(
+            move |backend, namespace| backend.set(namespace, &key, value, ttl),
+            || context,
+        )
+        .await
+    }
+
+    async fn delete(&mut self, key: String) -> Result<Result<bool, Resource<kv::Error>>> {
+        let context = format!("Error deleting key: {}", key);
+        self.run(
+            move |backend, namespace| backend.delete(namespace, &key),
+            || context,
+        )
+        .await
+    }
+
+    async fn list(&mut self, prefix: String) -> Result<Result<Vec<String>, Resource<kv::Error>>> {
+        let context = format!("Error listing keys with prefix: {}", prefix);
+        self.run(
+            move |backend, namespace| backend.list(namespace, &prefix),
+            || context,
+        )
+        .await
+    }
+}
+
+impl<T> kv::HostError for KvImpl<T>
+where
+    T: KvView,
+{
+    fn code(&mut self, error: Resource<Error>) -> Result<ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            hayride_host_traits::kv::ErrorCode::InvalidKey => Ok(ErrorCode::InvalidKey),
+            hayride_host_traits::kv::ErrorCode::ValueTooLarge => Ok(ErrorCode::ValueTooLarge),
+            hayride_host_traits::kv::ErrorCode::StorageFailed => Ok(ErrorCode::StorageFailed),
+            hayride_host_traits::kv::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
@@ -1,37 +1,100 @@
+pub mod access_log;
 pub mod ai;
 pub mod bindings;
+pub mod blob;
+pub mod capabilities;
+pub mod component_cache;
 pub mod core;
+pub mod daemon;
 pub mod db;
+pub mod egress;
+pub mod encoding;
 pub mod engine;
+pub mod events;
+pub mod gateway;
+pub mod http_client;
+pub mod json_lines;
+pub mod kv;
+pub mod manifest;
 pub mod mcp;
+pub mod middleware;
+pub mod mounts;
+pub mod pool;
+pub mod ratelimit;
+pub mod retention;
 pub mod server;
+pub mod session_input;
+pub mod session_output;
 pub mod silo;
+pub mod socket;
+pub mod sse;
+pub mod status;
+pub mod stdio_log;
+pub mod telemetry;
+pub mod template;
+pub mod timeouts;
+pub mod timers;
+pub mod validate;
 pub mod wac;
+pub mod watch;
 pub mod websocket;
 
 use crate::ai::{AiCtx, AiView};
+use crate::blob::{BlobCtx, BlobView};
+use crate::capabilities::CapabilityPolicy;
 use crate::core::{CoreCtx, CoreView};
 use crate::db::{DBCtx, DBView};
+use crate::egress::EgressPolicy;
+use crate::http_client::HttpClient;
+use crate::kv::{KvCtx, KvView};
 use crate::mcp::{McpCtx, McpView};
+use crate::mounts::Mount;
+use crate::session_input::{session_inputs, StdinMode};
 use crate::silo::{SiloCtx, SiloView};
+use crate::socket::{SocketCtx, SocketView};
+use crate::sse::{SseCtx, SseView};
+use crate::stdio_log::LogOutput;
+use crate::template::{TemplateCtx, TemplateView};
+use crate::validate::{ValidateCtx, ValidateView};
 use crate::wac::{WacCtx, WacView};
 
+use std::io::Read;
+use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 use wasmtime::component::ResourceTable;
-use wasmtime_wasi::cli::{InputFile, OutputFile};
+use wasmtime_wasi::cli::InputFile;
+use wasmtime_wasi::p2::pipe::AsyncReadStream;
+use wasmtime_wasi::p2::DynInputStream;
 use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
 
-use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::{HostFutureIncomingResponse, OutgoingRequestConfig};
+use wasmtime_wasi_http::{HttpResult, WasiHttpCtx, WasiHttpView};
+
+use std::sync::Arc;
+
+// Bytes of a blocking reader buffered ahead of the guest reading them
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
 
 pub struct Host {
     ctx: WasiCtx,
     http_ctx: WasiHttpCtx,
+    egress: Arc<EgressPolicy>,
+    http_client: Arc<HttpClient>,
+    capabilities: CapabilityPolicy,
     core_ctx: CoreCtx,
     ai_ctx: AiCtx,
     mcp_ctx: McpCtx,
     silo_ctx: SiloCtx,
     wac_ctx: WacCtx,
+    template_ctx: TemplateCtx,
+    validate_ctx: ValidateCtx,
     db_ctx: DBCtx,
+    kv_ctx: KvCtx,
+    blob_ctx: BlobCtx,
+    socket_ctx: SocketCtx,
+    sse_ctx: SseCtx,
     table: ResourceTable,
 }
 
@@ -52,6 +115,38 @@ impl WasiHttpView for Host {
     fn table(&mut self) -> &mut ResourceTable {
         &mut self.table
     }
+
+    fn send_request(
+        &mut self,
+        mut request: hyper::Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HttpResult<HostFutureIncomingResponse> {
+        let host = request.uri().host().unwrap_or_default();
+        let port = request
+            .uri()
+            .port_u16()
+            .unwrap_or(if config.use_tls { 443 } else { 80 });
+        if !self.capabilities.http_egress {
+            log::warn!(
+                "denied outgoing request to {}:{}, http egress is disabled",
+                host,
+                port
+            );
+            return Err(ErrorCode::HttpRequestDenied.into());
+        }
+        if !self.egress.is_allowed(host, port) {
+            log::warn!("denied outgoing request to {}:{}", host, port);
+            return Err(ErrorCode::HttpRequestDenied.into());
+        }
+
+        // Continue the trace of the request or thread in the service called, unless the
+        // morph set a trace context of its own
+        if !request.headers().contains_key("traceparent") {
+            crate::telemetry::inject(request.headers_mut());
+        }
+
+        Ok(self.http_client.send_request(request, config))
+    }
 }
 
 impl CoreView for Host {
@@ -99,6 +194,24 @@ impl WacView for Host {
     }
 }
 
+impl TemplateView for Host {
+    fn ctx(&mut self) -> &mut TemplateCtx {
+        &mut self.template_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl ValidateView for Host {
+    fn ctx(&mut self) -> &mut ValidateCtx {
+        &mut self.validate_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
 impl DBView for Host {
     fn ctx(&mut self) -> &mut DBCtx {
         &mut self.db_ctx
@@ -108,39 +221,78 @@ impl DBView for Host {
     }
 }
 
+impl KvView for Host {
+    fn ctx(&mut self) -> &mut KvCtx {
+        &mut self.kv_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl BlobView for Host {
+    fn ctx(&mut self) -> &mut BlobCtx {
+        &mut self.blob_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl SocketView for Host {
+    fn ctx(&mut self) -> &mut SocketCtx {
+        &mut self.socket_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl SseView for Host {
+    fn ctx(&mut self) -> &mut SseCtx {
+        &mut self.sse_ctx
+    }
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
 fn create_wasi_ctx(
     args: &[impl AsRef<str> + std::marker::Sync],
     out_dir: Option<String>,
     id: Uuid,
-    stdin: bool,
+    stdin: StdinMode,
     envs: &[(impl AsRef<str>, impl AsRef<str>)],
+    mounts: &[Mount],
+    log_stdio: Option<&str>,
 ) -> wasmtime::Result<WasiCtx> {
-    let hayride_dir = hayride_utils::paths::hayride::default_hayride_dir()?;
-    let hayride_dir_str = hayride_dir
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Failed to convert hayride dir to string"))?;
-
     let mut binding = WasiCtxBuilder::new();
     let mut wasi_ctx_builder = binding
         .args(args)
         .inherit_stderr()
         .inherit_stdio() // Default inherit stdout
         .env("PWD", ".") // Set the current working directory
-        .envs(envs) // append custom envs
-        .preopened_dir(
-            ".",
-            ".",
-            wasmtime_wasi::DirPerms::all(),
-            wasmtime_wasi::FilePerms::all(),
-        )?
-        .preopened_dir(
-            hayride_dir_str,
-            "/.hayride",
-            wasmtime_wasi::DirPerms::all(),
-            wasmtime_wasi::FilePerms::all(),
-        )?;
-
-    if let Some(out_dir) = out_dir {
+        .envs(envs); // append custom envs
+
+    // Directories granted by the embedder
+    for mount in mounts {
+        log::debug!(
+            "mounting {} at {} ({:?})",
+            mount.host_path,
+            mount.guest_path,
+            mount.perms
+        );
+        wasi_ctx_builder = wasi_ctx_builder
+            .preopened_dir(
+                &mount.host_path,
+                &mount.guest_path,
+                mount.perms.dir_perms(),
+                mount.perms.file_perms(),
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to mount {}: {}", mount.host_path, e))?;
+    }
+
+    if let Some(out_dir) = &out_dir {
         let output_path = out_dir.clone() + "/" + &id.to_string() + "/out";
         let error_path = out_dir.clone() + "/" + &id.to_string() + "/err";
 
@@ -148,53 +300,90 @@ fn create_wasi_ctx(
         std::fs::create_dir_all(out_dir.clone() + "/" + &id.to_string())
             .expect("Failed to create output directory for thread");
 
-        let out_file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(false)
-            .open(output_path.clone())
-            .expect("Failed to open output file stdout");
-
-        let err_file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(false)
-            .open(error_path.clone())
-            .expect("Failed to open error file for stderr");
-
-        let output_file = OutputFile::new(
-            out_file
-                .try_clone()
-                .map_err(|e| anyhow::anyhow!("Failed to clone output file: {:?}", e))?,
-        );
-        wasi_ctx_builder = wasi_ctx_builder.stdout(output_file);
+        // Session files are shared with other stores writing to them, and compressed or
+        // rotated as configured
+        let output_file = crate::session_output::session_outputs()
+            .open(std::path::Path::new(&output_path))
+            .map_err(|e| anyhow::anyhow!("Failed to open output file: {:?}", e))?;
+        let error_file = crate::session_output::session_outputs()
+            .open(std::path::Path::new(&error_path))
+            .map_err(|e| anyhow::anyhow!("Failed to open error file: {:?}", e))?;
+        match log_stdio {
+            Some(morph) => {
+                wasi_ctx_builder = wasi_ctx_builder
+                    .stdout(LogOutput::stdout(id, morph).with_file(output_file))
+                    .stderr(LogOutput::stderr(id, morph).with_file(error_file));
+            }
+            None => {
+                wasi_ctx_builder = wasi_ctx_builder.stdout(output_file).stderr(error_file);
+            }
+        }
 
-        let error_file = OutputFile::new(
-            err_file
-                .try_clone()
-                .map_err(|e| anyhow::anyhow!("Failed to clone error file: {:?}", e))?,
-        );
-        wasi_ctx_builder = wasi_ctx_builder.stderr(error_file);
-
-        if stdin {
-            let input_path = out_dir.clone() + "/" + &id.to_string() + "/in";
-            // Create the input file to be used for stdin
-            let _in_file = std::fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .truncate(false)
-                .open(input_path.clone())
-                .expect("Failed to open input file");
-
-            let file = std::fs::File::open(&input_path)?;
-            let file_stdin = InputFile::new(file);
-
-            wasi_ctx_builder = wasi_ctx_builder.stdin(file_stdin);
+        let input_path = out_dir.clone() + "/" + &id.to_string() + "/in";
+        match stdin {
+            StdinMode::Inherit => {}
+            StdinMode::File => {
+                // Create the input file to be used for stdin
+                let _in_file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .read(true)
+                    .truncate(false)
+                    .open(input_path.clone())
+                    .expect("Failed to open input file");
+
+                let file = std::fs::File::open(&input_path)?;
+                let file_stdin = InputFile::new(file);
+
+                wasi_ctx_builder = wasi_ctx_builder.stdin(file_stdin);
+            }
+            StdinMode::Interactive => {
+                // Reads wait for the parent to write more input until it closes it
+                let input = session_inputs()
+                    .open(std::path::Path::new(&input_path))
+                    .map_err(|e| anyhow::anyhow!("Failed to open input file: {:?}", e))?;
+                wasi_ctx_builder = wasi_ctx_builder.stdin(input);
+            }
         }
     }
 
+    // Without session files output is only logged
+    if let (Some(morph), None) = (log_stdio, &out_dir) {
+        wasi_ctx_builder = wasi_ctx_builder
+            .stdout(LogOutput::stdout(id, morph))
+            .stderr(LogOutput::stderr(id, morph));
+    }
+
     let wasi_ctx: WasiCtx = wasi_ctx_builder.build();
 
     Ok(wasi_ctx)
 }
+
+// Stream a blocking reader to the guest, reading it on a blocking thread as the guest
+// reads the stream
+pub(crate) fn blocking_input_stream(
+    mut reader: impl Read + Send + 'static,
+    name: String,
+) -> DynInputStream {
+    let (mut writer, stream) = tokio::io::duplex(STREAM_CHUNK_SIZE);
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    log::warn!("failed to read {}: {:?}", name, e);
+                    break;
+                }
+            };
+            // The guest dropped the stream
+            if handle.block_on(writer.write_all(&buf[..n])).is_err() {
+                break;
+            }
+        }
+    });
+
+    Box::new(AsyncReadStream::new(stream))
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use hayride_host_traits::ai::Tensor;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// File the manifest of a session is written to, in the directory of the session.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+// Most distinct compute options recorded for a session
+const MAX_OPTIONS: usize = 32;
+
+/// Parameters a session ran with, enough to run it again the same way.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub id: String,
+    /// Path of the wasm file that ran
+    pub morph: PathBuf,
+    /// Sha256 of the component, replays fail once the file changed
+    pub component_hash: String,
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Names of the envs the session ran with, values are not written as they may hold
+    /// secrets
+    #[serde(default)]
+    pub envs: Vec<String>,
+    /// Seed of the session, the n-th compute call that leaves its seed random samples
+    /// with `seed + n`
+    pub seed: u32,
+    /// Models the session computed with
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Distinct options of its compute calls, with the seed they ran with
+    #[serde(default)]
+    pub options: Vec<Value>,
+}
+
+impl RunManifest {
+    /// Read the manifest from the directory of a session.
+    pub fn read(session_dir: &Path) -> Result<Self> {
+        let path = session_dir.join(MANIFEST_FILE);
+        let contents = std::fs::read(&path)
+            .map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_slice(&contents)
+            .map_err(|e| anyhow!("invalid manifest {}: {}", path.display(), e))
+    }
+}
+
+/// Records what a session computes with into its manifest, writing the file as it changes.
+///
+/// Clones record into the same manifest.
+#[derive(Clone)]
+pub struct ManifestRecorder {
+    manifest: Arc<Mutex<RunManifest>>,
+    // Compute calls whose seed was derived from the session seed
+    calls: Arc<AtomicU32>,
+    path: Option<PathBuf>,
+}
+
+impl ManifestRecorder {
+    /// Record into the manifest, writing it to `path` if set.
+    pub fn new(manifest: RunManifest, path: Option<PathBuf>) -> Self {
+        let recorder = Self {
+            manifest: Arc::new(Mutex::new(manifest)),
+            calls: Arc::new(AtomicU32::new(0)),
+            path,
+        };
+        recorder.write(&recorder.lock());
+        recorder
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.lock().seed
+    }
+
+    /// Set the seed of the options of a compute call that leaves it random to the next seed
+    /// derived from the session seed, recording the model and the options it computes with.
+    ///
+    /// Calls draw distinct seeds, a replay making the same calls in the same order draws
+    /// the same ones.
+    pub fn record_compute(&self, model: Option<&str>, inputs: &mut [(String, Tensor)]) {
+        let mut manifest = self.lock();
+        let mut changed = false;
+
+        if let Some(model) = model {
+            if !manifest.models.iter().any(|m| m == model) {
+                manifest.models.push(model.to_string());
+                changed = true;
+            }
+        }
+
+        let options = inputs
+            .iter_mut()
+            .find(|(name, _)| name == "options")
+            .map(|(_, tensor)| tensor);
+        if let Some(tensor) = options {
+            if let Ok(mut options) = serde_json::from_slice::<Value>(&tensor.data) {
+                // A seed of 0 is drawn at random by the backend
+                if options.is_object() && options["seed"].as_u64().unwrap_or_default() == 0 {
+                    let call = self.calls.fetch_add(1, Ordering::Relaxed);
+                    options["seed"] = json!(manifest.seed.wrapping_add(call).max(1));
+                    tensor.data = options.to_string().into_bytes();
+                }
+                if manifest.options.len() < MAX_OPTIONS && !manifest.options.contains(&options) {
+                    manifest.options.push(options);
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            self.write(&manifest);
+        }
+    }
+
+    fn write(&self, manifest: &RunManifest) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string(manifest)?));
+        if let Err(e) = result {
+            log::warn!("failed to write manifest {}: {:?}", path.display(), e);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RunManifest> {
+        self.manifest
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,486 @@
+use bytes::Bytes;
+use hayride_utils::config::MiddlewareConfig;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, AUTHORIZATION, CONTENT_LENGTH, ORIGIN, RETRY_AFTER, VARY,
+    WWW_AUTHENTICATE,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::{HyperIncomingBody, HyperOutgoingBody};
+
+// Clients tracked by the rate limiter before idle ones are forgotten
+const MAX_RATE_LIMITED_CLIENTS: usize = 10_000;
+
+// Header clients may send their api key in instead of a bearer token
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Header morphs get the name of the api key a request was authorized with in.
+pub const KEY_NAME_HEADER: &str = "x-hayride-key-name";
+
+pub type HostRequest = hyper::Request<BoxBody<Bytes, hyper::Error>>;
+pub type HostResponse = hyper::Response<HyperOutgoingBody>;
+
+/// Address of the client a request was accepted from, set as a request extension.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientAddr(pub SocketAddr);
+
+/// What to do with a request after a middleware saw it.
+pub enum Flow {
+    /// Pass the request on to the next middleware, and finally the component
+    Continue(HostRequest),
+    /// Answer the request from the host without invoking the component
+    Respond(HostResponse),
+}
+
+/// A host side step requests of server morphs pass through.
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync {
+    /// Check or rewrite a request before it reaches the component.
+    async fn request(&self, req: HostRequest) -> Flow {
+        Flow::Continue(req)
+    }
+
+    /// Adjust a response, given the headers of the request it answers.
+    fn response(&self, _request: &HeaderMap, _resp: &mut HostResponse) {}
+}
+
+/// Middleware applied in order to requests, and in reverse order to responses.
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the pipeline of the built in middleware, in the order requests should be
+    /// rejected: rate limit, auth token, body size, then CORS headers.
+    ///
+    /// The auth token is checked by [`ApiKeys`] instead when api keys are configured.
+    pub fn from_config(config: &MiddlewareConfig) -> Self {
+        let mut pipeline = Self::new();
+        if let Some(rate) = config.rate_limit {
+            pipeline = pipeline.with(Arc::new(RateLimit::new(rate, config.rate_burst)));
+        }
+        if let Some(token) = config
+            .auth_token
+            .as_ref()
+            .filter(|_| config.api_keys.is_empty())
+        {
+            pipeline = pipeline.with(Arc::new(BearerAuth::new(token.clone())));
+        }
+        if let Some(max) = config.max_body_size {
+            pipeline = pipeline.with(Arc::new(BodyLimit::new(max)));
+        }
+        if !config.cors_origins.is_empty() {
+            pipeline = pipeline.with(Arc::new(Cors::new(
+                config.cors_origins.clone(),
+                config.cors_methods.clone(),
+                config.cors_headers.clone(),
+            )));
+        }
+        pipeline
+    }
+
+    pub fn with(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    pub async fn request(&self, mut req: HostRequest) -> Flow {
+        for middleware in &self.middleware {
+            req = match middleware.request(req).await {
+                Flow::Continue(req) => req,
+                Flow::Respond(resp) => return Flow::Respond(resp),
+            };
+        }
+        Flow::Continue(req)
+    }
+
+    pub fn response(&self, request: &HeaderMap, resp: &mut HostResponse) {
+        for middleware in self.middleware.iter().rev() {
+            middleware.response(request, resp);
+        }
+    }
+}
+
+/// Rejects requests without the configured bearer token.
+pub struct BearerAuth {
+    token: String,
+}
+
+impl BearerAuth {
+    pub fn new(token: String) -> Self {
+        Self { token }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for BearerAuth {
+    async fn request(&self, req: HostRequest) -> Flow {
+        if is_bearer_token(req.headers(), &self.token) {
+            return Flow::Continue(req);
+        }
+        log::warn!("unauthorized request to {}", req.uri().path());
+        Flow::Respond(text_response(
+            hyper::StatusCode::UNAUTHORIZED,
+            "unauthorized",
+        ))
+    }
+}
+
+/// Rejects requests with bodies larger than a limit.
+///
+/// Bodies without a content length are streamed to the component, which gets an error
+/// reading the body once it passed the limit.
+pub struct BodyLimit {
+    max: u64,
+}
+
+impl BodyLimit {
+    pub fn new(max: u64) -> Self {
+        Self { max }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for BodyLimit {
+    async fn request(&self, req: HostRequest) -> Flow {
+        let too_large = || {
+            Flow::Respond(text_response(
+                hyper::StatusCode::PAYLOAD_TOO_LARGE,
+                "request body too large",
+            ))
+        };
+
+        let length = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if length.is_some_and(|length| length > self.max) {
+            return too_large();
+        }
+
+        let mut req = req;
+        req.extensions_mut().insert(MaxBodySize(self.max));
+        Flow::Continue(req)
+    }
+}
+
+/// Largest body of a request, set as a request extension by [`BodyLimit`].
+#[derive(Clone, Copy, Debug)]
+pub struct MaxBodySize(pub u64);
+
+/// A request body failing once more than the limit was read from it.
+pub struct LimitedBody {
+    inner: HyperIncomingBody,
+    max: u64,
+    read: u64,
+}
+
+impl LimitedBody {
+    pub fn new(inner: HyperIncomingBody, max: u64) -> Self {
+        Self {
+            inner,
+            max,
+            read: 0,
+        }
+    }
+}
+
+impl hyper::body::Body for LimitedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        let frame = match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            other => return other,
+        };
+        if let Some(data) = frame.data_ref() {
+            self.read += data.len() as u64;
+            if self.read > self.max {
+                let read = self.read;
+                return Poll::Ready(Some(Err(ErrorCode::HttpRequestBodySize(Some(read)))));
+            }
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Adds CORS headers to responses.
+pub struct Cors {
+    origins: Vec<String>,
+    methods: String,
+    headers: String,
+}
+
+impl Cors {
+    pub fn new(origins: Vec<String>, methods: String, headers: String) -> Self {
+        Self {
+            origins,
+            methods,
+            headers,
+        }
+    }
+
+    fn allowed_origin(&self, request: &HeaderMap) -> Option<HeaderValue> {
+        if self.origins.iter().any(|origin| origin == "*") {
+            return Some(HeaderValue::from_static("*"));
+        }
+        let origin = request.get(ORIGIN)?;
+        self.origins
+            .iter()
+            .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+            .then(|| origin.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for Cors {
+    fn response(&self, request: &HeaderMap, resp: &mut HostResponse) {
+        let Some(origin) = self.allowed_origin(request) else {
+            return;
+        };
+        let echoed = origin != "*";
+
+        let headers = resp.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        if let Ok(methods) = HeaderValue::from_str(&self.methods) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        if let Ok(allowed_headers) = HeaderValue::from_str(&self.headers) {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        // Responses differ by origin when it is echoed back
+        if echoed {
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+}
+
+/// Limits the request rate of each client address with a token bucket.
+pub struct RateLimit {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimit {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Take a token for the client, returning the seconds until one is available if none is
+    fn take(&self, client: IpAddr) -> Result<(), f64> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if buckets.len() >= MAX_RATE_LIMITED_CLIENTS {
+            // Forget clients whose buckets have refilled
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, (tokens, updated)| {
+                *tokens + now.duration_since(*updated).as_secs_f64() * rate < burst
+            });
+        }
+
+        let (tokens, updated) = buckets.entry(client).or_insert((self.burst, now));
+        *tokens =
+            (*tokens + now.duration_since(*updated).as_secs_f64() * self.rate).min(self.burst);
+        *updated = now;
+        if *tokens < 1.0 {
+            return Err((1.0 - *tokens) / self.rate);
+        }
+        *tokens -= 1.0;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimit {
+    async fn request(&self, req: HostRequest) -> Flow {
+        let Some(ClientAddr(addr)) = req.extensions().get::<ClientAddr>().copied() else {
+            return Flow::Continue(req);
+        };
+
+        match self.take(addr.ip()) {
+            Ok(()) => Flow::Continue(req),
+            Err(wait) => {
+                log::debug!("rate limited request from {}", addr);
+                let mut resp =
+                    text_response(hyper::StatusCode::TOO_MANY_REQUESTS, "too many requests");
+                resp.headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(wait.ceil() as u64));
+                Flow::Respond(resp)
+            }
+        }
+    }
+}
+
+/// What a request accesses, checked against the scopes of its api key.
+#[derive(Clone, Copy, Debug)]
+pub enum AuthTarget<'a> {
+    /// The status dashboard
+    Status,
+    /// The OpenAI compatible gateway
+    Gateway,
+    /// A server morph, by the path of the request before it was routed
+    Path(&'a str),
+}
+
+struct ApiKey {
+    name: String,
+    key: String,
+    scopes: Vec<String>,
+}
+
+/// Authorizes requests by api keys with scopes, so clients sharing a server only reach
+/// the morphs and host apis their key allows.
+///
+/// Requests without a known key are rejected with 401, requests outside the scopes of
+/// their key with 403.
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeys {
+    /// The api keys of the config, none when no api keys are configured.
+    pub fn from_config(config: &MiddlewareConfig) -> Option<Self> {
+        if config.api_keys.is_empty() {
+            return None;
+        }
+
+        let mut keys: Vec<ApiKey> = config
+            .api_keys
+            .iter()
+            .filter(|key| {
+                if key.key.is_empty() {
+                    log::warn!("ignoring api key without a key: {}", key.name);
+                }
+                !key.key.is_empty()
+            })
+            .map(|key| ApiKey {
+                name: key.name.clone(),
+                key: key.key.clone(),
+                scopes: key.scopes.clone(),
+            })
+            .collect();
+        if let Some(token) = &config.auth_token {
+            keys.push(ApiKey {
+                name: "default".to_string(),
+                key: token.clone(),
+                scopes: vec!["*".to_string()],
+            });
+        }
+        Some(Self { keys })
+    }
+
+    /// Check the headers carry a key whose scopes cover the target, returning the name of
+    /// the key, or the status to reject the request with.
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        target: AuthTarget<'_>,
+    ) -> Result<&str, hyper::StatusCode> {
+        let provided = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| bearer_token(headers));
+        let key = provided.and_then(|provided| {
+            self.keys
+                .iter()
+                .find(|key| constant_time_eq(provided, &key.key))
+        });
+        let Some(key) = key else {
+            log::warn!("request without a valid api key to {:?}", target);
+            return Err(hyper::StatusCode::UNAUTHORIZED);
+        };
+
+        if !key.scopes.iter().any(|scope| in_scope(scope, target)) {
+            log::warn!("api key {} may not access {:?}", key.name, target);
+            return Err(hyper::StatusCode::FORBIDDEN);
+        }
+        Ok(&key.name)
+    }
+
+    /// The response rejecting a request with the status returned by `authorize`.
+    pub fn rejection(status: hyper::StatusCode) -> HostResponse {
+        if status != hyper::StatusCode::UNAUTHORIZED {
+            return text_response(status, "forbidden");
+        }
+        let mut resp = text_response(status, "unauthorized");
+        resp.headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        resp
+    }
+}
+
+// Path scopes cover requests under the prefix, i.e. `/api` covers `/api/chat` but not `/apis`
+fn in_scope(scope: &str, target: AuthTarget<'_>) -> bool {
+    match (scope, target) {
+        ("*", _) | ("status", AuthTarget::Status) | ("gateway", AuthTarget::Gateway) => true,
+        (prefix, AuthTarget::Path(path)) if prefix.starts_with('/') => path
+            .strip_prefix(prefix.trim_end_matches('/'))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+        _ => false,
+    }
+}
+
+/// Returns true if the headers carry the bearer token, compared in constant time so the
+/// token cannot be guessed from response times.
+pub fn is_bearer_token(headers: &HeaderMap, token: &str) -> bool {
+    constant_time_eq(bearer_token(headers).unwrap_or_default(), token)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn constant_time_eq(provided: &str, token: &str) -> bool {
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn text_response(status: hyper::StatusCode, text: &'static str) -> HostResponse {
+    let mut resp = hyper::Response::new(
+        Full::new(Bytes::from_static(text.as_bytes()))
+            .map_err(|never| match never {})
+            .boxed(),
+    );
+    *resp.status_mut() = status;
+    resp
+}
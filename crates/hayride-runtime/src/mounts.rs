@@ -0,0 +1,139 @@
+use std::path::{Component, Path, PathBuf};
+use wasmtime_wasi::{DirPerms, FilePerms};
+
+/// Permissions a component is granted on a mounted directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MountPerms {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl MountPerms {
+    pub fn dir_perms(&self) -> DirPerms {
+        match self {
+            MountPerms::ReadOnly => DirPerms::READ,
+            MountPerms::ReadWrite => DirPerms::all(),
+        }
+    }
+
+    pub fn file_perms(&self) -> FilePerms {
+        match self {
+            MountPerms::ReadOnly => FilePerms::READ,
+            MountPerms::ReadWrite => FilePerms::all(),
+        }
+    }
+
+    /// Whether these permissions include the other permissions.
+    pub fn allows(&self, other: MountPerms) -> bool {
+        *self == MountPerms::ReadWrite || other == MountPerms::ReadOnly
+    }
+}
+
+impl std::str::FromStr for MountPerms {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ro" | "read" | "readonly" => Ok(MountPerms::ReadOnly),
+            "rw" | "readwrite" => Ok(MountPerms::ReadWrite),
+            _ => Err(anyhow::anyhow!("invalid mount permissions: {}", s)),
+        }
+    }
+}
+
+/// A host directory preopened for components at a guest path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+    pub host_path: String,
+    pub guest_path: String,
+    pub perms: MountPerms,
+}
+
+impl From<(String, String, MountPerms)> for Mount {
+    fn from((host_path, guest_path, perms): (String, String, MountPerms)) -> Self {
+        Self {
+            host_path,
+            guest_path,
+            perms,
+        }
+    }
+}
+
+/// The directories preopened for components unless disabled, the working
+/// directory and the hayride dir.
+pub fn default_mounts(perms: MountPerms) -> anyhow::Result<Vec<Mount>> {
+    let hayride_dir = hayride_utils::paths::hayride::default_hayride_dir()?;
+    let hayride_dir = hayride_dir
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Failed to convert hayride dir to string"))?;
+
+    Ok(vec![
+        Mount {
+            host_path: ".".to_string(),
+            guest_path: ".".to_string(),
+            perms,
+        },
+        Mount {
+            host_path: hayride_dir.to_string(),
+            guest_path: "/.hayride".to_string(),
+            perms,
+        },
+    ])
+}
+
+/// Resolve a directory a component can access, by its guest path, to a mount for a
+/// component it spawns.
+///
+/// Returns `None` if the path is outside of the mounts or the permissions exceed those
+/// of the mount, so spawned components never get more access than their parent.
+pub fn resolve(
+    mounts: &[Mount],
+    path: &str,
+    guest_path: String,
+    perms: MountPerms,
+) -> Option<Mount> {
+    let (mount, rest) = mounts
+        .iter()
+        .filter_map(|mount| relative_to(path, &mount.guest_path).map(|rest| (mount, rest)))
+        .max_by_key(|(mount, _)| mount.guest_path.len())?;
+    if !mount.perms.allows(perms) {
+        return None;
+    }
+
+    // Symlinks inside the mount must not lead out of it
+    let root = std::fs::canonicalize(&mount.host_path).ok()?;
+    let host_path = std::fs::canonicalize(Path::new(&mount.host_path).join(rest)).ok()?;
+    if !host_path.starts_with(&root) {
+        return None;
+    }
+
+    Some(Mount {
+        host_path: host_path.to_str()?.to_string(),
+        guest_path,
+        perms,
+    })
+}
+
+// Strip the guest path of a mount from a path, rejecting paths that leave the mount
+fn relative_to(path: &str, mount: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let rest = if mount == "." {
+        // The working directory holds every relative path
+        if path.is_absolute() {
+            return None;
+        }
+        path
+    } else {
+        path.strip_prefix(mount).ok()?
+    };
+
+    let mut relative = PathBuf::new();
+    for component in rest.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
@@ -0,0 +1,148 @@
+use crate::bindings::hayride_server::HayrideServer;
+use crate::capabilities::CapabilityPolicy;
+use crate::Host;
+use hayride_utils::config::PoolConfig;
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use wasmtime::Store;
+
+// Requests a reused instance answers before it is replaced, bounding what its store
+// accumulates
+const MAX_USES: u32 = 1000;
+
+/// Options for pooling instances of server morphs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolOptions {
+    /// Idle instances kept ready for requests
+    pub size: usize,
+    /// Return instances to the pool after they answered a request
+    pub reuse: bool,
+}
+
+impl PoolOptions {
+    /// The pool options of the config, none when no instances are pooled.
+    pub fn from_config(config: &PoolConfig) -> Option<Self> {
+        (config.size > 0).then_some(Self {
+            size: config.size,
+            reuse: config.reuse,
+        })
+    }
+}
+
+/// An instantiated server morph and its store.
+pub struct Instance {
+    pub store: Store<Host>,
+    pub proxy: HayrideServer,
+    // Component generation and capability policy the store was created with
+    generation: u64,
+    capabilities: CapabilityPolicy,
+    uses: u32,
+}
+
+impl Instance {
+    pub fn new(
+        store: Store<Host>,
+        proxy: HayrideServer,
+        generation: u64,
+        capabilities: CapabilityPolicy,
+    ) -> Self {
+        Self {
+            store,
+            proxy,
+            generation,
+            capabilities,
+            uses: 0,
+        }
+    }
+}
+
+/// Instances of a server morph instantiated ahead of requests.
+///
+/// Requests take an idle instance when one is ready and instantiate their own otherwise.
+/// Instances only go back to the pool after a request when reuse is enabled, so morphs
+/// keeping state between requests always get a fresh one. Idle instances of a component
+/// that was reloaded, or created under another capability policy, are dropped.
+pub struct InstancePool {
+    options: PoolOptions,
+    idle: Mutex<Vec<Instance>>,
+    generation: AtomicU64,
+    // Instances being instantiated to fill the pool
+    pending: AtomicUsize,
+}
+
+impl InstancePool {
+    pub fn new(options: PoolOptions) -> Self {
+        Self {
+            options,
+            idle: Mutex::new(Vec::with_capacity(options.size)),
+            generation: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    /// Generation of the component instances are created from.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Drop the idle instances after the component was reloaded.
+    pub fn reset(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.clear();
+        }
+    }
+
+    /// Take an idle instance created under the capability policy.
+    pub fn take(&self, capabilities: CapabilityPolicy) -> Option<Instance> {
+        let generation = self.generation();
+        let mut idle = self.idle.lock().ok()?;
+        while let Some(instance) = idle.pop() {
+            if instance.generation == generation && instance.capabilities == capabilities {
+                return Some(instance);
+            }
+        }
+        None
+    }
+
+    /// Return an instance that answered a request, if instances are reused.
+    pub fn recycle(&self, mut instance: Instance) {
+        instance.uses += 1;
+        if !self.options.reuse || instance.uses >= MAX_USES {
+            return;
+        }
+        self.put(instance);
+    }
+
+    /// Reserve the instances to instantiate to fill the pool, so concurrent requests do
+    /// not overfill it. Every reserved instance is passed to `fill`, even if it failed.
+    pub fn reserve(&self) -> usize {
+        let Ok(idle) = self.idle.lock() else {
+            return 0;
+        };
+        let pending = self.pending.load(Ordering::Acquire);
+        let missing = self.options.size.saturating_sub(idle.len() + pending);
+        self.pending.fetch_add(missing, Ordering::AcqRel);
+        missing
+    }
+
+    /// Add a reserved instance to the pool.
+    pub fn fill(&self, instance: Option<Instance>) {
+        self.pending.fetch_sub(1, Ordering::AcqRel);
+        if let Some(instance) = instance {
+            self.put(instance);
+        }
+    }
+
+    fn put(&self, instance: Instance) {
+        if instance.generation != self.generation() {
+            return;
+        }
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < self.options.size {
+                idle.push(instance);
+            }
+        }
+    }
+}
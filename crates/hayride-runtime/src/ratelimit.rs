@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Buckets the runtime keeps at once, buckets are never removed
+const MAX_BUCKETS: usize = 1024;
+
+// Longest bucket name, in characters
+const MAX_NAME_LEN: usize = 255;
+
+static RATE_LIMITS: OnceLock<RateLimits> = OnceLock::new();
+
+/// Returns the process wide rate limits.
+pub fn rate_limits() -> &'static RateLimits {
+    RATE_LIMITS.get_or_init(RateLimits::default)
+}
+
+/// Configuring or taking from a bucket failed.
+#[derive(Debug, PartialEq)]
+pub enum RateLimitError {
+    /// No bucket of the name was configured
+    NotFound,
+    /// The name is empty or too long, the rate is not positive or the burst is 0
+    InvalidArgument,
+    /// The runtime already has [`MAX_BUCKETS`] buckets
+    TooManyBuckets,
+}
+
+/// Named token buckets shared by every store, so threads calling the same api
+/// coordinate their request rate.
+#[derive(Default)]
+pub struct RateLimits {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    // Tokens added per second
+    rate: f64,
+    burst: f64,
+    // Tokens left at `updated`, negative while callers wait for reserved tokens
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+    }
+}
+
+impl RateLimits {
+    /// Create the bucket, or change the limits of an existing one.
+    pub fn configure(&self, name: &str, rate: f64, burst: u32) -> Result<(), RateLimitError> {
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(RateLimitError::InvalidArgument);
+        }
+        if !rate.is_finite() || rate <= 0.0 || burst == 0 {
+            return Err(RateLimitError::InvalidArgument);
+        }
+
+        let now = Instant::now();
+        let burst = burst as f64;
+        let mut buckets = self.lock();
+        if let Some(bucket) = buckets.get_mut(name) {
+            // Tokens are counted at the old rate up to now
+            bucket.refill(now);
+            bucket.rate = rate;
+            bucket.burst = burst;
+            bucket.tokens = bucket.tokens.min(burst);
+            return Ok(());
+        }
+        if buckets.len() >= MAX_BUCKETS {
+            return Err(RateLimitError::TooManyBuckets);
+        }
+        buckets.insert(
+            name.to_string(),
+            Bucket {
+                rate,
+                burst,
+                tokens: burst,
+                updated: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Take a token if one is available, returning whether one was taken.
+    pub fn try_acquire(&self, name: &str) -> Result<bool, RateLimitError> {
+        let mut buckets = self.lock();
+        let bucket = buckets.get_mut(name).ok_or(RateLimitError::NotFound)?;
+        bucket.refill(Instant::now());
+        if bucket.tokens < 1.0 {
+            return Ok(false);
+        }
+        bucket.tokens -= 1.0;
+        Ok(true)
+    }
+
+    /// Take a token, waiting until it is available.
+    ///
+    /// The token is reserved right away, so callers get tokens in the order they called.
+    pub async fn acquire(&self, name: &str) -> Result<(), RateLimitError> {
+        let wait = self.reserve(name)?;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    // Reserve a token, returning how long until it is available
+    fn reserve(&self, name: &str) -> Result<Duration, RateLimitError> {
+        let mut buckets = self.lock();
+        let bucket = buckets.get_mut(name).ok_or(RateLimitError::NotFound)?;
+        bucket.refill(Instant::now());
+        bucket.tokens -= 1.0;
+        match bucket.tokens < 0.0 {
+            true => {
+                Ok(Duration::try_from_secs_f64(-bucket.tokens / bucket.rate)
+                    .unwrap_or(Duration::MAX))
+            }
+            false => Ok(Duration::ZERO),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Bucket>> {
+        self.buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,179 @@
+use crate::session_output::session_outputs;
+use hayride_utils::config::OutputConfig;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+static STARTED: OnceLock<()> = OnceLock::new();
+
+/// How long sessions are kept in the output directory, and how much space they can take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetentionPolicy {
+    /// Remove sessions not written to for this long
+    pub max_age: Option<Duration>,
+    /// Remove the oldest sessions once all sessions take more than this many bytes
+    pub max_size: Option<u64>,
+    /// Time between cleanups
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            max_size: None,
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &OutputConfig) -> Self {
+        Self {
+            max_age: config
+                .max_age
+                .filter(|max_age| *max_age > 0.0)
+                .map(Duration::from_secs_f64),
+            max_size: config.max_size,
+            interval: Duration::from_secs_f64(config.cleanup_interval.max(1.0)),
+        }
+    }
+
+    /// Returns true if sessions are ever removed.
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_size.is_some()
+    }
+}
+
+/// Sessions removed by a cleanup.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cleanup {
+    pub removed: usize,
+    pub freed: u64,
+}
+
+/// Remove sessions of the output directory past the max age of the policy, then the oldest
+/// sessions until they fit the max size.
+///
+/// Sessions with out or err files still open, i.e. of running components, are never
+/// removed, but count towards the max size.
+pub fn cleanup(out_dir: &Path, policy: &RetentionPolicy) -> std::io::Result<Cleanup> {
+    let mut sessions = vec![];
+    for entry in std::fs::read_dir(out_dir)? {
+        let path = entry?.path();
+        // Only remove dirs named like sessions, in case the output dir holds anything else
+        let is_session = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| Uuid::parse_str(name).is_ok());
+        if is_session && path.is_dir() {
+            let (modified, size) = usage(&path);
+            sessions.push(Session {
+                in_use: session_outputs().in_use(&path),
+                path,
+                modified,
+                size,
+            });
+        }
+    }
+    // Oldest first
+    sessions.sort_by_key(|session| session.modified);
+
+    let mut total: u64 = sessions.iter().map(|session| session.size).sum();
+    let mut cleanup = Cleanup::default();
+    for session in sessions.iter().filter(|session| !session.in_use) {
+        let expired = policy.max_age.is_some_and(|max_age| {
+            session
+                .modified
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed > max_age)
+        });
+        let over = policy.max_size.is_some_and(|max_size| total > max_size);
+        if !expired && !over {
+            continue;
+        }
+
+        match std::fs::remove_dir_all(&session.path) {
+            Ok(_) => {
+                log::debug!("removed session: {}", session.path.display());
+                total = total.saturating_sub(session.size);
+                cleanup.removed += 1;
+                cleanup.freed += session.size;
+            }
+            Err(e) => log::warn!(
+                "failed to remove session {}: {:?}",
+                session.path.display(),
+                e
+            ),
+        }
+    }
+    Ok(cleanup)
+}
+
+/// Clean up the output directory now and then at the interval of the policy.
+///
+/// Sessions are shared by every engine of the process, so only the first call starts a
+/// cleanup task. Must be called within a tokio runtime.
+pub fn start(out_dir: &str, policy: RetentionPolicy) {
+    if !policy.is_enabled() || STARTED.set(()).is_err() {
+        return;
+    }
+
+    let out_dir = PathBuf::from(out_dir);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(policy.interval);
+        loop {
+            interval.tick().await;
+            let dir = out_dir.clone();
+            match tokio::task::spawn_blocking(move || cleanup(&dir, &policy)).await {
+                Ok(Ok(cleanup)) if cleanup.removed > 0 => log::info!(
+                    "removed {} sessions, freeing {} bytes",
+                    cleanup.removed,
+                    cleanup.freed
+                ),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::warn!("failed to clean up sessions: {:?}", e),
+                Err(e) => log::warn!("session cleanup failed: {}", e),
+            }
+        }
+    });
+}
+
+struct Session {
+    path: PathBuf,
+    // Last time anything in the session was written
+    modified: SystemTime,
+    size: u64,
+    in_use: bool,
+}
+
+// Latest modification time and total size of the files in a dir
+pub(crate) fn usage(dir: &Path) -> (SystemTime, u64) {
+    let mut modified = std::fs::metadata(dir)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut size = 0;
+
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Symlinks are not followed, their targets are not part of the session
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+            if let Ok(time) = metadata.modified() {
+                modified = modified.max(time);
+            }
+        }
+    }
+    (modified, size)
+}
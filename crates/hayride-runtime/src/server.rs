@@ -1,32 +1,105 @@
 use super::create_wasi_ctx;
+use crate::bindings::hayride_server::hayride::http::types::ServerConfig as HttpServerConfig;
 use crate::bindings::hayride_server::{HayrideServer, HayrideServerPre};
+use crate::blob::BlobCtx;
+use crate::capabilities::CapabilityPolicy;
 use crate::core::CoreCtx;
 use crate::db::DBCtx;
+use crate::egress::EgressPolicy;
+use crate::gateway::Gateway;
+use crate::http_client::HttpClient;
+use crate::kv::KvCtx;
 use crate::mcp::McpCtx;
+use crate::middleware::{
+    is_bearer_token, ApiKeys, AuthTarget, Flow, HostRequest, LimitedBody, MaxBodySize, Pipeline,
+    KEY_NAME_HEADER,
+};
+use crate::mounts::Mount;
+use crate::pool::{Instance, InstancePool, PoolOptions};
+use crate::session_input::StdinMode;
+use crate::silo::silo::THREADS_ADMIN_PATH;
 use crate::silo::SiloCtx;
+use crate::socket::SocketCtx;
+use crate::sse::SseCtx;
+use crate::template::TemplateCtx;
+use crate::timeouts::HostTimeouts;
+use crate::validate::ValidateCtx;
 use crate::wac::WacCtx;
 use crate::Host;
+use hayride_host_traits::ai::nn::OutputFilter;
+use hayride_utils::config::{MiddlewareConfig, RouteConfig};
 
 use anyhow::bail;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use hyper_util::server::conn::auto;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tracing::Instrument;
 
 use uuid::Uuid;
+use wasmtime::component::Resource;
 use wasmtime_wasi_http::bindings::http::types::Scheme;
+use wasmtime_wasi_http::body::HostIncomingBody;
+use wasmtime_wasi_http::hyper_request_error;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::types::HostIncomingRequest;
 use wasmtime_wasi_http::{body::HyperOutgoingBody, WasiHttpCtx, WasiHttpView};
 
+use crate::access_log::{AccessEntry, OriginalUri};
 use crate::ai::AiCtx;
 use wasmtime::{component::ResourceTable, Result};
 
+// Smallest read buffer hyper accepts, which limits the size of the headers
+const MIN_HEADER_BYTES: usize = 8192;
+
+// Time a request body may go without data before reading it fails, as in wasi-http
+const BETWEEN_BYTES_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Size of the header fields of incoming requests, the wasi-http default
+const FIELD_SIZE_LIMIT: usize = 2 << 30;
+
+// Largest capability policy the admin api reads
+const MAX_ADMIN_BODY_SIZE: usize = 64 << 10;
+
+// Idle time before http2 connections are pinged
+const HTTP2_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
 pub struct Server {
     id: Uuid,
     out_dir: Option<String>,
 
-    pre: HayrideServerPre<Host>,
+    // Swapped when the component is reloaded
+    pre: RwLock<HayrideServerPre<Host>>,
     silo_ctx: SiloCtx,
     core_ctx: CoreCtx,
     registry_path: String,
     model_path: Option<String>,
     args: Vec<String>,
     envs: Vec<(String, String)>,
+    mounts: Vec<Mount>,
+    timeouts: HostTimeouts,
+    egress: Arc<EgressPolicy>,
+    http_client: Arc<HttpClient>,
+    output_filter: Option<Arc<OutputFilter>>,
+    status_enabled: bool,
+    // Bearer token required by the admin api, which is disabled without one
+    admin_token: Option<String>,
+    // Host side checks and headers around component requests
+    middleware: Pipeline,
+    // OpenAI compatible api served instead of the component under `/v1/`
+    gateway: Option<Arc<Gateway>>,
+    // Api keys every request but those to the admin api must carry, if configured
+    api_keys: Option<Arc<ApiKeys>>,
+    // Instances instantiated ahead of requests, if pooled
+    pool: Option<Arc<InstancePool>>,
+    // Log stdout and stderr of the component instead of only writing them out
+    log_stdio: bool,
 }
 
 impl Server {
@@ -40,75 +113,457 @@ impl Server {
         model_path: Option<String>,
         args: Vec<String>,
         envs: Vec<(String, String)>,
+        mounts: Vec<Mount>,
+        timeouts: HostTimeouts,
+        egress: Arc<EgressPolicy>,
+        http_client: Arc<HttpClient>,
+        output_filter: Option<Arc<OutputFilter>>,
+        status_enabled: bool,
+        admin_token: Option<String>,
     ) -> Self {
         Self {
             id,
             out_dir,
-            pre,
+            pre: RwLock::new(pre),
             silo_ctx,
             core_ctx,
             registry_path,
             model_path,
             args,
             envs,
+            mounts,
+            timeouts,
+            egress,
+            http_client,
+            output_filter,
+            status_enabled,
+            admin_token,
+            middleware: Pipeline::from_config(&MiddlewareConfig::default()),
+            gateway: None,
+            api_keys: None,
+            pool: None,
+            log_stdio: false,
+        }
+    }
+
+    pub fn with_middleware(mut self, middleware: Pipeline) -> Self {
+        self.middleware = middleware;
+        self
+    }
+
+    pub fn with_gateway(mut self, gateway: Option<Arc<Gateway>>) -> Self {
+        self.gateway = gateway;
+        self
+    }
+
+    pub fn with_api_keys(mut self, api_keys: Option<Arc<ApiKeys>>) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    pub fn with_pool(mut self, pool: Option<PoolOptions>) -> Self {
+        self.pool = pool.map(|options| Arc::new(InstancePool::new(options)));
+        self
+    }
+
+    pub fn with_log_stdio(mut self, log_stdio: bool) -> Self {
+        self.log_stdio = log_stdio;
+        self
+    }
+
+    /// Serve new requests with a reloaded component, requests in flight finish with the
+    /// one they started with.
+    pub fn swap_pre(&self, pre: HayrideServerPre<Host>) {
+        if let Ok(mut current) = self.pre.write() {
+            *current = pre;
+            // Pooled instances are of the previous component
+            if let Some(pool) = &self.pool {
+                pool.reset();
+            }
+        }
+    }
+
+    /// Instantiate the instances missing from the pool in the background.
+    pub fn fill_pool(self: &Arc<Self>) {
+        let Some(pool) = &self.pool else {
+            return;
+        };
+        for _ in 0..pool.reserve() {
+            let server = self.clone();
+            let pool = pool.clone();
+            tokio::task::spawn(async move {
+                let capabilities = crate::capabilities::capabilities().policy();
+                let instance = server.instantiate(capabilities).await;
+                if let Err(e) = &instance {
+                    log::warn!("failed to instantiate pooled server morph: {:?}", e);
+                }
+                pool.fill(instance.ok());
+            });
         }
     }
 
     pub async fn handle_request(
+        self: &Arc<Self>,
+        req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let entry = AccessEntry::new("http", &req, self.id);
+        let span = tracing::info_span!(
+            "http.request",
+            otel.kind = "server",
+            http.request.method = %entry.method,
+            url.path = %entry.path,
+            session.id = %self.id,
+            http.response.status_code = tracing::field::Empty,
+            otel.status_description = tracing::field::Empty,
+        );
+        crate::telemetry::continue_trace(&span, req.headers());
+        match self.respond(req).instrument(span.clone()).await {
+            Ok(resp) => {
+                span.record("http.response.status_code", resp.status().as_u16());
+                Ok(entry.finish_with(resp))
+            }
+            Err(e) => {
+                span.record("otel.status_description", e.to_string());
+                // The connection is closed without a response
+                entry.finish(hyper::StatusCode::INTERNAL_SERVER_ERROR.as_u16(), 0);
+                Err(e)
+            }
+        }
+    }
+
+    async fn respond(
+        self: &Arc<Self>,
+        mut req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        // Reject requests without a key for what they access before anything is served
+        if let Err(status) = self.check_api_key(&mut req) {
+            return Ok(ApiKeys::rejection(status));
+        }
+
+        // Serve the capability admin api from the host when a token is configured
+        if self.admin_token.is_some() && req.uri().path() == crate::capabilities::ADMIN_PATH {
+            return self.admin_response(req).await;
+        }
+        if self.admin_token.is_some() && req.uri().path() == THREADS_ADMIN_PATH {
+            return self.threads_response(req);
+        }
+
+        // The status dashboard and the gateway are served from the host, without invoking
+        // the component, after the middleware checked the request
+        let serve_status = self.status_enabled
+            && req.method() == hyper::Method::GET
+            && req.uri().path() == crate::status::STATUS_PATH;
+        let gateway = self
+            .gateway
+            .as_ref()
+            .filter(|_| req.uri().path().starts_with(crate::gateway::GATEWAY_PREFIX));
+
+        let status = crate::status::status();
+        if !serve_status && gateway.is_none() {
+            status.request();
+        }
+        let headers = req.headers().clone();
+        let mut resp = match self.middleware.request(req.map(|body| body.boxed())).await {
+            Flow::Continue(_) if serve_status => self.status_response()?,
+            Flow::Continue(req) => match gateway {
+                Some(gateway) => gateway.handle_request(req).await?,
+                None => {
+                    let result = self.handle_component_request(req).await;
+                    if result.is_err() {
+                        status.request_error();
+                    }
+                    result?
+                }
+            },
+            Flow::Respond(resp) => resp,
+        };
+        self.middleware.response(&headers, &mut resp);
+        Ok(resp)
+    }
+
+    fn status_response(&self) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let html = crate::status::status().render_html(&self.silo_ctx.threads());
+        let body = Full::new(Bytes::from(html))
+            .map_err(|never| match never {})
+            .boxed();
+
+        let resp = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Cache-Control", "no-store")
+            .body(body)?;
+        Ok(resp)
+    }
+
+    async fn admin_response(
         &self,
         req: hyper::Request<hyper::body::Incoming>,
     ) -> Result<hyper::Response<HyperOutgoingBody>> {
-        let wasi_ctx =
-            create_wasi_ctx(&self.args, self.out_dir.clone(), self.id, false, &self.envs)?;
+        if !self.authorized(req.headers()) {
+            log::warn!("unauthorized admin request");
+            return json_response(
+                hyper::StatusCode::UNAUTHORIZED,
+                serde_json::json!({ "error": "unauthorized" }),
+            );
+        }
+
+        let capabilities = crate::capabilities::capabilities();
+        match *req.method() {
+            hyper::Method::GET => json_response(
+                hyper::StatusCode::OK,
+                serde_json::to_value(capabilities.policy())?,
+            ),
+            hyper::Method::PUT | hyper::Method::PATCH | hyper::Method::POST => {
+                let body = match Limited::new(req.into_body(), MAX_ADMIN_BODY_SIZE)
+                    .collect()
+                    .await
+                {
+                    Ok(body) => body.to_bytes(),
+                    Err(e) if e.is::<LengthLimitError>() => {
+                        return json_response(
+                            hyper::StatusCode::PAYLOAD_TOO_LARGE,
+                            serde_json::json!({ "error": "request body too large" }),
+                        );
+                    }
+                    Err(e) => return Err(anyhow::anyhow!(e)),
+                };
+                let updated = serde_json::from_slice(&body)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| capabilities.update(&json));
+                match updated {
+                    Ok(policy) => {
+                        json_response(hyper::StatusCode::OK, serde_json::to_value(policy)?)
+                    }
+                    Err(e) => json_response(
+                        hyper::StatusCode::BAD_REQUEST,
+                        serde_json::json!({ "error": e.to_string() }),
+                    ),
+                }
+            }
+            _ => json_response(
+                hyper::StatusCode::METHOD_NOT_ALLOWED,
+                serde_json::json!({ "error": "method not allowed" }),
+            ),
+        }
+    }
+
+    // List the threads of the server, or kill the one with the `id` query parameter
+    fn threads_response(
+        &self,
+        req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        if !self.authorized(req.headers()) {
+            log::warn!("unauthorized admin request");
+            return json_response(
+                hyper::StatusCode::UNAUTHORIZED,
+                serde_json::json!({ "error": "unauthorized" }),
+            );
+        }
+
+        match *req.method() {
+            hyper::Method::GET => {
+                json_response(hyper::StatusCode::OK, self.silo_ctx.threads_json())
+            }
+            hyper::Method::DELETE => {
+                let id =
+                    url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+                        .find(|(key, _)| key == "id")
+                        .and_then(|(_, id)| Uuid::parse_str(&id).ok());
+                let Some(id) = id else {
+                    return json_response(
+                        hyper::StatusCode::BAD_REQUEST,
+                        serde_json::json!({ "error": "missing or invalid thread id" }),
+                    );
+                };
+                match self.silo_ctx.kill_thread(id) {
+                    Ok(()) => json_response(
+                        hyper::StatusCode::OK,
+                        serde_json::json!({ "killed": id.to_string() }),
+                    ),
+                    Err(_) => json_response(
+                        hyper::StatusCode::NOT_FOUND,
+                        serde_json::json!({ "error": "no running thread with the id" }),
+                    ),
+                }
+            }
+            _ => json_response(
+                hyper::StatusCode::METHOD_NOT_ALLOWED,
+                serde_json::json!({ "error": "method not allowed" }),
+            ),
+        }
+    }
+
+    // Check the request carries an api key allowing it, telling the morph the name of the
+    // key. The admin api is only checked against its own token.
+    fn check_api_key(
+        &self,
+        req: &mut hyper::Request<hyper::body::Incoming>,
+    ) -> std::result::Result<(), hyper::StatusCode> {
+        let Some(api_keys) = &self.api_keys else {
+            return Ok(());
+        };
+        let path = req.uri().path();
+        if self.admin_token.is_some()
+            && (path == crate::capabilities::ADMIN_PATH || path == THREADS_ADMIN_PATH)
+        {
+            return Ok(());
+        }
+
+        let target = if self.status_enabled && path == crate::status::STATUS_PATH {
+            AuthTarget::Status
+        } else if self.gateway.is_some() && path.starts_with(crate::gateway::GATEWAY_PREFIX) {
+            AuthTarget::Gateway
+        } else {
+            // Scopes are paths before the request was routed to the morph
+            match req.extensions().get::<OriginalUri>() {
+                Some(OriginalUri(uri)) => AuthTarget::Path(uri.path()),
+                None => AuthTarget::Path(path),
+            }
+        };
+        let name = api_keys.authorize(req.headers(), target)?;
+
+        // Morphs can trust the name, clients can not set it themselves
+        let name = hyper::header::HeaderValue::from_str(name).ok();
+        let headers = req.headers_mut();
+        headers.remove(KEY_NAME_HEADER);
+        if let Some(name) = name {
+            headers.insert(KEY_NAME_HEADER, name);
+        }
+        Ok(())
+    }
+
+    // Check the request carries the admin bearer token
+    fn authorized(&self, headers: &hyper::HeaderMap) -> bool {
+        self.admin_token
+            .as_ref()
+            .is_some_and(|token| is_bearer_token(headers, token))
+    }
+
+    // Create a store of the current component and instantiate it
+    async fn instantiate(&self, capabilities: CapabilityPolicy) -> Result<Instance> {
+        let wasi_ctx = create_wasi_ctx(
+            &self.args,
+            self.out_dir.clone(),
+            self.id,
+            StdinMode::Inherit,
+            &self.envs,
+            &self.mounts,
+            self.log_stdio.then_some(self.core_ctx.morph.as_str()),
+        )?;
+        // Read together with the component, so instances of a reloaded one are told apart
+        let (pre, generation): (HayrideServerPre<Host>, u64) = {
+            let pre = self
+                .pre
+                .read()
+                .map_err(|_| anyhow::anyhow!("server component lock poisoned"))?;
+            let generation = self.pool.as_ref().map_or(0, |pool| pool.generation());
+            (pre.clone(), generation)
+        };
         let mut store: wasmtime::Store<Host> = wasmtime::Store::new(
-            &self.pre.engine(),
+            &pre.engine(),
             Host {
                 ctx: wasi_ctx,
                 http_ctx: WasiHttpCtx::new(),
+                egress: self.egress.clone(),
+                http_client: self.http_client.clone(),
+                capabilities,
                 core_ctx: self.core_ctx.clone(),
-                ai_ctx: AiCtx::new(self.out_dir.clone(), self.model_path.clone())?,
+                ai_ctx: AiCtx::new(
+                    self.id.to_string(),
+                    self.out_dir.clone(),
+                    self.model_path.clone(),
+                )?
+                .with_timeouts(self.timeouts)
+                .with_output_filter(self.output_filter.clone())
+                .with_capabilities(capabilities),
                 mcp_ctx: McpCtx::new(),
-                silo_ctx: self.silo_ctx.clone(),
-                wac_ctx: WacCtx::new(self.registry_path.clone()),
-                db_ctx: DBCtx::new(),
+                silo_ctx: self.silo_ctx.clone().with_capabilities(capabilities),
+                wac_ctx: WacCtx::new(self.registry_path.clone()).with_timeouts(self.timeouts),
+                template_ctx: TemplateCtx::new(self.registry_path.clone()),
+                validate_ctx: ValidateCtx::new(),
+                db_ctx: DBCtx::new()
+                    .with_timeouts(self.timeouts)
+                    .with_capabilities(capabilities),
+                kv_ctx: KvCtx::new(&self.core_ctx.morph),
+                blob_ctx: BlobCtx::new(),
+                socket_ctx: SocketCtx::new(),
+                sse_ctx: SseCtx::new(),
                 table: ResourceTable::default(),
             },
         );
 
         // Instantiate the server
-        let pre: HayrideServerPre<Host> = self.pre.clone();
         let proxy: HayrideServer = pre.instantiate_async(&mut store).await?;
+        Ok(Instance::new(store, proxy, generation, capabilities))
+    }
+
+    async fn handle_component_request(
+        self: &Arc<Self>,
+        req: HostRequest,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        // Clients may send and accept cbor, components only handle json
+        let accepts_cbor = crate::encoding::accepts_cbor(req.headers());
+        let req = crate::encoding::decode_request(req).await?;
+
+        // New stores pick up the current capability policy
+        let capabilities = crate::capabilities::capabilities().policy();
+        let pooled = self.pool.as_ref().and_then(|pool| pool.take(capabilities));
+        let mut instance = match pooled {
+            Some(instance) => instance,
+            None => self.instantiate(capabilities).await?,
+        };
+        self.fill_pool();
+
+        // Pooled instances answer many clients, memory is kept per session of a client
+        instance
+            .store
+            .data_mut()
+            .ai_ctx
+            .memory
+            .set_session(crate::ai::memory::session_id(req.headers()));
 
         // Create a new incoming request and response outparam
         let (sender, receiver) = tokio::sync::oneshot::channel();
-        let req = store.data_mut().new_incoming_request(Scheme::Http, req)?;
-        let out = store.data_mut().new_response_outparam(sender)?;
-
-        // run the http request in separate task
-        let task = tokio::task::spawn(async move {
-            if let Err(e) = proxy
-                .wasi_http_incoming_handler()
-                .call_handle(&mut store, req, out)
-                .await
-            {
-                return Err(e);
-            }
+        let req = new_incoming_request(instance.store.data_mut(), req)?;
+        let out = instance.store.data_mut().new_response_outparam(sender)?;
 
-            Ok(())
-        });
+        // Morphs may respond with an event stream instead of setting the outparam
+        let (events_sender, events_receiver) = tokio::sync::oneshot::channel();
+        instance.store.data_mut().sse_ctx = SseCtx::new().with_responder(events_sender);
 
-        match receiver.await {
-            Ok(Ok(mut resp)) => {
-                // Add CORS headers to the response
-                let headers = resp.headers_mut();
-                if let Ok(origin) = "*".parse() {
-                    headers.insert("Access-Control-Allow-Origin", origin);
+        // run the http request in separate task, within the span of the request
+        let pool = self.pool.clone();
+        let task = tokio::task::spawn(
+            async move {
+                if let Err(e) = instance
+                    .proxy
+                    .wasi_http_incoming_handler()
+                    .call_handle(&mut instance.store, req, out)
+                    .await
+                {
+                    return Err(e);
                 }
-                if let Ok(methods) = "GET, POST, OPTIONS".parse() {
-                    headers.insert("Access-Control-Allow-Methods", methods);
+
+                // Instances that trapped are dropped, others may answer later requests. Event
+                // streams end once the instance is dropped.
+                if let Some(pool) = pool.filter(|_| !instance.store.data().sse_ctx.opened) {
+                    pool.recycle(instance);
                 }
-                if let Ok(allowed_headers) = "*".parse() {
-                    headers.insert("Access-Control-Allow-Headers", allowed_headers);
+                Ok(())
+            }
+            .in_current_span(),
+        );
+
+        let result = tokio::select! {
+            biased;
+            // Event streams are sent as they are, without encoding them as cbor
+            Ok(resp) = events_receiver => return Ok(resp),
+            result = receiver => result,
+        };
+        match result {
+            Ok(Ok(resp)) => {
+                if accepts_cbor {
+                    return crate::encoding::encode_response(resp).await;
                 }
 
                 Ok(resp)
@@ -128,3 +583,322 @@ impl Server {
         }
     }
 }
+
+/// Requests a routed server morph handles, by host and path prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteRule {
+    pub host: Option<String>,
+    // Path prefix without a trailing slash, empty to match every path
+    pub path: String,
+}
+
+impl RouteRule {
+    pub fn from_config(config: &RouteConfig) -> anyhow::Result<Self> {
+        let path = config.path.as_deref().unwrap_or_default();
+        if !path.is_empty() && !path.starts_with('/') {
+            return Err(anyhow::anyhow!("route path must start with '/': {}", path));
+        }
+        if config.host.is_none() && path.trim_end_matches('/').is_empty() {
+            return Err(anyhow::anyhow!(
+                "route for {} needs a host or a path",
+                config.morph
+            ));
+        }
+
+        Ok(Self {
+            host: config.host.as_ref().map(|host| host.to_lowercase()),
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    // Returns the length of the matched path prefix
+    fn matches(&self, host: Option<&str>, path: &str) -> Option<usize> {
+        if self.host.as_deref().is_some_and(|h| Some(h) != host) {
+            return None;
+        }
+        let rest = path.strip_prefix(self.path.as_str())?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        Some(self.path.len())
+    }
+}
+
+impl std::fmt::Display for RouteRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}/", self.host.as_deref().unwrap_or("*"), self.path)
+    }
+}
+
+/// Serves several server morphs behind one listener.
+///
+/// Requests go to the route with the longest matching path prefix, preferring routes
+/// with a host, and to the main morph when no route matches.
+pub struct Router {
+    routes: Vec<(RouteRule, Arc<Server>)>,
+    default: Arc<Server>,
+}
+
+impl Router {
+    pub fn new(default: Arc<Server>, routes: Vec<(RouteRule, Arc<Server>)>) -> Self {
+        Self { routes, default }
+    }
+
+    pub async fn handle_request(
+        &self,
+        mut req: hyper::Request<hyper::body::Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let host = req
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or(req.uri().host())
+            .map(|host| strip_port(host).to_lowercase());
+
+        let route = self
+            .routes
+            .iter()
+            .filter_map(|(rule, server)| {
+                let len = rule.matches(host.as_deref(), req.uri().path())?;
+                Some((len, rule.host.is_some(), server))
+            })
+            .max_by_key(|(len, has_host, _)| (*len, *has_host));
+        let Some((prefix, _, server)) = route else {
+            return self.default.handle_request(req).await;
+        };
+
+        // Morphs see paths relative to their route
+        if prefix > 0 {
+            let original = OriginalUri(req.uri().clone());
+            req.extensions_mut().insert(original);
+            let path = match &req.uri().path()[prefix..] {
+                "" => "/",
+                path => path,
+            };
+            let path_and_query = match req.uri().query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path.to_string(),
+            };
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = Some(path_and_query.parse()?);
+            *req.uri_mut() = hyper::Uri::from_parts(parts)?;
+        }
+        server.handle_request(req).await
+    }
+}
+
+/// Settings of the connections of the listener of server morphs, from the config the
+/// main morph exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionOptions {
+    /// Time to wait for the headers of a request
+    pub read_timeout: Option<Duration>,
+    /// Time a write may wait on a slow client before the connection is closed
+    pub write_timeout: Option<Duration>,
+    pub max_header_bytes: Option<usize>,
+    /// Serve http2 next to http1, told apart by the preface of the connection (h2c)
+    pub http2: bool,
+    /// Requests open at once on an http2 connection
+    pub max_concurrent_streams: Option<u32>,
+}
+
+impl ConnectionOptions {
+    pub fn from_config(config: &HttpServerConfig, http2: bool) -> Self {
+        let seconds = |secs: u32| (secs > 0).then(|| Duration::from_secs(secs.into()));
+        Self {
+            read_timeout: seconds(config.read_timeout),
+            write_timeout: seconds(config.write_timeout),
+            max_header_bytes: (config.max_header_bytes > 0)
+                .then_some(config.max_header_bytes as usize),
+            http2,
+            max_concurrent_streams: (config.max_concurrent_streams > 0)
+                .then_some(config.max_concurrent_streams),
+        }
+    }
+
+    /// Serve the requests of the connection until it is closed.
+    pub async fn serve<S>(
+        &self,
+        client: TcpStream,
+        service: S,
+    ) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: hyper::service::Service<
+                hyper::Request<hyper::body::Incoming>,
+                Response = hyper::Response<HyperOutgoingBody>,
+                Error = anyhow::Error,
+            > + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        let io = WriteTimeout::new(TokioIo::new(client), self.write_timeout);
+
+        if !self.http2 {
+            let mut builder = hyper::server::conn::http1::Builder::new();
+            builder.keep_alive(true).timer(TokioTimer::new());
+            if let Some(timeout) = self.read_timeout {
+                builder.header_read_timeout(timeout);
+            }
+            if let Some(max) = self.max_header_bytes {
+                builder.max_buf_size(max.max(MIN_HEADER_BYTES));
+            }
+            return Ok(builder
+                .serve_connection(io, service)
+                .with_upgrades()
+                .await?);
+        }
+
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        let mut http1 = builder.http1();
+        http1.keep_alive(true).timer(TokioTimer::new());
+        if let Some(timeout) = self.read_timeout {
+            http1.header_read_timeout(timeout);
+        }
+        if let Some(max) = self.max_header_bytes {
+            http1.max_buf_size(max.max(MIN_HEADER_BYTES));
+        }
+        let mut http2 = builder.http2();
+        // Pings find connections of clients that went away without closing them
+        http2
+            .timer(TokioTimer::new())
+            .keep_alive_interval(HTTP2_KEEPALIVE_INTERVAL)
+            .max_concurrent_streams(self.max_concurrent_streams);
+        if let Some(max) = self.max_header_bytes {
+            http2.max_header_list_size(u32::try_from(max).unwrap_or(u32::MAX));
+        }
+        builder.serve_connection_with_upgrades(io, service).await
+    }
+}
+
+// Fails writes that waited on a slow client for longer than the timeout
+struct WriteTimeout<I> {
+    inner: I,
+    timeout: Option<Duration>,
+    // Started once a write has to wait, cleared once it made progress
+    stalled: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<I> WriteTimeout<I> {
+    fn new(inner: I, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            timeout,
+            stalled: None,
+        }
+    }
+
+    fn poll_stalled(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Error> {
+        let Some(timeout) = self.timeout else {
+            return Poll::Pending;
+        };
+        let stalled = self
+            .stalled
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match stalled.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "write to client timed out",
+            )),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    // Time out a pending operation, restarting the timeout once it made progress
+    fn timed<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<std::io::Result<T>>,
+    ) -> Poll<std::io::Result<T>> {
+        match poll {
+            Poll::Pending => self.poll_stalled(cx).map(Err),
+            ready => {
+                self.stalled = None;
+                ready
+            }
+        }
+    }
+}
+
+impl<I: hyper::rt::Read + Unpin> hyper::rt::Read for WriteTimeout<I> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<I: hyper::rt::Write + Unpin> hyper::rt::Write for WriteTimeout<I> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.timed(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.timed(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.timed(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    // Bracketed ipv6 addresses contain colons themselves
+    if let Some(end) = host.find(']') {
+        return &host[..=end];
+    }
+    host.split(':').next().unwrap_or(host)
+}
+
+// Hand a request to the component with its body streamed as the component reads it, up
+// to the limit the body limit middleware set
+fn new_incoming_request(
+    host: &mut Host,
+    req: HostRequest,
+) -> Result<Resource<HostIncomingRequest>> {
+    let (parts, body) = req.into_parts();
+    let body = body.map_err(hyper_request_error).boxed();
+    let body = match parts.extensions.get::<MaxBodySize>() {
+        Some(MaxBodySize(max)) => LimitedBody::new(body, *max).boxed(),
+        None => body,
+    };
+    let body = HostIncomingBody::new(body, BETWEEN_BYTES_TIMEOUT, FIELD_SIZE_LIMIT);
+    let req = HostIncomingRequest::new(host, parts, Scheme::Http, Some(body), FIELD_SIZE_LIMIT)?;
+    Ok(host.table.push(req)?)
+}
+
+pub(crate) fn json_response(
+    status: hyper::StatusCode,
+    json: serde_json::Value,
+) -> Result<hyper::Response<HyperOutgoingBody>> {
+    let body = Full::new(Bytes::from(json.to_string()))
+        .map_err(|never| match never {})
+        .boxed();
+
+    let resp = hyper::Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-store")
+        .body(body)?;
+    Ok(resp)
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio::io::{AsyncRead, ReadBuf};
+use wasmtime_wasi::cli::{IsTerminal, StdinStream};
+
+static SESSION_INPUTS: OnceLock<SessionInputs> = OnceLock::new();
+
+/// Returns the process wide registry of open interactive session in files.
+pub fn session_inputs() -> &'static SessionInputs {
+    SESSION_INPUTS.get_or_init(SessionInputs::default)
+}
+
+/// Where components read their stdin from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StdinMode {
+    /// Inherit the stdin of the runtime
+    Inherit,
+    /// Read the session in file, reaching its end closes stdin
+    File,
+    /// Read the session in file, waiting for more input at its end until it is closed
+    Interactive,
+}
+
+/// Session in files components read as their stdin while other components write to them.
+#[derive(Default)]
+pub struct SessionInputs {
+    open: Mutex<HashMap<PathBuf, Weak<Mutex<InputState>>>>,
+}
+
+#[derive(Default)]
+struct InputState {
+    closed: bool,
+    // Readers waiting for more input
+    wakers: Vec<Waker>,
+}
+
+impl SessionInputs {
+    /// Open a session in file as an interactive stdin, created if it does not exist.
+    pub fn open(&self, path: &Path) -> Result<SessionInput> {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let file = File::open(path)?;
+
+        let state = Arc::new(Mutex::new(InputState::default()));
+        let mut open = self
+            .open
+            .lock()
+            .map_err(|_| anyhow::anyhow!("session input lock poisoned"))?;
+        open.retain(|_, state| state.strong_count() > 0);
+        open.insert(path.to_path_buf(), Arc::downgrade(&state));
+
+        Ok(SessionInput {
+            file: Arc::new(file),
+            state,
+        })
+    }
+
+    /// Append input to a session in file, waking the component reading it.
+    ///
+    /// Fails if no component reads the file interactively or its input was closed.
+    pub fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let state = self.state(path)?;
+        let mut state = state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("session input lock poisoned"))?;
+        if state.closed {
+            anyhow::bail!("input of {} is closed", path.display());
+        }
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .write_all(data)?;
+        state.wakers.drain(..).for_each(Waker::wake);
+        Ok(())
+    }
+
+    /// Close the input of a session in file, the reading component sees its end once it
+    /// read the input written so far.
+    pub fn close(&self, path: &Path) -> Result<()> {
+        let state = self.state(path)?;
+        let mut state = state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("session input lock poisoned"))?;
+        state.closed = true;
+        state.wakers.drain(..).for_each(Waker::wake);
+        Ok(())
+    }
+
+    fn state(&self, path: &Path) -> Result<Arc<Mutex<InputState>>> {
+        self.open
+            .lock()
+            .map_err(|_| anyhow::anyhow!("session input lock poisoned"))?
+            .get(path)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| anyhow::anyhow!("{} is not read interactively", path.display()))
+    }
+}
+
+/// An interactive session in file, usable as the stdin of components.
+#[derive(Clone)]
+pub struct SessionInput {
+    file: Arc<File>,
+    state: Arc<Mutex<InputState>>,
+}
+
+impl AsyncRead for SessionInput {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        // Read while holding the lock so input written meanwhile wakes this reader
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| std::io::Error::other("session input lock poisoned"))?;
+        let n = (&*self.file).read(buf.initialize_unfilled())?;
+        if n > 0 || state.closed {
+            buf.advance(n);
+            return Poll::Ready(Ok(()));
+        }
+
+        state.wakers.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl IsTerminal for SessionInput {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl StdinStream for SessionInput {
+    fn async_stream(&self) -> Box<dyn AsyncRead + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,384 @@
+use anyhow::Result;
+use hayride_utils::config::OutputConfig;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
+
+// Magic number starting every zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Compression level of session files, favouring speed over ratio
+const ZSTD_LEVEL: i32 = 3;
+
+static SESSION_OUTPUTS: OnceLock<SessionOutputs> = OnceLock::new();
+
+/// Returns the process wide registry of open session out and err files.
+pub fn session_outputs() -> &'static SessionOutputs {
+    SESSION_OUTPUTS.get_or_init(SessionOutputs::default)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputOptions {
+    /// Compress session files as zstd frames
+    pub compress: bool,
+    /// Rotate a session file once this many bytes of output were written to it
+    pub rotate_size: Option<u64>,
+    /// Rotated files kept as `<file>.1` to `<file>.<rotate_keep>`, newest first
+    pub rotate_keep: usize,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            compress: false,
+            rotate_size: None,
+            rotate_keep: 3,
+        }
+    }
+}
+
+impl OutputOptions {
+    pub fn from_config(config: &OutputConfig) -> Self {
+        Self {
+            compress: config.compress,
+            rotate_size: config.rotate_size,
+            rotate_keep: config.rotate_keep,
+        }
+    }
+}
+
+/// Session out and err files, shared by everything writing to the same file so output of
+/// concurrent stores and json lines results never interleave inside a compressed frame.
+#[derive(Default)]
+pub struct SessionOutputs {
+    state: Mutex<SessionState>,
+}
+
+#[derive(Default)]
+struct SessionState {
+    options: OutputOptions,
+    open: HashMap<PathBuf, Weak<Mutex<SessionFile>>>,
+}
+
+impl SessionOutputs {
+    /// Options apply to files opened afterwards.
+    pub fn configure(&self, options: OutputOptions) {
+        if let Ok(mut state) = self.state.lock() {
+            state.options = options;
+        }
+    }
+
+    /// Open a session file for appending, or share it if it is already open.
+    pub fn open(&self, path: &Path) -> Result<SessionOutput> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("session output lock poisoned"))?;
+        state.open.retain(|_, file| file.strong_count() > 0);
+
+        if let Some(file) = state.open.get(path).and_then(Weak::upgrade) {
+            return Ok(SessionOutput { file });
+        }
+
+        let file = Arc::new(Mutex::new(SessionFile::open(
+            path.to_path_buf(),
+            state.options,
+        )?));
+        state.open.insert(path.to_path_buf(), Arc::downgrade(&file));
+        Ok(SessionOutput { file })
+    }
+
+    /// Returns true if a file in the dir is open, i.e. a component is writing to it.
+    pub fn in_use(&self, dir: &Path) -> bool {
+        let Ok(state) = self.state.lock() else {
+            return true;
+        };
+        state
+            .open
+            .iter()
+            .any(|(path, file)| path.starts_with(dir) && file.strong_count() > 0)
+    }
+
+    /// Read a session file with its rotated files, oldest first, decompressing them.
+    ///
+    /// A frame still being written by a running session is read up to its last flush.
+    pub fn read(&self, path: &Path) -> Vec<u8> {
+        let mut output = vec![];
+        let _ = self.reader(path).read_to_end(&mut output);
+        output
+    }
+
+    /// Stream a session file with its rotated files like [`SessionOutputs::read`], without
+    /// reading them into memory.
+    pub fn reader(&self, path: &Path) -> SessionReader {
+        let keep = self
+            .state
+            .lock()
+            .map(|state| state.options.rotate_keep)
+            .unwrap_or_default();
+
+        let mut files: VecDeque<PathBuf> = (1..=keep)
+            .rev()
+            .map(|index| rotated_path(path, index))
+            .collect();
+        files.push_back(path.to_path_buf());
+        SessionReader {
+            files,
+            current: None,
+        }
+    }
+}
+
+/// Reads a session file with its rotated files, oldest first, decompressing them.
+pub struct SessionReader {
+    files: VecDeque<PathBuf>,
+    current: Option<Box<dyn Read + Send>>,
+}
+
+impl SessionReader {
+    // Open the next file that exists, returns false once all files were read
+    fn next_file(&mut self) -> bool {
+        while let Some(path) = self.files.pop_front() {
+            let Ok(mut file) = File::open(&path) else {
+                continue;
+            };
+            let mut magic = [0u8; 4];
+            let n = match read_full(&mut file, &mut magic) {
+                Ok(n) => n,
+                Err(_) => continue,
+            };
+            let head = std::io::Cursor::new(magic[..n].to_vec());
+            self.current = match magic[..n] == ZSTD_MAGIC {
+                true => match zstd::stream::read::Decoder::new(head.chain(file)) {
+                    Ok(decoder) => Some(Box::new(decoder)),
+                    Err(_) => continue,
+                },
+                false => Some(Box::new(head.chain(file))),
+            };
+            return true;
+        }
+        false
+    }
+}
+
+impl Read for SessionReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.current.is_none() && !self.next_file() {
+                return Ok(0);
+            }
+            let Some(current) = self.current.as_mut() else {
+                continue;
+            };
+            match current.read(buf) {
+                Ok(0) => self.current = None,
+                Ok(n) => return Ok(n),
+                // The last frame is incomplete while the session is still writing it
+                Err(_) => self.current = None,
+            }
+        }
+    }
+}
+
+/// A session out or err file, usable as the stdout and stderr of components.
+#[derive(Clone)]
+pub struct SessionOutput {
+    file: Arc<Mutex<SessionFile>>,
+}
+
+impl SessionOutput {
+    /// Open a file for appending with its own options, outside of the shared session files.
+    pub fn open_with(path: &Path, options: OutputOptions) -> Result<Self> {
+        let file = SessionFile::open(path.to_path_buf(), options)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    fn with_file<R>(
+        &self,
+        f: impl FnOnce(&mut SessionFile) -> std::io::Result<R>,
+    ) -> std::io::Result<R> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| std::io::Error::other("session output lock poisoned"))?;
+        f(&mut file)
+    }
+}
+
+impl Write for SessionOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.with_file(|file| file.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.with_file(|file| file.flush())
+    }
+}
+
+impl AsyncWrite for SessionOutput {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(
+            self.with_file(|file| file.write_all(buf))
+                .map(|_| buf.len()),
+        )
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.with_file(|file| file.flush()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl IsTerminal for SessionOutput {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl StdoutStream for SessionOutput {
+    fn async_stream(&self) -> Box<dyn AsyncWrite + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+enum Writer {
+    Plain(File),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+struct SessionFile {
+    path: PathBuf,
+    options: OutputOptions,
+    writer: Option<Writer>,
+    // Bytes of output written since the file was last rotated
+    written: u64,
+}
+
+impl SessionFile {
+    fn open(path: PathBuf, options: OutputOptions) -> std::io::Result<Self> {
+        let mut file = Self {
+            path,
+            options,
+            writer: None,
+            written: 0,
+        };
+        file.written = std::fs::metadata(&file.path)
+            .map(|m| m.len())
+            .unwrap_or_default();
+        file.writer = Some(file.open_writer()?);
+        Ok(file)
+    }
+
+    fn open_writer(&self) -> std::io::Result<Writer> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        match self.options.compress {
+            // Each writer appends a new frame, zstd decodes concatenated frames as one
+            true => Ok(Writer::Zstd(zstd::stream::write::Encoder::new(
+                file, ZSTD_LEVEL,
+            )?)),
+            false => Ok(Writer::Plain(file)),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if self
+            .options
+            .rotate_size
+            .is_some_and(|size| self.written > 0 && self.written + buf.len() as u64 > size)
+        {
+            self.rotate()?;
+        }
+
+        match self.writer.as_mut() {
+            Some(Writer::Plain(file)) => file.write_all(buf)?,
+            Some(Writer::Zstd(encoder)) => encoder.write_all(buf)?,
+            None => return Err(std::io::Error::other("session output closed")),
+        }
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.writer.as_mut() {
+            Some(Writer::Plain(file)) => file.flush(),
+            Some(Writer::Zstd(encoder)) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        match self.writer.take() {
+            Some(Writer::Plain(mut file)) => file.flush(),
+            Some(Writer::Zstd(encoder)) => encoder.finish().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    // Shift rotated files up by one, dropping the oldest, and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.close()?;
+
+        let keep = self.options.rotate_keep;
+        if keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated_path(&self.path, keep));
+            for index in (1..keep).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.written = 0;
+        self.writer = Some(self.open_writer()?);
+        Ok(())
+    }
+}
+
+impl Drop for SessionFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::warn!("failed to close {}: {:?}", self.path.display(), e);
+        }
+    }
+}
+
+// Read until the buffer is full or the reader ends
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
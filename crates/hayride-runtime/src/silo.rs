@@ -1,18 +1,35 @@
+pub mod artifacts;
 pub mod bindings;
+pub mod limits;
+pub mod sessions;
 pub mod silo;
 mod silo_impl;
+pub mod spawned;
 
 pub use silo::SiloCtx;
 pub use silo::{SiloImpl, SiloView};
 
 use wasmtime::component::HasData;
 
-pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
 where
     T: SiloView,
 {
     crate::silo::bindings::process::add_to_linker::<T, HasSilo<T>>(l, |x| SiloImpl(x))?;
     crate::silo::bindings::threads::add_to_linker::<T, HasSilo<T>>(l, |x| SiloImpl(x))?;
+    crate::silo::bindings::sessions::add_to_linker::<T, HasSilo<T>>(l, |x| SiloImpl(x))?;
+    add_artifacts_to_linker(l)?;
+
+    Ok(())
+}
+
+/// Add only the artifacts interface, for threads that may publish artifacts to their
+/// parent but not spawn threads of their own.
+pub fn add_artifacts_to_linker<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: SiloView,
+{
+    crate::silo::bindings::artifacts::add_to_linker::<T, HasSilo<T>>(l, |x| SiloImpl(x))?;
 
     Ok(())
 }
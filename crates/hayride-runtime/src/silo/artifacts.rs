@@ -0,0 +1,176 @@
+use super::silo::ErrNo;
+use ring::digest::{Context, SHA256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// Longest artifact name accepted
+const MAX_NAME_LEN: usize = 255;
+
+/// A named artifact a thread published.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+    pub name: String,
+    /// Hex sha256 digest of the contents
+    pub digest: String,
+    pub size: u64,
+}
+
+/// Artifacts of a thread, kept in its session dir so they go with the session.
+///
+/// Contents are stored once per digest under `artifacts/blobs`, and each name is a file
+/// under `artifacts/names` holding the digest it points to.
+pub struct ArtifactStore {
+    dir: PathBuf,
+}
+
+impl ArtifactStore {
+    /// Artifacts of the session with the given id in the output directory.
+    pub fn new(out_dir: &str, session: &str) -> Self {
+        Self {
+            dir: Path::new(out_dir).join(session).join("artifacts"),
+        }
+    }
+
+    /// Store bytes under a name, returning the artifact.
+    pub fn publish(&self, name: &str, data: &[u8]) -> Result<Artifact, ErrNo> {
+        self.publish_from(name, data)
+    }
+
+    /// Store the contents of a file under a name, returning the artifact.
+    pub fn publish_file(&self, name: &str, path: &Path) -> Result<Artifact, ErrNo> {
+        let file = std::fs::File::open(path).map_err(|e| {
+            log::warn!("failed to open artifact file {}: {:?}", path.display(), e);
+            ErrNo::ArtifactNotFound
+        })?;
+        self.publish_from(name, file)
+    }
+
+    /// List the artifacts, by name.
+    pub fn list(&self) -> Vec<Artifact> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join("names")) else {
+            return vec![];
+        };
+        let mut artifacts: Vec<Artifact> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| valid_name(name))
+            .filter_map(|name| self.get(&name).ok())
+            .collect();
+        artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+        artifacts
+    }
+
+    /// Returns the artifact with the given name.
+    pub fn get(&self, name: &str) -> Result<Artifact, ErrNo> {
+        if !valid_name(name) {
+            return Err(ErrNo::InvalidArtifactName);
+        }
+        let digest = std::fs::read_to_string(self.dir.join("names").join(name))
+            .map_err(|_| ErrNo::ArtifactNotFound)?;
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ErrNo::ArtifactNotFound);
+        }
+        let size = std::fs::metadata(self.blob_path(&digest))
+            .map_err(|_| ErrNo::ArtifactNotFound)?
+            .len();
+        Ok(Artifact {
+            name: name.to_string(),
+            digest,
+            size,
+        })
+    }
+
+    /// Read the contents of the artifact with the given name.
+    pub fn fetch(&self, name: &str) -> Result<Vec<u8>, ErrNo> {
+        let artifact = self.get(name)?;
+        std::fs::read(self.blob_path(&artifact.digest)).map_err(|_| ErrNo::ArtifactNotFound)
+    }
+
+    fn publish_from(&self, name: &str, mut reader: impl Read) -> Result<Artifact, ErrNo> {
+        if !valid_name(name) {
+            return Err(ErrNo::InvalidArtifactName);
+        }
+        let blobs = self.dir.join("blobs");
+        let names = self.dir.join("names");
+        for dir in [&blobs, &names] {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                log::warn!("failed to create {}: {:?}", dir.display(), e);
+                ErrNo::FailedToWriteArtifact
+            })?;
+        }
+
+        // Hash the contents while writing them to a temporary file, then move it into place
+        let partial = blobs.join(format!(".{}", uuid::Uuid::new_v4()));
+        let written = (|| -> std::io::Result<(String, u64)> {
+            let mut file = std::fs::File::create(&partial)?;
+            let mut context = Context::new(&SHA256);
+            let mut size = 0;
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                context.update(&buf[..n]);
+                file.write_all(&buf[..n])?;
+                size += n as u64;
+            }
+            file.flush()?;
+            Ok((hex(context.finish().as_ref()), size))
+        })();
+        let (digest, size) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                log::warn!("failed to write artifact {}: {:?}", name, e);
+                return Err(ErrNo::FailedToWriteArtifact);
+            }
+        };
+
+        let blob = self.blob_path(&digest);
+        let stored = match blob.exists() {
+            // Identical contents are stored once
+            true => std::fs::remove_file(&partial),
+            false => std::fs::rename(&partial, &blob),
+        };
+        let previous = self.get(name).ok();
+        let named = stored.and_then(|_| {
+            let partial_name = names.join(format!(".{}", uuid::Uuid::new_v4()));
+            std::fs::write(&partial_name, &digest)?;
+            std::fs::rename(&partial_name, names.join(name))
+        });
+        if let Err(e) = named {
+            let _ = std::fs::remove_file(&partial);
+            log::warn!("failed to store artifact {}: {:?}", name, e);
+            return Err(ErrNo::FailedToWriteArtifact);
+        }
+
+        // Drop the contents the name pointed to before, unless another name still does
+        if let Some(previous) = previous.filter(|previous| previous.digest != digest) {
+            if !self.list().iter().any(|a| a.digest == previous.digest) {
+                let _ = std::fs::remove_file(self.blob_path(&previous.digest));
+            }
+        }
+
+        Ok(Artifact {
+            name: name.to_string(),
+            digest,
+            size,
+        })
+    }
+
+    fn blob_path(&self, digest: &str) -> PathBuf {
+        self.dir.join("blobs").join(digest)
+    }
+}
+
+// Names are single path components, so they can not lead out of the artifacts dir
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\', '\0'])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -3,8 +3,19 @@ pub mod generated {
     wasmtime::component::bindgen!({
         path: "../../wit",
         world: "hayride-silo",
+        // Waiting on a thread and finding morphs, which may pull them from the registry,
+        // is async so it does not block the runtime
+        imports: {
+            "hayride:silo/threads/[method]thread.wait": async,
+            "hayride:silo/threads/spawn": async,
+            "hayride:silo/threads/spawn-with-preopens": async,
+            "hayride:silo/threads/spawn-supervised": async,
+            "hayride:silo/threads/respawn": async,
+            "hayride:silo/threads/describe": async,
+        },
         with: {
             "hayride:silo/threads/thread": hayride_host_traits::silo::Thread,
+            "wasi:io": wasmtime_wasi::p2::bindings::io,
         },
     });
 }
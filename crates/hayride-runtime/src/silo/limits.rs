@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use hayride_utils::config::ThreadsConfig;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// Limits on the threads running at once, in total and for each morph.
+///
+/// Threads spawned past a limit wait in a queue, and start in the order they were
+/// spawned as other threads finish. The limits are shared by every store of an engine.
+#[derive(Default)]
+pub struct ThreadLimits {
+    // Permits of running threads, none without a limit
+    running: Option<Arc<Semaphore>>,
+    morphs: HashMap<String, Arc<Semaphore>>,
+    // Ids of the waiting threads, in the order they were spawned
+    queue: Mutex<VecDeque<Uuid>>,
+}
+
+/// Permits of a running thread, released when it finishes.
+pub struct ThreadPermit {
+    _running: Option<OwnedSemaphorePermit>,
+    _morph: Option<OwnedSemaphorePermit>,
+}
+
+/// A thread waiting in the queue, removed from it when dropped.
+pub struct QueuedThread {
+    limits: Arc<ThreadLimits>,
+    id: Uuid,
+}
+
+impl ThreadLimits {
+    pub fn new(max_concurrent: Option<usize>, morphs: HashMap<String, usize>) -> Self {
+        Self {
+            running: max_concurrent.map(|max| Arc::new(Semaphore::new(max))),
+            morphs: morphs
+                .into_iter()
+                .map(|(morph, max)| (morph, Arc::new(Semaphore::new(max))))
+                .collect(),
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// The limits of the config, failing on a limit of 0 threads.
+    pub fn from_config(config: &ThreadsConfig) -> Result<Self> {
+        if config.max_concurrent == Some(0) {
+            return Err(anyhow!("max concurrent threads must be at least 1"));
+        }
+        if let Some((morph, _)) = config.morphs.iter().find(|(_, max)| **max == 0) {
+            return Err(anyhow!(
+                "max concurrent threads of {} must be at least 1",
+                morph
+            ));
+        }
+        Ok(Self::new(
+            config.max_concurrent,
+            config
+                .morphs
+                .iter()
+                .map(|(morph, max)| (morph.clone(), *max))
+                .collect(),
+        ))
+    }
+
+    /// Take the permits of a thread of the morph, if it can start right away.
+    ///
+    /// Permits are not taken ahead of threads already waiting for them.
+    pub fn try_acquire(&self, morph: &str) -> Option<ThreadPermit> {
+        let morph = match self.morphs.get(morph) {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+        let running = match &self.running {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+        Some(ThreadPermit {
+            _running: running,
+            _morph: morph,
+        })
+    }
+
+    /// Put a thread that could not start at the end of the queue.
+    pub fn enqueue(self: &Arc<Self>, id: Uuid) -> QueuedThread {
+        self.lock().push_back(id);
+        QueuedThread {
+            limits: self.clone(),
+            id,
+        }
+    }
+
+    /// Threads waiting ahead of the thread, none if it is not waiting.
+    pub fn position(&self, id: Uuid) -> Option<u32> {
+        self.lock()
+            .iter()
+            .position(|queued| *queued == id)
+            .map(|position| position as u32)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Uuid>> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl QueuedThread {
+    /// Wait until the thread of the morph can start, leaving the queue.
+    pub async fn acquire(self, morph: &str) -> ThreadPermit {
+        // The semaphores are never closed
+        let morph = match self.limits.morphs.get(morph) {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        let running = match &self.limits.running {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        ThreadPermit {
+            _running: running,
+            _morph: morph,
+        }
+    }
+}
+
+impl Drop for QueuedThread {
+    fn drop(&mut self) {
+        let mut queue = self.limits.lock();
+        if let Some(position) = queue.iter().position(|queued| *queued == self.id) {
+            queue.remove(position);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use super::silo::ErrNo;
+use crate::retention::usage;
+use crate::session_output::{session_outputs, SessionReader};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// A file sessions write to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionFile {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+impl SessionFile {
+    fn name(&self) -> &'static str {
+        match self {
+            SessionFile::Stdin => "in",
+            SessionFile::Stdout => "out",
+            SessionFile::Stderr => "err",
+        }
+    }
+}
+
+/// A session in the output directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    /// Bytes taken by the files and artifacts of the session
+    pub size: u64,
+    /// Last time anything in the session was written
+    pub modified: SystemTime,
+    /// A component is still writing to the session
+    pub running: bool,
+    pub has_result: bool,
+}
+
+/// Sessions in the output directory, each a dir named by its id holding the `in`, `out`,
+/// `err` and `result` files and the artifacts of the session.
+///
+/// Ids are parsed as uuids, so they can not lead out of the output directory.
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(out_dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(out_dir),
+        }
+    }
+
+    /// List the sessions, most recently written first.
+    pub fn list(&self) -> Result<Vec<SessionInfo>, ErrNo> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| {
+            log::warn!("failed to read {}: {:?}", self.dir.display(), e);
+            ErrNo::MissingOutDir
+        })?;
+
+        let mut sessions: Vec<SessionInfo> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| Uuid::parse_str(&name).ok())
+            .filter_map(|id| {
+                let path = self.path(&id.to_string()).ok()?;
+                let (modified, size) = usage(&path);
+                Some(SessionInfo {
+                    id,
+                    size,
+                    modified,
+                    running: session_outputs().in_use(&path),
+                    has_result: path.join("result").is_file(),
+                })
+            })
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.modified));
+        Ok(sessions)
+    }
+
+    /// Read a file of the session with the files it was rotated to, decompressing them.
+    pub fn reader(&self, id: &str, file: SessionFile) -> Result<SessionReader, ErrNo> {
+        let path = self.path(id)?.join(file.name());
+        if !path.is_file() {
+            return Err(ErrNo::SessionNotFound);
+        }
+        Ok(session_outputs().reader(&path))
+    }
+
+    /// Read the result the function of the session returned.
+    pub fn result(&self, id: &str) -> Result<Vec<u8>, ErrNo> {
+        std::fs::read(self.path(id)?.join("result")).map_err(|_| ErrNo::SessionNotFound)
+    }
+
+    /// Remove the session with its files and artifacts, unless a component still writes
+    /// to it.
+    pub fn delete(&self, id: &str) -> Result<(), ErrNo> {
+        let path = self.path(id)?;
+        if session_outputs().in_use(&path) {
+            return Err(ErrNo::SessionInUse);
+        }
+        std::fs::remove_dir_all(&path).map_err(|e| {
+            log::warn!("failed to remove session {}: {:?}", path.display(), e);
+            ErrNo::Failed
+        })
+    }
+
+    // Path of the session dir, which must be a dir and not a symlink out of the output dir
+    fn path(&self, id: &str) -> Result<PathBuf, ErrNo> {
+        let id = Uuid::parse_str(id).map_err(|_| ErrNo::SessionNotFound)?;
+        let path = self.dir.join(id.to_string());
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => Ok(path),
+            _ => Err(ErrNo::SessionNotFound),
+        }
+    }
+}
@@ -1,21 +1,82 @@
+use crate::capabilities::CapabilityPolicy;
+use crate::component_cache::CompiledComponents;
+use crate::core::logging::MorphLogLevels;
+use crate::core::{ConfigBackend, RegistryBackend};
+use crate::egress::EgressPolicy;
+use crate::http_client::HttpClient;
+use crate::mounts::Mount;
+use crate::silo::artifacts::ArtifactStore;
+use crate::silo::limits::ThreadLimits;
+use crate::silo::spawned::SpawnedProcesses;
+use hayride_core::signature::MorphVerifier;
+use hayride_host_traits::ai::nn::OutputFilter;
 use hayride_host_traits::silo::{Thread, ThreadStatus};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 use wasmtime::component::ResourceTable;
 use wasmtime::Result;
 
 use tokio::task::JoinHandle;
 
+/// Path the thread admin api is served on by host servers.
+pub const THREADS_ADMIN_PATH: &str = "/_hayride/admin/threads";
+
 pub struct ThreadData {
     handle: Option<JoinHandle<()>>,
     metadata: Thread,
+    params: ThreadParams,
+}
+
+/// Parameters a thread was spawned with.
+#[derive(Clone)]
+pub struct ThreadParams {
+    pub morph: String,
+    pub function: String,
+    pub args: Vec<String>,
+    pub envs: Vec<(String, String)>,
+    pub mounts: Vec<Mount>,
+    pub restart: RestartPolicy,
+}
+
+// Longest wait before restarting a failed thread
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// When a thread that failed is restarted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    /// Restart up to `max_retries` times, waiting `backoff` before the first restart and
+    /// twice as long before each one after it
+    OnFailure { max_retries: u32, backoff: Duration },
+}
+
+impl RestartPolicy {
+    /// How long to wait before restarting a thread after its `attempt`th run failed,
+    /// none if it is not restarted.
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        match *self {
+            RestartPolicy::OnFailure {
+                max_retries,
+                backoff,
+            } if attempt <= max_retries => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+                Some(backoff.saturating_mul(factor).min(MAX_BACKOFF))
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct SiloCtx {
     // The output directory for the runtime.
     pub out_dir: Option<String>,
+    // Id of the session the context belongs to, which artifacts are published under
+    pub session_id: Option<Uuid>,
 
     pub model_path: Option<String>,
 
@@ -23,20 +84,118 @@ pub struct SiloCtx {
     pub threads: Arc<dashmap::DashMap<Uuid, ThreadData>>,
     thread_id: Arc<AtomicI32>,
     pub registry_path: String,
+
+    // Directories mounted for spawned threads
+    pub mounts: Vec<Mount>,
+    // Outbound http policy for spawned threads
+    pub egress: Arc<EgressPolicy>,
+    // Proxies and identity of outgoing requests of spawned threads
+    pub http_client: Arc<HttpClient>,
+    // Output filter for spawned threads
+    pub output_filter: Option<Arc<OutputFilter>>,
+    // Host settings for spawned threads
+    pub settings: ConfigBackend,
+    // Remote registry missing morphs are pulled from
+    pub registry: Option<RegistryBackend>,
+    // Checks the signatures of spawned morphs
+    pub verifier: Arc<MorphVerifier>,
+    // Spawned threads write their results as json lines
+    pub json_lines: bool,
+    // Spawned threads log their stdout and stderr through the host logger
+    pub log_stdio: bool,
+    // Log level and levels of the records morphs log for spawned threads
+    pub log_level: String,
+    pub morph_log_levels: MorphLogLevels,
+    // Directory precompiled components are cached in
+    pub component_cache: Option<PathBuf>,
+    // Wasmtime engine and compiled components shared with spawned threads
+    pub compiled: Option<CompiledComponents>,
+
+    // Limits on the threads running at once, shared by the stores of the engine
+    pub thread_limits: Arc<ThreadLimits>,
+
+    // Capabilities enabled when the store was created
+    pub capabilities: CapabilityPolicy,
+
+    // Processes spawned by the store, killed when it drops
+    pub processes: SpawnedProcesses,
 }
 
 impl SiloCtx {
-    pub fn new(out_dir: Option<String>, registry_path: String, model_path: Option<String>) -> Self {
+    pub fn new(
+        out_dir: Option<String>,
+        registry_path: String,
+        model_path: Option<String>,
+        mounts: Vec<Mount>,
+        egress: Arc<EgressPolicy>,
+        http_client: Arc<HttpClient>,
+        output_filter: Option<Arc<OutputFilter>>,
+        settings: ConfigBackend,
+        registry: Option<RegistryBackend>,
+        verifier: Arc<MorphVerifier>,
+        json_lines: bool,
+        log_stdio: bool,
+        component_cache: Option<PathBuf>,
+    ) -> Self {
         let thread_id = Arc::new(AtomicI32::new(0));
         Self {
             out_dir,
+            session_id: None,
             model_path,
             threads: Arc::new(dashmap::DashMap::new()),
             thread_id,
             registry_path: registry_path,
+            mounts,
+            egress,
+            http_client,
+            output_filter,
+            settings,
+            registry,
+            verifier,
+            json_lines,
+            log_stdio,
+            log_level: "info".to_string(),
+            morph_log_levels: MorphLogLevels::default(),
+            component_cache,
+            compiled: None,
+            thread_limits: Arc::new(ThreadLimits::default()),
+            capabilities: CapabilityPolicy::default(),
+            processes: SpawnedProcesses::default(),
         }
     }
 
+    pub fn with_capabilities(mut self, capabilities: CapabilityPolicy) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_log_levels(mut self, log_level: String, morph_log_levels: MorphLogLevels) -> Self {
+        self.log_level = log_level;
+        self.morph_log_levels = morph_log_levels;
+        self
+    }
+
+    pub fn with_thread_limits(mut self, thread_limits: Arc<ThreadLimits>) -> Self {
+        self.thread_limits = thread_limits;
+        self
+    }
+
+    pub fn with_compiled(mut self, compiled: CompiledComponents) -> Self {
+        self.compiled = Some(compiled);
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: Uuid) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /// Artifacts of this session, or of a thread it spawned.
+    pub fn artifacts(&self, session_id: Uuid) -> Result<ArtifactStore, ErrNo> {
+        let out_dir = self.out_dir.as_ref().ok_or(ErrNo::MissingOutDir)?;
+        Ok(ArtifactStore::new(out_dir, &session_id.to_string()))
+    }
+
     pub fn next_thread_id(&self) -> Option<i32> {
         match self
             .thread_id
@@ -49,8 +208,27 @@ impl SiloCtx {
         }
     }
 
-    pub fn insert_thread(&self, id: Uuid, handle: Option<JoinHandle<()>>, metadata: Thread) {
-        self.threads.insert(id, ThreadData { handle, metadata });
+    pub fn insert_thread(
+        &self,
+        id: Uuid,
+        handle: Option<JoinHandle<()>>,
+        metadata: Thread,
+        params: ThreadParams,
+    ) {
+        self.threads.insert(
+            id,
+            ThreadData {
+                handle,
+                metadata,
+                params,
+            },
+        );
+    }
+
+    pub fn set_thread_handle(&self, id: Uuid, handle: JoinHandle<()>) {
+        if let Some(mut data) = self.threads.get_mut(&id) {
+            data.handle = Some(handle);
+        }
     }
 
     pub fn metadata(&self, thread_id: Uuid) -> Result<Thread, ErrNo> {
@@ -60,6 +238,14 @@ impl SiloCtx {
             .ok_or(ErrNo::ThreadNotFound)
     }
 
+    /// Parameters the thread was spawned with.
+    pub fn params(&self, thread_id: Uuid) -> Result<ThreadParams, ErrNo> {
+        self.threads
+            .get(&thread_id)
+            .map(|data| data.params.clone())
+            .ok_or(ErrNo::ThreadNotFound)
+    }
+
     pub fn threads(&self) -> Vec<Thread> {
         self.threads
             .iter()
@@ -67,27 +253,39 @@ impl SiloCtx {
             .collect()
     }
 
-    /// Waits for the task with the given ID to complete.
-    pub async fn wait_for_thread(&self, thread_id: Uuid) -> Result<(), ErrNo> {
-        if let Some(mut entry) = self.threads.get_mut(&thread_id) {
-            // Take the handle out so we can await it
-            if let Some(handle) = entry.handle.take() {
-                match handle.await {
-                    Ok(_) => Ok(()),
-                    Err(err) => {
-                        log::warn!("thread {} failed: {:?}", thread_id, err);
-                        Err(ErrNo::ThreadFailed)
-                    }
+    /// Waits for the task with the given ID to complete, and for the threads it was
+    /// restarted as after failing. Returns the ID of the last thread.
+    pub async fn wait_for_thread(&self, mut thread_id: Uuid) -> Result<Uuid, ErrNo> {
+        loop {
+            // Take the handle out so we can await it without holding the map entry
+            let handle = match self.threads.get_mut(&thread_id) {
+                Some(mut entry) => entry.handle.take(),
+                None => return Err(ErrNo::ThreadNotFound),
+            };
+
+            if let Some(handle) = handle {
+                if let Err(err) = handle.await {
+                    log::warn!("thread {} failed: {:?}", thread_id, err);
+                    return Err(ErrNo::ThreadFailed);
                 }
             } else {
                 log::warn!("thread {} already awaited or never started", thread_id);
-                Err(ErrNo::ThreadNotFound)
+                return Err(ErrNo::ThreadNotFound);
+            }
+
+            let restarted_as = self.metadata(thread_id)?.restarted_as;
+            match restarted_as.and_then(|id| Uuid::parse_str(&id).ok()) {
+                Some(restarted_as) => thread_id = restarted_as,
+                None => return Ok(thread_id),
             }
-        } else {
-            Err(ErrNo::ThreadNotFound)
         }
     }
 
+    /// The threads as json, for the thread admin api.
+    pub fn threads_json(&self) -> serde_json::Value {
+        serde_json::Value::Array(self.threads().iter().map(thread_json).collect())
+    }
+
     /// Kills the task with the given ID.
     pub fn kill_thread(&self, thread_id: Uuid) -> Result<(), ErrNo> {
         if let Some(mut data) = self.threads.get_mut(&thread_id) {
@@ -114,6 +312,15 @@ impl SiloCtx {
         }
     }
 
+    pub fn update_restarted_as(&self, thread_id: Uuid, restarted_as: String) -> Result<()> {
+        if let Some(mut data) = self.threads.get_mut(&thread_id) {
+            data.metadata.restarted_as = Some(restarted_as);
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Thread not found"))
+        }
+    }
+
     pub fn update_output(&self, thread_id: Uuid, output: Vec<u8>) -> Result<()> {
         if let Some(mut data) = self.threads.get_mut(&thread_id) {
             data.metadata.output = output;
@@ -191,6 +398,17 @@ pub enum ErrNo {
     FailedToSpawnProcess = 10,
     FailedToCreateThreadResource = 11,
     Failed,
+    InvalidPreopen = 13,
+    Disabled = 14,
+    InvalidSignature = 15,
+    ArtifactNotFound = 16,
+    InvalidArtifactName = 17,
+    FailedToWriteArtifact = 18,
+    MissingOutDir = 19,
+    SessionNotFound = 20,
+    SessionInUse = 21,
+    StdinClosed = 22,
+    ThreadRunning = 23,
 }
 
 impl From<ErrNo> for u32 {
@@ -198,3 +416,17 @@ impl From<ErrNo> for u32 {
         code as u32
     }
 }
+
+/// A thread as json, for the admin apis.
+pub fn thread_json(thread: &Thread) -> serde_json::Value {
+    serde_json::json!({
+        "id": thread.id,
+        "morph": thread.pkg,
+        "function": thread.function,
+        "args": thread.args,
+        "status": crate::status::thread_status(&thread.status),
+        "attempt": thread.attempt,
+        "respawned_from": thread.respawned_from,
+        "restarted_as": thread.restarted_as,
+    })
+}
@@ -1,15 +1,28 @@
 use super::silo::ErrNo;
-use crate::silo::bindings::{process, threads};
-use crate::silo::{SiloImpl, SiloView};
+use crate::component_cache::CompiledComponents;
+use crate::engine::REACTOR_ARG_TYPES;
+use crate::mounts::{self, MountPerms};
+use crate::session_input::session_inputs;
+use crate::session_output::session_outputs;
+use crate::silo::bindings::{artifacts, process, sessions, threads, types, types::PreopenPerms};
+use crate::silo::sessions::{SessionFile, SessionStore};
+use crate::silo::silo::{RestartPolicy, ThreadParams};
+use crate::silo::spawned::SpawnOptions;
+use crate::silo::{SiloCtx, SiloImpl, SiloView};
 
 use hayride_host_traits::silo::{Thread, ThreadStatus};
+use hayride_utils::wit::parser::WitParser;
 
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
+use tracing::Instrument;
 use uuid::Uuid;
 
 use wasmtime::component::Resource;
+use wasmtime_wasi::p2::DynInputStream;
 
 #[cfg(unix)]
 use nix::sys::signal::Signal;
@@ -35,22 +48,38 @@ where
         args: Vec<String>,
         envs: Vec<(String, String)>,
     ) -> Result<i32, process::ErrNo> {
-        let mut cmd = Command::new(name);
-        cmd.args(args);
-
-        // Add environment variables to the command
-        for (key, value) in envs {
-            cmd.env(key, value);
-        }
+        self.spawn_process(name, args, envs, SpawnOptions::default())
+    }
 
-        // Spawn a process and return the pid
-        let child = cmd.spawn().map_err(|_| ErrNo::FailedToSpawnProcess)?;
+    fn spawn_with_options(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+        options: process::SpawnOptions,
+    ) -> Result<i32, process::ErrNo> {
+        let options = SpawnOptions {
+            capture_output: options.capture_output,
+            stdin: options.stdin,
+        };
+        self.spawn_process(name, args, envs, options)
+    }
 
-        Ok(child.id() as i32)
+    fn read_output(&mut self, pid: u32) -> Result<process::ProcessOutput, process::ErrNo> {
+        let output = self.ctx().processes.read_output(pid)?;
+        Ok(process::ProcessOutput {
+            stdout: output.stdout,
+            stderr: output.stderr,
+            closed: output.closed,
+        })
     }
 
     fn wait(&mut self, pid: u32) -> Result<i32, process::ErrNo> {
-        wait_impl(pid)
+        let result = wait_impl(pid);
+        if result.is_ok() {
+            self.ctx().processes.forget(pid);
+        }
+        result
     }
 
     fn status(&mut self, pid: u32) -> Result<bool, process::ErrNo> {
@@ -60,6 +89,46 @@ where
     fn kill(&mut self, pid: u32, sig: i32) -> Result<i32, process::ErrNo> {
         kill_impl(pid, sig)
     }
+
+    fn kill_tree(&mut self, pid: u32, sig: i32) -> Result<i32, process::ErrNo> {
+        self.ctx().processes.kill_tree(pid, sig)?;
+        Ok(pid as i32)
+    }
+}
+
+impl<T> SiloImpl<T>
+where
+    T: SiloView,
+{
+    fn spawn_process(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+        options: SpawnOptions,
+    ) -> Result<i32, process::ErrNo> {
+        if !self.ctx().capabilities.silo_spawn {
+            log::warn!("denied process spawn of {}, silo spawns are disabled", name);
+            return Err(ErrNo::Disabled.into());
+        }
+
+        let mut cmd = Command::new(name);
+        cmd.args(args);
+
+        // Add environment variables to the command
+        for (key, value) in envs {
+            cmd.env(key, value);
+        }
+
+        // Spawn a process and return the pid
+        let pid = self
+            .ctx()
+            .processes
+            .spawn(cmd, options)
+            .map_err(|_| ErrNo::FailedToSpawnProcess)?;
+
+        Ok(pid as i32)
+    }
 }
 
 #[cfg(unix)]
@@ -156,7 +225,7 @@ where
         Ok(thread.id.clone())
     }
 
-    fn wait(&mut self, thread: Resource<Thread>) -> Result<Vec<u8>, threads::ErrNo> {
+    async fn wait(&mut self, thread: Resource<Thread>) -> Result<Vec<u8>, threads::ErrNo> {
         let thread = self.table().get(&thread).map_err(|_| {
             return ErrNo::ThreadNotFound;
         })?;
@@ -165,163 +234,329 @@ where
             return ErrNo::InvalidThreadId;
         })?;
 
-        // Wait for the thread to complete
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Runtime::new()
-                .map_err(|_| ErrNo::EngineError)?
-                .block_on(async {
-                    let _ = self.ctx().wait_for_thread(id).await?;
+        // Wait for the thread to complete, and the threads it was restarted as
+        let id = self.ctx().wait_for_thread(id).await?;
 
-                    if let Some(out_dir) = &self.ctx().out_dir {
-                        // Read the output file and return the contents as bytes
-                        let output_path = out_dir.clone() + "/" + &id.to_string() + "/out";
-                        let result = get_file_as_byte_vec(&output_path);
+        if let Some(out_dir) = &self.ctx().out_dir {
+            // Read the output file and return the contents as bytes
+            let output_path = out_dir.clone() + "/" + &id.to_string() + "/out";
+            let result = session_outputs().read(Path::new(&output_path));
 
-                        return Ok(result);
-                    }
+            return Ok(result);
+        }
 
-                    return Ok(vec![]);
-                })
-        })
+        return Ok(vec![]);
     }
 
     fn drop(&mut self, thread: Resource<Thread>) -> wasmtime::Result<()> {
-        self.table().delete(thread)?;
+        let thread = self.table().delete(thread)?;
+
+        // Like the write end of a pipe, dropping the thread closes its stdin
+        if let Ok(path) = self.thread_input(&thread.id) {
+            let _ = session_inputs().close(&path);
+        }
         Ok(())
     }
 }
 
-impl<T> threads::Host for SiloImpl<T>
-where
-    T: SiloView,
-{
-    fn spawn(
-        &mut self,
-        morph: String,
-        function: String,
-        mut args: Vec<String>,
-        envs: Vec<(String, String)>,
-    ) -> Result<Resource<Thread>, threads::ErrNo> {
-        log::debug!(
-            "executing spawn: {} with function: {}, and args: {:?}",
-            morph,
-            function,
-            args
-        );
-
-        // add the morph as the first argument
-        args.insert(0, morph.clone());
-
+impl SiloCtx {
+    // Find a morph in the registry, pulling it from the remote registry if missing
+    async fn find_morph(&self, morph: &str) -> Result<PathBuf, ErrNo> {
         let mut path = hayride_utils::paths::hayride::default_hayride_dir().map_err(|_err| {
             return ErrNo::MissingHomedir;
         })?;
-        path.push(self.ctx().registry_path.clone());
-        let path = hayride_utils::paths::registry::find_morph_path(
+        path.push(self.registry_path.clone());
+        crate::core::registry::find_or_pull(
+            self.registry.as_ref(),
             path.to_str()
                 .ok_or_else(|| ErrNo::FailedToFindRegistry)?
                 .to_string(),
-            morph.as_str(),
+            morph,
         )
+        .await
         .map_err(|_err| {
             return ErrNo::MorphNotFound;
-        })?;
+        })
+    }
 
-        let out_dir = self.ctx().out_dir.clone();
-        let model_path = self.ctx().model_path.clone();
+    /// Start a thread of the morph, which is attempt `attempt` of running it.
+    pub async fn start_thread(
+        &self,
+        params: ThreadParams,
+        respawned_from: Option<String>,
+        attempt: u32,
+    ) -> Result<Thread, ErrNo> {
+        if !self.capabilities.silo_spawn {
+            log::warn!("denied spawn of {}, silo spawns are disabled", params.morph);
+            return Err(ErrNo::Disabled);
+        }
 
-        // Setup the engine
-        let wasmtime_engine = wasmtime::Engine::new(
-            wasmtime::Config::new()
-                .wasm_component_model(true)
-                .async_support(true),
-        )
-        .map_err(|_err| {
-            return ErrNo::EngineError;
+        let path = self.find_morph(&params.morph).await?;
+        self.run_thread(path, params, respawned_from, attempt)
+    }
+
+    // Run a thread of the morph found at `path`, restarts reuse the path of the first attempt
+    fn run_thread(
+        &self,
+        path: PathBuf,
+        params: ThreadParams,
+        respawned_from: Option<String>,
+        attempt: u32,
+    ) -> Result<Thread, ErrNo> {
+        let ThreadParams {
+            morph,
+            function,
+            mut args,
+            envs,
+            mounts,
+            restart,
+        } = params.clone();
+
+        log::debug!(
+            "executing spawn: {} with function: {}, and args: {:?}",
+            morph,
+            function,
+            args
+        );
+
+        // add the morph as the first argument
+        args.insert(0, morph.clone());
+
+        // Reject the spawn up front rather than failing the thread
+        let bytes = std::fs::read(&path).map_err(|_| ErrNo::MorphNotFound)?;
+        self.verifier.check(&path, &bytes).map_err(|e| {
+            log::warn!("denied spawn of {}: {}", morph, e);
+            ErrNo::InvalidSignature
         })?;
-        let engine =
-            crate::engine::EngineBuilder::new(wasmtime_engine, self.ctx().registry_path.clone())
-                .out_dir(out_dir.clone())
-                .model_path(model_path)
-                .ai_enabled(true)
-                .mcp_enabled(true)
-                // Disable silo for spawned morphs
-                .silo_enabled(false)
-                .wac_enabled(true)
-                .wasi_enabled(true)
-                .envs(envs.clone())
-                .build()
+
+        let out_dir = self.out_dir.clone();
+        let model_path = self.model_path.clone();
+        let mounts = mounts
+            .into_iter()
+            .map(|m| (m.host_path, m.guest_path, m.perms))
+            .collect();
+        // Spawned threads may not reach more hosts than their parent
+        let egress = self.egress.as_ref().clone();
+        let output_filter = self.output_filter.clone();
+        let settings = self.settings.clone();
+        let registry = self.registry.clone();
+        let verifier = self.verifier.clone();
+
+        // Setup the engine, reusing the wasmtime engine and compiled components of the
+        // parent so spawning a morph again skips compiling it
+        let compiled = match &self.compiled {
+            Some(compiled) => compiled.clone(),
+            None => {
+                let wasmtime_engine = wasmtime::Engine::new(
+                    wasmtime::Config::new()
+                        .wasm_component_model(true)
+                        .async_support(true),
+                )
                 .map_err(|_err| {
                     return ErrNo::EngineError;
                 })?;
+                CompiledComponents::new(wasmtime_engine)
+            }
+        };
+        let engine = crate::engine::EngineBuilder::shared(compiled, self.registry_path.clone())
+            .out_dir(out_dir.clone())
+            .model_path(model_path)
+            .ai_enabled(true)
+            .mcp_enabled(true)
+            // Disable silo for spawned morphs
+            .silo_enabled(false)
+            .wac_enabled(true)
+            .template_enabled(true)
+            .validate_enabled(true)
+            .kv_enabled(true)
+            .blob_enabled(true)
+            .wasi_enabled(true)
+            .envs(envs.clone())
+            // Mounts already hold the preopens the thread is granted
+            .default_mounts(None)
+            .mounts(mounts)
+            .egress(egress)
+            .http_client(self.http_client.clone())
+            .output_filter(output_filter)
+            .settings(settings)
+            .registry(registry)
+            .verifier(verifier)
+            .json_lines(self.json_lines)
+            .log_stdio(self.log_stdio)
+            .log_level(self.log_level.clone())
+            .morph_log_levels(self.morph_log_levels.clone())
+            // The parent drives the stdin of the thread
+            .interactive_stdin(true)
+            .component_cache(self.component_cache.clone())
+            .build()
+            .map_err(|_err| {
+                return ErrNo::EngineError;
+            })?;
 
         log::debug!("Running engine with id: {}", engine.id);
         let thread_id = engine.id;
 
+        // The thread continues the trace of the request or thread spawning it
+        let span = tracing::info_span!(
+            "silo.thread",
+            thread.id = %thread_id,
+            morph = %morph,
+            function = %function,
+        );
+
+        // Threads past the concurrency limits wait for a running thread to finish
+        let limits = self.thread_limits.clone();
+        let start = limits.try_acquire(&morph).ok_or_else(|| {
+            log::debug!("queueing thread {} of {}", thread_id, morph);
+            limits.enqueue(thread_id)
+        });
+
         // Create the Thread resource
         let thread = Thread {
             id: thread_id.to_string(),
-            pkg: morph,
+            pkg: morph.clone(),
             function: function.clone(),
             args: args.clone(),
-            status: ThreadStatus::Processing,
+            status: match start {
+                Ok(_) => ThreadStatus::Processing,
+                Err(_) => ThreadStatus::Queued,
+            },
             output: vec![],
+            respawned_from,
+            attempt,
+            restarted_as: None,
         };
 
-        let ctx = self.ctx().clone();
+        // Insert the thread before it runs, so it can update its status, keeping the
+        // parameters to respawn it
+        self.insert_thread(thread_id, None, thread.clone(), params.clone());
+
+        let ctx = self.clone();
         // run engine in a separate thread
-        let handle: tokio::task::JoinHandle<()> = tokio::task::spawn(async move {
-            match engine
-                .run(path.clone(), function.clone(), &args.clone())
-                .await
-            {
-                Ok(result) => {
-                    // If out_dir is set, write a result file
-                    if let Some(out_dir) = &out_dir {
-                        // Create the output directory if it doesn't exist
-                        let output_path =
-                            out_dir.clone() + "/" + &thread_id.to_string() + "/result";
-                        match File::create(output_path) {
-                            Ok(mut file) => {
-                                // Write the result to the file
-                                if let Err(e) = file.write_all(&result) {
-                                    log::warn!("Failed to write to output file: {:?}", e);
+        let handle: tokio::task::JoinHandle<()> = tokio::task::spawn(
+            async move {
+                // Held until the thread finishes
+                let permit = match start {
+                    Ok(permit) => permit,
+                    Err(queued) => {
+                        let permit = queued.acquire(&morph).await;
+                        ctx.update_status(thread_id, ThreadStatus::Processing)
+                            .map_err(|err| {
+                                log::warn!(
+                                    "error updating thread status after queueing: {:?}",
+                                    err
+                                );
+                            })
+                            .unwrap_or_default();
+                        permit
+                    }
+                };
+
+                // Spawned servers keep the thread running until they stop serving
+                let result = match engine
+                    .run(path.clone(), function.clone(), &args.clone())
+                    .await
+                {
+                    Ok(outcome) => outcome.wait().await,
+                    Err(e) => Err(e),
+                };
+                let status = match result {
+                    Ok(result) => {
+                        // If out_dir is set, write a result file
+                        if let Some(out_dir) = &out_dir {
+                            // Create the output directory if it doesn't exist
+                            let output_path =
+                                out_dir.clone() + "/" + &thread_id.to_string() + "/result";
+                            match File::create(output_path) {
+                                Ok(mut file) => {
+                                    // Write the result to the file
+                                    if let Err(e) = file.write_all(&result) {
+                                        log::warn!("Failed to write to output file: {:?}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    log::warn!("Failed to create output file: {:?}", e);
                                 }
-                            }
-                            Err(e) => {
-                                log::warn!("Failed to create output file: {:?}", e);
                             }
                         }
-                    }
 
-                    ctx.update_output(thread_id, result.clone())
+                        ctx.update_output(thread_id, result.clone())
+                            .map_err(|err| {
+                                log::warn!("error updating thread output: {:?}", err);
+                            })
+                            .unwrap_or_default();
+                        ThreadStatus::Exited
+                    }
+                    Err(e) => {
+                        // If the engine fails, log the error
+                        crate::status::status().thread_error();
+                        log::warn!(
+                            "error running component {:?} with function: {:?} and args: {:?}: {:?}",
+                            path,
+                            function,
+                            args,
+                            e
+                        );
+                        ThreadStatus::Failed
+                    }
+                };
+                drop(permit);
+
+                // Update the thread status to Exited or Failed
+                let failed = status == ThreadStatus::Failed;
+                ctx.update_status(thread_id, status)
+                    .map_err(|err| {
+                        log::warn!("error updating thread status after exiting: {:?}", err);
+                    })
+                    .unwrap_or_default();
+
+                // Failed threads are restarted as a new thread under their policy
+                let Some(backoff) = restart.backoff(attempt).filter(|_| failed) else {
+                    return;
+                };
+                log::debug!(
+                    "restarting thread {} of {} in {:?}",
+                    thread_id,
+                    morph,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                match ctx.run_thread(path, params, Some(thread_id.to_string()), attempt + 1) {
+                    Ok(thread) => ctx
+                        .update_restarted_as(thread_id, thread.id)
                         .map_err(|err| {
-                            log::warn!("error updating thread output: {:?}", err);
+                            log::warn!("error updating thread after restarting: {:?}", err);
                         })
-                        .unwrap_or_default();
-                }
-                Err(e) => {
-                    // If the engine fails, log the error
-                    log::warn!(
-                        "error running component {:?} with function: {:?} and args: {:?}: {:?}",
-                        path,
-                        function,
-                        args,
-                        e
-                    );
+                        .unwrap_or_default(),
+                    Err(e) => {
+                        log::warn!(
+                            "failed to restart thread {}: error {}",
+                            thread_id,
+                            u32::from(e)
+                        );
+                    }
                 }
             }
-
-            // Update the thread status to Exited
-            ctx.update_status(thread_id, ThreadStatus::Exited)
-                .map_err(|err| {
-                    log::warn!("error updating thread status after exiting: {:?}", err);
-                })
-                .unwrap_or_default();
-        });
+            .instrument(span),
+        );
 
         // Insert the thread handle into the thread map
-        self.ctx()
-            .insert_thread(thread_id, Some(handle), thread.clone());
+        self.set_thread_handle(thread_id, handle);
+
+        Ok(thread)
+    }
+}
+
+impl<T> SiloImpl<T>
+where
+    T: SiloView,
+{
+    async fn spawn_thread(
+        &mut self,
+        params: ThreadParams,
+        respawned_from: Option<String>,
+    ) -> Result<Resource<Thread>, threads::ErrNo> {
+        let thread = self.ctx().start_thread(params, respawned_from, 1).await?;
 
         // Push the thread resource to the table
         let id = self.table().push(thread).map_err(|_| {
@@ -331,6 +566,146 @@ where
         // Return Thread resource ID
         Ok(id)
     }
+}
+
+impl<T> threads::Host for SiloImpl<T>
+where
+    T: SiloView,
+{
+    async fn spawn(
+        &mut self,
+        morph: String,
+        function: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+    ) -> Result<Resource<Thread>, threads::ErrNo> {
+        // Spawned threads get the same mounts as their parent
+        let mounts = self.ctx().mounts.clone();
+        let params = ThreadParams {
+            morph,
+            function,
+            args,
+            envs,
+            mounts,
+            restart: RestartPolicy::Never,
+        };
+        self.spawn_thread(params, None).await
+    }
+
+    async fn spawn_with_preopens(
+        &mut self,
+        morph: String,
+        function: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+        preopens: Vec<threads::Preopen>,
+    ) -> Result<Resource<Thread>, threads::ErrNo> {
+        let mut granted = vec![];
+        for preopen in preopens {
+            let perms = match preopen.perms {
+                PreopenPerms::ReadOnly => MountPerms::ReadOnly,
+                PreopenPerms::ReadWrite => MountPerms::ReadWrite,
+            };
+            let mount =
+                mounts::resolve(&self.ctx().mounts, &preopen.path, preopen.guest_path, perms)
+                    .ok_or_else(|| {
+                        log::warn!("denied preopen of {} for spawned thread", preopen.path);
+                        ErrNo::InvalidPreopen
+                    })?;
+            granted.push(mount);
+        }
+
+        let params = ThreadParams {
+            morph,
+            function,
+            args,
+            envs,
+            mounts: granted,
+            restart: RestartPolicy::Never,
+        };
+        self.spawn_thread(params, None).await
+    }
+
+    async fn spawn_supervised(
+        &mut self,
+        morph: String,
+        function: String,
+        args: Vec<String>,
+        envs: Vec<(String, String)>,
+        policy: threads::RestartPolicy,
+    ) -> Result<Resource<Thread>, threads::ErrNo> {
+        let mounts = self.ctx().mounts.clone();
+        let params = ThreadParams {
+            morph,
+            function,
+            args,
+            envs,
+            mounts,
+            restart: match policy {
+                threads::RestartPolicy::Never => RestartPolicy::Never,
+                threads::RestartPolicy::OnFailure(limits) => RestartPolicy::OnFailure {
+                    max_retries: limits.max_retries,
+                    backoff: Duration::from_millis(limits.backoff_ms),
+                },
+            },
+        };
+        self.spawn_thread(params, None).await
+    }
+
+    async fn respawn(&mut self, thread_id: String) -> Result<Resource<Thread>, threads::ErrNo> {
+        let id = Uuid::parse_str(&thread_id).map_err(|_| ErrNo::InvalidThreadId)?;
+
+        // Only threads that finished are run again
+        let thread = self.ctx().metadata(id)?;
+        if matches!(
+            thread.status,
+            ThreadStatus::Queued | ThreadStatus::Processing
+        ) {
+            return Err(ErrNo::ThreadRunning.into());
+        }
+
+        let params = self.ctx().params(id)?;
+        log::debug!("respawning thread {} of {}", thread_id, params.morph);
+        self.spawn_thread(params, Some(thread_id)).await
+    }
+
+    async fn describe(
+        &mut self,
+        morph: String,
+    ) -> Result<Vec<threads::FunctionSignature>, threads::ErrNo> {
+        let path = self.ctx().find_morph(&morph).await?;
+        let bytes = std::fs::read(&path).map_err(|_| ErrNo::MorphNotFound)?;
+        let wit = WitParser::new(bytes).map_err(|e| {
+            log::warn!("failed to decode {}: {:?}", morph, e);
+            ErrNo::Failed
+        })?;
+
+        let signatures = wit
+            .function_exports()
+            .iter()
+            .map(|f| threads::FunctionSignature {
+                interface: f.interface.as_ref().and_then(|i| i.name.clone()),
+                name: f.function.name.clone(),
+                invocable: f
+                    .signature
+                    .params
+                    .iter()
+                    .all(|(_, ty)| REACTOR_ARG_TYPES.contains(&ty.as_str())),
+                params: f
+                    .signature
+                    .params
+                    .iter()
+                    .map(|(name, ty)| types::Param {
+                        name: name.clone(),
+                        type_: ty.clone(),
+                    })
+                    .collect(),
+                result: f.signature.result.clone(),
+            })
+            .collect();
+
+        Ok(signatures)
+    }
 
     fn status(&mut self, thread_id: String) -> Result<threads::ThreadMetadata, threads::ErrNo> {
         let id = Uuid::parse_str(&thread_id).map_err(|_err| {
@@ -339,22 +714,9 @@ where
 
         // Get the thread metadata
         let thread = self.ctx().metadata(id)?;
+        let queue_position = self.ctx().thread_limits.position(id);
 
-        let metadata = threads::ThreadMetadata {
-            id: thread.id,
-            pkg: thread.pkg,
-            function: thread.function,
-            args: thread.args,
-            status: match thread.status {
-                ThreadStatus::Unknown => threads::ThreadStatus::Unknown,
-                ThreadStatus::Processing => threads::ThreadStatus::Processing,
-                ThreadStatus::Exited => threads::ThreadStatus::Exited,
-                ThreadStatus::Killed => threads::ThreadStatus::Killed,
-            },
-            output: thread.output,
-        };
-
-        Ok(metadata)
+        Ok(thread_metadata(thread, queue_position))
     }
 
     fn kill(&mut self, thread_id: String) -> Result<(), threads::ErrNo> {
@@ -367,25 +729,39 @@ where
         Ok(())
     }
 
+    fn write_stdin(&mut self, thread_id: String, data: Vec<u8>) -> Result<(), threads::ErrNo> {
+        let path = self.thread_input(&thread_id)?;
+        session_inputs().write(&path, &data).map_err(|e| {
+            log::warn!("failed to write stdin of thread {}: {:?}", thread_id, e);
+            ErrNo::StdinClosed
+        })?;
+
+        Ok(())
+    }
+
+    fn close_stdin(&mut self, thread_id: String) -> Result<(), threads::ErrNo> {
+        let path = self.thread_input(&thread_id)?;
+        session_inputs().close(&path).map_err(|e| {
+            log::warn!("failed to close stdin of thread {}: {:?}", thread_id, e);
+            ErrNo::StdinClosed
+        })?;
+
+        Ok(())
+    }
+
     fn group(&mut self) -> Result<Vec<threads::ThreadMetadata>, threads::ErrNo> {
         // Get all threads in the silo
         let threads = self.ctx().threads();
 
         // Map the threads to ThreadMetadata
+        let limits = self.ctx().thread_limits.clone();
         let metadata: Vec<threads::ThreadMetadata> = threads
-            .iter()
-            .map(|thread| threads::ThreadMetadata {
-                id: thread.id.clone(),
-                pkg: thread.pkg.clone(),
-                function: thread.function.clone(),
-                args: thread.args.clone(),
-                status: match thread.status {
-                    ThreadStatus::Unknown => threads::ThreadStatus::Unknown,
-                    ThreadStatus::Processing => threads::ThreadStatus::Processing,
-                    ThreadStatus::Exited => threads::ThreadStatus::Exited,
-                    ThreadStatus::Killed => threads::ThreadStatus::Killed,
-                },
-                output: thread.output.clone(),
+            .into_iter()
+            .map(|thread| {
+                let queue_position = Uuid::parse_str(&thread.id)
+                    .ok()
+                    .and_then(|id| limits.position(id));
+                thread_metadata(thread, queue_position)
             })
             .collect();
 
@@ -393,11 +769,193 @@ where
     }
 }
 
-fn get_file_as_byte_vec(filename: &String) -> Vec<u8> {
-    let mut f = File::open(&filename).expect("no file found");
-    let metadata = fs::metadata(&filename).expect("unable to read metadata");
-    let mut buffer = vec![0; metadata.len() as usize];
-    f.read(&mut buffer).expect("buffer overflow");
+fn thread_metadata(thread: Thread, queue_position: Option<u32>) -> threads::ThreadMetadata {
+    threads::ThreadMetadata {
+        id: thread.id,
+        pkg: thread.pkg,
+        function: thread.function,
+        args: thread.args,
+        status: match thread.status {
+            ThreadStatus::Unknown => threads::ThreadStatus::Unknown,
+            ThreadStatus::Queued => threads::ThreadStatus::Queued,
+            ThreadStatus::Processing => threads::ThreadStatus::Processing,
+            ThreadStatus::Exited => threads::ThreadStatus::Exited,
+            ThreadStatus::Failed => threads::ThreadStatus::Failed,
+            ThreadStatus::Killed => threads::ThreadStatus::Killed,
+        },
+        output: thread.output,
+        queue_position,
+        respawned_from: thread.respawned_from,
+        attempt: thread.attempt,
+        restarted_as: thread.restarted_as,
+    }
+}
+
+impl<T> SiloImpl<T>
+where
+    T: SiloView,
+{
+    // Artifacts of a thread this silo spawned
+    fn thread_artifacts(
+        &mut self,
+        thread_id: &str,
+    ) -> Result<crate::silo::artifacts::ArtifactStore, ErrNo> {
+        let id = Uuid::parse_str(thread_id).map_err(|_err| {
+            return ErrNo::InvalidThreadId;
+        })?;
+
+        // Only the parent of a thread may read its artifacts
+        self.ctx().metadata(id)?;
+        self.ctx().artifacts(id)
+    }
+
+    // Session in file a thread this silo spawned reads its stdin from
+    fn thread_input(&mut self, thread_id: &str) -> Result<PathBuf, ErrNo> {
+        let id = Uuid::parse_str(thread_id).map_err(|_| ErrNo::InvalidThreadId)?;
+
+        // Only the parent of a thread may write to its stdin
+        self.ctx().metadata(id)?;
+        let out_dir = self.ctx().out_dir.as_ref().ok_or(ErrNo::MissingOutDir)?;
+        Ok(Path::new(out_dir).join(id.to_string()).join("in"))
+    }
+
+    // Artifacts published by the calling thread
+    fn own_artifacts(&mut self) -> Result<crate::silo::artifacts::ArtifactStore, ErrNo> {
+        let session_id = self.ctx().session_id.ok_or(ErrNo::Failed)?;
+        self.ctx().artifacts(session_id)
+    }
+}
+
+impl<T> artifacts::Host for SiloImpl<T>
+where
+    T: SiloView,
+{
+    fn publish(&mut self, name: String, data: Vec<u8>) -> Result<String, artifacts::ErrNo> {
+        let artifact = self.own_artifacts()?.publish(&name, &data)?;
+        log::debug!("published artifact {} ({})", artifact.name, artifact.digest);
+
+        Ok(artifact.digest)
+    }
+
+    fn publish_file(&mut self, name: String, path: String) -> Result<String, artifacts::ErrNo> {
+        // Files may only be published from within the thread's own preopens
+        let mount = mounts::resolve(
+            &self.ctx().mounts,
+            &path,
+            path.clone(),
+            MountPerms::ReadOnly,
+        )
+        .ok_or_else(|| {
+            log::warn!("denied publishing {} as artifact {}", path, name);
+            ErrNo::InvalidPreopen
+        })?;
+        let artifact = self
+            .own_artifacts()?
+            .publish_file(&name, Path::new(&mount.host_path))?;
+        log::debug!("published artifact {} ({})", artifact.name, artifact.digest);
+
+        Ok(artifact.digest)
+    }
+
+    fn list(&mut self, thread_id: String) -> Result<Vec<types::Artifact>, artifacts::ErrNo> {
+        let artifacts = self
+            .thread_artifacts(&thread_id)?
+            .list()
+            .into_iter()
+            .map(|artifact| types::Artifact {
+                name: artifact.name,
+                digest: artifact.digest,
+                size: artifact.size,
+            })
+            .collect();
+
+        Ok(artifacts)
+    }
+
+    fn fetch(&mut self, thread_id: String, name: String) -> Result<Vec<u8>, artifacts::ErrNo> {
+        let data = self.thread_artifacts(&thread_id)?.fetch(&name)?;
 
-    buffer
+        Ok(data)
+    }
+}
+
+impl<T> SiloImpl<T>
+where
+    T: SiloView,
+{
+    fn sessions(&mut self) -> Result<SessionStore, ErrNo> {
+        let out_dir = self.ctx().out_dir.as_ref().ok_or(ErrNo::MissingOutDir)?;
+        Ok(SessionStore::new(out_dir))
+    }
+}
+
+impl<T> sessions::Host for SiloImpl<T>
+where
+    T: SiloView,
+{
+    fn list(&mut self) -> Result<Vec<types::SessionInfo>, sessions::ErrNo> {
+        let sessions = self
+            .sessions()?
+            .list()?
+            .into_iter()
+            .map(|session| types::SessionInfo {
+                id: session.id.to_string(),
+                size: session.size,
+                modified: session
+                    .modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default(),
+                running: session.running,
+                has_result: session.has_result,
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
+    fn read(
+        &mut self,
+        id: String,
+        file: types::SessionFile,
+    ) -> Result<Resource<DynInputStream>, sessions::ErrNo> {
+        let file = match file {
+            types::SessionFile::Stdin => SessionFile::Stdin,
+            types::SessionFile::Stdout => SessionFile::Stdout,
+            types::SessionFile::Stderr => SessionFile::Stderr,
+        };
+        let reader = self.sessions()?.reader(&id, file)?;
+
+        // Decompress on a blocking thread, feeding the stream as the guest reads it
+        let stream = crate::blocking_input_stream(reader, format!("session {}", id));
+        let stream = self.table().push(stream).map_err(|_| ErrNo::Failed)?;
+
+        Ok(stream)
+    }
+
+    fn result(&mut self, id: String) -> Result<Vec<u8>, sessions::ErrNo> {
+        let result = self.sessions()?.result(&id)?;
+
+        Ok(result)
+    }
+
+    fn delete(&mut self, id: String) -> Result<(), sessions::ErrNo> {
+        // The calling session and threads it spawned that are still running are kept
+        if let Ok(session_id) = Uuid::parse_str(&id) {
+            if self.ctx().session_id == Some(session_id)
+                || self.ctx().metadata(session_id).is_ok_and(|thread| {
+                    matches!(
+                        thread.status,
+                        ThreadStatus::Queued | ThreadStatus::Processing
+                    )
+                })
+            {
+                return Err(ErrNo::SessionInUse.into());
+            }
+        }
+        self.sessions()?.delete(&id)?;
+        log::debug!("deleted session {}", id);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+#[cfg(unix)]
+use nix::sys::signal::Signal;
+#[cfg(unix)]
+use nix::unistd::Pid;
+
+#[cfg(windows)]
+use std::os::windows::io::AsRawHandle;
+#[cfg(windows)]
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    },
+};
+
+/// Error number of a pid the store did not spawn.
+#[cfg(unix)]
+pub const NO_SUCH_PROCESS: u32 = nix::errno::Errno::ESRCH as u32;
+#[cfg(windows)]
+pub const NO_SUCH_PROCESS: u32 = windows_sys::Win32::Foundation::ERROR_INVALID_PARAMETER;
+
+// Output of a stream buffered until it is read, later output is dropped
+const MAX_CAPTURED: usize = 16 * 1024 * 1024;
+
+/// Processes a store spawned through `hayride:silo/process`.
+///
+/// Each process leads its own process group, or is assigned a job object on windows, so
+/// it can be killed together with the processes it started. Processes still running when
+/// the store drops are killed with their children and reaped.
+///
+/// Processes belong to the store that spawned them, a context cloned for another store
+/// starts without any.
+#[derive(Default)]
+pub struct SpawnedProcesses {
+    children: HashMap<u32, Spawned>,
+    // Captured output, kept after the process was waited on until it is read
+    outputs: HashMap<u32, Arc<Mutex<Captured>>>,
+}
+
+/// How a process is spawned.
+#[derive(Clone, Debug, Default)]
+pub struct SpawnOptions {
+    /// Capture stdout and stderr instead of inheriting them
+    pub capture_output: bool,
+    /// Written to stdin before it is closed, stdin is inherited if none
+    pub stdin: Option<Vec<u8>>,
+}
+
+/// Output captured from a process since it was last read.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProcessOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Both streams were closed and all their output was read
+    pub closed: bool,
+}
+
+#[derive(Default)]
+struct Captured {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    // Streams still open
+    open: usize,
+}
+
+struct Spawned {
+    child: Child,
+    #[cfg(windows)]
+    job: Job,
+}
+
+impl Clone for SpawnedProcesses {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl SpawnedProcesses {
+    /// Spawn the command as the leader of a new process group, returning its pid.
+    pub fn spawn(&mut self, mut cmd: Command, options: SpawnOptions) -> io::Result<u32> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        if options.capture_output {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        if options.stdin.is_some() {
+            cmd.stdin(Stdio::piped());
+        }
+
+        let mut child = cmd.spawn()?;
+        let pid = child.id();
+
+        // Pipes are drained and filled on their own threads, so a process blocked on one
+        // never blocks the store
+        if let (Some(data), Some(mut stdin)) = (options.stdin, child.stdin.take()) {
+            std::thread::spawn(move || {
+                if let Err(e) = stdin.write_all(&data) {
+                    log::debug!("failed to write stdin of process {}: {:?}", pid, e);
+                }
+            });
+        }
+        if options.capture_output {
+            let captured = Arc::new(Mutex::new(Captured::default()));
+            if let Some(stdout) = child.stdout.take() {
+                capture(stdout, captured.clone(), |captured| &mut captured.stdout);
+            }
+            if let Some(stderr) = child.stderr.take() {
+                capture(stderr, captured.clone(), |captured| &mut captured.stderr);
+            }
+            self.outputs.insert(pid, captured);
+        } else {
+            self.outputs.remove(&pid);
+        }
+
+        // Processes started before the job is assigned are not part of it
+        #[cfg(windows)]
+        let job = match Job::assign(&child) {
+            Ok(job) => job,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+
+        self.children.insert(
+            pid,
+            Spawned {
+                child,
+                #[cfg(windows)]
+                job,
+            },
+        );
+        Ok(pid)
+    }
+
+    pub fn contains(&self, pid: u32) -> bool {
+        self.children.contains_key(&pid)
+    }
+
+    /// Forget a process that was waited on, so its pid is not signalled once it is reused.
+    ///
+    /// Its captured output can still be read.
+    pub fn forget(&mut self, pid: u32) {
+        self.children.remove(&pid);
+    }
+
+    /// Take the output the process wrote since it was last read.
+    ///
+    /// Once both streams are closed and read the output is forgotten as well.
+    pub fn read_output(&mut self, pid: u32) -> Result<ProcessOutput, u32> {
+        let captured = self.outputs.get(&pid).ok_or(NO_SUCH_PROCESS)?;
+        let output = {
+            let mut captured = captured
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let closed = captured.open == 0;
+            ProcessOutput {
+                stdout: std::mem::take(&mut captured.stdout),
+                stderr: std::mem::take(&mut captured.stderr),
+                closed,
+            }
+        };
+        if output.closed {
+            self.outputs.remove(&pid);
+        }
+        Ok(output)
+    }
+
+    /// Signal the process and every process in its group, on windows the processes of
+    /// its job are terminated whatever the signal.
+    pub fn kill_tree(&self, pid: u32, sig: i32) -> Result<(), u32> {
+        let spawned = self.children.get(&pid).ok_or(NO_SUCH_PROCESS)?;
+        spawned.kill_tree(sig)
+    }
+}
+
+impl Spawned {
+    #[cfg(unix)]
+    fn kill_tree(&self, sig: i32) -> Result<(), u32> {
+        let signal = Signal::try_from(sig).map_err(|e| e as u32)?;
+        let group = Pid::from_raw(self.child.id() as i32);
+        nix::sys::signal::killpg(group, signal).map_err(|e| e as u32)
+    }
+
+    #[cfg(windows)]
+    fn kill_tree(&self, _sig: i32) -> Result<(), u32> {
+        self.job
+            .terminate()
+            .map_err(|e| e.raw_os_error().unwrap_or(1) as u32)
+    }
+}
+
+impl Drop for SpawnedProcesses {
+    fn drop(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+        log::debug!(
+            "killing {} processes left by a dropped store",
+            self.children.len()
+        );
+
+        let mut children = Vec::with_capacity(self.children.len());
+        for (_, spawned) in self.children.drain() {
+            #[cfg(unix)]
+            let _ = spawned.kill_tree(Signal::SIGKILL as i32);
+            // Closing the job kills its processes
+            #[cfg(windows)]
+            drop(spawned.job);
+            children.push(spawned.child);
+        }
+
+        // Reap the killed processes without blocking the runtime
+        std::thread::spawn(move || {
+            for mut child in children {
+                let _ = child.wait();
+            }
+        });
+    }
+}
+
+// Buffer the output of a pipe until it is closed
+fn capture(
+    mut pipe: impl Read + Send + 'static,
+    captured: Arc<Mutex<Captured>>,
+    buffer: fn(&mut Captured) -> &mut Vec<u8>,
+) {
+    if let Ok(mut captured) = captured.lock() {
+        captured.open += 1;
+    }
+    std::thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let mut captured = captured
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let buffer = buffer(&mut captured);
+            let kept = read.min(MAX_CAPTURED.saturating_sub(buffer.len()));
+            buffer.extend_from_slice(&chunk[..kept]);
+        }
+        let mut captured = captured
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        captured.open -= 1;
+    });
+}
+
+/// A job object killing its processes when it is closed.
+#[cfg(windows)]
+struct Job(HANDLE);
+
+// The handle is only used to terminate and close the job
+#[cfg(windows)]
+unsafe impl Send for Job {}
+
+#[cfg(windows)]
+impl Job {
+    fn assign(child: &Child) -> io::Result<Self> {
+        unsafe {
+            let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let job = Job(handle);
+
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let success = SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &info as *const JOBOBJECT_EXTENDED_LIMIT_INFORMATION as *const _,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            );
+            if success == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if AssignProcessToJobObject(job.0, child.as_raw_handle() as HANDLE) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(job)
+        }
+    }
+
+    fn terminate(&self) -> io::Result<()> {
+        match unsafe { TerminateJobObject(self.0, 1) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
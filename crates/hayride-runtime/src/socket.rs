@@ -0,0 +1,23 @@
+pub mod bindings;
+pub mod socket;
+mod socket_impl;
+
+pub use socket::SocketCtx;
+pub use socket::{SocketImpl, SocketView};
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: SocketView,
+{
+    crate::socket::bindings::frames::add_to_linker::<T, HasSocket<T>>(l, |x| SocketImpl(x))?;
+
+    Ok(())
+}
+
+struct HasSocket<T>(T);
+
+impl<T: 'static> HasData for HasSocket<T> {
+    type Data<'a> = SocketImpl<&'a mut T>;
+}
@@ -0,0 +1,8 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-socket",
+    });
+}
+
+pub use self::generated::hayride::socket::*;
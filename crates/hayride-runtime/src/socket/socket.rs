@@ -0,0 +1,78 @@
+use crate::websocket::FrameSender;
+use wasmtime::component::ResourceTable;
+
+/// The request of a multiplexed websocket connection a store handles.
+#[derive(Clone)]
+pub struct MuxRequest {
+    pub id: String,
+    pub channel: String,
+    pub frames: FrameSender,
+}
+
+#[derive(Clone, Default)]
+pub struct SocketCtx {
+    // None unless the store handles a request of a multiplexed connection
+    pub request: Option<MuxRequest>,
+}
+
+impl SocketCtx {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_request(mut self, request: MuxRequest) -> Self {
+        self.request = Some(request);
+        self
+    }
+}
+
+pub trait SocketView: Send {
+    /// Returns a mutable reference to the socket context.
+    fn ctx(&mut self) -> &mut SocketCtx;
+
+    /// Returns a mutable reference to the socket resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + SocketView> SocketView for &mut T {
+    fn ctx(&mut self) -> &mut SocketCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + SocketView> SocketView for Box<T> {
+    fn ctx(&mut self) -> &mut SocketCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:socket`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_sync`](crate::socket::add_to_linker_sync)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct SocketImpl<T>(pub T);
+
+impl<T: SocketView> SocketView for SocketImpl<T> {
+    fn ctx(&mut self) -> &mut SocketCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
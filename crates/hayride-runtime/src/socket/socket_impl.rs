@@ -0,0 +1,27 @@
+use crate::socket::bindings::frames;
+use crate::socket::{SocketImpl, SocketView};
+use crate::websocket::{Frame, FrameType};
+
+impl<T> frames::Host for SocketImpl<T>
+where
+    T: SocketView,
+{
+    fn current_request(&mut self) -> Option<frames::Request> {
+        self.ctx().request.as_ref().map(|request| frames::Request {
+            id: request.id.clone(),
+            channel: request.channel.clone(),
+        })
+    }
+
+    fn send(&mut self, channel: String, data: String) -> bool {
+        let Some(request) = &self.ctx().request else {
+            return false;
+        };
+        request.frames.send(&Frame {
+            id: request.id.clone(),
+            channel,
+            kind: FrameType::Data,
+            data,
+        })
+    }
+}
@@ -0,0 +1,24 @@
+pub mod bindings;
+pub mod body;
+pub mod sse;
+mod sse_impl;
+
+pub use sse::SseCtx;
+pub use sse::{SseImpl, SseView};
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: SseView,
+{
+    crate::sse::bindings::sse::add_to_linker::<T, HasSse<T>>(l, |x| SseImpl(x))?;
+
+    Ok(())
+}
+
+struct HasSse<T>(T);
+
+impl<T: 'static> HasData for HasSse<T> {
+    type Data<'a> = SseImpl<&'a mut T>;
+}
@@ -0,0 +1,18 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-sse",
+        imports: {
+            "hayride:http/sse/[method]event-stream.send": async | trappable,
+            default: trappable,
+        },
+        with: {
+            // Upstream package dependencies
+            "wasi:io": wasmtime_wasi::p2::bindings::io,
+
+            "hayride:http/sse/event-stream": crate::sse::sse::EventStream,
+        },
+    });
+}
+
+pub use self::generated::hayride::http::*;
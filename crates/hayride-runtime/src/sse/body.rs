@@ -0,0 +1,94 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame};
+use tokio::sync::mpsc;
+use tokio::time::{Instant, Sleep};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+// Idle time before a keepalive comment is sent, so proxies do not close the connection
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+const KEEPALIVE: &[u8] = b": keepalive\n\n";
+
+/// Content type of event stream responses.
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+/// Frame an event, with a `data` line for each line of the data.
+pub fn encode_event(
+    event: Option<&str>,
+    id: Option<&str>,
+    data: &str,
+    retry: Option<u32>,
+) -> Bytes {
+    let mut framed = String::new();
+    // Fields end at a line break, which would cut them short
+    if let Some(event) = event {
+        framed.push_str(&format!("event: {}\n", single_line(event)));
+    }
+    if let Some(id) = id {
+        framed.push_str(&format!("id: {}\n", single_line(id)));
+    }
+    if let Some(retry) = retry {
+        framed.push_str(&format!("retry: {}\n", retry));
+    }
+    for line in data.split('\n') {
+        framed.push_str(&format!(
+            "data: {}\n",
+            line.strip_suffix('\r').unwrap_or(line)
+        ));
+    }
+    framed.push('\n');
+    Bytes::from(framed)
+}
+
+fn single_line(field: &str) -> String {
+    field.replace(['\r', '\n'], " ")
+}
+
+/// Body of an event stream response, a frame for each event.
+///
+/// Ends once every sender of events is dropped.
+pub struct EventBody {
+    events: mpsc::Receiver<Bytes>,
+    keepalive: Pin<Box<Sleep>>,
+}
+
+impl EventBody {
+    pub fn new(events: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            events,
+            keepalive: Box::pin(tokio::time::sleep(KEEPALIVE_INTERVAL)),
+        }
+    }
+}
+
+impl Body for EventBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let event = match self.events.poll_recv(cx) {
+            Poll::Ready(Some(event)) => event,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {
+                if self.keepalive.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                Bytes::from_static(KEEPALIVE)
+            }
+        };
+
+        // Keepalives are only sent while no events are
+        self.keepalive
+            .as_mut()
+            .reset(Instant::now() + KEEPALIVE_INTERVAL);
+        Poll::Ready(Some(Ok(Frame::data(event))))
+    }
+}
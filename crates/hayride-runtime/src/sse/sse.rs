@@ -0,0 +1,155 @@
+use crate::sse::body::encode_event;
+use wasmtime::component::ResourceTable;
+use wasmtime_wasi::p2::StreamError;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+
+// Bytes of data that may be written at once, each write is sent as an event
+const WRITE_BUDGET: usize = 64 * 1024;
+
+/// An event stream a morph opened, sending the framed events to the response body.
+pub struct EventStream {
+    pub sender: mpsc::Sender<Bytes>,
+}
+
+#[derive(Default)]
+pub struct SseCtx {
+    // Sends the response to the request the store handles, none once it was sent
+    pub responder: Option<oneshot::Sender<hyper::Response<HyperOutgoingBody>>>,
+    /// Whether the store responded with an event stream
+    pub opened: bool,
+}
+
+impl SseCtx {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_responder(
+        mut self,
+        responder: oneshot::Sender<hyper::Response<HyperOutgoingBody>>,
+    ) -> Self {
+        self.responder = Some(responder);
+        self
+    }
+}
+
+pub trait SseView: Send {
+    /// Returns a mutable reference to the sse context.
+    fn ctx(&mut self) -> &mut SseCtx;
+
+    /// Returns a mutable reference to the sse resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + SseView> SseView for &mut T {
+    fn ctx(&mut self) -> &mut SseCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + SseView> SseView for Box<T> {
+    fn ctx(&mut self) -> &mut SseCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:http/sse`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_async`](crate::sse::add_to_linker_async)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct SseImpl<T>(pub T);
+
+impl<T: SseView> SseView for SseImpl<T> {
+    fn ctx(&mut self) -> &mut SseCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
+
+/// A stream of a morph whose writes are each sent as an unnamed event.
+///
+/// Writes wait for room in the queue of events like the event stream does.
+pub struct EventOutputStream {
+    sender: mpsc::Sender<Bytes>,
+    // Room in the queue for the next write, taken by check-write
+    permit: Option<mpsc::OwnedPermit<Bytes>>,
+}
+
+impl EventOutputStream {
+    pub fn new(sender: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            sender,
+            permit: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl wasmtime_wasi::p2::OutputStream for EventOutputStream {
+    fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
+        let data = std::str::from_utf8(&bytes).map_err(|e| {
+            StreamError::LastOperationFailed(anyhow::anyhow!("event data is not utf-8: {}", e))
+        })?;
+        let event = encode_event(None, None, data, None);
+
+        match self.permit.take() {
+            Some(permit) => {
+                permit.send(event);
+                Ok(())
+            }
+            None => self.sender.try_send(event).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => {
+                    StreamError::Trap(anyhow::anyhow!("write exceeded the permit of check-write"))
+                }
+                mpsc::error::TrySendError::Closed(_) => StreamError::Closed,
+            }),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), StreamError> {
+        // Events are sent as they are written
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> Result<usize, StreamError> {
+        if self.permit.is_none() {
+            match self.sender.clone().try_reserve_owned() {
+                Ok(permit) => self.permit = Some(permit),
+                Err(mpsc::error::TrySendError::Full(_)) => return Ok(0),
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(StreamError::Closed),
+            }
+        }
+        Ok(WRITE_BUDGET)
+    }
+}
+
+#[async_trait::async_trait]
+impl wasmtime_wasi::p2::Pollable for EventOutputStream {
+    async fn ready(&mut self) {
+        if self.permit.is_none() {
+            // A closed stream is reported by check-write
+            self.permit = self.sender.clone().reserve_owned().await.ok();
+        }
+    }
+}
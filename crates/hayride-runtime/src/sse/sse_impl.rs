@@ -0,0 +1,85 @@
+use crate::sse::bindings::sse::{self, ErrorCode, Event};
+use crate::sse::body::{encode_event, EventBody, CONTENT_TYPE};
+use crate::sse::sse::{EventOutputStream, EventStream};
+use crate::sse::{SseImpl, SseView};
+
+use http_body_util::BodyExt;
+use tokio::sync::mpsc;
+use wasmtime::component::Resource;
+use wasmtime::Result;
+use wasmtime_wasi::p2::DynOutputStream;
+
+// Events queued for the client before senders have to wait
+const EVENT_BUFFER: usize = 256;
+
+impl<T> sse::Host for SseImpl<T>
+where
+    T: SseView,
+{
+    fn open(&mut self) -> Result<Result<Resource<EventStream>, ErrorCode>> {
+        let ctx = self.ctx();
+        let Some(responder) = ctx.responder.take() else {
+            return match ctx.opened {
+                true => Ok(Err(ErrorCode::AlreadyOpen)),
+                false => Ok(Err(ErrorCode::Unsupported)),
+            };
+        };
+
+        let (sender, receiver) = mpsc::channel(EVENT_BUFFER);
+        let resp = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header("Content-Type", CONTENT_TYPE)
+            .header("Cache-Control", "no-cache")
+            // Proxies like nginx would hold events back otherwise
+            .header("X-Accel-Buffering", "no")
+            .body(EventBody::new(receiver).boxed())?;
+        if responder.send(resp).is_err() {
+            return Ok(Err(ErrorCode::Closed));
+        }
+        ctx.opened = true;
+
+        let stream = self.table().push(EventStream { sender })?;
+        Ok(Ok(stream))
+    }
+}
+
+impl<T> sse::HostEventStream for SseImpl<T>
+where
+    T: SseView,
+{
+    async fn send(
+        &mut self,
+        stream: Resource<EventStream>,
+        event: Event,
+    ) -> Result<Result<(), ErrorCode>> {
+        let sender = self.table().get(&stream)?.sender.clone();
+        let framed = encode_event(
+            event.event.as_deref(),
+            event.id.as_deref(),
+            &event.data,
+            event.retry,
+        );
+        match sender.send(framed).await {
+            Ok(()) => Ok(Ok(())),
+            Err(_) => Ok(Err(ErrorCode::Closed)),
+        }
+    }
+
+    fn data_stream(
+        &mut self,
+        stream: Resource<EventStream>,
+    ) -> Result<Result<Resource<DynOutputStream>, ErrorCode>> {
+        let sender = self.table().get(&stream)?.sender.clone();
+        if sender.is_closed() {
+            return Ok(Err(ErrorCode::Closed));
+        }
+        let output: DynOutputStream = Box::new(EventOutputStream::new(sender));
+        let output = self.table().push(output)?;
+        Ok(Ok(output))
+    }
+
+    fn drop(&mut self, stream: Resource<EventStream>) -> Result<()> {
+        self.table().delete(stream)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use hayride_db::statement_cache::statement_cache;
+use hayride_host_traits::silo::{Thread, ThreadStatus};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+// Path the status dashboard is served on by host servers
+pub const STATUS_PATH: &str = "/_hayride/status";
+
+// Number of recent sessions kept for the dashboard
+const MAX_SESSIONS: usize = 50;
+
+static STATUS: OnceLock<Status> = OnceLock::new();
+
+/// Returns the process wide runtime status.
+pub fn status() -> &'static Status {
+    STATUS.get_or_init(Status::new)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SessionState {
+    Running,
+    Exited,
+    Failed,
+}
+
+#[derive(Clone)]
+pub struct SessionStatus {
+    pub id: String,
+    pub morph: String,
+    pub started: SystemTime,
+    pub state: SessionState,
+}
+
+#[derive(Clone)]
+pub struct ModelStatus {
+    pub name: String,
+    pub backend: String,
+    pub loads: u64,
+    pub last_loaded: SystemTime,
+}
+
+/// Counters and recent activity of the runtime, rendered by the status dashboard.
+pub struct Status {
+    started: SystemTime,
+    requests: AtomicU64,
+    request_errors: AtomicU64,
+    model_errors: AtomicU64,
+    thread_errors: AtomicU64,
+    sessions: Mutex<VecDeque<SessionStatus>>,
+    models: dashmap::DashMap<String, ModelStatus>,
+}
+
+impl Status {
+    fn new() -> Self {
+        Self {
+            started: SystemTime::now(),
+            requests: AtomicU64::new(0),
+            request_errors: AtomicU64::new(0),
+            model_errors: AtomicU64::new(0),
+            thread_errors: AtomicU64::new(0),
+            sessions: Mutex::new(VecDeque::new()),
+            models: dashmap::DashMap::new(),
+        }
+    }
+
+    pub fn session_started(&self, id: String, morph: String) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            if sessions.len() >= MAX_SESSIONS {
+                sessions.pop_back();
+            }
+            sessions.push_front(SessionStatus {
+                id,
+                morph,
+                started: SystemTime::now(),
+                state: SessionState::Running,
+            });
+        }
+    }
+
+    pub fn session_finished(&self, id: &str, state: SessionState) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            if let Some(session) = sessions.iter_mut().find(|s| s.id == id) {
+                session.state = state;
+            }
+        }
+    }
+
+    pub fn model_loaded(&self, name: &str, backend: &str) {
+        let mut model = self
+            .models
+            .entry(name.to_string())
+            .or_insert_with(|| ModelStatus {
+                name: name.to_string(),
+                backend: backend.to_string(),
+                loads: 0,
+                last_loaded: SystemTime::now(),
+            });
+        model.backend = backend.to_string();
+        model.loads += 1;
+        model.last_loaded = SystemTime::now();
+    }
+
+    pub fn model_error(&self) {
+        self.model_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn request_error(&self) {
+        self.request_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn thread_error(&self) {
+        self.thread_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn sessions(&self) -> Vec<SessionStatus> {
+        self.sessions
+            .lock()
+            .map(|sessions| sessions.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn models(&self) -> Vec<ModelStatus> {
+        let mut models: Vec<ModelStatus> = self.models.iter().map(|m| m.value().clone()).collect();
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        models
+    }
+
+    /// Render the status dashboard as a simple html page.
+    pub fn render_html(&self, threads: &[Thread]) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str("<meta http-equiv=\"refresh\" content=\"10\">\n");
+        html.push_str("<title>Hayride Status</title>\n");
+        html.push_str(
+            "<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}\
+             th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}th{background:#eee}</style>\n",
+        );
+        html.push_str("</head>\n<body>\n<h1>Hayride Status</h1>\n");
+
+        html.push_str(&format!(
+            "<p>Uptime: {}</p>\n",
+            format_duration(self.started.elapsed().unwrap_or_default())
+        ));
+
+        // Error and cache counts
+        html.push_str("<h2>Counters</h2>\n<table>\n");
+        let counters = [
+            ("Requests", self.requests.load(Ordering::Relaxed)),
+            (
+                "Request errors",
+                self.request_errors.load(Ordering::Relaxed),
+            ),
+            ("Model errors", self.model_errors.load(Ordering::Relaxed)),
+            ("Thread errors", self.thread_errors.load(Ordering::Relaxed)),
+            ("Statement cache hits", statement_cache().hits()),
+            ("Statement cache misses", statement_cache().misses()),
+        ];
+        for (name, value) in counters {
+            html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", name, value));
+        }
+        html.push_str("</table>\n");
+
+        // Threads, active first
+        html.push_str("<h2>Threads</h2>\n");
+        if threads.is_empty() {
+            html.push_str("<p>No threads</p>\n");
+        } else {
+            let mut threads: Vec<&Thread> = threads.iter().collect();
+            threads.sort_by_key(|t| t.status != ThreadStatus::Processing);
+
+            html.push_str(
+                "<table>\n<tr><th>ID</th><th>Morph</th><th>Function</th><th>Args</th><th>Status</th></tr>\n",
+            );
+            for thread in threads {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&thread.id),
+                    escape_html(&thread.pkg),
+                    escape_html(&thread.function),
+                    escape_html(&thread.args.join(" ")),
+                    thread_status(&thread.status),
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        // Recent sessions
+        html.push_str("<h2>Recent Sessions</h2>\n");
+        let sessions = self.sessions();
+        if sessions.is_empty() {
+            html.push_str("<p>No sessions</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>ID</th><th>Morph</th><th>Started</th><th>State</th></tr>\n",
+            );
+            for session in sessions {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{} ago</td><td>{}</td></tr>\n",
+                    escape_html(&session.id),
+                    escape_html(&session.morph),
+                    format_duration(session.started.elapsed().unwrap_or_default()),
+                    session_state(session.state),
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        // Loaded models
+        html.push_str("<h2>Loaded Models</h2>\n");
+        let models = self.models();
+        if models.is_empty() {
+            html.push_str("<p>No models loaded</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>Model</th><th>Backend</th><th>Loads</th><th>Last loaded</th></tr>\n",
+            );
+            for model in models {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{} ago</td></tr>\n",
+                    escape_html(&model.name),
+                    escape_html(&model.backend),
+                    model.loads,
+                    format_duration(model.last_loaded.elapsed().unwrap_or_default()),
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+pub(crate) fn thread_status(status: &ThreadStatus) -> &'static str {
+    match status {
+        ThreadStatus::Unknown => "unknown",
+        ThreadStatus::Queued => "queued",
+        ThreadStatus::Processing => "processing",
+        ThreadStatus::Exited => "exited",
+        ThreadStatus::Failed => "failed",
+        ThreadStatus::Killed => "killed",
+    }
+}
+
+fn session_state(state: SessionState) -> &'static str {
+    match state {
+        SessionState::Running => "running",
+        SessionState::Exited => "exited",
+        SessionState::Failed => "failed",
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}h {}m {}s", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
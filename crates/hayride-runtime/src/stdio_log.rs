@@ -0,0 +1,135 @@
+use crate::session_output::SessionOutput;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use uuid::Uuid;
+use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
+
+// Target of logged output, so it can be filtered apart from host logs
+const LOG_TARGET: &str = "hayride_runtime::morph";
+
+// Longest line buffered, longer lines are logged in parts
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// Stdout or stderr of a component, logged through the host logger a line at a time.
+///
+/// Lines are tagged with the session id and the morph writing them, stdout is logged at
+/// info and stderr at warn. Output is also written to the session file when one is set.
+#[derive(Clone)]
+pub struct LogOutput {
+    lines: Arc<Mutex<LineBuffer>>,
+    file: Option<SessionOutput>,
+}
+
+impl LogOutput {
+    pub fn stdout(id: Uuid, morph: &str) -> Self {
+        Self::new(id, morph, log::Level::Info)
+    }
+
+    pub fn stderr(id: Uuid, morph: &str) -> Self {
+        Self::new(id, morph, log::Level::Warn)
+    }
+
+    fn new(id: Uuid, morph: &str, level: log::Level) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(LineBuffer {
+                id,
+                morph: morph.to_string(),
+                level,
+                buffer: Vec::new(),
+            })),
+            file: None,
+        }
+    }
+
+    /// Also write the output to a session file.
+    pub fn with_file(mut self, file: SessionOutput) -> Self {
+        self.file = Some(file);
+        self
+    }
+}
+
+impl AsyncWrite for LogOutput {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        if let Some(file) = this.file.as_mut() {
+            file.write_all(buf)?;
+        }
+        if let Ok(mut lines) = this.lines.lock() {
+            lines.push(buf);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut().file.as_mut() {
+            Some(file) => Poll::Ready(file.flush()),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl IsTerminal for LogOutput {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl StdoutStream for LogOutput {
+    fn async_stream(&self) -> Box<dyn AsyncWrite + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+struct LineBuffer {
+    id: Uuid,
+    morph: String,
+    level: log::Level,
+    // Output after the last complete line
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, buf: &[u8]) {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.log(&line[..end]);
+        }
+        if self.buffer.len() >= MAX_LINE_LEN {
+            let line = std::mem::take(&mut self.buffer);
+            self.log(&line);
+        }
+    }
+
+    fn log(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        log::log!(
+            target: LOG_TARGET,
+            self.level,
+            "[{}] {}: {}",
+            self.id,
+            self.morph,
+            line.trim_end_matches('\r')
+        );
+    }
+}
+
+impl Drop for LineBuffer {
+    // Output without a trailing newline is logged once the component is done
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            self.log(&self.buffer);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Spans of engine runs, requests, silo threads, ai and db calls, exported to an
+//! OpenTelemetry collector over OTLP/HTTP.
+//!
+//! Spans are created with `tracing`, under targets of the hayride crates, and exported
+//! through `tracing-opentelemetry`. Requests continue the W3C trace context of their
+//! `traceparent` header, so a trace follows a request from the client through the
+//! threads it spawns and the requests they send. Fields named `otel.kind`,
+//! `otel.status_code` and `otel.status_description` set the kind and status of a span.
+
+use anyhow::{anyhow, Result};
+use hayride_utils::config::TracingConfig;
+use hyper::HeaderMap;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Continue the trace of the `traceparent` header in the span, if spans are exported.
+pub fn continue_trace(span: &tracing::Span, headers: &HeaderMap) {
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    });
+    // Spans that are not exported have no context to continue
+    let _ = span.set_parent(parent);
+}
+
+/// Add the `traceparent` of the current span to the headers, if spans are exported.
+pub fn inject(headers: &mut HeaderMap) {
+    let context = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
+}
+
+/// Export spans to the collector configured, if any.
+///
+/// Spans are exported process wide, so only the first call installs an exporter.
+pub fn init(config: &TracingConfig) -> Result<()> {
+    let Some(endpoint) = &config.endpoint else {
+        return Ok(());
+    };
+    if PROVIDER.get().is_some() {
+        return Ok(());
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(exporter_url(endpoint))
+        .with_headers(config.headers.clone().into_iter().collect())
+        .build()
+        .map_err(|e| anyhow!("invalid otlp endpoint {}: {}", endpoint, e))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+    if PROVIDER.set(provider.clone()).is_err() {
+        return Ok(());
+    }
+
+    // Dependencies have their own spans, which would flood traces
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("hayride"))
+        .with_filter(tracing_subscriber::filter::filter_fn(|metadata| {
+            metadata.is_span() && metadata.target().starts_with("hayride")
+        }));
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .map_err(|e| anyhow!("failed to install span exporter: {}", e))?;
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    log::info!("exporting spans to {}", exporter_url(endpoint));
+    Ok(())
+}
+
+/// Export spans that finished so far, waiting until the collector received them.
+pub async fn flush() {
+    let Some(provider) = PROVIDER.get() else {
+        return;
+    };
+    // The exporter sends spans over a blocking client
+    let result = tokio::task::spawn_blocking(|| provider.force_flush()).await;
+    if let Ok(Err(e)) = result {
+        log::warn!("failed to export spans: {}", e);
+    }
+}
+
+// Collectors take traces at `/v1/traces` of the endpoint
+fn exporter_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    match endpoint.ends_with("/v1/traces") {
+        true => endpoint.to_string(),
+        false => format!("{}/v1/traces", endpoint),
+    }
+}
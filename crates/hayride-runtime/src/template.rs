@@ -0,0 +1,46 @@
+pub mod bindings;
+#[allow(clippy::module_inception)]
+pub mod template;
+mod template_impl;
+
+pub use template::TemplateCtx;
+pub use template::{TemplateImpl, TemplateView};
+
+use hayride_host_traits::template::TemplateTrait;
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: TemplateView,
+{
+    crate::template::bindings::template::add_to_linker::<T, HasTemplate<T>>(l, |x| {
+        TemplateImpl(x)
+    })?;
+
+    Ok(())
+}
+
+struct HasTemplate<T>(T);
+
+impl<T: 'static> HasData for HasTemplate<T> {
+    type Data<'a> = TemplateImpl<&'a mut T>;
+}
+
+pub struct TemplateBackend(Box<dyn TemplateTrait>);
+impl std::ops::Deref for TemplateBackend {
+    type Target = dyn TemplateTrait;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl std::ops::DerefMut for TemplateBackend {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut()
+    }
+}
+impl<T: TemplateTrait + 'static> From<T> for TemplateBackend {
+    fn from(value: T) -> Self {
+        Self(Box::new(value))
+    }
+}
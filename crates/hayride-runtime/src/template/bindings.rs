@@ -0,0 +1,14 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-template",
+        imports: {
+            default: trappable,
+        },
+        with: {
+            "hayride:template/template/error": hayride_host_traits::template::Error,
+        },
+    });
+}
+
+pub use self::generated::hayride::template::*;
@@ -0,0 +1,70 @@
+use wasmtime::component::ResourceTable;
+
+use super::TemplateBackend;
+
+pub struct TemplateCtx {
+    pub template_backend: TemplateBackend,
+}
+
+impl TemplateCtx {
+    pub fn new(registry_path: String) -> Self {
+        let template_backend: Box<hayride_template::TemplateBackend> =
+            Box::new(hayride_template::TemplateBackend::new(registry_path));
+        Self {
+            template_backend: TemplateBackend(template_backend),
+        }
+    }
+}
+
+pub trait TemplateView: Send {
+    /// Returns a mutable reference to the template context.
+    fn ctx(&mut self) -> &mut TemplateCtx;
+
+    /// Returns a mutable reference to the template resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + TemplateView> TemplateView for &mut T {
+    fn ctx(&mut self) -> &mut TemplateCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + TemplateView> TemplateView for Box<T> {
+    fn ctx(&mut self) -> &mut TemplateCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:template`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_async`](crate::add_to_linker_async)
+/// or
+/// [`add_to_linker_sync`](crate::add_to_linker_sync)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct TemplateImpl<T>(pub T);
+
+impl<T: TemplateView> TemplateView for TemplateImpl<T> {
+    fn ctx(&mut self) -> &mut TemplateCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
@@ -0,0 +1,77 @@
+use crate::template::bindings::{template, types::ErrorCode};
+use crate::template::{TemplateImpl, TemplateView};
+use hayride_host_traits::template::Error;
+
+use wasmtime::component::Resource;
+use wasmtime::Result;
+
+impl<T> template::Host for TemplateImpl<T>
+where
+    T: TemplateView,
+{
+    fn render(
+        &mut self,
+        source: String,
+        data: String,
+    ) -> Result<Result<String, Resource<template::Error>>, anyhow::Error> {
+        let result = self.ctx().template_backend.render(source, data);
+
+        match result {
+            Ok(output) => {
+                return Ok(Ok(output));
+            }
+            Err(error) => {
+                let id = self.table().push(error)?;
+                return Ok(Err(id));
+            }
+        }
+    }
+
+    fn render_named(
+        &mut self,
+        name: String,
+        data: String,
+    ) -> Result<Result<String, Resource<template::Error>>, anyhow::Error> {
+        let result = self.ctx().template_backend.render_named(name, data);
+
+        match result {
+            Ok(output) => {
+                return Ok(Ok(output));
+            }
+            Err(error) => {
+                let id = self.table().push(error)?;
+                return Ok(Err(id));
+            }
+        }
+    }
+}
+
+impl<T> template::HostError for TemplateImpl<T>
+where
+    T: TemplateView,
+{
+    fn code(&mut self, error: Resource<Error>) -> Result<ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            hayride_host_traits::template::ErrorCode::TemplateNotFound => {
+                Ok(ErrorCode::TemplateNotFound)
+            }
+            hayride_host_traits::template::ErrorCode::InvalidTemplate => {
+                Ok(ErrorCode::InvalidTemplate)
+            }
+            hayride_host_traits::template::ErrorCode::InvalidData => Ok(ErrorCode::InvalidData),
+            hayride_host_traits::template::ErrorCode::RenderFailed => Ok(ErrorCode::RenderFailed),
+            hayride_host_traits::template::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        return Ok(error.data.to_string());
+    }
+
+    fn drop(&mut self, error: Resource<Error>) -> Result<()> {
+        self.table().delete(error)?;
+        return Ok(());
+    }
+}
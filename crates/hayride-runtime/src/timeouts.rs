@@ -0,0 +1,83 @@
+use hayride_utils::config::TimeoutConfig;
+use std::future::Future;
+use std::time::Duration;
+
+// Environment variables with the timeout in seconds for each capability
+const AI_TIMEOUT: &str = "HAYRIDE_TIMEOUT_AI";
+const RAG_TIMEOUT: &str = "HAYRIDE_TIMEOUT_RAG";
+const DB_TIMEOUT: &str = "HAYRIDE_TIMEOUT_DB";
+const WAC_TIMEOUT: &str = "HAYRIDE_TIMEOUT_WAC";
+
+/// Deadlines for host calls per capability, calls without a deadline wait forever.
+///
+/// A call is aborted the next time it yields after its deadline, backends that block
+/// without yielding are only stopped once they return.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HostTimeouts {
+    pub ai: Option<Duration>,
+    pub rag: Option<Duration>,
+    pub db: Option<Duration>,
+    pub wac: Option<Duration>,
+}
+
+impl HostTimeouts {
+    /// Read the timeouts in seconds from `HAYRIDE_TIMEOUT_AI`, `HAYRIDE_TIMEOUT_RAG`,
+    /// `HAYRIDE_TIMEOUT_DB` and `HAYRIDE_TIMEOUT_WAC`.
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    pub fn from_config(config: &TimeoutConfig) -> Self {
+        let timeout = |secs: Option<f64>| secs.filter(|s| *s > 0.0).map(Duration::from_secs_f64);
+        Self {
+            ai: timeout(config.ai),
+            rag: timeout(config.rag),
+            db: timeout(config.db),
+            wac: timeout(config.wac),
+        }
+    }
+
+    /// Override the timeouts that are set in the environment.
+    pub fn with_env(self) -> Self {
+        Self {
+            ai: env_timeout(AI_TIMEOUT).or(self.ai),
+            rag: env_timeout(RAG_TIMEOUT).or(self.rag),
+            db: env_timeout(DB_TIMEOUT).or(self.db),
+            wac: env_timeout(WAC_TIMEOUT).or(self.wac),
+        }
+    }
+}
+
+/// A host call exceeded its deadline.
+#[derive(Debug)]
+pub struct Elapsed(pub Duration);
+
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "host call timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Await the future, giving up once the deadline has passed.
+pub async fn deadline<F: Future>(limit: Option<Duration>, future: F) -> Result<F::Output, Elapsed> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future).await.map_err(|_| {
+            log::warn!("host call timed out after {:?}", limit);
+            Elapsed(limit)
+        }),
+        None => Ok(future.await),
+    }
+}
+
+fn env_timeout(key: &str) -> Option<Duration> {
+    let value = std::env::var(key).ok()?;
+    match value.trim().parse::<f64>() {
+        Ok(secs) if secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+        _ => {
+            log::warn!("ignoring invalid timeout for {}: {}", key, value);
+            None
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::events::bus;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+use tokio::time::MissedTickBehavior;
+
+// Timers a morph may have scheduled at once
+const MAX_TIMERS_PER_MORPH: usize = 256;
+
+/// Shortest interval of repeating timers, so they never keep the runtime busy.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+static TIMERS: OnceLock<Timers> = OnceLock::new();
+
+/// Returns the process wide timers.
+pub fn timers() -> &'static Timers {
+    TIMERS.get_or_init(Timers::default)
+}
+
+/// Scheduling a timer failed.
+#[derive(Debug, PartialEq)]
+pub enum TimerError {
+    /// The interval of a repeating timer is shorter than [`MIN_INTERVAL`]
+    InvalidInterval,
+    /// The morph already has the most timers scheduled
+    TooManyTimers,
+}
+
+/// Timers publishing events on the bus when they fire, run on the tokio runtime.
+///
+/// Timers belong to the morph that scheduled them, only it can cancel them, and outlive
+/// the store that scheduled them.
+#[derive(Default)]
+pub struct Timers {
+    next_id: AtomicU64,
+    scheduled: Mutex<HashMap<u64, Scheduled>>,
+}
+
+struct Scheduled {
+    owner: String,
+    handle: AbortHandle,
+}
+
+impl Timers {
+    /// Publish data on the topic after the delay, then every interval if one is set,
+    /// returning the id of the timer.
+    pub fn schedule(
+        &self,
+        owner: &str,
+        delay: Duration,
+        interval: Option<Duration>,
+        topic: String,
+        data: Vec<u8>,
+    ) -> Result<u64, TimerError> {
+        if interval.is_some_and(|interval| interval < MIN_INTERVAL) {
+            return Err(TimerError::InvalidInterval);
+        }
+
+        let mut scheduled = self
+            .scheduled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let owned = scheduled
+            .values()
+            .filter(|timer| timer.owner == owner)
+            .count();
+        if owned >= MAX_TIMERS_PER_MORPH {
+            return Err(TimerError::TooManyTimers);
+        }
+
+        // The lock is held until the timer is inserted, so a timer firing right away
+        // removes itself after it was inserted
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let Some(interval) = interval else {
+                bus().publish(&topic, data);
+                timers().remove(id);
+                return;
+            };
+
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                bus().publish(&topic, data.clone());
+            }
+        })
+        .abort_handle();
+
+        scheduled.insert(
+            id,
+            Scheduled {
+                owner: owner.to_string(),
+                handle,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Cancel a timer of the owner, returning whether it was still scheduled.
+    pub fn cancel(&self, owner: &str, id: u64) -> bool {
+        let mut scheduled = self
+            .scheduled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match scheduled.get(&id) {
+            Some(timer) if timer.owner == owner => {
+                timer.handle.abort();
+                scheduled.remove(&id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        self.scheduled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&id);
+    }
+}
@@ -0,0 +1,46 @@
+pub mod bindings;
+#[allow(clippy::module_inception)]
+pub mod validate;
+mod validate_impl;
+
+pub use validate::ValidateCtx;
+pub use validate::{ValidateImpl, ValidateView};
+
+use hayride_host_traits::validate::ValidateTrait;
+
+use wasmtime::component::HasData;
+
+pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+where
+    T: ValidateView,
+{
+    crate::validate::bindings::validate::add_to_linker::<T, HasValidate<T>>(l, |x| {
+        ValidateImpl(x)
+    })?;
+
+    Ok(())
+}
+
+struct HasValidate<T>(T);
+
+impl<T: 'static> HasData for HasValidate<T> {
+    type Data<'a> = ValidateImpl<&'a mut T>;
+}
+
+pub struct ValidateBackend(Box<dyn ValidateTrait>);
+impl std::ops::Deref for ValidateBackend {
+    type Target = dyn ValidateTrait;
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+impl std::ops::DerefMut for ValidateBackend {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut()
+    }
+}
+impl<T: ValidateTrait + 'static> From<T> for ValidateBackend {
+    fn from(value: T) -> Self {
+        Self(Box::new(value))
+    }
+}
@@ -0,0 +1,15 @@
+pub mod generated {
+    wasmtime::component::bindgen!({
+        path: "../../wit",
+        world: "hayride-validate",
+        imports: {
+            default: trappable,
+        },
+        with: {
+            "hayride:validate/validate/error": hayride_host_traits::validate::Error,
+            "hayride:validate/validate/schema": hayride_host_traits::validate::Schema,
+        },
+    });
+}
+
+pub use self::generated::hayride::validate::*;
@@ -0,0 +1,76 @@
+use wasmtime::component::ResourceTable;
+
+use super::ValidateBackend;
+
+pub struct ValidateCtx {
+    pub validate_backend: ValidateBackend,
+}
+
+impl ValidateCtx {
+    pub fn new() -> Self {
+        let validate_backend: Box<hayride_validate::ValidateBackend> =
+            Box::new(hayride_validate::ValidateBackend::new());
+        Self {
+            validate_backend: ValidateBackend(validate_backend),
+        }
+    }
+}
+
+impl Default for ValidateCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait ValidateView: Send {
+    /// Returns a mutable reference to the validate context.
+    fn ctx(&mut self) -> &mut ValidateCtx;
+
+    /// Returns a mutable reference to the validate resource table.
+    fn table(&mut self) -> &mut ResourceTable;
+}
+
+impl<T: ?Sized + ValidateView> ValidateView for &mut T {
+    fn ctx(&mut self) -> &mut ValidateCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+impl<T: ?Sized + ValidateView> ValidateView for Box<T> {
+    fn ctx(&mut self) -> &mut ValidateCtx {
+        T::ctx(self)
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        T::table(self)
+    }
+}
+
+/// A concrete structure that all generated `Host` traits are implemented for.
+///
+/// This type serves as a small newtype wrapper to implement all of the `Host`
+/// traits for `hayride:validate`. This type is internally used and is only needed if
+/// you're interacting with `add_to_linker` functions generated by bindings
+/// themselves (or `add_to_linker_get_host`).
+///
+/// This type is automatically used when using
+/// [`add_to_linker_async`](crate::add_to_linker_async)
+/// or
+/// [`add_to_linker_sync`](crate::add_to_linker_sync)
+/// and doesn't need to be manually configured.
+#[repr(transparent)]
+pub struct ValidateImpl<T>(pub T);
+
+impl<T: ValidateView> ValidateView for ValidateImpl<T> {
+    fn ctx(&mut self) -> &mut ValidateCtx {
+        self.0.ctx()
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        self.0.table()
+    }
+}
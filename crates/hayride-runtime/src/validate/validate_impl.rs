@@ -0,0 +1,129 @@
+use crate::validate::bindings::{
+    types::{ErrorCode, ValidationError},
+    validate,
+};
+use crate::validate::{ValidateImpl, ValidateView};
+use hayride_host_traits::validate::{Error, Schema};
+
+use wasmtime::component::Resource;
+use wasmtime::Result;
+
+use anyhow::anyhow;
+
+impl<T> validate::Host for ValidateImpl<T>
+where
+    T: ValidateView,
+{
+    fn compile(
+        &mut self,
+        schema: String,
+    ) -> Result<Result<Resource<Schema>, Resource<validate::Error>>, anyhow::Error> {
+        match self.ctx().validate_backend.compile(schema) {
+            Ok(schema) => {
+                let id = self.table().push(schema)?;
+                Ok(Ok(id))
+            }
+            Err(e) => {
+                let error = Error {
+                    code: e,
+                    data: anyhow!("Error compiling schema"),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    fn validate(
+        &mut self,
+        schema: String,
+        document: String,
+    ) -> Result<Result<Vec<ValidationError>, Resource<validate::Error>>, anyhow::Error> {
+        let result = self
+            .ctx()
+            .validate_backend
+            .compile(schema)
+            .map_err(|e| (e, "Error compiling schema"))
+            .and_then(|schema| {
+                schema
+                    .validate(document)
+                    .map_err(|e| (e, "Error validating document"))
+            });
+
+        match result {
+            Ok(errors) => Ok(Ok(errors.into_iter().map(validation_error).collect())),
+            Err((e, message)) => {
+                let error = Error {
+                    code: e,
+                    data: anyhow!(message),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> validate::HostSchema for ValidateImpl<T>
+where
+    T: ValidateView,
+{
+    fn validate(
+        &mut self,
+        schema: Resource<Schema>,
+        document: String,
+    ) -> Result<Result<Vec<ValidationError>, Resource<validate::Error>>, anyhow::Error> {
+        let result = self.table().get(&schema)?.validate(document);
+
+        match result {
+            Ok(errors) => Ok(Ok(errors.into_iter().map(validation_error).collect())),
+            Err(e) => {
+                let error = Error {
+                    code: e,
+                    data: anyhow!("Error validating document"),
+                };
+                let id = self.table().push(error)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    fn drop(&mut self, schema: Resource<Schema>) -> Result<()> {
+        self.table().delete(schema)?;
+        Ok(())
+    }
+}
+
+impl<T> validate::HostError for ValidateImpl<T>
+where
+    T: ValidateView,
+{
+    fn code(&mut self, error: Resource<Error>) -> Result<ErrorCode> {
+        let error = self.table().get(&error)?;
+        match error.code {
+            hayride_host_traits::validate::ErrorCode::InvalidSchema => Ok(ErrorCode::InvalidSchema),
+            hayride_host_traits::validate::ErrorCode::InvalidDocument => {
+                Ok(ErrorCode::InvalidDocument)
+            }
+            hayride_host_traits::validate::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
+        }
+    }
+
+    fn data(&mut self, error: Resource<Error>) -> Result<String> {
+        let error = self.table().get(&error)?;
+        Ok(error.data.to_string())
+    }
+
+    fn drop(&mut self, error: Resource<Error>) -> Result<()> {
+        self.table().delete(error)?;
+        Ok(())
+    }
+}
+
+fn validation_error(error: hayride_host_traits::validate::ValidationError) -> ValidationError {
+    ValidationError {
+        instance_location: error.instance_location,
+        keyword_location: error.keyword_location,
+        message: error.message,
+    }
+}
@@ -9,7 +9,7 @@ use hayride_host_traits::wac::WacTrait;
 
 use wasmtime::component::HasData;
 
-pub fn add_to_linker_sync<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
+pub fn add_to_linker_async<T>(l: &mut wasmtime::component::Linker<T>) -> anyhow::Result<()>
 where
     T: WacView,
 {
@@ -2,10 +2,21 @@ pub mod generated {
     wasmtime::component::bindgen!({
         path: "../../wit",
         world: "hayride-wac",
+        // Compositions run on a blocking thread so they can time out
         imports: {
+            "hayride:wac/wac/compose": async | trappable,
+            "hayride:wac/wac/compose-with": async | trappable,
+            "hayride:wac/wac/compose-to-file": async | trappable,
+            "hayride:wac/wac/compose-and-store": async | trappable,
+            "hayride:wac/wac/compose-to-stream": async | trappable,
+            "hayride:wac/wac/plug": async | trappable,
+            "hayride:wac/wac/plug-with": async | trappable,
+            "hayride:wac/wac/validate": async | trappable,
+            "hayride:wac/wac/describe": async | trappable,
             default: trappable,
         },
         with: {
+            "wasi:io": wasmtime_wasi::p2::bindings::io,
             "hayride:wac/wac/error": hayride_host_traits::wac::Error,
         },
     });
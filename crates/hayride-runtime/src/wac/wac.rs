@@ -1,19 +1,43 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use wasmtime::component::ResourceTable;
 
 use super::WacBackend;
+use crate::timeouts::HostTimeouts;
 
 pub struct WacCtx {
-    pub wac_backend: WacBackend,
+    // Shared with the blocking task composing, so calls can time out
+    pub wac_backend: Arc<Mutex<WacBackend>>,
+
+    // Registry of morphs, relative to the hayride dir
+    pub registry_path: String,
+
+    // Deadline for compose and plug calls
+    pub timeouts: HostTimeouts,
 }
 
 impl WacCtx {
     pub fn new(registry_path: String) -> Self {
         let wac_backend: Box<hayride_wac::WacBackend> =
-            Box::new(hayride_wac::WacBackend::new(registry_path));
+            Box::new(hayride_wac::WacBackend::new(registry_path.clone()));
         Self {
-            wac_backend: WacBackend(wac_backend),
+            wac_backend: Arc::new(Mutex::new(WacBackend(wac_backend))),
+            registry_path,
+            timeouts: HostTimeouts::default(),
         }
     }
+
+    pub fn with_timeouts(mut self, timeouts: HostTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Returns the registry directory composed components are written to.
+    pub fn registry_dir(&self) -> anyhow::Result<PathBuf> {
+        let mut dir = hayride_utils::paths::hayride::default_hayride_dir()?;
+        dir.push(&self.registry_path);
+        Ok(dir)
+    }
 }
 
 pub trait WacView: Send {
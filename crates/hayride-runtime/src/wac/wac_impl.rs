@@ -1,58 +1,403 @@
+use crate::timeouts::deadline;
 use crate::wac::bindings::{types::ErrorCode, wac};
-use crate::wac::{WacImpl, WacView};
-use hayride_host_traits::wac::Error;
+use crate::wac::{WacBackend, WacImpl, WacView};
+use hayride_host_traits::wac::{ComponentSource, Error, ErrorCode as WacErrorCode, ResolveOptions};
+use ring::digest::{digest, SHA256};
+use std::path::{Component, Path, PathBuf};
 
 use wasmtime::component::Resource;
 use wasmtime::Result;
+use wasmtime_wasi::p2::DynOutputStream;
 
 use anyhow::anyhow;
 
+// Size of the chunks composed components are written to streams in
+const CHUNK_SIZE: usize = 64 * 1024;
+
 impl<T> wac::Host for WacImpl<T>
 where
     T: WacView,
 {
-    fn compose(
+    async fn compose(
         &mut self,
         path: String,
     ) -> Result<Result<Vec<u8>, Resource<wac::Error>>, anyhow::Error> {
-        let result = self.ctx().wac_backend.compose(path.clone());
+        let result = self
+            .run_backend(move |backend| backend.compose(path, &ResolveOptions::default()))
+            .await;
 
         match result {
             Ok(c) => {
                 return Ok(Ok(c));
             }
             Err(e) => {
+                let id = self.table().push(e)?;
+                return Ok(Err(id));
+            }
+        }
+    }
+
+    async fn compose_with(
+        &mut self,
+        contents: String,
+        options: wac::ResolveOptions,
+    ) -> Result<Result<Vec<u8>, Resource<wac::Error>>, anyhow::Error> {
+        let options = resolve_options(options);
+        let result = self
+            .run_backend(move |backend| backend.compose(contents, &options))
+            .await;
+
+        match result {
+            Ok(c) => Ok(Ok(c)),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    async fn compose_to_file(
+        &mut self,
+        contents: String,
+        path: Option<String>,
+    ) -> Result<Result<String, Resource<wac::Error>>, anyhow::Error> {
+        let registry = self.ctx().registry_dir();
+        let target = path.clone();
+        let result = self
+            .run_backend(move |backend| {
+                let bytes = backend.compose(contents, &ResolveOptions::default())?;
+                let registry = registry.map_err(|e| {
+                    log::warn!("failed to find registry: {:?}", e);
+                    Error::from(WacErrorCode::WriteFailed)
+                })?;
+                write_component(&registry, target.as_deref(), &bytes).map_err(|code| Error {
+                    code,
+                    data: anyhow!(
+                        "Error composing to file: {}",
+                        target.as_deref().unwrap_or("composed")
+                    ),
+                    diagnostics: vec![],
+                })
+            })
+            .await;
+
+        match result {
+            Ok(path) => {
+                return Ok(Ok(path));
+            }
+            Err(e) => {
+                let id = self.table().push(e)?;
+                return Ok(Err(id));
+            }
+        }
+    }
+
+    async fn compose_and_store(
+        &mut self,
+        name: String,
+        version: String,
+        contents: String,
+    ) -> Result<Result<String, Resource<wac::Error>>, anyhow::Error> {
+        let (path, morph) = match package_path(&name, &version) {
+            Ok(package) => package,
+            Err(e) => {
+                let id = self.table().push(e)?;
+                return Ok(Err(id));
+            }
+        };
+
+        let registry = self.ctx().registry_dir();
+        let result = self
+            .run_backend(move |backend| {
+                let bytes = backend.compose(contents, &ResolveOptions::default())?;
+                let registry = registry.map_err(|e| {
+                    log::warn!("failed to find registry: {:?}", e);
+                    Error::from(WacErrorCode::WriteFailed)
+                })?;
+                write_component(&registry, Some(&path), &bytes).map_err(|code| Error {
+                    code,
+                    data: anyhow!("Error storing composed component: {}", path),
+                    diagnostics: vec![],
+                })
+            })
+            .await;
+
+        match result {
+            Ok(_) => Ok(Ok(morph)),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    async fn compose_to_stream(
+        &mut self,
+        contents: String,
+        output: Resource<DynOutputStream>,
+    ) -> Result<Result<u64, Resource<wac::Error>>, anyhow::Error> {
+        let result = self
+            .run_backend(move |backend| backend.compose(contents, &ResolveOptions::default()))
+            .await;
+        let bytes = match result {
+            Ok(bytes) => bytes::Bytes::from(bytes),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                return Ok(Err(id));
+            }
+        };
+
+        // Write in chunks, waiting on the reader so the stream does not buffer it all
+        let stream = self.table().get_mut(&output)?;
+        let mut written = 0;
+        while written < bytes.len() {
+            let end = (written + CHUNK_SIZE).min(bytes.len());
+            if let Err(e) = stream
+                .blocking_write_and_flush(bytes.slice(written..end))
+                .await
+            {
                 let error = Error {
-                    code: e,
-                    data: anyhow!("Error composing path: {}", path),
+                    code: WacErrorCode::WriteFailed,
+                    data: anyhow!("Error writing composed component: {}", e),
+                    diagnostics: vec![],
                 };
                 let id = self.table().push(error)?;
                 return Ok(Err(id));
             }
+            written = end;
         }
+
+        return Ok(Ok(written as u64));
     }
 
-    fn plug(
+    async fn plug(
         &mut self,
         socket_path: String,
         plug_path: Vec<String>,
     ) -> Result<Result<Vec<u8>, Resource<wac::Error>>, anyhow::Error> {
-        let result = self.ctx().wac_backend.plug(socket_path.clone(), plug_path);
+        let result = self
+            .run_backend(move |backend| {
+                backend.plug(socket_path, plug_path, &ResolveOptions::default())
+            })
+            .await;
 
         match result {
             Ok(c) => {
                 return Ok(Ok(c));
             }
             Err(e) => {
-                let error = Error {
-                    code: e,
-                    data: anyhow!("Error plugging socket path: {}", socket_path),
-                };
-                let id = self.table().push(error)?;
+                let id = self.table().push(e)?;
                 return Ok(Err(id));
             }
         }
     }
+
+    async fn plug_with(
+        &mut self,
+        socket_path: String,
+        plug_path: Vec<String>,
+        options: wac::ResolveOptions,
+    ) -> Result<Result<Vec<u8>, Resource<wac::Error>>, anyhow::Error> {
+        let options = resolve_options(options);
+        let result = self
+            .run_backend(move |backend| backend.plug(socket_path, plug_path, &options))
+            .await;
+
+        match result {
+            Ok(c) => Ok(Ok(c)),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    async fn validate(
+        &mut self,
+        component: wac::ComponentSource,
+    ) -> Result<Result<(), Resource<wac::Error>>, anyhow::Error> {
+        let component = component_source(component);
+        let result = self
+            .run_backend(move |backend| backend.validate(component))
+            .await;
+
+        match result {
+            Ok(()) => Ok(Ok(())),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                Ok(Err(id))
+            }
+        }
+    }
+
+    async fn describe(
+        &mut self,
+        component: wac::ComponentSource,
+    ) -> Result<Result<wac::ComponentDescription, Resource<wac::Error>>, anyhow::Error> {
+        let component = component_source(component);
+        let result = self
+            .run_backend(move |backend| backend.describe(component))
+            .await;
+
+        let items = |items: Vec<hayride_host_traits::wac::WorldItem>| {
+            items
+                .into_iter()
+                .map(|item| wac::WorldItem {
+                    name: item.name,
+                    functions: item.functions,
+                })
+                .collect()
+        };
+        match result {
+            Ok(description) => Ok(Ok(wac::ComponentDescription {
+                package_name: description.package,
+                imports: items(description.imports),
+                exports: items(description.exports),
+            })),
+            Err(e) => {
+                let id = self.table().push(e)?;
+                Ok(Err(id))
+            }
+        }
+    }
+}
+
+impl<T> WacImpl<T>
+where
+    T: WacView,
+{
+    // Run the backend on a blocking thread, so a stuck composition can time out
+    async fn run_backend<F, R>(&mut self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut WacBackend) -> Result<R, Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        let backend = self.ctx().wac_backend.clone();
+        let timeout = self.ctx().timeouts.wac;
+
+        let task = tokio::task::spawn_blocking(move || match backend.lock() {
+            Ok(mut backend) => f(&mut backend),
+            Err(_) => Err(WacErrorCode::Unknown.into()),
+        });
+
+        match deadline(timeout, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => {
+                log::warn!("wac task failed: {}", e);
+                Err(WacErrorCode::Unknown.into())
+            }
+            Err(_) => Err(Error {
+                code: WacErrorCode::Timeout,
+                data: anyhow!("composition timed out"),
+                diagnostics: vec![],
+            }),
+        }
+    }
+}
+
+// Convert the component of a call
+fn component_source(component: wac::ComponentSource) -> ComponentSource {
+    match component {
+        wac::ComponentSource::Morph(morph) => ComponentSource::Morph(morph),
+        wac::ComponentSource::Bytes(bytes) => ComponentSource::Bytes(bytes),
+    }
+}
+
+// Convert the options of a call, relative paths are resolved against the working directory
+fn resolve_options(options: wac::ResolveOptions) -> ResolveOptions {
+    ResolveOptions {
+        overrides: options
+            .overrides
+            .into_iter()
+            .map(|(name, path)| (name, PathBuf::from(path)))
+            .collect(),
+        registries: options.registries.into_iter().map(PathBuf::from).collect(),
+    }
+}
+
+// Path of a package version relative to the registry, in the `<namespace>/<version>/<name>.wasm`
+// layout morphs are found in, and its morph path
+fn package_path(name: &str, version: &str) -> Result<(String, String), Error> {
+    let invalid = |data: String| Error {
+        code: WacErrorCode::InvalidPath,
+        data: anyhow!(data),
+        diagnostics: vec![],
+    };
+    // Package names have to be valid in wac documents composing them
+    let is_identifier = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    };
+    let (namespace, package) = name
+        .split_once(':')
+        .filter(|(namespace, package)| is_identifier(namespace) && is_identifier(package))
+        .ok_or_else(|| {
+            invalid(format!(
+                "invalid package name `{}`, expected <namespace>:<name>",
+                name
+            ))
+        })?;
+    let version = semver::Version::parse(version)
+        .map_err(|e| invalid(format!("invalid version `{}`: {}", version, e)))?;
+
+    Ok((
+        format!("{}/{}/{}.wasm", namespace, version, package),
+        format!("{}:{}@{}", namespace, package, version),
+    ))
+}
+
+// Write a composed component into the registry, returning its path relative to the registry
+fn write_component(
+    registry: &Path,
+    path: Option<&str>,
+    bytes: &[u8],
+) -> Result<String, WacErrorCode> {
+    let relative = match path {
+        Some(path) => {
+            let relative = PathBuf::from(path);
+            // Paths can not lead out of the registry
+            let contained = relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+            if path.is_empty() || !contained {
+                log::warn!("invalid path for composed component: {}", path);
+                return Err(WacErrorCode::InvalidPath);
+            }
+            relative
+        }
+        None => {
+            let name: String = digest(&SHA256, bytes)
+                .as_ref()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            Path::new("composed").join(format!("{}.wasm", name))
+        }
+    };
+
+    let file = registry.join(&relative);
+    let written = (|| -> std::io::Result<()> {
+        let dir = file.parent().unwrap_or(registry);
+        std::fs::create_dir_all(dir)?;
+
+        // Write to a temporary file first so a partial component is never resolved
+        let partial = dir.join(format!(".{}", uuid::Uuid::new_v4()));
+        std::fs::write(&partial, bytes)?;
+        std::fs::rename(&partial, &file).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })
+    })();
+    if let Err(e) = written {
+        log::warn!("failed to write {}: {:?}", file.display(), e);
+        return Err(WacErrorCode::WriteFailed);
+    }
+
+    relative
+        .to_str()
+        .map(|path| path.to_string())
+        .ok_or(WacErrorCode::InvalidPath)
 }
 
 impl<T> wac::HostError for WacImpl<T>
@@ -66,6 +411,12 @@ where
             hayride_host_traits::wac::ErrorCode::ComposeFailed => Ok(ErrorCode::ComposeFailed),
             hayride_host_traits::wac::ErrorCode::ResolveFailed => Ok(ErrorCode::ResolveFailed),
             hayride_host_traits::wac::ErrorCode::EncodeFailed => Ok(ErrorCode::EncodeFailed),
+            hayride_host_traits::wac::ErrorCode::Timeout => Ok(ErrorCode::Timeout),
+            hayride_host_traits::wac::ErrorCode::InvalidPath => Ok(ErrorCode::InvalidPath),
+            hayride_host_traits::wac::ErrorCode::WriteFailed => Ok(ErrorCode::WriteFailed),
+            hayride_host_traits::wac::ErrorCode::InvalidComponent => {
+                Ok(ErrorCode::InvalidComponent)
+            }
             hayride_host_traits::wac::ErrorCode::Unknown => Ok(ErrorCode::Unknown),
         }
     }
@@ -75,6 +426,23 @@ where
         return Ok(error.data.to_string());
     }
 
+    fn diagnostics(&mut self, error: Resource<Error>) -> Result<Vec<wac::Diagnostic>> {
+        let error = self.table().get(&error)?;
+        Ok(error
+            .diagnostics
+            .iter()
+            .map(|diagnostic| wac::Diagnostic {
+                message: diagnostic.message.clone(),
+                span: diagnostic.span.map(|span| wac::SourceSpan {
+                    line: span.line,
+                    column: span.column,
+                    length: span.length,
+                }),
+                package_name: diagnostic.package.clone(),
+            })
+            .collect())
+    }
+
     fn drop(&mut self, error: Resource<Error>) -> Result<()> {
         self.table().delete(error)?;
         return Ok(());
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// How often watched morphs are checked for changes.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls a morph for changes, so servers can reload it while in development.
+///
+/// A change is only reported once the file has stopped changing between two polls, so a
+/// morph is not reloaded while a rebuild is still writing it.
+pub struct MorphWatcher {
+    path: PathBuf,
+    // State of the file at the last poll
+    seen: Option<(SystemTime, u64)>,
+    // State of the file when it was last loaded
+    loaded: Option<(SystemTime, u64)>,
+}
+
+impl MorphWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let state = file_state(&path);
+        Self {
+            path,
+            seen: state,
+            loaded: state,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Returns true once the morph changed since it was last loaded and is stable.
+    pub fn poll(&mut self) -> bool {
+        let state = file_state(&self.path);
+        if state != self.seen {
+            self.seen = state;
+            return false;
+        }
+        if state.is_none() || state == self.loaded {
+            return false;
+        }
+
+        self.loaded = state;
+        true
+    }
+}
+
+fn file_state(path: &PathBuf) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -1,8 +1,18 @@
 use super::create_wasi_ctx;
+use crate::access_log::AccessEntry;
 use crate::bindings::hayride_ws::{HayrideWs, HayrideWsPre};
 use crate::core::CoreCtx;
+use crate::egress::EgressPolicy;
+use crate::http_client::HttpClient;
+use crate::mounts::Mount;
+use crate::session_input::StdinMode;
 use crate::silo::SiloCtx;
+use crate::socket::socket::MuxRequest;
+use crate::socket::SocketCtx;
+use crate::sse::SseCtx;
+use crate::timeouts::HostTimeouts;
 use crate::Host;
+use hayride_host_traits::ai::nn::OutputFilter;
 
 use anyhow::bail;
 
@@ -15,22 +25,49 @@ use hyper::body::Body;
 use hyper::upgrade::Upgraded;
 use hyper_tungstenite::WebSocketStream;
 use hyper_tungstenite::{tungstenite, HyperWebsocket};
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::{
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
     task::{Context, Poll},
 };
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::sync::mpsc;
+use tracing::Instrument;
 use tungstenite::Message;
 use uuid::Uuid;
 
 use crate::ai::AiCtx;
+use crate::blob::BlobCtx;
 use crate::db::DBCtx;
+use crate::kv::KvCtx;
 use crate::mcp::McpCtx;
+use crate::template::TemplateCtx;
+use crate::validate::ValidateCtx;
 use crate::wac::WacCtx;
 use wasmtime::{component::ResourceTable, Result};
 use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
 
+// Subprotocol clients request to multiplex requests over the connection
+pub const MUX_PROTOCOL: &str = "hayride-mux";
+
+// Channel of frames sent without one
+const DEFAULT_CHANNEL: &str = "default";
+
+// Requests open at once on a multiplexed connection
+const MAX_REQUESTS: usize = 64;
+
+// Frames of a request buffered ahead of the component reading them
+const REQUEST_BUFFER: usize = 256;
+
+// Messages queued to be sent over a websocket before writers have to wait
+const SEND_BUFFER: usize = 2048;
+
+// Bytes that may be written at once, each write is sent as a message
+const WRITE_BUDGET: usize = 64 * 1024;
+
 // Trait extensions
 use futures::sink::SinkExt;
 use futures::stream::{SplitSink, SplitStream, Stream, StreamExt};
@@ -46,6 +83,13 @@ pub struct WebsocketServer {
     model_path: Option<String>,
     args: Vec<String>,
     envs: Vec<(String, String)>,
+    mounts: Vec<Mount>,
+    timeouts: HostTimeouts,
+    egress: Arc<EgressPolicy>,
+    http_client: Arc<HttpClient>,
+    output_filter: Option<Arc<OutputFilter>>,
+    // Log stdout and stderr of the component instead of only writing them out
+    log_stdio: bool,
 }
 
 impl WebsocketServer {
@@ -59,6 +103,11 @@ impl WebsocketServer {
         model_path: Option<String>,
         args: Vec<String>,
         envs: Vec<(String, String)>,
+        mounts: Vec<Mount>,
+        timeouts: HostTimeouts,
+        egress: Arc<EgressPolicy>,
+        http_client: Arc<HttpClient>,
+        output_filter: Option<Arc<OutputFilter>>,
     ) -> Self {
         Self {
             id,
@@ -70,43 +119,83 @@ impl WebsocketServer {
             model_path,
             args,
             envs,
+            mounts,
+            timeouts,
+            egress,
+            http_client,
+            output_filter,
+            log_stdio: false,
         }
     }
 
+    pub fn with_log_stdio(mut self, log_stdio: bool) -> Self {
+        self.log_stdio = log_stdio;
+        self
+    }
+
     pub async fn handle_request(
-        &self,
+        self: &Arc<Self>,
         mut req: hyper::Request<hyper::body::Incoming>,
     ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let entry = AccessEntry::new("websocket", &req, self.id);
+
         // Check if this is a websocket request and handle it
         if hyper_tungstenite::is_upgrade_request(&req) {
-            let wasi_ctx =
-                create_wasi_ctx(&self.args, self.out_dir.clone(), self.id, false, &self.envs)?;
-            let mut store: wasmtime::Store<Host> = wasmtime::Store::new(
-                &self.ws_pre.engine(),
-                Host {
-                    ctx: wasi_ctx,
-                    http_ctx: WasiHttpCtx::new(),
-                    core_ctx: self.core_ctx.clone(),
-                    ai_ctx: AiCtx::new(self.out_dir.clone(), self.model_path.clone())?,
-                    mcp_ctx: McpCtx::new(),
-                    silo_ctx: self.silo_ctx.clone(),
-                    wac_ctx: WacCtx::new(self.registry_path.clone()),
-                    db_ctx: DBCtx::new(),
-                    table: ResourceTable::default(),
-                },
+            let multiplexed = requests_mux(&req);
+            // Requests of a connection share the memory session of the connection
+            let session_id = crate::ai::memory::session_id(req.headers());
+
+            // Multiplexed connections instantiate the component for each request instead
+            let instance = match multiplexed {
+                true => None,
+                false => Some(
+                    self.instantiate(SocketCtx::new(), session_id.clone())
+                        .await?,
+                ),
+            };
+
+            let (mut response, websocket) = hyper_tungstenite::upgrade(&mut req, None)?;
+
+            // The span of the connection lasts until the websocket is closed
+            let span = tracing::info_span!(
+                "websocket.connection",
+                otel.kind = "server",
+                url.path = %entry.path,
+                session.id = %self.id,
+                websocket.multiplexed = multiplexed,
             );
-
-            // Instantiate the server
-            let pre = self.ws_pre.clone();
-            let server: HayrideWs = pre.instantiate_async(&mut store).await?;
-
-            let (response, websocket) = hyper_tungstenite::upgrade(&mut req, None)?;
-
-            tokio::spawn(async move {
-                if let Err(e) = serve_websocket(websocket, server, store, req).await {
-                    eprintln!("websocket error: {:?}", e);
+            crate::telemetry::continue_trace(&span, req.headers());
+            match instance {
+                Some((server, store)) => {
+                    tokio::spawn(
+                        async move {
+                            if let Err(e) =
+                                serve_websocket(websocket, server, store, req, entry).await
+                            {
+                                eprintln!("websocket error: {:?}", e);
+                            }
+                        }
+                        .instrument(span),
+                    );
                 }
-            });
+                None => {
+                    response.headers_mut().insert(
+                        hyper::header::SEC_WEBSOCKET_PROTOCOL,
+                        hyper::header::HeaderValue::from_static(MUX_PROTOCOL),
+                    );
+                    let server = self.clone();
+                    tokio::spawn(
+                        async move {
+                            if let Err(e) =
+                                serve_multiplexed(server, websocket, entry, session_id).await
+                            {
+                                eprintln!("websocket error: {:?}", e);
+                            }
+                        }
+                        .instrument(span),
+                    );
+                }
+            }
 
             // Convert and return response so spawned future can continue.
             let response = response.map(|body| {
@@ -116,23 +205,97 @@ impl WebsocketServer {
             return Ok(response); // 101 Switching Protocols
         }
 
+        entry.finish(hyper::StatusCode::INTERNAL_SERVER_ERROR.as_u16(), 0);
         bail!("Request not handled, was not a websocket upgrade request");
     }
+
+    // Instantiate the component in a new store, remembering in the memory session
+    async fn instantiate(
+        &self,
+        socket_ctx: SocketCtx,
+        session_id: String,
+    ) -> Result<(HayrideWs, wasmtime::Store<Host>)> {
+        let wasi_ctx = create_wasi_ctx(
+            &self.args,
+            self.out_dir.clone(),
+            self.id,
+            StdinMode::Inherit,
+            &self.envs,
+            &self.mounts,
+            self.log_stdio.then_some(self.core_ctx.morph.as_str()),
+        )?;
+        // New stores pick up the current capability policy
+        let capabilities = crate::capabilities::capabilities().policy();
+        let mut store: wasmtime::Store<Host> = wasmtime::Store::new(
+            &self.ws_pre.engine(),
+            Host {
+                ctx: wasi_ctx,
+                http_ctx: WasiHttpCtx::new(),
+                egress: self.egress.clone(),
+                http_client: self.http_client.clone(),
+                capabilities,
+                core_ctx: self.core_ctx.clone(),
+                ai_ctx: AiCtx::new(session_id, self.out_dir.clone(), self.model_path.clone())?
+                    .with_timeouts(self.timeouts)
+                    .with_output_filter(self.output_filter.clone())
+                    .with_capabilities(capabilities),
+                mcp_ctx: McpCtx::new(),
+                silo_ctx: self.silo_ctx.clone().with_capabilities(capabilities),
+                wac_ctx: WacCtx::new(self.registry_path.clone()).with_timeouts(self.timeouts),
+                template_ctx: TemplateCtx::new(self.registry_path.clone()),
+                validate_ctx: ValidateCtx::new(),
+                db_ctx: DBCtx::new()
+                    .with_timeouts(self.timeouts)
+                    .with_capabilities(capabilities),
+                kv_ctx: KvCtx::new(&self.core_ctx.morph),
+                blob_ctx: BlobCtx::new(),
+                socket_ctx,
+                sse_ctx: SseCtx::new(),
+                table: ResourceTable::default(),
+            },
+        );
+
+        // Instantiate the server
+        let pre = self.ws_pre.clone();
+        let server: HayrideWs = pre.instantiate_async(&mut store).await?;
+        Ok((server, store))
+    }
+}
+
+// Whether the client asked to multiplex requests over the connection
+fn requests_mux<B>(req: &hyper::Request<B>) -> bool {
+    req.headers()
+        .get_all(hyper::header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim() == MUX_PROTOCOL)
 }
 
 /// Handle a websocket connection.
+///
+/// The connection is recorded in the access log once it closes, with the bytes the
+/// component sent over it.
 async fn serve_websocket<B>(
     websocket: HyperWebsocket,
     server: HayrideWs,
     mut store: wasmtime::Store<Host>,
     _req: hyper::Request<B>,
+    entry: AccessEntry,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>>
 where
     B: Body<Data = Bytes, Error = hyper::Error> + Send + Sync + 'static,
 {
-    let websocket: WebSocketStream<hyper_util::rt::TokioIo<Upgraded>> = websocket.await?;
+    let websocket: WebSocketStream<hyper_util::rt::TokioIo<Upgraded>> = match websocket.await {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            entry.finish(hyper::StatusCode::SWITCHING_PROTOCOLS.as_u16(), 0);
+            return Err(e.into());
+        }
+    };
     let (write, read) = websocket.split();
     let out = WebsocketOutputPipe::new(write);
+    let sent = out.sent.clone();
 
     let boxed_output: Box<dyn wasmtime_wasi::p2::OutputStream> = Box::new(out.clone());
     let output_arg = store.data_mut().table.push(boxed_output)?;
@@ -143,11 +306,15 @@ where
     let boxed_input: Box<dyn wasmtime_wasi::p2::InputStream> = Box::new(input);
     let input_arg = store.data_mut().table.push(boxed_input)?;
 
-    if let Err(e) = server
+    let result = server
         .hayride_socket_websocket()
         .call_handle(&mut store, input_arg, output_arg)
-        .await
-    {
+        .await;
+    entry.finish(
+        hyper::StatusCode::SWITCHING_PROTOCOLS.as_u16(),
+        sent.load(Ordering::Relaxed),
+    );
+    if let Err(e) = result {
         log::warn!("error handling websocket request: {:?}", e);
         return Err(e.into());
     }
@@ -155,30 +322,425 @@ where
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+/// Handle a websocket connection multiplexed with the [`MUX_PROTOCOL`] subprotocol.
+///
+/// Each request gets its own instance of the component, which reads the data frames the
+/// client sends for the request and whose output is sent back as data frames on the
+/// channel of the request. Requests still running when the connection closes are aborted.
+async fn serve_multiplexed(
+    server: Arc<WebsocketServer>,
+    websocket: HyperWebsocket,
+    entry: AccessEntry,
+    session_id: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let websocket: WebSocketStream<hyper_util::rt::TokioIo<Upgraded>> = match websocket.await {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            entry.finish(hyper::StatusCode::SWITCHING_PROTOCOLS.as_u16(), 0);
+            return Err(e.into());
+        }
+    };
+    let (write, mut read) = websocket.split();
+    let frames = FrameSender {
+        out: WebsocketOutputPipe::new(write),
+    };
+    let sent = frames.out.sent.clone();
+
+    // Inputs of the running requests, none once the client ended the input
+    let mut inputs: HashMap<String, Option<mpsc::Sender<Result<Bytes, StreamError>>>> =
+        HashMap::new();
+    let mut requests = tokio::task::JoinSet::new();
+    loop {
+        let message = tokio::select! {
+            message = read.next() => message,
+            Some(finished) = requests.join_next() => {
+                if let Ok(id) = finished {
+                    inputs.remove(&id);
+                }
+                continue;
+            }
+        };
+        let text = match message {
+            Some(Ok(Message::Text(text))) => text.to_string(),
+            Some(Ok(Message::Binary(data))) => String::from_utf8_lossy(&data).to_string(),
+            Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+            Some(Ok(Message::Close(_))) | None => break,
+            Some(Err(e)) => {
+                log::debug!("error reading multiplexed websocket: {:?}", e);
+                break;
+            }
+        };
+        let Some(frame) = Frame::parse(&text) else {
+            frames.send(&Frame::new(
+                "",
+                DEFAULT_CHANNEL,
+                FrameType::Error,
+                "invalid frame",
+            ));
+            continue;
+        };
+
+        match frame.kind {
+            FrameType::Data => {
+                if !inputs.contains_key(&frame.id) {
+                    if inputs.len() >= MAX_REQUESTS {
+                        frames.send(&frame.reply(FrameType::Error, "too many requests"));
+                        continue;
+                    }
+                    let (input, receiver) = mpsc::channel(REQUEST_BUFFER);
+                    inputs.insert(frame.id.clone(), Some(input));
+                    let request = MuxRequest {
+                        id: frame.id.clone(),
+                        channel: frame.channel.clone(),
+                        frames: frames.clone(),
+                    };
+                    requests.spawn(
+                        serve_request(server.clone(), request, receiver, session_id.clone())
+                            .in_current_span(),
+                    );
+                }
+                let sent = match inputs.get(&frame.id) {
+                    Some(Some(input)) => input.try_send(Ok(Bytes::from(frame.data.clone()))),
+                    _ => {
+                        frames.send(&frame.reply(FrameType::Error, "request input ended"));
+                        continue;
+                    }
+                };
+                // Data is not queued behind a request that does not read it, holding up
+                // the other requests
+                if let Err(mpsc::error::TrySendError::Full(_)) = sent {
+                    frames.send(&frame.reply(FrameType::Error, "request input full"));
+                }
+            }
+            FrameType::End | FrameType::Error => {
+                if let Some(input) = inputs.get_mut(&frame.id).and_then(Option::take) {
+                    let _ = input.try_send(Err(StreamError::Closed));
+                }
+            }
+        }
+    }
+
+    requests.shutdown().await;
+    entry.finish(
+        hyper::StatusCode::SWITCHING_PROTOCOLS.as_u16(),
+        sent.load(Ordering::Relaxed),
+    );
+
+    Ok(())
+}
+
+// Handle a request of a multiplexed connection in its own instance of the component,
+// returning the id of the request once it is done
+async fn serve_request(
+    server: Arc<WebsocketServer>,
+    request: MuxRequest,
+    input: mpsc::Receiver<Result<Bytes, StreamError>>,
+    session_id: String,
+) -> String {
+    let frames = request.frames.clone();
+    let output = FrameOutputPipe {
+        out: frames.out.clone(),
+        id: request.id.clone(),
+        channel: request.channel.clone(),
+    };
+    let (id, channel) = (request.id.clone(), request.channel.clone());
+
+    let result = async {
+        let (instance, mut store) = server
+            .instantiate(SocketCtx::new().with_request(request), session_id)
+            .await?;
+
+        let boxed_output: Box<dyn wasmtime_wasi::p2::OutputStream> = Box::new(output);
+        let output_arg = store.data_mut().table.push(boxed_output)?;
+        let boxed_input: Box<dyn wasmtime_wasi::p2::InputStream> =
+            Box::new(WebsocketInputPipe::from_receiver(input));
+        let input_arg = store.data_mut().table.push(boxed_input)?;
+
+        instance
+            .hayride_socket_websocket()
+            .call_handle(&mut store, input_arg, output_arg)
+            .await
+    }
+    .await;
+
+    let frame = match result {
+        Ok(()) => Frame::new(&id, &channel, FrameType::End, ""),
+        Err(e) => {
+            log::warn!("error handling websocket request {}: {:?}", id, e);
+            Frame::new(&id, &channel, FrameType::Error, &e.to_string())
+        }
+    };
+    frames.send_wait(&frame).await;
+
+    id
+}
+
+/// Kind of a frame of a multiplexed connection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameType {
+    /// Data for or from a request
+    Data,
+    /// The client is done sending data for a request, or the component is done with it
+    End,
+    /// The request failed, with the error as the data
+    Error,
+}
+
+impl FrameType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FrameType::Data => "data",
+            FrameType::End => "end",
+            FrameType::Error => "error",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "data" => Some(FrameType::Data),
+            "end" => Some(FrameType::End),
+            "error" => Some(FrameType::Error),
+            _ => None,
+        }
+    }
+}
+
+/// A json frame of a request of a multiplexed connection,
+/// `{"id": string, "channel": string, "type": "data" | "end" | "error", "data": string}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub id: String,
+    pub channel: String,
+    pub kind: FrameType,
+    pub data: String,
+}
+
+impl Frame {
+    pub fn new(id: &str, channel: &str, kind: FrameType, data: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            channel: channel.to_string(),
+            kind,
+            data: data.to_string(),
+        }
+    }
+
+    /// Parse a frame a client sent, the channel and data may be left out.
+    pub fn parse(text: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(text).ok()?;
+        Some(Self {
+            id: value.get("id")?.as_str()?.to_string(),
+            channel: value
+                .get("channel")
+                .and_then(Value::as_str)
+                .unwrap_or(DEFAULT_CHANNEL)
+                .to_string(),
+            kind: FrameType::parse(value.get("type")?.as_str()?)?,
+            data: value
+                .get("data")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({
+            "id": self.id,
+            "channel": self.channel,
+            "type": self.kind.as_str(),
+            "data": self.data,
+        })
+        .to_string()
+    }
+
+    // A frame for the same request and channel
+    fn reply(&self, kind: FrameType, data: &str) -> Self {
+        Self::new(&self.id, &self.channel, kind, data)
+    }
+}
+
+/// Sends the frames of the requests of a multiplexed connection over the websocket.
+#[derive(Clone, Debug)]
+pub struct FrameSender {
+    out: WebsocketOutputPipe,
+}
+
+impl FrameSender {
+    /// Queue the frame to be sent, returning false if the connection is closed or too
+    /// many messages are queued.
+    pub fn send(&self, frame: &Frame) -> bool {
+        let text = frame.to_json();
+        let len = text.len() as u64;
+        if let Err(e) = self.out.sender.try_send(Message::Text(text.into())) {
+            log::warn!("error sending frame of request {}: {:?}", frame.id, e);
+            return false;
+        }
+        self.out.sent.fetch_add(len, Ordering::Relaxed);
+        true
+    }
+
+    // Queue the frame to be sent, waiting for room in the queue
+    async fn send_wait(&self, frame: &Frame) -> bool {
+        let text = frame.to_json();
+        let len = text.len() as u64;
+        if self
+            .out
+            .sender
+            .send(Message::Text(text.into()))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        self.out.sent.fetch_add(len, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Output of a request of a multiplexed connection, sent as data frames on its channel.
+///
+/// Writes wait for room in the queue of the connection like [`WebsocketOutputPipe`].
+pub struct FrameOutputPipe {
+    out: WebsocketOutputPipe,
+    id: String,
+    channel: String,
+}
+
+#[async_trait::async_trait]
+impl wasmtime_wasi::p2::OutputStream for FrameOutputPipe {
+    fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
+        // Frames carry their data as a json string
+        let data = std::str::from_utf8(&bytes).map_err(|e| {
+            StreamError::LastOperationFailed(anyhow::anyhow!("frame data is not utf-8: {}", e))
+        })?;
+
+        let frame = Frame::new(&self.id, &self.channel, FrameType::Data, data);
+        self.out.queue(Message::Text(frame.to_json().into()))
+    }
+
+    fn flush(&mut self) -> Result<(), StreamError> {
+        self.out.flush()
+    }
+
+    fn check_write(&mut self) -> Result<usize, StreamError> {
+        self.out.check_write()
+    }
+}
+
+#[async_trait::async_trait]
+impl wasmtime_wasi::p2::Pollable for FrameOutputPipe {
+    async fn ready(&mut self) {
+        wasmtime_wasi::p2::Pollable::ready(&mut self.out).await
+    }
+}
+
+/// Output sent over a websocket, as text messages for utf-8 data and binary messages
+/// otherwise.
+///
+/// Messages are queued for a task writing them to the websocket. Once the queue is full,
+/// `check-write` permits no writes until the guest waited for room, so a slow client
+/// holds up the guest instead of losing its output.
+#[derive(Debug)]
 pub struct WebsocketOutputPipe {
-    // websocket: Arc<Mutex<SplitSink<WebSocketStream<hyper_util::rt::TokioIo<Upgraded>>, Message>>>,
-    sender: tokio::sync::mpsc::Sender<Utf8Bytes>,
+    sender: mpsc::Sender<Message>,
+    // Room in the queue for the next write, taken by check-write
+    permit: Option<mpsc::OwnedPermit<Message>>,
+    // Set by a flush until the queue is empty
+    flushing: bool,
+    // Bytes queued to be sent over the websocket
+    sent: Arc<AtomicU64>,
 }
 
 impl WebsocketOutputPipe {
     pub fn new(
         mut write: SplitSink<WebSocketStream<hyper_util::rt::TokioIo<Upgraded>>, Message>,
     ) -> Self {
-        let (sender, mut receiver) = tokio::sync::mpsc::channel(2048);
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(SEND_BUFFER);
 
         // Spawn a task to handle sending messages
         tokio::spawn(async move {
-            while let Some(bytes) = receiver.recv().await {
-                if let Err(e) = write.send(Message::Text(bytes)).await {
+            while let Some(message) = receiver.recv().await {
+                if let Err(e) = write.send(message).await {
                     eprintln!("Error sending websocket message: {:?}", e);
                 }
             }
         });
 
         WebsocketOutputPipe {
-            // websocket: Arc::new(Mutex::new(websocket)),
             sender,
+            permit: None,
+            flushing: false,
+            sent: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Send the data as a text message if it is utf-8
+    fn message(bytes: Bytes) -> Message {
+        match Utf8Bytes::try_from(bytes.clone()) {
+            Ok(text) => Message::Text(text),
+            Err(_) => Message::Binary(bytes),
+        }
+    }
+
+    // Queue the message in the room check-write took, or any room left in the queue
+    fn queue(&mut self, message: Message) -> Result<(), StreamError> {
+        let len = message.len() as u64;
+        match self.permit.take() {
+            Some(permit) => {
+                permit.send(message);
+            }
+            None => self.sender.try_send(message).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => {
+                    StreamError::Trap(anyhow::anyhow!("write exceeded the permit of check-write"))
+                }
+                mpsc::error::TrySendError::Closed(_) => StreamError::Closed,
+            })?,
+        }
+        self.sent.fetch_add(len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), StreamError> {
+        if self.sender.is_closed() {
+            return Err(StreamError::Closed);
+        }
+        self.flushing = true;
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> Result<usize, StreamError> {
+        if self.sender.is_closed() {
+            return Err(StreamError::Closed);
+        }
+        // The queue is empty again once all of it is free, this permit aside
+        let queued = self.sender.max_capacity()
+            - self.sender.capacity()
+            - usize::from(self.permit.is_some());
+        if self.flushing && queued > 0 {
+            return Ok(0);
+        }
+        self.flushing = false;
+
+        if self.permit.is_none() {
+            match self.sender.clone().try_reserve_owned() {
+                Ok(permit) => self.permit = Some(permit),
+                Err(mpsc::error::TrySendError::Full(_)) => return Ok(0),
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(StreamError::Closed),
+            }
+        }
+        Ok(WRITE_BUDGET)
+    }
+}
+
+impl Clone for WebsocketOutputPipe {
+    // Clones write to the same websocket, each taking its own room in the queue
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            permit: None,
+            flushing: false,
+            sent: self.sent.clone(),
         }
     }
 }
@@ -192,55 +754,57 @@ impl IsTerminal for WebsocketOutputPipe {
 #[async_trait::async_trait]
 impl wasmtime_wasi::p2::OutputStream for WebsocketOutputPipe {
     fn write(&mut self, bytes: Bytes) -> Result<(), StreamError> {
-        // Convert Bytes to string
-        let data = std::str::from_utf8(&bytes).map_err(|e| {
-            log::warn!("error converting bytes to string: {:?}", e);
-            return StreamError::Closed; // TODO: Update error
-        })?;
-
-        // Send the bytes to the channel
-        // NOTE: If the buffer is full, this will fail and skip sending the bytes
-        // TODO: How to handle this gracefully?
-        if let Err(e) = self.sender.try_send(data.into()) {
-            log::warn!("error sending bytes to channel: {:?}", e);
-            return Err(StreamError::Closed);
-        }
-
-        Ok(())
+        self.queue(Self::message(bytes))
     }
 
     fn flush(&mut self) -> Result<(), StreamError> {
-        // TODO: Trigger the Message write here?
-        // This stream is always flushed
-        Ok(())
+        WebsocketOutputPipe::flush(self)
     }
 
-    // TODO: Implement this
     fn check_write(&mut self) -> Result<usize, StreamError> {
-        Ok(0)
+        WebsocketOutputPipe::check_write(self)
     }
 }
 
 #[async_trait::async_trait]
 impl wasmtime_wasi::p2::Pollable for WebsocketOutputPipe {
-    async fn ready(&mut self) {}
+    // Ready once there is room for a write, or the queue emptied after a flush
+    async fn ready(&mut self) {
+        if self.flushing {
+            // All of the queue is free once the task took every queued message
+            let room = self.sender.max_capacity() - usize::from(self.permit.is_some());
+            let _ = self.sender.reserve_many(room).await;
+            return;
+        }
+        if self.permit.is_none() {
+            // A closed queue is reported by check-write
+            self.permit = self.sender.clone().reserve_owned().await.ok();
+        }
+    }
 }
 
 impl AsyncWrite for WebsocketOutputPipe {
     fn poll_write(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, std::io::Error>> {
-        // Convert bytes to string
-        let data = std::str::from_utf8(buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let message = Self::message(Bytes::copy_from_slice(buf));
 
-        // Send the bytes to the channel
-        match self.sender.try_send(data.into()) {
-            Ok(()) => Poll::Ready(Ok(buf.len())),
+        // Send the message to the channel
+        match self.sender.try_send(message) {
+            Ok(()) => {
+                self.sent.fetch_add(buf.len() as u64, Ordering::Relaxed);
+                Poll::Ready(Ok(buf.len()))
+            }
             Err(mpsc::error::TrySendError::Full(_)) => {
-                // Channel is full, would block
+                // Channel is full, wake the writer once there is room again
+                let sender = self.sender.clone();
+                let waker = cx.waker().clone();
+                tokio::spawn(async move {
+                    let _ = sender.reserve().await;
+                    waker.wake();
+                });
                 Poll::Pending
             }
             Err(mpsc::error::TrySendError::Closed(_)) => Poll::Ready(Err(std::io::Error::new(
@@ -283,6 +847,16 @@ pub struct WebsocketInputPipe {
 }
 
 impl WebsocketInputPipe {
+    /// Read the chunks sent on the channel, closed by an error.
+    pub fn from_receiver(receiver: mpsc::Receiver<Result<Bytes, StreamError>>) -> Self {
+        Self {
+            closed: false,
+            buffer: None,
+            receiver,
+            _join_handle: None,
+        }
+    }
+
     pub fn new<T: tokio::io::AsyncRead + Send + Unpin + 'static>(mut reader: T) -> Self {
         // let (sender, receiver) = mpsc::channel(2048);
         let (sender, receiver) = mpsc::channel(2048);
@@ -0,0 +1,101 @@
+use minijinja::{Environment, ErrorKind};
+
+use anyhow::anyhow;
+use hayride_host_traits::template::{errors::ErrorCode, Error, TemplateTrait};
+
+pub struct TemplateBackend {
+    env: Environment<'static>,
+}
+
+impl TemplateBackend {
+    pub fn new(registry_path: String) -> Self {
+        let mut env = Environment::new();
+
+        // Named templates, including those referenced by `include` and `extends`,
+        // are loaded from the registry on first use and cached by the environment
+        env.set_loader(move |name| {
+            let mut path = match hayride_utils::paths::hayride::default_hayride_dir() {
+                Ok(path) => path,
+                Err(e) => {
+                    log::warn!("failed to find hayride dir: {}", e);
+                    return Ok(None);
+                }
+            };
+            path.push(registry_path.clone());
+
+            let path_str = path.to_string_lossy().to_string();
+            let template_path =
+                match hayride_utils::paths::registry::find_template_path(path_str, name) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        log::debug!("template not found in registry: {}: {}", name, e);
+                        return Ok(None);
+                    }
+                };
+
+            match std::fs::read_to_string(&template_path) {
+                Ok(source) => Ok(Some(source)),
+                Err(e) => Err(minijinja::Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("failed to read template {}", template_path.display()),
+                )
+                .with_source(e)),
+            }
+        });
+
+        Self { env }
+    }
+}
+
+impl TemplateTrait for TemplateBackend {
+    fn render(&mut self, source: String, data: String) -> Result<String, Error> {
+        let data = parse_data(&data)?;
+
+        self.env.render_str(&source, data).map_err(|e| {
+            log::warn!("failed to render template: {:#}", e);
+            error(&e)
+        })
+    }
+
+    fn render_named(&mut self, name: String, data: String) -> Result<String, Error> {
+        let data = parse_data(&data)?;
+
+        let template = self.env.get_template(&name).map_err(|e| {
+            log::warn!("failed to load template {}: {:#}", name, e);
+            error(&e)
+        })?;
+
+        template.render(data).map_err(|e| {
+            log::warn!("failed to render template {}: {:#}", name, e);
+            error(&e)
+        })
+    }
+}
+
+// Parse the json encoded template data, an empty string renders without data
+fn parse_data(data: &str) -> Result<serde_json::Value, Error> {
+    if data.trim().is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    serde_json::from_str(data).map_err(|e| {
+        log::warn!("failed to parse template data: {}", e);
+        Error {
+            code: ErrorCode::InvalidData,
+            data: anyhow!("invalid template data: {}", e),
+        }
+    })
+}
+
+// Keep the message of the minijinja error with the source lines it points at
+fn error(e: &minijinja::Error) -> Error {
+    let code = match e.kind() {
+        ErrorKind::TemplateNotFound => ErrorCode::TemplateNotFound,
+        ErrorKind::SyntaxError => ErrorCode::InvalidTemplate,
+        _ => ErrorCode::RenderFailed,
+    };
+    Error {
+        code,
+        data: anyhow!("{}{}", e, e.display_debug_info()),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::stores::bindings::Encoding;
 use crate::stores::prompt::{Prompt, PromptOptionsStoreFields, PromptStoreFields};
 use leptos::prelude::*;
 use reactive_stores::Store;
@@ -12,6 +13,8 @@ pub fn Config() -> impl IntoView {
     // let system_prompt = prompt.system();
     // Get the agent from global state
     let agent = prompt.agent();
+    // Get the wire encoding from global state
+    let encoding = prompt.encoding();
 
     view! {
         <div class="flex flex-col">
@@ -66,6 +69,21 @@ pub fn Config() -> impl IntoView {
                     <p class="text-center mt-2">Temperature: {move || temperature.get()}</p>
                 </div>
             </div>
+            <div class="dialog bg-base-100 shadow-md rounded-lg p-4">
+                <label class="label cursor-pointer">
+                    <span>Binary encoding (CBOR)</span>
+                    <input
+                        type="checkbox"
+                        class="toggle"
+                        prop:checked={move || encoding.get() == Encoding::Cbor}
+                        on:change=move |e| {
+                            if let Some(input) = e.target().and_then(|t| t.dyn_into::<leptos::web_sys::HtmlInputElement>().ok()) {
+                                encoding.set(if input.checked() { Encoding::Cbor } else { Encoding::Json });
+                            }
+                        }
+                    />
+                </label>
+            </div>
         </div>
     }
 }
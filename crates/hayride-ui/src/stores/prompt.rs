@@ -1,3 +1,4 @@
+use crate::stores::bindings::Encoding;
 use reactive_stores::Store;
 use serde::{Deserialize, Serialize};
 
@@ -45,6 +46,8 @@ pub struct Prompt {
     // pub system: String,
     pub agent: String,
     pub options: PromptOptions,
+    // Wire encoding used when talking to the runtime
+    pub encoding: Encoding,
 }
 
 #[derive(Clone, Serialize, Deserialize, Default, Store)]
@@ -4,19 +4,24 @@ use reactive_stores::Store;
 
 use crate::components::chat::{ChatBubble, ChatMessage, ChatTextArea};
 use crate::stores::bindings::{
-    api::Generate, Message, MessageContent, Request, RequestData, Response, ResponseData, Role,
+    api::Generate, Encoding, Message, MessageContent, Request, RequestData, Response, ResponseData,
+    Role,
 };
 use crate::stores::prompt::Prompt;
 use wasm_bindgen_futures::spawn_local;
 
-async fn fetch_generate(data: String) -> Result<Response, Error> {
+async fn fetch_generate(data: Vec<u8>, encoding: Encoding) -> Result<Response, Error> {
+    let body = js_sys::Uint8Array::from(data.as_slice());
     let response = reqwasm::http::Request::post("http://localhost:8082/v1/generate")
-        .body(data)
+        .header("Content-Type", encoding.content_type())
+        .header("Accept", encoding.content_type())
+        .body(body)
         .send()
         .await?;
 
-    // Getting response as a plain text, but could parse json here if needed
-    let prompt = response.json::<Response>().await?;
+    // The runtime responds in the requested encoding
+    let bytes = response.binary().await?;
+    let prompt = encoding.decode::<Response>(&bytes)?;
     Ok(prompt)
 }
 
@@ -77,7 +82,8 @@ pub fn Chat() -> impl IntoView {
                     metadata: metadata,
                 };
 
-                match serde_json::to_string(&request) {
+                let encoding = prompt.encoding;
+                match encoding.encode(&request) {
                     Ok(d) => {
                         // Spawn an async task
                         let set_messages = set_messages.clone();
@@ -97,7 +103,7 @@ pub fn Chat() -> impl IntoView {
                             set_message_sent.set(true);
 
                             // Call the async fetch function
-                            match fetch_generate(d.clone()).await {
+                            match fetch_generate(d, encoding).await {
                                 Ok(response_data) => {
                                     // console::log_1(&format!("Response: {:?}", response_data).into());
                                     if response_data.error.len() > 0 {
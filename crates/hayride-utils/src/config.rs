@@ -0,0 +1,833 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+
+use crate::paths::hayride::default_hayride_dir;
+
+// Name of the config file in the hayride dir
+const CONFIG_FILE: &str = "config.toml";
+
+/// Runtime configuration loaded from `~/.hayride/config.toml`.
+///
+/// Every field is optional in the file. Environment variables take precedence
+/// over the file so existing setups keep working.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Registry of morphs, relative to the hayride dir
+    pub registry_path: String,
+    /// Directory models are loaded from, relative to the hayride dir
+    pub model_path: String,
+    /// Morph run on startup, `HAYRIDE_BIN`
+    pub bin: String,
+    /// Function of the morph run on startup, `HAYRIDE_ENTRYPOINT`
+    pub entrypoint: String,
+    pub log: LogConfig,
+    pub subsystems: Subsystems,
+    pub server: ServerConfig,
+    pub timeouts: TimeoutConfig,
+    pub egress: EgressConfig,
+    pub http: HttpConfig,
+    pub ai: AiConfig,
+    pub db: DbConfig,
+    pub blob: BlobConfig,
+    pub admin: AdminConfig,
+    pub registry: RegistryConfig,
+    pub verify: VerifyConfig,
+    pub update: UpdateConfig,
+    pub output: OutputConfig,
+    pub cache: CacheConfig,
+    pub tracing: TracingConfig,
+    pub threads: ThreadsConfig,
+    /// Settings morphs read through `hayride:core/config`
+    pub settings: toml::Table,
+    /// Settings overriding `settings` for a morph, keyed by `package:name`
+    pub morph_settings: BTreeMap<String, toml::Table>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            registry_path: "registry/morphs".to_string(),
+            model_path: "ai/models".to_string(),
+            bin: "hayride-core:cli".to_string(),
+            entrypoint: "run".to_string(),
+            log: LogConfig::default(),
+            subsystems: Subsystems::default(),
+            server: ServerConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            egress: EgressConfig::default(),
+            http: HttpConfig::default(),
+            ai: AiConfig::default(),
+            db: DbConfig::default(),
+            blob: BlobConfig::default(),
+            admin: AdminConfig::default(),
+            registry: RegistryConfig::default(),
+            verify: VerifyConfig::default(),
+            update: UpdateConfig::default(),
+            output: OutputConfig::default(),
+            cache: CacheConfig::default(),
+            tracing: TracingConfig::default(),
+            threads: ThreadsConfig::default(),
+            settings: toml::Table::new(),
+            morph_settings: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `HAYRIDE_LOG_LEVEL`
+    pub level: String,
+    /// Log file in the hayride logs dir, `HAYRIDE_LOG`
+    pub file: String,
+    /// Access log of host servers in the hayride logs dir, `HAYRIDE_ACCESS_LOG`, empty to
+    /// disable it
+    pub access_file: String,
+    /// Rotate the access log after this many bytes
+    pub access_rotate_size: Option<u64>,
+    /// Rotated access logs kept
+    pub access_rotate_keep: usize,
+    /// Rotate the log after this many bytes
+    pub rotate_size: Option<u64>,
+    /// Rotate the log after this many seconds, e.g. `86400` to rotate it daily
+    pub rotate_interval: Option<f64>,
+    /// Rotated logs kept
+    pub rotate_keep: usize,
+    /// Level of the records morphs log through `hayride:core/logging`, keyed by
+    /// `package:name`, morphs not listed log at `level`
+    pub morphs: BTreeMap<String, String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: "hayride.log".to_string(),
+            access_file: "access.log".to_string(),
+            access_rotate_size: Some(10 * 1024 * 1024),
+            access_rotate_keep: 5,
+            rotate_size: Some(10 * 1024 * 1024),
+            rotate_interval: None,
+            rotate_keep: 5,
+            morphs: BTreeMap::new(),
+        }
+    }
+}
+
+/// Host capabilities linked for components.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Subsystems {
+    pub ai: bool,
+    pub mcp: bool,
+    pub silo: bool,
+    pub wac: bool,
+    pub template: bool,
+    pub validate: bool,
+    pub wasi: bool,
+    pub core: bool,
+    pub db: bool,
+    /// Durable key-value state of morphs with `hayride:kv`
+    pub kv: bool,
+    /// Content-addressed store of large artifacts with `hayride:blob`
+    pub blob: bool,
+    /// Serve the status dashboard behind the middleware of servers, `HAYRIDE_STATUS`
+    pub status: bool,
+}
+
+impl Default for Subsystems {
+    fn default() -> Self {
+        Self {
+            ai: true,
+            mcp: true,
+            silo: true,
+            wac: true,
+            template: true,
+            validate: true,
+            wasi: true,
+            core: true,
+            db: true,
+            kv: true,
+            blob: true,
+            status: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Overrides the address http server morphs configure for themselves
+    pub address: Option<String>,
+    pub websocket_address: String,
+    /// Reload server morphs when their wasm file changes, `HAYRIDE_WATCH`
+    pub watch: bool,
+    /// Serve http2 next to http1 on the listener of server morphs, `HAYRIDE_HTTP2`
+    pub http2: bool,
+    /// Server morphs served behind the listener of the main morph
+    pub routes: Vec<RouteConfig>,
+    pub middleware: MiddlewareConfig,
+    pub gateway: GatewayConfig,
+    pub pool: PoolConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: None,
+            websocket_address: "127.0.0.1:8082".to_string(),
+            watch: false,
+            http2: false,
+            routes: vec![],
+            middleware: MiddlewareConfig::default(),
+            gateway: GatewayConfig::default(),
+            pool: PoolConfig::default(),
+        }
+    }
+}
+
+/// Instances of server morphs kept ready for requests.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolConfig {
+    /// Instances of each server morph instantiated ahead of requests, `HAYRIDE_POOL_SIZE`
+    pub size: usize,
+    /// Reuse instances for later requests, only for morphs keeping no state between
+    /// requests, `HAYRIDE_POOL_REUSE`
+    pub reuse: bool,
+}
+
+/// OpenAI compatible api served by the host next to server morphs, under `/v1/`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+    /// Serve `/v1/chat/completions`, `/v1/embeddings` and `/v1/models`, `HAYRIDE_GATEWAY`
+    pub enabled: bool,
+    /// Bearer token requests must carry, `HAYRIDE_GATEWAY_TOKEN`
+    pub auth_token: Option<String>,
+    /// Prompt format of chat messages, one of `chatml`, `llama3`, `gemma` or `mistral`,
+    /// picked from the model name when unset
+    pub chat_format: Option<String>,
+}
+
+/// Checks and headers the host applies to requests of server morphs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiddlewareConfig {
+    /// Bearer token requests must carry, `HAYRIDE_SERVER_TOKEN`
+    pub auth_token: Option<String>,
+    /// Origins allowed by CORS, `*` allows any and none disables CORS headers
+    pub cors_origins: Vec<String>,
+    pub cors_methods: String,
+    pub cors_headers: String,
+    /// Largest request body accepted, in bytes, `HAYRIDE_MAX_BODY_SIZE`
+    pub max_body_size: Option<u64>,
+    /// Requests per second allowed for each client address
+    pub rate_limit: Option<f64>,
+    /// Requests a client may make at once before the rate limit applies
+    pub rate_burst: u32,
+    /// Keys every request must carry, the auth token is accepted as a key of every scope
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+impl Default for MiddlewareConfig {
+    fn default() -> Self {
+        Self {
+            auth_token: None,
+            cors_origins: vec!["*".to_string()],
+            cors_methods: "GET, POST, OPTIONS".to_string(),
+            cors_headers: "*".to_string(),
+            max_body_size: None,
+            rate_limit: None,
+            rate_burst: 10,
+            api_keys: vec![],
+        }
+    }
+}
+
+/// A key clients send as `X-API-Key` or as a bearer token.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Name of the holder of the key, passed to morphs in `X-Hayride-Key-Name`
+    pub name: String,
+    pub key: String,
+    /// What the key may access: `*` for everything, `status`, `gateway`, or path
+    /// prefixes of morph requests like `/api`
+    pub scopes: Vec<String>,
+}
+
+/// A server morph handling the requests matching a host and path prefix.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Morph handling the route, as `package:name`
+    pub morph: String,
+    /// Host the route matches, any host without one
+    pub host: Option<String>,
+    /// Path prefix the route matches, stripped before the morph sees the request
+    pub path: Option<String>,
+}
+
+/// Deadlines for host calls in seconds.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    pub ai: Option<f64>,
+    pub rag: Option<f64>,
+    pub db: Option<f64>,
+    pub wac: Option<f64>,
+}
+
+/// Outbound http policy as `host[:port]` rules.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EgressConfig {
+    pub allow: Vec<String>,
+    /// Hosts reachable by a name or address not listed here are still allowed, only
+    /// `deny_by_default` with an allow list restricts egress
+    pub deny: Vec<String>,
+    pub deny_by_default: bool,
+}
+
+/// Proxies, trusted certificates and identity of outgoing http requests.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy url for http requests, `HTTP_PROXY`
+    pub proxy: Option<String>,
+    /// Proxy url for https requests, `HTTPS_PROXY`
+    pub https_proxy: Option<String>,
+    /// Hosts and domains reached without a proxy, `NO_PROXY`
+    pub no_proxy: Vec<String>,
+    /// PEM file of CA certificates trusted besides the bundled roots, `HAYRIDE_CA_BUNDLE`
+    pub ca_bundle: Option<String>,
+    /// User-Agent of requests that do not set one
+    pub user_agent: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            https_proxy: None,
+            no_proxy: vec![],
+            ca_bundle: None,
+            user_agent: format!("hayride/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AiConfig {
+    /// File of patterns masked in inference output, `HAYRIDE_OUTPUT_FILTER`
+    pub output_filter: Option<String>,
+    /// Post-processing of compute output, the first profile matching the model applies
+    pub profiles: Vec<ModelProfile>,
+    /// Token for gated and private Hugging Face repositories, `HAYRIDE_HF_TOKEN`
+    pub hf_token: Option<String>,
+    /// Serve models from local directories instead of the Hugging Face hub
+    pub local_models: Option<LocalModelsConfig>,
+    /// Serve the models of an Ollama instance instead of the Hugging Face hub
+    pub ollama: Option<OllamaConfig>,
+    /// Record the compute calls of sessions to their `trace.jsonl`, `HAYRIDE_AI_TRACE`
+    pub trace: bool,
+}
+
+/// An Ollama instance on the same machine, whose model blobs hayride can read.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OllamaConfig {
+    /// Url of the Ollama api, `HAYRIDE_OLLAMA_HOST`
+    pub endpoint: String,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:11434".to_string(),
+        }
+    }
+}
+
+/// Directories models are served from, e.g. a mounted NAS share.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalModelsConfig {
+    /// Directories scanned for models in order, relative to the hayride dir,
+    /// `HAYRIDE_MODEL_ROOTS` separated like `PATH`
+    pub roots: Vec<String>,
+    /// Serve files and directories that are symlinks
+    pub follow_symlinks: bool,
+}
+
+impl Default for LocalModelsConfig {
+    fn default() -> Self {
+        Self {
+            roots: vec![],
+            follow_symlinks: true,
+        }
+    }
+}
+
+/// Settings of models with a name matching a pattern.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ModelProfile {
+    /// Pattern matched against the lowercase file name of models, `*` matches anything
+    pub model: String,
+    /// Steps applied in order to compute output, output is left as is without a profile
+    #[serde(default)]
+    pub post_process: Vec<PostStep>,
+}
+
+/// A step of post-processing, i.e. `{ step = "strip_stop", sequences = ["</s>"] }`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PostStep {
+    Trim,
+    StripStop {
+        sequences: Vec<String>,
+    },
+    StripThink {
+        open: Option<String>,
+        close: Option<String>,
+    },
+    Replace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+}
+
+/// Cache of `hayride:db` query results and prepared statements.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbConfig {
+    /// Seconds results are cached for, caching is disabled without one, `HAYRIDE_DB_CACHE_TTL`
+    pub cache_ttl: Option<f64>,
+    pub cache_max_entries: usize,
+    /// Results with more rows are not cached
+    pub cache_max_rows: usize,
+    /// Statements kept prepared per connection, unless its connection string sets
+    /// `statement_cache`
+    pub statement_cache: usize,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl: None,
+            cache_max_entries: 1024,
+            cache_max_rows: 1000,
+            statement_cache: 64,
+        }
+    }
+}
+
+/// Store of `hayride:blob` blobs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlobConfig {
+    /// Directory blobs are stored in, relative to the hayride dir
+    pub path: String,
+    /// Store blobs in an S3 compatible bucket instead of the directory
+    pub s3: Option<S3Config>,
+}
+
+impl Default for BlobConfig {
+    fn default() -> Self {
+        Self {
+            path: "blobs".to_string(),
+            s3: None,
+        }
+    }
+}
+
+/// An S3 compatible bucket, addressed with path style urls.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// Url of the service, e.g. `https://s3.us-east-1.amazonaws.com`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// Prefix of the keys blobs are stored at
+    pub prefix: String,
+    /// `AWS_ACCESS_KEY_ID`
+    pub access_key_id: Option<String>,
+    /// `AWS_SECRET_ACCESS_KEY`
+    pub secret_access_key: Option<String>,
+    /// `AWS_SESSION_TOKEN`, for temporary credentials
+    pub session_token: Option<String>,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            prefix: "blobs".to_string(),
+            access_key_id: None,
+            secret_access_key: None,
+            session_token: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token for the capability admin api, `HAYRIDE_ADMIN_TOKEN`
+    pub token: Option<String>,
+    /// Address the admin api of the daemon listens on, `HAYRIDE_DAEMON_ADDRESS`
+    pub daemon_address: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            daemon_address: "127.0.0.1:8083".to_string(),
+        }
+    }
+}
+
+/// Remote registry morphs are pulled from when missing locally.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// Base url of the registry, `HAYRIDE_REGISTRY_URL`
+    pub url: Option<String>,
+    /// Bearer token sent to the registry, `HAYRIDE_REGISTRY_TOKEN`
+    pub token: Option<String>,
+    /// PKCS#8 ed25519 key file pushed morphs are signed with
+    pub signing_key: Option<String>,
+    /// Base64 ed25519 public keys pulled morphs must be signed by
+    pub trusted_keys: Vec<String>,
+}
+
+/// Signature checks of morphs before they are instantiated.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerifyConfig {
+    /// Reject unsigned and invalid morphs instead of warning, `HAYRIDE_VERIFY`
+    pub enforce: bool,
+    /// Base64 ed25519 public keys morphs may be signed by
+    pub trusted_keys: Vec<String>,
+    /// Directory of `.pub` key files, relative to the hayride dir
+    pub key_dir: String,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            enforce: false,
+            trusted_keys: vec![],
+            key_dir: "keys/trusted".to_string(),
+        }
+    }
+}
+
+/// Signature checks of releases installed with `hayride update`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// Base64 ed25519 public keys release assets must be signed by
+    pub trusted_keys: Vec<String>,
+    /// Directory of `.pub` key files, relative to the hayride dir
+    pub key_dir: String,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            trusted_keys: vec![],
+            key_dir: "keys/release".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Write reactor results as json lines to stdout or the out file, `HAYRIDE_JSON_LINES`
+    pub json_lines: bool,
+    /// Compress session out and err files with zstd, `HAYRIDE_COMPRESS_OUTPUT`
+    pub compress: bool,
+    /// Rotate session out and err files after this many bytes of output
+    pub rotate_size: Option<u64>,
+    /// Rotated files kept for each session file
+    pub rotate_keep: usize,
+    /// Remove sessions not written to for this many seconds, `HAYRIDE_SESSION_MAX_AGE`
+    pub max_age: Option<f64>,
+    /// Remove the oldest sessions once all sessions take more than this many bytes,
+    /// `HAYRIDE_SESSION_MAX_SIZE`
+    pub max_size: Option<u64>,
+    /// Seconds between removing sessions past their max age or size
+    pub cleanup_interval: f64,
+    /// Log stdout and stderr of morphs through the host logger, tagged with their
+    /// session and name, `HAYRIDE_LOG_STDIO`
+    pub log_stdio: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            json_lines: false,
+            compress: false,
+            rotate_size: None,
+            rotate_keep: 3,
+            max_age: None,
+            max_size: None,
+            cleanup_interval: 3600.0,
+            log_stdio: false,
+        }
+    }
+}
+
+/// Precompiled components reused across runs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Cache compiled components, `HAYRIDE_COMPONENT_CACHE`
+    pub components: bool,
+    /// Directory of the cache, relative to the hayride dir
+    pub dir: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            components: true,
+            dir: "cache/components".to_string(),
+        }
+    }
+}
+
+/// Spans of engine runs, requests, threads, ai and db calls exported over OTLP.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingConfig {
+    /// OTLP/HTTP collector spans are sent to, i.e. `http://localhost:4318`, tracing is off
+    /// without one, `OTEL_EXPORTER_OTLP_ENDPOINT`
+    pub endpoint: Option<String>,
+    /// Headers sent with every export, `OTEL_EXPORTER_OTLP_HEADERS` as `key=value,...`
+    pub headers: BTreeMap<String, String>,
+    /// `OTEL_SERVICE_NAME`
+    pub service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            headers: BTreeMap::new(),
+            service_name: "hayride".to_string(),
+        }
+    }
+}
+
+/// Limits on the threads morphs spawn through `hayride:silo/threads`.
+///
+/// Threads spawned past a limit wait in a queue until another thread finishes.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThreadsConfig {
+    /// Threads running at once, unlimited without one, `HAYRIDE_MAX_THREADS`
+    pub max_concurrent: Option<usize>,
+    /// Threads of a morph running at once, keyed by `package:name`
+    pub morphs: BTreeMap<String, usize>,
+}
+
+impl Config {
+    /// Load the config from `HAYRIDE_CONFIG` or the hayride dir, falling back to the
+    /// defaults if there is no config file, then apply environment overrides.
+    pub fn load() -> Result<Self> {
+        let path = match env::var("HAYRIDE_CONFIG") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => default_hayride_dir()?.join(CONFIG_FILE),
+        };
+
+        let mut config = if path.exists() {
+            Self::from_file(&path)?
+        } else {
+            Self::default()
+        };
+        config.apply_env();
+        config.check_tokens()?;
+        Ok(config)
+    }
+
+    // An empty token would be matched by requests without one, so auth is turned off by
+    // leaving a token unset instead
+    fn check_tokens(&self) -> Result<()> {
+        let tokens = [
+            (
+                "server.middleware.auth_token",
+                &self.server.middleware.auth_token,
+            ),
+            ("server.gateway.auth_token", &self.server.gateway.auth_token),
+            ("admin.token", &self.admin.token),
+        ];
+        for (name, token) in tokens {
+            if token.as_ref().is_some_and(|token| token.trim().is_empty()) {
+                return Err(anyhow!("{} is empty, leave it unset to disable auth", name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        toml::from_str(&contents)
+            .map_err(|e| anyhow!("Failed to parse config {}: {}", path.display(), e))
+    }
+
+    // Environment variables the runtime has always read override the file
+    fn apply_env(&mut self) {
+        if let Ok(bin) = env::var("HAYRIDE_BIN") {
+            self.bin = bin;
+        }
+        if let Ok(entrypoint) = env::var("HAYRIDE_ENTRYPOINT") {
+            self.entrypoint = entrypoint;
+        }
+        if let Ok(file) = env::var("HAYRIDE_LOG") {
+            self.log.file = file;
+        }
+        if let Ok(level) = env::var("HAYRIDE_LOG_LEVEL") {
+            self.log.level = level;
+        }
+        if let Ok(access_file) = env::var("HAYRIDE_ACCESS_LOG") {
+            self.log.access_file = access_file;
+        }
+        if let Ok(status) = env::var("HAYRIDE_STATUS") {
+            self.subsystems.status = status == "true" || status == "1";
+        }
+        if let Ok(output_filter) = env::var("HAYRIDE_OUTPUT_FILTER") {
+            self.ai.output_filter = Some(output_filter);
+        }
+        if let Some(roots) = env::var_os("HAYRIDE_MODEL_ROOTS") {
+            let local_models = self.ai.local_models.get_or_insert_with(Default::default);
+            local_models.roots = env::split_paths(&roots)
+                .filter(|root| !root.as_os_str().is_empty())
+                .map(|root| root.to_string_lossy().to_string())
+                .collect();
+        }
+        if let Ok(endpoint) = env::var("HAYRIDE_OLLAMA_HOST") {
+            self.ai.ollama = Some(OllamaConfig { endpoint });
+        }
+        if let Ok(token) = env::var("HAYRIDE_HF_TOKEN") {
+            self.ai.hf_token = Some(token);
+        }
+        if let Ok(trace) = env::var("HAYRIDE_AI_TRACE") {
+            self.ai.trace = trace == "true" || trace == "1";
+        }
+        if let Ok(token) = env::var("HAYRIDE_ADMIN_TOKEN") {
+            self.admin.token = Some(token);
+        }
+        if let Ok(address) = env::var("HAYRIDE_DAEMON_ADDRESS") {
+            self.admin.daemon_address = address;
+        }
+        if let Ok(url) = env::var("HAYRIDE_REGISTRY_URL") {
+            self.registry.url = Some(url);
+        }
+        if let Ok(token) = env::var("HAYRIDE_REGISTRY_TOKEN") {
+            self.registry.token = Some(token);
+        }
+        if let Ok(enforce) = env::var("HAYRIDE_VERIFY") {
+            self.verify.enforce = enforce == "true" || enforce == "1";
+        }
+        if let Ok(token) = env::var("HAYRIDE_SERVER_TOKEN") {
+            self.server.middleware.auth_token = Some(token);
+        }
+        if let Some(max) = env::var("HAYRIDE_MAX_BODY_SIZE")
+            .ok()
+            .and_then(|max| max.parse().ok())
+        {
+            self.server.middleware.max_body_size = Some(max);
+        }
+        if let Ok(gateway) = env::var("HAYRIDE_GATEWAY") {
+            self.server.gateway.enabled = gateway == "true" || gateway == "1";
+        }
+        if let Ok(token) = env::var("HAYRIDE_GATEWAY_TOKEN") {
+            self.server.gateway.auth_token = Some(token);
+        }
+        if let Some(size) = env::var("HAYRIDE_POOL_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+        {
+            self.server.pool.size = size;
+        }
+        if let Some(max_concurrent) = env::var("HAYRIDE_MAX_THREADS")
+            .ok()
+            .and_then(|max_concurrent| max_concurrent.parse().ok())
+        {
+            self.threads.max_concurrent = Some(max_concurrent);
+        }
+        if let Ok(reuse) = env::var("HAYRIDE_POOL_REUSE") {
+            self.server.pool.reuse = reuse == "true" || reuse == "1";
+        }
+        if let Ok(watch) = env::var("HAYRIDE_WATCH") {
+            self.server.watch = watch == "true" || watch == "1";
+        }
+        if let Ok(http2) = env::var("HAYRIDE_HTTP2") {
+            self.server.http2 = http2 == "true" || http2 == "1";
+        }
+        if let Ok(components) = env::var("HAYRIDE_COMPONENT_CACHE") {
+            self.cache.components = components == "true" || components == "1";
+        }
+        if let Ok(json_lines) = env::var("HAYRIDE_JSON_LINES") {
+            self.output.json_lines = json_lines == "true" || json_lines == "1";
+        }
+        if let Ok(log_stdio) = env::var("HAYRIDE_LOG_STDIO") {
+            self.output.log_stdio = log_stdio == "true" || log_stdio == "1";
+        }
+        if let Ok(compress) = env::var("HAYRIDE_COMPRESS_OUTPUT") {
+            self.output.compress = compress == "true" || compress == "1";
+        }
+        if let Some(max_age) = env::var("HAYRIDE_SESSION_MAX_AGE")
+            .ok()
+            .and_then(|max_age| max_age.parse().ok())
+        {
+            self.output.max_age = Some(max_age);
+        }
+        if let Some(max_size) = env::var("HAYRIDE_SESSION_MAX_SIZE")
+            .ok()
+            .and_then(|max_size| max_size.parse().ok())
+        {
+            self.output.max_size = Some(max_size);
+        }
+        if let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            self.tracing.endpoint = Some(endpoint);
+        }
+        if let Ok(headers) = env::var("OTEL_EXPORTER_OTLP_HEADERS") {
+            self.tracing.headers = headers
+                .split(',')
+                .filter_map(|header| header.split_once('='))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect();
+        }
+        if let Ok(service_name) = env::var("OTEL_SERVICE_NAME") {
+            self.tracing.service_name = service_name;
+        }
+        if let Some(s3) = self.blob.s3.as_mut() {
+            if let Ok(access_key_id) = env::var("AWS_ACCESS_KEY_ID") {
+                s3.access_key_id = Some(access_key_id);
+            }
+            if let Ok(secret_access_key) = env::var("AWS_SECRET_ACCESS_KEY") {
+                s3.secret_access_key = Some(secret_access_key);
+            }
+            if let Ok(session_token) = env::var("AWS_SESSION_TOKEN") {
+                s3.session_token = Some(session_token);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod log;
 pub mod paths;
 pub mod wit;
@@ -1,3 +1,5 @@
 pub mod logger;
+pub mod rotate;
 
-pub use logger::init_logger;
+pub use logger::{init_logger, init_logger_with_targets};
+pub use rotate::{RotatingFile, Rotation};
@@ -1,3 +1,4 @@
+use super::rotate::{RotatingFile, Rotation};
 use env_logger::{Builder, Env, Logger};
 use log::{Level, LevelFilter};
 use log_reload::{ReloadHandle, ReloadLog};
@@ -7,6 +8,7 @@ use toml::Value;
 
 static LOG_HANDLE: OnceLock<ReloadHandle<log_reload::LevelFilter<Logger>>> = OnceLock::new();
 static LOG_PATH: OnceLock<String> = OnceLock::new();
+static LOG_ROTATION: OnceLock<Rotation> = OnceLock::new();
 
 /// Sets a static log path that all future logger initializations will use.
 pub fn set_log_path(path: String) -> anyhow::Result<()> {
@@ -16,9 +18,26 @@ pub fn set_log_path(path: String) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Sets how the log file is rotated, by default it is never rotated.
+pub fn set_log_rotation(rotation: Rotation) -> anyhow::Result<()> {
+    if LOG_ROTATION.set(rotation).is_err() {
+        return Err(anyhow::anyhow!("Log rotation has already been set"));
+    }
+    Ok(())
+}
+
 /// Initializes the logger with a specific log level for the workspace crates.
 /// Will only initialize once, even if called multiple times to prevent multiple env logger initialization
 pub fn init_logger(log_level: String) -> anyhow::Result<()> {
+    init_logger_with_targets(log_level, &[])
+}
+
+/// Initializes the logger like `init_logger`, logging each target at its own level instead
+/// of the level of the workspace crates.
+pub fn init_logger_with_targets(
+    log_level: String,
+    targets: &[(&str, LevelFilter)],
+) -> anyhow::Result<()> {
     let workspace_crates = get_workspace_crates();
 
     // Get the log level from the string
@@ -39,6 +58,13 @@ pub fn init_logger(log_level: String) -> anyhow::Result<()> {
         log::Level::Trace => LevelFilter::Trace,
     };
 
+    // The most verbose level logged, targets may log more than the workspace crates
+    let max_filter = targets
+        .iter()
+        .map(|(_, filter)| *filter)
+        .fold(level_filter, std::cmp::max);
+    let max_level = max_filter.to_level().unwrap_or(level);
+
     // Get or init the log handle with the specified log level
     let log_handle = LOG_HANDLE.get_or_init(|| {
         let mut builder = Builder::from_env(Env::default());
@@ -50,12 +76,15 @@ pub fn init_logger(log_level: String) -> anyhow::Result<()> {
         for crate_name in workspace_crates.clone() {
             builder.filter_module(crate_name.as_str(), level_filter);
         }
+        for (target, filter) in targets {
+            builder.filter_module(target, *filter);
+        }
 
         let logger = builder.build();
-        log::set_max_level(level_filter);
+        log::set_max_level(max_filter);
 
         // Create a new logger that will filter the logs based on the max level
-        let level_filter_logger = log_reload::LevelFilter::new(level, logger);
+        let level_filter_logger = log_reload::LevelFilter::new(max_level, logger);
 
         let reload_log = ReloadLog::new(level_filter_logger);
         let handle = reload_log.handle();
@@ -78,6 +107,9 @@ pub fn init_logger(log_level: String) -> anyhow::Result<()> {
     for crate_name in workspace_crates {
         builder.filter_module(crate_name.as_str(), level_filter);
     }
+    for (target, filter) in targets {
+        builder.filter_module(target, *filter);
+    }
 
     // Ensure the log directory exists before creating the file
     if let Some(log_path) = LOG_PATH.get() {
@@ -86,18 +118,16 @@ pub fn init_logger(log_level: String) -> anyhow::Result<()> {
         }
 
         // Open the log file in append mode if it exists, or create it otherwise
-        let log_file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_path)?;
+        let rotation = LOG_ROTATION.get().copied().unwrap_or_default();
+        let log_file = RotatingFile::open(log_path, rotation)?;
         builder.target(env_logger::Target::Pipe(Box::new(log_file)));
     }
 
     let logger = builder.build();
-    log::set_max_level(level_filter);
+    log::set_max_level(max_filter);
 
     // Create a new logger that will filter the logs based on the max level
-    let level_filter_logger = log_reload::LevelFilter::new(level, logger);
+    let level_filter_logger = log_reload::LevelFilter::new(max_level, logger);
 
     return log_handle
         .replace(level_filter_logger)
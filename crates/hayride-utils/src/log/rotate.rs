@@ -0,0 +1,156 @@
+use crate::config::LogConfig;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// When a log file is rotated, and how many rotated files are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    /// Rotate once the file grew past this many bytes
+    pub size: Option<u64>,
+    /// Rotate once the file was written to for this long
+    pub interval: Option<Duration>,
+    /// Rotated files kept as `<file>.1` to `<file>.<keep>`, newest first
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            size: None,
+            interval: None,
+            keep: 5,
+        }
+    }
+}
+
+impl Rotation {
+    pub fn from_config(config: &LogConfig) -> Self {
+        Self {
+            size: config.rotate_size,
+            interval: config
+                .rotate_interval
+                .filter(|interval| *interval > 0.0)
+                .map(Duration::from_secs_f64),
+            keep: config.rotate_keep,
+        }
+    }
+}
+
+/// A log file opened for appending, rotated by size and age.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: Option<File>,
+    size: u64,
+    // When the current file was started
+    started: SystemTime,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl Into<PathBuf>, rotation: Rotation) -> std::io::Result<Self> {
+        let mut file = Self {
+            path: path.into(),
+            rotation,
+            file: None,
+            size: 0,
+            started: SystemTime::now(),
+        };
+        file.open_file()?;
+        Ok(file)
+    }
+
+    fn open_file(&mut self) -> std::io::Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.size = metadata.len();
+        // A file kept from an earlier run was started when it was created
+        self.started = match self.size {
+            0 => SystemTime::now(),
+            _ => metadata.created().unwrap_or_else(|_| SystemTime::now()),
+        };
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn due(&self, len: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let full = self
+            .rotation
+            .size
+            .is_some_and(|size| self.size + len as u64 > size);
+        let expired = self.rotation.interval.is_some_and(|interval| {
+            self.started
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed >= interval)
+        });
+        full || expired
+    }
+
+    // Shift rotated files up by one, dropping the oldest, and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+
+        let keep = self.rotation.keep;
+        if keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated_path(&self.path, keep));
+            for index in (1..keep).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.open_file()
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.due(buf.len()) {
+            // Keep logging to the current file if it can not be rotated, retrying once
+            // another rotation is due
+            if let Err(e) = self.rotate() {
+                eprintln!("failed to rotate {}: {:?}", self.path.display(), e);
+                if self.file.is_none() {
+                    self.open_file()?;
+                }
+                self.size = 0;
+                self.started = SystemTime::now();
+            }
+        }
+
+        let file = self
+            .file
+            .as_mut()
+            .ok_or_else(|| std::io::Error::other("log file closed"))?;
+        let n = file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
@@ -7,6 +7,17 @@ use std::path::PathBuf;
 /// in the format package:name@version
 /// If version is not set, the latest version will be used
 pub fn find_morph_path(registry_path: String, input: &str) -> Result<PathBuf> {
+    find_package_path(registry_path, input, "wasm")
+}
+
+/// Find a template file path with the given package and template name and optional version
+/// in the format package:name@version
+/// If version is not set, the latest version will be used
+pub fn find_template_path(registry_path: String, input: &str) -> Result<PathBuf> {
+    find_package_path(registry_path, input, "jinja")
+}
+
+fn find_package_path(registry_path: String, input: &str, extension: &str) -> Result<PathBuf> {
     match parse_identifier(input) {
         Some((package, name, version)) => {
             let mut path = PathBuf::new();
@@ -34,18 +45,19 @@ pub fn find_morph_path(registry_path: String, input: &str) -> Result<PathBuf> {
                 path.push(latest_version.file_name());
             }
 
-            path.push(format!("{}.wasm", name));
+            path.push(format!("{}.{}", name, extension));
             path = path.canonicalize()?;
             Ok(path)
         }
         None => Err(anyhow::anyhow!(
-            "Invalid morph identifier: [{}] expected format: <package>:<name>@<version>",
+            "Invalid identifier: [{}] expected format: <package>:<name>@<version>",
             input
         )),
     }
 }
 
-fn parse_identifier(input: &str) -> Option<(&str, &str, Option<&str>)> {
+/// Split an identifier in the format package:name@version into its parts
+pub fn parse_identifier(input: &str) -> Option<(&str, &str, Option<&str>)> {
     let (package, rest) = input.split_once(':')?;
     let (name, version) = rest
         .split_once('@')
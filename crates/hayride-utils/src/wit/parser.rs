@@ -6,12 +6,48 @@ use wit_parser::decoding::DecodedWasm;
 pub struct WitParser {
     imports: Vec<wit_parser::Package>,
     function_exports: Vec<Function>,
+    world_imports: Vec<WorldItem>,
+    world_exports: Vec<WorldItem>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub function: wit_parser::Function,
     pub interface: Option<wit_parser::Interface>,
+    pub signature: Signature,
+}
+
+/// Parameter and result types of a function, as written in WIT.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub params: Vec<(String, String)>,
+    pub result: Option<String>,
+}
+
+impl Signature {
+    /// Render the function as written in WIT, e.g. `get: func(key: string) -> option<string>`.
+    pub fn to_wit(&self, name: &str) -> String {
+        let params = self
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match &self.result {
+            Some(result) => format!("{}: func({}) -> {}", name, params, result),
+            None => format!("{}: func({})", name, params),
+        }
+    }
+}
+
+/// An interface, function or type of the world of a component.
+#[derive(Debug, Clone)]
+pub struct WorldItem {
+    /// Interface name with its package, e.g. `wasi:cli/run@0.2.0`, or the name of the
+    /// function or type.
+    pub name: String,
+    /// Functions of the item as written in WIT.
+    pub functions: Vec<String>,
 }
 
 impl WitParser {
@@ -20,10 +56,14 @@ impl WitParser {
         let resolved: &wit_parser::Resolve = decoded.resolve();
         let imports = parse_imports(resolved)?;
         let functions = parse_function_exports(resolved)?;
+        let world_imports = parse_world_items(resolved, false);
+        let world_exports = parse_world_items(resolved, true);
 
         Ok(Self {
             imports: imports,
             function_exports: functions,
+            world_imports,
+            world_exports,
         })
     }
 
@@ -34,6 +74,16 @@ impl WitParser {
     pub fn function_exports(&self) -> &Vec<Function> {
         return &self.function_exports;
     }
+
+    /// Everything the component imports, in the order of its world.
+    pub fn world_imports(&self) -> &[WorldItem] {
+        &self.world_imports
+    }
+
+    /// Everything the component exports, in the order of its world.
+    pub fn world_exports(&self) -> &[WorldItem] {
+        &self.world_exports
+    }
 }
 
 /// parse imports and return a list of packages
@@ -90,6 +140,7 @@ fn parse_function_exports(resolved: &wit_parser::Resolve) -> Result<Vec<Function
                                 let f = Function {
                                     function: f.1.clone(),
                                     interface: Some(i.clone()),
+                                    signature: signature(resolved, f.1),
                                 };
                                 functions.push(f);
                             });
@@ -103,6 +154,7 @@ fn parse_function_exports(resolved: &wit_parser::Resolve) -> Result<Vec<Function
                     let f = Function {
                         function: f.clone(),
                         interface: None,
+                        signature: signature(resolved, f),
                     };
                     functions.push(f);
                 }
@@ -115,3 +167,106 @@ fn parse_function_exports(resolved: &wit_parser::Resolve) -> Result<Vec<Function
 
     return Ok(functions);
 }
+
+/// parse the imports or exports of the worlds, with the functions of each interface
+fn parse_world_items(resolved: &wit_parser::Resolve, exports: bool) -> Vec<WorldItem> {
+    let render = |f: &wit_parser::Function| signature(resolved, f).to_wit(&f.name);
+
+    resolved
+        .worlds
+        .iter()
+        .flat_map(|(_, world)| {
+            if exports {
+                &world.exports
+            } else {
+                &world.imports
+            }
+        })
+        .map(|(key, item)| {
+            let functions = match item {
+                wit_parser::WorldItem::Interface { id, .. } => resolved
+                    .interfaces
+                    .get(*id)
+                    .map(|i| i.functions.values().map(render).collect())
+                    .unwrap_or_default(),
+                wit_parser::WorldItem::Function(f) => vec![render(f)],
+                wit_parser::WorldItem::Type(_) => vec![],
+            };
+            WorldItem {
+                name: resolved.name_world_key(key),
+                functions,
+            }
+        })
+        .collect()
+}
+
+fn signature(resolved: &wit_parser::Resolve, function: &wit_parser::Function) -> Signature {
+    let params = function
+        .params
+        .iter()
+        .map(|(name, ty)| (name.clone(), type_name(resolved, ty)))
+        .collect();
+    let result = match &function.results {
+        wit_parser::Results::Anon(ty) => Some(type_name(resolved, ty)),
+        wit_parser::Results::Named(results) if results.is_empty() => None,
+        wit_parser::Results::Named(results) => Some(format!(
+            "({})",
+            results
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, type_name(resolved, ty)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    };
+
+    Signature { params, result }
+}
+
+/// Render a type as it would be written in WIT, named types by their name.
+fn type_name(resolved: &wit_parser::Resolve, ty: &wit_parser::Type) -> String {
+    use wit_parser::{Handle, Type, TypeDefKind};
+
+    let id = match ty {
+        Type::Bool => return "bool".to_string(),
+        Type::U8 => return "u8".to_string(),
+        Type::U16 => return "u16".to_string(),
+        Type::U32 => return "u32".to_string(),
+        Type::U64 => return "u64".to_string(),
+        Type::S8 => return "s8".to_string(),
+        Type::S16 => return "s16".to_string(),
+        Type::S32 => return "s32".to_string(),
+        Type::S64 => return "s64".to_string(),
+        Type::F32 => return "f32".to_string(),
+        Type::F64 => return "f64".to_string(),
+        Type::Char => return "char".to_string(),
+        Type::String => return "string".to_string(),
+        Type::Id(id) => *id,
+    };
+    let Some(def) = resolved.types.get(id) else {
+        return "unknown".to_string();
+    };
+    if let Some(name) = &def.name {
+        return name.clone();
+    }
+
+    let name = |ty: &Type| type_name(resolved, ty);
+    let optional = |ty: &Option<Type>| ty.as_ref().map(name).unwrap_or("_".to_string());
+    match &def.kind {
+        TypeDefKind::Type(ty) => name(ty),
+        TypeDefKind::List(ty) => format!("list<{}>", name(ty)),
+        TypeDefKind::Option(ty) => format!("option<{}>", name(ty)),
+        TypeDefKind::Result(result) => match (&result.ok, &result.err) {
+            (None, None) => "result".to_string(),
+            (ok, err) => format!("result<{}, {}>", optional(ok), optional(err)),
+        },
+        TypeDefKind::Tuple(tuple) => format!(
+            "tuple<{}>",
+            tuple.types.iter().map(name).collect::<Vec<_>>().join(", ")
+        ),
+        TypeDefKind::Handle(Handle::Own(id)) => name(&Type::Id(*id)),
+        TypeDefKind::Handle(Handle::Borrow(id)) => format!("borrow<{}>", name(&Type::Id(*id))),
+        TypeDefKind::Future(ty) => format!("future<{}>", optional(ty)),
+        TypeDefKind::Stream(ty) => format!("stream<{}>", optional(ty)),
+        kind => kind.as_str().to_string(),
+    }
+}
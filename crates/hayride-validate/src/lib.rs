@@ -0,0 +1,40 @@
+mod validator;
+
+pub use validator::Validator;
+
+use hayride_host_traits::validate::{
+    errors::ErrorCode, Schema, ValidateSchema, ValidateTrait, ValidationError,
+};
+
+/// Validates json documents against JSON schemas (draft 2020-12) on the host.
+#[derive(Default)]
+pub struct ValidateBackend {}
+
+impl ValidateBackend {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ValidateTrait for ValidateBackend {
+    fn compile(&mut self, schema: String) -> Result<Schema, ErrorCode> {
+        let validator = Validator::compile(&schema).map_err(|e| {
+            log::warn!("failed to compile json schema: {}", e);
+            ErrorCode::InvalidSchema
+        })?;
+
+        let schema: Box<dyn ValidateSchema> = Box::new(validator);
+        Ok(Schema::from(schema))
+    }
+}
+
+impl ValidateSchema for Validator {
+    fn validate(&self, document: String) -> Result<Vec<ValidationError>, ErrorCode> {
+        let document: serde_json::Value = serde_json::from_str(&document).map_err(|e| {
+            log::warn!("failed to parse json document: {}", e);
+            ErrorCode::InvalidDocument
+        })?;
+
+        Ok(Validator::validate(self, &document))
+    }
+}
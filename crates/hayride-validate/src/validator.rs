@@ -0,0 +1,35 @@
+use serde_json::Value;
+
+use hayride_host_traits::validate::ValidationError;
+
+/// A compiled JSON schema, draft 2020-12.
+///
+/// Remote references are not retrieved, so references must resolve within the schema.
+pub struct Validator {
+    inner: jsonschema::Validator,
+}
+
+impl Validator {
+    /// Compile a json encoded schema, checking its keywords and references.
+    pub fn compile(schema: &str) -> Result<Self, String> {
+        let schema: Value =
+            serde_json::from_str(schema).map_err(|e| format!("failed to parse schema: {}", e))?;
+        let inner = jsonschema::draft202012::options()
+            .build(&schema)
+            .map_err(|e| e.to_string())?;
+        Ok(Self { inner })
+    }
+
+    /// Validate a document, returning every violation found.
+    pub fn validate(&self, instance: &Value) -> Vec<ValidationError> {
+        self.inner
+            .iter_errors(instance)
+            .map(|e| ValidationError {
+                instance_location: e.instance_path().to_string(),
+                // The path through references, like the keyword location of the specification
+                keyword_location: e.evaluation_path().to_string(),
+                message: e.to_string(),
+            })
+            .collect()
+    }
+}
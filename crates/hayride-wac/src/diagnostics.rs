@@ -0,0 +1,94 @@
+use anyhow::anyhow;
+use hayride_host_traits::wac::{Diagnostic, Error, ErrorCode, SourceSpan};
+
+/// Build the error of a failed composition from its diagnostics, the data of the error
+/// holds their messages.
+pub fn error(code: ErrorCode, diagnostics: Vec<Diagnostic>) -> Error {
+    let message = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Error {
+        code,
+        data: anyhow!(message),
+        diagnostics,
+    }
+}
+
+/// Diagnostics of an error in a composition source, the first for the error itself and
+/// one more for each secondary label, e.g. where a duplicate name was first defined.
+pub fn source_diagnostics(
+    error: &dyn miette::Diagnostic,
+    source: &str,
+    package: Option<String>,
+) -> Vec<Diagnostic> {
+    let mut labels: Vec<miette::LabeledSpan> = error
+        .labels()
+        .map(|labels| labels.collect())
+        .unwrap_or_default();
+    // The primary label marks where the error is
+    labels.sort_by_key(|label| !label.primary());
+
+    let mut diagnostics = vec![Diagnostic {
+        message: message(error),
+        span: labels.first().map(|label| span(source, label.inner())),
+        package,
+    }];
+    diagnostics.extend(labels.iter().skip(1).filter_map(|label| {
+        Some(Diagnostic {
+            message: label.label()?.to_string(),
+            span: Some(span(source, label.inner())),
+            package: None,
+        })
+    }));
+    diagnostics
+}
+
+/// Diagnostic of an error that is not located in the composition source.
+pub fn diagnostic(error: &dyn std::error::Error, package: Option<String>) -> Diagnostic {
+    Diagnostic {
+        message: message(error),
+        span: None,
+        package,
+    }
+}
+
+/// Name of the package a resolver error is about.
+pub fn package(error: &wac_resolver::Error) -> Option<String> {
+    match error {
+        wac_resolver::Error::UnknownPackage { name, .. }
+        | wac_resolver::Error::InvalidPackageName { name, .. }
+        | wac_resolver::Error::PackageDoesNotExist { name, .. }
+        | wac_resolver::Error::PackageResolutionFailure { name, .. } => Some(name.clone()),
+        _ => None,
+    }
+}
+
+// The message of the error followed by those of its sources
+fn message(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(&format!(": {}", error));
+        source = error.source();
+    }
+    message
+}
+
+// Line and column of a byte offset span in the source
+fn span(source: &str, span: &miette::SourceSpan) -> SourceSpan {
+    let offset = span.offset().min(source.len());
+    let before = source.get(..offset).unwrap_or(source);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count())
+        + 1;
+    SourceSpan {
+        line: line as u32,
+        column: column as u32,
+        length: span.len() as u32,
+    }
+}
@@ -1,3 +1,5 @@
+mod diagnostics;
+
 use anyhow::{anyhow, Context, Result};
 use indexmap::IndexMap;
 use miette::SourceSpan;
@@ -10,7 +12,11 @@ use wac_parser::Document;
 use wac_resolver::{packages, Error};
 use wac_types::BorrowedPackageKey;
 
-use hayride_host_traits::wac::{errors::ErrorCode, WacTrait};
+use hayride_host_traits::wac::{errors::ErrorCode, ResolveOptions, WacTrait};
+use hayride_host_traits::wac::{
+    ComponentDescription, ComponentSource, Diagnostic, Error as WacError, WorldItem,
+};
+use hayride_utils::wit::parser::WitParser;
 
 #[derive(Clone)]
 pub struct WacBackend {
@@ -21,36 +27,78 @@ impl WacBackend {
     pub fn new(registry_path: String) -> Self {
         Self { registry_path }
     }
-}
 
-impl WacTrait for WacBackend {
-    fn compose(&mut self, contents: String) -> Result<Vec<u8>, ErrorCode> {
+    // Registries to resolve from in order, ending with the registry of the backend
+    fn registries(&self, options: &ResolveOptions) -> Result<Vec<PathBuf>, ErrorCode> {
         let mut registry_path = hayride_utils::paths::hayride::default_hayride_dir()
             .map_err(|_| ErrorCode::ComposeFailed)?;
         registry_path.push(self.registry_path.clone());
 
+        let mut registries = options.registries.clone();
+        registries.push(registry_path);
+        Ok(registries)
+    }
+
+    // Bytes of a component, with the registry package of a morph
+    fn component_bytes(
+        &self,
+        component: ComponentSource,
+    ) -> Result<(Vec<u8>, Option<String>), WacError> {
+        match component {
+            ComponentSource::Bytes(bytes) => Ok((bytes, None)),
+            ComponentSource::Morph(morph) => {
+                let registries = self.registries(&ResolveOptions::default())?;
+                let path = resolve_morph_path(&registries, &HashMap::new(), &morph)?;
+                let bytes = fs::read(&path).map_err(|e| {
+                    diagnostics::error(
+                        ErrorCode::FileNotFound,
+                        vec![diagnostics::diagnostic(&e, Some(morph.clone()))],
+                    )
+                })?;
+                Ok((bytes, package_name(&morph, &path)))
+            }
+        }
+    }
+}
+
+impl WacTrait for WacBackend {
+    fn compose(&mut self, contents: String, options: &ResolveOptions) -> Result<Vec<u8>, WacError> {
+        let registries = self.registries(options)?;
+
         let document = Document::parse(&contents).map_err(|e| {
             log::error!("Failed to parse wac compose contents: {}", e);
-            ErrorCode::ComposeFailed
+            diagnostics::error(
+                ErrorCode::ComposeFailed,
+                diagnostics::source_diagnostics(&e, &contents, None),
+            )
         })?;
 
         let mut resolver = PackageResolver::new(
-            registry_path,  // deps
-            HashMap::new(), // overrides
+            registries,                // deps
+            options.overrides.clone(), // overrides
         )
         .map_err(|e| {
             log::error!("Failed to create package resolver: {}", e);
-            ErrorCode::ComposeFailed
+            WacError::from(ErrorCode::ComposeFailed)
         })?;
 
-        let packages = resolver.resolve(&document).map_err(|e| {
-            log::error!("Failed to resolve packages: {}", e);
-            ErrorCode::ResolveFailed
+        // Report every package that is missing, not only the first
+        let packages = resolver.resolve_all(&document).map_err(|errors| {
+            let mut diagnostics = vec![];
+            for e in &errors {
+                log::error!("Failed to resolve packages: {}", e);
+                let package = diagnostics::package(e);
+                diagnostics.extend(diagnostics::source_diagnostics(e, &contents, package));
+            }
+            diagnostics::error(ErrorCode::ResolveFailed, diagnostics)
         })?;
 
         let resolution = document.resolve(packages).map_err(|e| {
             log::error!("Failed to resolve document: {}", e);
-            ErrorCode::ResolveFailed
+            diagnostics::error(
+                ErrorCode::ResolveFailed,
+                diagnostics::source_diagnostics(&e, &contents, None),
+            )
         })?;
 
         let bytes = resolution
@@ -61,85 +109,173 @@ impl WacTrait for WacBackend {
             })
             .map_err(|e| {
                 log::error!("Failed to encode component: {}", e);
-                ErrorCode::EncodeFailed
+                diagnostics::error(
+                    ErrorCode::EncodeFailed,
+                    vec![diagnostics::diagnostic(&e, None)],
+                )
             })?;
 
         return Ok(bytes);
     }
 
-    fn plug(&mut self, socket_path: String, plug_paths: Vec<String>) -> Result<Vec<u8>, ErrorCode> {
-        // Build registry path from home directory
-        let mut registry_path = hayride_utils::paths::hayride::default_hayride_dir()
-            .map_err(|_| ErrorCode::ComposeFailed)?;
-        registry_path.push(self.registry_path.clone());
-        let registry_path = registry_path
-            .to_str()
-            .ok_or_else(|| ErrorCode::ComposeFailed)?;
+    fn plug(
+        &mut self,
+        socket_path: String,
+        plug_paths: Vec<String>,
+        options: &ResolveOptions,
+    ) -> Result<Vec<u8>, WacError> {
+        // Registries from the options, then the registry in the home directory
+        let registries = self.registries(options)?;
 
         let mut graph = CompositionGraph::new();
 
         // Register the plug dependencies into the graph
         let mut plug_packages = Vec::new();
         for plug_path in plug_paths {
-            let plug_path = resolve_morph_path(registry_path, &plug_path)?;
+            let plug = plug_path;
+            let plug_path = resolve_morph_path(&registries, &options.overrides, &plug)?;
 
             let name = Path::new(&plug_path)
                 .file_name()
                 .and_then(|name| name.to_str())
-                .ok_or_else(|| ErrorCode::FileNotFound)?; // Convert OsStr to &str
+                .ok_or(ErrorCode::FileNotFound)?; // Convert OsStr to &str
 
             let package = Package::from_file(name, None, plug_path.clone(), graph.types_mut())
                 .map_err(|e| {
                     log::error!("Failed to find plug: {}", e);
-                    ErrorCode::FileNotFound
+                    diagnostics::error(
+                        ErrorCode::FileNotFound,
+                        vec![diagnostics::diagnostic(e.as_ref(), Some(plug))],
+                    )
                 })?;
             let plug = graph.register_package(package).unwrap();
             plug_packages.push(plug);
         }
 
         // Socket component
-        let socket_path = resolve_morph_path(registry_path, &socket_path)?;
+        let socket_name = socket_path;
+        let socket_path = resolve_morph_path(&registries, &options.overrides, &socket_name)?;
 
-        let package =
-            Package::from_file("socket", None, socket_path, graph.types_mut()).map_err(|e| {
+        let package = Package::from_file("socket", None, socket_path.clone(), graph.types_mut())
+            .map_err(|e| {
                 log::error!("Failed to find socket: {}", e);
-                ErrorCode::FileNotFound
+                diagnostics::error(
+                    ErrorCode::FileNotFound,
+                    vec![diagnostics::diagnostic(
+                        e.as_ref(),
+                        Some(socket_name.clone()),
+                    )],
+                )
             })?;
         let socket = graph.register_package(package).map_err(|e| {
             log::error!("Failed to register socket: {}", e);
-            ErrorCode::EncodeFailed
+            diagnostics::error(
+                ErrorCode::EncodeFailed,
+                vec![diagnostics::diagnostic(&e, Some(socket_name.clone()))],
+            )
         })?;
 
         wac_graph::plug(&mut graph, plug_packages, socket).map_err(|e| {
             log::error!("Failed to plug packages: {}", e);
-            ErrorCode::EncodeFailed
+            diagnostics::error(
+                ErrorCode::EncodeFailed,
+                vec![diagnostics::diagnostic(&e, Some(socket_name))],
+            )
         })?;
 
         // Encode the graph into a WASM bytes
         let encoding = graph.encode(EncodeOptions::default()).map_err(|e| {
             log::error!("Failed to encode to bytes: {}", e);
-            ErrorCode::EncodeFailed
+            diagnostics::error(
+                ErrorCode::EncodeFailed,
+                vec![diagnostics::diagnostic(&e, None)],
+            )
         })?;
         return Ok(encoding);
     }
+
+    fn validate(&mut self, component: ComponentSource) -> Result<(), WacError> {
+        let (bytes, package) = self.component_bytes(component)?;
+        if !wasmparser::Parser::is_component(&bytes) {
+            return Err(diagnostics::error(
+                ErrorCode::InvalidComponent,
+                vec![Diagnostic {
+                    message: "not a component".to_string(),
+                    span: None,
+                    package,
+                }],
+            ));
+        }
+
+        wasmparser::Validator::new()
+            .validate_all(&bytes)
+            .map_err(|e| {
+                log::warn!("Invalid component: {}", e);
+                diagnostics::error(
+                    ErrorCode::InvalidComponent,
+                    vec![diagnostics::diagnostic(&e, package)],
+                )
+            })?;
+        Ok(())
+    }
+
+    fn describe(&mut self, component: ComponentSource) -> Result<ComponentDescription, WacError> {
+        let (bytes, package) = self.component_bytes(component)?;
+        let wit = WitParser::new(bytes).map_err(|e| {
+            log::warn!("Failed to decode component: {}", e);
+            diagnostics::error(
+                ErrorCode::InvalidComponent,
+                vec![diagnostics::diagnostic(e.as_ref(), package.clone())],
+            )
+        })?;
+
+        let items = |items: &[hayride_utils::wit::parser::WorldItem]| {
+            items
+                .iter()
+                .map(|item| WorldItem {
+                    name: item.name.clone(),
+                    functions: item.functions.clone(),
+                })
+                .collect()
+        };
+        Ok(ComponentDescription {
+            package,
+            imports: items(wit.world_imports()),
+            exports: items(wit.world_exports()),
+        })
+    }
+}
+
+// Registry package of a morph given by its identifier, at the version it resolved to
+fn package_name(morph: &str, path: &Path) -> Option<String> {
+    let (package, name, version) = hayride_utils::paths::registry::parse_identifier(morph)?;
+    // File paths are no identifiers, even with a drive letter
+    if name.contains(['/', '\\']) {
+        return None;
+    }
+    let version = match version {
+        Some(version) => version.to_string(),
+        None => path.parent()?.file_name()?.to_str()?.to_string(),
+    };
+    Some(format!("{}:{}@{}", package, name, version))
 }
 
 /// Used to resolve packages from the Hayride file system.
 pub struct HayridePackageResolver {
-    root: PathBuf,
+    roots: Vec<PathBuf>,
     overrides: HashMap<String, PathBuf>,
     error_on_unknown: bool,
 }
 
 impl HayridePackageResolver {
-    /// Creates a new file system resolver with the given root directory.
+    /// Creates a new file system resolver searching the given root directories in order.
     pub fn new(
-        root: impl Into<PathBuf>,
+        roots: Vec<PathBuf>,
         overrides: HashMap<String, PathBuf>,
         error_on_unknown: bool,
     ) -> Self {
         Self {
-            root: root.into(),
+            roots,
             overrides,
             error_on_unknown,
         }
@@ -169,24 +305,16 @@ impl HayridePackageResolver {
                     path.clone()
                 }
                 _ => {
-                    let mut path = self.root.clone();
-                    for segment in key.name.split(':') {
-                        path.push(segment);
-                    }
-
-                    if let Some(version) = key.version {
-                        path = path
-                            .parent()
-                            .map(|p| p.join(version.to_string()).join(path.file_name().unwrap()))
-                            .unwrap();
-                    }
-
-                    // If the path is not a directory, use a `.wasm` or `.wat` extension
-                    if !path.is_dir() {
-                        append_extension(&mut path, "wasm");
+                    // The first root holding the package, reported missing from the last
+                    let paths: Vec<PathBuf> = self
+                        .roots
+                        .iter()
+                        .map(|root| package_path(root, key))
+                        .collect();
+                    match paths.iter().position(|path| path.is_file()) {
+                        Some(index) => paths[index].clone(),
+                        None => paths.last().cloned().unwrap_or_default(),
                     }
-
-                    path
                 }
             };
 
@@ -223,6 +351,28 @@ impl HayridePackageResolver {
     }
 }
 
+// Path of a package in a registry root
+fn package_path(root: &Path, key: &BorrowedPackageKey) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for segment in key.name.split(':') {
+        path.push(segment);
+    }
+
+    if let Some(version) = key.version {
+        path = path
+            .parent()
+            .map(|p| p.join(version.to_string()).join(path.file_name().unwrap()))
+            .unwrap();
+    }
+
+    // If the path is not a directory, use a `.wasm` or `.wat` extension
+    if !path.is_dir() {
+        append_extension(&mut path, "wasm");
+    }
+
+    path
+}
+
 /// Similar to Path::set_extension except it always appends.
 /// For example "0.0.1" -> "0.0.1.wasm" (instead of to "0.0.wasm").
 fn append_extension(path: &mut PathBuf, extension: &str) {
@@ -242,10 +392,10 @@ pub struct PackageResolver {
 }
 
 impl PackageResolver {
-    /// Creates a new package resolver.
-    pub fn new(dir: impl Into<PathBuf>, overrides: HashMap<String, PathBuf>) -> Result<Self> {
+    /// Creates a new package resolver searching the given directories in order.
+    pub fn new(dirs: Vec<PathBuf>, overrides: HashMap<String, PathBuf>) -> Result<Self> {
         Ok(Self {
-            fs: HayridePackageResolver::new(dir, overrides, false),
+            fs: HayridePackageResolver::new(dirs, overrides, false),
         })
     }
 
@@ -254,42 +404,96 @@ impl PackageResolver {
         &mut self,
         document: &'a Document<'a>,
     ) -> Result<IndexMap<BorrowedPackageKey<'a>, Vec<u8>>, Error> {
-        let mut keys = packages(document)?;
+        self.resolve_all(document)
+            .map_err(|mut errors| errors.remove(0))
+    }
+
+    /// Resolve all packages referenced in the given document, returning an unknown
+    /// package error for each package that could not be found.
+    pub fn resolve_all<'a>(
+        &mut self,
+        document: &'a Document<'a>,
+    ) -> Result<IndexMap<BorrowedPackageKey<'a>, Vec<u8>>, Vec<Error>> {
+        let mut keys = packages(document).map_err(|e| vec![e])?;
 
         // Next, we resolve as many of the packages from the file system as possible
         // and filter out the ones that were resolved.
         #[allow(unused_mut)]
-        let mut packages = self.fs.resolve(&keys)?;
+        let mut packages = self.fs.resolve(&keys).map_err(|e| vec![e])?;
         keys.retain(|key, _| !packages.contains_key(key));
 
-        // At this point keys should be empty, otherwise we have an unknown package
-        if let Some((key, span)) = keys.first() {
-            return Err(Error::UnknownPackage {
-                name: key.name.to_string(),
-                span: *span,
-            });
+        // At this point keys should be empty, otherwise we have unknown packages
+        if !keys.is_empty() {
+            return Err(keys
+                .iter()
+                .map(|(key, span)| Error::UnknownPackage {
+                    name: key.name.to_string(),
+                    span: *span,
+                })
+                .collect());
         }
 
         Ok(packages)
     }
 }
 
-fn resolve_morph_path(registry_path: &str, morph_path: &str) -> Result<PathBuf, ErrorCode> {
-    // First, check if the morph path is a valid morph path
-    let result = match hayride_utils::paths::registry::find_morph_path(
-        registry_path.to_string(),
-        &morph_path,
-    ) {
-        Ok(path) => path,
-        Err(_) => {
+fn resolve_morph_path(
+    registries: &[PathBuf],
+    overrides: &HashMap<String, PathBuf>,
+    morph_path: &str,
+) -> Result<PathBuf, WacError> {
+    let not_found = |message: String| {
+        diagnostics::error(
+            ErrorCode::FileNotFound,
+            vec![Diagnostic {
+                message,
+                span: None,
+                package: Some(morph_path.to_string()),
+            }],
+        )
+    };
+
+    // Overrides take the place of unversioned packages
+    if let Some(path) = overrides.get(morph_path) {
+        if !path.is_file() {
+            log::error!(
+                "local path `{}` for package `{}` does not exist",
+                path.display(),
+                morph_path
+            );
+            return Err(not_found(format!(
+                "local path `{}` for package `{}` does not exist",
+                path.display(),
+                morph_path
+            )));
+        }
+        return path
+            .canonicalize()
+            .map_err(|e| not_found(format!("failed to read `{}`: {}", path.display(), e)));
+    }
+
+    // Then check if the morph path is a valid morph path in one of the registries
+    let found = registries.iter().find_map(|registry| {
+        hayride_utils::paths::registry::find_morph_path(
+            registry.to_string_lossy().into_owned(),
+            morph_path,
+        )
+        .ok()
+    });
+    let result = match found {
+        Some(path) => path,
+        None => {
             // If not a valid morph path, processes it as a regular file path returning PathBuf
             let path = Path::new(&morph_path);
             if !path.is_file() {
-                return Err(ErrorCode::FileNotFound);
+                return Err(not_found(format!(
+                    "package `{}` not found in the registries or as a file",
+                    morph_path
+                )));
             }
             let path = path.canonicalize().map_err(|e| {
                 log::error!("Failed to canonicalize plug path: {}", e);
-                ErrorCode::FileNotFound
+                not_found(format!("failed to read `{}`: {}", morph_path, e))
             })?;
             path
         }
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use hayride_host_traits::ai::{
+    BackendError, BackendExecutionContext, BackendGraph, BackendInner, ExecutionContext, Graph,
+    Tensor, TensorStream, TensorType,
+};
+
+// Sample rate whisper.cpp expects for input audio
+const WHISPER_SAMPLE_RATE: usize = 16000;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct TranscribeOptions {
+    // Spoken language of the audio, auto detected when empty
+    #[serde(default)]
+    language: String,
+    // Translate the transcription to english
+    #[serde(default)]
+    translate: bool,
+    #[serde(default)]
+    num_threads: i32,
+}
+
+#[derive(Default)]
+pub struct WhisperBackend {
+    models: HashMap<String, Arc<WhisperContext>>,
+}
+
+impl WhisperBackend {
+    pub fn new() -> Self {
+        WhisperBackend {
+            models: HashMap::new(),
+        }
+    }
+}
+
+impl BackendInner for WhisperBackend {
+    fn load(&mut self, name: String) -> Result<Graph, BackendError> {
+        log::debug!("loading whisper model: {}", name);
+
+        if let Some(model) = self.models.get(&name) {
+            let graph: Box<dyn BackendGraph> = Box::new(WhisperGraph {
+                model: model.clone(),
+            });
+            return Ok(graph.into());
+        }
+
+        let model = WhisperContext::new_with_params(&name, WhisperContextParameters::default())
+            .map_err(|e| {
+                log::warn!("failed to load whisper model {}: {}", name, e);
+                BackendError::FailedToLoadModel
+            })?;
+        let model = Arc::new(model);
+
+        self.models.insert(name, model.clone());
+        let graph: Box<dyn BackendGraph> = Box::new(WhisperGraph { model });
+        Ok(graph.into())
+    }
+}
+
+struct WhisperGraph {
+    model: Arc<WhisperContext>,
+}
+
+impl BackendGraph for WhisperGraph {
+    fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+        let context: Box<dyn BackendExecutionContext> = Box::new(WhisperExecutionContext {
+            model: self.model.clone(),
+        });
+        return Ok(context.into());
+    }
+}
+
+struct WhisperExecutionContext {
+    model: Arc<WhisperContext>,
+}
+
+impl WhisperExecutionContext {
+    // Transcribe the audio tensor, an optional options tensor configures the transcription
+    fn transcribe(&self, tensors: Vec<(String, Tensor)>) -> Result<String, BackendError> {
+        let mut options_tensor = None;
+        let mut input_tensor = None;
+        for (id, tensor) in tensors {
+            if id == "options" {
+                options_tensor = Some(tensor);
+            } else {
+                input_tensor = Some(tensor);
+            }
+        }
+
+        let input_tensor = input_tensor.ok_or(BackendError::FailedTensorNotSet)?;
+        let audio = decode_audio(&input_tensor)?;
+        log::debug!(
+            "transcribing {:.2}s of audio",
+            audio.len() as f32 / WHISPER_SAMPLE_RATE as f32
+        );
+
+        let options = match options_tensor {
+            Some(tensor) => {
+                serde_json::from_slice(&tensor.data).map_err(|_| BackendError::FailedDecoding)?
+            }
+            None => TranscribeOptions::default(),
+        };
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        if !options.language.is_empty() {
+            params.set_language(Some(&options.language));
+        } else {
+            params.set_language(Some("auto"));
+        }
+        params.set_translate(options.translate);
+        if options.num_threads > 0 {
+            params.set_n_threads(options.num_threads);
+        }
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        let mut state = self
+            .model
+            .create_state()
+            .map_err(|_| BackendError::FailedToInitContext)?;
+        state.full(params, &audio).map_err(|e| {
+            log::warn!("failed to transcribe audio: {}", e);
+            BackendError::FailedDecoding
+        })?;
+
+        let segments = state
+            .full_n_segments()
+            .map_err(|_| BackendError::FailedResultNotSet)?;
+        let mut result = String::new();
+        for i in 0..segments {
+            let text = state
+                .full_get_segment_text(i)
+                .map_err(|_| BackendError::FailedResultNotSet)?;
+            result.push_str(&text);
+        }
+
+        Ok(result.trim().to_string())
+    }
+}
+
+impl BackendExecutionContext for WhisperExecutionContext {
+    fn compute(&mut self, tensors: Vec<(String, Tensor)>) -> Result<Tensor, BackendError> {
+        let result = self.transcribe(tensors)?;
+        log::debug!("setting result tensor with data: [{}]", result);
+
+        Ok(Tensor {
+            data: result.into_bytes(),
+            dimensions: vec![1],
+            ty: TensorType::U8,
+        })
+    }
+
+    fn compute_stream(
+        &mut self,
+        tensors: Vec<(String, Tensor)>,
+    ) -> Result<TensorStream, BackendError> {
+        // Transcription is done in a single pass, stream the full result
+        let result = self.transcribe(tensors)?;
+        let buffer = std::io::Cursor::new(result.into_bytes());
+
+        Ok(TensorStream::new(vec![1], TensorType::U8, buffer))
+    }
+}
+
+// Decode the audio tensor into 16kHz mono f32 samples
+fn decode_audio(tensor: &Tensor) -> Result<Vec<f32>, BackendError> {
+    match tensor.ty {
+        TensorType::FP32 => {
+            if tensor.data.len() % 4 != 0 {
+                return Err(BackendError::FailedDecoding);
+            }
+            Ok(tensor
+                .data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        _ => {
+            log::warn!(
+                "unsupported audio tensor type: {:?}, expected fp32 pcm",
+                tensor.ty
+            );
+            Err(BackendError::FailedDecoding)
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Run, serve and compose morphs, and manage the models and threads of the runtime.
+///
+/// Arguments that do not start with a subcommand are passed to the configured bin morph.
+#[derive(Parser, Debug)]
+#[command(name = "hayride", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a morph, passing it the arguments
+    Run {
+        /// Morph identifier or path to a wasm file
+        #[arg(required_unless_present = "replay")]
+        morph: Option<String>,
+        /// Function of a reactor morph to call, the configured entrypoint otherwise
+        #[arg(short, long)]
+        function: Option<String>,
+        /// Run a session again with the morph, args and seed in its manifest, and the envs configured now
+        #[arg(long, value_name = "SESSION_ID", conflicts_with_all = ["morph", "function", "args"])]
+        replay: Option<String>,
+        /// Write the output of the morph to its session instead of the terminal
+        #[arg(long, hide = true)]
+        detached: bool,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Serve a server morph until it stops
+    Serve {
+        /// Morph identifier or path to a wasm file
+        morph: String,
+        /// Address to listen on, overriding the one the morph configures
+        #[arg(short, long)]
+        address: Option<String>,
+    },
+    /// Run a morph in the background, printing the process id
+    Spawn {
+        /// Morph identifier or path to a wasm file
+        morph: String,
+        /// Function of a reactor morph to call, the configured entrypoint otherwise
+        #[arg(short, long)]
+        function: Option<String>,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Compose a component from a wac file with the morphs of the registry
+    Compose {
+        /// Path to the wac file
+        file: PathBuf,
+        /// Path to write the component to, the wac file with a `.wasm` extension otherwise
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Score a model against a dataset of prompts and expected properties
+    Eval {
+        /// Path to the json dataset
+        dataset: PathBuf,
+        /// Model to evaluate, the model of the dataset otherwise
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Cases computed at once
+        #[arg(short, long, default_value_t = hayride_runtime::ai::eval::DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+        /// Path to write the json report to
+        #[arg(short, long)]
+        report: Option<PathBuf>,
+    },
+    /// Run as a daemon managed through a local admin api
+    Daemon {
+        /// Address the admin api listens on, the configured daemon address otherwise
+        #[arg(short, long)]
+        address: Option<String>,
+    },
+    /// Install the latest release, verified against the trusted release keys
+    Update {
+        /// Only print whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Manage the models of the configured model repository
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Manage the threads of a running server, with the admin token of the config
+    Threads {
+        /// Address of the server, the configured server address otherwise
+        #[arg(short, long)]
+        server: Option<String>,
+        #[command(subcommand)]
+        command: ThreadsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ModelsCommand {
+    /// List the downloaded models
+    List {
+        /// File extension without the leading dot, e.g. `gguf`
+        #[arg(short, long)]
+        extension: Option<String>,
+        /// Repository id, e.g. `unsloth/Qwen3-0.6B-GGUF`
+        #[arg(short, long)]
+        repo: Option<String>,
+    },
+    /// Download a model as `<owner>/<repo>/<file>`, printing its path
+    Download { name: String },
+    /// Print the metadata of a downloaded model as json
+    Info { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ThreadsCommand {
+    /// List the threads of the server
+    List,
+    /// Kill the thread with the id
+    Kill { id: String },
+}
+
+impl Cli {
+    /// Parse the args, none when they do not start with a subcommand.
+    pub fn parse_args(args: &[String]) -> Option<Self> {
+        let name = args.get(1)?;
+        let command = Self::command();
+        let known = name == "help"
+            || command
+                .get_subcommands()
+                .any(|subcommand| subcommand.get_name() == name);
+        if !known {
+            return None;
+        }
+        Some(Self::parse_from(args))
+    }
+}
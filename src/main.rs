@@ -1,38 +1,345 @@
-use hayride_runtime::engine::EngineBuilder;
+mod cli;
+
+use cli::{Cli, Command, ModelsCommand, ThreadsCommand};
+use hayride_core::signature::MorphVerifier;
+use hayride_host_traits::ai::model::ModelFilter;
+use hayride_host_traits::core::version::VersionInner;
+use hayride_host_traits::wac::{ResolveOptions, WacTrait};
+use hayride_runtime::ai::eval::{evaluate, EvalOptions};
+use hayride_runtime::daemon::{Daemon, EngineFactory};
+use hayride_runtime::engine::{EngineBuilder, RunOutcome, WasmtimeEngine};
+use hayride_runtime::silo::silo::THREADS_ADMIN_PATH;
+use hayride_utils::config::Config;
 use std::env;
+use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let hayride_dir = hayride_utils::paths::hayride::default_hayride_dir()?;
-    let morphs_dir: String = "registry/morphs".to_string();
-    let model_dir: String = "ai/models".to_string();
+
+    // Load $HOME/.hayride/config.toml, or the file set by "HAYRIDE_CONFIG"
+    let config = Config::load()?;
 
     // Setup logging
     // The ENV "HAYRIDE_LOG" can be used to set the log file path
     // otherwise fallback to $HOME/.hayride/logs/hayride.log
-    let log_file: String = env::var("HAYRIDE_LOG").unwrap_or("hayride.log".to_string());
-    // Put log in the hayride logs directory
     let mut log_dir = hayride_dir.clone();
     log_dir.push("logs");
-    log_dir.push(log_file);
+    log_dir.push(&config.log.file);
     let log_path = log_dir
         .to_str()
         .ok_or(anyhow::anyhow!("Failed to convert path to string"))?
         .to_string();
 
     hayride_utils::log::logger::set_log_path(log_path)?;
+    hayride_utils::log::logger::set_log_rotation(hayride_utils::log::Rotation::from_config(
+        &config.log,
+    ))?;
+
+    // Parse args to pass to the component
+    let args: Vec<String> = env::args().collect();
 
-    let bin_path = env::var("HAYRIDE_BIN").unwrap_or("hayride-core:cli".to_string());
-    let entrypoint = env::var("HAYRIDE_ENTRYPOINT").unwrap_or("run".to_string());
-    let log_level = env::var("HAYRIDE_LOG_LEVEL").unwrap_or("info".to_string());
+    let cli = Cli::parse_args(&args);
+    let subcommand = cli.is_some();
+    let result = match cli {
+        Some(cli) => run_command(cli.command, &config, &hayride_dir).await,
+        // Without a subcommand, pass the args to the configured bin morph
+        None => {
+            let bin_path = config.bin.clone();
+            // Only inherit stdio for cli
+            let inherit_stdio = bin_path == "hayride-core:cli";
+            let engine = build_engine(&config, &hayride_dir, inherit_stdio, None)?;
+            run_morph(engine, &config, &hayride_dir, &bin_path, None, &args)
+                .await
+                .map(|_| ())
+        }
+    };
+    if let Err(e) = &result {
+        log::error!("Error running component: {:?}", e);
+    }
+
+    // Send the spans of the run before exiting
+    hayride_runtime::telemetry::flush().await;
+
+    // Errors of the bin morph are only logged
+    if subcommand {
+        result
+    } else {
+        Ok(())
+    }
+}
 
-    // Only inherit stdio for cli
-    let inherit_stdio = bin_path == "hayride-core:cli";
+async fn run_command(command: Command, config: &Config, hayride_dir: &Path) -> Result<()> {
+    match command {
+        Command::Run {
+            morph,
+            function,
+            replay,
+            detached,
+            args,
+        } => {
+            let engine = build_engine(config, hayride_dir, !detached, None)?;
+            let output = match (replay, morph) {
+                (Some(session_id), _) => output(engine.replay(&session_id).await?).await?,
+                (None, Some(morph)) => {
+                    let args: Vec<String> = std::iter::once(morph.clone()).chain(args).collect();
+                    run_morph(engine, config, hayride_dir, &morph, function, &args).await?
+                }
+                (None, None) => unreachable!("clap requires a morph without --replay"),
+            };
+            match output {
+                Output::Exit(0) => Ok(()),
+                Output::Exit(code) => {
+                    hayride_runtime::telemetry::flush().await;
+                    std::process::exit(code)
+                }
+                Output::Bytes(bytes) => {
+                    if !bytes.is_empty() {
+                        println!("{}", String::from_utf8_lossy(&bytes));
+                    }
+                    Ok(())
+                }
+            }
+        }
+        Command::Serve { morph, address } => {
+            let engine = build_engine(config, hayride_dir, true, address)?;
+            run_morph(
+                engine,
+                config,
+                hayride_dir,
+                &morph,
+                None,
+                std::slice::from_ref(&morph),
+            )
+            .await?;
+            Ok(())
+        }
+        Command::Spawn {
+            morph,
+            function,
+            args,
+        } => {
+            // Run the morph in a process of its own, writing its output to its session
+            let mut command = std::process::Command::new(env::current_exe()?);
+            command.arg("run").arg("--detached");
+            if let Some(function) = function {
+                command.arg("--function").arg(function);
+            }
+            let child = command
+                .arg(morph)
+                .args(args)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()?;
+            println!("{}", child.id());
+            Ok(())
+        }
+        Command::Compose { file, output } => {
+            let contents = std::fs::read_to_string(&file)?;
+            let mut backend = hayride_wac::WacBackend::new(config.registry_path.clone());
+            let bytes = backend
+                .compose(contents, &ResolveOptions::default())
+                .map_err(|e| anyhow::anyhow!("failed to compose {}: {}", file.display(), e.data))?;
+            let output = output.unwrap_or_else(|| file.with_extension("wasm"));
+            std::fs::write(&output, bytes)?;
+            println!("{}", output.display());
+            Ok(())
+        }
+        Command::Eval {
+            dataset,
+            model,
+            concurrency,
+            report,
+        } => {
+            let contents = std::fs::read_to_string(&dataset)?;
+            let engine = build_engine(config, hayride_dir, true, None)?;
+            let mut ai = engine.ai_ctx()?;
+            let options = EvalOptions { model, concurrency };
+            let result = evaluate(&mut ai, &contents, &options).await.map_err(|e| {
+                anyhow::anyhow!("failed to evaluate {}: {}", dataset.display(), e.data)
+            })?;
+
+            for case in &result.cases {
+                let status = if case.passed { "PASS" } else { "FAIL" };
+                println!(
+                    "{}\t{}\t{:.3}\t{}ms",
+                    status, case.id, case.score, case.duration_ms
+                );
+                if let Some(error) = &case.error {
+                    println!("\terror: {}", error);
+                }
+                for check in case.checks.iter().filter(|check| !check.passed) {
+                    println!("\t{}: {}", check.kind, check.detail);
+                }
+            }
+            println!(
+                "{}: {} passed, {} failed, score {:.3} in {}ms",
+                result.model, result.passed, result.failed, result.score, result.duration_ms
+            );
+            if let Some(report) = report {
+                std::fs::write(&report, serde_json::to_string_pretty(&result)?)?;
+            }
+            if result.failed > 0 {
+                return Err(anyhow::anyhow!(
+                    "{} of {} cases failed",
+                    result.failed,
+                    result.cases.len()
+                ));
+            }
+            Ok(())
+        }
+        Command::Daemon { address } => {
+            // Threads spawned by the daemon write their output to their sessions
+            let hayride_dir = hayride_dir.to_path_buf();
+            let factory: EngineFactory =
+                Box::new(move |config| build_engine(config, &hayride_dir, false, None));
+            let daemon = Arc::new(Daemon::new(config, factory)?);
+            let address = address.unwrap_or_else(|| config.admin.daemon_address.clone());
+            daemon.serve(&address).await
+        }
+        Command::Update { check } => update(check, config, hayride_dir).await,
+        Command::Models { command } => {
+            // Building the engine configures the model repository
+            build_engine(config, hayride_dir, true, None)?;
+            // Repositories block on their downloads
+            tokio::task::spawn_blocking(move || models(command)).await?
+        }
+        Command::Threads { server, command } => {
+            let address =
+                server
+                    .or_else(|| config.server.address.clone())
+                    .ok_or(anyhow::anyhow!(
+                        "no server address configured, pass one with --server"
+                    ))?;
+            let token = config.admin.token.clone().ok_or(anyhow::anyhow!(
+                "the thread admin api needs an admin token, set HAYRIDE_ADMIN_TOKEN"
+            ))?;
+            let base = if address.contains("://") {
+                address
+            } else {
+                format!("http://{}", address)
+            };
+            let url = format!("{}{}", base.trim_end_matches('/'), THREADS_ADMIN_PATH);
+            tokio::task::spawn_blocking(move || threads(command, &url, &token)).await?
+        }
+    }
+}
+
+async fn update(check: bool, config: &Config, hayride_dir: &Path) -> Result<()> {
+    let current = hayride_runtime::core::build::VERSION;
+    let release = hayride_core::VersionBackend::default()
+        .release()
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to check the latest release: {}", e))?;
+    if !hayride_core::update::is_newer(&release.version, current) {
+        println!("hayride {} is the latest release", current);
+        return Ok(());
+    }
+    if check {
+        println!(
+            "hayride {} is available, running {}",
+            release.version, current
+        );
+        return Ok(());
+    }
+
+    let verifier = MorphVerifier::new()
+        .with_trusted_keys(&config.update.trusted_keys)?
+        .with_key_dir(&hayride_dir.join(&config.update.key_dir))?;
+    let updates_dir = hayride_dir.join("updates");
+    let version = release.version.clone();
+    // Downloads block on the request
+    tokio::task::spawn_blocking(move || {
+        let path = hayride_core::update::download(&release, &updates_dir, &verifier)?;
+        hayride_core::update::apply(&path)
+    })
+    .await??;
+    println!("installed hayride {}, restart to run it", version);
+    Ok(())
+}
+
+fn models(command: ModelsCommand) -> Result<()> {
+    let mut repository = hayride_runtime::ai::model_repository()?;
+    match command {
+        ModelsCommand::List { extension, repo } => {
+            let models = repository
+                .list(&ModelFilter { extension, repo })
+                .map_err(|e| anyhow::anyhow!("failed to list models: {}", e))?;
+            for model in models {
+                println!("{}", model);
+            }
+        }
+        ModelsCommand::Download { name } => {
+            let path = repository
+                .download(name.clone())
+                .map_err(|e| anyhow::anyhow!("failed to download {}: {}", name, e))?;
+            println!("{}", path);
+        }
+        ModelsCommand::Info { name } => {
+            let info = repository
+                .info(name.clone())
+                .map_err(|e| anyhow::anyhow!("failed to read {}: {}", name, e))?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        }
+    }
+    Ok(())
+}
+
+fn threads(command: ThreadsCommand, url: &str, token: &str) -> Result<()> {
+    let client = reqwest::blocking::Client::new();
+    match command {
+        ThreadsCommand::List => {
+            let threads: serde_json::Value = client
+                .get(url)
+                .bearer_auth(token)
+                .send()?
+                .error_for_status()?
+                .json()?;
+            for thread in threads.as_array().into_iter().flatten() {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    thread["id"].as_str().unwrap_or_default(),
+                    thread["morph"].as_str().unwrap_or_default(),
+                    thread["function"].as_str().unwrap_or_default(),
+                    thread["status"].as_str().unwrap_or_default(),
+                );
+            }
+        }
+        ThreadsCommand::Kill { id } => {
+            client
+                .delete(url)
+                .query(&[("id", &id)])
+                .bearer_auth(token)
+                .send()?
+                .error_for_status()?;
+            println!("{}", id);
+        }
+    }
+    Ok(())
+}
+
+/// What a morph run from the command line produced.
+enum Output {
+    Exit(i32),
+    Bytes(Vec<u8>),
+}
+
+fn build_engine(
+    config: &Config,
+    hayride_dir: &Path,
+    inherit_stdio: bool,
+    server_address: Option<String>,
+) -> Result<WasmtimeEngine> {
+    let bin_path = config.bin.clone();
+    let entrypoint = config.entrypoint.clone();
+    let log_level = config.log.level.clone();
 
     // Output directory
-    let mut out_dir = hayride_dir.clone();
+    let mut out_dir = hayride_dir.to_path_buf();
     out_dir.push("sessions");
     let out_dir = out_dir
         .to_str()
@@ -44,40 +351,47 @@ async fn main() -> Result<()> {
             .wasm_component_model(true)
             .async_support(true),
     )?;
-    let engine = EngineBuilder::new(wasmtime_engine, morphs_dir.clone())
-        .log_level(log_level.clone())
+    let mut builder = EngineBuilder::new(wasmtime_engine, config.registry_path.clone())
+        .config(config)?
         .out_dir(Some(out_dir)) // outdir set in context for spawned components
         .inherit_stdio(inherit_stdio)
-        .model_path(Some(model_dir))
-        .silo_enabled(true)
-        .wac_enabled(true)
-        .wasi_enabled(true)
-        .ai_enabled(true)
-        .mcp_enabled(true)
         .envs(vec![
-            ("HAYRIDE_LOG_LEVEL".to_string(), log_level.clone()),
-            ("HAYRIDE_BIN".to_string(), bin_path.clone()),
-            ("HAYRIDE_ENTRYPOINT".to_string(), entrypoint.clone()),
-        ])
-        .build()?;
-
-    // Parse args to pass to the component
-    let args: Vec<String> = env::args().collect();
+            ("HAYRIDE_LOG_LEVEL".to_string(), log_level),
+            ("HAYRIDE_BIN".to_string(), bin_path),
+            ("HAYRIDE_ENTRYPOINT".to_string(), entrypoint),
+        ]);
+    if server_address.is_some() {
+        builder = builder.server_address(server_address);
+    }
+    builder.build()
+}
 
-    let mut morph_path = hayride_dir.clone();
-    morph_path.push("registry");
-    morph_path.push("morphs");
+// Find the morph in the registry and run it, waiting for servers to stop serving
+async fn run_morph(
+    engine: WasmtimeEngine,
+    config: &Config,
+    hayride_dir: &Path,
+    morph: &str,
+    function: Option<String>,
+    args: &[String],
+) -> Result<Output> {
+    let mut morph_path = hayride_dir.to_path_buf();
+    morph_path.push(&config.registry_path);
     let path_str = morph_path
         .to_str()
         .ok_or(anyhow::anyhow!("Failed to convert path to string"))?
         .to_string();
 
-    // TODO: ENV for the cli morph name
-    let wasm_file = hayride_utils::paths::registry::find_morph_path(path_str, &bin_path)?;
+    let wasm_file =
+        hayride_runtime::core::registry::find_or_pull(engine.registry(), path_str, morph).await?;
 
-    if let Err(e) = engine.run(wasm_file, entrypoint.to_string(), &args).await {
-        log::error!("Error running component: {:?}", e);
-    }
+    let function = function.unwrap_or_else(|| config.entrypoint.clone());
+    output(engine.run(wasm_file, function, args).await?).await
+}
 
-    Ok(())
+async fn output(outcome: RunOutcome) -> Result<Output> {
+    match outcome {
+        RunOutcome::Cli { exit_code } => Ok(Output::Exit(exit_code)),
+        outcome => Ok(Output::Bytes(outcome.wait().await?)),
+    }
 }